@@ -0,0 +1,328 @@
+//! Derive macros for `lanai-infrastructure`
+//!
+//! Use them through the main crate (`lanai_infrastructure::messaging::events::LanaiEvent`),
+//! which re-exports them next to the traits they implement.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitInt, LitStr};
+
+/// Implements `LanaiEvent` and `DescribeEvent` from `#[event(domain = "...", entity = "...",
+/// action = "...", version = N)]`, which publishes on `lanai.{domain}.{entity}.{action}`,
+/// followed by `.{org_id}` when the struct has an `org_id` field:
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, LanaiEvent)]
+/// #[event(domain = "inventory", entity = "product", action = "created", version = 2)]
+/// pub struct ProductCreatedEvent {
+///     pub product_id: ProductId,
+///     pub org_id: OrgId,
+/// }
+/// ```
+///
+/// Other subjects are spelled out with `subject`, `{field}` segments being filled from the
+/// struct's fields: `#[event(subject = "lanai.inventory.stock.adjusted.{org_id}.{warehouse_id}")]`.
+///
+/// A field named `org_id` (a `Uuid`, an ID newtype, a string, or an `Option` of those)
+/// becomes the envelope's organization.
+#[proc_macro_derive(LanaiEvent, attributes(event))]
+pub fn derive_lanai_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_lanai_event(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+struct EventAttr {
+    subject: LitStr,
+    version: u32,
+}
+
+/// `has_org_id`: whether the struct has an `org_id` field, which ends built subjects.
+fn parse_event_attr(input: &DeriveInput, has_org_id: bool) -> syn::Result<EventAttr> {
+    let mut subject = None;
+    let (mut domain, mut entity, mut action) = (None, None, None);
+    let mut version = 1;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("event")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("subject") {
+                subject = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("domain") {
+                domain = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("entity") {
+                entity = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("action") {
+                action = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("version") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                version = lit.base10_parse()?;
+                if version == 0 {
+                    return Err(syn::Error::new(lit.span(), "event versions start at 1"));
+                }
+            } else {
+                return Err(meta.error("expected `domain`, `entity`, `action`, `subject` or `version`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let subject = match (subject, domain, entity, action) {
+        (subject, None, None, None) => subject,
+        (None, Some(domain), Some(entity), Some(action)) => {
+            let mut template = format!("lanai.{}.{}.{}", domain.value(), entity.value(), action.value());
+            if has_org_id {
+                template.push_str(".{org_id}");
+            }
+            Some(LitStr::new(&template, domain.span()))
+        }
+        (Some(subject), ..) => {
+            return Err(syn::Error::new(subject.span(), "use either `subject` or `domain`, `entity` and `action`"))
+        }
+        (None, ..) => {
+            return Err(syn::Error::new(Span::call_site(), "`domain`, `entity` and `action` go together"))
+        }
+    };
+
+    let subject = subject.ok_or_else(|| {
+        syn::Error::new(Span::call_site(), "missing `#[event(domain = \"...\", entity = \"...\", action = \"...\")]`")
+    })?;
+    Ok(EventAttr { subject, version })
+}
+
+/// One segment of a subject template.
+enum Segment {
+    Literal(String),
+    Field(String),
+}
+
+fn parse_subject(subject: &LitStr) -> syn::Result<Vec<Segment>> {
+    let template = subject.value();
+    let error = |message: String| syn::Error::new(subject.span(), message);
+
+    let mut segments = Vec::new();
+    for part in template.split('.') {
+        if let Some(field) = part.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+            if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(error(format!("invalid placeholder `{}` in subject", part)));
+            }
+            segments.push(Segment::Field(field.to_string()));
+        } else if !part.is_empty()
+            && part.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        {
+            if matches!(segments.last(), Some(Segment::Field(_))) {
+                return Err(error("placeholders must come after the fixed part of the subject".to_string()));
+            }
+            segments.push(Segment::Literal(part.to_string()));
+        } else {
+            return Err(error(format!(
+                "invalid subject segment `{}`: use lowercase letters, digits, `_` and `-`, or a `{{field}}`",
+                part
+            )));
+        }
+    }
+
+    let literals = segments.iter().filter(|segment| matches!(segment, Segment::Literal(_))).count();
+    if !matches!(segments.first(), Some(Segment::Literal(first)) if first == "lanai") || literals < 4 {
+        return Err(error(format!("subject `{}` must start with `lanai.{{domain}}.{{entity}}.{{action}}`", template)));
+    }
+    Ok(segments)
+}
+
+fn expand_lanai_event(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "LanaiEvent requires a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "LanaiEvent can only be derived for structs")),
+    };
+    let field_names: Vec<String> =
+        fields.iter().filter_map(|field| field.ident.as_ref().map(|ident| ident.to_string())).collect();
+
+    let attr = parse_event_attr(input, field_names.iter().any(|name| name == "org_id"))?;
+    let segments = parse_subject(&attr.subject)?;
+
+    let mut format = Vec::new();
+    let mut pattern = Vec::new();
+    let mut args = Vec::new();
+    for segment in &segments {
+        match segment {
+            Segment::Literal(literal) => {
+                format.push(literal.clone());
+                pattern.push(literal.clone());
+            }
+            Segment::Field(field) => {
+                if !field_names.iter().any(|name| name == field) {
+                    return Err(syn::Error::new(
+                        attr.subject.span(),
+                        format!("subject placeholder `{{{}}}` is not a field of `{}`", field, input.ident),
+                    ));
+                }
+                let ident = Ident::new(field, Span::call_site());
+                format.push("{}".to_string());
+                pattern.push("*".to_string());
+                args.push(quote!(self.#ident));
+            }
+        }
+    }
+    let format = format.join(".");
+    let pattern = pattern.join(".");
+    let template = attr.subject.value();
+    let version = attr.version;
+
+    let name = &input.ident;
+    let event_type = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let private = quote!(::lanai_infrastructure::messaging::events::__private);
+
+    let org_id = if field_names.iter().any(|name| name == "org_id") {
+        quote! {
+            fn org_id(&self) -> ::core::option::Option<#private::Uuid> {
+                #private::OrgIdField::org_uuid(&self.org_id)
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let schema_fields = fields.iter().map(|field| {
+        let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+        let ty = &field.ty;
+        let ty = quote!(#ty).to_string().replace(' ', "");
+        quote!(::lanai_infrastructure::messaging::events::EventField { name: #name, ty: #ty })
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::lanai_infrastructure::messaging::events::LanaiEvent for #name #ty_generics #where_clause {
+            fn subject(&self) -> ::std::string::String {
+                ::std::format!(#format, #(#args),*)
+            }
+
+            fn version(&self) -> u32 {
+                #version
+            }
+
+            fn event_type(&self) -> &'static str {
+                #event_type
+            }
+
+            #org_id
+        }
+
+        impl #impl_generics ::lanai_infrastructure::messaging::events::DescribeEvent for #name #ty_generics #where_clause {
+            fn event_schema() -> ::lanai_infrastructure::messaging::events::EventSchema {
+                ::lanai_infrastructure::messaging::events::EventSchema {
+                    event_type: #event_type,
+                    subject: #template,
+                    subject_pattern: #pattern,
+                    version: #version,
+                    fields: ::std::vec![#(#schema_fields),*],
+                }
+            }
+        }
+    })
+}
+
+/// Implements `DescribeDto` from `#[dto(name = "...", version = N)]` and per-field
+/// `#[dto(since = N)]` / `#[dto(deprecated = "...")]`:
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, ApiDto)]
+/// #[dto(name = "Product", version = 2)]
+/// pub struct ProductV2 {
+///     pub id: ProductId,
+///     #[dto(deprecated = "use `price.amount`")]
+///     pub price_cents: i64,
+///     #[dto(since = 2)]
+///     pub price: Money,
+/// }
+/// ```
+///
+/// `name` defaults to the struct name and `version` to 1.
+#[proc_macro_derive(ApiDto, attributes(dto))]
+pub fn derive_api_dto(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_api_dto(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn parse_version(lit: &LitInt) -> syn::Result<u32> {
+    let version = lit.base10_parse()?;
+    if version == 0 {
+        return Err(syn::Error::new(lit.span(), "API versions start at 1"));
+    }
+    Ok(version)
+}
+
+fn expand_api_dto(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "ApiDto requires a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "ApiDto can only be derived for structs")),
+    };
+
+    let mut model = input.ident.to_string();
+    let mut version = 1;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("dto")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                model = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("version") {
+                version = parse_version(&meta.value()?.parse::<LitInt>()?)?;
+            } else {
+                return Err(meta.error("expected `name` or `version`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut schema_fields = Vec::new();
+    for field in fields {
+        let mut since = quote!(::core::option::Option::None);
+        let mut deprecated = quote!(::core::option::Option::None);
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("dto")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("since") {
+                    let lit = meta.value()?.parse::<LitInt>()?;
+                    let field_since = parse_version(&lit)?;
+                    if field_since > version {
+                        return Err(syn::Error::new(lit.span(), "field is newer than the DTO version"));
+                    }
+                    since = quote!(::core::option::Option::Some(#field_since));
+                } else if meta.path.is_ident("deprecated") {
+                    let note = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<LitStr>()?.value()
+                    } else {
+                        String::new()
+                    };
+                    deprecated = quote!(::core::option::Option::Some(#note));
+                } else {
+                    return Err(meta.error("expected `since` or `deprecated`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+        let ty = &field.ty;
+        let ty = quote!(#ty).to_string().replace(' ', "");
+        schema_fields.push(quote! {
+            ::lanai_infrastructure::dto::DtoField { name: #name, ty: #ty, since: #since, deprecated: #deprecated }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lanai_infrastructure::dto::DescribeDto for #name #ty_generics #where_clause {
+            fn dto_schema() -> ::lanai_infrastructure::dto::DtoSchema {
+                ::lanai_infrastructure::dto::DtoSchema {
+                    model: #model,
+                    version: #version,
+                    fields: ::std::vec![#(#schema_fields),*],
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,462 @@
+//! Runtime infrastructure controls for operators
+//!
+//! `AdminApi` mounts a service-token protected scope at `/internal/admin`:
+//!
+//! | Route | |
+//! |---|---|
+//! | `GET /breakers` | state and call counts of every registered circuit breaker |
+//! | `POST /breakers/{name}/reset` | close a breaker, ending a forced state |
+//! | `POST /breakers/{name}/open` | `{"seconds": 300}`: reject all calls for that long |
+//! | `POST /breakers/{name}/close` | let all calls through until reset |
+//! | `GET /rate-limits` | rate limit overrides |
+//! | `PUT /rate-limits/{prefix}` | override the limit for client keys starting with `prefix` |
+//! | `DELETE /rate-limits/{prefix}` | remove an override |
+//! | `GET /flags` | feature flags |
+//! | `PUT /flags/{name}` | `{"enabled": true, "org_id": null}` |
+//! | `DELETE /flags/{name}` | remove a flag, or one org's override with `?org_id=` |
+//! | `POST /cache/purge` | `{"pattern": "products:*", "org_id": null}` |
+//! | `GET /jobs` | lane, scheduled and dead-letter counts of every registered job queue |
+//! | `GET /jobs/{queue}/scheduled` | next scheduled jobs (`?limit=`, default 50) |
+//! | `GET /jobs/{queue}/dead` | most recent dead letters (`?limit=`, default 50) |
+//! | `POST /jobs/{queue}/dead/{entry_id}/retry` | re-enqueue a dead letter |
+//! | `DELETE /jobs/{queue}/{job_id}` | cancel a scheduled or waiting job |
+//! | `GET /maintenance` | maintenance mode state |
+//! | `PUT /maintenance` | `{"message": "..."}` |
+//! | `DELETE /maintenance` | leave maintenance mode |
+//! | `GET /env` | effective value of every declared environment variable, secrets redacted |
+//!
+//! ```ignore
+//! let admin = AdminApi::new()
+//!     .breaker("payments", payments_breaker.clone())
+//!     .cache(Cache::from_env("orders").await?)
+//!     .jobs(queue.clone());
+//!
+//! ServerBuilder::new().run(move |cfg| {
+//!     admin.configure(cfg);
+//!     configure_routes(cfg);
+//! }).await
+//! ```
+
+use actix_web::{web, HttpResponse};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::cache::Cache;
+use crate::env::EnvRegistry;
+use crate::error::LanaiError;
+use crate::flags::FeatureFlags;
+use crate::jobs::JobQueue;
+use crate::middleware::maintenance::MaintenanceMode;
+use crate::middleware::service_token::ServiceTokenGuard;
+use crate::middleware::tenant_context::TenantContext;
+use crate::rate_limit::{RateLimitOverrides, RateLimitPolicy};
+use crate::resilience::{CircuitBreaker, CircuitBreakerStats};
+
+pub const ADMIN_SCOPE: &str = "/internal/admin";
+
+struct Inner {
+    breakers: BTreeMap<String, Arc<CircuitBreaker>>,
+    rate_limits: RateLimitOverrides,
+    flags: FeatureFlags,
+    maintenance: MaintenanceMode,
+    cache: Option<Cache>,
+    jobs: BTreeMap<String, JobQueue>,
+    env: Option<EnvRegistry>,
+}
+
+/// Admin routes over the process-wide rate limit overrides, feature flags and maintenance
+/// mode, plus the breakers and cache registered on the builder.
+#[derive(Clone)]
+pub struct AdminApi {
+    inner: Arc<Inner>,
+    guard: ServiceTokenGuard,
+}
+
+impl Default for AdminApi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdminApi {
+    /// Guarded by `ServiceTokenGuard::from_env()`.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                breakers: BTreeMap::new(),
+                rate_limits: RateLimitOverrides::global().clone(),
+                flags: FeatureFlags::global().clone(),
+                maintenance: MaintenanceMode::global().clone(),
+                cache: None,
+                jobs: BTreeMap::new(),
+                env: None,
+            }),
+            guard: ServiceTokenGuard::from_env(),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("AdminApi is configured before it is shared"));
+        self
+    }
+
+    pub fn breaker(self, name: &str, breaker: Arc<CircuitBreaker>) -> Self {
+        self.update(|inner| {
+            inner.breakers.insert(name.to_string(), breaker);
+        })
+    }
+
+    pub fn cache(self, cache: Cache) -> Self {
+        self.update(|inner| inner.cache = Some(cache))
+    }
+
+    /// Expose `queue` under `/jobs/{queue name}`.
+    pub fn jobs(self, queue: JobQueue) -> Self {
+        self.update(|inner| {
+            inner.jobs.insert(queue.name().to_string(), queue);
+        })
+    }
+
+    /// Report the variables declared in `registry` under `/env`.
+    pub fn env(self, registry: EnvRegistry) -> Self {
+        self.update(|inner| inner.env = Some(registry))
+    }
+
+    pub fn rate_limits(self, overrides: RateLimitOverrides) -> Self {
+        self.update(|inner| inner.rate_limits = overrides)
+    }
+
+    pub fn flags(self, flags: FeatureFlags) -> Self {
+        self.update(|inner| inner.flags = flags)
+    }
+
+    pub fn maintenance(self, mode: MaintenanceMode) -> Self {
+        self.update(|inner| inner.maintenance = mode)
+    }
+
+    pub fn guard(mut self, guard: ServiceTokenGuard) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    fn find_breaker(&self, name: &str) -> Result<&Arc<CircuitBreaker>, LanaiError> {
+        self.inner
+            .breakers
+            .get(name)
+            .ok_or_else(|| LanaiError::NotFound(format!("No circuit breaker named '{}'", name)))
+    }
+
+    fn job_queue(&self, name: &str) -> Result<&JobQueue, LanaiError> {
+        self.inner.jobs.get(name).ok_or_else(|| LanaiError::NotFound(format!("No job queue named '{}'", name)))
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.service(
+            web::scope(ADMIN_SCOPE)
+                .wrap(self.guard.clone())
+                .app_data(web::Data::new(self.clone()))
+                .route("/breakers", web::get().to(list_breakers))
+                .route("/breakers/{name}/reset", web::post().to(reset_breaker))
+                .route("/breakers/{name}/open", web::post().to(force_open_breaker))
+                .route("/breakers/{name}/close", web::post().to(force_close_breaker))
+                .route("/rate-limits", web::get().to(list_rate_limits))
+                .route("/rate-limits/{prefix}", web::put().to(set_rate_limit))
+                .route("/rate-limits/{prefix}", web::delete().to(remove_rate_limit))
+                .route("/flags", web::get().to(list_flags))
+                .route("/flags/{name}", web::put().to(set_flag))
+                .route("/flags/{name}", web::delete().to(remove_flag))
+                .route("/cache/purge", web::post().to(purge_cache))
+                .route("/jobs", web::get().to(job_stats))
+                .route("/jobs/{queue}/scheduled", web::get().to(scheduled_jobs))
+                .route("/jobs/{queue}/dead", web::get().to(dead_jobs))
+                .route("/jobs/{queue}/dead/{entry_id}/retry", web::post().to(retry_dead_job))
+                .route("/jobs/{queue}/{job_id}", web::delete().to(cancel_job))
+                .route("/maintenance", web::get().to(maintenance_status))
+                .route("/maintenance", web::put().to(enable_maintenance))
+                .route("/maintenance", web::delete().to(disable_maintenance))
+                .route("/env", web::get().to(env_report)),
+        );
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BreakerStatus {
+    name: String,
+    #[serde(flatten)]
+    stats: CircuitBreakerStats,
+}
+
+async fn list_breakers(admin: web::Data<AdminApi>) -> HttpResponse {
+    let mut breakers = Vec::new();
+    for (name, breaker) in &admin.inner.breakers {
+        breakers.push(BreakerStatus { name: name.clone(), stats: breaker.stats().await });
+    }
+    HttpResponse::Ok().json(breakers)
+}
+
+async fn reset_breaker(admin: web::Data<AdminApi>, name: web::Path<String>) -> Result<HttpResponse, LanaiError> {
+    admin.find_breaker(&name)?.reset().await;
+    info!("🔧 Circuit breaker '{}' reset via admin API", name);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+struct ForceOpen {
+    seconds: u64,
+}
+
+async fn force_open_breaker(
+    admin: web::Data<AdminApi>,
+    name: web::Path<String>,
+    body: web::Json<ForceOpen>,
+) -> Result<HttpResponse, LanaiError> {
+    if body.seconds == 0 {
+        return Err(LanaiError::BadRequest("seconds must be positive".to_string()));
+    }
+    admin.find_breaker(&name)?.force_open(Duration::from_secs(body.seconds)).await;
+    info!("🔧 Circuit breaker '{}' forced open for {}s via admin API", name, body.seconds);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn force_close_breaker(admin: web::Data<AdminApi>, name: web::Path<String>) -> Result<HttpResponse, LanaiError> {
+    admin.find_breaker(&name)?.force_closed().await;
+    info!("🔧 Circuit breaker '{}' forced closed via admin API", name);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn list_rate_limits(admin: web::Data<AdminApi>) -> HttpResponse {
+    HttpResponse::Ok().json(admin.inner.rate_limits.list())
+}
+
+async fn set_rate_limit(
+    admin: web::Data<AdminApi>,
+    prefix: web::Path<String>,
+    policy: web::Json<RateLimitPolicy>,
+) -> Result<HttpResponse, LanaiError> {
+    if policy.max_requests == 0 || policy.window_seconds == 0 {
+        return Err(LanaiError::BadRequest("max_requests and window_seconds must be positive".to_string()));
+    }
+    admin.inner.rate_limits.set(&prefix, policy.into_inner());
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn remove_rate_limit(admin: web::Data<AdminApi>, prefix: web::Path<String>) -> Result<HttpResponse, LanaiError> {
+    match admin.inner.rate_limits.remove(&prefix) {
+        true => Ok(HttpResponse::NoContent().finish()),
+        false => Err(LanaiError::NotFound(format!("No rate limit override for '{}'", prefix))),
+    }
+}
+
+async fn list_flags(admin: web::Data<AdminApi>) -> HttpResponse {
+    HttpResponse::Ok().json(admin.inner.flags.list())
+}
+
+#[derive(Debug, Deserialize)]
+struct SetFlag {
+    enabled: bool,
+    #[serde(default)]
+    org_id: Option<Uuid>,
+}
+
+async fn set_flag(admin: web::Data<AdminApi>, name: web::Path<String>, body: web::Json<SetFlag>) -> HttpResponse {
+    match body.org_id {
+        Some(org_id) => admin.inner.flags.set_for_org(&name, org_id, body.enabled),
+        None => admin.inner.flags.set(&name, body.enabled),
+    }
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Debug, Deserialize)]
+struct FlagScope {
+    #[serde(default)]
+    org_id: Option<Uuid>,
+}
+
+async fn remove_flag(
+    admin: web::Data<AdminApi>,
+    name: web::Path<String>,
+    scope: web::Query<FlagScope>,
+) -> Result<HttpResponse, LanaiError> {
+    match scope.org_id {
+        Some(org_id) => admin.inner.flags.clear_for_org(&name, org_id),
+        None if admin.inner.flags.remove(&name) => {}
+        None => return Err(LanaiError::NotFound(format!("No feature flag named '{}'", name))),
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+struct PurgeCache {
+    pattern: String,
+    /// Purge one organization's entries instead of the global namespace.
+    #[serde(default)]
+    org_id: Option<Uuid>,
+}
+
+async fn env_report(admin: web::Data<AdminApi>) -> Result<HttpResponse, LanaiError> {
+    let registry = admin
+        .inner
+        .env
+        .as_ref()
+        .ok_or_else(|| LanaiError::NotFound("No environment registry is registered with the admin API".to_string()))?;
+    Ok(HttpResponse::Ok().json(registry.report()))
+}
+
+async fn purge_cache(admin: web::Data<AdminApi>, body: web::Json<PurgeCache>) -> Result<HttpResponse, LanaiError> {
+    let cache = admin
+        .inner
+        .cache
+        .as_ref()
+        .ok_or_else(|| LanaiError::NotFound("No cache is registered with the admin API".to_string()))?;
+    let cache = match body.org_id {
+        Some(org_id) => cache.for_tenant(&TenantContext { org_id }),
+        None => cache.clone(),
+    };
+    let purged = cache.purge(&body.pattern).await.map_err(LanaiError::internal)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "purged": purged })))
+}
+
+#[derive(Debug, Deserialize)]
+struct Listing {
+    #[serde(default = "default_listing_limit")]
+    limit: usize,
+}
+
+fn default_listing_limit() -> usize {
+    50
+}
+
+async fn job_stats(admin: web::Data<AdminApi>) -> Result<HttpResponse, LanaiError> {
+    let mut stats = Vec::new();
+    for queue in admin.inner.jobs.values() {
+        stats.push(queue.stats().await.map_err(LanaiError::internal)?);
+    }
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+async fn scheduled_jobs(
+    admin: web::Data<AdminApi>,
+    queue: web::Path<String>,
+    listing: web::Query<Listing>,
+) -> Result<HttpResponse, LanaiError> {
+    let jobs = admin.job_queue(&queue)?.scheduled(listing.limit.min(500)).await.map_err(LanaiError::internal)?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+async fn dead_jobs(
+    admin: web::Data<AdminApi>,
+    queue: web::Path<String>,
+    listing: web::Query<Listing>,
+) -> Result<HttpResponse, LanaiError> {
+    let jobs = admin.job_queue(&queue)?.dead_letters(listing.limit.min(500)).await.map_err(LanaiError::internal)?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+async fn retry_dead_job(
+    admin: web::Data<AdminApi>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, LanaiError> {
+    let (queue, entry_id) = path.into_inner();
+    match admin.job_queue(&queue)?.retry_dead(&entry_id).await.map_err(LanaiError::internal)? {
+        Some(job_id) => Ok(HttpResponse::Ok().json(serde_json::json!({ "job_id": job_id }))),
+        None => Err(LanaiError::NotFound(format!("No dead letter '{}' in '{}'", entry_id, queue))),
+    }
+}
+
+async fn cancel_job(admin: web::Data<AdminApi>, path: web::Path<(String, Uuid)>) -> Result<HttpResponse, LanaiError> {
+    let (queue, job_id) = path.into_inner();
+    let unscheduled = admin.job_queue(&queue)?.cancel(job_id).await.map_err(LanaiError::internal)?;
+    info!("🔧 Job {} on '{}' cancelled via admin API", job_id, queue);
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "unscheduled": unscheduled })))
+}
+
+#[derive(Debug, Deserialize)]
+struct EnableMaintenance {
+    message: String,
+}
+
+async fn maintenance_status(admin: web::Data<AdminApi>) -> HttpResponse {
+    HttpResponse::Ok().json(admin.inner.maintenance.status())
+}
+
+async fn enable_maintenance(admin: web::Data<AdminApi>, body: web::Json<EnableMaintenance>) -> HttpResponse {
+    admin.inner.maintenance.enable(&body.message);
+    HttpResponse::Ok().json(admin.inner.maintenance.status())
+}
+
+async fn disable_maintenance(admin: web::Data<AdminApi>) -> HttpResponse {
+    admin.inner.maintenance.disable();
+    HttpResponse::Ok().json(admin.inner.maintenance.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::service_token::SERVICE_TOKEN_HEADER;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_admin_controls() {
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let _ = breaker.call(|| async { Err::<(), _>("boom") }).await;
+        let flags = FeatureFlags::new();
+        let admin = AdminApi::new()
+            .guard(ServiceTokenGuard::new(vec!["ops".to_string()]))
+            .breaker("payments", breaker.clone())
+            .flags(flags.clone())
+            .rate_limits(RateLimitOverrides::default())
+            .maintenance(MaintenanceMode::default());
+        let app = test::init_service(App::new().configure(|cfg| admin.configure(cfg))).await;
+
+        let req = test::TestRequest::get().uri("/internal/admin/breakers").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+        let req = test::TestRequest::get()
+            .uri("/internal/admin/breakers")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["state"], "Open");
+
+        let req = test::TestRequest::post()
+            .uri("/internal/admin/breakers/payments/reset")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 204);
+        assert_eq!(breaker.state().await, crate::resilience::CircuitState::Closed);
+
+        let req = test::TestRequest::post()
+            .uri("/internal/admin/breakers/payments/open")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .set_json(serde_json::json!({"seconds": 300}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 204);
+        assert!(breaker.is_forced().await);
+        assert_eq!(breaker.state().await, crate::resilience::CircuitState::Open);
+
+        let req = test::TestRequest::put()
+            .uri("/internal/admin/flags/new_checkout")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .set_json(serde_json::json!({"enabled": true}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 204);
+        assert!(flags.is_enabled("new_checkout"));
+
+        let req = test::TestRequest::post()
+            .uri("/internal/admin/cache/purge")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .set_json(serde_json::json!({"pattern": "*"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let req = test::TestRequest::get()
+            .uri("/internal/admin/jobs/billing/dead")
+            .insert_header((SERVICE_TOKEN_HEADER, "ops"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+}
@@ -0,0 +1,382 @@
+//! Audit Trail
+//!
+//! An `AuditEvent` records who (actor) did what (action) to which resource, in which
+//! organization, with a field-level before/after diff. The `Auditor` writes each event to
+//! every configured sink: NATS (`lanai.audit.{org_id}.{action}`, for streaming to the
+//! central audit log) and/or PostgreSQL (queryable with `AuditQuery`).
+//!
+//! Values of fields whose name looks sensitive (`password`, `secret`, `token`, ...) are
+//! replaced by `"[REDACTED]"` in diffs.
+//!
+//! ```ignore
+//! let auditor = Auditor::new().sink(NatsAuditSink::new()).sink(PostgresAuditSink::new(pool.clone()));
+//!
+//! // in a handler (actor, tenant, IP and trace ID come from the request)
+//! lanai_infrastructure::audit::audit!(auditor, &req, "product.update", ("product", product.id), before = &old, after = &product);
+//! ```
+
+use actix_web::{HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+pub mod nats;
+pub mod postgres;
+
+pub use crate::__lanai_audit as audit;
+pub use nats::NatsAuditSink;
+pub use postgres::{AuditQuery, PostgresAuditSink};
+
+use crate::middleware::auth_guard::Claims;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Field names (case-insensitive substrings) whose values are never written to the audit log.
+const SENSITIVE_FIELDS: &[&str] = &["password", "secret", "token", "api_key", "card_number", "cvv"];
+
+/// Audit error types
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Failed to serialize audit event: {0}")]
+    Serialization(String),
+
+    #[error("Audit sink '{sink}' failed: {reason}")]
+    Sink { sink: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActorKind {
+    User,
+    Service,
+    System,
+    Anonymous,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub kind: ActorKind,
+    pub id: String,
+    pub email: Option<String>,
+}
+
+impl Actor {
+    pub fn user(id: impl Into<String>, email: Option<String>) -> Self {
+        Self { kind: ActorKind::User, id: id.into(), email }
+    }
+
+    pub fn service(name: impl Into<String>) -> Self {
+        Self { kind: ActorKind::Service, id: name.into(), email: None }
+    }
+
+    pub fn system() -> Self {
+        Self { kind: ActorKind::System, id: "system".to_string(), email: None }
+    }
+
+    pub fn anonymous() -> Self {
+        Self { kind: ActorKind::Anonymous, id: "anonymous".to_string(), email: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resource {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+impl Resource {
+    pub fn new(kind: impl Into<String>, id: impl ToString) -> Self {
+        Self { kind: kind.into(), id: id.to_string() }
+    }
+}
+
+/// One changed field; `path` is dot-separated (`address.city`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub path: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: Actor,
+    pub org_id: Option<Uuid>,
+    /// Dot-separated verb, e.g. `product.update`.
+    pub action: String,
+    pub resource: Resource,
+    pub outcome: AuditOutcome,
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+    pub ip: Option<String>,
+    pub trace_id: Option<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+impl AuditEvent {
+    pub fn new(actor: Actor, action: &str, resource: Resource) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            occurred_at: Utc::now(),
+            actor,
+            org_id: None,
+            action: action.to_string(),
+            resource,
+            outcome: AuditOutcome::Success,
+            changes: Vec::new(),
+            ip: None,
+            trace_id: crate::observability::current_trace_id(),
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    /// Event attributed to the request's authenticated user (`Claims`) and tenant
+    /// (`TenantContext`), with the client IP.
+    pub fn from_request(req: &HttpRequest, action: &str, resource: Resource) -> Self {
+        // The extensions borrow must end before `connection_info`, which may insert into them.
+        let (actor, org_id) = {
+            let extensions = req.extensions();
+            let claims = extensions.get::<Claims>();
+            let actor = claims.map_or_else(Actor::anonymous, |c| Actor::user(&c.sub, Some(c.email.clone())));
+            let org_id = extensions
+                .get::<TenantContext>()
+                .map(|tenant| tenant.org_id)
+                .or_else(|| claims.and_then(|c| c.org_id.as_deref()).and_then(|id| Uuid::parse_str(id).ok()));
+            (actor, org_id)
+        };
+
+        Self {
+            org_id,
+            ip: req.connection_info().realip_remote_addr().map(str::to_string),
+            ..Self::new(actor, action, resource)
+        }
+    }
+
+    pub fn org(mut self, org_id: Uuid) -> Self {
+        self.org_id = Some(org_id);
+        self
+    }
+
+    pub fn failed(mut self) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Record the field-level difference between two serializable states. Use
+    /// `&serde_json::Value::Null` as `before` for creations or `after` for deletions.
+    pub fn with_changes<B: Serialize + ?Sized, A: Serialize + ?Sized>(mut self, before: &B, after: &A) -> Self {
+        match (serde_json::to_value(before), serde_json::to_value(after)) {
+            (Ok(before), Ok(after)) => self.changes = diff(&before, &after),
+            (Err(e), _) | (_, Err(e)) => error!("❌ Failed to serialize audited state for '{}': {}", self.action, e),
+        }
+        self
+    }
+}
+
+/// Field-level difference between two JSON values, with sensitive values redacted.
+pub fn diff(before: &serde_json::Value, after: &serde_json::Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_into("", before, after, &mut changes);
+    changes
+}
+
+fn diff_into(path: &str, before: &serde_json::Value, after: &serde_json::Value, changes: &mut Vec<FieldChange>) {
+    use serde_json::{Map, Value};
+
+    // Null counts as an empty object so creations and deletions diff field by field.
+    let fields = |value: &Value| match value {
+        Value::Object(map) => Some(map.clone()),
+        Value::Null => Some(Map::new()),
+        _ => None,
+    };
+
+    match (fields(before), fields(after)) {
+        (Some(before_map), Some(after_map)) if before.is_object() || after.is_object() => {
+            let mut keys: Vec<&String> = before_map.keys().chain(after_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let old = before_map.get(key).unwrap_or(&Value::Null);
+                let new = after_map.get(key).unwrap_or(&Value::Null);
+                if is_sensitive(key) {
+                    if old != new {
+                        let redacted = |v: &Value| (!v.is_null()).then(|| Value::String("[REDACTED]".to_string()));
+                        changes.push(FieldChange { path: child, before: redacted(old), after: redacted(new) });
+                    }
+                } else {
+                    diff_into(&child, old, new, changes);
+                }
+            }
+        }
+        _ if before != after => changes.push(FieldChange {
+            path: if path.is_empty() { "$".to_string() } else { path.to_string() },
+            before: (!before.is_null()).then(|| before.clone()),
+            after: (!after.is_null()).then(|| after.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn is_sensitive(field: &str) -> bool {
+    let field = field.to_ascii_lowercase();
+    SENSITIVE_FIELDS.iter().any(|sensitive| field.contains(sensitive))
+}
+
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn write(&self, event: &AuditEvent) -> Result<(), AuditError>;
+}
+
+/// Writes audit events to every configured sink.
+#[derive(Clone, Default)]
+pub struct Auditor {
+    sinks: Vec<Arc<dyn AuditSink>>,
+}
+
+impl Auditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sink<S: AuditSink + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Write `event` to all sinks; every sink is attempted even if one fails.
+    pub async fn record(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        let mut first_error = None;
+        for sink in &self.sinks {
+            if let Err(e) = sink.write(event).await {
+                error!("❌ Failed to write audit event {} ({}): {}", event.id, event.action, e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Record in the background; failures are logged.
+    pub fn submit(&self, event: AuditEvent) {
+        let auditor = self.clone();
+        tokio::spawn(async move {
+            let _ = auditor.record(&event).await;
+        });
+    }
+}
+
+/// Keeps events in memory (for tests).
+#[derive(Default)]
+pub struct InMemoryAuditSink {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+impl InMemoryAuditSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn events(&self) -> Vec<AuditEvent> {
+        self.events.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl AuditSink for InMemoryAuditSink {
+    async fn write(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        self.events.write().await.push(event.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: AuditSink + ?Sized> AuditSink for Arc<S> {
+    async fn write(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        (**self).write(event).await
+    }
+}
+
+/// Record an audit event for the current request in the background.
+///
+/// `audit!(auditor, &req, "product.update", ("product", id))`, optionally followed by
+/// `before = &old, after = &new` to attach a diff.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lanai_audit {
+    ($auditor:expr, $req:expr, $action:expr, ($kind:expr, $id:expr)) => {
+        $auditor.submit($crate::audit::AuditEvent::from_request(
+            $req,
+            $action,
+            $crate::audit::Resource::new($kind, $id),
+        ))
+    };
+    ($auditor:expr, $req:expr, $action:expr, ($kind:expr, $id:expr), before = $before:expr, after = $after:expr) => {
+        $auditor.submit(
+            $crate::audit::AuditEvent::from_request($req, $action, $crate::audit::Resource::new($kind, $id))
+                .with_changes($before, $after),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_nested_and_redacted() {
+        let before = json!({"name": "Cola", "price": "1.50", "supplier": {"city": "Lima"}, "api_key": "a"});
+        let after = json!({"name": "Cola", "price": "1.75", "supplier": {"city": "Cusco"}, "api_key": "b", "sku": "C1"});
+
+        let changes = diff(&before, &after);
+        let paths: Vec<_> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["api_key", "price", "sku", "supplier.city"]);
+        assert_eq!(changes[0].after, Some(json!("[REDACTED]")));
+        assert_eq!(changes[2].before, None);
+    }
+
+    #[test]
+    fn test_diff_creation() {
+        let changes = diff(&serde_json::Value::Null, &json!({"id": 1}));
+        assert_eq!(changes, vec![FieldChange { path: "id".to_string(), before: None, after: Some(json!(1)) }]);
+    }
+
+    #[actix_web::test]
+    async fn test_macro_records_request_context() {
+        let sink = Arc::new(InMemoryAuditSink::new());
+        let auditor = Auditor::new().sink(sink.clone());
+        let org_id = Uuid::new_v4();
+
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(TenantContext { org_id });
+        crate::audit::audit!(auditor, &req, "product.delete", ("product", 42));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let events = sink.events().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].org_id, Some(org_id));
+        assert_eq!(events[0].actor.kind, ActorKind::Anonymous);
+        assert_eq!(events[0].resource, Resource::new("product", 42));
+    }
+}
@@ -0,0 +1,49 @@
+//! NATS audit sink
+
+use async_trait::async_trait;
+
+use super::{AuditError, AuditEvent, AuditSink};
+use crate::messaging::NatsClient;
+
+/// Publishes events to `{prefix}.{org_id}.{action}` (`system` when there is no org).
+/// Requires `NatsClient::init`.
+pub struct NatsAuditSink {
+    prefix: String,
+}
+
+impl NatsAuditSink {
+    /// Publishes under `lanai.audit`.
+    pub fn new() -> Self {
+        Self { prefix: "lanai.audit".to_string() }
+    }
+
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self { prefix: prefix.to_string() }
+    }
+
+    pub fn subject(&self, event: &AuditEvent) -> String {
+        let org = event.org_id.map_or_else(|| "system".to_string(), |id| id.to_string());
+        // Actions are dot-separated already; anything else a subject can't contain is replaced.
+        let action: String = event
+            .action
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+            .collect();
+        format!("{}.{}.{}", self.prefix, org, action.trim_matches('.'))
+    }
+}
+
+impl Default for NatsAuditSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl AuditSink for NatsAuditSink {
+    async fn write(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        NatsClient::publish_event(&self.subject(event), event)
+            .await
+            .map_err(|e| AuditError::Sink { sink: "nats", reason: e.to_string() })
+    }
+}
@@ -0,0 +1,168 @@
+//! PostgreSQL audit sink and queries
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::{AuditError, AuditEvent, AuditSink};
+
+/// Table used by `PostgresAuditSink`; include it in a service migration or call
+/// `ensure_schema` at startup. The filterable fields are columns, the full event is `event`.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_audit_log (
+    id UUID PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    org_id UUID,
+    actor_kind TEXT NOT NULL,
+    actor_id TEXT NOT NULL,
+    action TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    event JSONB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS lanai_audit_log_org_time ON lanai_audit_log (org_id, occurred_at DESC);
+CREATE INDEX IF NOT EXISTS lanai_audit_log_resource ON lanai_audit_log (resource_type, resource_id)";
+
+pub struct PostgresAuditSink {
+    pool: PgPool,
+}
+
+impl PostgresAuditSink {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), AuditError> {
+        sqlx::raw_sql(SCHEMA).execute(&self.pool).await.map_err(sink_error)?;
+        Ok(())
+    }
+
+    /// Events matching `query`, newest first.
+    pub async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>, AuditError> {
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT event FROM lanai_audit_log WHERE TRUE");
+        if let Some(org_id) = query.org_id {
+            builder.push(" AND org_id = ").push_bind(org_id);
+        }
+        if let Some(actor_id) = &query.actor_id {
+            builder.push(" AND actor_id = ").push_bind(actor_id.clone());
+        }
+        if let Some(action) = &query.action {
+            builder.push(" AND action = ").push_bind(action.clone());
+        }
+        if let Some(resource_type) = &query.resource_type {
+            builder.push(" AND resource_type = ").push_bind(resource_type.clone());
+        }
+        if let Some(resource_id) = &query.resource_id {
+            builder.push(" AND resource_id = ").push_bind(resource_id.clone());
+        }
+        if let Some(from) = query.from {
+            builder.push(" AND occurred_at >= ").push_bind(from);
+        }
+        if let Some(to) = query.to {
+            builder.push(" AND occurred_at < ").push_bind(to);
+        }
+        builder.push(" ORDER BY occurred_at DESC LIMIT ").push_bind(query.limit);
+
+        let rows: Vec<Json<AuditEvent>> =
+            builder.build_query_scalar().fetch_all(&self.pool).await.map_err(sink_error)?;
+        Ok(rows.into_iter().map(|Json(event)| event).collect())
+    }
+}
+
+fn sink_error(e: sqlx::Error) -> AuditError {
+    AuditError::Sink { sink: "postgres", reason: e.to_string() }
+}
+
+#[async_trait]
+impl AuditSink for PostgresAuditSink {
+    async fn write(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        let actor_kind = serde_json::to_value(event.actor.kind)
+            .map_err(|e| AuditError::Serialization(e.to_string()))?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        sqlx::query(
+            "INSERT INTO lanai_audit_log
+                (id, occurred_at, org_id, actor_kind, actor_id, action, resource_type, resource_id, event)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(event.id)
+        .bind(event.occurred_at)
+        .bind(event.org_id)
+        .bind(actor_kind)
+        .bind(&event.actor.id)
+        .bind(&event.action)
+        .bind(&event.resource.kind)
+        .bind(&event.resource.id)
+        .bind(Json(event))
+        .execute(&self.pool)
+        .await
+        .map_err(sink_error)?;
+        Ok(())
+    }
+}
+
+/// Filters for `PostgresAuditSink::query`; unset fields match everything.
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    pub org_id: Option<Uuid>,
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+impl AuditQuery {
+    /// Latest 100 events of an organization.
+    pub fn org(org_id: Uuid) -> Self {
+        Self { org_id: Some(org_id), ..Self::default() }
+    }
+
+    pub fn resource(mut self, kind: &str, id: impl ToString) -> Self {
+        self.resource_type = Some(kind.to_string());
+        self.resource_id = Some(id.to_string());
+        self
+    }
+
+    pub fn actor(mut self, actor_id: &str) -> Self {
+        self.actor_id = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn action(mut self, action: &str) -> Self {
+        self.action = Some(action.to_string());
+        self
+    }
+
+    pub fn between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.from = Some(from);
+        self.to = Some(to);
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = limit.clamp(1, 1000);
+        self
+    }
+}
+
+impl Default for AuditQuery {
+    fn default() -> Self {
+        Self {
+            org_id: None,
+            actor_id: None,
+            action: None,
+            resource_type: None,
+            resource_id: None,
+            from: None,
+            to: None,
+            limit: 100,
+        }
+    }
+}
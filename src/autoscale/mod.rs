@@ -0,0 +1,283 @@
+//! Autoscaling hints from backlog depth
+//!
+//! Worker deployments are better scaled on how much work is waiting than on CPU. A
+//! `ScalingHints` component measures backlogs (job queues, JetStream consumers, or any
+//! `BacklogSource`) and exposes them, with the replica count each backlog calls for, in two
+//! formats:
+//! - `GET /internal/scaling/metrics` — Prometheus text, for the Prometheus adapter or a
+//!   KEDA `prometheus` trigger
+//! - `GET /internal/scaling/{backlog}` — JSON for a KEDA `metrics-api` trigger
+//!   (`valueLocation: depth`, `targetValue` = the backlog's target per replica)
+//!
+//! ```ignore
+//! let hints = ScalingHints::new()
+//!     .job_queue(Backlog::new("billing-jobs", 50).replicas(1, 20), queue.clone())
+//!     .consumer(Backlog::new("orders-projector", 1_000), "ORDERS", "projector");
+//!
+//! ServerBuilder::new("lanai-billing")
+//!     .run(move |cfg| hints.configure(cfg))
+//!     .await
+//! ```
+//!
+//! Desired replicas are `ceil(depth / target_per_replica)`, clamped to the backlog's range.
+
+use actix_web::{web, HttpResponse};
+use async_trait::async_trait;
+use log::warn;
+use opentelemetry::metrics::Gauge;
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::error::LanaiError;
+use crate::jobs::JobQueue;
+use crate::messaging::{NatsClient, NatsError};
+
+/// Something with a measurable amount of waiting work.
+#[async_trait]
+pub trait BacklogSource: Send + Sync {
+    async fn depth(&self) -> Result<u64, String>;
+}
+
+#[async_trait]
+impl BacklogSource for JobQueue {
+    async fn depth(&self) -> Result<u64, String> {
+        self.len().await.map_err(|e| e.to_string())
+    }
+}
+
+/// Messages a JetStream consumer has not received or not acknowledged yet.
+pub struct ConsumerBacklog {
+    stream: String,
+    consumer: String,
+}
+
+impl ConsumerBacklog {
+    pub fn new(stream: &str, consumer: &str) -> Self {
+        Self { stream: stream.to_string(), consumer: consumer.to_string() }
+    }
+}
+
+#[async_trait]
+impl BacklogSource for ConsumerBacklog {
+    async fn depth(&self) -> Result<u64, String> {
+        let stream = NatsClient::jetstream()
+            .map_err(|e: NatsError| e.to_string())?
+            .get_stream(&self.stream)
+            .await
+            .map_err(|e| e.to_string())?;
+        let info = stream.consumer_info(&self.consumer).await.map_err(|e| e.to_string())?;
+        Ok(info.num_pending + info.num_ack_pending as u64)
+    }
+}
+
+/// Scaling thresholds of one backlog.
+#[derive(Debug, Clone)]
+pub struct Backlog {
+    name: String,
+    target_per_replica: u64,
+    min_replicas: u32,
+    max_replicas: u32,
+}
+
+impl Backlog {
+    /// One replica per `target_per_replica` waiting items, between 1 and 100 replicas.
+    pub fn new(name: &str, target_per_replica: u64) -> Self {
+        assert!(target_per_replica > 0, "target_per_replica must be positive");
+        Self { name: name.to_string(), target_per_replica, min_replicas: 1, max_replicas: 100 }
+    }
+
+    pub fn replicas(mut self, min: u32, max: u32) -> Self {
+        assert!(min <= max, "min replicas must not exceed max replicas");
+        self.min_replicas = min;
+        self.max_replicas = max;
+        self
+    }
+
+    pub fn desired_replicas(&self, depth: u64) -> u32 {
+        let wanted = depth.div_ceil(self.target_per_replica).min(u32::MAX as u64) as u32;
+        wanted.clamp(self.min_replicas, self.max_replicas)
+    }
+}
+
+/// A backlog measurement.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BacklogReading {
+    pub backlog: String,
+    pub depth: u64,
+    pub target_per_replica: u64,
+    pub desired_replicas: u32,
+}
+
+struct Inner {
+    backlogs: Vec<(Backlog, Box<dyn BacklogSource>)>,
+    depth: Gauge<u64>,
+    desired: Gauge<u64>,
+}
+
+/// Backlog depths and replica hints; clones share state.
+#[derive(Clone)]
+pub struct ScalingHints {
+    inner: Arc<Inner>,
+}
+
+impl Default for ScalingHints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScalingHints {
+    pub fn new() -> Self {
+        let meter = global::meter("lanai.autoscale");
+        Self {
+            inner: Arc::new(Inner {
+                backlogs: Vec::new(),
+                depth: meter
+                    .u64_gauge("backlog_depth")
+                    .with_description("Items waiting in a backlog used for autoscaling")
+                    .build(),
+                desired: meter
+                    .u64_gauge("backlog_desired_replicas")
+                    .with_description("Replicas a backlog calls for at its target per replica")
+                    .build(),
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("ScalingHints is configured before it is shared"));
+        self
+    }
+
+    pub fn source(self, backlog: Backlog, source: impl BacklogSource + 'static) -> Self {
+        self.update(|inner| inner.backlogs.push((backlog, Box::new(source))))
+    }
+
+    pub fn job_queue(self, backlog: Backlog, queue: JobQueue) -> Self {
+        self.source(backlog, queue)
+    }
+
+    pub fn consumer(self, backlog: Backlog, stream: &str, consumer: &str) -> Self {
+        self.source(backlog, ConsumerBacklog::new(stream, consumer))
+    }
+
+    async fn read(&self, backlog: &Backlog, source: &dyn BacklogSource) -> Result<BacklogReading, LanaiError> {
+        let depth = source.depth().await.map_err(|e| {
+            warn!("⚠️ Failed to measure backlog '{}': {}", backlog.name, e);
+            LanaiError::Unavailable(format!("Backlog '{}' unavailable", backlog.name))
+        })?;
+        let desired_replicas = backlog.desired_replicas(depth);
+        let labels = [KeyValue::new("backlog", backlog.name.clone())];
+        self.inner.depth.record(depth, &labels);
+        self.inner.desired.record(desired_replicas as u64, &labels);
+        Ok(BacklogReading {
+            backlog: backlog.name.clone(),
+            depth,
+            target_per_replica: backlog.target_per_replica,
+            desired_replicas,
+        })
+    }
+
+    /// Measure one backlog.
+    pub async fn reading(&self, name: &str) -> Result<BacklogReading, LanaiError> {
+        let (backlog, source) = self
+            .inner
+            .backlogs
+            .iter()
+            .find(|(backlog, _)| backlog.name == name)
+            .ok_or_else(|| LanaiError::NotFound(format!("Backlog '{}' is not registered", name)))?;
+        self.read(backlog, source.as_ref()).await
+    }
+
+    /// Measure every backlog; ones that cannot be measured are left out.
+    pub async fn readings(&self) -> Vec<BacklogReading> {
+        let mut readings = Vec::new();
+        for (backlog, source) in &self.inner.backlogs {
+            readings.extend(self.read(backlog, source.as_ref()).await.ok());
+        }
+        readings
+    }
+
+    /// Mount `GET /internal/scaling/metrics` and `GET /internal/scaling/{backlog}`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone())).service(
+            web::scope("/internal/scaling")
+                .route("/metrics", web::get().to(prometheus_metrics))
+                .route("/{backlog}", web::get().to(backlog_reading)),
+        );
+    }
+}
+
+/// A gauge as (name, help, value of a reading).
+type Series = (&'static str, &'static str, fn(&BacklogReading) -> u64);
+
+/// Prometheus text exposition of `readings`.
+pub fn render_prometheus(readings: &[BacklogReading]) -> String {
+    let mut out = String::new();
+    let series: [Series; 3] = [
+        ("lanai_backlog_depth", "Items waiting in the backlog", |r| r.depth),
+        ("lanai_backlog_target_per_replica", "Items one replica is expected to handle", |r| r.target_per_replica),
+        ("lanai_backlog_desired_replicas", "Replicas the backlog calls for", |r| r.desired_replicas as u64),
+    ];
+    for (name, help, value) in series {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        for reading in readings {
+            let label = reading.backlog.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(out, "{}{{backlog=\"{}\"}} {}", name, label, value(reading));
+        }
+    }
+    out
+}
+
+async fn prometheus_metrics(hints: web::Data<ScalingHints>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(render_prometheus(&hints.readings().await))
+}
+
+async fn backlog_reading(hints: web::Data<ScalingHints>, name: web::Path<String>) -> Result<HttpResponse, LanaiError> {
+    Ok(HttpResponse::Ok().json(hints.reading(&name).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+
+    struct Fixed(u64);
+
+    #[async_trait]
+    impl BacklogSource for Fixed {
+        async fn depth(&self) -> Result<u64, String> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_desired_replicas_are_clamped() {
+        let backlog = Backlog::new("jobs", 50).replicas(2, 10);
+        assert_eq!(backlog.desired_replicas(0), 2);
+        assert_eq!(backlog.desired_replicas(101), 3);
+        assert_eq!(backlog.desired_replicas(100_000), 10);
+    }
+
+    #[actix_web::test]
+    async fn test_scaling_endpoints() {
+        let hints = ScalingHints::new().source(Backlog::new("billing-jobs", 50), Fixed(120));
+        let app = actix_test::init_service(App::new().configure(|cfg| hints.configure(cfg))).await;
+
+        let req = actix_test::TestRequest::get().uri("/internal/scaling/billing-jobs").to_request();
+        let reading: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(reading["depth"], 120);
+        assert_eq!(reading["desired_replicas"], 3);
+
+        let req = actix_test::TestRequest::get().uri("/internal/scaling/metrics").to_request();
+        let body = actix_test::call_and_read_body(&app, req).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("lanai_backlog_depth{backlog=\"billing-jobs\"} 120"));
+
+        let req = actix_test::TestRequest::get().uri("/internal/scaling/unknown").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 404);
+    }
+}
@@ -0,0 +1,87 @@
+//! Re-publish events from a JetStream stream
+//!
+//! ```text
+//! lanai-republish --stream INVENTORY [--subject lanai.inventory.>] [--org <uuid>]
+//!                 [--since <rfc3339>] [--until <rfc3339>] [--limit <n>] [--dry-run]
+//! ```
+//!
+//! Connects to `NATS_URL` (default `nats://localhost:4222`). Run with `--dry-run` first.
+
+use chrono::{DateTime, Utc};
+use lanai_infrastructure::messaging::republish::{JetStreamRepublishSource, RepublishFilter, Republisher};
+use lanai_infrastructure::messaging::{NatsClient, DEFAULT_NATS_URL, NATS_URL_ENV};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const USAGE: &str = "usage: lanai-republish --stream <name> [--subject <pattern>] [--org <uuid>] \
+[--since <rfc3339>] [--until <rfc3339>] [--limit <n>] [--dry-run]";
+
+struct Args {
+    stream: String,
+    filter: RepublishFilter,
+    dry_run: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut stream = None;
+    let mut filter = RepublishFilter::new();
+    let mut dry_run = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--dry-run" {
+            dry_run = true;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        let time = |value: &str| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| format!("{} must be RFC 3339: {}", flag, e))
+        };
+        match flag.as_str() {
+            "--stream" => stream = Some(value),
+            "--subject" => filter = filter.subject(&value),
+            "--org" => filter = filter.org(value.parse().map_err(|e| format!("--org must be a UUID: {}", e))?),
+            "--since" => filter = filter.since(time(&value)?),
+            "--until" => filter = filter.until(time(&value)?),
+            "--limit" => filter = filter.limit(value.parse().map_err(|e| format!("--limit must be a number: {}", e))?),
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+
+    let stream = stream.ok_or("--stream is required")?;
+    Ok(Args { stream, filter, dry_run })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let url = std::env::var(NATS_URL_ENV).unwrap_or_else(|_| DEFAULT_NATS_URL.to_string());
+    if let Err(e) = NatsClient::init(&url).await {
+        eprintln!("failed to connect to NATS at {}: {}", url, e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut republisher = Republisher::new(Arc::new(JetStreamRepublishSource::new(&args.stream)));
+    if args.dry_run {
+        republisher = republisher.dry_run();
+    }
+    match republisher.run(&args.filter).await {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("republish failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
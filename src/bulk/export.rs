@@ -0,0 +1,120 @@
+//! Chunked NDJSON and CSV export responses
+
+use actix_web::{http::header, HttpResponse};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use log::error;
+use serde::Serialize;
+use std::fmt::Display;
+
+use super::BulkFormat;
+
+/// Streams records as a chunked download, encoding a batch of records per chunk.
+#[derive(Debug, Clone)]
+pub struct BulkExport {
+    format: BulkFormat,
+    filename: Option<String>,
+    batch: usize,
+}
+
+impl BulkExport {
+    /// 500 records per chunk.
+    pub fn new(format: BulkFormat) -> Self {
+        Self { format, filename: None, batch: 500 }
+    }
+
+    /// Offer the export as a download named `{name}.csv` / `{name}.ndjson`.
+    pub fn filename(mut self, name: &str) -> Self {
+        self.filename = Some(name.to_string());
+        self
+    }
+
+    /// Records encoded per chunk (records that are ready are not held back waiting for more).
+    pub fn batch(mut self, records: usize) -> Self {
+        self.batch = records.max(1);
+        self
+    }
+
+    /// `200 OK` streaming `records`. The status is sent before the first record, so an
+    /// error from the stream is logged and aborts the transfer; clients see a truncated
+    /// download rather than a complete-looking one.
+    pub fn respond<T, E, S>(self, records: S) -> HttpResponse
+    where
+        T: Serialize,
+        E: Display,
+        S: Stream<Item = Result<T, E>> + 'static,
+    {
+        let format = self.format;
+        let mut with_header = true;
+
+        let body = records.ready_chunks(self.batch).map(move |batch| {
+            let mut out = Vec::new();
+            for record in batch {
+                let record = record.map_err(|e| {
+                    error!("❌ Export failed while reading records: {}", e);
+                    actix_web::error::ErrorInternalServerError("Export failed")
+                })?;
+                encode(format, &record, with_header, &mut out).map_err(|e| {
+                    error!("❌ Export failed to encode a record: {}", e);
+                    actix_web::error::ErrorInternalServerError("Export failed")
+                })?;
+                with_header = false;
+            }
+            Ok::<_, actix_web::Error>(Bytes::from(out))
+        });
+
+        let mut response = HttpResponse::Ok();
+        response.content_type(self.format.content_type());
+        if let Some(name) = &self.filename {
+            response.insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", name.replace('"', ""), self.format.extension()),
+            ));
+        }
+        response.streaming(body)
+    }
+}
+
+fn encode<T: Serialize>(format: BulkFormat, record: &T, with_header: bool, out: &mut Vec<u8>) -> Result<(), String> {
+    match format {
+        BulkFormat::Ndjson => {
+            serde_json::to_writer(&mut *out, record).map_err(|e| e.to_string())?;
+            out.push(b'\n');
+        }
+        BulkFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(with_header).from_writer(&mut *out);
+            writer.serialize(record).map_err(|e| e.to_string())?;
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct ProductRow {
+        sku: &'static str,
+        name: &'static str,
+        stock: u32,
+    }
+
+    #[actix_web::test]
+    async fn test_csv_export_writes_header_once() {
+        let rows = futures_util::stream::iter(vec![
+            Ok::<_, String>(ProductRow { sku: "A-1", name: "Café, 250g", stock: 3 }),
+            Ok(ProductRow { sku: "B-2", name: "Tea", stock: 0 }),
+            Ok(ProductRow { sku: "C-3", name: "Mate", stock: 7 }),
+        ]);
+        let response = BulkExport::new(BulkFormat::Csv).filename("products").batch(2).respond(rows);
+        assert_eq!(
+            response.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"products.csv\""
+        );
+
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "sku,name,stock\nA-1,\"Café, 250g\",3\nB-2,Tea,0\nC-3,Mate,7\n");
+    }
+}
@@ -0,0 +1,331 @@
+//! Record-by-record reading of NDJSON and CSV request bodies
+
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::time::Duration;
+use validator::Validate;
+
+use super::{request_format, BulkError, BulkFormat, ImportReport, RecordError};
+use crate::error::{FieldViolation, LanaiError};
+use crate::middleware::request_size::RequestSizeLimit;
+use crate::validation::violations;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Import limits; register as `web::Data<ImportOptions>` to change the defaults.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    max_errors: usize,
+    read_timeout: Duration,
+}
+
+impl Default for ImportOptions {
+    /// Up to 1,000 invalid records, 30 seconds without data.
+    fn default() -> Self {
+        Self { max_errors: 1000, read_timeout: Duration::from_secs(30) }
+    }
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort the import once more records than this were rejected.
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Longest wait for the next chunk of the body.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+}
+
+enum Decoded<T> {
+    Header,
+    Record(T),
+    Invalid(RecordError),
+}
+
+/// Typed records of an `application/x-ndjson` or `text/csv` body. Extraction fails with 400
+/// for other content types.
+pub struct BulkImport<T> {
+    payload: Payload,
+    format: BulkFormat,
+    max_bytes: Option<usize>,
+    options: ImportOptions,
+    buffer: BytesMut,
+    /// Bytes of `buffer` already searched for a record end, and the CSV quote state there.
+    scanned: usize,
+    in_quotes: bool,
+    received: usize,
+    eof: bool,
+    failed: bool,
+    header: Option<csv::StringRecord>,
+    records: usize,
+    errors: Vec<RecordError>,
+    _record: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned + Validate> BulkImport<T> {
+    pub fn format(&self) -> BulkFormat {
+        self.format
+    }
+
+    /// The next valid record. Invalid records are skipped and collected in `errors()`;
+    /// an `Err` (body too large, stalled upload, too many invalid records) ends the import.
+    pub async fn next(&mut self) -> Option<Result<T, BulkError>> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let raw = match self.next_raw().await {
+                Ok(Some(raw)) => raw,
+                Ok(None) => return None,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            };
+
+            match self.decode(&raw) {
+                Decoded::Header => {}
+                Decoded::Record(record) => return Some(Ok(record)),
+                Decoded::Invalid(error) => {
+                    self.errors.push(error);
+                    if self.errors.len() > self.options.max_errors {
+                        self.failed = true;
+                        return Some(Err(BulkError::TooManyErrors(self.options.max_errors)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records rejected so far.
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    pub fn report(&self, imported: usize) -> ImportReport {
+        ImportReport { imported, failed: self.errors.len(), errors: self.errors.clone() }
+    }
+
+    /// The next non-blank record, without its line terminator.
+    async fn next_raw(&mut self) -> Result<Option<Bytes>, BulkError> {
+        loop {
+            while let Some(raw) = self.split_record() {
+                if !raw.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(Some(raw));
+                }
+            }
+            if self.eof {
+                let rest = self.buffer.split().freeze();
+                return Ok((!rest.iter().all(u8::is_ascii_whitespace)).then_some(rest));
+            }
+
+            match tokio::time::timeout(self.options.read_timeout, self.payload.next()).await {
+                Err(_) => return Err(BulkError::Timeout(self.options.read_timeout)),
+                Ok(None) => self.eof = true,
+                Ok(Some(Err(e))) => return Err(BulkError::Payload(e.to_string())),
+                Ok(Some(Ok(chunk))) => {
+                    let first = self.received == 0;
+                    self.received += chunk.len();
+                    if let Some(max) = self.max_bytes.filter(|max| self.received > *max) {
+                        return Err(BulkError::TooLarge(max));
+                    }
+                    let chunk = if first && chunk.starts_with(BOM) { chunk.slice(BOM.len()..) } else { chunk };
+                    self.buffer.extend_from_slice(&chunk);
+                }
+            }
+        }
+    }
+
+    /// Split off the first complete record of the buffer. CSV newlines inside quoted
+    /// fields do not end a record.
+    fn split_record(&mut self) -> Option<Bytes> {
+        let csv = self.format == BulkFormat::Csv;
+        for i in self.scanned..self.buffer.len() {
+            match self.buffer[i] {
+                b'"' if csv => self.in_quotes = !self.in_quotes,
+                b'\n' if !self.in_quotes => {
+                    let mut line = self.buffer.split_to(i + 1);
+                    line.truncate(i);
+                    if line.ends_with(b"\r") {
+                        line.truncate(i - 1);
+                    }
+                    self.scanned = 0;
+                    return Some(line.freeze());
+                }
+                _ => {}
+            }
+        }
+        self.scanned = self.buffer.len();
+        None
+    }
+
+    fn decode(&mut self, raw: &[u8]) -> Decoded<T> {
+        if self.format == BulkFormat::Csv && self.header.is_none() {
+            return match parse_csv(raw) {
+                Ok(mut header) => {
+                    header.trim();
+                    self.header = Some(header);
+                    Decoded::Header
+                }
+                Err(e) => Decoded::Invalid(RecordError { record: 0, message: e, violations: Vec::new() }),
+            };
+        }
+
+        self.records += 1;
+        let record = self.records;
+        let parsed: Result<T, DecodeError> = match self.format {
+            BulkFormat::Ndjson => decode_json(raw),
+            BulkFormat::Csv => decode_csv(raw, self.header.as_ref().expect("CSV header is read first")),
+        };
+        let invalid = |message: String, violations: Vec<FieldViolation>| {
+            Decoded::Invalid(RecordError { record, message, violations })
+        };
+
+        match parsed {
+            Ok(value) => match value.validate() {
+                Ok(()) => Decoded::Record(value),
+                Err(errors) => invalid("Validation failed".to_string(), violations(&errors)),
+            },
+            Err((message, violations)) => invalid(message, violations),
+        }
+    }
+}
+
+type DecodeError = (String, Vec<FieldViolation>);
+
+fn decode_json<T: DeserializeOwned>(raw: &[u8]) -> Result<T, DecodeError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(raw);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        if inner.is_data() && path != "." {
+            (inner.to_string(), vec![FieldViolation::new(path, "invalid_type").message(inner.to_string())])
+        } else {
+            (format!("Malformed JSON: {}", inner), Vec::new())
+        }
+    })
+}
+
+fn parse_csv(raw: &[u8]) -> Result<csv::StringRecord, String> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(raw)
+        .records()
+        .next()
+        .unwrap_or_else(|| Ok(csv::StringRecord::new()))
+        .map_err(|e| BulkError::Csv(e.to_string()).to_string())
+}
+
+fn decode_csv<T: DeserializeOwned>(raw: &[u8], header: &csv::StringRecord) -> Result<T, DecodeError> {
+    let record = parse_csv(raw).map_err(|e| (e, Vec::new()))?;
+    record.deserialize(Some(header)).map_err(|e| {
+        let field = match e.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.field().and_then(|index| header.get(index as usize)),
+            _ => None,
+        };
+        let violations = field
+            .map(|field| vec![FieldViolation::new(field, "invalid_type").message(e.to_string())])
+            .unwrap_or_default();
+        (e.to_string(), violations)
+    })
+}
+
+impl<T> FromRequest for BulkImport<T> {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let format = match request_format(req) {
+            Ok(format) => format,
+            Err(e) => return ready(Err(e.into())),
+        };
+        let options = req.app_data::<web::Data<ImportOptions>>().map(|options| options.get_ref().clone());
+
+        ready(Ok(BulkImport {
+            payload: payload.take(),
+            format,
+            max_bytes: req.extensions().get::<RequestSizeLimit>().map(|limit| limit.0),
+            options: options.unwrap_or_default(),
+            buffer: BytesMut::new(),
+            scanned: 0,
+            in_quotes: false,
+            received: 0,
+            eof: false,
+            failed: false,
+            header: None,
+            records: 0,
+            errors: Vec::new(),
+            _record: PhantomData,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Validate, PartialEq)]
+    struct ProductRow {
+        #[validate(length(min = 1))]
+        sku: String,
+        name: String,
+        stock: u32,
+    }
+
+    async fn import(content_type: &str, body: &'static str) -> (Vec<ProductRow>, Vec<RecordError>) {
+        let (req, mut payload) = TestRequest::post()
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_http_parts();
+        let mut rows = BulkImport::<ProductRow>::from_request(&req, &mut payload).await.unwrap();
+        let mut imported = Vec::new();
+        while let Some(row) = rows.next().await {
+            imported.push(row.unwrap());
+        }
+        (imported, rows.errors().to_vec())
+    }
+
+    #[actix_web::test]
+    async fn test_csv_import_collects_invalid_records() {
+        let body = "\u{FEFF}sku,name,stock\r\nA-1,\"Café, 250g\",3\r\n,Empty sku,1\r\nB-2,\"Multi\nline\",lots\r\nC-3,Tea,7";
+        let (rows, errors) = import("text/csv", body).await;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], ProductRow { sku: "A-1".into(), name: "Café, 250g".into(), stock: 3 });
+        assert_eq!(rows[1].sku, "C-3");
+        assert_eq!(errors.iter().map(|e| e.record).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(errors[0].violations[0].field, "sku");
+        assert_eq!(errors[1].violations[0].field, "stock");
+    }
+
+    #[actix_web::test]
+    async fn test_ndjson_import_and_size_limit() {
+        let body = "{\"sku\":\"A-1\",\"name\":\"Coffee\",\"stock\":3}\n\n{\"sku\":\"B-2\",\"name\":1,\"stock\":0}\n";
+        let (rows, errors) = import("application/x-ndjson", body).await;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(errors[0].violations[0].field, "name");
+
+        let (req, mut payload) = TestRequest::post()
+            .insert_header(("content-type", "application/x-ndjson"))
+            .set_payload(body)
+            .to_http_parts();
+        req.extensions_mut().insert(RequestSizeLimit(16));
+        let mut rows = BulkImport::<ProductRow>::from_request(&req, &mut payload).await.unwrap();
+        assert!(matches!(rows.next().await, Some(Err(BulkError::TooLarge(16)))));
+        assert!(rows.next().await.is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Streaming bulk import and export (NDJSON and CSV)
+//!
+//! `BulkImport<T>` reads a request body record by record while it arrives, so a catalog
+//! of 200k products never sits in memory as one buffer. Records that fail to decode or
+//! validate are collected with their record number instead of failing the whole upload;
+//! the handler persists the good ones and answers with an `ImportReport`:
+//!
+//! ```ignore
+//! async fn import_products(mut rows: BulkImport<ProductRow>) -> LanaiResult<HttpResponse> {
+//!     let mut imported = 0;
+//!     while let Some(row) = rows.next().await {
+//!         repo.upsert(row?).await?;
+//!         imported += 1;
+//!     }
+//!     Ok(HttpResponse::Ok().json(rows.report(imported)))
+//! }
+//!
+//! async fn export_products(format: BulkFormat) -> HttpResponse {
+//!     BulkExport::new(format).filename("products").respond(repo.stream_all())
+//! }
+//! ```
+//!
+//! The body limit is the `RequestSizeLimitMiddleware` one for the route (raise it with
+//! `ServerBuilder::max_request_size_for("/products/import", 200 * 1024 * 1024)`), and is
+//! enforced while streaming, also for chunked uploads. A client that stalls between
+//! chunks for longer than the read timeout fails the import with 408.
+
+use actix_web::{http::header, FromRequest, HttpMessage, HttpRequest};
+use serde::Serialize;
+use std::future::{ready, Ready};
+use thiserror::Error;
+
+use crate::error::{FieldViolation, LanaiError};
+
+pub mod export;
+pub mod import;
+
+pub use export::BulkExport;
+pub use import::{BulkImport, ImportOptions};
+
+/// Bulk error types
+#[derive(Debug, Error)]
+pub enum BulkError {
+    #[error("Unsupported bulk format '{0}' (expected application/x-ndjson or text/csv)")]
+    UnsupportedFormat(String),
+
+    #[error("Body exceeds {0} bytes")]
+    TooLarge(usize),
+
+    #[error("No data received for {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("Failed to read body: {0}")]
+    Payload(String),
+
+    #[error("Malformed CSV: {0}")]
+    Csv(String),
+
+    #[error("More than {0} invalid records")]
+    TooManyErrors(usize),
+}
+
+impl From<BulkError> for LanaiError {
+    fn from(e: BulkError) -> Self {
+        match e {
+            BulkError::Timeout(_) => Self::Timeout(e.to_string()),
+            other => Self::BadRequest(other.to_string()),
+        }
+    }
+}
+
+/// Record encodings for imports and exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkFormat {
+    /// One JSON document per line.
+    Ndjson,
+    /// RFC 4180 with a header row naming the fields.
+    Csv,
+}
+
+impl BulkFormat {
+    /// From a media type such as `text/csv; charset=utf-8`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => Some(Self::Ndjson),
+            "text/csv" | "application/csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// The format a client wants an export in: `?format=csv|ndjson`, else the first
+/// supported `Accept` type, else NDJSON.
+impl FromRequest for BulkFormat {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let query = actix_web::web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+            .map(|query| query.into_inner())
+            .unwrap_or_default();
+        if let Some((_, format)) = query.iter().find(|(name, _)| name == "format") {
+            return ready(match format.as_str() {
+                "csv" => Ok(Self::Csv),
+                "ndjson" | "jsonl" => Ok(Self::Ndjson),
+                other => Err(LanaiError::from(BulkError::UnsupportedFormat(other.to_string())).into()),
+            });
+        }
+
+        let accepted = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .and_then(|accept| accept.split(',').find_map(BulkFormat::from_mime));
+        ready(Ok(accepted.unwrap_or(Self::Ndjson)))
+    }
+}
+
+fn request_format(req: &HttpRequest) -> Result<BulkFormat, BulkError> {
+    BulkFormat::from_mime(req.content_type())
+        .ok_or_else(|| BulkError::UnsupportedFormat(req.content_type().to_string()))
+}
+
+/// A record that was skipped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordError {
+    /// 1-based position among the data records (the CSV header is not counted).
+    pub record: usize,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<FieldViolation>,
+}
+
+/// Outcome of an import, usually the response body.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub failed: usize,
+    pub errors: Vec<RecordError>,
+}
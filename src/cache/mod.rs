@@ -0,0 +1,477 @@
+//! Typed Redis Cache
+//!
+//! JSON-serialized values with TTLs, namespaced by service and (optionally) tenant:
+//! `{service}:global:{key}` or `{service}:org:{org_id}:{key}`.
+//!
+//! All caches in a process share one auto-reconnecting Redis connection manager, which
+//! fails over to the endpoints in `REDIS_FALLBACK_URLS` while `REDIS_URL` is unhealthy
+//! (see `resilience::failover`).
+//! `get_or_compute` is protected against stampedes (see `stampede`). For very hot keys,
+//! `TieredCache` adds an in-process L1 in front of Redis.
+//!
+//! ```ignore
+//! let cache = Cache::from_env("lanai-inventory-service").await?;
+//! let product: Product = cache
+//!     .for_tenant(&tenant)
+//!     .get_or_compute(&format!("product:{}", id), None, || repo.find(id))
+//!     .await?;
+//! ```
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use opentelemetry::global;
+use redis::aio::ConnectionManager;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+pub mod stampede;
+pub mod tiered;
+
+pub use stampede::StampedeProtection;
+pub use tiered::{InvalidationBus, TieredCache};
+use stampede::SingleFlight;
+
+use crate::common::{Clock, SystemClock};
+use crate::env::{EnvVar, VarType};
+use crate::messaging::shutdown;
+use crate::middleware::tenant_context::TenantContext;
+use crate::rate_limit::REDIS_URL_ENV;
+use crate::resilience::failover::{parse_endpoints, Connector, Failover, FailoverError, FailoverPolicy};
+
+/// Default TTL for entries written without an explicit one
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// Environment variable listing fallback Redis URLs, tried in order when `REDIS_URL` is down
+pub const REDIS_FALLBACK_URLS_ENV: &str = "REDIS_FALLBACK_URLS";
+
+/// Variables read for the shared Redis connection (also used by rate limiting)
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar::new(REDIS_URL_ENV, VarType::Url).description("Redis URL for cache, rate limits and jobs"),
+    EnvVar::new(REDIS_FALLBACK_URLS_ENV, VarType::UrlList).description("Fallback Redis endpoints, in order"),
+];
+
+static REDIS_CONNECTION: OnceCell<Failover<RedisConnector>> = OnceCell::const_new();
+
+/// Cache error types
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("REDIS_URL is not set")]
+    NotConfigured,
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("Redis connection failed: {0}")]
+    Connection(#[from] FailoverError),
+
+    #[error("Failed to serialize cache value: {0}")]
+    Serialization(String),
+
+    #[error("Invalidation bus error: {0}")]
+    Bus(String),
+}
+
+/// Shared connection manager for `REDIS_URL`, created on first use. After a failover this
+/// is a connection to the fallback region, so fetch it per use rather than keeping it.
+pub async fn shared_connection() -> Result<ConnectionManager, CacheError> {
+    Ok(shared_failover().await?.current())
+}
+
+/// The failover behind `shared_connection`. Only monitored when `REDIS_FALLBACK_URLS` is set;
+/// subscribe to it to learn when the connection is replaced.
+pub async fn shared_failover() -> Result<&'static Failover<RedisConnector>, CacheError> {
+    REDIS_CONNECTION
+        .get_or_try_init(|| async {
+            let url = std::env::var(REDIS_URL_ENV).map_err(|_| CacheError::NotConfigured)?;
+            let fallbacks = std::env::var(REDIS_FALLBACK_URLS_ENV).unwrap_or_default();
+            let endpoints = parse_endpoints(&url, &fallbacks);
+            let monitored = endpoints.len() > 1;
+            let failover = Failover::connect("redis", endpoints, RedisConnector, FailoverPolicy::default()).await?;
+            if monitored {
+                failover.start();
+            }
+            register_connection_metrics(failover.clone());
+            Ok(failover)
+        })
+        .await
+}
+
+/// Connects `Failover` to Redis endpoints; healthy means answering `PING`.
+pub struct RedisConnector;
+
+#[async_trait]
+impl Connector for RedisConnector {
+    type Connection = ConnectionManager;
+
+    async fn connect(&self, url: &str) -> Result<ConnectionManager, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        ConnectionManager::new(client).await.map_err(|e| e.to_string())
+    }
+
+    async fn ping(&self, connection: &ConnectionManager) -> bool {
+        redis::cmd("PING").query_async::<_, String>(&mut connection.clone()).await.is_ok()
+    }
+}
+
+/// Probe the shared connection with `PING` every 15s until shutdown, exporting
+/// `redis_ping_duration_seconds` and `redis_connection_up`. The connection manager
+/// multiplexes one connection, so a rising ping time is the sign of a saturated connection.
+fn register_connection_metrics(failover: Failover<RedisConnector>) {
+    let meter = global::meter("lanai.cache");
+    let up = Arc::new(AtomicU64::new(1));
+    let ping = meter
+        .f64_histogram("redis_ping_duration_seconds")
+        .with_description("Round-trip time of PING on the shared Redis connection")
+        .with_unit("s")
+        .build();
+    let observed = up.clone();
+    meter
+        .u64_observable_gauge("redis_connection_up")
+        .with_description("Whether the last PING on the shared Redis connection succeeded")
+        .with_callback(move |observer| observer.observe(observed.load(Ordering::Relaxed), &[]))
+        .build();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(15));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown::requested() => break,
+            }
+            let started = Instant::now();
+            let result = tokio::time::timeout(
+                Duration::from_secs(5),
+                redis::cmd("PING").query_async::<_, String>(&mut failover.current()),
+            )
+            .await;
+            let ok = matches!(result, Ok(Ok(_)));
+            if ok {
+                ping.record(started.elapsed().as_secs_f64(), &[]);
+            } else {
+                warn!("⚠️ Redis PING failed on the shared connection");
+            }
+            up.store(ok as u64, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Stored form of a cached value, with the metadata needed for early expiration.
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    #[serde(rename = "v")]
+    value: T,
+    /// How long the value took to compute, in milliseconds.
+    #[serde(rename = "d", default)]
+    delta_ms: u64,
+    /// Logical expiry as a Unix timestamp in milliseconds.
+    #[serde(rename = "e")]
+    expires_at_ms: i64,
+}
+
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// Build the fully-qualified Redis key.
+fn namespaced_key(service: &str, org_id: Option<Uuid>, key: &str) -> String {
+    match org_id {
+        Some(org_id) => format!("{}:org:{}:{}", service, org_id, key),
+        None => format!("{}:global:{}", service, key),
+    }
+}
+
+#[derive(Clone)]
+pub struct Cache {
+    conn: ConnectionManager,
+    service: Arc<str>,
+    org_id: Option<Uuid>,
+    default_ttl: Duration,
+    protection: StampedeProtection,
+    flights: Arc<SingleFlight>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Cache {
+    pub fn new(conn: ConnectionManager, service: &str) -> Self {
+        Self {
+            conn,
+            service: Arc::from(service),
+            org_id: None,
+            default_ttl: DEFAULT_TTL,
+            protection: StampedeProtection::default(),
+            flights: Arc::new(SingleFlight::default()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Cache on the shared connection for `REDIS_URL`.
+    pub async fn from_env(service: &str) -> Result<Self, CacheError> {
+        Ok(Self::new(shared_connection().await?, service))
+    }
+
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    pub fn with_stampede_protection(mut self, protection: StampedeProtection) -> Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Read the time for logical expiry and early refresh from `clock`. Redis evicts
+    /// entries on its own clock regardless.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// View of this cache whose keys are scoped to the tenant's organization.
+    pub fn for_tenant(&self, tenant: &TenantContext) -> Self {
+        Self { org_id: Some(tenant.org_id), ..self.clone() }
+    }
+
+    /// Fully-qualified Redis key for `key` in this cache's namespace.
+    pub fn key(&self, key: &str) -> String {
+        namespaced_key(&self.service, self.org_id, key)
+    }
+
+    /// Values that no longer deserialize (e.g. after a schema change) are treated as misses.
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, CacheError> {
+        Ok(self.get_entry(&self.key(key)).await?.map(|entry| entry.value))
+    }
+
+    /// Store `value` for `ttl` (or the default TTL).
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Option<Duration>) -> Result<(), CacheError> {
+        self.set_entry(&self.key(key), value, ttl, Duration::ZERO).await
+    }
+
+    /// Returns true if an entry was removed.
+    pub async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let removed: i64 = redis::cmd("DEL").arg(self.key(key)).query_async(&mut self.conn.clone()).await?;
+        Ok(removed > 0)
+    }
+
+    /// Delete every entry in this cache's namespace whose key matches the glob `pattern`
+    /// (`*` for all of them). Returns the number of entries removed.
+    pub async fn purge(&self, pattern: &str) -> Result<u64, CacheError> {
+        let mut conn = self.conn.clone();
+        let matching = self.key(pattern);
+        let mut cursor: u64 = 0;
+        let mut removed: u64 = 0;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&matching)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            if !keys.is_empty() {
+                let count: u64 = redis::cmd("UNLINK").arg(&keys).query_async(&mut conn).await?;
+                removed += count;
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        info!("🧹 Purged {} cache entries matching '{}'", removed, matching);
+        Ok(removed)
+    }
+
+    /// Return the cached value, or compute, store and return it.
+    ///
+    /// Only one caller per key refills an expired entry (see `stampede`); entries close to
+    /// expiry may be refreshed early. Cache failures are logged and bypassed: the value is
+    /// computed as if it were a miss, so a Redis outage degrades latency rather than availability.
+    pub async fn get_or_compute<T, E, F, Fut>(&self, key: &str, ttl: Option<Duration>, compute: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let full_key = self.key(key);
+
+        let cached = self.read_fresh(&full_key).await;
+        let mut stale = match cached {
+            Ok(Some((value, false))) => return Ok(value),
+            Ok(Some((value, true))) => Some(value),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("⚠️ Cache read for '{}' failed, computing: {}", full_key, e);
+                return self.compute_and_store(&full_key, ttl, compute).await;
+            }
+        };
+
+        // Single-flight within this process: wait for the caller already refilling the key,
+        // unless we can serve the old value in the meantime.
+        let flight = self.flights.lock_for(&full_key);
+        let guard = match flight.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => {
+                if let Some(value) = stale.take() {
+                    return Ok(value);
+                }
+                flight.lock().await
+            }
+        };
+        let result = self.refill(&full_key, ttl, stale, compute).await;
+        drop(guard);
+        self.flights.release(&full_key, flight);
+        result
+    }
+
+    async fn refill<T, E, F, Fut>(&self, full_key: &str, ttl: Option<Duration>, stale: Option<T>, compute: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        // Another caller in this process may have refilled it while we waited.
+        if let Ok(Some((value, false))) = self.read_fresh::<T>(full_key).await {
+            return Ok(value);
+        }
+
+        let lock_key = format!("{}:lock", full_key);
+        let token = Uuid::new_v4().to_string();
+        match self.try_lock(&lock_key, &token).await {
+            Ok(true) => {
+                let result = self.compute_and_store(full_key, ttl, compute).await;
+                self.unlock(&lock_key, &token).await;
+                result
+            }
+            Ok(false) => {
+                // Another replica is refilling: serve the old value if we have one...
+                if let Some(value) = stale {
+                    debug!("🕰️ Serving cached '{}' while another instance refreshes it", full_key);
+                    return Ok(value);
+                }
+                // ...otherwise wait for its result, and compute ourselves if it never arrives.
+                let deadline = Instant::now() + self.protection.lock_wait;
+                while Instant::now() < deadline {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    if let Ok(Some(entry)) = self.get_entry::<T>(full_key).await {
+                        return Ok(entry.value);
+                    }
+                }
+                warn!("⚠️ Gave up waiting for another instance to refill '{}', computing", full_key);
+                self.compute_and_store(full_key, ttl, compute).await
+            }
+            Err(e) => {
+                warn!("⚠️ Cache lock for '{}' failed, computing: {}", full_key, e);
+                self.compute_and_store(full_key, ttl, compute).await
+            }
+        }
+    }
+
+    /// Cached value and whether it should be refreshed early.
+    async fn read_fresh<T: DeserializeOwned>(&self, full_key: &str) -> Result<Option<(T, bool)>, CacheError> {
+        let now_ms = self.clock.utc_now().timestamp_millis();
+        Ok(self.get_entry::<T>(full_key).await?.map(|entry| {
+            let refresh = self.protection.should_refresh(now_ms, entry.delta_ms, entry.expires_at_ms);
+            (entry.value, refresh)
+        }))
+    }
+
+    async fn compute_and_store<T, E, F, Fut>(&self, full_key: &str, ttl: Option<Duration>, compute: F) -> Result<T, E>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let started = self.clock.now();
+        let value = compute().await?;
+        let delta = self.clock.now().saturating_duration_since(started);
+        if let Err(e) = self.set_entry(full_key, &value, ttl, delta).await {
+            warn!("⚠️ Cache write for '{}' failed: {}", full_key, e);
+        }
+        Ok(value)
+    }
+
+    async fn get_entry<T: DeserializeOwned>(&self, full_key: &str) -> Result<Option<Entry<T>>, CacheError> {
+        let raw: Option<String> = redis::cmd("GET").arg(full_key).query_async(&mut self.conn.clone()).await?;
+
+        Ok(raw.and_then(|raw| match serde_json::from_str(&raw) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("⚠️ Ignoring undecodable cache entry '{}': {}", full_key, e);
+                None
+            }
+        }))
+    }
+
+    async fn set_entry<T: Serialize>(
+        &self,
+        full_key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+        delta: Duration,
+    ) -> Result<(), CacheError> {
+        let ttl_ms = ttl.unwrap_or(self.default_ttl).as_millis().max(1) as u64;
+        let entry = Entry {
+            value,
+            delta_ms: delta.as_millis() as u64,
+            expires_at_ms: self.clock.utc_now().timestamp_millis() + ttl_ms as i64,
+        };
+        let payload = serde_json::to_string(&entry).map_err(|e| CacheError::Serialization(e.to_string()))?;
+
+        redis::cmd("SET")
+            .arg(full_key)
+            .arg(payload)
+            .arg("PX")
+            .arg(ttl_ms)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    async fn try_lock(&self, lock_key: &str, token: &str) -> Result<bool, CacheError> {
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(lock_key)
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.protection.lock_ttl.as_millis() as u64)
+            .query_async(&mut self.conn.clone())
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    /// Release the lock only if we still own it (it may have expired and been re-acquired).
+    async fn unlock(&self, lock_key: &str, token: &str) {
+        let result: Result<i64, _> = redis::Script::new(RELEASE_LOCK_SCRIPT)
+            .key(lock_key)
+            .arg(token)
+            .invoke_async(&mut self.conn.clone())
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Failed to release cache lock '{}': {}", lock_key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_namespacing() {
+        let org_id = Uuid::nil();
+        assert_eq!(namespaced_key("lanai-inventory", None, "product:1"), "lanai-inventory:global:product:1");
+        assert_eq!(
+            namespaced_key("lanai-inventory", Some(org_id), "product:1"),
+            format!("lanai-inventory:org:{}:product:1", org_id)
+        );
+    }
+}
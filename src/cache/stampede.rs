@@ -0,0 +1,98 @@
+//! Cache stampede protection
+//!
+//! When a hot key expires, `get_or_compute` lets a single caller refill it:
+//! - Within a process, callers for the same key queue on a per-key lock (single-flight)
+//!   and re-read the cache once they get it.
+//! - Across replicas, the refilling caller holds a short Redis lock; others serve the
+//!   still-cached value if there is one, or poll for the refilled value.
+//! - Entries are refreshed probabilistically before they expire ("XFetch"): the closer to
+//!   expiry and the slower the computation, the more likely a read triggers a refresh, so
+//!   hot keys are usually recomputed while the old value is still being served.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Tuning for `Cache::get_or_compute`.
+#[derive(Debug, Clone)]
+pub struct StampedeProtection {
+    /// Early-expiration aggressiveness (0 disables; 1.0 is the usual choice).
+    pub beta: f64,
+    /// Lifetime of the Redis refill lock, bounding how long a crashed holder blocks others.
+    pub lock_ttl: Duration,
+    /// How long a caller without a cached value waits for another replica's refill
+    /// before computing the value itself.
+    pub lock_wait: Duration,
+}
+
+impl Default for StampedeProtection {
+    fn default() -> Self {
+        Self { beta: 1.0, lock_ttl: Duration::from_secs(10), lock_wait: Duration::from_secs(5) }
+    }
+}
+
+impl StampedeProtection {
+    /// XFetch: decide whether to refresh an entry computed in `delta_ms` that expires at
+    /// `expires_at_ms`, as of `now_ms`.
+    pub(crate) fn should_refresh(&self, now_ms: i64, delta_ms: u64, expires_at_ms: i64) -> bool {
+        if self.beta <= 0.0 {
+            return now_ms >= expires_at_ms;
+        }
+        // 1 - random() lies in (0, 1], so ln() is finite and non-positive.
+        let gap = delta_ms as f64 * self.beta * -(1.0 - rand::random::<f64>()).ln();
+        now_ms as f64 + gap >= expires_at_ms as f64
+    }
+}
+
+/// Per-key in-process locks.
+#[derive(Default)]
+pub(crate) struct SingleFlight {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl SingleFlight {
+    pub(crate) fn lock_for(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(key.to_string()).or_default().clone()
+    }
+
+    /// Drop the lock for `key` once no other caller holds or waits on it.
+    pub(crate) fn release(&self, key: &str, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        // One reference in the map, one in `lock`.
+        if Arc::strong_count(&lock) <= 2 {
+            locks.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_early_expiration() {
+        let protection = StampedeProtection::default();
+        // Far from expiry relative to compute time: almost never refreshed.
+        let refreshes = (0..1000).filter(|_| protection.should_refresh(0, 10, 1_000_000)).count();
+        assert!(refreshes < 5);
+        // Already expired: always refreshed.
+        assert!(protection.should_refresh(2_000, 10, 1_000));
+
+        let disabled = StampedeProtection { beta: 0.0, ..Default::default() };
+        assert!(!disabled.should_refresh(999, 1_000_000, 1_000));
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_lock_is_shared_and_released() {
+        let flight = SingleFlight::default();
+        let first = flight.lock_for("k");
+        let second = flight.lock_for("k");
+        assert!(Arc::ptr_eq(&first, &second));
+
+        flight.release("k", second);
+        assert!(flight.locks.lock().unwrap().contains_key("k"));
+        flight.release("k", first);
+        assert!(!flight.locks.lock().unwrap().contains_key("k"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Two-tier cache: in-process L1 (moka) in front of the Redis L2
+//!
+//! Reads hit the local L1 first, so hot entries such as tenant configuration are served
+//! without a network round-trip. Writes and deletes go to Redis and publish an
+//! invalidation message so every replica evicts its L1 copy. L1 entries also expire after
+//! a short TTL, bounding staleness if an invalidation is missed.
+//!
+//! ```ignore
+//! let config_cache = TieredCache::new(Cache::from_env("lanai-config").await?, InvalidationBus::Redis);
+//! let _listener = config_cache.start_invalidation().await?;
+//! let settings: TenantSettings = config_cache.for_tenant(&tenant).get_or_compute("settings", None, load).await?;
+//! ```
+
+use futures_util::StreamExt;
+use log::{debug, info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::{Cache, CacheError};
+use crate::messaging::events::decode_event;
+use crate::messaging::NatsClient;
+use crate::middleware::tenant_context::TenantContext;
+use crate::rate_limit::REDIS_URL_ENV;
+
+/// Transport used to broadcast invalidations between replicas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidationBus {
+    /// Redis pub/sub on `lanai:cache:invalidate:{service}`.
+    Redis,
+    /// NATS subject `lanai.cache.invalidate.{service}` (requires `NatsClient::init`).
+    Nats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Invalidation {
+    key: String,
+    /// Instance that made the change; it has already updated its own L1.
+    origin: Uuid,
+}
+
+type Local = moka::future::Cache<String, Arc<dyn Any + Send + Sync>>;
+
+#[derive(Clone)]
+pub struct TieredCache {
+    l2: Cache,
+    l1: Local,
+    bus: InvalidationBus,
+    instance_id: Uuid,
+}
+
+impl TieredCache {
+    /// L1 holds up to 10,000 entries for 30 seconds each.
+    pub fn new(l2: Cache, bus: InvalidationBus) -> Self {
+        Self::with_l1(l2, bus, 10_000, Duration::from_secs(30))
+    }
+
+    pub fn with_l1(l2: Cache, bus: InvalidationBus, max_entries: u64, ttl: Duration) -> Self {
+        let l1 = moka::future::Cache::builder().max_capacity(max_entries).time_to_live(ttl).build();
+        Self { l2, l1, bus, instance_id: Uuid::new_v4() }
+    }
+
+    /// View scoped to the tenant's organization; shares L1 and invalidations.
+    pub fn for_tenant(&self, tenant: &TenantContext) -> Self {
+        Self { l2: self.l2.for_tenant(tenant), ..self.clone() }
+    }
+
+    pub async fn get<T>(&self, key: &str) -> Result<Option<T>, CacheError>
+    where
+        T: Clone + DeserializeOwned + Send + Sync + 'static,
+    {
+        let full_key = self.l2.key(key);
+        if let Some(value) = self.local::<T>(&full_key).await {
+            return Ok(Some(value));
+        }
+
+        let value = self.l2.get::<T>(key).await?;
+        if let Some(value) = &value {
+            self.l1.insert(full_key, Arc::new(value.clone())).await;
+        }
+        Ok(value)
+    }
+
+    /// Write through to Redis and tell other replicas to drop their L1 copy.
+    pub async fn set<T>(&self, key: &str, value: &T, ttl: Option<Duration>) -> Result<(), CacheError>
+    where
+        T: Clone + Serialize + Send + Sync + 'static,
+    {
+        self.l2.set(key, value, ttl).await?;
+        let full_key = self.l2.key(key);
+        self.l1.insert(full_key.clone(), Arc::new(value.clone())).await;
+        self.publish(full_key).await;
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let removed = self.l2.delete(key).await?;
+        let full_key = self.l2.key(key);
+        self.l1.invalidate(&full_key).await;
+        self.publish(full_key).await;
+        Ok(removed)
+    }
+
+    /// L1, then L2 (with its stampede protection), then `compute`.
+    ///
+    /// A value computed here is new to every replica's L1, so no invalidation is published.
+    pub async fn get_or_compute<T, E, F, Fut>(&self, key: &str, ttl: Option<Duration>, compute: F) -> Result<T, E>
+    where
+        T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let full_key = self.l2.key(key);
+        if let Some(value) = self.local::<T>(&full_key).await {
+            return Ok(value);
+        }
+
+        let value = self.l2.get_or_compute(key, ttl, compute).await?;
+        self.l1.insert(full_key, Arc::new(value.clone())).await;
+        Ok(value)
+    }
+
+    /// Listen for invalidations from other replicas. Abort the handle to stop.
+    pub async fn start_invalidation(&self) -> Result<JoinHandle<()>, CacheError> {
+        let l1 = self.l1.clone();
+        let instance_id = self.instance_id;
+        let channel = self.channel();
+
+        let mut messages: futures_util::stream::BoxStream<'static, Vec<u8>> = match self.bus {
+            InvalidationBus::Redis => {
+                let url = std::env::var(REDIS_URL_ENV).map_err(|_| CacheError::NotConfigured)?;
+                let mut pubsub = redis::Client::open(url.as_str())?.get_async_connection().await?.into_pubsub();
+                pubsub.subscribe(&channel).await?;
+                pubsub.into_on_message().map(|msg| msg.get_payload_bytes().to_vec()).boxed()
+            }
+            InvalidationBus::Nats => {
+                if !NatsClient::is_initialized() {
+                    return Err(CacheError::NotConfigured);
+                }
+                let subscriber = NatsClient::subscribe(&channel).await.map_err(|e| CacheError::Bus(e.to_string()))?;
+                subscriber.map(|msg| msg.payload.to_vec()).boxed()
+            }
+        };
+
+        info!("🧹 Listening for cache invalidations on '{}'", channel);
+        Ok(tokio::spawn(async move {
+            while let Some(payload) = messages.next().await {
+                match decode_event::<Invalidation>(&payload) {
+                    Ok(invalidation) if invalidation.origin == instance_id => {}
+                    Ok(invalidation) => {
+                        debug!("🧹 Evicting '{}' from L1", invalidation.key);
+                        l1.invalidate(&invalidation.key).await;
+                    }
+                    Err(e) => warn!("⚠️ Ignoring malformed cache invalidation: {}", e),
+                }
+            }
+            warn!("⚠️ Cache invalidation stream on '{}' ended", channel);
+        }))
+    }
+
+    async fn local<T: Clone + 'static>(&self, full_key: &str) -> Option<T> {
+        self.l1.get(full_key).await.and_then(|value| value.downcast_ref::<T>().cloned())
+    }
+
+    fn channel(&self) -> String {
+        match self.bus {
+            InvalidationBus::Redis => format!("lanai:cache:invalidate:{}", self.l2.service),
+            InvalidationBus::Nats => format!("lanai.cache.invalidate.{}", self.l2.service),
+        }
+    }
+
+    /// Broadcast an invalidation; failures only delay other replicas until their L1 TTL.
+    async fn publish(&self, key: String) {
+        let invalidation = Invalidation { key, origin: self.instance_id };
+        let result = match self.bus {
+            InvalidationBus::Redis => match serde_json::to_string(&invalidation) {
+                Ok(payload) => redis::cmd("PUBLISH")
+                    .arg(self.channel())
+                    .arg(payload)
+                    .query_async::<_, i64>(&mut self.l2.conn.clone())
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            InvalidationBus::Nats => NatsClient::publish_event(&self.channel(), &invalidation)
+                .await
+                .map_err(|e| e.to_string()),
+        };
+
+        if let Err(e) = result {
+            warn!("⚠️ Failed to publish cache invalidation for '{}': {}", invalidation.key, e);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Fault injection for NATS publishes
+
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use super::{Chaos, ChaosTarget, Fault};
+use crate::messaging::{MessageBus, NatsError, Subscription};
+
+/// Wraps a `MessageBus`, delaying, failing or silently losing publishes; subscriptions pass through.
+/// A dropped publish returns `Ok` without reaching the broker, like a message lost in transit.
+///
+/// ```ignore
+/// let bus: Arc<dyn MessageBus> = Arc::new(ChaosMessageBus::new(NatsMessageBus));
+/// ```
+pub struct ChaosMessageBus<B> {
+    inner: B,
+    chaos: Option<Chaos>,
+}
+
+impl<B: MessageBus> ChaosMessageBus<B> {
+    /// Uses the global injector; a passthrough when chaos is off.
+    pub fn new(inner: B) -> Self {
+        Self { inner, chaos: Chaos::global().cloned() }
+    }
+
+    pub fn with_chaos(inner: B, chaos: Chaos) -> Self {
+        Self { inner, chaos: Some(chaos) }
+    }
+}
+
+#[async_trait]
+impl<B: MessageBus> MessageBus for ChaosMessageBus<B> {
+    async fn publish_raw(&self, subject: &str, headers: HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+        if let Some(chaos) = &self.chaos {
+            match chaos.inject(ChaosTarget::Nats, subject).await {
+                Some(Fault::Error) => return Err(NatsError::PublishError("chaos: injected publish failure".to_string())),
+                Some(Fault::Drop) => return Ok(()),
+                None => {}
+            }
+        }
+        self.inner.publish_raw(subject, headers, payload).await
+    }
+
+    async fn subscribe(&self, subject: &str) -> Result<Subscription, NatsError> {
+        self.inner.subscribe(subject).await
+    }
+
+    async fn queue_subscribe(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        self.inner.queue_subscribe(subject, group).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaos::ChaosConfig;
+    use crate::messaging::MessageBusExt;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingBus(AtomicUsize);
+
+    #[async_trait]
+    impl MessageBus for CountingBus {
+        async fn publish_raw(&self, _: &str, _: HeaderMap, _: Bytes) -> Result<(), NatsError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_faults() {
+        let failing = ChaosMessageBus::with_chaos(CountingBus::default(), Chaos::forced(ChaosConfig::new().errors(1.0)));
+        assert!(failing.publish_event("orders.created", &json!({})).await.is_err());
+        assert_eq!(failing.inner.0.load(Ordering::SeqCst), 0);
+
+        let dropping = ChaosMessageBus::with_chaos(CountingBus::default(), Chaos::forced(ChaosConfig::new().drops(1.0)));
+        assert!(dropping.publish_event("orders.created", &json!({})).await.is_ok());
+        assert_eq!(dropping.inner.0.load(Ordering::SeqCst), 0);
+    }
+}
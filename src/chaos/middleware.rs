@@ -0,0 +1,130 @@
+//! Fault injection for HTTP handlers
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use super::{Chaos, ChaosTarget, Fault};
+
+pub const CHAOS_FAULT_HEADER: &str = "X-Chaos-Fault";
+
+/// Delays requests, answers 503 without running the handler, or runs the handler and
+/// then withholds its response (hanging for `drop_hang` before a 504), so clients see
+/// a timeout for work that actually happened.
+///
+/// ```ignore
+/// App::new().wrap(ChaosMiddleware::from_env())
+/// ```
+#[derive(Clone, Default)]
+pub struct ChaosMiddleware {
+    chaos: Option<Chaos>,
+}
+
+impl ChaosMiddleware {
+    /// Uses the global injector; a passthrough when chaos is off.
+    pub fn from_env() -> Self {
+        Self { chaos: Chaos::global().cloned() }
+    }
+
+    pub fn new(chaos: Chaos) -> Self {
+        Self { chaos: Some(chaos) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ChaosMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ChaosMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ChaosMiddlewareService { service: Rc::new(service), chaos: self.chaos.clone() }))
+    }
+}
+
+pub struct ChaosMiddlewareService<S> {
+    service: Rc<S>,
+    chaos: Option<Chaos>,
+}
+
+impl<S, B> Service<ServiceRequest> for ChaosMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let chaos = self.chaos.clone();
+
+        Box::pin(async move {
+            let Some(chaos) = chaos else {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            };
+
+            let operation = format!("{} {}", req.method(), req.path());
+            match chaos.inject(ChaosTarget::Http, &operation).await {
+                Some(Fault::Error) => {
+                    let response = HttpResponse::ServiceUnavailable()
+                        .insert_header((CHAOS_FAULT_HEADER, "error"))
+                        .json(serde_json::json!({"error": "Chaos: injected failure"}));
+                    Ok(req.into_response(response))
+                }
+                Some(Fault::Drop) => {
+                    let res = service.call(req).await?;
+                    tokio::time::sleep(chaos.config().drop_hang).await;
+                    let response = HttpResponse::GatewayTimeout().insert_header((CHAOS_FAULT_HEADER, "drop")).finish();
+                    Ok(res.into_response(response))
+                }
+                None => service.call(req).await.map(|res| res.map_into_boxed_body()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaos::ChaosConfig;
+    use actix_web::{test, web, App};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_dropped_response_still_runs_handler() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let chaos = Chaos::forced(ChaosConfig::new().drops(1.0).drop_hang(Duration::ZERO));
+        let app = test::init_service(App::new().wrap(ChaosMiddleware::new(chaos)).route(
+            "/",
+            web::post().to(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { HttpResponse::Ok().finish() }
+            }),
+        ))
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::post().uri("/").to_request()).await;
+        assert_eq!(res.status(), 504);
+        assert_eq!(res.headers().get(CHAOS_FAULT_HEADER).unwrap(), "drop");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
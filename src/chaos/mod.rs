@@ -0,0 +1,388 @@
+//! Fault injection for resilience testing
+//!
+//! Injects latency, errors and dropped responses into HTTP handlers (`ChaosMiddleware`),
+//! NATS publishes (`ChaosMessageBus`) and outbound calls (`Chaos::call`), so circuit
+//! breakers, retries and saga compensations can be exercised against a running stack.
+//! `ResiliencePipeline` injects into the calls it wraps; with a bare `CircuitBreaker`, wrap
+//! the call itself: `cb.call(|| chaos::outbound("payments", || client.charge(&order)))`.
+//!
+//! Rates apply to every operation unless overridden for one by name (the pipeline or
+//! outbound operation name, the NATS subject or `METHOD /path`):
+//!
+//! ```text
+//! LANAI_CHAOS_ENABLED=true
+//! LANAI_CHAOS_OPERATIONS=payments.error=0.3,payments.latency=1.0,orders.created.drop=0.1
+//! ```
+//!
+//! Nothing is injected unless `LANAI_CHAOS_ENABLED=true`; without it every entry point
+//! is a passthrough, so the wrappers can stay wired in production builds.
+
+pub mod bus;
+pub mod middleware;
+
+pub use bus::ChaosMessageBus;
+pub use middleware::ChaosMiddleware;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::error::LanaiError;
+
+pub const CHAOS_ENABLED_ENV: &str = "LANAI_CHAOS_ENABLED";
+/// Probability (0.0-1.0) of adding latency.
+pub const CHAOS_LATENCY_RATE_ENV: &str = "LANAI_CHAOS_LATENCY_RATE";
+/// Upper bound of the added latency, in milliseconds.
+pub const CHAOS_LATENCY_MS_ENV: &str = "LANAI_CHAOS_LATENCY_MS";
+/// Probability (0.0-1.0) of failing the call.
+pub const CHAOS_ERROR_RATE_ENV: &str = "LANAI_CHAOS_ERROR_RATE";
+/// Probability (0.0-1.0) of running the call but losing its response.
+pub const CHAOS_DROP_RATE_ENV: &str = "LANAI_CHAOS_DROP_RATE";
+/// Comma-separated subset of `http,nats,outbound`. Defaults to all.
+pub const CHAOS_TARGETS_ENV: &str = "LANAI_CHAOS_TARGETS";
+/// Comma-separated `{operation}.{latency|error|drop}={rate}` overrides for single operations.
+pub const CHAOS_OPERATIONS_ENV: &str = "LANAI_CHAOS_OPERATIONS";
+
+/// Whether fault injection is switched on for this process.
+pub fn enabled() -> bool {
+    std::env::var(CHAOS_ENABLED_ENV)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+/// Where a fault is injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChaosTarget {
+    Http,
+    Nats,
+    Outbound,
+}
+
+impl ChaosTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Nats => "nats",
+            Self::Outbound => "outbound",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "http" => Some(Self::Http),
+            "nats" => Some(Self::Nats),
+            "outbound" => Some(Self::Outbound),
+            _ => None,
+        }
+    }
+}
+
+/// The failure injected into a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The call fails without running.
+    Error,
+    /// The call runs, but the caller never sees its response.
+    Drop,
+}
+
+/// Rates for one operation; unset ones fall back to the `ChaosConfig` rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OperationRates {
+    pub latency_rate: Option<f64>,
+    pub error_rate: Option<f64>,
+    pub drop_rate: Option<f64>,
+}
+
+/// Injection rates. All zero by default, so only the faults asked for are injected.
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    pub latency_rate: f64,
+    pub max_latency: Duration,
+    pub error_rate: f64,
+    pub drop_rate: f64,
+    pub targets: Vec<ChaosTarget>,
+    /// How long a dropped HTTP response hangs before the connection gets a 504.
+    pub drop_hang: Duration,
+    /// Rates of single operations, by name.
+    pub operations: HashMap<String, OperationRates>,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            latency_rate: 0.0,
+            max_latency: Duration::from_millis(500),
+            error_rate: 0.0,
+            drop_rate: 0.0,
+            targets: vec![ChaosTarget::Http, ChaosTarget::Nats, ChaosTarget::Outbound],
+            drop_hang: Duration::from_secs(30),
+            operations: HashMap::new(),
+        }
+    }
+}
+
+impl ChaosConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read rates from the `LANAI_CHAOS_*` variables; unset ones stay at their defaults.
+    pub fn from_env() -> Self {
+        let rate = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+        let mut config = Self::default();
+        if let Some(r) = rate(CHAOS_LATENCY_RATE_ENV) {
+            config.latency_rate = r.clamp(0.0, 1.0);
+        }
+        if let Some(ms) = std::env::var(CHAOS_LATENCY_MS_ENV).ok().and_then(|v| v.parse().ok()) {
+            config.max_latency = Duration::from_millis(ms);
+        }
+        if let Some(r) = rate(CHAOS_ERROR_RATE_ENV) {
+            config.error_rate = r.clamp(0.0, 1.0);
+        }
+        if let Some(r) = rate(CHAOS_DROP_RATE_ENV) {
+            config.drop_rate = r.clamp(0.0, 1.0);
+        }
+        if let Ok(targets) = std::env::var(CHAOS_TARGETS_ENV) {
+            config.targets = targets.split(',').filter_map(ChaosTarget::parse).collect();
+        }
+        if let Ok(operations) = std::env::var(CHAOS_OPERATIONS_ENV) {
+            config.operations = parse_operations(&operations);
+        }
+        config
+    }
+
+    /// Override the rates of `operation`.
+    pub fn operation(mut self, operation: &str, rates: OperationRates) -> Self {
+        let clamp = |rate: Option<f64>| rate.map(|r| r.clamp(0.0, 1.0));
+        let rates = OperationRates {
+            latency_rate: clamp(rates.latency_rate),
+            error_rate: clamp(rates.error_rate),
+            drop_rate: clamp(rates.drop_rate),
+        };
+        self.operations.insert(operation.to_string(), rates);
+        self
+    }
+
+    pub fn latency(mut self, rate: f64, max: Duration) -> Self {
+        self.latency_rate = rate.clamp(0.0, 1.0);
+        self.max_latency = max;
+        self
+    }
+
+    pub fn errors(mut self, rate: f64) -> Self {
+        self.error_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn drops(mut self, rate: f64) -> Self {
+        self.drop_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn targets(mut self, targets: &[ChaosTarget]) -> Self {
+        self.targets = targets.to_vec();
+        self
+    }
+
+    pub fn drop_hang(mut self, hang: Duration) -> Self {
+        self.drop_hang = hang;
+        self
+    }
+}
+
+/// Outcome of a call made through `Chaos::call`.
+#[derive(Debug)]
+pub enum ChaosOutcome<E> {
+    /// A fault was injected.
+    Injected(Fault),
+    /// The underlying operation returned an error.
+    OperationError(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ChaosOutcome<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Injected(Fault::Error) => write!(f, "Chaos: injected failure"),
+            Self::Injected(Fault::Drop) => write!(f, "Chaos: response dropped"),
+            Self::OperationError(e) => write!(f, "Operation error: {}", e),
+        }
+    }
+}
+
+impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for ChaosOutcome<E> {}
+
+impl<E: Into<LanaiError>> From<ChaosOutcome<E>> for LanaiError {
+    fn from(outcome: ChaosOutcome<E>) -> Self {
+        match outcome {
+            ChaosOutcome::Injected(Fault::Error) => Self::Unavailable("Dependency unavailable, please retry later".to_string()),
+            ChaosOutcome::Injected(Fault::Drop) => Self::Timeout("Dependency did not respond in time".to_string()),
+            ChaosOutcome::OperationError(e) => e.into(),
+        }
+    }
+}
+
+/// An enabled fault injector. Only obtainable while `LANAI_CHAOS_ENABLED` is set.
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    config: Arc<ChaosConfig>,
+}
+
+static GLOBAL: OnceLock<Option<Chaos>> = OnceLock::new();
+
+impl Chaos {
+    /// `None` unless chaos is enabled for this process.
+    pub fn new(config: ChaosConfig) -> Option<Self> {
+        if !enabled() {
+            return None;
+        }
+        log::warn!("🐒 Chaos enabled: {:?}", config);
+        Some(Self { config: Arc::new(config) })
+    }
+
+    /// The process-wide injector configured from the environment, read once.
+    pub fn global() -> Option<&'static Chaos> {
+        GLOBAL.get_or_init(|| Self::new(ChaosConfig::from_env())).as_ref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn forced(config: ChaosConfig) -> Self {
+        Self { config: Arc::new(config) }
+    }
+
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    /// Sleep for any injected latency, then return the fault to apply, if any.
+    pub async fn inject(&self, target: ChaosTarget, operation: &str) -> Option<Fault> {
+        if !self.config.targets.contains(&target) {
+            return None;
+        }
+        let rates = self.config.operations.get(operation).copied().unwrap_or_default();
+
+        if roll(rates.latency_rate.unwrap_or(self.config.latency_rate)) {
+            let delay = self.config.max_latency.mul_f64(rand::random::<f64>());
+            log::warn!("🐒 Chaos: delaying {} {} by {:?}", target.as_str(), operation, delay);
+            tokio::time::sleep(delay).await;
+        }
+
+        let fault = if roll(rates.error_rate.unwrap_or(self.config.error_rate)) {
+            Some(Fault::Error)
+        } else if roll(rates.drop_rate.unwrap_or(self.config.drop_rate)) {
+            Some(Fault::Drop)
+        } else {
+            None
+        };
+        if let Some(fault) = fault {
+            log::warn!("🐒 Chaos: injecting {:?} into {} {}", fault, target.as_str(), operation);
+        }
+        fault
+    }
+
+    /// Run an outbound call with faults injected. On `Fault::Drop` the call still runs,
+    /// so the remote side sees it while the caller gets an error.
+    ///
+    /// ```ignore
+    /// let response = chaos::outbound("vault", || client.get(url).send()).await?;
+    /// ```
+    pub async fn call<F, Fut, T, E>(&self, operation: &str, f: F) -> Result<T, ChaosOutcome<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        match self.inject(ChaosTarget::Outbound, operation).await {
+            Some(Fault::Error) => Err(ChaosOutcome::Injected(Fault::Error)),
+            Some(Fault::Drop) => {
+                let _ = f().await;
+                Err(ChaosOutcome::Injected(Fault::Drop))
+            }
+            None => f().await.map_err(ChaosOutcome::OperationError),
+        }
+    }
+}
+
+/// Run an outbound call through the global injector; a plain call when chaos is off.
+pub async fn outbound<F, Fut, T, E>(operation: &str, f: F) -> Result<T, ChaosOutcome<E>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    match Chaos::global() {
+        Some(chaos) => chaos.call(operation, f).await,
+        None => f().await.map_err(ChaosOutcome::OperationError),
+    }
+}
+
+/// `{operation}.{latency|error|drop}={rate}` entries; the operation may contain dots.
+fn parse_operations(raw: &str) -> HashMap<String, OperationRates> {
+    let mut operations: HashMap<String, OperationRates> = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let parsed = entry.split_once('=').and_then(|(key, rate)| {
+            let (operation, kind) = key.trim().rsplit_once('.')?;
+            Some((operation, kind, rate.trim().parse::<f64>().ok()?.clamp(0.0, 1.0)))
+        });
+        let Some((operation, kind, rate)) = parsed else {
+            log::warn!("⚠️ Ignoring invalid {} entry '{}'", CHAOS_OPERATIONS_ENV, entry);
+            continue;
+        };
+        let rates = operations.entry(operation.to_string()).or_default();
+        match kind {
+            "latency" => rates.latency_rate = Some(rate),
+            "error" => rates.error_rate = Some(rate),
+            "drop" => rates.drop_rate = Some(rate),
+            _ => log::warn!("⚠️ Ignoring invalid {} entry '{}'", CHAOS_OPERATIONS_ENV, entry),
+        }
+    }
+    operations
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_outbound_faults() {
+        let calls = AtomicUsize::new(0);
+        let call = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>(42)
+        };
+
+        let failing = Chaos::forced(ChaosConfig::new().errors(1.0));
+        assert!(matches!(failing.call("test", call).await, Err(ChaosOutcome::Injected(Fault::Error))));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let dropping = Chaos::forced(ChaosConfig::new().drops(1.0));
+        assert!(matches!(dropping.call("test", call).await, Err(ChaosOutcome::Injected(Fault::Drop))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calm = Chaos::forced(ChaosConfig::new());
+        assert_eq!(calm.call("test", call).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_operation_rates_override_defaults() {
+        let operations = parse_operations("payments.error=1.0, orders.created.drop=0.5, bogus");
+        assert_eq!(operations["payments"].error_rate, Some(1.0));
+        assert_eq!(operations["orders.created"].drop_rate, Some(0.5));
+        assert_eq!(operations.len(), 2);
+
+        let chaos = Chaos::forced(ChaosConfig { operations, ..ChaosConfig::new() });
+        assert_eq!(chaos.inject(ChaosTarget::Outbound, "payments").await, Some(Fault::Error));
+        assert_eq!(chaos.inject(ChaosTarget::Outbound, "inventory").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_untargeted_calls_pass_through() {
+        let chaos = Chaos::forced(ChaosConfig::new().errors(1.0).targets(&[ChaosTarget::Nats]));
+        assert_eq!(chaos.inject(ChaosTarget::Http, "GET /").await, None);
+        assert_eq!(chaos.inject(ChaosTarget::Nats, "orders.created").await, Some(Fault::Error));
+    }
+}
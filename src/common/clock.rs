@@ -0,0 +1,30 @@
+//! Time source
+//!
+//! Components that measure time (circuit breaker, in-memory rate limiter, cache expiry,
+//! JWT expiry in `AuthGuard`) read it from a `Clock`, so tests can substitute a fake one
+//! (`testing::FakeClock`) instead of sleeping.
+
+use chrono::{DateTime, Utc};
+use std::time::Instant;
+
+pub trait Clock: Send + Sync {
+    /// Monotonic time, for measuring durations.
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time.
+    fn utc_now(&self) -> DateTime<Utc>;
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
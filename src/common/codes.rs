@@ -0,0 +1,319 @@
+//! Barcodes and SKUs
+//!
+//! `Ean13` and `Upc` (UPC-A) only hold numbers with a correct GS1 check digit, and `Sku`
+//! only holds well-formed internal codes, so a value that deserialized or came out of the
+//! database can be trusted. All three serialize and bind in sqlx as plain text.
+//!
+//! ```ignore
+//! let scanned = Barcode::parse(&input)?;          // EAN-13 or UPC-A by length
+//! let label = Ean13::in_store(product.number)?;     // 2xxxxxxxxxxxC for own-brand items
+//! let skus = SkuPattern::new("BEV", 5)?;
+//! let sku = skus.format(42)?;                       // BEV-00042
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Code error types
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CodeError {
+    #[error("'{0}' must have {1} digits")]
+    InvalidLength(String, usize),
+
+    #[error("'{0}' must contain only digits")]
+    NotNumeric(String),
+
+    #[error("'{0}' has a wrong check digit")]
+    CheckDigit(String),
+
+    #[error("Invalid SKU '{0}'")]
+    InvalidSku(String),
+
+    #[error("{0} does not fit in the code")]
+    OutOfRange(u64),
+}
+
+/// GS1 mod-10 check digit of the digits before it (EAN-8/13, UPC-A, GTIN-14).
+pub fn gs1_check_digit(body: &str) -> Option<u8> {
+    let mut sum = 0u32;
+    for (i, b) in body.bytes().rev().enumerate() {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        sum += (b - b'0') as u32 * if i % 2 == 0 { 3 } else { 1 };
+    }
+    Some(((10 - sum % 10) % 10) as u8)
+}
+
+fn parse_gs1(input: &str, len: usize) -> Result<String, CodeError> {
+    let code = input.trim();
+    if !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(CodeError::NotNumeric(input.to_string()));
+    }
+    if code.len() != len {
+        return Err(CodeError::InvalidLength(input.to_string(), len));
+    }
+    let (body, check) = code.split_at(len - 1);
+    if gs1_check_digit(body) != Some(check.as_bytes()[0] - b'0') {
+        return Err(CodeError::CheckDigit(input.to_string()));
+    }
+    Ok(code.to_string())
+}
+
+/// `body` plus its check digit; `body` must be `len - 1` digits.
+fn complete_gs1(body: &str, len: usize) -> Result<String, CodeError> {
+    if body.len() != len - 1 {
+        return Err(CodeError::InvalidLength(body.to_string(), len - 1));
+    }
+    let check = gs1_check_digit(body).ok_or_else(|| CodeError::NotNumeric(body.to_string()))?;
+    Ok(format!("{}{}", body, check))
+}
+
+/// Implements `Display`, `FromStr` (via `parse`), serde and sqlx (as text) for a validated
+/// string newtype.
+macro_rules! text_code {
+    ($name:ident) => {
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = CodeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Self::parse(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl sqlx::Type<sqlx::Postgres> for $name {
+            fn type_info() -> sqlx::postgres::PgTypeInfo {
+                <&str as sqlx::Type<sqlx::Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+                <&str as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+            }
+        }
+
+        impl<'q> sqlx::Encode<'q, sqlx::Postgres> for $name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut sqlx::postgres::PgArgumentBuffer,
+            ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+                <&str as sqlx::Encode<sqlx::Postgres>>::encode(self.0.as_str(), buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, sqlx::Postgres> for $name {
+            fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                Ok(Self::parse(<&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?)?)
+            }
+        }
+    };
+}
+
+/// EAN-13 / GTIN-13.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ean13(String);
+
+impl Ean13 {
+    pub fn parse(input: &str) -> Result<Self, CodeError> {
+        parse_gs1(input, 13).map(Self)
+    }
+
+    /// From the first 12 digits, adding the check digit.
+    pub fn from_body(body: &str) -> Result<Self, CodeError> {
+        complete_gs1(body, 13).map(Self)
+    }
+
+    /// Restricted-circulation code (GS1 prefix `20`) for items without a manufacturer
+    /// barcode: `20`, `number` padded to 10 digits, check digit.
+    pub fn in_store(number: u64) -> Result<Self, CodeError> {
+        if number >= 10_000_000_000 {
+            return Err(CodeError::OutOfRange(number));
+        }
+        Self::from_body(&format!("20{:010}", number))
+    }
+
+    /// GS1 prefixes 20-29 are for in-store use (own labels, variable-weight items).
+    pub fn is_in_store(&self) -> bool {
+        self.0.starts_with('2')
+    }
+
+    /// The UPC-A inside an EAN-13 that starts with `0`.
+    pub fn to_upc(&self) -> Option<Upc> {
+        self.0.strip_prefix('0').map(|upc| Upc(upc.to_string()))
+    }
+}
+
+text_code!(Ean13);
+
+/// UPC-A (12 digits).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Upc(String);
+
+impl Upc {
+    pub fn parse(input: &str) -> Result<Self, CodeError> {
+        parse_gs1(input, 12).map(Self)
+    }
+
+    /// From the first 11 digits, adding the check digit.
+    pub fn from_body(body: &str) -> Result<Self, CodeError> {
+        complete_gs1(body, 12).map(Self)
+    }
+
+    /// The same product as an EAN-13 (leading `0`), the form to store and look up by.
+    pub fn to_ean13(&self) -> Ean13 {
+        Ean13(format!("0{}", self.0))
+    }
+}
+
+text_code!(Upc);
+
+/// A scanned retail barcode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Barcode {
+    Ean13(Ean13),
+    Upc(Upc),
+}
+
+impl Barcode {
+    /// EAN-13 or UPC-A, told apart by length.
+    pub fn parse(input: &str) -> Result<Self, CodeError> {
+        match input.trim().len() {
+            12 => Upc::parse(input).map(Self::Upc),
+            _ => Ean13::parse(input).map(Self::Ean13),
+        }
+    }
+
+    /// As EAN-13, so both kinds match the same catalog entry.
+    pub fn to_ean13(&self) -> Ean13 {
+        match self {
+            Self::Ean13(ean) => ean.clone(),
+            Self::Upc(upc) => upc.to_ean13(),
+        }
+    }
+}
+
+/// Internal stock keeping unit: 2 to 32 upper-case letters, digits and single dashes,
+/// e.g. `BEV-COLA-500ML`. Input is trimmed and upper-cased.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sku(String);
+
+impl Sku {
+    pub fn parse(input: &str) -> Result<Self, CodeError> {
+        let sku = input.trim().to_ascii_uppercase();
+        let valid = (2..=32).contains(&sku.len())
+            && sku.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !sku.starts_with('-')
+            && !sku.ends_with('-')
+            && !sku.contains("--");
+        if !valid {
+            return Err(CodeError::InvalidSku(input.to_string()));
+        }
+        Ok(Self(sku))
+    }
+
+    /// Join parts with dashes, dropping anything that is not a letter or digit:
+    /// `["Bev", "Coca Cola", "500ml"]` -> `BEV-COCACOLA-500ML`.
+    pub fn from_parts<S: AsRef<str>>(parts: &[S]) -> Result<Self, CodeError> {
+        let parts: Vec<String> = parts
+            .iter()
+            .map(|part| part.as_ref().chars().filter(char::is_ascii_alphanumeric).collect::<String>())
+            .filter(|part| !part.is_empty())
+            .collect();
+        Self::parse(&parts.join("-"))
+    }
+}
+
+text_code!(Sku);
+
+/// Sequential SKUs `{PREFIX}-{number}` with the number zero-padded to `width`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkuPattern {
+    prefix: String,
+    width: usize,
+}
+
+impl SkuPattern {
+    pub fn new(prefix: &str, width: usize) -> Result<Self, CodeError> {
+        let prefix = Sku::parse(prefix)?.0;
+        let max_width = 31usize.saturating_sub(prefix.len()).max(1);
+        Ok(Self { prefix, width: width.clamp(1, max_width) })
+    }
+
+    pub fn format(&self, number: u64) -> Result<Sku, CodeError> {
+        let sku = format!("{}-{:0width$}", self.prefix, number, width = self.width);
+        if sku.len() > 32 {
+            return Err(CodeError::OutOfRange(number));
+        }
+        Sku::parse(&sku)
+    }
+
+    /// The number of a SKU of this pattern, `None` for SKUs of other patterns.
+    pub fn number_of(&self, sku: &Sku) -> Option<u64> {
+        let digits = sku.as_str().strip_prefix(&self.prefix)?.strip_prefix('-')?;
+        if digits.len() < self.width || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gs1_codes() {
+        assert_eq!(Ean13::parse("4006381333931").unwrap().as_str(), "4006381333931");
+        assert_eq!(Ean13::parse("4006381333932"), Err(CodeError::CheckDigit("4006381333932".to_string())));
+        assert_eq!(Ean13::from_body("400638133393").unwrap().as_str(), "4006381333931");
+        assert!(Ean13::parse("400638133393A").is_err());
+
+        let upc = Upc::parse("036000291452").unwrap();
+        assert_eq!(upc.to_ean13().as_str(), "0036000291452");
+        assert_eq!(Barcode::parse("036000291452").unwrap().to_ean13().to_upc(), Some(upc));
+
+        let own = Ean13::in_store(42).unwrap();
+        assert!(own.is_in_store());
+        assert!(Ean13::parse(own.as_str()).is_ok());
+        assert!(serde_json::from_str::<Ean13>("\"4006381333932\"").is_err());
+    }
+
+    #[test]
+    fn test_skus() {
+        assert_eq!(Sku::parse(" bev-cola-500ml ").unwrap().as_str(), "BEV-COLA-500ML");
+        assert!(Sku::parse("BEV--COLA").is_err());
+        assert_eq!(Sku::from_parts(&["Bev", "Coca Cola", "500ml"]).unwrap().as_str(), "BEV-COCACOLA-500ML");
+
+        let pattern = SkuPattern::new("bev", 5).unwrap();
+        let sku = pattern.format(42).unwrap();
+        assert_eq!(sku.as_str(), "BEV-00042");
+        assert_eq!(pattern.number_of(&sku), Some(42));
+        assert_eq!(pattern.number_of(&Sku::parse("FOO-00042").unwrap()), None);
+    }
+}
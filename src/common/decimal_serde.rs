@@ -1,5 +1,32 @@
-use rust_decimal::Decimal;
-use serde::{Deserialize, Deserializer, de};
+//! Serde helpers for `Decimal`
+//!
+//! Deserializers accept numbers, strings and floats. Serializers write a string by default,
+//! preserving the value's scale (`"12.50"` stays `"12.50"`), so events round-trip exactly.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Line {
+//!     #[serde(with = "decimal_serde")]
+//!     unit_price: Decimal,
+//!     #[serde(with = "decimal_serde::option", default)]
+//!     discount: Option<Decimal>,
+//!     #[serde(with = "decimal_serde::number")]
+//!     weight_kg: Decimal,
+//! }
+//! ```
+//!
+//! For a fixed scale or another rounding, wrap a `DecimalFormat` in a function and use it
+//! with `serialize_with`:
+//!
+//! ```ignore
+//! fn two_places<S: Serializer>(value: &Decimal, s: S) -> Result<S::Ok, S::Error> {
+//!     DecimalFormat::new().scale(2).serialize(value, s)
+//! }
+//! ```
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serializer, de};
 use std::str::FromStr;
 
 /// Robust deserializer for Decimal that handles numbers, strings, and floats
@@ -55,3 +82,154 @@ where
         None => Ok(None),
     }
 }
+
+/// Serialize as a string with the value's own scale (the counterpart of `deserialize`)
+pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    DecimalFormat::new().serialize(value, serializer)
+}
+
+/// How a `Decimal` is written.
+#[derive(Debug, Clone, Copy)]
+pub struct DecimalFormat {
+    scale: Option<u32>,
+    rounding: RoundingStrategy,
+    as_number: bool,
+}
+
+impl Default for DecimalFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecimalFormat {
+    /// String output, scale unchanged.
+    pub const fn new() -> Self {
+        Self { scale: None, rounding: RoundingStrategy::MidpointNearestEven, as_number: false }
+    }
+
+    /// Write exactly `scale` decimal places, rounding (banker's rounding by default) or
+    /// padding with zeros as needed.
+    pub const fn scale(mut self, scale: u32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub const fn rounding(mut self, rounding: RoundingStrategy) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Write a JSON number instead of a string. Precision is limited to what an `f64`
+    /// holds (about 15 significant digits).
+    pub const fn number(mut self) -> Self {
+        self.as_number = true;
+        self
+    }
+
+    pub fn format(&self, value: &Decimal) -> Decimal {
+        match self.scale {
+            Some(scale) => {
+                let mut rounded = value.round_dp_with_strategy(scale, self.rounding);
+                rounded.rescale(scale);
+                rounded
+            }
+            None => *value,
+        }
+    }
+
+    pub fn serialize<S: Serializer>(&self, value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.format(value);
+        if self.as_number {
+            let number = value
+                .to_f64()
+                .ok_or_else(|| serde::ser::Error::custom(format!("Decimal {} is not representable as a number", value)))?;
+            serializer.serialize_f64(number)
+        } else {
+            serializer.collect_str(&value)
+        }
+    }
+}
+
+/// `Option<Decimal>` as a string or `null`: `#[serde(with = "decimal_serde::option", default)]`
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+        super::deserialize_option(deserializer)
+    }
+}
+
+/// `Decimal` as a JSON number: `#[serde(with = "decimal_serde::number")]`
+pub mod number {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        DecimalFormat::new().number().serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        super::deserialize(deserializer)
+    }
+
+    /// `Option<Decimal>` as a JSON number or `null`.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+            super::super::deserialize_option(deserializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Line {
+        #[serde(with = "crate::common::decimal_serde")]
+        price: Decimal,
+        #[serde(with = "crate::common::decimal_serde::option", default)]
+        discount: Option<Decimal>,
+        #[serde(with = "crate::common::decimal_serde::number")]
+        weight: Decimal,
+    }
+
+    #[test]
+    fn test_round_trip_preserves_scale() {
+        let line: Line = serde_json::from_value(json!({"price": "12.50", "discount": null, "weight": 1.5})).unwrap();
+        let value = serde_json::to_value(&line).unwrap();
+        assert_eq!(value, json!({"price": "12.50", "discount": null, "weight": 1.5}));
+        assert_eq!(serde_json::from_value::<Line>(value).unwrap(), line);
+    }
+
+    #[test]
+    fn test_fixed_scale_rounding() {
+        let format = DecimalFormat::new().scale(2);
+        assert_eq!(format.format(&Decimal::from_str("2.345").unwrap()).to_string(), "2.34");
+        assert_eq!(format.format(&Decimal::from(3)).to_string(), "3.00");
+
+        let up = format.rounding(RoundingStrategy::MidpointAwayFromZero);
+        assert_eq!(up.format(&Decimal::from_str("2.345").unwrap()).to_string(), "2.35");
+    }
+}
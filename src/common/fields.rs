@@ -0,0 +1,188 @@
+//! Sparse fieldsets: `?fields=` response projection
+//!
+//! Clients on slow links (POS terminals on mobile data) ask for just the fields they use,
+//! with dotted paths for nested objects. Projection works on the serialized JSON, so
+//! handlers keep returning their usual types:
+//!
+//! ```ignore
+//! // GET /orders?fields=id,total,lines.sku,lines.quantity
+//! async fn list_orders(fields: Fields, ...) -> Result<HttpResponse, LanaiError> {
+//!     let orders: Vec<Order> = load_orders(fields.includes("lines")).await?;
+//!     fields.respond(&orders)
+//! }
+//! ```
+//!
+//! Arrays are projected element by element. Without `fields=` the value is returned as is;
+//! requested fields the value does not have are ignored.
+
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::{ready, Ready};
+
+use crate::error::LanaiError;
+
+/// Query parameter holding the comma-separated field paths
+pub const FIELDS_PARAM: &str = "fields";
+
+const MAX_FIELDS: usize = 100;
+const MAX_DEPTH: usize = 8;
+
+/// Selected fields; a node without children keeps its whole value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct FieldTree(BTreeMap<String, FieldTree>);
+
+impl FieldTree {
+    fn insert(&mut self, path: &str) {
+        let mut node = self;
+        for segment in path.split('.') {
+            node = node.0.entry(segment.to_string()).or_default();
+        }
+    }
+
+    fn apply(&self, value: Value) -> Value {
+        if self.0.is_empty() {
+            return value;
+        }
+        match value {
+            Value::Object(mut object) => Value::Object(
+                self.0
+                    .iter()
+                    .filter_map(|(name, child)| object.remove(name).map(|value| (name.clone(), child.apply(value))))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.apply(item)).collect()),
+            scalar => scalar,
+        }
+    }
+}
+
+/// The `fields=` selection of a request. Extracting it fails with 400 on malformed paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields {
+    tree: Option<FieldTree>,
+}
+
+impl Fields {
+    /// Every field.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Parse a comma-separated list such as `id,total,lines.sku`.
+    pub fn parse(spec: &str) -> Result<Self, LanaiError> {
+        let mut fields = Self::all();
+        fields.extend(spec)?;
+        Ok(fields)
+    }
+
+    fn extend(&mut self, spec: &str) -> Result<(), LanaiError> {
+        let tree = self.tree.get_or_insert_with(FieldTree::default);
+        let paths: Vec<&str> = spec.split(',').map(str::trim).filter(|path| !path.is_empty()).collect();
+        if paths.len() > MAX_FIELDS {
+            return Err(LanaiError::BadRequest(format!("At most {} fields can be requested", MAX_FIELDS)));
+        }
+        for path in paths {
+            let valid_segment = |segment: &str| {
+                !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            };
+            if path.split('.').count() > MAX_DEPTH || !path.split('.').all(valid_segment) {
+                return Err(LanaiError::BadRequest(format!("Invalid field '{}'", path)));
+            }
+            tree.insert(path);
+        }
+        Ok(())
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.tree.as_ref().is_none_or(|tree| tree.0.is_empty())
+    }
+
+    /// Whether `path` (dotted) is part of the response, e.g. to skip loading a relation.
+    pub fn includes(&self, path: &str) -> bool {
+        let Some(mut node) = self.tree.as_ref().filter(|tree| !tree.0.is_empty()) else {
+            return true;
+        };
+        for segment in path.split('.') {
+            if node.0.is_empty() {
+                return true;
+            }
+            match node.0.get(segment) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Serialize `value` keeping only the selected fields.
+    pub fn project<T: Serialize + ?Sized>(&self, value: &T) -> Result<Value, serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        Ok(match &self.tree {
+            Some(tree) => tree.apply(value),
+            None => value,
+        })
+    }
+
+    /// `200 OK` with the projected JSON.
+    pub fn respond<T: Serialize + ?Sized>(&self, value: &T) -> Result<HttpResponse, LanaiError> {
+        let projected = self.project(value).map_err(LanaiError::internal)?;
+        Ok(HttpResponse::Ok().json(projected))
+    }
+}
+
+/// Reads every `fields=` parameter of the query string.
+impl FromRequest for Fields {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let params = match web::Query::<Vec<(String, String)>>::from_query(req.query_string()) {
+            Ok(params) => params.into_inner(),
+            Err(_) => return ready(Err(LanaiError::BadRequest("Invalid query string".to_string()).into())),
+        };
+        let mut fields = Fields::all();
+        for (name, spec) in params {
+            if name == FIELDS_PARAM {
+                if let Err(e) = fields.extend(&spec) {
+                    return ready(Err(e.into()));
+                }
+            }
+        }
+        ready(Ok(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    #[test]
+    fn test_projection_of_nested_fields() {
+        let orders = json!([
+            { "id": 1, "total": "10.00", "notes": "x", "lines": [{ "sku": "A", "quantity": 2, "price": "5.00" }] },
+            { "id": 2, "total": "0.00", "notes": null, "lines": [] }
+        ]);
+        let fields = Fields::parse("id, lines.sku,lines.quantity,missing").unwrap();
+        assert_eq!(
+            fields.project(&orders).unwrap(),
+            json!([{ "id": 1, "lines": [{ "sku": "A", "quantity": 2 }] }, { "id": 2, "lines": [] }])
+        );
+        assert!(fields.includes("lines.sku") && !fields.includes("notes"));
+        assert_eq!(Fields::all().project(&orders).unwrap(), orders);
+        assert!(Fields::parse("id,lines..sku").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_extractor_merges_parameters() {
+        let req = TestRequest::with_uri("/orders?fields=id&page=2&fields=customer.name").to_http_request();
+        let fields = Fields::extract(&req).await.unwrap();
+        assert!(fields.includes("customer.name") && !fields.includes("customer.email"));
+
+        let req = TestRequest::with_uri("/orders").to_http_request();
+        assert!(Fields::extract(&req).await.unwrap().is_all());
+    }
+}
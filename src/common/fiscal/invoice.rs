@@ -0,0 +1,214 @@
+//! Invoice numbers and their sequences
+//!
+//! Tax authorities require invoice numbers without gaps per series (`F001`, `B001`...).
+//! `PostgresInvoiceSequence::next_in` takes the number inside the transaction that stores
+//! the invoice: the series row stays locked until commit and a rollback gives the number
+//! back.
+//!
+//! ```ignore
+//! let number = PostgresInvoiceSequence::next_in(tx.conn(), tenant.org_id, "F001").await?;
+//! Insert::new("invoices", &stamp).value("number", number.to_string())...
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::FiscalError;
+
+/// Table used by `PostgresInvoiceSequence`.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_invoice_sequences (
+    org_id UUID NOT NULL,
+    series TEXT NOT NULL,
+    last_number BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (org_id, series)
+)";
+
+/// Digits the number is zero-padded to (SUNAT's correlativo).
+const NUMBER_WIDTH: usize = 8;
+
+/// `{series}-{number}`, e.g. `F001-00000123`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InvoiceNumber {
+    series: String,
+    number: u64,
+}
+
+impl InvoiceNumber {
+    /// `series` is 1 to 8 letters or digits (upper-cased); `number` starts at 1.
+    pub fn new(series: &str, number: u64) -> Result<Self, FiscalError> {
+        let series = normalize_series(series)?;
+        if number == 0 {
+            return Err(FiscalError::InvalidInvoiceNumber(format!("{}-0", series)));
+        }
+        Ok(Self { series, number })
+    }
+
+    pub fn series(&self) -> &str {
+        &self.series
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+}
+
+fn normalize_series(series: &str) -> Result<String, FiscalError> {
+    let valid = (1..=8).contains(&series.len()) && series.bytes().all(|b| b.is_ascii_alphanumeric());
+    if !valid {
+        return Err(FiscalError::InvalidSeries(series.to_string()));
+    }
+    Ok(series.to_ascii_uppercase())
+}
+
+impl fmt::Display for InvoiceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{:0width$}", self.series, self.number, width = NUMBER_WIDTH)
+    }
+}
+
+impl FromStr for InvoiceNumber {
+    type Err = FiscalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FiscalError::InvalidInvoiceNumber(s.to_string());
+        let (series, number) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let number = number.parse().map_err(|_| invalid())?;
+        Self::new(series, number).map_err(|_| invalid())
+    }
+}
+
+impl Serialize for InvoiceNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InvoiceNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hands out the next number of a tenant's series.
+#[async_trait]
+pub trait InvoiceSequence: Send + Sync {
+    async fn next(&self, org_id: Uuid, series: &str) -> Result<InvoiceNumber, FiscalError>;
+
+    /// The last number handed out, if any.
+    async fn current(&self, org_id: Uuid, series: &str) -> Result<Option<InvoiceNumber>, FiscalError>;
+}
+
+/// In-memory sequences (for tests and development).
+#[derive(Default)]
+pub struct InMemoryInvoiceSequence {
+    last: Mutex<HashMap<(Uuid, String), u64>>,
+}
+
+impl InMemoryInvoiceSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl InvoiceSequence for InMemoryInvoiceSequence {
+    async fn next(&self, org_id: Uuid, series: &str) -> Result<InvoiceNumber, FiscalError> {
+        let series = normalize_series(series)?;
+        let mut last = self.last.lock().await;
+        let number = last.entry((org_id, series.clone())).or_insert(0);
+        *number += 1;
+        InvoiceNumber::new(&series, *number)
+    }
+
+    async fn current(&self, org_id: Uuid, series: &str) -> Result<Option<InvoiceNumber>, FiscalError> {
+        let series = normalize_series(series)?;
+        let last = self.last.lock().await.get(&(org_id, series.clone())).copied();
+        last.map(|number| InvoiceNumber::new(&series, number)).transpose()
+    }
+}
+
+/// Sequences in PostgreSQL. `next` commits on its own, so a failed invoice insert leaves a
+/// gap; use `next_in` on the invoice's transaction where gaps are not allowed.
+pub struct PostgresInvoiceSequence {
+    pool: PgPool,
+}
+
+impl PostgresInvoiceSequence {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), FiscalError> {
+        sqlx::query(SCHEMA).execute(&self.pool).await.map_err(sequence_error)?;
+        Ok(())
+    }
+
+    /// Next number on the caller's connection or transaction.
+    pub async fn next_in(conn: &mut PgConnection, org_id: Uuid, series: &str) -> Result<InvoiceNumber, FiscalError> {
+        let series = normalize_series(series)?;
+        let number: i64 = sqlx::query_scalar(
+            "INSERT INTO lanai_invoice_sequences (org_id, series, last_number) VALUES ($1, $2, 1)
+             ON CONFLICT (org_id, series)
+             DO UPDATE SET last_number = lanai_invoice_sequences.last_number + 1, updated_at = now()
+             RETURNING last_number",
+        )
+        .bind(org_id)
+        .bind(&series)
+        .fetch_one(conn)
+        .await
+        .map_err(sequence_error)?;
+        InvoiceNumber::new(&series, number as u64)
+    }
+}
+
+fn sequence_error(e: sqlx::Error) -> FiscalError {
+    FiscalError::Sequence(e.to_string())
+}
+
+#[async_trait]
+impl InvoiceSequence for PostgresInvoiceSequence {
+    async fn next(&self, org_id: Uuid, series: &str) -> Result<InvoiceNumber, FiscalError> {
+        let mut conn = self.pool.acquire().await.map_err(sequence_error)?;
+        Self::next_in(&mut conn, org_id, series).await
+    }
+
+    async fn current(&self, org_id: Uuid, series: &str) -> Result<Option<InvoiceNumber>, FiscalError> {
+        let series = normalize_series(series)?;
+        let number: Option<i64> =
+            sqlx::query_scalar("SELECT last_number FROM lanai_invoice_sequences WHERE org_id = $1 AND series = $2")
+                .bind(org_id)
+                .bind(&series)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(sequence_error)?;
+        number.map(|number| InvoiceNumber::new(&series, number as u64)).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_numbers_and_in_memory_sequence() {
+        let number: InvoiceNumber = "f001-123".parse().unwrap();
+        assert_eq!(number.to_string(), "F001-00000123");
+        assert_eq!(serde_json::to_value(&number).unwrap(), "F001-00000123");
+        assert!("F001".parse::<InvoiceNumber>().is_err());
+        assert!(InvoiceNumber::new("F-01", 1).is_err());
+
+        let sequence = InMemoryInvoiceSequence::new();
+        let (org_a, org_b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(sequence.next(org_a, "F001").await.unwrap().to_string(), "F001-00000001");
+        assert_eq!(sequence.next(org_a, "f001").await.unwrap().number(), 2);
+        assert_eq!(sequence.next(org_b, "F001").await.unwrap().number(), 1);
+        assert_eq!(sequence.current(org_a, "B001").await.unwrap(), None);
+    }
+}
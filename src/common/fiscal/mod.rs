@@ -0,0 +1,331 @@
+//! LATAM tax IDs and invoice numbering
+//!
+//! `TaxId` validates and formats the business tax identifiers of the countries Lanai
+//! operates in, check digit included:
+//!
+//! | Country   | Kind | Stored        | Formatted       |
+//! |-----------|------|---------------|-----------------|
+//! | Peru      | RUC  | `20100070970` | `20100070970`   |
+//! | Chile     | RUT  | `123456785`   | `12.345.678-5`  |
+//! | Argentina | CUIT | `30500010912` | `30-50001091-2` |
+//! | Colombia  | NIT  | `8001972684`  | `800.197.268-4` |
+//!
+//! Input may contain dots, dashes and spaces; the stored form is digits only (plus `K` for
+//! Chilean RUTs). Serialized as `{"kind": "RUC", "number": "20100070970"}`. For request
+//! bodies that carry the number as a plain string, use the `validate_*` functions with
+//! `#[validate(custom(function = "..."))]`.
+//!
+//! Invoice numbers (`F001-00000123`) and their gapless per-series sequences are in `invoice`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+use crate::error::LanaiError;
+
+pub mod invoice;
+
+pub use invoice::{InMemoryInvoiceSequence, InvoiceNumber, InvoiceSequence, PostgresInvoiceSequence};
+
+/// Fiscal error types
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FiscalError {
+    #[error("Invalid {kind} '{value}': {reason}")]
+    InvalidTaxId { kind: TaxIdKind, value: String, reason: &'static str },
+
+    #[error("Invalid invoice series '{0}'")]
+    InvalidSeries(String),
+
+    #[error("Invalid invoice number '{0}'")]
+    InvalidInvoiceNumber(String),
+
+    #[error("Invoice sequence error: {0}")]
+    Sequence(String),
+}
+
+impl From<FiscalError> for LanaiError {
+    fn from(e: FiscalError) -> Self {
+        match e {
+            FiscalError::Sequence(_) => LanaiError::Internal(e.to_string()),
+            _ => LanaiError::BadRequest(e.to_string()),
+        }
+    }
+}
+
+/// Countries with fiscal support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Country {
+    #[serde(rename = "PE")]
+    Peru,
+    #[serde(rename = "CL")]
+    Chile,
+    #[serde(rename = "AR")]
+    Argentina,
+    #[serde(rename = "CO")]
+    Colombia,
+}
+
+impl Country {
+    /// From an ISO 3166-1 alpha-2 code, case-insensitive.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.to_ascii_uppercase().as_str() {
+            "PE" => Some(Self::Peru),
+            "CL" => Some(Self::Chile),
+            "AR" => Some(Self::Argentina),
+            "CO" => Some(Self::Colombia),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Peru => "PE",
+            Self::Chile => "CL",
+            Self::Argentina => "AR",
+            Self::Colombia => "CO",
+        }
+    }
+
+    /// The business tax ID of the country.
+    pub fn tax_id_kind(&self) -> TaxIdKind {
+        match self {
+            Self::Peru => TaxIdKind::Ruc,
+            Self::Chile => TaxIdKind::Rut,
+            Self::Argentina => TaxIdKind::Cuit,
+            Self::Colombia => TaxIdKind::Nit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TaxIdKind {
+    Ruc,
+    Rut,
+    Cuit,
+    Nit,
+}
+
+impl TaxIdKind {
+    pub fn country(&self) -> Country {
+        match self {
+            Self::Ruc => Country::Peru,
+            Self::Rut => Country::Chile,
+            Self::Cuit => Country::Argentina,
+            Self::Nit => Country::Colombia,
+        }
+    }
+}
+
+impl fmt::Display for TaxIdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ruc => "RUC",
+            Self::Rut => "RUT",
+            Self::Cuit => "CUIT",
+            Self::Nit => "NIT",
+        })
+    }
+}
+
+/// A validated tax ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct TaxId {
+    kind: TaxIdKind,
+    number: String,
+}
+
+impl TaxId {
+    pub fn parse(kind: TaxIdKind, input: &str) -> Result<Self, FiscalError> {
+        let number: String = input
+            .chars()
+            .filter(|c| !matches!(c, '.' | '-' | ' '))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let invalid = |reason| FiscalError::InvalidTaxId { kind, value: input.to_string(), reason };
+
+        let (body, check) = match kind {
+            TaxIdKind::Rut => {
+                if !(2..=9).contains(&number.len()) {
+                    return Err(invalid("must have 2 to 9 characters"));
+                }
+                number.split_at(number.len() - 1)
+            }
+            TaxIdKind::Nit => {
+                if !(2..=16).contains(&number.len()) {
+                    return Err(invalid("must have 2 to 16 digits"));
+                }
+                number.split_at(number.len() - 1)
+            }
+            TaxIdKind::Ruc | TaxIdKind::Cuit => {
+                if number.len() != 11 {
+                    return Err(invalid("must have 11 digits"));
+                }
+                number.split_at(10)
+            }
+        };
+        if !body.bytes().all(|b| b.is_ascii_digit()) || !(check.bytes().all(|b| b.is_ascii_digit()) || check == "K") {
+            return Err(invalid("must contain only digits"));
+        }
+
+        let prefix_ok = match kind {
+            TaxIdKind::Ruc => ["10", "15", "16", "17", "20"].contains(&&body[..2]),
+            TaxIdKind::Cuit => ["20", "23", "24", "27", "30", "33", "34"].contains(&&body[..2]),
+            TaxIdKind::Rut | TaxIdKind::Nit => true,
+        };
+        if !prefix_ok {
+            return Err(invalid("unknown taxpayer type prefix"));
+        }
+        if check_digit(kind, body) != Some(check.chars().next().unwrap_or_default()) {
+            return Err(invalid("wrong check digit"));
+        }
+        Ok(Self { kind, number })
+    }
+
+    /// Parse the business tax ID of `country`.
+    pub fn for_country(country: Country, input: &str) -> Result<Self, FiscalError> {
+        Self::parse(country.tax_id_kind(), input)
+    }
+
+    pub fn kind(&self) -> TaxIdKind {
+        self.kind
+    }
+
+    pub fn country(&self) -> Country {
+        self.kind.country()
+    }
+
+    /// Digits only (and `K`), for storage and comparisons.
+    pub fn number(&self) -> &str {
+        &self.number
+    }
+
+    /// The way the country prints it on invoices.
+    pub fn formatted(&self) -> String {
+        let (body, check) = self.number.split_at(self.number.len() - 1);
+        match self.kind {
+            TaxIdKind::Ruc => self.number.clone(),
+            TaxIdKind::Cuit => format!("{}-{}-{}", &body[..2], &body[2..], check),
+            TaxIdKind::Rut | TaxIdKind::Nit => format!("{}-{}", group_thousands(body), check),
+        }
+    }
+}
+
+impl fmt::Display for TaxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.formatted())
+    }
+}
+
+impl<'de> Deserialize<'de> for TaxId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            kind: TaxIdKind,
+            number: String,
+        }
+        let raw = Raw::deserialize(deserializer)?;
+        Self::parse(raw.kind, &raw.number).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Expected check digit of `body`, or `None` where no valid one exists.
+fn check_digit(kind: TaxIdKind, body: &str) -> Option<char> {
+    let digits: Vec<u32> = body.bytes().map(|b| (b - b'0') as u32).collect();
+    match kind {
+        TaxIdKind::Ruc | TaxIdKind::Cuit => {
+            const WEIGHTS: [u32; 10] = [5, 4, 3, 2, 7, 6, 5, 4, 3, 2];
+            let sum: u32 = digits.iter().zip(WEIGHTS).map(|(d, w)| d * w).sum();
+            match (11 - sum % 11, kind) {
+                (11, TaxIdKind::Ruc) => Some('1'),
+                (10, TaxIdKind::Ruc) | (11, _) => Some('0'),
+                (10, _) => None,
+                (n, _) => char::from_digit(n, 10),
+            }
+        }
+        TaxIdKind::Rut => {
+            let sum: u32 = digits.iter().rev().zip([2, 3, 4, 5, 6, 7].iter().cycle()).map(|(d, w)| d * w).sum();
+            match 11 - sum % 11 {
+                11 => Some('0'),
+                10 => Some('K'),
+                n => char::from_digit(n, 10),
+            }
+        }
+        TaxIdKind::Nit => {
+            const WEIGHTS: [u32; 15] = [3, 7, 13, 17, 19, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71];
+            let sum: u32 = digits.iter().rev().zip(WEIGHTS).map(|(d, w)| d * w).sum();
+            match sum % 11 {
+                n @ (0 | 1) => char::from_digit(n, 10),
+                n => char::from_digit(11 - n, 10),
+            }
+        }
+    }
+}
+
+/// `12345678` -> `12.345.678`
+fn group_thousands(digits: &str) -> String {
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push('.');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn validate(kind: TaxIdKind, value: &str) -> Result<(), validator::ValidationError> {
+    TaxId::parse(kind, value).map(|_| ()).map_err(|e| {
+        let mut error = validator::ValidationError::new("tax_id");
+        error.message = Some(e.to_string().into());
+        error
+    })
+}
+
+pub fn validate_ruc(value: &str) -> Result<(), validator::ValidationError> {
+    validate(TaxIdKind::Ruc, value)
+}
+
+pub fn validate_rut(value: &str) -> Result<(), validator::ValidationError> {
+    validate(TaxIdKind::Rut, value)
+}
+
+pub fn validate_cuit(value: &str) -> Result<(), validator::ValidationError> {
+    validate(TaxIdKind::Cuit, value)
+}
+
+pub fn validate_nit(value: &str) -> Result<(), validator::ValidationError> {
+    validate(TaxIdKind::Nit, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tax_ids() {
+        let ruc = TaxId::parse(TaxIdKind::Ruc, "20100070970").unwrap();
+        assert_eq!(ruc.formatted(), "20100070970");
+        assert!(TaxId::parse(TaxIdKind::Ruc, "20100070971").is_err());
+        assert!(TaxId::parse(TaxIdKind::Ruc, "99100070970").is_err());
+
+        let rut = TaxId::for_country(Country::Chile, "12.345.678-5").unwrap();
+        assert_eq!((rut.number(), rut.formatted().as_str()), ("123456785", "12.345.678-5"));
+        assert!(TaxId::parse(TaxIdKind::Rut, "12345678-K").is_err());
+
+        let cuit = TaxId::parse(TaxIdKind::Cuit, "30500010912").unwrap();
+        assert_eq!(cuit.formatted(), "30-50001091-2");
+
+        let nit = TaxId::parse(TaxIdKind::Nit, "800197268-4").unwrap();
+        assert_eq!(nit.to_string(), "800.197.268-4");
+        assert!(validate_nit("800197268-5").is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let id: TaxId = serde_json::from_value(serde_json::json!({"kind": "RUT", "number": "12.345.678-5"})).unwrap();
+        assert_eq!(serde_json::to_value(&id).unwrap(), serde_json::json!({"kind": "RUT", "number": "123456785"}));
+        assert!(serde_json::from_value::<TaxId>(serde_json::json!({"kind": "RUC", "number": "123"})).is_err());
+        assert_eq!(serde_json::to_value(Country::Peru).unwrap(), "PE");
+    }
+}
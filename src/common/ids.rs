@@ -0,0 +1,231 @@
+//! Strongly-typed IDs
+//!
+//! Newtypes over `Uuid` so an order ID can't be passed where a product ID is expected.
+//! They serialize, display, parse and bind in sqlx exactly like the `Uuid` they wrap, so
+//! switching a field from `Uuid` to `OrderId` does not change the wire or database format.
+//!
+//! Services define their own with `define_id!`:
+//!
+//! ```ignore
+//! lanai_infrastructure::common::define_id!(
+//!     /// Identifies a supplier.
+//!     pub SupplierId
+//! );
+//! ```
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+    pub use sqlx;
+    pub use uuid::{self, Uuid};
+    #[cfg(feature = "contracts")]
+    pub use schemars;
+}
+
+pub use crate::__lanai_define_id as define_id;
+
+/// Define a `Uuid` newtype with serde, sqlx (Postgres), `Display` and `FromStr` impls, and a
+/// JSON Schema with the `contracts` feature.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lanai_define_id {
+    ($(#[$meta:meta])* $vis:vis $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        $vis struct $name($crate::common::ids::__private::Uuid);
+
+        impl $name {
+            /// A new random (v4) ID.
+            pub fn generate() -> Self {
+                Self($crate::common::ids::__private::Uuid::new_v4())
+            }
+
+            pub const fn from_uuid(uuid: $crate::common::ids::__private::Uuid) -> Self {
+                Self(uuid)
+            }
+
+            pub const fn as_uuid(&self) -> &$crate::common::ids::__private::Uuid {
+                &self.0
+            }
+
+            pub const fn into_uuid(self) -> $crate::common::ids::__private::Uuid {
+                self.0
+            }
+        }
+
+        impl ::std::convert::From<$crate::common::ids::__private::Uuid> for $name {
+            fn from(uuid: $crate::common::ids::__private::Uuid) -> Self {
+                Self(uuid)
+            }
+        }
+
+        impl ::std::convert::From<$name> for $crate::common::ids::__private::Uuid {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = $crate::common::ids::__private::uuid::Error;
+
+            fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+                $crate::common::ids::__private::Uuid::parse_str(s).map(Self)
+            }
+        }
+
+        impl $crate::common::ids::__private::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::common::ids::__private::serde::Serializer,
+            {
+                $crate::common::ids::__private::serde::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> $crate::common::ids::__private::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: $crate::common::ids::__private::serde::Deserializer<'de>,
+            {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::serde::Deserialize>::deserialize(
+                    deserializer,
+                )
+                .map(Self)
+            }
+        }
+
+        impl $crate::common::ids::__private::sqlx::Type<$crate::common::ids::__private::sqlx::Postgres> for $name {
+            fn type_info() -> $crate::common::ids::__private::sqlx::postgres::PgTypeInfo {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::sqlx::Type<
+                    $crate::common::ids::__private::sqlx::Postgres,
+                >>::type_info()
+            }
+        }
+
+        impl $crate::common::ids::__private::sqlx::postgres::PgHasArrayType for $name {
+            fn array_type_info() -> $crate::common::ids::__private::sqlx::postgres::PgTypeInfo {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::sqlx::postgres::PgHasArrayType>::array_type_info()
+            }
+        }
+
+        impl<'q> $crate::common::ids::__private::sqlx::Encode<'q, $crate::common::ids::__private::sqlx::Postgres> for $name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut $crate::common::ids::__private::sqlx::postgres::PgArgumentBuffer,
+            ) -> ::std::result::Result<
+                $crate::common::ids::__private::sqlx::encode::IsNull,
+                $crate::common::ids::__private::sqlx::error::BoxDynError,
+            > {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::sqlx::Encode<
+                    'q,
+                    $crate::common::ids::__private::sqlx::Postgres,
+                >>::encode_by_ref(&self.0, buf)
+            }
+        }
+
+        impl<'r> $crate::common::ids::__private::sqlx::Decode<'r, $crate::common::ids::__private::sqlx::Postgres> for $name {
+            fn decode(
+                value: $crate::common::ids::__private::sqlx::postgres::PgValueRef<'r>,
+            ) -> ::std::result::Result<Self, $crate::common::ids::__private::sqlx::error::BoxDynError> {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::sqlx::Decode<
+                    'r,
+                    $crate::common::ids::__private::sqlx::Postgres,
+                >>::decode(value)
+                .map(Self)
+            }
+        }
+
+        $crate::__lanai_id_json_schema!($name);
+    };
+}
+
+/// `JsonSchema` for an ID: the schema of `Uuid`, inlined.
+#[cfg(feature = "contracts")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lanai_id_json_schema {
+    ($name:ident) => {
+        impl $crate::common::ids::__private::schemars::JsonSchema for $name {
+            fn schema_name() -> ::std::borrow::Cow<'static, str> {
+                ::std::borrow::Cow::Borrowed(stringify!($name))
+            }
+
+            fn inline_schema() -> bool {
+                true
+            }
+
+            fn json_schema(
+                generator: &mut $crate::common::ids::__private::schemars::SchemaGenerator,
+            ) -> $crate::common::ids::__private::schemars::Schema {
+                <$crate::common::ids::__private::Uuid as $crate::common::ids::__private::schemars::JsonSchema>::json_schema(
+                    generator,
+                )
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "contracts"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lanai_id_json_schema {
+    ($name:ident) => {};
+}
+
+define_id!(
+    /// Identifies an organization (tenant).
+    pub OrgId
+);
+define_id!(
+    /// Identifies a product.
+    pub ProductId
+);
+define_id!(
+    /// Identifies an order.
+    pub OrderId
+);
+define_id!(
+    /// Identifies a store (point of sale or warehouse) of an organization.
+    pub StoreId
+);
+define_id!(
+    /// Identifies a user.
+    pub UserId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[allow(dead_code)]
+    mod custom {
+        crate::common::ids::define_id!(pub SupplierId);
+    }
+    use custom::SupplierId;
+
+    #[test]
+    fn test_ids_match_uuid_representation() {
+        let uuid = Uuid::new_v4();
+        let order = OrderId::from(uuid);
+
+        assert_eq!(order.to_string(), uuid.to_string());
+        assert_eq!(serde_json::to_value(order).unwrap(), serde_json::to_value(uuid).unwrap());
+        assert_eq!(serde_json::from_value::<OrderId>(serde_json::json!(uuid)).unwrap(), order);
+        assert_eq!(uuid.to_string().parse::<OrderId>().unwrap(), order);
+        assert!("not-an-id".parse::<ProductId>().is_err());
+    }
+
+    #[test]
+    fn test_custom_ids() {
+        let id = SupplierId::generate();
+        assert_eq!(Uuid::from(id), *id.as_uuid());
+        assert_ne!(id, SupplierId::generate());
+    }
+}
@@ -0,0 +1,224 @@
+//! Masking of personal data for logs, audit records and responses to lower-privilege roles
+//!
+//! Maskers keep the formatting of the input (separators, domain) and only reveal what is
+//! needed to recognize a value: the first letter of an email, the last four digits of a
+//! phone or card number, the last three characters of a tax ID.
+//!
+//! ```ignore
+//! #[derive(Serialize)]
+//! struct CustomerSummary {
+//!     name: String,
+//!     email: Masked<String, Email>,
+//!     tax_id: Masked<String, TaxId>,
+//! }
+//!
+//! log::info!("Charging card {}", Masked::<_, Card>::new(&card_number));
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+const MASK: char = '*';
+
+/// `juan.perez@lanai.pe` -> `j*********@lanai.pe`. Input without `@` is fully masked.
+pub fn mask_email(email: &str) -> String {
+    match email.rsplit_once('@') {
+        Some((local, domain)) if !local.is_empty() => {
+            let mut chars = local.chars();
+            let first = chars.next().map(String::from).unwrap_or_default();
+            format!("{}{}@{}", first, MASK.to_string().repeat(chars.count().max(1)), domain)
+        }
+        _ => mask_all(email),
+    }
+}
+
+/// `+51 987 654 321` -> `+** *** **4 321`.
+pub fn mask_phone(phone: &str) -> String {
+    mask_keeping_last(phone, 4, |c| c.is_ascii_digit())
+}
+
+/// `4111 1111 1111 1111` -> `**** **** **** 1111`.
+pub fn mask_card(pan: &str) -> String {
+    mask_keeping_last(pan, 4, |c| c.is_ascii_digit())
+}
+
+/// RUC, RFC, CUIT, NIT...: `20-12345678-9` -> `**-******78-9`.
+pub fn mask_tax_id(tax_id: &str) -> String {
+    mask_keeping_last(tax_id, 3, |c| c.is_ascii_alphanumeric())
+}
+
+/// Every character but whitespace masked.
+pub fn mask_all(value: &str) -> String {
+    value.chars().map(|c| if c.is_whitespace() { c } else { MASK }).collect()
+}
+
+/// Mask characters matching `maskable` except the last `visible` of them; others
+/// (separators, `+`) are kept.
+pub fn mask_keeping_last(value: &str, visible: usize, maskable: impl Fn(char) -> bool) -> String {
+    let total = value.chars().filter(|c| maskable(*c)).count();
+    let hidden = total.saturating_sub(visible);
+    let mut seen = 0;
+    value
+        .chars()
+        .map(|c| {
+            if !maskable(c) {
+                return c;
+            }
+            seen += 1;
+            if seen <= hidden {
+                MASK
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// How a `Masked` value is rendered.
+pub trait MaskStrategy {
+    fn mask(value: &str) -> String;
+}
+
+/// Fully masked.
+pub struct Redact;
+pub struct Email;
+pub struct Phone;
+pub struct Card;
+pub struct TaxId;
+
+impl MaskStrategy for Redact {
+    fn mask(value: &str) -> String {
+        mask_all(value)
+    }
+}
+
+impl MaskStrategy for Email {
+    fn mask(value: &str) -> String {
+        mask_email(value)
+    }
+}
+
+impl MaskStrategy for Phone {
+    fn mask(value: &str) -> String {
+        mask_phone(value)
+    }
+}
+
+impl MaskStrategy for Card {
+    fn mask(value: &str) -> String {
+        mask_card(value)
+    }
+}
+
+impl MaskStrategy for TaxId {
+    fn mask(value: &str) -> String {
+        mask_tax_id(value)
+    }
+}
+
+/// A value that serializes, displays and debug-prints masked with `S`, while the code
+/// holding it still has the plain value. Deserializes from the plain value.
+pub struct Masked<T, S = Redact> {
+    value: T,
+    _strategy: PhantomData<fn() -> S>,
+}
+
+impl<T, S> Masked<T, S> {
+    pub fn new(value: T) -> Self {
+        Self { value, _strategy: PhantomData }
+    }
+
+    /// The unmasked value.
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: AsRef<str>, S: MaskStrategy> Masked<T, S> {
+    pub fn masked(&self) -> String {
+        S::mask(self.value.as_ref())
+    }
+}
+
+impl<T, S> From<T> for Masked<T, S> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Clone, S> Clone for Masked<T, S> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T: PartialEq, S> PartialEq for Masked<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: AsRef<str>, S: MaskStrategy> fmt::Display for Masked<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.masked())
+    }
+}
+
+impl<T: AsRef<str>, S: MaskStrategy> fmt::Debug for Masked<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Masked({:?})", self.masked())
+    }
+}
+
+impl<T: AsRef<str>, S: MaskStrategy> Serialize for Masked<T, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.serialize_str(&self.masked())
+    }
+}
+
+impl<'de, T: Deserialize<'de>, S> Deserialize<'de> for Masked<T, S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maskers() {
+        assert_eq!(mask_email("juan.perez@lanai.pe"), "j*********@lanai.pe");
+        assert_eq!(mask_email("a@lanai.pe"), "a*@lanai.pe");
+        assert_eq!(mask_email("not-an-email"), "************");
+        assert_eq!(mask_phone("+51 987 654 321"), "+** *** **4 321");
+        assert_eq!(mask_card("4111 1111 1111 1111"), "**** **** **** 1111");
+        assert_eq!(mask_card("4111111111111111"), "************1111");
+        assert_eq!(mask_tax_id("20-12345678-9"), "**-******78-9");
+        assert_eq!(mask_tax_id("GODE561231GR8"), "**********GR8");
+        assert_eq!(mask_card("12"), "12");
+    }
+
+    #[test]
+    fn test_masked_serializes_masked() {
+        #[derive(Serialize, Deserialize)]
+        struct Customer {
+            email: Masked<String, Email>,
+            notes: Masked<String>,
+        }
+
+        let customer: Customer =
+            serde_json::from_str(r#"{"email": "ana@lanai.pe", "notes": "vip"}"#).unwrap();
+        assert_eq!(customer.email.expose(), "ana@lanai.pe");
+        assert_eq!(format!("{:?}", customer.email), r#"Masked("a**@lanai.pe")"#);
+
+        let json = serde_json::to_value(&customer).unwrap();
+        assert_eq!(json["email"], "a**@lanai.pe");
+        assert_eq!(json["notes"], "***");
+    }
+}
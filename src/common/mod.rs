@@ -1 +1,19 @@
+pub mod clock;
+pub mod codes;
 pub mod decimal_serde;
+pub mod fields;
+pub mod fiscal;
+pub mod ids;
+pub mod masking;
+pub mod money;
+pub mod time_serde;
+pub mod timestamp;
+
+pub use clock::{Clock, SystemClock};
+pub use codes::{Barcode, CodeError, Ean13, Sku, SkuPattern, Upc};
+pub use fields::Fields;
+pub use fiscal::{Country, FiscalError, InvoiceNumber, TaxId, TaxIdKind};
+pub use ids::{define_id, OrderId, OrgId, ProductId, StoreId, UserId};
+pub use masking::Masked;
+pub use money::{Currency, Money, MoneyError};
+pub use timestamp::Timestamp;
@@ -0,0 +1,352 @@
+//! Currency-aware monetary amounts
+//!
+//! `Money` pairs a `Decimal` amount with an ISO 4217 currency. Arithmetic between two
+//! amounts fails with `MoneyError::CurrencyMismatch` instead of silently mixing currencies,
+//! and every operation is checked for overflow. Rounding defaults to banker's rounding
+//! (half to even) at the currency's minor unit; `allocate`/`split` distribute leftover
+//! cents so the parts always add up to the original amount. `convert_to` converts with
+//! `fx::ExchangeRates`.
+//!
+//! Serialized as `{"amount": "12.50", "currency": "PEN"}`. In PostgreSQL store the amount
+//! as `NUMERIC` and the currency as `CHAR(3)`/`TEXT` (`Currency` implements the sqlx traits).
+//!
+//! ```ignore
+//! let total = Money::new(dec!(100), Currency::PEN).checked_mul(dec!(1.18))?.round();
+//! let installments = total.split(3)?; // 39.34, 39.33, 39.33
+//! ```
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::fx::ExchangeRates;
+
+/// Money error types
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MoneyError {
+    #[error("Currency mismatch: {0} vs {1}")]
+    CurrencyMismatch(Currency, Currency),
+
+    #[error("Invalid currency code '{0}'")]
+    InvalidCurrency(String),
+
+    #[error("Arithmetic overflow")]
+    Overflow,
+
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Invalid allocation: {0}")]
+    InvalidAllocation(String),
+
+    #[error("No exchange rate from {0} to {1}")]
+    MissingRate(Currency, Currency),
+}
+
+/// ISO 4217 alphabetic currency code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const PEN: Currency = Currency(*b"PEN");
+    pub const MXN: Currency = Currency(*b"MXN");
+    pub const COP: Currency = Currency(*b"COP");
+    pub const CLP: Currency = Currency(*b"CLP");
+    pub const BRL: Currency = Currency(*b"BRL");
+    pub const ARS: Currency = Currency(*b"ARS");
+
+    /// Accepts three ASCII letters, case-insensitive.
+    pub fn new(code: &str) -> Result<Self, MoneyError> {
+        match code.as_bytes() {
+            [a, b, c] if code.bytes().all(|ch| ch.is_ascii_alphabetic()) => {
+                Ok(Self([a.to_ascii_uppercase(), b.to_ascii_uppercase(), c.to_ascii_uppercase()]))
+            }
+            _ => Err(MoneyError::InvalidCurrency(code.to_string())),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        // Only ever constructed from ASCII letters.
+        std::str::from_utf8(&self.0).unwrap_or("XXX")
+    }
+
+    /// Decimal places of the currency's minor unit (2 unless ISO 4217 says otherwise).
+    pub fn minor_units(&self) -> u32 {
+        match self.code() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX" | "UYI" | "VND"
+            | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            "CLF" | "UYW" => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Self::new(&code).map_err(serde::de::Error::custom)
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for Currency {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <&str as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <&str as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Postgres> for Currency {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <&str as sqlx::Encode<sqlx::Postgres>>::encode(self.code(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for Currency {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let code = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        // CHAR(3) columns may come back padded.
+        Ok(Self::new(code.trim())?)
+    }
+}
+
+/// An amount in a specific currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    #[serde(deserialize_with = "super::decimal_serde::deserialize")]
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    /// Amount from an integer count of minor units (cents for most currencies).
+    pub fn from_minor(units: i64, currency: Currency) -> Self {
+        Self::new(Decimal::new(units, currency.minor_units()), currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch(self.currency, other.currency))
+        }
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount = self.amount.checked_add(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount = self.amount.checked_sub(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    /// Multiply by a factor (quantity, tax rate, ...). The result is not rounded.
+    pub fn checked_mul(&self, factor: Decimal) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    /// Divide by a factor. The result is not rounded; use `split` to divide into parts.
+    pub fn checked_div(&self, divisor: Decimal) -> Result<Money, MoneyError> {
+        if divisor.is_zero() {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let amount = self.amount.checked_div(divisor).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    /// Sum of `items`, all in `currency`.
+    pub fn sum<'a>(currency: Currency, items: impl IntoIterator<Item = &'a Money>) -> Result<Money, MoneyError> {
+        items.into_iter().try_fold(Self::zero(currency), |total, item| total.checked_add(item))
+    }
+
+    /// Round to the currency's minor unit with banker's rounding (half to even).
+    pub fn round(&self) -> Money {
+        self.round_with(RoundingStrategy::MidpointNearestEven)
+    }
+
+    /// Round to the currency's minor unit with `strategy`.
+    pub fn round_with(&self, strategy: RoundingStrategy) -> Money {
+        Self::new(self.amount.round_dp_with_strategy(self.currency.minor_units(), strategy), self.currency)
+    }
+
+    /// Split into parts proportional to `ratios`, in minor units. Cents left over after the
+    /// proportional split go one each to the first parts, so the parts add up to the
+    /// (rounded) amount exactly.
+    pub fn allocate(&self, ratios: &[u32]) -> Result<Vec<Money>, MoneyError> {
+        let total_ratio: u64 = ratios.iter().map(|r| u64::from(*r)).sum();
+        if ratios.is_empty() || total_ratio == 0 {
+            return Err(MoneyError::InvalidAllocation("ratios must not be empty or all zero".to_string()));
+        }
+
+        let minor_units = self.currency.minor_units();
+        let unit = Decimal::new(1, minor_units);
+        let total_units = (self.round().amount / unit).trunc();
+        let total_ratio = Decimal::from(total_ratio);
+
+        let mut parts = Vec::with_capacity(ratios.len());
+        let mut allocated = Decimal::ZERO;
+        for ratio in ratios {
+            let share = total_units
+                .checked_mul(Decimal::from(*ratio))
+                .and_then(|units| units.checked_div(total_ratio))
+                .ok_or(MoneyError::Overflow)?
+                .trunc();
+            allocated += share;
+            parts.push(share);
+        }
+
+        // The remainder is smaller than the number of parts and has the amount's sign.
+        let remainder = total_units - allocated;
+        let step = if remainder.is_sign_negative() { -Decimal::ONE } else { Decimal::ONE };
+        let leftover = remainder.abs().to_usize().ok_or(MoneyError::Overflow)?;
+        for share in parts.iter_mut().take(leftover) {
+            *share += step;
+        }
+
+        Ok(parts
+            .into_iter()
+            .map(|units| Self::new((units * unit).round_dp(minor_units), self.currency))
+            .collect())
+    }
+
+    /// The amount in `currency` at `rates`, rounded to its minor unit.
+    pub fn convert_to(&self, currency: Currency, rates: &ExchangeRates) -> Result<Money, MoneyError> {
+        let rate = rates.rate(self.currency, currency).ok_or(MoneyError::MissingRate(self.currency, currency))?;
+        let amount = self.amount.checked_mul(rate).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, currency).round())
+    }
+
+    /// Split into `parts` equal parts, spreading leftover cents over the first ones.
+    pub fn split(&self, parts: usize) -> Result<Vec<Money>, MoneyError> {
+        if parts == 0 {
+            return Err(MoneyError::InvalidAllocation("cannot split into zero parts".to_string()));
+        }
+        self.allocate(&vec![1; parts])
+    }
+}
+
+impl PartialOrd for Money {
+    /// Amounts in different currencies are not comparable.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.amount.cmp(&other.amount))
+    }
+}
+
+impl std::ops::Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Self::new(-self.amount, self.currency)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pen(amount: &str) -> Money {
+        Money::new(Decimal::from_str(amount).unwrap(), Currency::PEN)
+    }
+
+    #[test]
+    fn test_arithmetic_rejects_mixed_currencies() {
+        assert_eq!(pen("1.50").checked_add(&pen("2.25")).unwrap(), pen("3.75"));
+        let usd = Money::new(Decimal::ONE, Currency::USD);
+        assert_eq!(pen("1").checked_sub(&usd), Err(MoneyError::CurrencyMismatch(Currency::PEN, Currency::USD)));
+        assert_eq!(pen("1").partial_cmp(&usd), None);
+        assert_eq!(pen("1").checked_div(Decimal::ZERO), Err(MoneyError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_bankers_rounding() {
+        assert_eq!(pen("2.345").round(), pen("2.34"));
+        assert_eq!(pen("2.355").round(), pen("2.36"));
+        assert_eq!(Money::new(Decimal::from_str("1500.5").unwrap(), Currency::CLP).round().amount(), Decimal::from(1500));
+    }
+
+    #[test]
+    fn test_allocation_preserves_total() {
+        assert_eq!(pen("100").split(3).unwrap(), vec![pen("33.34"), pen("33.33"), pen("33.33")]);
+        assert_eq!(pen("-0.05").split(2).unwrap(), vec![pen("-0.03"), pen("-0.02")]);
+        assert_eq!(pen("10").allocate(&[70, 30]).unwrap(), vec![pen("7.00"), pen("3.00")]);
+
+        let parts = pen("0.07").allocate(&[1, 1, 1]).unwrap();
+        assert_eq!(Money::sum(Currency::PEN, &parts).unwrap(), pen("0.07"));
+        assert!(pen("1").allocate(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let money: Money = serde_json::from_str(r#"{"amount": 12.5, "currency": "pen"}"#).unwrap();
+        assert_eq!(money, pen("12.5"));
+        assert_eq!(serde_json::to_value(money).unwrap(), serde_json::json!({"amount": "12.5", "currency": "PEN"}));
+        assert!(Currency::new("SOLES").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Serde helpers for `DateTime<Utc>`
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Shipment {
+//!     #[serde(with = "time_serde::rfc3339")]
+//!     shipped_at: DateTime<Utc>,            // "2024-05-01T14:03:07.250Z"
+//!     #[serde(with = "time_serde::unix_seconds::option", default)]
+//!     delivered_at: Option<DateTime<Utc>>,  // 1714572187 or null
+//!     #[serde(with = "time_serde::unix_millis")]
+//!     scanned_at: DateTime<Utc>,            // 1714572187250
+//! }
+//! ```
+//!
+//! `rfc3339` always writes UTC with millisecond precision and a `Z` suffix, and reads any
+//! RFC 3339 offset.
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// Format used by `rfc3339` and `Timestamp`: `2024-05-01T14:03:07.250Z`
+pub fn format_rfc3339(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Parse any RFC 3339 timestamp into UTC.
+pub fn parse_rfc3339(value: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(value).map(|parsed| parsed.with_timezone(&Utc))
+}
+
+/// RFC 3339 strings: `#[serde(with = "time_serde::rfc3339")]`
+pub mod rfc3339 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_rfc3339(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_rfc3339(&value).map_err(de::Error::custom)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(value) if !value.is_empty() => parse_rfc3339(&value).map(Some).map_err(de::Error::custom),
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
+/// Integer seconds since the Unix epoch: `#[serde(with = "time_serde::unix_seconds")]`
+pub mod unix_seconds {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(value.timestamp())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let seconds = i64::deserialize(deserializer)?;
+        Utc.timestamp_opt(seconds, 0)
+            .single()
+            .ok_or_else(|| de::Error::custom(format!("Timestamp {} is out of range", seconds)))
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+            match Option::<i64>::deserialize(deserializer)? {
+                Some(seconds) => Utc
+                    .timestamp_opt(seconds, 0)
+                    .single()
+                    .map(Some)
+                    .ok_or_else(|| de::Error::custom(format!("Timestamp {} is out of range", seconds))),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+/// Integer milliseconds since the Unix epoch: `#[serde(with = "time_serde::unix_millis")]`
+pub mod unix_millis {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(value.timestamp_millis())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let millis = i64::deserialize(deserializer)?;
+        DateTime::from_timestamp_millis(millis)
+            .ok_or_else(|| de::Error::custom(format!("Timestamp {} is out of range", millis)))
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+            match Option::<i64>::deserialize(deserializer)? {
+                Some(millis) => DateTime::from_timestamp_millis(millis)
+                    .map(Some)
+                    .ok_or_else(|| de::Error::custom(format!("Timestamp {} is out of range", millis))),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Times {
+        #[serde(with = "crate::common::time_serde::rfc3339")]
+        at: DateTime<Utc>,
+        #[serde(with = "crate::common::time_serde::unix_seconds::option", default)]
+        seconds: Option<DateTime<Utc>>,
+        #[serde(with = "crate::common::time_serde::unix_millis")]
+        millis: DateTime<Utc>,
+    }
+
+    #[test]
+    fn test_formats() {
+        let times: Times = serde_json::from_value(json!({
+            "at": "2024-05-01T09:03:07.25-05:00",
+            "seconds": 1714572187,
+            "millis": 1714572187250i64,
+        }))
+        .unwrap();
+
+        assert_eq!(times.at, times.millis);
+        assert_eq!(
+            serde_json::to_value(&times).unwrap(),
+            json!({"at": "2024-05-01T14:03:07.250Z", "seconds": 1714572187, "millis": 1714572187250i64})
+        );
+
+        let missing: Times = serde_json::from_value(json!({"at": "2024-05-01T14:03:07Z", "millis": 0})).unwrap();
+        assert_eq!(missing.seconds, None);
+    }
+}
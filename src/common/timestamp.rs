@@ -0,0 +1,151 @@
+//! UTC timestamp with one wire format
+//!
+//! `Timestamp` always serializes as RFC 3339 in UTC with millisecond precision
+//! (`2024-05-01T14:03:07.250Z`). It deserializes from any RFC 3339 string, and from Unix
+//! seconds or milliseconds for events published by older services. Stored in PostgreSQL as
+//! `TIMESTAMPTZ`.
+
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use super::time_serde::{format_rfc3339, parse_rfc3339};
+
+/// Integers above this are read as milliseconds (seconds this large are past the year 5000).
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp(DateTime<Utc>);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(Utc::now())
+    }
+
+    pub const fn from_datetime(value: DateTime<Utc>) -> Self {
+        Self(value)
+    }
+
+    pub fn from_unix_millis(millis: i64) -> Option<Self> {
+        DateTime::from_timestamp_millis(millis).map(Self)
+    }
+
+    pub const fn as_datetime(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+
+    pub const fn into_datetime(self) -> DateTime<Utc> {
+        self.0
+    }
+
+    pub fn unix_seconds(&self) -> i64 {
+        self.0.timestamp()
+    }
+
+    pub fn unix_millis(&self) -> i64 {
+        self.0.timestamp_millis()
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(value: Timestamp) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_rfc3339(&self.0))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_rfc3339(s).map(Self)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_rfc3339(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(value) => value.parse().map_err(de::Error::custom),
+            serde_json::Value::Number(number) => {
+                let value = number.as_i64().ok_or_else(|| de::Error::custom("Expected an integer Unix timestamp"))?;
+                let millis = if value.abs() >= MILLIS_THRESHOLD { value } else { value.saturating_mul(1000) };
+                Self::from_unix_millis(millis)
+                    .ok_or_else(|| de::Error::custom(format!("Timestamp {} is out of range", value)))
+            }
+            _ => Err(de::Error::custom("Expected an RFC 3339 string or a Unix timestamp")),
+        }
+    }
+}
+
+#[cfg(feature = "contracts")]
+impl schemars::JsonSchema for Timestamp {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Timestamp".into()
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "format": "date-time" })
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for Timestamp {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <DateTime<Utc> as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <DateTime<Utc> as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Postgres> for Timestamp {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <DateTime<Utc> as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for Timestamp {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        <DateTime<Utc> as sqlx::Decode<sqlx::Postgres>>::decode(value).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_accepts_legacy_formats() {
+        let expected: Timestamp = "2024-05-01T14:03:07Z".parse().unwrap();
+        for input in [json!("2024-05-01T09:03:07-05:00"), json!(1714572187), json!(1714572187000i64)] {
+            assert_eq!(serde_json::from_value::<Timestamp>(input).unwrap(), expected);
+        }
+        assert_eq!(serde_json::to_value(expected).unwrap(), json!("2024-05-01T14:03:07.000Z"));
+    }
+}
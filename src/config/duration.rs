@@ -0,0 +1,113 @@
+//! Human-readable `Duration` (de)serializer for config structs
+//!
+//! Accepts `"250ms"`, `"30s"`, `"5m"`, `"2h"`, `"1d"` or a bare number of seconds.
+//!
+//! ```ignore
+//! #[serde(with = "lanai_infrastructure::config::duration")]
+//! pub request_timeout: Duration,
+//! ```
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::time::Duration;
+
+pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format(*duration))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(num) => num
+            .as_f64()
+            .filter(|secs| *secs >= 0.0)
+            .map(Duration::from_secs_f64)
+            .ok_or_else(|| de::Error::custom("Expected a non-negative number of seconds")),
+        serde_json::Value::String(s) => parse(&s).map_err(de::Error::custom),
+        _ => Err(de::Error::custom("Expected a duration such as \"30s\" or a number of seconds")),
+    }
+}
+
+/// The same for `Option<Duration>`, with `null` for `None`:
+///
+/// ```ignore
+/// #[serde(default, with = "lanai_infrastructure::config::duration::option")]
+/// pub slow_threshold: Option<Duration>,
+/// ```
+pub mod option {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => super::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super")] Duration);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(duration)| duration))
+    }
+}
+
+/// Parse `"<number><unit>"` where unit is one of `ms`, `s`, `m`, `h`, `d` (default `s`).
+pub fn parse(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let value: f64 = number.parse().map_err(|_| format!("Invalid duration '{}'", input))?;
+
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        other => return Err(format!("Unknown duration unit '{}' in '{}'", other, input)),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn format(duration: Duration) -> String {
+    if duration.subsec_nanos() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse("10 parsecs").is_err());
+        assert!(parse("soon").is_err());
+    }
+
+    #[test]
+    fn test_format_round_trips() {
+        for duration in [Duration::from_secs(45), Duration::from_millis(1500)] {
+            assert_eq!(parse(&format(duration)).unwrap(), duration);
+        }
+    }
+}
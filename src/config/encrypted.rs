@@ -0,0 +1,140 @@
+//! Encrypted configuration values
+//!
+//! String values of the form `enc:<base64>` are decrypted while loading, so settings that
+//! are sensitive but not worth a secrets manager entry (a partner API key, a webhook
+//! secret) can be committed to config files. They are AES-256-GCM encrypted with a
+//! config key: base64 in `LANAI_CONFIG_KEY`, or wrapped by a KMS `MasterKey` and
+//! unwrapped once at startup:
+//!
+//! ```ignore
+//! // Plain key from the environment (picked up automatically)
+//! let config: BillingConfig = ConfigLoader::new().file("config/billing.yaml").load()?;
+//!
+//! // Key wrapped by a KMS
+//! let key = ConfigKey::unwrap_with(&kms, &std::env::var("LANAI_CONFIG_KEY_WRAPPED")?).await?;
+//! let config: BillingConfig = ConfigLoader::new().file("config/billing.yaml").decrypt_with(key.clone()).load()?;
+//!
+//! // Producing a value for the file
+//! println!("{}", key.encrypt("sk_live_..."));
+//! ```
+
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::Value;
+use std::fmt;
+
+use super::ConfigError;
+use crate::crypto::{self, MasterKey};
+
+pub const CONFIG_KEY_ENV: &str = "LANAI_CONFIG_KEY";
+
+/// Prefix marking an encrypted config value.
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Ciphertexts are bound to their purpose, so they cannot be swapped with other
+/// values sealed under the same key.
+const AAD: &[u8] = b"lanai-config";
+
+/// AES-256-GCM key for config values.
+#[derive(Clone)]
+pub struct ConfigKey {
+    cipher: Aes256Gcm,
+}
+
+impl fmt::Debug for ConfigKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConfigKey(<redacted>)")
+    }
+}
+
+impl ConfigKey {
+    /// `key` must be 32 bytes.
+    pub fn new(key: &[u8]) -> Result<Self, ConfigError> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|_| ConfigError::Key(format!("config key must be 32 bytes, got {}", key.len())))?;
+        Ok(Self { cipher })
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, ConfigError> {
+        let key = BASE64.decode(encoded.trim()).map_err(|e| ConfigError::Key(e.to_string()))?;
+        Self::new(&key)
+    }
+
+    /// The key in `LANAI_CONFIG_KEY`, if set.
+    pub fn from_env() -> Result<Option<Self>, ConfigError> {
+        std::env::var(CONFIG_KEY_ENV).ok().map(|encoded| Self::from_base64(&encoded)).transpose()
+    }
+
+    /// Unwrap a base64 key previously wrapped by `master` (see `wrap_with`).
+    pub async fn unwrap_with(master: &dyn MasterKey, wrapped: &str) -> Result<Self, ConfigError> {
+        let wrapped = BASE64.decode(wrapped.trim()).map_err(|e| ConfigError::Key(e.to_string()))?;
+        let key = master.unwrap(&wrapped, AAD).await.map_err(|e| ConfigError::Key(e.to_string()))?;
+        Self::new(&key)
+    }
+
+    /// A new random key, base64 in the clear and wrapped by `master`, for provisioning.
+    pub async fn wrap_with(master: &dyn MasterKey) -> Result<(String, String), ConfigError> {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let wrapped = master.wrap(&key, AAD).await.map_err(|e| ConfigError::Key(e.to_string()))?;
+        Ok((BASE64.encode(key), BASE64.encode(wrapped)))
+    }
+
+    /// A random key, base64-encoded, for provisioning `LANAI_CONFIG_KEY`.
+    pub fn generate() -> String {
+        BASE64.encode(Aes256Gcm::generate_key(OsRng))
+    }
+
+    /// `enc:<base64>`, ready to paste into a config file.
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let sealed = crypto::seal(&self.cipher, plaintext.as_bytes(), AAD)
+            .expect("AES-GCM encryption of a config value cannot fail");
+        format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(sealed))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, String> {
+        let encoded = value.strip_prefix(ENCRYPTED_PREFIX).ok_or("not an encrypted value")?;
+        let sealed = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+        let plain = crypto::open(&self.cipher, &sealed, AAD).map_err(|e| e.to_string())?;
+        String::from_utf8(plain).map_err(|_| "decrypted value is not UTF-8".to_string())
+    }
+}
+
+/// Decrypt every encrypted string under `value` in place. Without a key, any encrypted
+/// value is an error.
+pub(crate) fn decrypt_values(value: &mut Value, key: Option<&ConfigKey>) -> Result<(), ConfigError> {
+    let mut error = None;
+    visit(value, String::new(), &mut |path, s| {
+        let result = match key {
+            Some(key) => key.decrypt(s),
+            None => Err(format!("encrypted, but {} is not set", CONFIG_KEY_ENV)),
+        };
+        match result {
+            Ok(plain) => *s = plain,
+            Err(reason) => {
+                error.get_or_insert(ConfigError::Decrypt { path, reason });
+            }
+        }
+    });
+    error.map_or(Ok(()), Err)
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn visit(value: &mut Value, path: String, f: &mut impl FnMut(String, &mut String)) {
+    match value {
+        Value::String(s) if s.starts_with(ENCRYPTED_PREFIX) => f(path, s),
+        Value::Object(map) => map.iter_mut().for_each(|(k, v)| visit(v, child(&path, k), f)),
+        Value::Array(items) => {
+            items.iter_mut().enumerate().for_each(|(i, v)| visit(v, format!("{}[{}]", path, i), f))
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,365 @@
+//! Layered, typed configuration
+//!
+//! Builds a service's settings struct from three layers, later ones winning:
+//! 1. `Default` implementation of the struct
+//! 2. A YAML (`.yaml`/`.yml`) or TOML (`.toml`) file
+//! 3. Environment variables `{PREFIX}_{FIELD}`, with `__` separating nested sections
+//!    (`BILLING_DATABASE__MAX_CONNECTIONS=20` sets `database.max_connections`)
+//!
+//! String values written as `enc:<base64>` are decrypted after merging (see `encrypted`).
+//! The merged result is deserialized into the struct and checked with its `Validate`
+//! impl. Errors name the offending field, e.g. `database.max_connections: invalid type`.
+//!
+//! ```ignore
+//! #[derive(Debug, Serialize, Deserialize)]
+//! #[serde(default)]
+//! pub struct BillingConfig {
+//!     pub database: DatabaseSection,
+//!     #[serde(with = "lanai_infrastructure::config::duration")]
+//!     pub invoice_grace: Duration,
+//! }
+//!
+//! let config: BillingConfig = ConfigLoader::new()
+//!     .optional_file("config/billing.yaml")
+//!     .env_prefix("BILLING")
+//!     .load()?;
+//! ```
+
+use log::info;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub mod duration;
+pub mod encrypted;
+pub mod validate;
+
+pub use encrypted::ConfigKey;
+pub use validate::{Validate, Validator};
+
+/// Configuration error types
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {reason}")]
+    Read { path: String, reason: String },
+
+    #[error("Failed to parse config file {path}: {reason}")]
+    Parse { path: String, reason: String },
+
+    #[error("Unsupported config file format: {0} (expected .yaml, .yml or .toml)")]
+    UnsupportedFormat(String),
+
+    #[error("Failed to serialize configuration defaults: {0}")]
+    Defaults(String),
+
+    #[error("Invalid value for '{path}': {reason}")]
+    Deserialize { path: String, reason: String },
+
+    #[error("Invalid config key: {0}")]
+    Key(String),
+
+    #[error("Failed to decrypt '{path}': {reason}")]
+    Decrypt { path: String, reason: String },
+
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+struct FileSource {
+    path: PathBuf,
+    required: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    files: Vec<FileSource>,
+    env_prefix: Option<String>,
+    key: Option<ConfigKey>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge `path`; loading fails if it does not exist.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push(FileSource { path: path.as_ref().to_path_buf(), required: true });
+        self
+    }
+
+    /// Merge `path` if it exists.
+    pub fn optional_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push(FileSource { path: path.as_ref().to_path_buf(), required: false });
+        self
+    }
+
+    /// Read overrides from environment variables starting with `{prefix}_`.
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = Some(prefix.trim_end_matches('_').to_uppercase());
+        self
+    }
+
+    /// Decrypt `enc:` values with `key` instead of the one in `LANAI_CONFIG_KEY`.
+    pub fn decrypt_with(mut self, key: ConfigKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn load<T>(&self) -> Result<T, ConfigError>
+    where
+        T: Serialize + DeserializeOwned + Default + Validate,
+    {
+        self.load_from(std::env::vars())
+    }
+
+    fn load_from<T, I>(&self, env: I) -> Result<T, ConfigError>
+    where
+        T: Serialize + DeserializeOwned + Default + Validate,
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut merged = serde_json::to_value(T::default()).map_err(|e| ConfigError::Defaults(e.to_string()))?;
+
+        for source in &self.files {
+            if let Some(layer) = read_file(source)? {
+                info!("⚙️ Loaded configuration from {}", source.path.display());
+                merge(&mut merged, layer);
+            }
+        }
+
+        if let Some(prefix) = &self.env_prefix {
+            let prefix = format!("{}_", prefix);
+            for (name, raw) in env {
+                if let Some(field) = name.strip_prefix(&prefix) {
+                    let path: Vec<String> = field.to_lowercase().split("__").map(str::to_string).collect();
+                    set_path(&mut merged, &path, &raw);
+                }
+            }
+        }
+
+        let key = match &self.key {
+            Some(key) => Some(key.clone()),
+            None => ConfigKey::from_env()?,
+        };
+        encrypted::decrypt_values(&mut merged, key.as_ref())?;
+
+        let config: T = serde_path_to_error::deserialize(merged).map_err(|e| ConfigError::Deserialize {
+            path: e.path().to_string(),
+            reason: e.into_inner().to_string(),
+        })?;
+
+        let mut validator = Validator::new();
+        config.validate(&mut validator);
+        if !validator.is_valid() {
+            return Err(ConfigError::Invalid(validator.into_errors()));
+        }
+        Ok(config)
+    }
+}
+
+fn read_file(source: &FileSource) -> Result<Option<Value>, ConfigError> {
+    let display = source.path.display().to_string();
+    let contents = match std::fs::read_to_string(&source.path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !source.required => return Ok(None),
+        Err(e) => return Err(ConfigError::Read { path: display, reason: e.to_string() }),
+    };
+
+    let extension = source.path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let value = match extension {
+        "yaml" | "yml" => serde_yaml::from_str::<Value>(&contents).map_err(|e| e.to_string()),
+        "toml" => toml::from_str::<Value>(&contents).map_err(|e| e.to_string()),
+        _ => return Err(ConfigError::UnsupportedFormat(display)),
+    }
+    .map_err(|reason| ConfigError::Parse { path: display, reason })?;
+
+    // An empty YAML file parses as null.
+    Ok(Some(if value.is_null() { Value::Object(Map::new()) } else { value }))
+}
+
+/// Deep-merge `layer` into `base`: objects merge key by key, anything else replaces.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Set an environment override, typed after the value it replaces.
+fn set_path(root: &mut Value, path: &[String], raw: &str) {
+    let mut node = root;
+    for key in &path[..path.len() - 1] {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node = node
+            .as_object_mut()
+            .expect("node is an object")
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    if !node.is_object() {
+        *node = Value::Object(Map::new());
+    }
+    let fields = node.as_object_mut().expect("node is an object");
+    let last = &path[path.len() - 1];
+    let value = coerce(fields.get(last), raw);
+    fields.insert(last.clone(), value);
+}
+
+/// Env vars are strings; keep them strings where the default is one, otherwise parse them
+/// as JSON so numbers, booleans and lists work. Lists also accept `a,b,c`.
+fn coerce(existing: Option<&Value>, raw: &str) -> Value {
+    match existing {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        Some(Value::Array(_)) if !raw.trim_start().starts_with('[') => Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| coerce(None, item))
+                .collect(),
+        ),
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Database {
+        url: String,
+        max_connections: u32,
+    }
+
+    impl Default for Database {
+        fn default() -> Self {
+            Self { url: "postgres://localhost/app".to_string(), max_connections: 10 }
+        }
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct AppConfig {
+        name: String,
+        database: Database,
+        allowed_origins: Vec<String>,
+        #[serde(with = "duration")]
+        timeout: Duration,
+    }
+
+    impl Validate for AppConfig {
+        fn validate(&self, v: &mut Validator) {
+            v.range("database.max_connections", self.database.max_connections, 1..=100);
+            v.url("database.url", &self.database.url, &["postgres", "postgresql"]);
+        }
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", uuid::Uuid::new_v4(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_layers_are_merged_in_order() {
+        let file = write_temp("app.yaml", "name: billing\ndatabase:\n  max_connections: 20\ntimeout: 5s\n");
+        let config: AppConfig = ConfigLoader::new()
+            .file(&file)
+            .env_prefix("APP")
+            .load_from(env(&[
+                ("APP_DATABASE__MAX_CONNECTIONS", "30"),
+                ("APP_NAME", "12345"),
+                ("APP_ALLOWED_ORIGINS", "https://a.example, https://b.example"),
+                ("OTHER_NAME", "ignored"),
+            ]))
+            .unwrap();
+        std::fs::remove_file(file).ok();
+
+        assert_eq!(config.name, "12345");
+        assert_eq!(config.database.url, "postgres://localhost/app");
+        assert_eq!(config.database.max_connections, 30);
+        assert_eq!(config.allowed_origins, vec!["https://a.example", "https://b.example"]);
+        assert_eq!(config.timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_toml_file_and_missing_optional_file() {
+        let file = write_temp("app.toml", "name = \"orders\"\n[database]\nmax_connections = 7\n");
+        let config: AppConfig = ConfigLoader::new()
+            .optional_file("/nonexistent/app.yaml")
+            .file(&file)
+            .load_from(Vec::new())
+            .unwrap();
+        std::fs::remove_file(file).ok();
+
+        assert_eq!(config.name, "orders");
+        assert_eq!(config.database.max_connections, 7);
+        assert!(matches!(
+            ConfigLoader::new().file("/nonexistent/app.yaml").load_from::<AppConfig, _>(Vec::new()),
+            Err(ConfigError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encrypted_values_are_decrypted() {
+        let key = ConfigKey::from_base64(&ConfigKey::generate()).unwrap();
+        let file = write_temp("app.yaml", &format!("name: {}\n", key.encrypt("billing")));
+        let config: AppConfig = ConfigLoader::new()
+            .file(&file)
+            .env_prefix("APP")
+            .decrypt_with(key.clone())
+            .load_from(env(&[("APP_DATABASE__URL", &key.encrypt("postgres://user:pw@db/app"))]))
+            .unwrap();
+        assert_eq!(config.name, "billing");
+        assert_eq!(config.database.url, "postgres://user:pw@db/app");
+
+        let other = ConfigKey::from_base64(&ConfigKey::generate()).unwrap();
+        let result = ConfigLoader::new().file(&file).decrypt_with(other).load_from::<AppConfig, _>(Vec::new());
+        std::fs::remove_file(file).ok();
+        match result {
+            Err(ConfigError::Decrypt { path, .. }) => assert_eq!(path, "name"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let result: Result<AppConfig, _> = ConfigLoader::new()
+            .env_prefix("APP")
+            .load_from(env(&[("APP_DATABASE__MAX_CONNECTIONS", "lots")]));
+        match result {
+            Err(ConfigError::Deserialize { path, .. }) => assert_eq!(path, "database.max_connections"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let result: Result<AppConfig, _> = ConfigLoader::new().env_prefix("APP").load_from(env(&[
+            ("APP_DATABASE__MAX_CONNECTIONS", "0"),
+            ("APP_DATABASE__URL", "mysql://db"),
+        ]));
+        match result {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Validation of loaded configuration
+//!
+//! Implement `Validate` next to your config struct; the loader collects every problem
+//! before failing, so a misconfigured service reports them all at once.
+//!
+//! ```ignore
+//! impl Validate for BillingConfig {
+//!     fn validate(&self, v: &mut Validator) {
+//!         v.non_empty("database.url", &self.database.url);
+//!         v.range("database.max_connections", self.database.max_connections, 1..=200);
+//!         v.check("retry.max_attempts", self.retry.max_attempts > 0, "must be at least 1");
+//!     }
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+pub trait Validate {
+    /// Report problems to `v`. The default accepts everything.
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+/// Collects validation errors as `"<path>: <message>"`.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<String>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `message` for `path` unless `ok`.
+    pub fn check(&mut self, path: &str, ok: bool, message: &str) {
+        if !ok {
+            self.error(path, message);
+        }
+    }
+
+    pub fn error(&mut self, path: &str, message: impl Display) {
+        self.errors.push(format!("{}: {}", path, message));
+    }
+
+    pub fn non_empty(&mut self, path: &str, value: &str) {
+        self.check(path, !value.trim().is_empty(), "must not be empty");
+    }
+
+    pub fn range<T>(&mut self, path: &str, value: T, range: RangeInclusive<T>)
+    where
+        T: PartialOrd + Display,
+    {
+        if !range.contains(&value) {
+            self.error(path, format!("{} is outside {}..={}", value, range.start(), range.end()));
+        }
+    }
+
+    /// Require an absolute URL with one of `schemes` (e.g. `["postgres", "postgresql"]`).
+    pub fn url(&mut self, path: &str, value: &str, schemes: &[&str]) {
+        match value.split_once("://") {
+            Some((scheme, rest)) if schemes.contains(&scheme) && !rest.is_empty() => {}
+            _ => self.error(path, format!("expected a {} URL", schemes.join("/"))),
+        }
+    }
+
+    /// Validate a nested section, prefixing its paths with `prefix`.
+    pub fn nested<T: Validate>(&mut self, prefix: &str, section: &T) {
+        let mut inner = Validator::new();
+        section.validate(&mut inner);
+        self.errors.extend(inner.errors.into_iter().map(|e| format!("{}.{}", prefix, e)));
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_errors(self) -> Vec<String> {
+        self.errors
+    }
+}
@@ -7,9 +7,17 @@ use actix_cors::Cors;
 use actix_web::http::header;
 use log::info;
 
+use crate::env::{EnvVar, VarType};
+
 /// Environment variable name for allowed origins (comma-separated).
 pub const CORS_ALLOWED_ORIGINS_ENV: &str = "CORS_ALLOWED_ORIGINS";
 
+/// Variables read by `create_cors`. Production must list its origins: the fallback only
+/// allows localhost.
+pub const ENV_VARS: &[EnvVar] = &[EnvVar::new(CORS_ALLOWED_ORIGINS_ENV, VarType::List)
+    .description("Origins allowed to make cross-origin requests")
+    .required_in_production()];
+
 /// Default allowed origins for development.
 const DEV_ORIGINS: &[&str] = &[
     "http://localhost:5173",
@@ -67,6 +75,7 @@ pub fn create_cors() -> Cors {
         .expose_headers(vec![
             header::HeaderName::from_static("x-request-id"),
             header::HeaderName::from_static("x-rate-limit-remaining"),
+            header::HeaderName::from_static("x-token-expires-in"),
         ])
         .supports_credentials()
         .max_age(3600);
@@ -0,0 +1,607 @@
+//! Tenant-scoped Envelope Encryption
+//!
+//! Each organization gets its own data-encryption keys (DEKs, AES-256-GCM). DEKs are stored
+//! wrapped by a master key (`MasterKey`: a local key from the environment or a secrets
+//! provider, or a KMS behind the same trait) and cached unwrapped for a few minutes.
+//! Ciphertexts are bound to their tenant, so a value copied into another tenant's row
+//! does not decrypt.
+//!
+//! Rotation never breaks old data: `rotate_data_key` adds a new DEK version that new
+//! encryptions use while older versions keep decrypting, `reencrypt` moves a value to the
+//! active version, and `rewrap` re-wraps a tenant's DEKs after the master key changes.
+//!
+//! ```ignore
+//! let crypto = TenantCrypto::new(Arc::new(LocalMasterKey::from_env()?), Arc::new(PostgresDataKeyStore::new(pool)));
+//!
+//! let tax_id: Encrypted<String> = Encrypted::seal(&crypto, org_id, &input.tax_id).await?;
+//! sqlx::query("UPDATE customers SET tax_id = $1 WHERE id = $2").bind(&tax_id).bind(id).execute(&pool).await?;
+//!
+//! let plain: String = row.tax_id.open(&crypto, org_id).await?;
+//! ```
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::secrets::SecretProvider;
+
+pub mod postgres;
+
+pub use postgres::PostgresDataKeyStore;
+
+pub const MASTER_KEY_ENV: &str = "LANAI_MASTER_KEY";
+pub const MASTER_KEY_ID_ENV: &str = "LANAI_MASTER_KEY_ID";
+
+const NONCE_LEN: usize = 12;
+const FIELD_PREFIX: &str = "enc:v1";
+
+/// Encryption error types
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Master key is not configured: {0}")]
+    NotConfigured(String),
+
+    #[error("No data key version {version} for organization {org_id}")]
+    KeyNotFound { org_id: Uuid, version: u32 },
+
+    #[error("Unknown master key '{0}'")]
+    UnknownMasterKey(String),
+
+    #[error("Encryption failed")]
+    Encrypt,
+
+    /// Wrong tenant, wrong key or tampered ciphertext; deliberately indistinguishable.
+    #[error("Decryption failed")]
+    Decrypt,
+
+    #[error("Malformed encrypted value: {0}")]
+    Malformed(String),
+
+    #[error("Failed to serialize encrypted value: {0}")]
+    Serialization(String),
+
+    #[error("Data key store error: {0}")]
+    Store(String),
+}
+
+/// A key-encryption key. Implement this over a KMS to keep the master key out of process.
+#[async_trait]
+pub trait MasterKey: Send + Sync {
+    /// Stable identifier stored next to every key it wraps.
+    fn id(&self) -> &str;
+
+    async fn wrap(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    async fn unwrap(&self, wrapped: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError>;
+}
+
+/// AES-256-GCM master key held in memory.
+pub struct LocalMasterKey {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl LocalMasterKey {
+    /// `key` must be 32 bytes.
+    pub fn new(id: &str, key: &[u8]) -> Result<Self, CryptoError> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|_| CryptoError::InvalidKey(format!("master key must be 32 bytes, got {}", key.len())))?;
+        Ok(Self { id: id.to_string(), cipher })
+    }
+
+    /// Base64 key from `LANAI_MASTER_KEY`, id from `LANAI_MASTER_KEY_ID` (default `local-1`).
+    pub fn from_env() -> Result<Self, CryptoError> {
+        let encoded = std::env::var(MASTER_KEY_ENV).map_err(|_| CryptoError::NotConfigured(MASTER_KEY_ENV.to_string()))?;
+        let id = std::env::var(MASTER_KEY_ID_ENV).unwrap_or_else(|_| "local-1".to_string());
+        Self::from_base64(&id, &encoded)
+    }
+
+    /// Base64 key stored as secret `name` (e.g. in Vault); the secret version, if any,
+    /// becomes part of the id so rotated master keys are told apart.
+    pub async fn from_secret(provider: &dyn SecretProvider, name: &str) -> Result<Self, CryptoError> {
+        let secret = provider.get(name).await.map_err(|e| CryptoError::NotConfigured(e.to_string()))?;
+        let id = match &secret.version {
+            Some(version) => format!("{}@{}", name, version),
+            None => name.to_string(),
+        };
+        Self::from_base64(&id, secret.value.expose())
+    }
+
+    pub fn from_base64(id: &str, encoded: &str) -> Result<Self, CryptoError> {
+        let key = BASE64.decode(encoded.trim()).map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+        Self::new(id, &key)
+    }
+
+    /// A random key, base64-encoded, for provisioning `LANAI_MASTER_KEY`.
+    pub fn generate() -> String {
+        BASE64.encode(Aes256Gcm::generate_key(OsRng))
+    }
+}
+
+#[async_trait]
+impl MasterKey for LocalMasterKey {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn wrap(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        seal(&self.cipher, plaintext, aad)
+    }
+
+    async fn unwrap(&self, wrapped: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        open(&self.cipher, wrapped, aad)
+    }
+}
+
+/// `nonce || ciphertext`.
+pub(crate) fn seal(cipher: &Aes256Gcm, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg, aad }).map_err(|_| CryptoError::Encrypt)?;
+    let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub(crate) fn open(cipher: &Aes256Gcm, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(CryptoError::Decrypt);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| CryptoError::Decrypt)
+}
+
+/// A tenant's DEK as stored: wrapped by the master key `master_key_id`.
+#[derive(Debug, Clone)]
+pub struct WrappedKey {
+    pub org_id: Uuid,
+    pub version: u32,
+    pub master_key_id: String,
+    pub wrapped: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WrappedKey {
+    /// Binds the wrapped key to its tenant and version.
+    fn aad(org_id: Uuid, version: u32) -> Vec<u8> {
+        format!("lanai:dek:{}:{}", org_id, version).into_bytes()
+    }
+}
+
+#[async_trait]
+pub trait DataKeyStore: Send + Sync {
+    /// The highest version for `org_id`.
+    async fn active(&self, org_id: Uuid) -> Result<Option<WrappedKey>, CryptoError>;
+
+    async fn get(&self, org_id: Uuid, version: u32) -> Result<Option<WrappedKey>, CryptoError>;
+
+    async fn list(&self, org_id: Uuid) -> Result<Vec<WrappedKey>, CryptoError>;
+
+    /// Store a new version; returns false if that version already exists (a concurrent rotation won).
+    async fn insert(&self, key: &WrappedKey) -> Result<bool, CryptoError>;
+
+    /// Replace the wrapping of an existing version.
+    async fn update(&self, key: &WrappedKey) -> Result<(), CryptoError>;
+}
+
+/// Process-local key store for tests.
+#[derive(Default)]
+pub struct InMemoryDataKeyStore {
+    keys: Mutex<HashMap<Uuid, Vec<WrappedKey>>>,
+}
+
+impl InMemoryDataKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DataKeyStore for InMemoryDataKeyStore {
+    async fn active(&self, org_id: Uuid) -> Result<Option<WrappedKey>, CryptoError> {
+        let keys = self.keys.lock().await;
+        Ok(keys.get(&org_id).and_then(|versions| versions.iter().max_by_key(|k| k.version)).cloned())
+    }
+
+    async fn get(&self, org_id: Uuid, version: u32) -> Result<Option<WrappedKey>, CryptoError> {
+        let keys = self.keys.lock().await;
+        Ok(keys.get(&org_id).and_then(|versions| versions.iter().find(|k| k.version == version)).cloned())
+    }
+
+    async fn list(&self, org_id: Uuid) -> Result<Vec<WrappedKey>, CryptoError> {
+        Ok(self.keys.lock().await.get(&org_id).cloned().unwrap_or_default())
+    }
+
+    async fn insert(&self, key: &WrappedKey) -> Result<bool, CryptoError> {
+        let mut keys = self.keys.lock().await;
+        let versions = keys.entry(key.org_id).or_default();
+        if versions.iter().any(|k| k.version == key.version) {
+            return Ok(false);
+        }
+        versions.push(key.clone());
+        Ok(true)
+    }
+
+    async fn update(&self, key: &WrappedKey) -> Result<(), CryptoError> {
+        let mut keys = self.keys.lock().await;
+        match keys.get_mut(&key.org_id).and_then(|versions| versions.iter_mut().find(|k| k.version == key.version)) {
+            Some(existing) => {
+                *existing = key.clone();
+                Ok(())
+            }
+            None => Err(CryptoError::KeyNotFound { org_id: key.org_id, version: key.version }),
+        }
+    }
+}
+
+/// Ciphertext of one field: `enc:v1:{dek_version}:{base64(nonce || ciphertext)}`.
+/// Stored as TEXT and serialized as that string.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptedField {
+    pub key_version: u32,
+    sealed: Vec<u8>,
+}
+
+impl fmt::Display for EncryptedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", FIELD_PREFIX, self.key_version, BASE64.encode(&self.sealed))
+    }
+}
+
+impl fmt::Debug for EncryptedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EncryptedField(v{}, {} bytes)", self.key_version, self.sealed.len())
+    }
+}
+
+impl FromStr for EncryptedField {
+    type Err = CryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix(FIELD_PREFIX)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or_else(|| CryptoError::Malformed("missing 'enc:v1' prefix".to_string()))?;
+        let (version, payload) =
+            rest.split_once(':').ok_or_else(|| CryptoError::Malformed("missing key version".to_string()))?;
+        let key_version = version.parse().map_err(|_| CryptoError::Malformed(format!("bad key version '{}'", version)))?;
+        let sealed = BASE64.decode(payload).map_err(|e| CryptoError::Malformed(e.to_string()))?;
+        Ok(Self { key_version, sealed })
+    }
+}
+
+impl Serialize for EncryptedField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptedField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for EncryptedField {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for EncryptedField {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for EncryptedField {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+/// A typed encrypted column: `T` as JSON, encrypted with the tenant's DEK. Serializes and
+/// stores exactly like `EncryptedField`, so plaintext never leaves through serde or sqlx.
+pub struct Encrypted<T> {
+    field: EncryptedField,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Encrypted<T> {
+    pub async fn seal(crypto: &TenantCrypto, org_id: Uuid, value: &T) -> Result<Self, CryptoError> {
+        let plaintext = serde_json::to_vec(value).map_err(|e| CryptoError::Serialization(e.to_string()))?;
+        Ok(Self::from_field(crypto.encrypt_field(org_id, &plaintext).await?))
+    }
+
+    pub async fn open(&self, crypto: &TenantCrypto, org_id: Uuid) -> Result<T, CryptoError> {
+        let plaintext = crypto.decrypt_field(org_id, &self.field).await?;
+        serde_json::from_slice(&plaintext).map_err(|e| CryptoError::Serialization(e.to_string()))
+    }
+}
+
+impl<T> Encrypted<T> {
+    pub fn from_field(field: EncryptedField) -> Self {
+        Self { field, _marker: PhantomData }
+    }
+
+    pub fn field(&self) -> &EncryptedField {
+        &self.field
+    }
+}
+
+impl<T> Clone for Encrypted<T> {
+    fn clone(&self) -> Self {
+        Self::from_field(self.field.clone())
+    }
+}
+
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.field, f)
+    }
+}
+
+impl<T> Serialize for Encrypted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.field.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        EncryptedField::deserialize(deserializer).map(Self::from_field)
+    }
+}
+
+impl<T> sqlx::Type<sqlx::Postgres> for Encrypted<T> {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <EncryptedField as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <EncryptedField as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<T> sqlx::Encode<'_, sqlx::Postgres> for Encrypted<T> {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <EncryptedField as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.field, buf)
+    }
+}
+
+impl<'r, T> sqlx::Decode<'r, sqlx::Postgres> for Encrypted<T> {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        <EncryptedField as sqlx::Decode<sqlx::Postgres>>::decode(value).map(Self::from_field)
+    }
+}
+
+struct Inner {
+    master: Arc<dyn MasterKey>,
+    previous: HashMap<String, Arc<dyn MasterKey>>,
+    store: Arc<dyn DataKeyStore>,
+    cache: Cache<(Uuid, u32), Arc<Aes256Gcm>>,
+}
+
+/// Encrypts and decrypts tenant data. Clones share the key cache.
+#[derive(Clone)]
+pub struct TenantCrypto {
+    inner: Arc<Inner>,
+}
+
+impl TenantCrypto {
+    /// Unwrapped DEKs are cached for 5 minutes.
+    pub fn new(master: Arc<dyn MasterKey>, store: Arc<dyn DataKeyStore>) -> Self {
+        Self::build(master, HashMap::new(), store, Duration::from_secs(300))
+    }
+
+    fn build(
+        master: Arc<dyn MasterKey>,
+        previous: HashMap<String, Arc<dyn MasterKey>>,
+        store: Arc<dyn DataKeyStore>,
+        cache_ttl: Duration,
+    ) -> Self {
+        let cache = Cache::builder().max_capacity(10_000).time_to_live(cache_ttl).build();
+        Self { inner: Arc::new(Inner { master, previous, store, cache }) }
+    }
+
+    /// Also unwrap DEKs wrapped by `key`, a master key being rotated out. Call before cloning.
+    pub fn with_previous_master(self, key: Arc<dyn MasterKey>) -> Self {
+        let inner = Arc::try_unwrap(self.inner).unwrap_or_else(|_| panic!("add master keys before cloning TenantCrypto"));
+        let mut previous = inner.previous;
+        previous.insert(key.id().to_string(), key);
+        Self::build(inner.master, previous, inner.store, Duration::from_secs(300))
+    }
+
+    pub async fn encrypt_field(&self, org_id: Uuid, plaintext: &[u8]) -> Result<EncryptedField, CryptoError> {
+        let (key_version, cipher) = self.active_key(org_id).await?;
+        let sealed = seal(&cipher, plaintext, &field_aad(org_id, key_version))?;
+        Ok(EncryptedField { key_version, sealed })
+    }
+
+    pub async fn decrypt_field(&self, org_id: Uuid, field: &EncryptedField) -> Result<Vec<u8>, CryptoError> {
+        let cipher = self.key(org_id, field.key_version).await?;
+        open(&cipher, &field.sealed, &field_aad(org_id, field.key_version))
+    }
+
+    pub async fn encrypt_str(&self, org_id: Uuid, plaintext: &str) -> Result<String, CryptoError> {
+        Ok(self.encrypt_field(org_id, plaintext.as_bytes()).await?.to_string())
+    }
+
+    pub async fn decrypt_str(&self, org_id: Uuid, encrypted: &str) -> Result<String, CryptoError> {
+        let plaintext = self.decrypt_field(org_id, &encrypted.parse()?).await?;
+        String::from_utf8(plaintext).map_err(|_| CryptoError::Decrypt)
+    }
+
+    /// Create a new DEK version for `org_id`; new encryptions use it. Returns the version.
+    pub async fn rotate_data_key(&self, org_id: Uuid) -> Result<u32, CryptoError> {
+        let next = self.inner.store.active(org_id).await?.map_or(1, |key| key.version + 1);
+        self.create_key(org_id, next).await?;
+        log::info!("🔑 Rotated data key for org {} to version {}", org_id, next);
+        self.active_key(org_id).await.map(|(version, _)| version)
+    }
+
+    /// Re-encrypt `field` with the active DEK, if it used an older one.
+    pub async fn reencrypt(&self, org_id: Uuid, field: &EncryptedField) -> Result<EncryptedField, CryptoError> {
+        let (active, _) = self.active_key(org_id).await?;
+        if field.key_version == active {
+            return Ok(field.clone());
+        }
+        let plaintext = self.decrypt_field(org_id, field).await?;
+        self.encrypt_field(org_id, &plaintext).await
+    }
+
+    /// Re-wrap every DEK of `org_id` under the current master key. Returns how many changed.
+    pub async fn rewrap(&self, org_id: Uuid) -> Result<usize, CryptoError> {
+        let mut rewrapped = 0;
+        for mut key in self.inner.store.list(org_id).await? {
+            if key.master_key_id == self.inner.master.id() {
+                continue;
+            }
+            let aad = WrappedKey::aad(org_id, key.version);
+            let dek = self.master_for(&key.master_key_id)?.unwrap(&key.wrapped, &aad).await?;
+            key.wrapped = self.inner.master.wrap(&dek, &aad).await?;
+            key.master_key_id = self.inner.master.id().to_string();
+            self.inner.store.update(&key).await?;
+            rewrapped += 1;
+        }
+        Ok(rewrapped)
+    }
+
+    async fn active_key(&self, org_id: Uuid) -> Result<(u32, Arc<Aes256Gcm>), CryptoError> {
+        let key = match self.inner.store.active(org_id).await? {
+            Some(key) => key,
+            None => {
+                self.create_key(org_id, 1).await?;
+                self.inner.store.active(org_id).await?.ok_or(CryptoError::KeyNotFound { org_id, version: 1 })?
+            }
+        };
+        let version = key.version;
+        Ok((version, self.cached(key).await?))
+    }
+
+    async fn key(&self, org_id: Uuid, version: u32) -> Result<Arc<Aes256Gcm>, CryptoError> {
+        if let Some(cipher) = self.inner.cache.get(&(org_id, version)).await {
+            return Ok(cipher);
+        }
+        let key = self.inner.store.get(org_id, version).await?.ok_or(CryptoError::KeyNotFound { org_id, version })?;
+        self.cached(key).await
+    }
+
+    async fn cached(&self, key: WrappedKey) -> Result<Arc<Aes256Gcm>, CryptoError> {
+        let cache_key = (key.org_id, key.version);
+        if let Some(cipher) = self.inner.cache.get(&cache_key).await {
+            return Ok(cipher);
+        }
+        let dek = self
+            .master_for(&key.master_key_id)?
+            .unwrap(&key.wrapped, &WrappedKey::aad(key.org_id, key.version))
+            .await?;
+        let cipher = Arc::new(Aes256Gcm::new_from_slice(&dek).map_err(|_| CryptoError::InvalidKey("data key".to_string()))?);
+        self.inner.cache.insert(cache_key, cipher.clone()).await;
+        Ok(cipher)
+    }
+
+    /// Generate and store DEK `version`; losing a race to another replica is fine.
+    async fn create_key(&self, org_id: Uuid, version: u32) -> Result<(), CryptoError> {
+        let dek = Aes256Gcm::generate_key(OsRng);
+        let key = WrappedKey {
+            org_id,
+            version,
+            master_key_id: self.inner.master.id().to_string(),
+            wrapped: self.inner.master.wrap(&dek, &WrappedKey::aad(org_id, version)).await?,
+            created_at: Utc::now(),
+        };
+        self.inner.store.insert(&key).await?;
+        Ok(())
+    }
+
+    fn master_for(&self, id: &str) -> Result<&Arc<dyn MasterKey>, CryptoError> {
+        if id == self.inner.master.id() {
+            return Ok(&self.inner.master);
+        }
+        self.inner.previous.get(id).ok_or_else(|| CryptoError::UnknownMasterKey(id.to_string()))
+    }
+}
+
+fn field_aad(org_id: Uuid, version: u32) -> Vec<u8> {
+    format!("lanai:field:{}:{}", org_id, version).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(id: &str) -> Arc<dyn MasterKey> {
+        Arc::new(LocalMasterKey::from_base64(id, &LocalMasterKey::generate()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_is_tenant_bound() {
+        let crypto = TenantCrypto::new(master("m1"), Arc::new(InMemoryDataKeyStore::new()));
+        let (org, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let encrypted = crypto.encrypt_str(org, "20123456789").await.unwrap();
+        assert!(encrypted.starts_with("enc:v1:1:"));
+        assert_eq!(crypto.decrypt_str(org, &encrypted).await.unwrap(), "20123456789");
+        // The other tenant has a DEK of the same version, but the value stays bound to `org`
+        crypto.encrypt_str(other, "30712345678").await.unwrap();
+        assert!(matches!(crypto.decrypt_str(other, &encrypted).await, Err(CryptoError::Decrypt)));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_old_versions_readable() {
+        let store: Arc<dyn DataKeyStore> = Arc::new(InMemoryDataKeyStore::new());
+        let org = Uuid::new_v4();
+        let old_master = master("m1");
+        let crypto = TenantCrypto::new(old_master.clone(), store.clone());
+
+        let sealed: Encrypted<String> = Encrypted::seal(&crypto, org, &"secret".to_string()).await.unwrap();
+        assert_eq!(crypto.rotate_data_key(org).await.unwrap(), 2);
+        let moved = crypto.reencrypt(org, sealed.field()).await.unwrap();
+        assert_eq!(moved.key_version, 2);
+        assert_eq!(sealed.open(&crypto, org).await.unwrap(), "secret");
+
+        // Master key rotation: both DEK versions are re-wrapped and stay readable.
+        let rotated = TenantCrypto::new(master("m2"), store).with_previous_master(old_master);
+        assert_eq!(rotated.rewrap(org).await.unwrap(), 2);
+        assert_eq!(sealed.open(&rotated, org).await.unwrap(), "secret");
+        assert_eq!(rotated.decrypt_field(org, &moved).await.unwrap(), b"\"secret\"");
+    }
+
+    #[test]
+    fn test_field_format() {
+        let field: EncryptedField = "enc:v1:3:AAAA".parse().unwrap();
+        assert_eq!(field.key_version, 3);
+        assert_eq!(field.to_string(), "enc:v1:3:AAAA");
+        assert!("plain".parse::<EncryptedField>().is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! PostgreSQL data key store
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{CryptoError, DataKeyStore, WrappedKey};
+
+/// Table used by `PostgresDataKeyStore`; include it in a service migration or call
+/// `ensure_schema` at startup.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_data_keys (
+    org_id UUID NOT NULL,
+    version INTEGER NOT NULL,
+    master_key_id TEXT NOT NULL,
+    wrapped BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (org_id, version)
+)";
+
+pub struct PostgresDataKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresDataKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), CryptoError> {
+        sqlx::raw_sql(SCHEMA).execute(&self.pool).await.map_err(store_error)?;
+        Ok(())
+    }
+}
+
+fn store_error(e: sqlx::Error) -> CryptoError {
+    CryptoError::Store(e.to_string())
+}
+
+type Row = (Uuid, i32, String, Vec<u8>, DateTime<Utc>);
+
+fn from_row((org_id, version, master_key_id, wrapped, created_at): Row) -> WrappedKey {
+    WrappedKey { org_id, version: version as u32, master_key_id, wrapped, created_at }
+}
+
+const COLUMNS: &str = "org_id, version, master_key_id, wrapped, created_at";
+
+#[async_trait]
+impl DataKeyStore for PostgresDataKeyStore {
+    async fn active(&self, org_id: Uuid) -> Result<Option<WrappedKey>, CryptoError> {
+        let row: Option<Row> = sqlx::query_as(&format!(
+            "SELECT {} FROM lanai_data_keys WHERE org_id = $1 ORDER BY version DESC LIMIT 1",
+            COLUMNS
+        ))
+        .bind(org_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(store_error)?;
+        Ok(row.map(from_row))
+    }
+
+    async fn get(&self, org_id: Uuid, version: u32) -> Result<Option<WrappedKey>, CryptoError> {
+        let row: Option<Row> =
+            sqlx::query_as(&format!("SELECT {} FROM lanai_data_keys WHERE org_id = $1 AND version = $2", COLUMNS))
+                .bind(org_id)
+                .bind(version as i32)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(store_error)?;
+        Ok(row.map(from_row))
+    }
+
+    async fn list(&self, org_id: Uuid) -> Result<Vec<WrappedKey>, CryptoError> {
+        let rows: Vec<Row> =
+            sqlx::query_as(&format!("SELECT {} FROM lanai_data_keys WHERE org_id = $1 ORDER BY version", COLUMNS))
+                .bind(org_id)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(store_error)?;
+        Ok(rows.into_iter().map(from_row).collect())
+    }
+
+    async fn insert(&self, key: &WrappedKey) -> Result<bool, CryptoError> {
+        let result = sqlx::query(
+            "INSERT INTO lanai_data_keys (org_id, version, master_key_id, wrapped, created_at)
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+        )
+        .bind(key.org_id)
+        .bind(key.version as i32)
+        .bind(&key.master_key_id)
+        .bind(&key.wrapped)
+        .bind(key.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(store_error)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn update(&self, key: &WrappedKey) -> Result<(), CryptoError> {
+        let result = sqlx::query(
+            "UPDATE lanai_data_keys SET master_key_id = $3, wrapped = $4 WHERE org_id = $1 AND version = $2",
+        )
+        .bind(key.org_id)
+        .bind(key.version as i32)
+        .bind(&key.master_key_id)
+        .bind(&key.wrapped)
+        .execute(&self.pool)
+        .await
+        .map_err(store_error)?;
+        match result.rows_affected() {
+            0 => Err(CryptoError::KeyNotFound { org_id: key.org_id, version: key.version }),
+            _ => Ok(()),
+        }
+    }
+}
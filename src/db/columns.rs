@@ -0,0 +1,282 @@
+//! Standard Lanai table columns: soft deletes and audit stamps
+//!
+//! Tenant tables share these columns:
+//!
+//! ```sql
+//! id          UUID PRIMARY KEY,
+//! org_id      UUID NOT NULL,
+//! created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+//! updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+//! deleted_at  TIMESTAMPTZ,
+//! created_by  TEXT
+//! ```
+//!
+//! `select` hides soft-deleted rows unless asked otherwise, `Insert` fills `org_id` and
+//! `created_by` from the request's `Stamp` (`TenantContext` and `Claims`), and `Update`
+//! bumps `updated_at`. Timestamps come from the database clock:
+//!
+//! ```ignore
+//! async fn create_product(mut tx: TenantTx, stamp: Stamp, body: ValidatedJson<NewProduct>) -> LanaiResult<HttpResponse> {
+//!     let product: Product = Insert::new("products", &stamp)
+//!         .value("id", Uuid::new_v4())
+//!         .value("name", body.name.clone())
+//!         .fetch(tx.conn())
+//!         .await?;
+//!     ...
+//! }
+//!
+//! let mut query = columns::select("products", "*", Visibility::Active);
+//! query.push(" AND category = ").push_bind(category);
+//! let products: Vec<Product> = query.build_query_as().fetch_all(tx.conn()).await?;
+//!
+//! columns::soft_delete(tx.conn(), "products", id).await?;
+//! ```
+//!
+//! Table and column names are written into the SQL as is, hence `&'static str`.
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use sqlx::postgres::{PgArguments, PgRow};
+use sqlx::{Arguments, Encode, FromRow, PgConnection, Postgres, QueryBuilder, Type};
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::DbError;
+use crate::middleware::auth_guard::Claims;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Which rows `select` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    /// Rows that are not soft-deleted.
+    #[default]
+    Active,
+    /// Every row, e.g. for admin views.
+    WithDeleted,
+    /// The trash.
+    OnlyDeleted,
+}
+
+/// Who is writing: the tenant and user of the request (either may be missing, e.g. for
+/// anonymous requests or background jobs).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stamp {
+    pub org_id: Option<Uuid>,
+    pub user: Option<String>,
+}
+
+impl Stamp {
+    /// For jobs and consumers, e.g. `Stamp::system(org_id, "lanai-inventory-sync")`.
+    pub fn system(org_id: Uuid, actor: &str) -> Self {
+        Self { org_id: Some(org_id), user: Some(actor.to_string()) }
+    }
+
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let extensions = req.extensions();
+        Self {
+            org_id: extensions.get::<TenantContext>().map(|tenant| tenant.org_id),
+            user: extensions.get::<Claims>().map(|claims| claims.sub.clone()),
+        }
+    }
+}
+
+impl FromRequest for Stamp {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(Ok(Stamp::from_request(req)))
+    }
+}
+
+/// `SELECT {columns} FROM {table} WHERE {visibility}`; append conditions with `" AND ..."`.
+pub fn select<'a>(table: &'static str, columns: &'static str, visibility: Visibility) -> QueryBuilder<'a, Postgres> {
+    let filter = match visibility {
+        Visibility::Active => "deleted_at IS NULL",
+        Visibility::WithDeleted => "TRUE",
+        Visibility::OnlyDeleted => "deleted_at IS NOT NULL",
+    };
+    QueryBuilder::new(format!("SELECT {} FROM {} WHERE {}", columns, table, filter))
+}
+
+/// Move a row to the trash. Returns false if it does not exist or was already deleted.
+pub async fn soft_delete(conn: &mut PgConnection, table: &'static str, id: impl Into<Uuid>) -> Result<bool, DbError> {
+    let sql = format!("UPDATE {} SET deleted_at = now(), updated_at = now() WHERE id = $1 AND deleted_at IS NULL", table);
+    let result = sqlx::query(&sql).bind(id.into()).execute(conn).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Take a row out of the trash. Returns false if it was not soft-deleted.
+pub async fn restore(conn: &mut PgConnection, table: &'static str, id: impl Into<Uuid>) -> Result<bool, DbError> {
+    let sql = format!("UPDATE {} SET deleted_at = NULL, updated_at = now() WHERE id = $1 AND deleted_at IS NOT NULL", table);
+    let result = sqlx::query(&sql).bind(id.into()).execute(conn).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Permanently delete one soft-deleted row. Active rows are left alone (returns false).
+pub async fn purge(conn: &mut PgConnection, table: &'static str, id: impl Into<Uuid>) -> Result<bool, DbError> {
+    let sql = format!("DELETE FROM {} WHERE id = $1 AND deleted_at IS NOT NULL", table);
+    let result = sqlx::query(&sql).bind(id.into()).execute(conn).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Permanently delete rows that have been in the trash for longer than `retention`.
+/// Returns how many were deleted.
+pub async fn purge_deleted(conn: &mut PgConnection, table: &'static str, retention: Duration) -> Result<u64, DbError> {
+    let sql = format!("DELETE FROM {} WHERE deleted_at < now() - make_interval(secs => $1)", table);
+    let result = sqlx::query(&sql).bind(retention.as_secs_f64()).execute(conn).await?;
+    Ok(result.rows_affected())
+}
+
+/// Bound column values, keeping the first encoding error for `execute`.
+struct Values<'a> {
+    columns: Vec<&'static str>,
+    args: PgArguments,
+    error: Option<sqlx::error::BoxDynError>,
+    _args: PhantomData<&'a ()>,
+}
+
+impl<'a> Values<'a> {
+    fn new() -> Self {
+        Self { columns: Vec::new(), args: PgArguments::default(), error: None, _args: PhantomData }
+    }
+
+    fn add<T>(&mut self, column: &'static str, value: T)
+    where
+        T: 'a + Encode<'a, Postgres> + Type<Postgres> + Send,
+    {
+        if let Err(e) = Arguments::<'a>::add(&mut self.args, value) {
+            self.error.get_or_insert(e);
+        }
+        self.columns.push(column);
+    }
+
+    fn into_args(self) -> Result<PgArguments, DbError> {
+        match self.error {
+            Some(e) => Err(DbError::Sqlx(sqlx::Error::Encode(e))),
+            None => Ok(self.args),
+        }
+    }
+}
+
+/// `INSERT` that also sets `org_id` and `created_by` from a `Stamp`.
+pub struct Insert<'a> {
+    table: &'static str,
+    values: Values<'a>,
+}
+
+impl<'a> Insert<'a> {
+    pub fn new(table: &'static str, stamp: &Stamp) -> Self {
+        let mut values = Values::new();
+        if let Some(org_id) = stamp.org_id {
+            values.add("org_id", org_id);
+        }
+        if let Some(user) = &stamp.user {
+            values.add("created_by", user.clone());
+        }
+        Self { table, values }
+    }
+
+    pub fn value<T>(mut self, column: &'static str, value: T) -> Self
+    where
+        T: 'a + Encode<'a, Postgres> + Type<Postgres> + Send,
+    {
+        self.values.add(column, value);
+        self
+    }
+
+    fn sql(&self, returning: bool) -> String {
+        let placeholders: Vec<String> = (1..=self.values.columns.len()).map(|n| format!("${}", n)).collect();
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}){}",
+            self.table,
+            self.values.columns.join(", "),
+            placeholders.join(", "),
+            if returning { " RETURNING *" } else { "" }
+        )
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> Result<(), DbError> {
+        let sql = self.sql(false);
+        sqlx::query_with(&sql, self.values.into_args()?).execute(conn).await?;
+        Ok(())
+    }
+
+    /// Insert and return the stored row (`RETURNING *`).
+    pub async fn fetch<T>(self, conn: &mut PgConnection) -> Result<T, DbError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let sql = self.sql(true);
+        Ok(sqlx::query_as_with(&sql, self.values.into_args()?).fetch_one(conn).await?)
+    }
+}
+
+/// `UPDATE` of one active row that also bumps `updated_at`. Soft-deleted rows count as
+/// missing (`RowNotFound`, 404).
+pub struct Update<'a> {
+    table: &'static str,
+    id: Uuid,
+    values: Values<'a>,
+}
+
+impl<'a> Update<'a> {
+    pub fn new(table: &'static str, id: impl Into<Uuid>) -> Self {
+        Self { table, id: id.into(), values: Values::new() }
+    }
+
+    pub fn set<T>(mut self, column: &'static str, value: T) -> Self
+    where
+        T: 'a + Encode<'a, Postgres> + Type<Postgres> + Send,
+    {
+        self.values.add(column, value);
+        self
+    }
+
+    fn sql(&self) -> String {
+        let assignments: String =
+            self.values.columns.iter().enumerate().map(|(n, column)| format!("{} = ${}, ", column, n + 1)).collect();
+        format!(
+            "UPDATE {} SET {}updated_at = now() WHERE id = ${} AND deleted_at IS NULL",
+            self.table,
+            assignments,
+            self.values.columns.len() + 1
+        )
+    }
+
+    pub async fn execute(mut self, conn: &mut PgConnection) -> Result<(), DbError> {
+        let sql = self.sql();
+        self.values.add("id", self.id);
+        let result = sqlx::query_with(&sql, self.values.into_args()?).execute(conn).await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::Sqlx(sqlx::Error::RowNotFound));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_sql() {
+        assert_eq!(
+            select("products", "id, name", Visibility::Active).sql(),
+            "SELECT id, name FROM products WHERE deleted_at IS NULL"
+        );
+        assert_eq!(select("products", "*", Visibility::OnlyDeleted).sql(), "SELECT * FROM products WHERE deleted_at IS NOT NULL");
+
+        let stamp = Stamp::system(Uuid::new_v4(), "lanai-inventory-sync");
+        let insert = Insert::new("products", &stamp).value("name", "Coffee");
+        assert_eq!(insert.sql(true), "INSERT INTO products (org_id, created_by, name) VALUES ($1, $2, $3) RETURNING *");
+
+        let update = Update::new("products", Uuid::nil()).set("name", "Tea").set("stock", 5_i32);
+        assert_eq!(
+            update.sql(),
+            "UPDATE products SET name = $1, stock = $2, updated_at = now() WHERE id = $3 AND deleted_at IS NULL"
+        );
+        assert_eq!(Stamp::from_request(&actix_web::test::TestRequest::default().to_http_request()), Stamp::default());
+    }
+}
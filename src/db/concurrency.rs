@@ -0,0 +1,188 @@
+//! Optimistic concurrency with version columns
+//!
+//! Rows carry a `version BIGINT NOT NULL DEFAULT 1` column. Reads return it as the `ETag`,
+//! clients send it back in `If-Match`, and updates only apply if the row is still at that
+//! version. A lost race answers 412 instead of silently overwriting the other edit:
+//!
+//! ```ignore
+//! async fn get_product(tx: TenantTx, id: web::Path<Uuid>) -> LanaiResult<HttpResponse> {
+//!     let product: Product = ...;
+//!     Ok(HttpResponse::Ok().insert_header(product.version.etag()).json(product))
+//! }
+//!
+//! async fn update_product(mut tx: TenantTx, if_match: IfMatch, id: web::Path<Uuid>, body: ValidatedJson<UpdateProduct>) -> LanaiResult<HttpResponse> {
+//!     let version = VersionedUpdate::new("products", *id, if_match.expected()?)
+//!         .set("name", body.name.clone())
+//!         .set("price", body.price)
+//!         .execute(tx.conn())
+//!         .await?;
+//!     tx.commit().await?;
+//!     Ok(HttpResponse::NoContent().insert_header(version.etag()).finish())
+//! }
+//! ```
+
+use actix_web::{http::header, FromRequest, HttpRequest};
+use serde::{Deserialize, Serialize};
+use sqlx::{Encode, PgConnection, Postgres, QueryBuilder, Type};
+use std::fmt;
+use std::future::{ready, Ready};
+use uuid::Uuid;
+
+use super::DbError;
+use crate::error::LanaiError;
+
+/// Row version, sent to clients as the strong ETag `"{version}"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Version(pub i64);
+
+impl Version {
+    /// The `ETag` response header.
+    pub fn etag(&self) -> (header::HeaderName, String) {
+        (header::ETAG, self.to_string())
+    }
+
+    /// Parse one entity tag; weak tags (`W/"3"`) are accepted.
+    pub fn from_etag(tag: &str) -> Option<Self> {
+        let tag = tag.trim();
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok().map(Version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+/// The request's `If-Match` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    Absent,
+    /// `If-Match: *`
+    Any,
+    Versions(Vec<Version>),
+}
+
+impl IfMatch {
+    /// The single version the client edited, for `VersionedUpdate`. 428 without `If-Match`.
+    pub fn expected(&self) -> Result<Version, LanaiError> {
+        match self {
+            Self::Absent => Err(LanaiError::PreconditionRequired("If-Match header is required".to_string())),
+            Self::Versions(versions) if versions.len() == 1 => Ok(versions[0]),
+            _ => Err(LanaiError::BadRequest("If-Match must carry the ETag of the resource".to_string())),
+        }
+    }
+
+    /// 412 unless `current` is one of the listed versions (or `*`); 428 without `If-Match`.
+    pub fn check(&self, current: Version) -> Result<(), LanaiError> {
+        match self {
+            Self::Absent => Err(LanaiError::PreconditionRequired("If-Match header is required".to_string())),
+            Self::Any => Ok(()),
+            Self::Versions(versions) if versions.contains(&current) => Ok(()),
+            Self::Versions(_) => Err(DbError::VersionMismatch(current.0).into()),
+        }
+    }
+}
+
+/// Unparseable tags make extraction fail with 400.
+impl FromRequest for IfMatch {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let Some(value) = req.headers().get(header::IF_MATCH) else {
+            return ready(Ok(Self::Absent));
+        };
+        let invalid = || LanaiError::BadRequest("Invalid If-Match header".to_string());
+        let result = value.to_str().map_err(|_| invalid()).and_then(|value| {
+            if value.trim() == "*" {
+                return Ok(Self::Any);
+            }
+            value
+                .split(',')
+                .map(|tag| Version::from_etag(tag).ok_or_else(invalid))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Self::Versions)
+        });
+        ready(result)
+    }
+}
+
+/// `UPDATE {table} SET ..., version = version + 1 WHERE id = $id AND version = $expected`.
+///
+/// Table and column names are written into the SQL as is, hence `&'static str`; values are
+/// bound. Returns the new version, `DbError::VersionMismatch` if the row moved on, and
+/// `RowNotFound` (404) if it does not exist. Row-level security still applies when run on
+/// a `TenantTx`.
+pub struct VersionedUpdate<'a> {
+    table: &'static str,
+    id: Uuid,
+    expected: Version,
+    builder: QueryBuilder<'a, Postgres>,
+}
+
+impl<'a> VersionedUpdate<'a> {
+    pub fn new(table: &'static str, id: impl Into<Uuid>, expected: Version) -> Self {
+        let builder = QueryBuilder::new(format!("UPDATE {} SET ", table));
+        Self { table, id: id.into(), expected, builder }
+    }
+
+    pub fn set<T>(mut self, column: &'static str, value: T) -> Self
+    where
+        T: 'a + Encode<'a, Postgres> + Type<Postgres> + Send,
+    {
+        self.builder.push(column).push(" = ").push_bind(value).push(", ");
+        self
+    }
+
+    pub async fn execute(mut self, conn: &mut PgConnection) -> Result<Version, DbError> {
+        self.builder
+            .push("version = version + 1 WHERE id = ")
+            .push_bind(self.id)
+            .push(" AND version = ")
+            .push_bind(self.expected.0)
+            .push(" RETURNING version");
+
+        let updated: Option<i64> = self.builder.build_query_scalar().fetch_optional(&mut *conn).await?;
+        if let Some(version) = updated {
+            return Ok(Version(version));
+        }
+
+        let sql = format!("SELECT version FROM {} WHERE id = $1", self.table);
+        let current: Option<i64> = sqlx::query_scalar(&sql).bind(self.id).fetch_optional(&mut *conn).await?;
+        match current {
+            Some(current) => Err(DbError::VersionMismatch(current)),
+            None => Err(DbError::Sqlx(sqlx::Error::RowNotFound)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+
+    async fn if_match(value: &str) -> Result<IfMatch, LanaiError> {
+        let req = TestRequest::default().insert_header((header::IF_MATCH, value)).to_http_request();
+        IfMatch::extract(&req).await
+    }
+
+    #[actix_web::test]
+    async fn test_if_match_parsing_and_checks() {
+        assert_eq!(if_match("\"3\", W/\"4\"").await.unwrap(), IfMatch::Versions(vec![Version(3), Version(4)]));
+        assert_eq!(if_match("*").await.unwrap(), IfMatch::Any);
+        assert!(if_match("3").await.is_err());
+
+        let edited = if_match(&Version(3).to_string()).await.unwrap();
+        assert_eq!(edited.expected().unwrap(), Version(3));
+        assert!(edited.check(Version(3)).is_ok());
+        assert_eq!(edited.check(Version(4)).unwrap_err().status_code().as_u16(), 412);
+
+        let absent = IfMatch::extract(&TestRequest::default().to_http_request()).await.unwrap();
+        assert_eq!(absent.expected().unwrap_err().status_code().as_u16(), 428);
+    }
+}
@@ -0,0 +1,81 @@
+//! Embedded migrations run at startup
+//!
+//! ```ignore
+//! ServerBuilder::new("lanai-inventory-service")
+//!     .migrations(pool.clone(), lanai_infrastructure::db::migrate!())
+//!     .run(configure)
+//!     .await
+//! ```
+//!
+//! Migrations only run when `DB_RUN_MIGRATIONS=true`, so a single job or replica can own
+//! schema changes. Replicas starting together serialize on a Postgres advisory lock: the
+//! first one migrates, the others wait and then find nothing left to apply.
+//! `migrate!` expands to `sqlx::migrate!`, so the calling crate needs `sqlx` as a dependency.
+
+use log::{info, warn};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPool;
+use std::time::{Duration, Instant};
+
+use super::DbError;
+
+/// Set to `true` to run migrations on startup
+pub const DB_RUN_MIGRATIONS_ENV: &str = "DB_RUN_MIGRATIONS";
+
+/// Advisory lock key shared by every Lanai service migrating the same database ("lanaimig").
+pub const MIGRATION_LOCK_KEY: i64 = 0x6c61_6e61_696d_6967;
+
+/// How long a replica waits for another one to finish migrating.
+const LOCK_WAIT: Duration = Duration::from_secs(300);
+
+/// Embed the migrations in `./migrations` (or the given directory) into the binary.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lanai_db_migrate {
+    () => {
+        ::sqlx::migrate!()
+    };
+    ($dir:literal) => {
+        ::sqlx::migrate!($dir)
+    };
+}
+
+/// Returns true if `DB_RUN_MIGRATIONS` enables startup migrations.
+pub fn migrations_enabled() -> bool {
+    std::env::var(DB_RUN_MIGRATIONS_ENV)
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Apply pending migrations while holding the migration advisory lock.
+pub async fn run_migrations(pool: &PgPool, migrator: &Migrator) -> Result<(), DbError> {
+    let mut conn = super::pool::acquire(pool).await?;
+    let started = Instant::now();
+
+    loop {
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(MIGRATION_LOCK_KEY)
+            .fetch_one(&mut *conn)
+            .await?;
+        if locked {
+            break;
+        }
+        if started.elapsed() > LOCK_WAIT {
+            return Err(DbError::Migration(format!("migration lock still held after {:?}", LOCK_WAIT)));
+        }
+        warn!("⏳ Another instance is running migrations, waiting...");
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    info!("🛠️ Running database migrations ({} embedded)...", migrator.iter().count());
+    let result = migrator.run(&mut *conn).await;
+
+    let _ = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await;
+
+    result.map_err(|e| DbError::Migration(e.to_string()))?;
+    info!("✅ Database migrations applied in {:?}", started.elapsed());
+    Ok(())
+}
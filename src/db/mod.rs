@@ -0,0 +1,77 @@
+//! PostgreSQL access for Lanai services
+//!
+//! Provides a standardized `sqlx` pool setup:
+//! - Env-based configuration (`DATABASE_URL`, pool sizes, timeouts)
+//! - Statement and slow-statement logging, per-query timeouts with tenant/route attribution
+//! - Health checks and OpenTelemetry pool metrics
+//! - Tenant-scoped transactions for row-level security
+//! - Soft deletes and `created_at`/`updated_at`/`created_by` audit columns
+//! - Optimistic concurrency with `version` columns and `If-Match`/`ETag`
+//! - Embedded migrations coordinated across replicas
+//! - Primary/replica routing with lag-aware fallback
+
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::env::{EnvVar, VarType};
+
+pub mod columns;
+pub mod concurrency;
+pub mod migrate;
+pub mod pool;
+pub mod query;
+pub mod router;
+pub mod tenant;
+
+pub use crate::__lanai_db_migrate as migrate;
+pub use columns::{Stamp, Visibility};
+pub use concurrency::{IfMatch, Version, VersionedUpdate};
+pub use migrate::{migrations_enabled, run_migrations};
+pub use pool::{acquire, begin, health_check, PgPoolBuilder};
+pub use query::{observe, QueryContext, QueryContextMiddleware};
+pub use router::DbRouter;
+pub use tenant::{TenantDb, TenantTx};
+
+/// Variables read by the pool, migrations, query observer and replica router
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar::new(pool::DATABASE_URL_ENV, VarType::Url).description("Postgres connection string"),
+    EnvVar::new(pool::DB_MAX_CONNECTIONS_ENV, VarType::Integer).description("Maximum pool size").default_value("10"),
+    EnvVar::new(pool::DB_MIN_CONNECTIONS_ENV, VarType::Integer).description("Minimum idle connections").default_value("1"),
+    EnvVar::new(pool::DB_ACQUIRE_TIMEOUT_ENV, VarType::Integer).description("Seconds to wait for a connection").default_value("5"),
+    EnvVar::new(pool::DB_IDLE_TIMEOUT_ENV, VarType::Integer).description("Seconds before an idle connection closes").default_value("600"),
+    EnvVar::new(pool::DB_STATEMENT_TIMEOUT_ENV, VarType::Integer).description("Server-side statement timeout in ms").default_value("30000"),
+    EnvVar::new(pool::DB_SLOW_STATEMENT_ENV, VarType::Integer).description("Slow statement threshold in ms").default_value("1000"),
+    EnvVar::new(query::DB_QUERY_TIMEOUT_ENV, VarType::Integer).description("Client-side query timeout in ms"),
+    EnvVar::new(migrate::DB_RUN_MIGRATIONS_ENV, VarType::Bool).description("Run migrations on startup").default_value("false"),
+    EnvVar::new(router::DATABASE_REPLICA_URLS_ENV, VarType::UrlList).description("Read replica connection strings"),
+    EnvVar::new(router::DB_MAX_REPLICA_LAG_ENV, VarType::Integer).description("Tolerated replica lag in ms").default_value("5000"),
+];
+
+/// Database error types
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("DATABASE_URL is not set")]
+    MissingUrl,
+
+    #[error("Invalid database configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Failed to connect to database: {0}")]
+    Connection(String),
+
+    #[error("Tenant-scoped query attempted without a tenant")]
+    MissingTenant,
+
+    #[error("Migration failed: {0}")]
+    Migration(String),
+
+    #[error("Database did not respond within {0:?}")]
+    Timeout(Duration),
+
+    /// Optimistic concurrency check failed; holds the current version.
+    #[error("Row was modified concurrently (now at version {0})")]
+    VersionMismatch(i64),
+
+    #[error("Database error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+}
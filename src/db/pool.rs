@@ -0,0 +1,292 @@
+//! Connection pool builder
+//!
+//! ```ignore
+//! let pool = PgPoolBuilder::from_env("lanai-inventory-service")?
+//!     .max_connections(20)
+//!     .build()
+//!     .await?;
+//! ```
+
+use log::{info, LevelFilter};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::{global, KeyValue};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::{ConnectOptions, Postgres, Transaction};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+use super::DbError;
+
+/// Environment variable for the Postgres connection string
+pub const DATABASE_URL_ENV: &str = "DATABASE_URL";
+/// Maximum pool size (default: 10)
+pub const DB_MAX_CONNECTIONS_ENV: &str = "DB_MAX_CONNECTIONS";
+/// Minimum idle connections kept open (default: 1)
+pub const DB_MIN_CONNECTIONS_ENV: &str = "DB_MIN_CONNECTIONS";
+/// Seconds to wait for a free connection (default: 5)
+pub const DB_ACQUIRE_TIMEOUT_ENV: &str = "DB_ACQUIRE_TIMEOUT_SECS";
+/// Seconds before an idle connection is closed (default: 600)
+pub const DB_IDLE_TIMEOUT_ENV: &str = "DB_IDLE_TIMEOUT_SECS";
+/// Server-side `statement_timeout` in milliseconds (default: 30000, 0 disables)
+pub const DB_STATEMENT_TIMEOUT_ENV: &str = "DB_STATEMENT_TIMEOUT_MS";
+/// Statements slower than this many milliseconds are logged as warnings (default: 1000)
+pub const DB_SLOW_STATEMENT_ENV: &str = "DB_SLOW_STATEMENT_MS";
+
+/// Builder for standardized Postgres pools.
+#[derive(Debug, Clone)]
+pub struct PgPoolBuilder {
+    url: String,
+    application_name: String,
+    max_connections: u32,
+    min_connections: u32,
+    acquire_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    statement_timeout: Option<Duration>,
+    log_statements: LevelFilter,
+    slow_statement_threshold: Duration,
+    metrics: bool,
+}
+
+impl PgPoolBuilder {
+    pub fn new(url: &str, application_name: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            application_name: application_name.to_string(),
+            max_connections: 10,
+            min_connections: 1,
+            acquire_timeout: Duration::from_secs(5),
+            idle_timeout: Some(Duration::from_secs(600)),
+            max_lifetime: Some(Duration::from_secs(1800)),
+            statement_timeout: Some(Duration::from_secs(30)),
+            log_statements: LevelFilter::Debug,
+            slow_statement_threshold: Duration::from_secs(1),
+            metrics: true,
+        }
+    }
+
+    /// Configure from `DATABASE_URL` and the `DB_*` variables, falling back to defaults.
+    pub fn from_env(application_name: &str) -> Result<Self, DbError> {
+        let url = std::env::var(DATABASE_URL_ENV).map_err(|_| DbError::MissingUrl)?;
+        let mut builder = Self::new(&url, application_name);
+
+        if let Some(max) = env_parse(DB_MAX_CONNECTIONS_ENV)? {
+            builder.max_connections = max;
+        }
+        if let Some(min) = env_parse(DB_MIN_CONNECTIONS_ENV)? {
+            builder.min_connections = min;
+        }
+        if let Some(secs) = env_parse(DB_ACQUIRE_TIMEOUT_ENV)? {
+            builder.acquire_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = env_parse(DB_IDLE_TIMEOUT_ENV)? {
+            builder.idle_timeout = Some(Duration::from_secs(secs));
+        }
+        if let Some(ms) = env_parse::<u64>(DB_STATEMENT_TIMEOUT_ENV)? {
+            builder.statement_timeout = (ms > 0).then(|| Duration::from_millis(ms));
+        }
+        if let Some(ms) = env_parse(DB_SLOW_STATEMENT_ENV)? {
+            builder.slow_statement_threshold = Duration::from_millis(ms);
+        }
+        Ok(builder)
+    }
+
+    /// Point the same configuration at another server (e.g. a read replica).
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.min_connections = min;
+        self
+    }
+
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    pub fn max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = lifetime;
+        self
+    }
+
+    /// Server-side limit for every statement on pooled connections (`None` disables it).
+    pub fn statement_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.statement_timeout = timeout;
+        self
+    }
+
+    /// Level at which every executed statement is logged (default: debug).
+    pub fn log_statements(mut self, level: LevelFilter) -> Self {
+        self.log_statements = level;
+        self
+    }
+
+    /// Statements slower than `threshold` are logged as warnings.
+    pub fn slow_statement_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_statement_threshold = threshold;
+        self
+    }
+
+    pub fn disable_metrics(mut self) -> Self {
+        self.metrics = false;
+        self
+    }
+
+    /// Connection options with logging and session settings applied, without connecting.
+    pub fn connect_options(&self) -> Result<PgConnectOptions, DbError> {
+        let mut options = PgConnectOptions::from_str(&self.url)
+            .map_err(|e| DbError::InvalidConfig(e.to_string()))?
+            .application_name(&self.application_name)
+            .log_statements(self.log_statements)
+            .log_slow_statements(LevelFilter::Warn, self.slow_statement_threshold);
+
+        if let Some(timeout) = self.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+        }
+        Ok(options)
+    }
+
+    /// Open the pool and make sure the database is reachable.
+    pub async fn build(self) -> Result<PgPool, DbError> {
+        let options = self.connect_options()?;
+        let span = tracing::info_span!(
+            "db.connect",
+            db.system = "postgresql",
+            db.name = options.get_database().unwrap_or_default(),
+            service = %self.application_name,
+        );
+
+        let host = format!("{}:{}", options.get_host(), options.get_port());
+        info!("🗄️ Connecting to Postgres at {} as '{}'...", host, self.application_name);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .test_before_acquire(true)
+            .connect_with(options)
+            .instrument(span)
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?;
+
+        if self.metrics {
+            register_pool_metrics(&pool, &self.application_name, &host);
+        }
+
+        info!("✅ Postgres pool ready ({}..{} connections)", self.min_connections, self.max_connections);
+        Ok(pool)
+    }
+}
+
+/// Run `SELECT 1` and return the round-trip time.
+pub async fn health_check(pool: &PgPool, timeout: Duration) -> Result<Duration, DbError> {
+    let started = Instant::now();
+    tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(pool))
+        .await
+        .map_err(|_| DbError::Timeout(timeout))??;
+    Ok(started.elapsed())
+}
+
+/// Acquire a connection, recording the wait in `db_pool_acquire_duration_seconds`.
+pub async fn acquire(pool: &PgPool) -> Result<PoolConnection<Postgres>, DbError> {
+    let started = Instant::now();
+    let result = pool.acquire().await;
+    record_acquire(pool, started, result.is_ok());
+    Ok(result?)
+}
+
+/// Begin a transaction, recording the wait for its connection like `acquire`.
+pub async fn begin(pool: &PgPool) -> Result<Transaction<'static, Postgres>, DbError> {
+    let started = Instant::now();
+    let result = pool.begin().await;
+    record_acquire(pool, started, result.is_ok());
+    Ok(result?)
+}
+
+fn record_acquire(pool: &PgPool, started: Instant, acquired: bool) {
+    static ACQUIRE_WAIT: OnceLock<Histogram<f64>> = OnceLock::new();
+    let histogram = ACQUIRE_WAIT.get_or_init(|| {
+        global::meter("lanai.db")
+            .f64_histogram("db_pool_acquire_duration_seconds")
+            .with_description("Time spent waiting for a pooled connection")
+            .with_unit("s")
+            .build()
+    });
+    let application_name = pool.connect_options().get_application_name().unwrap_or_default().to_string();
+    histogram.record(
+        started.elapsed().as_secs_f64(),
+        &[KeyValue::new("pool", application_name), KeyValue::new("outcome", if acquired { "ok" } else { "error" })],
+    );
+}
+
+/// Export pool size and idle connections as observable gauges, labelled by pool and host.
+fn register_pool_metrics(pool: &PgPool, application_name: &str, host: &str) {
+    let meter = global::meter("lanai.db");
+    let pool = pool.clone();
+    let max_connections = pool.options().get_max_connections() as u64;
+    let attrs = [KeyValue::new("pool", application_name.to_string()), KeyValue::new("db.host", host.to_string())];
+
+    meter
+        .u64_observable_gauge("db_pool_connections")
+        .with_description("Connections in the database pool by state")
+        .with_callback(move |observer| {
+            let size = pool.size() as u64;
+            let idle = pool.num_idle() as u64;
+            for (state, value) in [("in_use", size.saturating_sub(idle)), ("idle", idle), ("max", max_connections)] {
+                let mut labels = attrs.to_vec();
+                labels.push(KeyValue::new("state", state));
+                observer.observe(value, &labels);
+            }
+        })
+        .build();
+}
+
+fn env_parse<T: FromStr>(name: &str) -> Result<Option<T>, DbError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| DbError::InvalidConfig(format!("{} must be a number, got '{}'", name, value))),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_options() {
+        let builder = PgPoolBuilder::new("postgres://lanai:secret@db:5433/inventory", "lanai-inventory-service")
+            .max_connections(20);
+        let options = builder.connect_options().unwrap();
+        assert_eq!(options.get_host(), "db");
+        assert_eq!(options.get_port(), 5433);
+        assert_eq!(options.get_database(), Some("inventory"));
+        assert_eq!(builder.max_connections, 20);
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        let builder = PgPoolBuilder::new("not a url", "lanai-test");
+        assert!(matches!(builder.connect_options(), Err(DbError::InvalidConfig(_))));
+    }
+}
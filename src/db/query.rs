@@ -0,0 +1,207 @@
+//! Per-query timeouts and slow query reporting
+//!
+//! `observe` runs a named query with a timeout and reports it when it is slow: a warning
+//! with the trace ID, tenant and route that issued it, `db_slow_queries_total`, and every
+//! query's duration in `db_query_duration_seconds`. The tenant and route come from
+//! `QueryContextMiddleware`, which `ServerBuilder` installs.
+//!
+//! ```ignore
+//! let orders: Vec<Order> = db::observe("orders.list_open", None, async {
+//!     sqlx::query_as("SELECT * FROM orders WHERE status = 'open'").fetch_all(tx.conn()).await
+//! })
+//! .await?;
+//! ```
+//!
+//! The timeout is enforced client-side; `TenantTx::statement_timeout` sets a server-side
+//! limit for the rest of a transaction, which also stops the statement on the server.
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::{global, KeyValue};
+use std::future::{ready, Future, Ready};
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::pool::DB_SLOW_STATEMENT_ENV;
+use super::DbError;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Default client-side timeout for observed queries in milliseconds (unset: none)
+pub const DB_QUERY_TIMEOUT_ENV: &str = "DB_QUERY_TIMEOUT_MS";
+
+/// Who issued the queries of the current request.
+#[derive(Debug, Clone, Default)]
+pub struct QueryContext {
+    pub org_id: Option<Uuid>,
+    /// `METHOD /path` of the HTTP request, or a job/consumer name.
+    pub route: Option<String>,
+}
+
+tokio::task_local! {
+    static QUERY_CONTEXT: QueryContext;
+}
+
+impl QueryContext {
+    /// The context of the current task, if one was set.
+    pub fn current() -> Option<QueryContext> {
+        QUERY_CONTEXT.try_with(|context| context.clone()).ok()
+    }
+
+    /// Run `f` with this context, e.g. in a job handler or NATS consumer.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        QUERY_CONTEXT.scope(self, f).await
+    }
+}
+
+struct Settings {
+    slow_threshold: Duration,
+    default_timeout: Option<Duration>,
+    duration: Histogram<f64>,
+    slow: Counter<u64>,
+}
+
+fn settings() -> &'static Settings {
+    static SETTINGS: OnceLock<Settings> = OnceLock::new();
+    SETTINGS.get_or_init(|| {
+        let millis = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let meter = global::meter("lanai.db");
+        Settings {
+            slow_threshold: Duration::from_millis(millis(DB_SLOW_STATEMENT_ENV).unwrap_or(1000)),
+            default_timeout: millis(DB_QUERY_TIMEOUT_ENV).filter(|ms| *ms > 0).map(Duration::from_millis),
+            duration: meter
+                .f64_histogram("db_query_duration_seconds")
+                .with_description("Duration of observed queries, by query name and outcome")
+                .with_unit("s")
+                .build(),
+            slow: meter
+                .u64_counter("db_slow_queries_total")
+                .with_description("Observed queries slower than the slow query threshold")
+                .build(),
+        }
+    })
+}
+
+/// Run `query` under `name`, failing with `DbError::Timeout` after `timeout` (or
+/// `DB_QUERY_TIMEOUT_MS`), and report it if it exceeds `DB_SLOW_STATEMENT_MS`.
+pub async fn observe<T, F>(name: &str, timeout: Option<Duration>, query: F) -> Result<T, DbError>
+where
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    let settings = settings();
+    let started = Instant::now();
+
+    let result = match timeout.or(settings.default_timeout) {
+        Some(limit) => match tokio::time::timeout(limit, query).await {
+            Ok(result) => result.map_err(DbError::from),
+            Err(_) => Err(DbError::Timeout(limit)),
+        },
+        None => query.await.map_err(DbError::from),
+    };
+    let elapsed = started.elapsed();
+
+    let outcome = match &result {
+        Ok(_) => "ok",
+        Err(DbError::Timeout(_)) => "timeout",
+        Err(_) => "error",
+    };
+    settings.duration.record(
+        elapsed.as_secs_f64(),
+        &[KeyValue::new("query", name.to_string()), KeyValue::new("outcome", outcome)],
+    );
+
+    if elapsed >= settings.slow_threshold {
+        settings.slow.add(1, &[KeyValue::new("query", name.to_string())]);
+        let context = QueryContext::current().unwrap_or_default();
+        warn!(
+            "🐢 Slow query '{}' took {:?} ({}) org={} route={} trace_id={}",
+            name,
+            elapsed,
+            outcome,
+            context.org_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()),
+            context.route.as_deref().unwrap_or("-"),
+            crate::observability::current_trace_id().unwrap_or_else(|| "-".to_string()),
+        );
+    }
+    result
+}
+
+/// Makes the request's tenant and route available to `observe`. Reads the `TenantContext`,
+/// so it must run after `TenantMiddleware` (be registered before it).
+pub struct QueryContextMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for QueryContextMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = QueryContextMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(QueryContextMiddlewareService { service: Rc::new(service) }))
+    }
+}
+
+pub struct QueryContextMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for QueryContextMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let context = QueryContext {
+            org_id: req.extensions().get::<TenantContext>().map(|tenant| tenant.org_id),
+            route: Some(format!("{} {}", req.method(), req.path())),
+        };
+        let fut = self.service.call(req);
+        Box::pin(QUERY_CONTEXT.scope(context, fut))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_observe_times_out() {
+        let result: Result<(), DbError> = observe("test.sleep", Some(Duration::from_millis(10)), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(DbError::Timeout(_))));
+
+        let result = observe("test.ok", None, async { Ok::<_, sqlx::Error>(7) }).await.unwrap();
+        assert_eq!(result, 7);
+    }
+
+    #[tokio::test]
+    async fn test_query_context_scope() {
+        assert!(QueryContext::current().is_none());
+        let org_id = Uuid::new_v4();
+        let context = QueryContext { org_id: Some(org_id), route: Some("GET /orders".to_string()) };
+        let seen = context.scope(async { QueryContext::current() }).await.unwrap();
+        assert_eq!(seen.org_id, Some(org_id));
+    }
+}
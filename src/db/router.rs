@@ -0,0 +1,190 @@
+//! Primary/replica routing
+//!
+//! `DbRouter` sends writes to the primary and spreads reads across replicas whose
+//! replication lag is within bounds. Lag is sampled in the background; when every replica
+//! is lagging (or unreachable) reads fall back to the primary.
+//!
+//! ```ignore
+//! let router = DbRouter::from_env("lanai-reporting-service").await?;
+//! let rows = sqlx::query("SELECT ...").fetch_all(router.reader()).await?;
+//! sqlx::query("INSERT ...").execute(router.writer()).await?;
+//! ```
+
+use log::{debug, info, warn};
+use sqlx::postgres::PgPool;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use super::{DbError, PgPoolBuilder};
+
+/// Comma-separated replica connection strings (optional)
+pub const DATABASE_REPLICA_URLS_ENV: &str = "DATABASE_REPLICA_URLS";
+/// Maximum tolerated replication lag in milliseconds (default: 5000)
+pub const DB_MAX_REPLICA_LAG_ENV: &str = "DB_MAX_REPLICA_LAG_MS";
+
+/// Marks a replica whose lag is unknown (not sampled yet or unreachable).
+const LAG_UNKNOWN: u64 = u64::MAX;
+
+/// Replay lag in seconds; 0 when the replica has replayed everything it received.
+const LAG_QUERY: &str = "SELECT (CASE \
+        WHEN NOT pg_is_in_recovery() THEN 0 \
+        WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+        ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0) \
+    END)::float8";
+
+struct Replica {
+    pool: PgPool,
+    lag_ms: AtomicU64,
+}
+
+struct Inner {
+    primary: PgPool,
+    replicas: Vec<Replica>,
+    max_lag: Duration,
+    next: AtomicUsize,
+}
+
+#[derive(Clone)]
+pub struct DbRouter {
+    inner: Arc<Inner>,
+}
+
+impl DbRouter {
+    pub fn new(primary: PgPool) -> Self {
+        Self::with_replicas(primary, Vec::new(), Duration::from_secs(5))
+    }
+
+    /// Route reads to `replicas` while their lag stays below `max_lag`.
+    /// Replicas count as lagging until `start_lag_monitor` has sampled them.
+    pub fn with_replicas(primary: PgPool, replicas: Vec<PgPool>, max_lag: Duration) -> Self {
+        let replicas = replicas
+            .into_iter()
+            .map(|pool| Replica { pool, lag_ms: AtomicU64::new(LAG_UNKNOWN) })
+            .collect();
+        Self { inner: Arc::new(Inner { primary, replicas, max_lag, next: AtomicUsize::new(0) }) }
+    }
+
+    /// Build the primary from `DATABASE_URL`, replicas from `DATABASE_REPLICA_URLS`, and
+    /// start the lag monitor.
+    pub async fn from_env(application_name: &str) -> Result<Self, DbError> {
+        let primary_builder = PgPoolBuilder::from_env(application_name)?;
+        let primary = primary_builder.clone().build().await?;
+
+        let max_lag = match std::env::var(DB_MAX_REPLICA_LAG_ENV) {
+            Ok(ms) => Duration::from_millis(ms.parse().map_err(|_| {
+                DbError::InvalidConfig(format!("{} must be a number, got '{}'", DB_MAX_REPLICA_LAG_ENV, ms))
+            })?),
+            Err(_) => Duration::from_secs(5),
+        };
+
+        let mut replicas = Vec::new();
+        let urls = std::env::var(DATABASE_REPLICA_URLS_ENV).unwrap_or_default();
+        for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+            replicas.push(primary_builder.clone().url(url).build().await?);
+        }
+
+        let router = Self::with_replicas(primary, replicas, max_lag);
+        if !router.inner.replicas.is_empty() {
+            info!("🔀 DbRouter routing reads to {} replica(s) (max lag {:?})", router.inner.replicas.len(), max_lag);
+            router.start_lag_monitor(Duration::from_secs(1));
+        }
+        Ok(router)
+    }
+
+    /// Pool for writes and reads that must see the latest data.
+    pub fn writer(&self) -> &PgPool {
+        &self.inner.primary
+    }
+
+    /// Pool for read-only queries: a fresh-enough replica, or the primary.
+    pub fn reader(&self) -> &PgPool {
+        match self.select_replica() {
+            Some(i) => &self.inner.replicas[i].pool,
+            None => {
+                if !self.inner.replicas.is_empty() {
+                    debug!("🔀 No replica within {:?} lag, reading from primary", self.inner.max_lag);
+                }
+                &self.inner.primary
+            }
+        }
+    }
+
+    /// Current lag of each replica (`None` if unknown).
+    pub fn replica_lags(&self) -> Vec<Option<Duration>> {
+        self.inner
+            .replicas
+            .iter()
+            .map(|r| match r.lag_ms.load(Ordering::Relaxed) {
+                LAG_UNKNOWN => None,
+                ms => Some(Duration::from_millis(ms)),
+            })
+            .collect()
+    }
+
+    /// Sample replica lag every `interval`. Abort the handle to stop.
+    pub fn start_lag_monitor(&self, interval: Duration) -> JoinHandle<()> {
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for (i, replica) in inner.replicas.iter().enumerate() {
+                    let lag = tokio::time::timeout(interval, sqlx::query_scalar::<_, f64>(LAG_QUERY).fetch_one(&replica.pool)).await;
+                    let lag_ms = match lag {
+                        Ok(Ok(secs)) => (secs.max(0.0) * 1000.0) as u64,
+                        Ok(Err(e)) => {
+                            warn!("⚠️ Replica {} lag check failed: {}", i, e);
+                            LAG_UNKNOWN
+                        }
+                        Err(_) => {
+                            warn!("⚠️ Replica {} lag check timed out", i);
+                            LAG_UNKNOWN
+                        }
+                    };
+                    replica.lag_ms.store(lag_ms, Ordering::Relaxed);
+                }
+            }
+        })
+    }
+
+    /// Round-robin over replicas within the lag bound.
+    fn select_replica(&self) -> Option<usize> {
+        let replicas = &self.inner.replicas;
+        if replicas.is_empty() {
+            return None;
+        }
+        let max_lag_ms = self.inner.max_lag.as_millis() as u64;
+        let start = self.inner.next.fetch_add(1, Ordering::Relaxed);
+        (0..replicas.len())
+            .map(|offset| (start + offset) % replicas.len())
+            .find(|&i| replicas[i].lag_ms.load(Ordering::Relaxed) <= max_lag_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::PgPoolOptions;
+
+    fn lazy_pool() -> PgPool {
+        PgPoolOptions::new().connect_lazy("postgres://lanai@localhost/lanai").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reads_skip_lagging_replicas() {
+        let router = DbRouter::with_replicas(lazy_pool(), vec![lazy_pool(), lazy_pool()], Duration::from_secs(1));
+        assert_eq!(router.select_replica(), None);
+
+        router.inner.replicas[0].lag_ms.store(5_000, Ordering::Relaxed);
+        router.inner.replicas[1].lag_ms.store(200, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert_eq!(router.select_replica(), Some(1));
+        }
+
+        router.inner.replicas[0].lag_ms.store(0, Ordering::Relaxed);
+        let picks: Vec<_> = (0..4).filter_map(|_| router.select_replica()).collect();
+        assert!(picks.contains(&0) && picks.contains(&1));
+    }
+}
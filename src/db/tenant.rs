@@ -0,0 +1,126 @@
+//! Tenant-scoped database access for Postgres row-level security
+//!
+//! Every tenant query runs inside a transaction where `app.current_org` is set (with
+//! `set_config(..., true)`, the bind-parameter form of `SET LOCAL`) to the caller's
+//! organization, so RLS policies such as
+//!
+//! ```sql
+//! ALTER TABLE products ENABLE ROW LEVEL SECURITY;
+//! CREATE POLICY tenant_isolation ON products
+//!     USING (org_id = current_setting('app.current_org')::uuid);
+//! ```
+//!
+//! apply consistently. `TenantDb` does not expose its pool, so the only way to reach the
+//! database through it is a `TenantTx` bound to a tenant; the setting is scoped to the
+//! transaction and cannot leak to the next user of the pooled connection.
+//!
+//! ```ignore
+//! async fn list_products(mut tx: TenantTx) -> Result<HttpResponse, actix_web::Error> {
+//!     let products = sqlx::query_as::<_, Product>("SELECT * FROM products")
+//!         .fetch_all(tx.conn())
+//!         .await?;
+//!     tx.commit().await?;
+//!     Ok(HttpResponse::Ok().json(products))
+//! }
+//!
+//! // app.app_data(web::Data::new(TenantDb::new(pool)))
+//! ```
+
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use log::error;
+use sqlx::postgres::{PgConnection, PgPool};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::DbError;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Postgres setting read by RLS policies.
+pub const CURRENT_ORG_SETTING: &str = "app.current_org";
+
+/// Pool wrapper that only hands out tenant-scoped transactions.
+#[derive(Clone)]
+pub struct TenantDb {
+    pool: PgPool,
+}
+
+impl TenantDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Begin a transaction scoped to `tenant`.
+    pub async fn begin(&self, tenant: &TenantContext) -> Result<TenantTx, DbError> {
+        if tenant.org_id.is_nil() {
+            return Err(DbError::MissingTenant);
+        }
+
+        let mut tx = super::pool::begin(&self.pool).await?;
+        sqlx::query("SELECT set_config($1, $2, true)")
+            .bind(CURRENT_ORG_SETTING)
+            .bind(tenant.org_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(TenantTx { tx, org_id: tenant.org_id })
+    }
+}
+
+/// A transaction with `app.current_org` set. Dropping it without `commit` rolls back.
+pub struct TenantTx {
+    tx: Transaction<'static, Postgres>,
+    org_id: Uuid,
+}
+
+impl TenantTx {
+    pub fn org_id(&self) -> Uuid {
+        self.org_id
+    }
+
+    /// Connection to run queries on, e.g. `.fetch_all(tx.conn())`.
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    /// Server-side limit for the remaining statements of this transaction (`SET LOCAL`).
+    pub async fn statement_timeout(&mut self, timeout: std::time::Duration) -> Result<(), DbError> {
+        sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+            .bind(timeout.as_millis().to_string())
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn commit(self) -> Result<(), DbError> {
+        self.tx.commit().await.map_err(DbError::from)
+    }
+
+    pub async fn rollback(self) -> Result<(), DbError> {
+        self.tx.rollback().await.map_err(DbError::from)
+    }
+}
+
+/// Extract a tenant transaction from the request's `TenantContext` and the `TenantDb` app data.
+impl FromRequest for TenantTx {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let tenant = req.extensions().get::<TenantContext>().copied();
+        let db = req.app_data::<web::Data<TenantDb>>().cloned();
+
+        Box::pin(async move {
+            let tenant = tenant.ok_or_else(|| actix_web::error::ErrorForbidden("Tenant context required"))?;
+            let db = db.ok_or_else(|| {
+                error!("❌ TenantTx extractor used without TenantDb app data");
+                actix_web::error::ErrorInternalServerError("Database unavailable")
+            })?;
+
+            db.begin(&tenant).await.map_err(|e| {
+                error!("❌ Failed to open tenant transaction for org {}: {}", tenant.org_id, e);
+                actix_web::error::ErrorServiceUnavailable("Database unavailable")
+            })
+        })
+    }
+}
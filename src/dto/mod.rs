@@ -0,0 +1,447 @@
+//! Versioned API models
+//!
+//! Handlers work with domain structs; each API version has its own DTOs. `ApiModel` keeps
+//! the mapping for every version of one model in one place, so `/v1` and `/v2` handlers
+//! share code and a breaking change is one new DTO plus one `.response::<T>()` line:
+//!
+//! ```ignore
+//! #[derive(Serialize, ApiDto)]
+//! #[dto(name = "Product", version = 2)]
+//! pub struct ProductV2 { ... }
+//!
+//! impl FromDomain<Product> for ProductV2 { ... }
+//!
+//! let products = ApiModel::<Product>::new("Product")
+//!     .response::<ProductV1>()
+//!     .response::<ProductV2>()
+//!     .request::<CreateProductV2>()
+//!     .deprecate(1, Some("Tue, 30 Jun 2026 00:00:00 GMT"));
+//!
+//! async fn get_product(version: ApiVersion, ...) -> LanaiResult<HttpResponse> {
+//!     products.respond(version, &product)
+//! }
+//!
+//! let components = products.openapi();
+//! ```
+//!
+//! The version comes from the `/v{n}/` path segment or the `Api-Version` header.
+//! Deprecated versions and fields are flagged in the exported schemas; responses of
+//! deprecated versions carry `Deprecation` and `Sunset` headers.
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use crate::error::{LanaiError, LanaiResult};
+
+pub use lanai_infrastructure_derive::ApiDto;
+
+pub const API_VERSION_HEADER: &str = "Api-Version";
+
+/// Requested API version, from the `/v{n}/` path segment, else `Api-Version`, else 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion(pub u32);
+
+impl ApiVersion {
+    pub fn from_request(req: &HttpRequest) -> Result<Self, LanaiError> {
+        let from_path = req.path().split('/').find_map(|segment| segment.strip_prefix('v')?.parse().ok());
+        if let Some(version) = from_path.filter(|version| *version > 0) {
+            return Ok(Self(version));
+        }
+        match req.headers().get(API_VERSION_HEADER) {
+            None => Ok(Self(1)),
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().trim_start_matches('v').parse().ok())
+                .filter(|version| *version > 0)
+                .map(Self)
+                .ok_or_else(|| LanaiError::BadRequest(format!("Invalid {} header", API_VERSION_HEADER))),
+        }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl FromRequest for ApiVersion {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(ApiVersion::from_request(req))
+    }
+}
+
+/// Builds a response DTO from a domain struct.
+pub trait FromDomain<D> {
+    fn from_domain(domain: &D) -> Self;
+}
+
+/// Turns a request DTO into a domain struct, filling fields older versions lack.
+pub trait IntoDomain<D> {
+    fn into_domain(self) -> LanaiResult<D>;
+}
+
+/// Description of a DTO, from `#[derive(ApiDto)]`
+pub trait DescribeDto {
+    fn dto_schema() -> DtoSchema;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DtoSchema {
+    pub model: &'static str,
+    pub version: u32,
+    pub fields: Vec<DtoField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DtoField {
+    pub name: &'static str,
+    /// Rust type as written in the struct
+    pub ty: &'static str,
+    /// Version that introduced the field
+    pub since: Option<u32>,
+    /// Set for deprecated fields, with what to use instead (may be empty)
+    pub deprecated: Option<&'static str>,
+}
+
+impl DtoSchema {
+    /// Component name, e.g. `ProductV2`.
+    pub fn component_name(&self) -> String {
+        format!("{}V{}", self.model, self.version)
+    }
+
+    /// OpenAPI 3.1 schema object. Fields map to JSON types by their Rust type; other types
+    /// become `$ref`s to a component of the same name.
+    pub fn openapi(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for field in &self.fields {
+            let (mut schema, optional) = openapi_type(field.ty);
+            if let Some(since) = field.since {
+                schema["x-since-version"] = json!(since);
+            }
+            if let Some(note) = field.deprecated {
+                schema["deprecated"] = json!(true);
+                if !note.is_empty() {
+                    schema["description"] = json!(format!("Deprecated: {}", note));
+                }
+            }
+            if !optional {
+                required.push(field.name);
+            }
+            properties.insert(field.name.to_string(), schema);
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "x-api-version": self.version,
+        })
+    }
+}
+
+/// JSON schema for a Rust type name, and whether it is an `Option`.
+fn openapi_type(ty: &str) -> (Value, bool) {
+    let generic = |wrapper: &str| ty.strip_prefix(wrapper).and_then(|rest| rest.strip_suffix('>'));
+    if let Some(inner) = generic("Option<") {
+        let (mut schema, _) = openapi_type(inner);
+        match schema.get("type").cloned() {
+            Some(Value::String(kind)) => schema["type"] = json!([kind, "null"]),
+            _ => schema = json!({ "oneOf": [schema, { "type": "null" }] }),
+        }
+        return (schema, true);
+    }
+    if let Some(inner) = generic("Vec<") {
+        return (json!({ "type": "array", "items": openapi_type(inner).0 }), false);
+    }
+
+    let name = ty.rsplit("::").next().unwrap_or(ty);
+    let schema = match name {
+        "String" | "&str" | "&'staticstr" => json!({ "type": "string" }),
+        "bool" => json!({ "type": "boolean" }),
+        "i8" | "i16" | "i32" | "u8" | "u16" => json!({ "type": "integer", "format": "int32" }),
+        "i64" | "u32" | "u64" | "isize" | "usize" => json!({ "type": "integer", "format": "int64" }),
+        "f32" | "f64" => json!({ "type": "number" }),
+        "Decimal" => json!({ "type": "string", "format": "decimal" }),
+        "Uuid" => json!({ "type": "string", "format": "uuid" }),
+        "Timestamp" => json!({ "type": "string", "format": "date-time" }),
+        "NaiveDate" => json!({ "type": "string", "format": "date" }),
+        "Value" => json!({}),
+        _ if name.starts_with("DateTime<") => json!({ "type": "string", "format": "date-time" }),
+        _ if name.ends_with("Id") => json!({ "type": "string", "format": "uuid" }),
+        _ => json!({ "$ref": format!("#/components/schemas/{}", name) }),
+    };
+    (schema, false)
+}
+
+/// `components` object of an OpenAPI document.
+pub fn openapi_components(schemas: impl IntoIterator<Item = DtoSchema>) -> Value {
+    let map = schemas.into_iter().map(|schema| (schema.component_name(), schema.openapi())).collect();
+    json!({ "schemas": Value::Object(map) })
+}
+
+type ToDto<D> = Box<dyn Fn(&D) -> Result<Value, serde_json::Error> + Send + Sync>;
+type FromDto<D> = Box<dyn Fn(Value) -> LanaiResult<D> + Send + Sync>;
+
+struct Versions<D> {
+    response: Option<(DtoSchema, ToDto<D>)>,
+    request: Option<(DtoSchema, FromDto<D>)>,
+    /// `Some(sunset)` once deprecated
+    deprecated: Option<Option<String>>,
+}
+
+impl<D> Default for Versions<D> {
+    fn default() -> Self {
+        Self { response: None, request: None, deprecated: None }
+    }
+}
+
+struct Inner<D> {
+    model: String,
+    versions: BTreeMap<u32, Versions<D>>,
+}
+
+/// Mapping of one domain model to its DTOs, per API version. Cheap to clone.
+pub struct ApiModel<D> {
+    inner: Arc<Inner<D>>,
+}
+
+impl<D> Clone for ApiModel<D> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<D: 'static> ApiModel<D> {
+    pub fn new(model: &str) -> Self {
+        Self { inner: Arc::new(Inner { model: model.to_string(), versions: BTreeMap::new() }) }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner<D>)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("ApiModel is configured before it is shared"));
+        self
+    }
+
+    /// Serve `T` to clients asking for `T`'s version.
+    pub fn response<T>(self) -> Self
+    where
+        T: FromDomain<D> + Serialize + DescribeDto,
+    {
+        let schema = T::dto_schema();
+        let version = schema.version;
+        self.update(|inner| {
+            let to_dto: ToDto<D> = Box::new(|domain| serde_json::to_value(T::from_domain(domain)));
+            inner.versions.entry(version).or_default().response = Some((schema, to_dto));
+        })
+    }
+
+    /// Accept `T` as the request body of `T`'s version.
+    pub fn request<T>(self) -> Self
+    where
+        T: IntoDomain<D> + DeserializeOwned + DescribeDto,
+    {
+        let schema = T::dto_schema();
+        let version = schema.version;
+        let name = schema.component_name();
+        self.update(|inner| {
+            let from_dto: FromDto<D> = Box::new(move |body| {
+                let dto: T = serde_json::from_value(body)
+                    .map_err(|e| LanaiError::BadRequest(format!("Invalid {} body: {}", name, e)))?;
+                dto.into_domain()
+            });
+            inner.versions.entry(version).or_default().request = Some((schema, from_dto));
+        })
+    }
+
+    /// Flag `version` as deprecated, with an optional HTTP-date after which it is removed.
+    pub fn deprecate(self, version: u32, sunset: Option<&str>) -> Self {
+        self.update(|inner| {
+            inner.versions.entry(version).or_default().deprecated = Some(sunset.map(str::to_string));
+        })
+    }
+
+    fn versions(&self, version: ApiVersion) -> LanaiResult<&Versions<D>> {
+        self.inner.versions.get(&version.0).ok_or_else(|| {
+            let supported: Vec<String> = self.inner.versions.keys().map(|v| format!("v{}", v)).collect();
+            LanaiError::NotFound(format!(
+                "{} is not available in API {} (supported: {})",
+                self.inner.model,
+                version,
+                supported.join(", ")
+            ))
+        })
+    }
+
+    pub fn to_dto(&self, version: ApiVersion, domain: &D) -> LanaiResult<Value> {
+        let (_, to_dto) = self.versions(version)?.response.as_ref().ok_or_else(|| self.unsupported(version))?;
+        to_dto(domain).map_err(LanaiError::internal)
+    }
+
+    pub fn to_dtos(&self, version: ApiVersion, domains: &[D]) -> LanaiResult<Value> {
+        let (_, to_dto) = self.versions(version)?.response.as_ref().ok_or_else(|| self.unsupported(version))?;
+        let dtos = domains.iter().map(to_dto).collect::<Result<Vec<_>, _>>().map_err(LanaiError::internal)?;
+        Ok(Value::Array(dtos))
+    }
+
+    pub fn from_dto(&self, version: ApiVersion, body: Value) -> LanaiResult<D> {
+        let (_, from_dto) = self.versions(version)?.request.as_ref().ok_or_else(|| self.unsupported(version))?;
+        from_dto(body)
+    }
+
+    fn unsupported(&self, version: ApiVersion) -> LanaiError {
+        LanaiError::NotFound(format!("{} is not available in API {}", self.inner.model, version))
+    }
+
+    /// `200 OK` with the DTO of `version`.
+    pub fn respond(&self, version: ApiVersion, domain: &D) -> LanaiResult<HttpResponse> {
+        let body = self.to_dto(version, domain)?;
+        Ok(self.response_builder(version).json(body))
+    }
+
+    /// `200 OK` with `Deprecation`/`Sunset` headers when `version` is deprecated.
+    pub fn response_builder(&self, version: ApiVersion) -> actix_web::HttpResponseBuilder {
+        let mut builder = HttpResponse::Ok();
+        if let Some(Some(deprecated)) = self.inner.versions.get(&version.0).map(|v| v.deprecated.as_ref()) {
+            builder.insert_header(("Deprecation", "true"));
+            if let Some(sunset) = deprecated {
+                builder.insert_header(("Sunset", sunset.as_str()));
+            }
+        }
+        builder
+    }
+
+    /// Schemas of all registered DTOs. Give request DTOs their own `name` so they do not
+    /// share a component name with the response of the same version.
+    pub fn schemas(&self) -> Vec<DtoSchema> {
+        self.inner
+            .versions
+            .values()
+            .flat_map(|v| {
+                v.response.iter().map(|(schema, _)| schema).chain(v.request.iter().map(|(schema, _)| schema))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// `components` object for this model; schemas of deprecated versions are flagged.
+    pub fn openapi(&self) -> Value {
+        let mut components = openapi_components(self.schemas());
+        for versions in self.inner.versions.values() {
+            let Some(sunset) = &versions.deprecated else { continue };
+            let schemas = versions.response.iter().map(|(schema, _)| schema);
+            for schema in schemas.chain(versions.request.iter().map(|(schema, _)| schema)) {
+                let component = &mut components["schemas"][schema.component_name()];
+                component["deprecated"] = json!(true);
+                if let Some(sunset) = sunset {
+                    component["x-sunset"] = json!(sunset);
+                }
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    struct Product {
+        name: String,
+        price_cents: i64,
+    }
+
+    #[derive(Serialize, ApiDto)]
+    #[dto(name = "Product", version = 1)]
+    struct ProductV1 {
+        name: String,
+        price: f64,
+    }
+
+    #[derive(Serialize, Deserialize, ApiDto)]
+    #[dto(name = "Product", version = 2)]
+    struct ProductV2 {
+        name: String,
+        #[dto(deprecated = "use `price_cents`")]
+        price: Option<f64>,
+        #[dto(since = 2)]
+        price_cents: i64,
+    }
+
+    impl FromDomain<Product> for ProductV1 {
+        fn from_domain(product: &Product) -> Self {
+            Self { name: product.name.clone(), price: product.price_cents as f64 / 100.0 }
+        }
+    }
+
+    impl FromDomain<Product> for ProductV2 {
+        fn from_domain(product: &Product) -> Self {
+            Self { name: product.name.clone(), price: None, price_cents: product.price_cents }
+        }
+    }
+
+    impl IntoDomain<Product> for ProductV2 {
+        fn into_domain(self) -> LanaiResult<Product> {
+            Ok(Product { name: self.name, price_cents: self.price_cents })
+        }
+    }
+
+    #[test]
+    fn test_version_mapping() {
+        let model = ApiModel::<Product>::new("Product")
+            .response::<ProductV1>()
+            .response::<ProductV2>()
+            .deprecate(1, Some("Tue, 30 Jun 2026 00:00:00 GMT"));
+        let product = Product { name: "Coffee".to_string(), price_cents: 450 };
+
+        let req = TestRequest::with_uri("/v1/products/42").to_http_request();
+        let v1 = ApiVersion::from_request(&req).unwrap();
+        assert_eq!(model.to_dto(v1, &product).unwrap(), json!({"name": "Coffee", "price": 4.5}));
+        let response = model.respond(v1, &product).unwrap();
+        assert_eq!(response.headers().get("Deprecation").unwrap(), "true");
+
+        let req = TestRequest::with_uri("/products/42").insert_header((API_VERSION_HEADER, "2")).to_http_request();
+        let v2 = ApiVersion::from_request(&req).unwrap();
+        assert_eq!(model.to_dto(v2, &product).unwrap()["price_cents"], json!(450));
+        assert!(model.respond(v2, &product).unwrap().headers().get("Deprecation").is_none());
+        assert!(matches!(model.to_dto(ApiVersion(3), &product), Err(LanaiError::NotFound(_))));
+        assert!(model.from_dto(v2, json!({"name": "Tea", "price_cents": 300})).is_err());
+    }
+
+    #[test]
+    fn test_openapi_schema() {
+        let schema = ProductV2::dto_schema();
+        assert_eq!(schema.fields[1], DtoField {
+            name: "price",
+            ty: "Option<f64>",
+            since: None,
+            deprecated: Some("use `price_cents`")
+        });
+
+        let model =
+            ApiModel::<Product>::new("Product").response::<ProductV1>().request::<ProductV2>().deprecate(1, None);
+        let components = model.openapi();
+        assert_eq!(components["schemas"]["ProductV1"]["deprecated"], json!(true));
+        let v2 = &components["schemas"]["ProductV2"];
+        assert_eq!(v2["required"], json!(["name", "price_cents"]));
+        assert_eq!(v2["properties"]["price"]["type"], json!(["number", "null"]));
+        assert_eq!(v2["properties"]["price"]["deprecated"], json!(true));
+        assert_eq!(v2["properties"]["price_cents"]["x-since-version"], json!(2));
+
+        let product = model.from_dto(ApiVersion(2), json!({"name": "Tea", "price_cents": 300})).unwrap();
+        assert_eq!((product.name.as_str(), product.price_cents), ("Tea", 300));
+        assert!(matches!(model.from_dto(ApiVersion(2), json!({"name": "Tea"})), Err(LanaiError::BadRequest(_))));
+    }
+}
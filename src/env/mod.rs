@@ -0,0 +1,371 @@
+//! Typed environment variable registry
+//!
+//! Subsystems declare the variables they read as `EnvVar` constants (`cors::ENV_VARS`,
+//! `messaging::ENV_VARS`...). A service collects them in an `EnvRegistry`, adds its own,
+//! and validates them all at startup, so a missing or malformed variable fails the deploy
+//! instead of silently falling back to a development default:
+//!
+//! ```ignore
+//! let env = EnvRegistry::builtin()
+//!     .require(DATABASE_URL_ENV)
+//!     .declare(EnvVar::new("BILLING_INVOICE_PREFIX", VarType::String).default_value("INV"));
+//!
+//! ServerBuilder::new("lanai-billing").env(env.clone()).run(routes).await?;
+//! // GET /internal/admin/env lists the effective values, secrets redacted
+//! AdminApi::new().env(env);
+//! ```
+//!
+//! `LANAI_ENV=production` additionally enforces variables declared `required_in_production`.
+
+use log::{error, info};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Deployment environment (`production`, `staging`, `development`...)
+pub const LANAI_ENV_ENV: &str = "LANAI_ENV";
+
+/// Shown instead of the value of secret variables and URL passwords.
+const REDACTED: &str = "********";
+
+/// Whether `LANAI_ENV` names a production deployment (`production` or `prod`).
+pub fn is_production() -> bool {
+    std::env::var(LANAI_ENV_ENV).is_ok_and(|env| is_production_name(&env))
+}
+
+fn is_production_name(env: &str) -> bool {
+    env.eq_ignore_ascii_case("production") || env.eq_ignore_ascii_case("prod")
+}
+
+/// Environment variable error types
+#[derive(Debug, Error)]
+pub enum EnvError {
+    #[error("Invalid environment: {}", format_problems(.0))]
+    Invalid(Vec<(String, String)>),
+}
+
+fn format_problems(problems: &[(String, String)]) -> String {
+    problems.iter().map(|(name, reason)| format!("{} ({})", name, reason)).collect::<Vec<_>>().join(", ")
+}
+
+/// What a variable's value must parse as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VarType {
+    String,
+    /// `true`/`false`, `1`/`0`, `yes`/`no`
+    Bool,
+    Integer,
+    Float,
+    /// An absolute URL
+    Url,
+    /// Comma-separated URLs, each optionally `label=url`
+    UrlList,
+    /// Comma-separated values
+    List,
+}
+
+impl VarType {
+    fn check(self, value: &str) -> Result<(), String> {
+        match self {
+            Self::String | Self::List => Ok(()),
+            Self::Bool => match value.to_ascii_lowercase().as_str() {
+                "true" | "false" | "1" | "0" | "yes" | "no" => Ok(()),
+                _ => Err("expected a boolean".to_string()),
+            },
+            Self::Integer => value.trim().parse::<i64>().map(|_| ()).map_err(|_| "expected an integer".to_string()),
+            Self::Float => value.trim().parse::<f64>().map(|_| ()).map_err(|_| "expected a number".to_string()),
+            Self::Url => check_url(value.trim()),
+            Self::UrlList => list_entries(value).try_for_each(|entry| check_url(unlabelled(entry))),
+        }
+    }
+}
+
+fn list_entries(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty())
+}
+
+/// The URL of a `label=url` list entry.
+fn unlabelled(entry: &str) -> &str {
+    match entry.split_once('=') {
+        Some((label, url)) if !label.contains("://") => url.trim(),
+        _ => entry,
+    }
+}
+
+fn check_url(value: &str) -> Result<(), String> {
+    reqwest::Url::parse(value).map(|_| ()).map_err(|e| format!("invalid URL '{}': {}", redact_url(value), e))
+}
+
+/// `value` with the password of any URL in it replaced.
+fn redact_url(value: &str) -> String {
+    match reqwest::Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some(REDACTED));
+            url.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Declaration of one environment variable.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvVar {
+    pub name: &'static str,
+    pub var_type: VarType,
+    pub description: &'static str,
+    pub required: bool,
+    /// Required when `LANAI_ENV=production`, e.g. where the fallback only suits development.
+    pub required_in_production: bool,
+    /// Value used when the variable is unset, as the reading code applies it.
+    pub default: Option<&'static str>,
+    /// Never printed or reported.
+    pub secret: bool,
+}
+
+impl EnvVar {
+    pub const fn new(name: &'static str, var_type: VarType) -> Self {
+        Self {
+            name,
+            var_type,
+            description: "",
+            required: false,
+            required_in_production: false,
+            default: None,
+            secret: false,
+        }
+    }
+
+    pub const fn description(mut self, description: &'static str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub const fn required_in_production(mut self) -> Self {
+        self.required_in_production = true;
+        self
+    }
+
+    pub const fn default_value(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub const fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+}
+
+/// Where an effective value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    Env,
+    Default,
+    Unset,
+}
+
+/// Effective value of one variable, redacted.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvEntry {
+    pub name: String,
+    pub var_type: VarType,
+    pub description: String,
+    pub source: ValueSource,
+    pub value: Option<String>,
+    pub secret: bool,
+    /// Why the value is missing or invalid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+/// Every declared variable with its effective value.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvReport {
+    pub environment: Option<String>,
+    pub production: bool,
+    pub vars: Vec<EnvEntry>,
+}
+
+impl EnvReport {
+    /// `(name, reason)` of each missing or invalid variable.
+    pub fn problems(&self) -> Vec<(String, String)> {
+        self.vars
+            .iter()
+            .filter_map(|entry| entry.problem.clone().map(|problem| (entry.name.clone(), problem)))
+            .collect()
+    }
+
+    /// Log one line per variable, then every problem.
+    pub fn log_summary(&self) {
+        info!(
+            "🧾 Environment ({}): {} variable(s) declared",
+            self.environment.as_deref().unwrap_or("unset"),
+            self.vars.len()
+        );
+        for entry in &self.vars {
+            let value = entry.value.as_deref().unwrap_or("-");
+            match entry.source {
+                ValueSource::Env => info!("   {} = {}", entry.name, value),
+                ValueSource::Default => info!("   {} = {} (default)", entry.name, value),
+                ValueSource::Unset => info!("   {} is not set", entry.name),
+            }
+        }
+        for (name, problem) in self.problems() {
+            error!("❌ {}: {}", name, problem);
+        }
+    }
+}
+
+/// Declared variables by name; later declarations of a name replace earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct EnvRegistry {
+    vars: BTreeMap<&'static str, EnvVar>,
+}
+
+impl EnvRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The variables read by this crate's subsystems.
+    pub fn builtin() -> Self {
+        Self::new()
+            .declare_all(crate::cors::ENV_VARS)
+            .declare_all(crate::messaging::ENV_VARS)
+            .declare_all(crate::messaging::security::ENV_VARS)
+            .declare_all(crate::cache::ENV_VARS)
+            .declare_all(crate::db::ENV_VARS)
+            .declare_all(crate::health::startup::ENV_VARS)
+            .declare_all(crate::middleware::service_token::ENV_VARS)
+            .declare_all(crate::observability::ENV_VARS)
+            .declare(EnvVar::new(LANAI_ENV_ENV, VarType::String).description("Deployment environment"))
+    }
+
+    pub fn declare(mut self, var: EnvVar) -> Self {
+        self.vars.insert(var.name, var);
+        self
+    }
+
+    pub fn declare_all(self, vars: &[EnvVar]) -> Self {
+        vars.iter().cloned().fold(self, Self::declare)
+    }
+
+    /// Make a declared variable required, e.g. `DATABASE_URL` in a service that needs a
+    /// database. Unknown names are declared as required strings.
+    pub fn require(mut self, name: &'static str) -> Self {
+        self.vars.entry(name).or_insert_with(|| EnvVar::new(name, VarType::String)).required = true;
+        self
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = &EnvVar> {
+        self.vars.values()
+    }
+
+    /// Effective value of every variable in the process environment.
+    pub fn report(&self) -> EnvReport {
+        let environment = std::env::var(LANAI_ENV_ENV).ok();
+        let production = environment.as_deref().is_some_and(is_production_name);
+        self.report_from(|name| std::env::var(name).ok(), environment, production)
+    }
+
+    /// `report`, failing if any variable is missing or invalid. Logs the summary either way.
+    pub fn validate(&self) -> Result<EnvReport, EnvError> {
+        let report = self.report();
+        report.log_summary();
+        match report.problems() {
+            problems if problems.is_empty() => Ok(report),
+            problems => Err(EnvError::Invalid(problems)),
+        }
+    }
+
+    fn report_from(
+        &self,
+        lookup: impl Fn(&str) -> Option<String>,
+        environment: Option<String>,
+        production: bool,
+    ) -> EnvReport {
+        let vars = self
+            .vars
+            .values()
+            .map(|var| {
+                let value = lookup(var.name).filter(|value| !value.trim().is_empty());
+                let (source, effective) = match (&value, var.default) {
+                    (Some(value), _) => (ValueSource::Env, Some(value.clone())),
+                    (None, Some(default)) => (ValueSource::Default, Some(default.to_string())),
+                    (None, None) => (ValueSource::Unset, None),
+                };
+                let problem = match &value {
+                    Some(value) => var.var_type.check(value).err(),
+                    None if var.required => Some("required but not set".to_string()),
+                    None if var.required_in_production && production => {
+                        Some("required in production but not set".to_string())
+                    }
+                    None => None,
+                };
+                let shown = effective.map(|value| match var.var_type {
+                    _ if var.secret => REDACTED.to_string(),
+                    VarType::Url => redact_url(&value),
+                    VarType::UrlList => {
+                        list_entries(&value).map(redact_url).collect::<Vec<_>>().join(",")
+                    }
+                    _ => value,
+                });
+                EnvEntry {
+                    name: var.name.to_string(),
+                    var_type: var.var_type,
+                    description: var.description.to_string(),
+                    source,
+                    value: shown,
+                    secret: var.secret,
+                    problem,
+                }
+            })
+            .collect();
+        EnvReport { environment, production, vars }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn registry() -> EnvRegistry {
+        EnvRegistry::new()
+            .declare(EnvVar::new("DB_URL", VarType::Url).required())
+            .declare(EnvVar::new("ORIGINS", VarType::List).required_in_production())
+            .declare(EnvVar::new("POOL_SIZE", VarType::Integer).default_value("10"))
+            .declare(EnvVar::new("TOKEN", VarType::String).secret())
+    }
+
+    fn report(vars: &[(&str, &str)], production: bool) -> EnvReport {
+        let env: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        registry().report_from(|name| env.get(name).cloned(), None, production)
+    }
+
+    #[test]
+    fn test_report_redacts_and_applies_defaults() {
+        let report = report(&[("DB_URL", "postgres://app:hunter2@db:5432/app"), ("TOKEN", "abc")], false);
+        assert!(report.problems().is_empty());
+
+        let values: HashMap<_, _> = report.vars.iter().map(|e| (e.name.as_str(), e.value.clone())).collect();
+        assert_eq!(values["DB_URL"].as_deref(), Some("postgres://app:********@db:5432/app"));
+        assert_eq!(values["TOKEN"].as_deref(), Some(REDACTED));
+        assert_eq!(values["POOL_SIZE"].as_deref(), Some("10"));
+        assert_eq!(values["ORIGINS"], None);
+    }
+
+    #[test]
+    fn test_report_lists_problems() {
+        let report = report(&[("POOL_SIZE", "ten")], true);
+        let problems: Vec<_> = report.problems().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(problems, vec!["DB_URL", "ORIGINS", "POOL_SIZE"]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Domain error codes
+//!
+//! Services declare their domain errors as namespaced codes (`INV-001`) with an HTTP
+//! status, a title and a description. A code becomes a `LanaiError::Coded`, so it renders
+//! as a regular problem-details body whose `code` the frontend maps to its own copy:
+//!
+//! ```ignore
+//! lanai_infrastructure::error_codes! {
+//!     /// Inventory errors
+//!     pub mod inventory_errors("INV") {
+//!         INSUFFICIENT_STOCK = "INV-001", 409, "Insufficient stock",
+//!             "The requested quantity exceeds the stock on hand";
+//!         UNKNOWN_WAREHOUSE = "INV-002", 404, "Unknown warehouse", "The warehouse does not exist";
+//!     }
+//! }
+//!
+//! ErrorCatalog::global().register(inventory_errors::ALL);
+//!
+//! return Err(inventory_errors::INSUFFICIENT_STOCK.with_detail(format!("Only {} left", on_hand)));
+//! ```
+//!
+//! Codes are checked at compile time (namespace prefix, status 400–599) and for
+//! uniqueness on registration. `ErrorCatalog::configure` serves every registered code
+//! as JSON at `GET /error-codes` for the frontend's error-mapping tables.
+
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+use super::LanaiError;
+
+/// A documented domain error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub status: u16,
+    pub title: &'static str,
+    pub description: &'static str,
+}
+
+impl ErrorCode {
+    /// Fails const evaluation unless `code` is `{namespace}-...` and `status` an error status.
+    pub const fn new(
+        namespace: &'static str,
+        code: &'static str,
+        status: u16,
+        title: &'static str,
+        description: &'static str,
+    ) -> Self {
+        assert!(has_namespace(code, namespace), "error code must start with its namespace and '-'");
+        assert!(status >= 400 && status <= 599, "error code status must be 4xx or 5xx");
+        Self { code, status, title, description }
+    }
+
+    /// The error, with the description as detail.
+    pub fn error(self) -> LanaiError {
+        LanaiError::Coded { code: self, detail: None }
+    }
+
+    /// The error with a detail specific to this occurrence.
+    pub fn with_detail(self, detail: impl Into<String>) -> LanaiError {
+        LanaiError::Coded { code: self, detail: Some(detail.into()) }
+    }
+}
+
+impl From<ErrorCode> for LanaiError {
+    fn from(code: ErrorCode) -> Self {
+        code.error()
+    }
+}
+
+const fn has_namespace(code: &str, namespace: &str) -> bool {
+    let (code, namespace) = (code.as_bytes(), namespace.as_bytes());
+    if namespace.is_empty() || code.len() <= namespace.len() + 1 || code[namespace.len()] != b'-' {
+        return false;
+    }
+    let mut i = 0;
+    while i < namespace.len() {
+        if code[i] != namespace[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Define a module of `ErrorCode` constants in one namespace, plus `ALL` listing them.
+#[macro_export]
+macro_rules! error_codes {
+    (
+        $(#[$meta:meta])*
+        $vis:vis mod $module:ident($namespace:literal) {
+            $(
+                $(#[$code_meta:meta])*
+                $name:ident = $code:literal, $status:literal, $title:literal, $description:literal;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis mod $module {
+            $(
+                $(#[$code_meta])*
+                pub const $name: $crate::error::ErrorCode =
+                    $crate::error::ErrorCode::new($namespace, $code, $status, $title, $description);
+            )*
+
+            /// Every code of this namespace, for `ErrorCatalog::register`.
+            pub const ALL: &[$crate::error::ErrorCode] = &[$($name),*];
+        }
+    };
+}
+
+/// Registered error codes, by code.
+#[derive(Default)]
+pub struct ErrorCatalog {
+    codes: RwLock<BTreeMap<&'static str, ErrorCode>>,
+}
+
+static GLOBAL: OnceLock<ErrorCatalog> = OnceLock::new();
+
+impl ErrorCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global() -> &'static ErrorCatalog {
+        GLOBAL.get_or_init(ErrorCatalog::new)
+    }
+
+    /// Add `codes` (usually a module's `ALL`). Registering the same code twice is fine;
+    /// two different definitions of one code panic, as that is a programming error.
+    pub fn register(&self, codes: &[ErrorCode]) -> &Self {
+        let mut registered = self.codes.write().unwrap_or_else(|e| e.into_inner());
+        for code in codes {
+            if let Some(existing) = registered.insert(code.code, *code) {
+                assert_eq!(existing, *code, "error code {} is defined twice", code.code);
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, code: &str) -> Option<ErrorCode> {
+        self.codes.read().unwrap_or_else(|e| e.into_inner()).get(code).copied()
+    }
+
+    /// Registered codes, sorted by code.
+    pub fn codes(&self) -> Vec<ErrorCode> {
+        self.codes.read().unwrap_or_else(|e| e.into_inner()).values().copied().collect()
+    }
+
+    /// Mount `GET /error-codes`, listing the global catalog.
+    pub fn configure(cfg: &mut web::ServiceConfig) {
+        cfg.route("/error-codes", web::get().to(|| async { HttpResponse::Ok().json(ErrorCatalog::global().codes()) }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+
+    crate::error_codes! {
+        mod inventory_errors("INV") {
+            INSUFFICIENT_STOCK = "INV-001", 409, "Insufficient stock", "The quantity exceeds the stock on hand";
+            UNKNOWN_WAREHOUSE = "INV-002", 404, "Unknown warehouse", "The warehouse does not exist";
+        }
+    }
+
+    #[test]
+    fn test_codes_render_as_problems_and_register() {
+        let problem = inventory_errors::INSUFFICIENT_STOCK.with_detail("Only 2 left").problem();
+        assert_eq!(problem.code, "INV-001");
+        assert_eq!(problem.problem_type, "urn:lanai:problem:INV-001");
+        assert_eq!((problem.status, problem.title.as_str()), (409, "Insufficient stock"));
+        assert_eq!(problem.detail, "Only 2 left");
+
+        let error = LanaiError::from(inventory_errors::UNKNOWN_WAREHOUSE);
+        assert_eq!(error.status_code().as_u16(), 404);
+        assert_eq!(error.to_string(), "The warehouse does not exist");
+
+        let catalog = ErrorCatalog::new();
+        catalog.register(inventory_errors::ALL).register(inventory_errors::ALL);
+        assert_eq!(catalog.codes().len(), 2);
+        assert_eq!(catalog.get("INV-002"), Some(inventory_errors::UNKNOWN_WAREHOUSE));
+        assert!(has_namespace("INV-001", "INV") && !has_namespace("INVX-001", "INV") && !has_namespace("INV-", "INV"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Problem Details Errors
+//!
+//! `LanaiError` is the error type for HTTP handlers. It renders as an RFC 7807
+//! `application/problem+json` body with a machine-readable `code`, the current `trace_id`
+//! and, for validation failures, one entry per invalid field:
+//!
+//! ```json
+//! {
+//!   "type": "urn:lanai:problem:validation_failed",
+//!   "title": "Validation failed",
+//!   "status": 400,
+//!   "detail": "One or more fields are invalid",
+//!   "code": "validation_failed",
+//!   "errors": [{ "field": "email", "code": "email", "message": "must be a valid email" }],
+//!   "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736"
+//! }
+//! ```
+//!
+//! `title`, `detail` and field messages are translated to the request's language when
+//! `i18n::LocalizationMiddleware` is installed.
+//!
+//! Infrastructure errors (sqlx, Redis, NATS, circuit breaker) convert with `?`. Details of
+//! internal errors are logged, never returned to the client. Domain errors with their own
+//! documented codes are declared with `error_codes!` (see `codes`).
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use log::error;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::db::DbError;
+use crate::i18n::{Catalog, Locale};
+use crate::messaging::NatsError;
+use crate::resilience::{CircuitBreakerError, CircuitBreakerOutcome};
+
+pub mod codes;
+
+pub use codes::{ErrorCatalog, ErrorCode};
+
+pub type LanaiResult<T> = Result<T, LanaiError>;
+
+/// One invalid field of a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldViolation {
+    /// Dot-separated path (`items[0].quantity`).
+    pub field: String,
+    /// Name of the violated constraint (`length`, `email`, `required`, ...).
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Constraint parameters (`min`, `max`, ...).
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+impl FieldViolation {
+    pub fn new(field: impl Into<String>, code: impl Into<String>) -> Self {
+        Self { field: field.into(), code: code.into(), message: None, params: serde_json::Map::new() }
+    }
+
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn param(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+}
+
+/// RFC 7807 response body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldViolation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+/// HTTP-facing error types
+#[derive(Debug, Error)]
+pub enum LanaiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("One or more fields are invalid")]
+    Validation(Vec<FieldViolation>),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// `If-Match` names a version that is no longer current.
+    #[error("{0}")]
+    PreconditionFailed(String),
+
+    /// The request must carry `If-Match`.
+    #[error("{0}")]
+    PreconditionRequired(String),
+
+    #[error("Rate limit exceeded")]
+    RateLimited { retry_after_secs: Option<u64> },
+
+    #[error("Quota exceeded for {metric} (limit {limit})")]
+    QuotaExceeded { metric: String, limit: u64, retry_after_secs: Option<u64> },
+
+    #[error("{0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Timeout(String),
+
+    /// The message is logged, not returned.
+    #[error("{0}")]
+    Internal(String),
+
+    /// A documented domain error (see `codes`); `detail` replaces the code's description.
+    #[error("{}", .detail.as_deref().unwrap_or(.code.description))]
+    Coded { code: ErrorCode, detail: Option<String> },
+}
+
+impl LanaiError {
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        Self::Internal(e.to_string())
+    }
+
+    /// Machine-readable code, stable across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::Validation(_) => "validation_failed",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::PreconditionFailed(_) => "precondition_failed",
+            Self::PreconditionRequired(_) => "precondition_required",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::QuotaExceeded { .. } => "quota_exceeded",
+            Self::Unavailable(_) => "service_unavailable",
+            Self::Timeout(_) => "timeout",
+            Self::Internal(_) => "internal_error",
+            Self::Coded { code, .. } => code.code,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "Bad request",
+            Self::Validation(_) => "Validation failed",
+            Self::Unauthorized(_) => "Unauthorized",
+            Self::Forbidden(_) => "Forbidden",
+            Self::NotFound(_) => "Not found",
+            Self::Conflict(_) => "Conflict",
+            Self::PreconditionFailed(_) => "Precondition failed",
+            Self::PreconditionRequired(_) => "Precondition required",
+            Self::RateLimited { .. } => "Too many requests",
+            Self::QuotaExceeded { .. } => "Quota exceeded",
+            Self::Unavailable(_) => "Service unavailable",
+            Self::Timeout(_) => "Timeout",
+            Self::Internal(_) => "Internal server error",
+            Self::Coded { code, .. } => code.title,
+        }
+    }
+
+    pub fn problem(&self) -> ProblemDetails {
+        let detail = match self {
+            Self::Internal(_) => "An unexpected error occurred".to_string(),
+            other => other.to_string(),
+        };
+
+        ProblemDetails {
+            problem_type: format!("urn:lanai:problem:{}", self.code()),
+            title: self.title().to_string(),
+            status: self.status_code().as_u16(),
+            detail,
+            code: self.code().to_string(),
+            errors: match self {
+                Self::Validation(violations) => violations.clone(),
+                _ => Vec::new(),
+            },
+            trace_id: crate::observability::current_trace_id(),
+        }
+    }
+}
+
+impl ResponseError for LanaiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) | Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            Self::RateLimited { .. } | Self::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Coded { code, .. } => StatusCode::from_u16(code.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let Self::Internal(message) = self {
+            error!("❌ Internal error: {}", message);
+        }
+
+        self.respond(&self.problem())
+    }
+}
+
+impl LanaiError {
+    /// Response with the problem body translated to `locale` (see `i18n`).
+    pub fn localized_response(&self, locale: Locale) -> HttpResponse {
+        self.respond(&Catalog::global().localize_problem(self, self.problem(), locale))
+    }
+
+    fn respond(&self, problem: &ProblemDetails) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        response.insert_header((header::CONTENT_TYPE, "application/problem+json"));
+        if let Self::RateLimited { retry_after_secs: Some(secs) } | Self::QuotaExceeded { retry_after_secs: Some(secs), .. } = self {
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        response.body(serde_json::to_string(problem).unwrap_or_default())
+    }
+}
+
+impl From<sqlx::Error> for LanaiError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::RowNotFound => Self::NotFound("Resource not found".to_string()),
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                Self::Conflict("Resource already exists".to_string())
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                Self::Conflict("Resource is referenced by or references a missing resource".to_string())
+            }
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+                error!("❌ Database unavailable: {}", e);
+                Self::Unavailable("Database unavailable".to_string())
+            }
+            _ => Self::internal(e),
+        }
+    }
+}
+
+impl From<DbError> for LanaiError {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::Sqlx(e) => e.into(),
+            DbError::MissingTenant => Self::Forbidden("Tenant context required".to_string()),
+            DbError::Timeout(_) => Self::Timeout("Database did not respond in time".to_string()),
+            DbError::VersionMismatch(_) => Self::PreconditionFailed("Resource was modified by another request".to_string()),
+            DbError::Connection(_) => {
+                error!("❌ Database unavailable: {}", e);
+                Self::Unavailable("Database unavailable".to_string())
+            }
+            other => Self::internal(other),
+        }
+    }
+}
+
+impl From<redis::RedisError> for LanaiError {
+    fn from(e: redis::RedisError) -> Self {
+        if e.is_timeout() {
+            Self::Timeout("Cache did not respond in time".to_string())
+        } else if e.is_connection_refusal() || e.is_connection_dropped() || e.is_io_error() {
+            error!("❌ Redis unavailable: {}", e);
+            Self::Unavailable("Cache unavailable".to_string())
+        } else {
+            Self::internal(e)
+        }
+    }
+}
+
+impl From<NatsError> for LanaiError {
+    fn from(e: NatsError) -> Self {
+        match e {
+            NatsError::Timeout(subject, _) => Self::Timeout(format!("No reply from '{}'", subject)),
+            NatsError::NotInitialized | NatsError::ConnectionError(_) => {
+                error!("❌ Messaging unavailable: {}", e);
+                Self::Unavailable("Messaging unavailable".to_string())
+            }
+            other => Self::internal(other),
+        }
+    }
+}
+
+impl From<CircuitBreakerError> for LanaiError {
+    fn from(e: CircuitBreakerError) -> Self {
+        match e {
+            CircuitBreakerError::Open => Self::Unavailable("Dependency unavailable, please retry later".to_string()),
+            CircuitBreakerError::Timeout => Self::Timeout("Dependency did not respond in time".to_string()),
+            CircuitBreakerError::OperationFailed(message) => Self::Internal(message),
+        }
+    }
+}
+
+impl<E: Into<LanaiError>> From<CircuitBreakerOutcome<E>> for LanaiError {
+    fn from(outcome: CircuitBreakerOutcome<E>) -> Self {
+        match outcome {
+            CircuitBreakerOutcome::CircuitOpen => CircuitBreakerError::Open.into(),
+            CircuitBreakerOutcome::OperationError(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn test_validation_problem_body() {
+        let error = LanaiError::Validation(vec![FieldViolation::new("email", "email").message("must be a valid email")]);
+        let response = error.error_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/problem+json");
+
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["type"], "urn:lanai:problem:validation_failed");
+        assert_eq!(body["status"], 400);
+        assert_eq!(body["errors"][0]["field"], "email");
+        assert!(body.get("trace_id").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_internal_details_are_hidden() {
+        let response = LanaiError::internal("connection string postgres://secret").error_response();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("secret"));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert!(matches!(LanaiError::from(sqlx::Error::RowNotFound), LanaiError::NotFound(_)));
+        assert!(matches!(LanaiError::from(CircuitBreakerError::Open), LanaiError::Unavailable(_)));
+        let outcome: CircuitBreakerOutcome<NatsError> = CircuitBreakerOutcome::OperationError(NatsError::NotInitialized);
+        assert_eq!(LanaiError::from(outcome).status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,383 @@
+//! Event Store
+//!
+//! Aggregates are persisted as their stream of domain events. Each append states the version
+//! the caller loaded (optimistic concurrency): if another writer appended in between, the
+//! append fails with `EventStoreError::Concurrency` and the command should be retried on
+//! fresh state. Loading replays the events on top of the latest snapshot, if any.
+//!
+//! Every stored event also gets a store-wide `position`, which projections use to follow
+//! all streams in order (`EventStore::read_all`).
+//!
+//! ```ignore
+//! impl Aggregate for Cart {
+//!     const TYPE: &'static str = "cart";
+//!     type Event = CartEvent;
+//!     fn apply(&mut self, event: &CartEvent) { ... }
+//! }
+//!
+//! let carts = Repository::<Cart>::new(Arc::new(PostgresEventStore::new(pool))).snapshot_every(100);
+//! let (cart, version) = carts.load(cart_id).await?;
+//! let events = cart.add_item(sku, qty)?;
+//! carts.save(cart_id, version, &events).await?;
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+pub mod postgres;
+
+pub use postgres::PostgresEventStore;
+
+/// Event store error types
+#[derive(Debug, Error)]
+pub enum EventStoreError {
+    /// The stream moved on since it was loaded.
+    #[error("Concurrency conflict on {aggregate_type} {aggregate_id}: expected version {expected}, found {actual}")]
+    Concurrency { aggregate_type: String, aggregate_id: Uuid, expected: i64, actual: i64 },
+
+    #[error("Failed to (de)serialize event: {0}")]
+    Serialization(String),
+
+    #[error("Event store backend error: {0}")]
+    Backend(String),
+}
+
+/// A domain event; `event_type` is stored alongside the payload for consumers that do not
+/// deserialize it (projections, audit).
+pub trait DomainEvent: Serialize + DeserializeOwned + Send + Sync {
+    fn event_type(&self) -> &'static str;
+}
+
+/// State rebuilt from its events.
+pub trait Aggregate: Default + Serialize + DeserializeOwned + Send + Sync {
+    /// Stream category, e.g. `"cart"`.
+    const TYPE: &'static str;
+    type Event: DomainEvent;
+
+    fn apply(&mut self, event: &Self::Event);
+}
+
+/// Event to append, already serialized.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub metadata: serde_json::Value,
+}
+
+impl NewEvent {
+    pub fn from_event<E: DomainEvent>(event: &E, metadata: serde_json::Value) -> Result<Self, EventStoreError> {
+        Ok(Self {
+            event_type: event.event_type().to_string(),
+            payload: serde_json::to_value(event).map_err(|e| EventStoreError::Serialization(e.to_string()))?,
+            metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEvent {
+    /// Store-wide sequence number, increasing in append order.
+    pub position: i64,
+    pub aggregate_type: String,
+    pub aggregate_id: Uuid,
+    /// 1-based version within the aggregate's stream.
+    pub version: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub metadata: serde_json::Value,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl StoredEvent {
+    pub fn decode<E: DeserializeOwned>(&self) -> Result<E, EventStoreError> {
+        serde_json::from_value(self.payload.clone()).map_err(|e| EventStoreError::Serialization(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub aggregate_type: String,
+    pub aggregate_id: Uuid,
+    /// Version of the last event folded into `state`.
+    pub version: i64,
+    pub state: serde_json::Value,
+    pub taken_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait EventStore: Send + Sync {
+    /// Append `events` if the stream is at `expected_version` (0 for a new stream).
+    /// Returns the new version.
+    async fn append(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        expected_version: i64,
+        events: Vec<NewEvent>,
+    ) -> Result<i64, EventStoreError>;
+
+    /// Events of one stream with a version greater than `after_version`, in order.
+    async fn load(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        after_version: i64,
+    ) -> Result<Vec<StoredEvent>, EventStoreError>;
+
+    /// Up to `limit` events from all streams with a position greater than `after_position`.
+    async fn read_all(&self, after_position: i64, limit: usize) -> Result<Vec<StoredEvent>, EventStoreError>;
+
+    /// Position of the most recent event (0 if the store is empty).
+    async fn head(&self) -> Result<i64, EventStoreError>;
+
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventStoreError>;
+
+    async fn load_snapshot(&self, aggregate_type: &str, aggregate_id: Uuid) -> Result<Option<Snapshot>, EventStoreError>;
+}
+
+/// Typed access to one aggregate type.
+pub struct Repository<A: Aggregate> {
+    store: Arc<dyn EventStore>,
+    snapshot_every: Option<i64>,
+    _aggregate: PhantomData<fn() -> A>,
+}
+
+impl<A: Aggregate> Clone for Repository<A> {
+    fn clone(&self) -> Self {
+        Self { store: self.store.clone(), snapshot_every: self.snapshot_every, _aggregate: PhantomData }
+    }
+}
+
+impl<A: Aggregate> Repository<A> {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self { store, snapshot_every: None, _aggregate: PhantomData }
+    }
+
+    /// Take a snapshot whenever a save crosses a multiple of `events` versions.
+    pub fn snapshot_every(mut self, events: i64) -> Self {
+        self.snapshot_every = Some(events.max(1));
+        self
+    }
+
+    /// Current state and version (0 and `A::default()` for an unknown aggregate).
+    pub async fn load(&self, id: Uuid) -> Result<(A, i64), EventStoreError> {
+        let (mut state, mut version) = match self.store.load_snapshot(A::TYPE, id).await? {
+            Some(snapshot) => {
+                let state = serde_json::from_value(snapshot.state)
+                    .map_err(|e| EventStoreError::Serialization(e.to_string()))?;
+                (state, snapshot.version)
+            }
+            None => (A::default(), 0),
+        };
+
+        for stored in self.store.load(A::TYPE, id, version).await? {
+            state.apply(&stored.decode::<A::Event>()?);
+            version = stored.version;
+        }
+        Ok((state, version))
+    }
+
+    /// Append `events` on top of `expected_version`; returns the new version.
+    pub async fn save(&self, id: Uuid, expected_version: i64, events: &[A::Event]) -> Result<i64, EventStoreError> {
+        self.save_with_metadata(id, expected_version, events, serde_json::json!({})).await
+    }
+
+    pub async fn save_with_metadata(
+        &self,
+        id: Uuid,
+        expected_version: i64,
+        events: &[A::Event],
+        metadata: serde_json::Value,
+    ) -> Result<i64, EventStoreError> {
+        let new_events = events
+            .iter()
+            .map(|event| NewEvent::from_event(event, metadata.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let version = self.store.append(A::TYPE, id, expected_version, new_events).await?;
+
+        if let Some(every) = self.snapshot_every {
+            if version / every > expected_version / every {
+                self.snapshot(id).await?;
+            }
+        }
+        Ok(version)
+    }
+
+    /// Store the current state as a snapshot.
+    pub async fn snapshot(&self, id: Uuid) -> Result<(), EventStoreError> {
+        let (state, version) = self.load(id).await?;
+        let snapshot = Snapshot {
+            aggregate_type: A::TYPE.to_string(),
+            aggregate_id: id,
+            version,
+            state: serde_json::to_value(&state).map_err(|e| EventStoreError::Serialization(e.to_string()))?,
+            taken_at: Utc::now(),
+        };
+        self.store.save_snapshot(&snapshot).await
+    }
+}
+
+/// In-memory event store (for tests and single-instance development).
+#[derive(Default)]
+pub struct InMemoryEventStore {
+    events: RwLock<Vec<StoredEvent>>,
+    snapshots: RwLock<HashMap<(String, Uuid), Snapshot>>,
+}
+
+impl InMemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EventStore for InMemoryEventStore {
+    async fn append(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        expected_version: i64,
+        events: Vec<NewEvent>,
+    ) -> Result<i64, EventStoreError> {
+        let mut stored = self.events.write().await;
+        let actual = stored
+            .iter()
+            .filter(|e| e.aggregate_type == aggregate_type && e.aggregate_id == aggregate_id)
+            .map(|e| e.version)
+            .max()
+            .unwrap_or(0);
+        if actual != expected_version {
+            return Err(EventStoreError::Concurrency {
+                aggregate_type: aggregate_type.to_string(),
+                aggregate_id,
+                expected: expected_version,
+                actual,
+            });
+        }
+
+        let mut version = expected_version;
+        for event in events {
+            version += 1;
+            let position = stored.len() as i64 + 1;
+            stored.push(StoredEvent {
+                position,
+                aggregate_type: aggregate_type.to_string(),
+                aggregate_id,
+                version,
+                event_type: event.event_type,
+                payload: event.payload,
+                metadata: event.metadata,
+                recorded_at: Utc::now(),
+            });
+        }
+        Ok(version)
+    }
+
+    async fn load(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        after_version: i64,
+    ) -> Result<Vec<StoredEvent>, EventStoreError> {
+        Ok(self
+            .events
+            .read()
+            .await
+            .iter()
+            .filter(|e| e.aggregate_type == aggregate_type && e.aggregate_id == aggregate_id && e.version > after_version)
+            .cloned()
+            .collect())
+    }
+
+    async fn read_all(&self, after_position: i64, limit: usize) -> Result<Vec<StoredEvent>, EventStoreError> {
+        Ok(self
+            .events
+            .read()
+            .await
+            .iter()
+            .filter(|e| e.position > after_position)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn head(&self) -> Result<i64, EventStoreError> {
+        Ok(self.events.read().await.len() as i64)
+    }
+
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventStoreError> {
+        self.snapshots
+            .write()
+            .await
+            .insert((snapshot.aggregate_type.clone(), snapshot.aggregate_id), snapshot.clone());
+        Ok(())
+    }
+
+    async fn load_snapshot(&self, aggregate_type: &str, aggregate_id: Uuid) -> Result<Option<Snapshot>, EventStoreError> {
+        Ok(self.snapshots.read().await.get(&(aggregate_type.to_string(), aggregate_id)).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "type")]
+    enum CounterEvent {
+        Incremented { by: u32 },
+    }
+
+    impl DomainEvent for CounterEvent {
+        fn event_type(&self) -> &'static str {
+            "counter.incremented"
+        }
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Counter {
+        total: u32,
+    }
+
+    impl Aggregate for Counter {
+        const TYPE: &'static str = "counter";
+        type Event = CounterEvent;
+
+        fn apply(&mut self, event: &CounterEvent) {
+            match event {
+                CounterEvent::Incremented { by } => self.total += by,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_load_and_conflicts() {
+        let store = Arc::new(InMemoryEventStore::new());
+        let counters = Repository::<Counter>::new(store.clone()).snapshot_every(2);
+        let id = Uuid::new_v4();
+
+        let version = counters.save(id, 0, &[CounterEvent::Incremented { by: 2 }]).await.unwrap();
+        let version = counters.save(id, version, &[CounterEvent::Incremented { by: 3 }]).await.unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(store.load_snapshot("counter", id).await.unwrap().unwrap().version, 2);
+
+        counters.save(id, version, &[CounterEvent::Incremented { by: 5 }]).await.unwrap();
+        let (counter, version) = counters.load(id).await.unwrap();
+        assert_eq!((counter.total, version), (10, 3));
+
+        let stale = counters.save(id, 1, &[CounterEvent::Incremented { by: 1 }]).await;
+        assert!(matches!(stale, Err(EventStoreError::Concurrency { expected: 1, actual: 3, .. })));
+
+        let all = store.read_all(1, 10).await.unwrap();
+        assert_eq!(all.iter().map(|e| e.position).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(all[0].event_type, "counter.incremented");
+    }
+}
@@ -0,0 +1,213 @@
+//! PostgreSQL-backed event store
+
+use async_trait::async_trait;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::{EventStore, EventStoreError, NewEvent, Snapshot, StoredEvent};
+
+/// Tables used by `PostgresEventStore`; include them in a service migration or call
+/// `ensure_schema` at startup. The primary key on `(aggregate_type, aggregate_id, version)`
+/// is what rejects concurrent appends.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_events (
+    position BIGSERIAL UNIQUE,
+    aggregate_type TEXT NOT NULL,
+    aggregate_id UUID NOT NULL,
+    version BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    metadata JSONB NOT NULL DEFAULT '{}',
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (aggregate_type, aggregate_id, version)
+);
+CREATE TABLE IF NOT EXISTS lanai_snapshots (
+    aggregate_type TEXT NOT NULL,
+    aggregate_id UUID NOT NULL,
+    version BIGINT NOT NULL,
+    state JSONB NOT NULL,
+    taken_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (aggregate_type, aggregate_id)
+)";
+
+const EVENT_COLUMNS: &str = "position, aggregate_type, aggregate_id, version, event_type, payload, metadata, recorded_at";
+
+/// Note: `position` comes from a sequence, so a transaction that commits late can make an
+/// event appear behind positions a reader already passed. Keep append transactions short.
+pub struct PostgresEventStore {
+    pool: PgPool,
+}
+
+impl PostgresEventStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), EventStoreError> {
+        sqlx::raw_sql(SCHEMA).execute(&self.pool).await.map_err(backend_error)?;
+        Ok(())
+    }
+
+    async fn current_version(&self, aggregate_type: &str, aggregate_id: Uuid) -> Result<i64, EventStoreError> {
+        let version: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM lanai_events WHERE aggregate_type = $1 AND aggregate_id = $2")
+                .bind(aggregate_type)
+                .bind(aggregate_id)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(backend_error)?;
+        Ok(version.unwrap_or(0))
+    }
+}
+
+fn backend_error(e: sqlx::Error) -> EventStoreError {
+    EventStoreError::Backend(e.to_string())
+}
+
+fn event_from_row(row: &PgRow) -> Result<StoredEvent, sqlx::Error> {
+    Ok(StoredEvent {
+        position: row.try_get("position")?,
+        aggregate_type: row.try_get("aggregate_type")?,
+        aggregate_id: row.try_get("aggregate_id")?,
+        version: row.try_get("version")?,
+        event_type: row.try_get("event_type")?,
+        payload: row.try_get("payload")?,
+        metadata: row.try_get("metadata")?,
+        recorded_at: row.try_get("recorded_at")?,
+    })
+}
+
+#[async_trait]
+impl EventStore for PostgresEventStore {
+    async fn append(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        expected_version: i64,
+        events: Vec<NewEvent>,
+    ) -> Result<i64, EventStoreError> {
+        let conflict = |actual| EventStoreError::Concurrency {
+            aggregate_type: aggregate_type.to_string(),
+            aggregate_id,
+            expected: expected_version,
+            actual,
+        };
+
+        let actual = self.current_version(aggregate_type, aggregate_id).await?;
+        if actual != expected_version {
+            return Err(conflict(actual));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(backend_error)?;
+        let mut version = expected_version;
+        for event in events {
+            version += 1;
+            let inserted = sqlx::query(
+                "INSERT INTO lanai_events (aggregate_type, aggregate_id, version, event_type, payload, metadata)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(aggregate_type)
+            .bind(aggregate_id)
+            .bind(version)
+            .bind(&event.event_type)
+            .bind(&event.payload)
+            .bind(&event.metadata)
+            .execute(&mut *tx)
+            .await;
+
+            match inserted {
+                Ok(_) => {}
+                // Another writer appended this version after our check.
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                    drop(tx);
+                    let actual = self.current_version(aggregate_type, aggregate_id).await?;
+                    return Err(conflict(actual));
+                }
+                Err(e) => return Err(backend_error(e)),
+            }
+        }
+        tx.commit().await.map_err(backend_error)?;
+        Ok(version)
+    }
+
+    async fn load(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: Uuid,
+        after_version: i64,
+    ) -> Result<Vec<StoredEvent>, EventStoreError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM lanai_events WHERE aggregate_type = $1 AND aggregate_id = $2 AND version > $3 ORDER BY version",
+            EVENT_COLUMNS
+        ))
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(after_version)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(backend_error)?;
+        rows.iter().map(event_from_row).collect::<Result<_, _>>().map_err(backend_error)
+    }
+
+    async fn read_all(&self, after_position: i64, limit: usize) -> Result<Vec<StoredEvent>, EventStoreError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM lanai_events WHERE position > $1 ORDER BY position LIMIT $2",
+            EVENT_COLUMNS
+        ))
+        .bind(after_position)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(backend_error)?;
+        rows.iter().map(event_from_row).collect::<Result<_, _>>().map_err(backend_error)
+    }
+
+    async fn head(&self) -> Result<i64, EventStoreError> {
+        sqlx::query_scalar("SELECT COALESCE(MAX(position), 0) FROM lanai_events")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(backend_error)
+    }
+
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventStoreError> {
+        sqlx::query(
+            "INSERT INTO lanai_snapshots (aggregate_type, aggregate_id, version, state, taken_at)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (aggregate_type, aggregate_id) DO UPDATE
+                SET version = EXCLUDED.version, state = EXCLUDED.state, taken_at = EXCLUDED.taken_at
+                WHERE lanai_snapshots.version < EXCLUDED.version",
+        )
+        .bind(&snapshot.aggregate_type)
+        .bind(snapshot.aggregate_id)
+        .bind(snapshot.version)
+        .bind(&snapshot.state)
+        .bind(snapshot.taken_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend_error)?;
+        Ok(())
+    }
+
+    async fn load_snapshot(&self, aggregate_type: &str, aggregate_id: Uuid) -> Result<Option<Snapshot>, EventStoreError> {
+        let row = sqlx::query(
+            "SELECT version, state, taken_at FROM lanai_snapshots WHERE aggregate_type = $1 AND aggregate_id = $2",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(backend_error)?;
+
+        row.map(|row| {
+            Ok::<_, sqlx::Error>(Snapshot {
+                aggregate_type: aggregate_type.to_string(),
+                aggregate_id,
+                version: row.try_get("version")?,
+                state: row.try_get("state")?,
+                taken_at: row.try_get("taken_at")?,
+            })
+        })
+        .transpose()
+        .map_err(backend_error)
+    }
+}
@@ -0,0 +1,129 @@
+//! Runtime feature flags
+//!
+//! Flags are toggled at runtime (e.g. through the admin API) and can be overridden per
+//! organization. A flag that was never set is disabled.
+//!
+//! ```ignore
+//! let flags = FeatureFlags::global();
+//! flags.set("new_checkout", true);
+//! flags.set_for_org("beta_reports", org_id, true);
+//!
+//! if flags.is_enabled_for("beta_reports", tenant.org_id) { ... }
+//! ```
+
+use log::info;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock, RwLock};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default)]
+struct Flag {
+    enabled: bool,
+    orgs: HashMap<Uuid, bool>,
+}
+
+/// A flag's global value and per-organization overrides.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlagState {
+    pub name: String,
+    pub enabled: bool,
+    pub org_overrides: BTreeMap<Uuid, bool>,
+}
+
+#[derive(Clone, Default)]
+pub struct FeatureFlags {
+    flags: Arc<RwLock<HashMap<String, Flag>>>,
+}
+
+static GLOBAL: OnceLock<FeatureFlags> = OnceLock::new();
+
+impl FeatureFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global() -> &'static FeatureFlags {
+        GLOBAL.get_or_init(FeatureFlags::new)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.read().get(name).is_some_and(|flag| flag.enabled)
+    }
+
+    /// The organization's override if any, else the global value.
+    pub fn is_enabled_for(&self, name: &str, org_id: Uuid) -> bool {
+        self.read()
+            .get(name)
+            .is_some_and(|flag| flag.orgs.get(&org_id).copied().unwrap_or(flag.enabled))
+    }
+
+    pub fn set(&self, name: &str, enabled: bool) {
+        info!("🚩 Feature flag '{}' set to {}", name, enabled);
+        self.write().entry(name.to_string()).or_default().enabled = enabled;
+    }
+
+    pub fn set_for_org(&self, name: &str, org_id: Uuid, enabled: bool) {
+        info!("🚩 Feature flag '{}' set to {} for org {}", name, enabled, org_id);
+        self.write().entry(name.to_string()).or_default().orgs.insert(org_id, enabled);
+    }
+
+    /// Drop an organization's override so it follows the global value again.
+    pub fn clear_for_org(&self, name: &str, org_id: Uuid) {
+        if let Some(flag) = self.write().get_mut(name) {
+            flag.orgs.remove(&org_id);
+        }
+    }
+
+    /// Returns true if the flag existed.
+    pub fn remove(&self, name: &str) -> bool {
+        self.write().remove(name).is_some()
+    }
+
+    /// All flags, sorted by name.
+    pub fn list(&self) -> Vec<FlagState> {
+        let mut flags: Vec<FlagState> = self
+            .read()
+            .iter()
+            .map(|(name, flag)| FlagState {
+                name: name.clone(),
+                enabled: flag.enabled,
+                org_overrides: flag.orgs.iter().map(|(org, enabled)| (*org, *enabled)).collect(),
+            })
+            .collect();
+        flags.sort_by(|a, b| a.name.cmp(&b.name));
+        flags
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Flag>> {
+        self.flags.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Flag>> {
+        self.flags.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_overrides_take_precedence() {
+        let flags = FeatureFlags::new();
+        let (beta_org, other_org) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(!flags.is_enabled("beta_reports"));
+
+        flags.set_for_org("beta_reports", beta_org, true);
+        assert!(flags.is_enabled_for("beta_reports", beta_org));
+        assert!(!flags.is_enabled_for("beta_reports", other_org));
+
+        flags.set("beta_reports", true);
+        flags.set_for_org("beta_reports", other_org, false);
+        assert!(!flags.is_enabled_for("beta_reports", other_org));
+
+        flags.clear_for_org("beta_reports", other_org);
+        assert!(flags.is_enabled_for("beta_reports", other_org));
+        assert_eq!(flags.list()[0].org_overrides.len(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+//! Currency exchange rates
+//!
+//! A `RateProvider` fetches the latest rates (`EcbProvider`, `OpenExchangeRatesProvider`,
+//! or `StaticRates` in tests) and `FxRates` caches them in Redis under `lanai:fx:{base}`,
+//! so all replicas of all services convert with the same rates until they expire (1 hour
+//! by default). Conversions go through `Money::convert_to`:
+//!
+//! ```ignore
+//! let fx = FxRates::new(Arc::new(EcbProvider::new())).with_redis(shared_connection().await?);
+//! let rates = fx.rates(Currency::USD).await?;
+//! let total = Money::sum(Currency::USD, &sales.iter().map(|s| s.total.convert_to(Currency::USD, &rates)).collect::<Result<Vec<_>, _>>()?)?;
+//! ```
+//!
+//! Rates are for reporting; invoices must use the rate the tax authority publishes for
+//! their date.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use redis::aio::ConnectionManager;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::common::Currency;
+use crate::error::LanaiError;
+
+pub mod providers;
+
+pub use providers::{EcbProvider, OpenExchangeRatesProvider, StaticRates, OXR_APP_ID_ENV};
+
+/// FX error types
+#[derive(Debug, Clone, Error)]
+pub enum FxError {
+    #[error("Exchange rate provider error: {0}")]
+    Provider(String),
+
+    #[error("No exchange rate from {0} to {1}")]
+    MissingRate(Currency, Currency),
+
+    #[error("Exchange rate provider is not configured: {0}")]
+    NotConfigured(String),
+}
+
+impl From<FxError> for LanaiError {
+    fn from(e: FxError) -> Self {
+        match e {
+            FxError::MissingRate(..) => LanaiError::BadRequest(e.to_string()),
+            FxError::Provider(_) => LanaiError::Unavailable(e.to_string()),
+            FxError::NotConfigured(_) => LanaiError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Units of each currency per one unit of `base`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRates {
+    pub base: Currency,
+    pub rates: HashMap<Currency, Decimal>,
+    /// When the provider published the rates.
+    pub as_of: DateTime<Utc>,
+}
+
+impl ExchangeRates {
+    pub fn new(base: Currency, as_of: DateTime<Utc>) -> Self {
+        Self { base, rates: HashMap::new(), as_of }
+    }
+
+    /// `rate` units of `currency` per unit of the base. Zero and negative rates are ignored.
+    pub fn with(mut self, currency: Currency, rate: Decimal) -> Self {
+        if rate > Decimal::ZERO {
+            self.rates.insert(currency, rate);
+        }
+        self
+    }
+
+    fn per_base(&self, currency: Currency) -> Option<Decimal> {
+        if currency == self.base {
+            return Some(Decimal::ONE);
+        }
+        self.rates.get(&currency).copied()
+    }
+
+    /// Units of `to` per unit of `from`, crossing through the base when neither is it.
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        self.per_base(to)?.checked_div(self.per_base(from)?)
+    }
+
+    /// The same rates expressed against another base.
+    pub fn rebase(&self, base: Currency) -> Result<ExchangeRates, FxError> {
+        let factor = self.per_base(base).ok_or(FxError::MissingRate(self.base, base))?;
+        let mut rebased = ExchangeRates::new(base, self.as_of);
+        for currency in self.rates.keys().copied().chain(std::iter::once(self.base)) {
+            if currency != base {
+                if let Some(rate) = self.per_base(currency).and_then(|rate| rate.checked_div(factor)) {
+                    rebased.rates.insert(currency, rate);
+                }
+            }
+        }
+        Ok(rebased)
+    }
+}
+
+/// Source of current rates.
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+    /// Latest rates against `base`.
+    async fn latest(&self, base: Currency) -> Result<ExchangeRates, FxError>;
+}
+
+/// Rates from a provider, cached in Redis (when configured) and shared by every replica.
+/// Redis failures are logged and bypassed.
+#[derive(Clone)]
+pub struct FxRates {
+    provider: Arc<dyn RateProvider>,
+    redis: Option<ConnectionManager>,
+    ttl: Duration,
+}
+
+impl FxRates {
+    pub fn new(provider: Arc<dyn RateProvider>) -> Self {
+        Self { provider, redis: None, ttl: Duration::from_secs(3600) }
+    }
+
+    pub fn with_redis(mut self, conn: ConnectionManager) -> Self {
+        self.redis = Some(conn);
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn key(base: Currency) -> String {
+        format!("lanai:fx:{}", base)
+    }
+
+    async fn cached(&self, base: Currency) -> Option<ExchangeRates> {
+        let mut conn = self.redis.clone()?;
+        let stored: Option<String> = match redis::cmd("GET").arg(Self::key(base)).query_async(&mut conn).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                warn!("⚠️ Reading cached exchange rates failed: {}", e);
+                return None;
+            }
+        };
+        stored.and_then(|stored| serde_json::from_str(&stored).ok())
+    }
+
+    async fn store(&self, rates: &ExchangeRates) {
+        let Some(mut conn) = self.redis.clone() else {
+            return;
+        };
+        let Ok(value) = serde_json::to_string(rates) else {
+            return;
+        };
+        let result = redis::cmd("SET")
+            .arg(Self::key(rates.base))
+            .arg(value)
+            .arg("EX")
+            .arg(self.ttl.as_secs().max(1))
+            .query_async::<_, ()>(&mut conn)
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Caching exchange rates failed: {}", e);
+        }
+    }
+
+    /// Current rates against `base`.
+    pub async fn rates(&self, base: Currency) -> Result<ExchangeRates, FxError> {
+        if let Some(rates) = self.cached(base).await {
+            return Ok(rates);
+        }
+        let rates = self.provider.latest(base).await?;
+        info!("💱 Fetched {} exchange rates against {} (as of {})", rates.rates.len(), base, rates.as_of);
+        self.store(&rates).await;
+        Ok(rates)
+    }
+
+    /// Drop the cached rates of `base`, e.g. after a provider correction.
+    pub async fn invalidate(&self, base: Currency) {
+        if let Some(mut conn) = self.redis.clone() {
+            if let Err(e) = redis::cmd("DEL").arg(Self::key(base)).query_async::<_, ()>(&mut conn).await {
+                warn!("⚠️ Invalidating exchange rates failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Money;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cross_rates_and_conversion() {
+        let rates = ExchangeRates::new(Currency::USD, Utc::now()).with(Currency::PEN, dec("3.75")).with(Currency::EUR, dec("0.5"));
+        assert_eq!(rates.rate(Currency::EUR, Currency::PEN), Some(dec("7.5")));
+        assert_eq!(rates.rate(Currency::USD, Currency::CLP), None);
+
+        let rebased = rates.rebase(Currency::PEN).unwrap();
+        assert_eq!(rebased.rate(Currency::PEN, Currency::EUR), rates.rate(Currency::PEN, Currency::EUR));
+
+        let fx = FxRates::new(Arc::new(StaticRates::new(rates)));
+        let rates = fx.rates(Currency::EUR).await.unwrap();
+        let price = Money::new(dec("10"), Currency::USD);
+        assert_eq!(price.convert_to(Currency::PEN, &rates).unwrap(), Money::new(dec("37.50"), Currency::PEN));
+        assert!(price.convert_to(Currency::CLP, &rates).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Exchange rate providers
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{ExchangeRates, FxError, RateProvider};
+use crate::common::Currency;
+
+/// openexchangerates.org App ID for `OpenExchangeRatesProvider::from_env`
+pub const OXR_APP_ID_ENV: &str = "LANAI_OXR_APP_ID";
+
+const ECB_DAILY_URL: &str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml";
+const OXR_LATEST_URL: &str = "https://openexchangerates.org/api/latest.json";
+
+fn provider_error(e: reqwest::Error) -> FxError {
+    FxError::Provider(e.to_string())
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default()
+}
+
+/// European Central Bank reference rates: free, no key, published once per working day
+/// around 16:00 CET, EUR-based (other bases are derived by crossing). No LATAM currency
+/// except BRL and MXN.
+#[derive(Debug, Clone)]
+pub struct EcbProvider {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl EcbProvider {
+    pub fn new() -> Self {
+        Self { url: ECB_DAILY_URL.to_string(), client: http_client() }
+    }
+
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self { url: ECB_DAILY_URL.to_string(), client }
+    }
+
+    /// Pick the `time`, `currency` and `rate` attributes out of the daily XML.
+    fn parse(xml: &str) -> Result<ExchangeRates, FxError> {
+        let attribute = |element: &str, name: &str| -> Option<String> {
+            let start = element.find(&format!("{}=", name))? + name.len() + 1;
+            let quote = element[start..].chars().next()?;
+            let value = &element[start + 1..];
+            Some(value[..value.find(quote)?].to_string())
+        };
+
+        let mut as_of = None;
+        let mut rates = HashMap::new();
+        for element in xml.split('<').filter(|element| element.starts_with("Cube ")) {
+            if let Some(time) = attribute(element, "time") {
+                let date = NaiveDate::parse_from_str(&time, "%Y-%m-%d")
+                    .map_err(|_| FxError::Provider(format!("invalid ECB date '{}'", time)))?;
+                as_of = date.and_hms_opt(16, 0, 0).map(|t| Utc.from_utc_datetime(&t));
+            }
+            if let (Some(currency), Some(rate)) = (attribute(element, "currency"), attribute(element, "rate")) {
+                let (Ok(currency), Ok(rate)) = (Currency::new(&currency), Decimal::from_str(&rate)) else {
+                    return Err(FxError::Provider(format!("invalid ECB rate {}={}", currency, rate)));
+                };
+                rates.insert(currency, rate);
+            }
+        }
+
+        let as_of = as_of.ok_or_else(|| FxError::Provider("ECB response without a date".to_string()))?;
+        if rates.is_empty() {
+            return Err(FxError::Provider("ECB response without rates".to_string()));
+        }
+        Ok(rates.into_iter().fold(ExchangeRates::new(Currency::EUR, as_of), |acc, (currency, rate)| acc.with(currency, rate)))
+    }
+}
+
+impl Default for EcbProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateProvider for EcbProvider {
+    async fn latest(&self, base: Currency) -> Result<ExchangeRates, FxError> {
+        let response = self.client.get(&self.url).send().await.map_err(provider_error)?;
+        let xml = response.error_for_status().map_err(provider_error)?.text().await.map_err(provider_error)?;
+        Self::parse(&xml)?.rebase(base)
+    }
+}
+
+/// openexchangerates.org: hourly rates for ~170 currencies, including PEN, CLP, COP and
+/// ARS. Free plans only allow USD as base, so the USD rates are fetched and crossed.
+#[derive(Debug, Clone)]
+pub struct OpenExchangeRatesProvider {
+    app_id: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct OxrResponse {
+    timestamp: i64,
+    base: String,
+    rates: HashMap<String, Decimal>,
+}
+
+impl OpenExchangeRatesProvider {
+    pub fn new(app_id: &str) -> Self {
+        Self { app_id: app_id.to_string(), client: http_client() }
+    }
+
+    /// App ID from `LANAI_OXR_APP_ID`.
+    pub fn from_env() -> Result<Self, FxError> {
+        let app_id = std::env::var(OXR_APP_ID_ENV).map_err(|_| FxError::NotConfigured(OXR_APP_ID_ENV.to_string()))?;
+        Ok(Self::new(&app_id))
+    }
+
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+}
+
+#[async_trait]
+impl RateProvider for OpenExchangeRatesProvider {
+    async fn latest(&self, base: Currency) -> Result<ExchangeRates, FxError> {
+        let response = self
+            .client
+            .get(OXR_LATEST_URL)
+            .query(&[("app_id", self.app_id.as_str())])
+            .send()
+            .await
+            .map_err(provider_error)?;
+        let body: OxrResponse = response.error_for_status().map_err(provider_error)?.json().await.map_err(provider_error)?;
+
+        let as_of = DateTime::from_timestamp(body.timestamp, 0).unwrap_or_else(Utc::now);
+        let oxr_base = Currency::new(&body.base).map_err(|e| FxError::Provider(e.to_string()))?;
+        let rates = body
+            .rates
+            .into_iter()
+            .filter_map(|(code, rate)| Some((Currency::new(&code).ok()?, rate)))
+            .fold(ExchangeRates::new(oxr_base, as_of), |acc, (currency, rate)| acc.with(currency, rate));
+        rates.rebase(base)
+    }
+}
+
+/// Fixed rates, for tests and local development.
+#[derive(Debug, Clone)]
+pub struct StaticRates {
+    rates: ExchangeRates,
+}
+
+impl StaticRates {
+    pub fn new(rates: ExchangeRates) -> Self {
+        Self { rates }
+    }
+}
+
+#[async_trait]
+impl RateProvider for StaticRates {
+    async fn latest(&self, base: Currency) -> Result<ExchangeRates, FxError> {
+        self.rates.rebase(base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ecb_daily() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+  <Cube>
+    <Cube time='2024-05-10'>
+      <Cube currency='USD' rate='1.0773'/>
+      <Cube currency='BRL' rate='5.5462'/>
+    </Cube>
+  </Cube>
+</gesmes:Envelope>"#;
+        let rates = EcbProvider::parse(xml).unwrap();
+        assert_eq!(rates.base, Currency::EUR);
+        assert_eq!(rates.rate(Currency::EUR, Currency::USD), Some(Decimal::from_str("1.0773").unwrap()));
+        assert_eq!(rates.as_of.date_naive(), NaiveDate::from_ymd_opt(2024, 5, 10).unwrap());
+        assert!(EcbProvider::parse("<Cube></Cube>").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! Canary and blue/green routing
+//!
+//! A `CanaryRouter` assigns each request to the `Stable` or `Canary` variant:
+//! 1. requests carrying a configured header value (`X-Canary: on`) go to the canary
+//! 2. tenants enrolled with `tenant` go to the canary
+//! 3. everyone else is placed in one of 100 buckets, by organization, else user, else a
+//!    random bucket remembered in a cookie, and goes to the canary if the bucket is below
+//!    the current percentage
+//!
+//! Buckets do not change when the percentage does, so ramping from 5% to 20% keeps the
+//! first 5% on the canary, and a client never flips between variants at a fixed
+//! percentage. Setting the percentage to 100 (or 0) is a blue/green switch, and
+//! `set_percent` changes it at runtime without a deploy.
+//!
+//! `CanaryMiddleware` makes the assignment available to handlers as a `Variant`
+//! extractor and exports `canary_requests_total` and `canary_request_duration_seconds` by
+//! variant. `CanaryProxy` routes a gateway mount to one of two `UpstreamProxy`s:
+//!
+//! ```ignore
+//! let router = CanaryRouter::new("orders-v2").percent(5).header("X-Canary", "on").tenant(pilot_org);
+//! let orders = CanaryProxy::new(
+//!     router.clone(),
+//!     UpstreamProxy::new("orders", "http://orders:8080").mount("/api/orders"),
+//!     UpstreamProxy::new("orders-canary", "http://orders-canary:8080").mount("/api/orders"),
+//! );
+//! ServerBuilder::new().run(move |cfg| orders.configure(cfg)).await?;
+//!
+//! // Or inside a service, between two handler implementations
+//! web::scope("/reports").wrap(CanaryMiddleware::new(router)).route("", web::get().to(
+//!     |variant: Variant| async move { if variant.is_canary() { new_report().await } else { old_report().await } }
+//! ))
+//! ```
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    cookie::{time, Cookie, SameSite},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::HeaderName,
+    web, Error, FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::future::LocalBoxFuture;
+use log::info;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::{global, KeyValue};
+use rand::Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
+
+use super::UpstreamProxy;
+use crate::middleware::auth_guard::Claims;
+use crate::middleware::tenant_context::TenantContext;
+
+/// How long the bucket cookie of anonymous clients lasts.
+const STICKY_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Stable,
+    Canary,
+}
+
+impl Variant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Canary => "canary",
+        }
+    }
+
+    pub fn is_canary(&self) -> bool {
+        *self == Self::Canary
+    }
+}
+
+/// `Stable` unless `CanaryMiddleware` assigned the request to the canary.
+impl FromRequest for Variant {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req.extensions().get::<Variant>().copied().unwrap_or_default()))
+    }
+}
+
+/// A variant assignment and the bucket to remember, for clients without an identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Assignment {
+    variant: Variant,
+    new_bucket: Option<u8>,
+}
+
+struct Inner {
+    name: String,
+    percent: AtomicU8,
+    headers: Vec<(HeaderName, String)>,
+    tenants: HashSet<Uuid>,
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+/// Variant assignment rules of one rollout; clones share the percentage.
+#[derive(Clone)]
+pub struct CanaryRouter {
+    inner: Arc<Inner>,
+}
+
+impl CanaryRouter {
+    /// Sends nothing to the canary until `percent`, `header` or `tenant` says otherwise.
+    pub fn new(name: &str) -> Self {
+        let meter = global::meter("lanai.gateway");
+        Self {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                percent: AtomicU8::new(0),
+                headers: Vec::new(),
+                tenants: HashSet::new(),
+                requests: meter
+                    .u64_counter("canary_requests_total")
+                    .with_description("Requests by rollout, variant and status class")
+                    .build(),
+                duration: meter
+                    .f64_histogram("canary_request_duration_seconds")
+                    .with_description("Request duration by rollout and variant")
+                    .with_unit("s")
+                    .build(),
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("CanaryRouter is configured before it is shared"));
+        self
+    }
+
+    /// Share of buckets sent to the canary, 0 to 100.
+    pub fn percent(self, percent: u8) -> Self {
+        self.inner.percent.store(percent.min(100), Ordering::Relaxed);
+        self
+    }
+
+    /// Send requests whose header `name` equals `value` to the canary.
+    pub fn header(self, name: &str, value: &str) -> Self {
+        let name = HeaderName::try_from(name).expect("header takes a valid header name");
+        self.update(|inner| inner.headers.push((name, value.to_string())))
+    }
+
+    /// Always send organization `org_id` to the canary.
+    pub fn tenant(self, org_id: Uuid) -> Self {
+        self.update(|inner| {
+            inner.tenants.insert(org_id);
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    pub fn current_percent(&self) -> u8 {
+        self.inner.percent.load(Ordering::Relaxed)
+    }
+
+    /// Change the canary share of every clone, e.g. from an admin endpoint.
+    pub fn set_percent(&self, percent: u8) {
+        let percent = percent.min(100);
+        let previous = self.inner.percent.swap(percent, Ordering::Relaxed);
+        info!("🐤 Canary '{}' moved from {}% to {}%", self.inner.name, previous, percent);
+    }
+
+    fn cookie_name(&self) -> String {
+        format!("lanai_canary_{}", self.inner.name)
+    }
+
+    /// Bucket of a stable identity, the same on every replica.
+    fn bucket_of(&self, identity: &str) -> u8 {
+        let digest = Sha256::new().chain_update(&self.inner.name).chain_update(b":").chain_update(identity).finalize();
+        (u64::from_be_bytes(digest[..8].try_into().expect("sha256 is 32 bytes")) % 100) as u8
+    }
+
+    fn assign(&self, req: &ServiceRequest) -> Assignment {
+        let forced = self.inner.headers.iter().any(|(name, value)| {
+            req.headers().get(name).and_then(|v| v.to_str().ok()) == Some(value.as_str())
+        });
+        let (org_id, sub) = {
+            let extensions = req.extensions();
+            let claims = extensions.get::<Claims>();
+            let org_id = match extensions.get::<TenantContext>() {
+                Some(tenant) => Some(tenant.org_id),
+                None => claims.and_then(|c| c.org_id.as_deref()?.parse().ok()),
+            };
+            (org_id, claims.map(|c| c.sub.clone()))
+        };
+        if forced || org_id.is_some_and(|org_id| self.inner.tenants.contains(&org_id)) {
+            return Assignment { variant: Variant::Canary, new_bucket: None };
+        }
+
+        // Parsing cookies borrows the extensions, so this runs after they are released
+        let remembered = req.cookie(&self.cookie_name()).and_then(|c| c.value().parse::<u8>().ok());
+        let remembered = remembered.filter(|bucket| *bucket < 100);
+        let (bucket, new_bucket) = match (org_id, sub, remembered) {
+            (Some(org_id), _, _) => (self.bucket_of(&org_id.to_string()), None),
+            (None, Some(sub), _) => (self.bucket_of(&sub), None),
+            (None, None, Some(bucket)) => (bucket, None),
+            (None, None, None) => {
+                let bucket = rand::thread_rng().gen_range(0..100);
+                (bucket, Some(bucket))
+            }
+        };
+        let variant = if bucket < self.current_percent() { Variant::Canary } else { Variant::Stable };
+        Assignment { variant, new_bucket }
+    }
+}
+
+/// Assigns requests to a variant (see `CanaryRouter`) and records metrics by variant.
+pub struct CanaryMiddleware {
+    router: CanaryRouter,
+}
+
+impl CanaryMiddleware {
+    pub fn new(router: CanaryRouter) -> Self {
+        Self { router }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CanaryMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CanaryMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CanaryMiddlewareService { service: Rc::new(service), router: self.router.clone() }))
+    }
+}
+
+pub struct CanaryMiddlewareService<S> {
+    service: Rc<S>,
+    router: CanaryRouter,
+}
+
+impl<S, B> Service<ServiceRequest> for CanaryMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let router = self.router.clone();
+        let assignment = router.assign(&req);
+        req.extensions_mut().insert(assignment.variant);
+
+        Box::pin(async move {
+            let started = Instant::now();
+            let mut res = service.call(req).await?.map_into_boxed_body();
+
+            let inner = &router.inner;
+            let variant = KeyValue::new("variant", assignment.variant.as_str());
+            let rollout = KeyValue::new("rollout", inner.name.clone());
+            let status = format!("{}xx", res.status().as_u16() / 100);
+            inner.requests.add(1, &[rollout.clone(), variant.clone(), KeyValue::new("status", status)]);
+            inner.duration.record(started.elapsed().as_secs_f64(), &[rollout, variant]);
+
+            if let Some(bucket) = assignment.new_bucket {
+                let cookie = Cookie::build(router.cookie_name(), bucket.to_string())
+                    .path("/")
+                    .http_only(true)
+                    .same_site(SameSite::Lax)
+                    .max_age(time::Duration::days(STICKY_DAYS))
+                    .finish();
+                res.response_mut().add_cookie(&cookie)?;
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Routes a gateway mount to a stable and a canary upstream.
+#[derive(Clone)]
+pub struct CanaryProxy {
+    router: CanaryRouter,
+    stable: UpstreamProxy,
+    canary: UpstreamProxy,
+}
+
+impl CanaryProxy {
+    /// Both proxies should have the same mount; the stable one's is used.
+    pub fn new(router: CanaryRouter, stable: UpstreamProxy, canary: UpstreamProxy) -> Self {
+        Self { router, stable, canary }
+    }
+
+    pub fn router(&self) -> &CanaryRouter {
+        &self.router
+    }
+
+    /// Mount the stable proxy's mount prefix and everything below it.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let proxy = self.clone();
+        cfg.service(
+            web::scope(&self.stable.inner.mount).wrap(CanaryMiddleware::new(self.router.clone())).default_service(
+                web::to(move |req: HttpRequest, variant: Variant, body: web::Bytes| {
+                    let proxy = proxy.clone();
+                    async move {
+                        match variant {
+                            Variant::Stable => proxy.stable.forward(req, body).await,
+                            Variant::Canary => proxy.canary.forward(req, body).await,
+                        }
+                    }
+                }),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App, HttpResponse};
+
+    #[test]
+    fn test_buckets_are_stable_and_spread() {
+        let router = CanaryRouter::new("orders-v2");
+        assert_eq!(router.bucket_of("org-1"), router.bucket_of("org-1"));
+        let canary = (0..1000).filter(|i| router.bucket_of(&i.to_string()) < 20).count();
+        assert!((120..=280).contains(&canary), "{} of 1000 in the first 20 buckets", canary);
+    }
+
+    #[actix_web::test]
+    async fn test_assignment_rules_and_sticky_cookie() {
+        let pilot = Uuid::new_v4();
+        let router = CanaryRouter::new("reports").header("X-Canary", "on").tenant(pilot);
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(CanaryMiddleware::new(router.clone()))
+                .wrap_fn(move |req, srv| {
+                    if req.headers().contains_key("x-pilot") {
+                        req.extensions_mut().insert(TenantContext { org_id: pilot });
+                    }
+                    actix_web::dev::Service::call(srv, req)
+                })
+                .route("/", web::get().to(|variant: Variant| async move { HttpResponse::Ok().body(variant.as_str()) })),
+        )
+        .await;
+
+        let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/").to_request()).await;
+        let cookie = res.response().cookies().next().expect("anonymous clients get a bucket cookie").into_owned();
+        assert_eq!(actix_test::read_body(res).await, "stable");
+
+        let req = actix_test::TestRequest::get().uri("/").insert_header(("X-Canary", "on")).to_request();
+        assert_eq!(actix_test::call_and_read_body(&app, req).await, "canary");
+        let req = actix_test::TestRequest::get().uri("/").insert_header(("x-pilot", "1")).to_request();
+        assert_eq!(actix_test::call_and_read_body(&app, req).await, "canary");
+
+        // The remembered bucket decides once the percentage covers it
+        let bucket: u8 = cookie.value().parse().unwrap();
+        router.set_percent(bucket + 1);
+        let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/").cookie(cookie.clone()).to_request()).await;
+        assert!(res.response().cookies().next().is_none());
+        assert_eq!(actix_test::read_body(res).await, "canary");
+        router.set_percent(bucket);
+        let req = actix_test::TestRequest::get().uri("/").cookie(cookie).to_request();
+        assert_eq!(actix_test::call_and_read_body(&app, req).await, "stable");
+    }
+}
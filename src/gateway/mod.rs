@@ -0,0 +1,347 @@
+//! Reverse proxying for the edge service
+//!
+//! An `UpstreamProxy` forwards every request under a public prefix to one upstream service:
+//! the prefix is rewritten, hop-by-hop headers are dropped, `X-Forwarded-*` and Trace
+//! Context are added, and the call goes through a circuit breaker with retries for
+//! idempotent methods. Proxied routes are ordinary routes of the app, so the server's
+//! auth, tenant and rate limiting middleware apply before forwarding.
+//!
+//! ```ignore
+//! let inventory = UpstreamProxy::new("inventory", "http://inventory:8080")
+//!     .mount("/api/inventory")
+//!     .upstream_prefix("/api/v1")
+//!     .strip_header("cookie");
+//! let billing = UpstreamProxy::new("billing", "http://billing:8080").mount("/api/billing");
+//!
+//! ServerBuilder::new().run(move |cfg| {
+//!     inventory.configure(cfg);
+//!     billing.configure(cfg);
+//! }).await?;
+//! ```
+//!
+//! `GET /api/inventory/products?page=2` is sent to `http://inventory:8080/api/v1/products?page=2`.
+//! Bodies are buffered, so the proxy is not meant for large uploads or streaming responses.
+//! `canary::CanaryProxy` splits one mount between a stable and a canary upstream.
+
+pub mod canary;
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::{error, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::error::LanaiError;
+use crate::resilience::{CircuitBreaker, CircuitBreakerError, CircuitBreakerOutcome};
+use crate::saga::RetryPolicy;
+
+/// Headers that describe one connection and must not be forwarded (RFC 9110 §7.6.1).
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Proxy error types
+#[derive(Debug, Error, Clone)]
+pub enum ProxyError {
+    #[error("Upstream '{0}' is unreachable: {1}")]
+    Unreachable(String, String),
+
+    #[error("Upstream '{0}' did not respond in time")]
+    Timeout(String),
+
+    /// 502, 503 or 504 from the upstream itself.
+    #[error("Upstream '{0}' answered {1}")]
+    Unavailable(String, u16),
+}
+
+impl From<ProxyError> for LanaiError {
+    fn from(e: ProxyError) -> Self {
+        match e {
+            ProxyError::Timeout(_) => Self::Timeout("Upstream service did not respond in time".to_string()),
+            _ => Self::Unavailable("Upstream service unavailable, please retry later".to_string()),
+        }
+    }
+}
+
+type Rewrite = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+struct Inner {
+    name: String,
+    upstream: String,
+    mount: String,
+    upstream_prefix: String,
+    rewrite: Option<Rewrite>,
+    strip_headers: Vec<String>,
+    retry: RetryPolicy<ProxyError>,
+    breaker: Arc<CircuitBreaker>,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+/// Forwards requests under `mount` to one upstream service.
+#[derive(Clone)]
+pub struct UpstreamProxy {
+    inner: Arc<Inner>,
+}
+
+impl UpstreamProxy {
+    /// 30s timeout, 3 attempts for idempotent methods, breaker opening after 5 consecutive
+    /// upstream failures for 30s.
+    pub fn new(name: &str, upstream: &str) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                upstream: upstream.trim_end_matches('/').to_string(),
+                mount: String::new(),
+                upstream_prefix: String::new(),
+                rewrite: None,
+                strip_headers: Vec::new(),
+                retry: RetryPolicy::exponential(3, Duration::from_millis(100)).with_max_backoff(Duration::from_secs(2)),
+                breaker: Arc::new(CircuitBreaker::new(5, Duration::from_secs(30))),
+                timeout: Duration::from_secs(30),
+                client: reqwest::Client::builder()
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()
+                    .expect("HTTP client for upstream requests"),
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("UpstreamProxy is configured before it is cloned")
+    }
+
+    /// Public path prefix handled by this proxy; it is removed before forwarding.
+    pub fn mount(mut self, prefix: &str) -> Self {
+        self.inner_mut().mount = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Prefix added to the forwarded path (e.g. `/api/v1`).
+    pub fn upstream_prefix(mut self, prefix: &str) -> Self {
+        self.inner_mut().upstream_prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Compute the upstream path from the path below the mount, replacing `upstream_prefix`.
+    pub fn rewrite<F>(mut self, rewrite: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.inner_mut().rewrite = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Do not forward `name` (e.g. `cookie` for an upstream that only needs the bearer token).
+    pub fn strip_header(mut self, name: &str) -> Self {
+        self.inner_mut().strip_headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner_mut().timeout = timeout;
+        self
+    }
+
+    /// Retries apply to idempotent methods only; `POST` and `PATCH` are attempted once.
+    pub fn retry(mut self, retry: RetryPolicy<ProxyError>) -> Self {
+        self.inner_mut().retry = retry;
+        self
+    }
+
+    pub fn circuit_breaker(mut self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.inner_mut().breaker = Arc::new(CircuitBreaker::new(failure_threshold, reset_timeout));
+        self
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.inner.breaker.clone()
+    }
+
+    /// Mount the mount prefix and everything below it.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let proxy = self.clone();
+        cfg.service(web::scope(&self.inner.mount).default_service(web::to(move |req: HttpRequest, body: web::Bytes| {
+            let proxy = proxy.clone();
+            async move { proxy.forward(req, body).await }
+        })));
+    }
+
+    /// Upstream URL for a request path and query.
+    pub fn upstream_url(&self, path: &str, query: &str) -> String {
+        let inner = &self.inner;
+        let below = path.strip_prefix(inner.mount.as_str()).unwrap_or(path);
+        let below = if below.is_empty() { "/" } else { below };
+        let path = match &inner.rewrite {
+            Some(rewrite) => rewrite(below),
+            None => format!("{}{}", inner.upstream_prefix, below),
+        };
+        match query {
+            "" => format!("{}{}", inner.upstream, path),
+            query => format!("{}{}?{}", inner.upstream, path, query),
+        }
+    }
+
+    /// Forward `req` upstream and relay the response. Usable as a handler for custom routes.
+    pub async fn forward(&self, req: HttpRequest, body: web::Bytes) -> Result<HttpResponse, LanaiError> {
+        let inner = &self.inner;
+        let url = self.upstream_url(req.path(), req.query_string());
+        // actix and reqwest use different `http` crate versions
+        let method = reqwest::Method::from_bytes(req.method().as_str().as_bytes()).map_err(LanaiError::internal)?;
+        let headers = self.request_headers(&req);
+        let idempotent = matches!(
+            method,
+            reqwest::Method::GET | reqwest::Method::HEAD | reqwest::Method::OPTIONS | reqwest::Method::PUT | reqwest::Method::DELETE
+        );
+
+        let mut attempt = 1;
+        loop {
+            let outcome = inner
+                .breaker
+                .call(|| async {
+                    let response = inner
+                        .client
+                        .request(method.clone(), &url)
+                        .timeout(inner.timeout)
+                        .headers(headers.clone())
+                        .body(body.clone())
+                        .send()
+                        .await
+                        .map_err(|e| {
+                            if e.is_timeout() {
+                                ProxyError::Timeout(inner.name.clone())
+                            } else {
+                                ProxyError::Unreachable(inner.name.clone(), e.to_string())
+                            }
+                        })?;
+                    match response.status().as_u16() {
+                        status @ 502..=504 => Err(ProxyError::Unavailable(inner.name.clone(), status)),
+                        _ => Ok(response),
+                    }
+                })
+                .await;
+
+            let e = match outcome {
+                Ok(response) => return relay(response).await,
+                Err(CircuitBreakerOutcome::CircuitOpen) => {
+                    warn!("⚠️ Circuit for upstream '{}' is open, rejecting {} {}", inner.name, method, url);
+                    return Err(CircuitBreakerError::Open.into());
+                }
+                Err(CircuitBreakerOutcome::OperationError(e)) => e,
+            };
+
+            if !idempotent || !inner.retry.should_retry(&e, attempt) {
+                error!("❌ Proxying {} {} failed: {}", method, url, e);
+                return Err(e.into());
+            }
+            let backoff = inner.retry.backoff(attempt);
+            warn!(
+                "🔄 {}, retrying {} {} in {:?} (attempt {}/{})",
+                e, method, url, backoff, attempt, inner.retry.max_attempts
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    fn request_headers(&self, req: &HttpRequest) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in req.headers() {
+            let lower = name.as_str();
+            if lower == "host" || HOP_BY_HOP.contains(&lower) || self.inner.strip_headers.iter().any(|h| h == lower) {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                reqwest::header::HeaderName::from_bytes(name.as_str().as_bytes()),
+                reqwest::header::HeaderValue::from_bytes(value.as_bytes()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+
+        let info = req.connection_info();
+        let forwarded_for = match (req.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok()), info.peer_addr()) {
+            (Some(chain), Some(peer)) => format!("{}, {}", chain, peer),
+            (None, Some(peer)) => peer.to_string(),
+            (Some(chain), None) => chain.to_string(),
+            (None, None) => String::new(),
+        };
+        for (name, value) in [
+            ("x-forwarded-for", forwarded_for.as_str()),
+            ("x-forwarded-host", info.host()),
+            ("x-forwarded-proto", info.scheme()),
+        ] {
+            if let Ok(value) = reqwest::header::HeaderValue::from_str(value) {
+                if !value.is_empty() {
+                    headers.insert(name, value);
+                }
+            }
+        }
+
+        let cx = tracing::Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut opentelemetry_http::HeaderInjector(&mut headers))
+        });
+        headers
+    }
+}
+
+async fn relay(response: reqwest::Response) -> Result<HttpResponse, LanaiError> {
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut builder = HttpResponse::build(status);
+    for (name, value) in response.headers() {
+        if !HOP_BY_HOP.contains(&name.as_str()) && name != reqwest::header::CONTENT_LENGTH {
+            builder.append_header((name.as_str(), value.as_bytes()));
+        }
+    }
+    let body = response.bytes().await.map_err(|e| {
+        warn!("⚠️ Upstream response body could not be read: {}", e);
+        LanaiError::Unavailable("Upstream service unavailable, please retry later".to_string())
+    })?;
+    Ok(builder.body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test as actix_test;
+
+    #[test]
+    fn test_upstream_url_rewriting() {
+        let proxy = UpstreamProxy::new("inventory", "http://inventory:8080/").mount("/api/inventory").upstream_prefix("/api/v1");
+        assert_eq!(proxy.upstream_url("/api/inventory/products", "page=2"), "http://inventory:8080/api/v1/products?page=2");
+        assert_eq!(proxy.upstream_url("/api/inventory", ""), "http://inventory:8080/api/v1/");
+
+        let proxy = UpstreamProxy::new("legacy", "http://legacy").rewrite(|path| path.replace("/v2/", "/v1/"));
+        assert_eq!(proxy.upstream_url("/v2/orders", ""), "http://legacy/v1/orders");
+    }
+
+    #[actix_web::test]
+    async fn test_request_headers_are_filtered() {
+        let proxy = UpstreamProxy::new("inventory", "http://inventory:8080").strip_header("Cookie");
+        let req = actix_test::TestRequest::get()
+            .uri("/products")
+            .insert_header(("connection", "keep-alive"))
+            .insert_header(("cookie", "session=1"))
+            .insert_header(("authorization", "Bearer t"))
+            .insert_header(("x-forwarded-for", "203.0.113.7"))
+            .peer_addr("10.0.0.2:5000".parse().unwrap())
+            .to_http_request();
+
+        let headers = proxy.request_headers(&req);
+        assert!(headers.get("connection").is_none());
+        assert!(headers.get("cookie").is_none());
+        assert_eq!(headers["authorization"], "Bearer t");
+        assert_eq!(headers["x-forwarded-for"], "203.0.113.7, 10.0.0.2");
+    }
+}
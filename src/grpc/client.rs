@@ -0,0 +1,319 @@
+//! Resilient tonic channel builder
+
+use opentelemetry::propagation::Injector;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::{deadline, GrpcError};
+use crate::resilience::{CircuitBreaker, CircuitBreakerOutcome};
+use crate::saga::RetryPolicy;
+
+/// Channel type to hand to generated clients (`FooClient::new(channel.channel())`).
+pub type GrpcService = InterceptedService<Channel, ClientInterceptor>;
+
+type TokenFn = Arc<dyn Fn() -> Option<String> + Send + Sync>;
+
+/// Codes that indicate the service (not the request) is failing; they trip the breaker.
+fn is_transient(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted | Code::Unknown
+    )
+}
+
+pub struct GrpcClientBuilder {
+    service: String,
+    endpoints: Vec<String>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy<Status>,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    token: Option<TokenFn>,
+}
+
+impl GrpcClientBuilder {
+    /// 5s call timeout, 3 attempts on `UNAVAILABLE`/`RESOURCE_EXHAUSTED`,
+    /// breaker opening after 5 consecutive transient failures for 30s.
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            endpoints: Vec::new(),
+            timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(3),
+            retry: RetryPolicy::exponential(3, Duration::from_millis(100))
+                .with_max_backoff(Duration::from_secs(2))
+                .retry_if(|status: &Status| matches!(status.code(), Code::Unavailable | Code::ResourceExhausted)),
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+            token: None,
+        }
+    }
+
+    pub fn endpoint(mut self, url: &str) -> Self {
+        self.endpoints.push(url.to_string());
+        self
+    }
+
+    /// Balance calls across `urls` (e.g. one per pod behind a headless service).
+    pub fn endpoints<I, S>(mut self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.endpoints.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Default deadline per call when no shorter propagated deadline applies.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy<Status>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn circuit_breaker(mut self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.failure_threshold = failure_threshold;
+        self.reset_timeout = reset_timeout;
+        self
+    }
+
+    /// Send `authorization: Bearer <token>` with every call; `token` is read per call so it can rotate.
+    pub fn service_token<F>(mut self, token: F) -> Self
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.token = Some(Arc::new(token));
+        self
+    }
+
+    /// Connect lazily: the channel is usable immediately and connects on first call.
+    pub fn build(self) -> Result<GrpcChannel, GrpcError> {
+        let endpoints = self
+            .endpoints
+            .iter()
+            .map(|url| {
+                Endpoint::from_shared(url.clone())
+                    .map(|endpoint| {
+                        endpoint
+                            .connect_timeout(self.connect_timeout)
+                            .tcp_keepalive(Some(Duration::from_secs(30)))
+                            .http2_keep_alive_interval(Duration::from_secs(30))
+                    })
+                    .map_err(|e| GrpcError::InvalidEndpoint(url.clone(), e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let channel = match endpoints.len() {
+            0 => return Err(GrpcError::NoEndpoints(self.service)),
+            1 => endpoints[0].connect_lazy(),
+            _ => Channel::balance_list(endpoints.into_iter()),
+        };
+
+        let interceptor = ClientInterceptor { timeout: self.timeout, token: self.token };
+        Ok(GrpcChannel {
+            service: Arc::from(self.service),
+            channel: InterceptedService::new(channel, interceptor),
+            breaker: Arc::new(CircuitBreaker::new(self.failure_threshold, self.reset_timeout)),
+            retry: self.retry,
+        })
+    }
+}
+
+/// Adds deadline, trace context and service token metadata to every call.
+#[derive(Clone)]
+pub struct ClientInterceptor {
+    timeout: Duration,
+    token: Option<TokenFn>,
+}
+
+impl Interceptor for ClientInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let timeout = match deadline::remaining() {
+            Some(remaining) if remaining.is_zero() => {
+                return Err(Status::deadline_exceeded("deadline expired before the call was made"))
+            }
+            Some(remaining) => remaining.min(self.timeout),
+            None => self.timeout,
+        };
+        request.set_timeout(timeout);
+
+        let cx = tracing::Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut MetadataInjector(request.metadata_mut()));
+        });
+
+        if let Some(token) = self.token.as_ref().and_then(|token| token()) {
+            let value = MetadataValue::try_from(format!("Bearer {}", token))
+                .map_err(|_| Status::internal("service token is not valid ASCII"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// Helper for injecting OTEL context into gRPC metadata
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl<'a> Injector for MetadataInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(key) = MetadataKey::from_bytes(key.as_bytes()) {
+            if let Ok(value) = MetadataValue::try_from(value.as_str()) {
+                self.0.insert(key, value);
+            }
+        }
+    }
+}
+
+/// A configured channel plus the breaker and retry policy for its service.
+#[derive(Clone)]
+pub struct GrpcChannel {
+    service: Arc<str>,
+    channel: GrpcService,
+    breaker: Arc<CircuitBreaker>,
+    retry: RetryPolicy<Status>,
+}
+
+impl GrpcChannel {
+    pub fn channel(&self) -> GrpcService {
+        self.channel.clone()
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.breaker.clone()
+    }
+
+    /// Run `call` through the circuit breaker, retrying transient failures.
+    ///
+    /// `call` is invoked once per attempt, so build the request inside it. Only transient
+    /// status codes count as breaker failures; `NOT_FOUND` and friends pass straight through.
+    pub async fn call<T, F, Fut>(&self, mut call: F) -> Result<tonic::Response<T>, Status>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<tonic::Response<T>, Status>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let outcome = self
+                .breaker
+                .call(|| {
+                    let fut = call();
+                    async move {
+                        match fut.await {
+                            Err(status) if is_transient(&status) => Err(status),
+                            other => Ok(other),
+                        }
+                    }
+                })
+                .await;
+
+            let status = match outcome {
+                Ok(result) => return result,
+                Err(CircuitBreakerOutcome::CircuitOpen) => {
+                    return Err(Status::unavailable(format!("circuit breaker for '{}' is open", self.service)))
+                }
+                Err(CircuitBreakerOutcome::OperationError(status)) => status,
+            };
+
+            if !self.retry.should_retry(&status, attempt) {
+                return Err(status);
+            }
+            let backoff = self.retry.backoff(attempt);
+            if deadline::remaining().is_some_and(|remaining| remaining <= backoff) {
+                return Err(status);
+            }
+            log::warn!(
+                "🔄 gRPC call to '{}' failed with {:?}, retrying in {:?} (attempt {}/{})",
+                self.service,
+                status.code(),
+                backoff,
+                attempt,
+                self.retry.max_attempts
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn channel() -> GrpcChannel {
+        GrpcClientBuilder::new("test")
+            .endpoint("http://127.0.0.1:1")
+            .retry(
+                RetryPolicy::exponential(3, Duration::from_millis(1))
+                    .retry_if(|status: &Status| status.code() == Code::Unavailable),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_sets_timeout_and_token() {
+        let mut interceptor = ClientInterceptor {
+            timeout: Duration::from_secs(5),
+            token: Some(Arc::new(|| Some("svc-token".to_string()))),
+        };
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer svc-token");
+        assert!(request.metadata().get("grpc-timeout").is_some());
+
+        let expired = tokio::time::Instant::now();
+        let result = deadline::with_deadline(expired, async move { interceptor.call(Request::new(())) }).await;
+        assert_eq!(result.unwrap_err().code(), Code::DeadlineExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_call_retries_transient_errors_only() {
+        let channel = channel();
+        let attempts = AtomicU32::new(0);
+        let result = channel
+            .call(|| {
+                let n = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if n < 2 {
+                        Err(Status::unavailable("down"))
+                    } else {
+                        Ok(tonic::Response::new(n))
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap().into_inner(), 2);
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<tonic::Response<()>, _> = channel
+            .call(|| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err(Status::not_found("no such order")) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::NotFound);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_build_requires_endpoints() {
+        assert!(matches!(GrpcClientBuilder::new("x").build(), Err(GrpcError::NoEndpoints(_))));
+    }
+}
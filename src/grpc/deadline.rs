@@ -0,0 +1,94 @@
+//! Deadline propagation
+//!
+//! A deadline set with `with_deadline` applies to every gRPC call made by `GrpcChannel`s
+//! inside that future, so a request that arrived with 800ms left never waits 5s on a
+//! downstream call. Servers can pick up the caller's deadline with `from_request`.
+//!
+//! ```ignore
+//! async fn get_order(&self, request: Request<GetOrderRequest>) -> Result<Response<Order>, Status> {
+//!     let deadline = deadline::from_request(&request);
+//!     deadline::with_optional_deadline(deadline, self.load_order(request.into_inner())).await
+//! }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Run `fut` with `deadline` (or the enclosing deadline, if earlier).
+pub async fn with_deadline<F: Future>(deadline: Instant, fut: F) -> F::Output {
+    let effective = current().map_or(deadline, |outer| outer.min(deadline));
+    DEADLINE.scope(effective, fut).await
+}
+
+pub async fn with_optional_deadline<F: Future>(deadline: Option<Instant>, fut: F) -> F::Output {
+    match deadline {
+        Some(deadline) => with_deadline(deadline, fut).await,
+        None => fut.await,
+    }
+}
+
+pub fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Time left before the current deadline, if any.
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Deadline of an incoming request, from its `grpc-timeout` header.
+pub fn from_request<T>(request: &tonic::Request<T>) -> Option<Instant> {
+    let header = request.metadata().get("grpc-timeout")?.to_str().ok()?;
+    parse_grpc_timeout(header).map(|timeout| Instant::now() + timeout)
+}
+
+/// Parse the `grpc-timeout` wire format: up to 8 digits followed by a unit (`H M S m u n`).
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grpc_timeout() {
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_grpc_timeout("3S"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_grpc_timeout("10x"), None);
+        assert_eq!(parse_grpc_timeout("S"), None);
+    }
+
+    #[tokio::test]
+    async fn test_nested_deadlines_keep_the_earliest() {
+        assert!(current().is_none());
+        let outer = Instant::now() + Duration::from_millis(100);
+        with_deadline(outer, async move {
+            with_deadline(Instant::now() + Duration::from_secs(10), async move {
+                assert_eq!(current(), Some(outer));
+                assert!(remaining().unwrap() <= Duration::from_millis(100));
+            })
+            .await;
+        })
+        .await;
+    }
+}
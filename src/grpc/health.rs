@@ -0,0 +1,179 @@
+//! `grpc.health.v1.Health` backed by the `HealthRegistry`
+//!
+//! gRPC probes (`grpc_health_probe`, Kubernetes `grpc:` probes, Envoy health checks) get
+//! the same answer as `GET /health/ready`: `NOT_SERVING` while any critical check is down.
+
+use async_trait::async_trait;
+use futures_util::Stream;
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::{Request, Response, Status};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse};
+
+use crate::health::{HealthRegistry, HealthStatus, Probe};
+
+/// Serves the standard health protocol for the whole server (`service: ""`) and for each
+/// registered service name. Clones share the draining flag.
+///
+/// ```ignore
+/// let health = GrpcHealthService::new(registry.clone())
+///     .service("lanai.inventory.v1.InventoryService");
+///
+/// Server::builder()
+///     .add_service(health.clone().into_server())
+///     .add_service(InventoryServiceServer::new(inventory))
+///     .serve_with_shutdown(addr, async move {
+///         shutdown_signal().await;
+///         health.drain();
+///     })
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub struct GrpcHealthService {
+    registry: HealthRegistry,
+    services: Arc<HashSet<String>>,
+    draining: Arc<AtomicBool>,
+    watch_interval: Duration,
+}
+
+impl GrpcHealthService {
+    /// `Watch` streams re-check every 5 seconds.
+    pub fn new(registry: HealthRegistry) -> Self {
+        Self {
+            registry,
+            services: Arc::new(HashSet::new()),
+            draining: Arc::new(AtomicBool::new(false)),
+            watch_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Answer for `name` (the fully-qualified service, e.g. `lanai.orders.v1.Orders`).
+    pub fn service(mut self, name: &str) -> Self {
+        Arc::make_mut(&mut self.services).insert(name.to_string());
+        self
+    }
+
+    pub fn watch_interval(mut self, interval: Duration) -> Self {
+        self.watch_interval = interval;
+        self
+    }
+
+    /// Report `NOT_SERVING` from now on, so load balancers stop routing here before shutdown.
+    pub fn drain(&self) {
+        log::info!("🛑 gRPC health: draining, reporting NOT_SERVING");
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn into_server(self) -> HealthServer<Self> {
+        HealthServer::new(self)
+    }
+
+    fn knows(&self, service: &str) -> bool {
+        service.is_empty() || self.services.contains(service)
+    }
+
+    async fn status(&self) -> ServingStatus {
+        if self.draining.load(Ordering::SeqCst) {
+            return ServingStatus::NotServing;
+        }
+        match self.registry.report(Probe::Readiness).await.status {
+            HealthStatus::Down => ServingStatus::NotServing,
+            HealthStatus::Up | HealthStatus::Degraded => ServingStatus::Serving,
+        }
+    }
+}
+
+fn response(status: ServingStatus) -> HealthCheckResponse {
+    HealthCheckResponse { status: status.into() }
+}
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send>>;
+
+#[async_trait]
+impl Health for GrpcHealthService {
+    async fn check(&self, request: Request<HealthCheckRequest>) -> Result<Response<HealthCheckResponse>, Status> {
+        let service = request.into_inner().service;
+        if !self.knows(&service) {
+            return Err(Status::not_found(format!("unknown service '{}'", service)));
+        }
+        Ok(Response::new(response(self.status().await)))
+    }
+
+    type WatchStream = WatchStream;
+
+    /// Sends the current status, then every change. Unknown services get `SERVICE_UNKNOWN`
+    /// rather than an error, as the protocol requires.
+    async fn watch(&self, request: Request<HealthCheckRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let service = request.into_inner().service;
+        let known = self.knows(&service);
+        let this = self.clone();
+
+        let stream = futures_util::stream::unfold(None, move |last: Option<ServingStatus>| {
+            let this = this.clone();
+            async move {
+                loop {
+                    let status = if known { this.status().await } else { ServingStatus::ServiceUnknown };
+                    if last != Some(status) {
+                        return Some((Ok(response(status)), Some(status)));
+                    }
+                    tokio::time::sleep(this.watch_interval).await;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::{CheckResult, HealthCheck};
+    use futures_util::StreamExt;
+
+    struct Fixed(CheckResult);
+
+    #[async_trait]
+    impl HealthCheck for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn check(&self) -> CheckResult {
+            self.0.clone()
+        }
+    }
+
+    fn request(service: &str) -> Request<HealthCheckRequest> {
+        Request::new(HealthCheckRequest { service: service.to_string() })
+    }
+
+    #[tokio::test]
+    async fn test_check_follows_registry() {
+        let up = GrpcHealthService::new(HealthRegistry::new().register(Fixed(CheckResult::up()))).service("lanai.Orders");
+        let res = up.check(request("lanai.Orders")).await.unwrap().into_inner();
+        assert_eq!(res.status, ServingStatus::Serving as i32);
+        assert_eq!(up.check(request("lanai.Unknown")).await.unwrap_err().code(), tonic::Code::NotFound);
+
+        up.drain();
+        let res = up.check(request("")).await.unwrap().into_inner();
+        assert_eq!(res.status, ServingStatus::NotServing as i32);
+
+        let down = GrpcHealthService::new(HealthRegistry::new().register(Fixed(CheckResult::down("refused"))));
+        let res = down.check(request("")).await.unwrap().into_inner();
+        assert_eq!(res.status, ServingStatus::NotServing as i32);
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_unknown_services() {
+        let health = GrpcHealthService::new(HealthRegistry::new());
+        let mut stream = health.watch(request("lanai.Unknown")).await.unwrap().into_inner();
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.status, ServingStatus::ServiceUnknown as i32);
+    }
+}
@@ -0,0 +1,44 @@
+//! gRPC Clients
+//!
+//! `GrpcClientBuilder` produces a tonic channel with the same guarantees our HTTP stack
+//! gives: load balancing across endpoints, a per-service circuit breaker, retries for
+//! transient failures, deadline propagation, trace context injection and service-token
+//! authentication.
+//!
+//! ```ignore
+//! let orders = GrpcClientBuilder::new("lanai-orders")
+//!     .endpoints(["http://orders-0.orders:50051", "http://orders-1.orders:50051"])
+//!     .service_token(move || token_cache.current())
+//!     .build()?;
+//! let client = OrdersClient::new(orders.channel());
+//!
+//! let order = orders
+//!     .call(|| {
+//!         let mut client = client.clone();
+//!         async move { client.get_order(GetOrderRequest { id }).await }
+//!     })
+//!     .await?
+//!     .into_inner();
+//! ```
+//!
+//! Servers expose `grpc.health.v1.Health` from their `HealthRegistry` with
+//! `GrpcHealthService`.
+
+use thiserror::Error;
+
+pub mod client;
+pub mod deadline;
+pub mod health;
+
+pub use client::{ClientInterceptor, GrpcChannel, GrpcClientBuilder, GrpcService};
+pub use health::GrpcHealthService;
+
+/// gRPC client configuration errors
+#[derive(Debug, Error)]
+pub enum GrpcError {
+    #[error("No endpoints configured for gRPC service '{0}'")]
+    NoEndpoints(String),
+
+    #[error("Invalid endpoint '{0}': {1}")]
+    InvalidEndpoint(String, String),
+}
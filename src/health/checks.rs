@@ -0,0 +1,193 @@
+//! Built-in health checks for the infrastructure this crate wires up
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{CheckResult, HealthCheck};
+use crate::messaging::NatsClient;
+use crate::resilience::{CircuitBreaker, CircuitState};
+
+/// Global NATS connection state (`NatsClient::init`); degraded while the connection flaps,
+/// its server is in lame duck mode or a consumer is too slow (see `messaging::connection`).
+pub struct NatsCheck;
+
+#[async_trait]
+impl HealthCheck for NatsCheck {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn check(&self) -> CheckResult {
+        if !NatsClient::is_connected() {
+            CheckResult::down(NatsClient::connection_status())
+        } else if let Some(reason) = NatsClient::connection_degradation() {
+            CheckResult::degraded(reason)
+        } else {
+            CheckResult::up()
+        }
+    }
+}
+
+/// Redis `PING`.
+pub struct RedisCheck {
+    conn: ConnectionManager,
+}
+
+impl RedisCheck {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for RedisCheck {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn check(&self) -> CheckResult {
+        match redis::cmd("PING").query_async::<_, String>(&mut self.conn.clone()).await {
+            Ok(_) => CheckResult::up(),
+            Err(e) => CheckResult::down(e.to_string()),
+        }
+    }
+}
+
+/// `SELECT 1` on a Postgres pool, reporting pool usage.
+pub struct PostgresCheck {
+    name: String,
+    pool: PgPool,
+}
+
+impl PostgresCheck {
+    pub fn new(pool: PgPool) -> Self {
+        Self::named("postgres", pool)
+    }
+
+    /// Distinguish several pools, e.g. `postgres-replica`.
+    pub fn named(name: &str, pool: PgPool) -> Self {
+        Self { name: name.to_string(), pool }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for PostgresCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> CheckResult {
+        // The registry's timeout bounds the whole check; this just avoids waiting forever on the pool.
+        match crate::db::health_check(&self.pool, Duration::from_secs(5)).await {
+            Ok(_) => CheckResult::up().with_details(format!(
+                "{} connections ({} idle)",
+                self.pool.size(),
+                self.pool.num_idle()
+            )),
+            Err(e) => CheckResult::down(e.to_string()),
+        }
+    }
+}
+
+/// Free space on the filesystem holding `path`.
+pub struct DiskCheck {
+    path: PathBuf,
+    min_free_ratio: f64,
+}
+
+impl DiskCheck {
+    /// Down once less than `min_free_ratio` (0.0..1.0) of the filesystem is free,
+    /// degraded below twice that.
+    pub fn new(path: impl Into<PathBuf>, min_free_ratio: f64) -> Self {
+        Self { path: path.into(), min_free_ratio }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for DiskCheck {
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    async fn check(&self) -> CheckResult {
+        let path = self.path.clone();
+        let space = tokio::task::spawn_blocking(move || {
+            Ok::<_, std::io::Error>((fs2::available_space(&path)?, fs2::total_space(&path)?))
+        })
+        .await;
+
+        let (available, total) = match space {
+            Ok(Ok(space)) => space,
+            Ok(Err(e)) => return CheckResult::down(format!("{}: {}", self.path.display(), e)),
+            Err(e) => return CheckResult::down(e.to_string()),
+        };
+
+        let ratio = if total == 0 { 0.0 } else { available as f64 / total as f64 };
+        let details = format!("{:.1}% free on {}", ratio * 100.0, self.path.display());
+        if ratio < self.min_free_ratio {
+            CheckResult::down(details)
+        } else if ratio < self.min_free_ratio * 2.0 {
+            CheckResult::degraded(details)
+        } else {
+            CheckResult::up().with_details(details)
+        }
+    }
+}
+
+/// Reports an open circuit as down and a half-open one as degraded.
+///
+/// Usually registered with `register_non_critical`: a tripped breaker means a dependency is
+/// failing, which should not take this service out of rotation.
+pub struct CircuitBreakerCheck {
+    name: String,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl CircuitBreakerCheck {
+    pub fn new(name: &str, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { name: format!("circuit:{}", name), breaker }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for CircuitBreakerCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> CheckResult {
+        match self.breaker.state().await {
+            CircuitState::Closed => CheckResult::up(),
+            CircuitState::HalfOpen => CheckResult::degraded("half-open"),
+            CircuitState::Open => CheckResult::down("open"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::HealthStatus;
+
+    #[tokio::test]
+    async fn test_circuit_breaker_check() {
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let check = CircuitBreakerCheck::new("payments", breaker.clone());
+        assert_eq!(check.name(), "circuit:payments");
+        assert_eq!(check.check().await.status, HealthStatus::Up);
+
+        let _ = breaker.call(|| async { Err::<(), _>("boom") }).await;
+        assert_eq!(check.check().await.status, HealthStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn test_disk_check_thresholds() {
+        assert_eq!(DiskCheck::new(std::env::temp_dir(), 0.0).check().await.status, HealthStatus::Up);
+        assert_eq!(DiskCheck::new(std::env::temp_dir(), 1.1).check().await.status, HealthStatus::Down);
+        assert_eq!(DiskCheck::new("/nonexistent/path", 0.1).check().await.status, HealthStatus::Down);
+    }
+}
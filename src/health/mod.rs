@@ -0,0 +1,306 @@
+//! Health Check Registry
+//!
+//! Services register `HealthCheck`s once and expose them as Kubernetes probes:
+//! - `GET /health/live`: liveness, only checks registered with `register_liveness`
+//!   (a failure here gets the pod restarted, so keep it to "the process is wedged")
+//! - `GET /health/ready` (and `GET /health`): readiness, every check
+//!
+//! Checks run concurrently, each bounded by a timeout, and reports are cached briefly so
+//! aggressive probing does not hammer dependencies. Any critical check `Down` makes the
+//! probe return 503; non-critical failures only mark the report `degraded`.
+//! `ReadinessController` turns dependency and breaker state into one readiness check.
+//!
+//! ```ignore
+//! let health = HealthRegistry::new()
+//!     .register(PostgresCheck::new(pool.clone()))
+//!     .register(RedisCheck::new(shared_connection().await?))
+//!     .register_non_critical(CircuitBreakerCheck::new("payments", payments_breaker.clone()))
+//!     .register_liveness(DiskCheck::new("/tmp", 0.05));
+//!
+//! ServerBuilder::new("lanai-billing").health(health).run(routes).await
+//! ```
+
+use actix_web::{web, HttpResponse};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub mod checks;
+pub mod readiness;
+pub mod startup;
+
+pub use checks::{CircuitBreakerCheck, DiskCheck, NatsCheck, PostgresCheck, RedisCheck};
+pub use readiness::ReadinessController;
+pub use startup::{wait_for_dependencies, DependencyGate, StartupError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl CheckResult {
+    pub fn up() -> Self {
+        Self { status: HealthStatus::Up, details: None, duration_ms: 0 }
+    }
+
+    pub fn degraded(details: impl Into<String>) -> Self {
+        Self { status: HealthStatus::Degraded, details: Some(details.into()), duration_ms: 0 }
+    }
+
+    pub fn down(details: impl Into<String>) -> Self {
+        Self { status: HealthStatus::Down, details: Some(details.into()), duration_ms: 0 }
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Key of this check in health reports.
+    fn name(&self) -> &str;
+
+    async fn check(&self) -> CheckResult;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Probe {
+    Liveness,
+    Readiness,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<String, CheckResult>,
+    pub checked_at: DateTime<Utc>,
+}
+
+struct Registered {
+    check: Arc<dyn HealthCheck>,
+    liveness: bool,
+    critical: bool,
+}
+
+#[derive(Clone)]
+pub struct HealthRegistry {
+    checks: Arc<Vec<Registered>>,
+    timeout: Duration,
+    cache_ttl: Duration,
+    live_cache: Arc<Mutex<Option<(Instant, HealthReport)>>>,
+    ready_cache: Arc<Mutex<Option<(Instant, HealthReport)>>>,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthRegistry {
+    /// Checks time out after 2 seconds; reports are reused for 2 seconds.
+    pub fn new() -> Self {
+        Self {
+            checks: Arc::new(Vec::new()),
+            timeout: Duration::from_secs(2),
+            cache_ttl: Duration::from_secs(2),
+            live_cache: Arc::new(Mutex::new(None)),
+            ready_cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long a report is served from cache. `Duration::ZERO` disables caching.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Readiness check; the service is not ready while it is down.
+    pub fn register<C: HealthCheck + 'static>(self, check: C) -> Self {
+        self.add(check, false, true)
+    }
+
+    /// Readiness check whose failure only degrades the report.
+    pub fn register_non_critical<C: HealthCheck + 'static>(self, check: C) -> Self {
+        self.add(check, false, false)
+    }
+
+    /// Liveness (and readiness) check; the pod is restarted while it is down.
+    pub fn register_liveness<C: HealthCheck + 'static>(self, check: C) -> Self {
+        self.add(check, true, true)
+    }
+
+    fn add<C: HealthCheck + 'static>(mut self, check: C, liveness: bool, critical: bool) -> Self {
+        let checks = Arc::get_mut(&mut self.checks).expect("register checks before cloning the registry");
+        checks.push(Registered { check: Arc::new(check), liveness, critical });
+        self
+    }
+
+    /// Run (or reuse a recent run of) the checks for `probe`.
+    pub async fn report(&self, probe: Probe) -> HealthReport {
+        let cache = match probe {
+            Probe::Liveness => &self.live_cache,
+            Probe::Readiness => &self.ready_cache,
+        };
+
+        // Holding the lock while checking also coalesces concurrent probes.
+        let mut cached = cache.lock().await;
+        if let Some((at, report)) = cached.as_ref() {
+            if at.elapsed() < self.cache_ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.run(probe).await;
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    async fn run(&self, probe: Probe) -> HealthReport {
+        let selected = self.checks.iter().filter(|r| probe == Probe::Readiness || r.liveness);
+        let results = futures_util::future::join_all(selected.map(|registered| async move {
+            let started = Instant::now();
+            let mut result = match tokio::time::timeout(self.timeout, registered.check.check()).await {
+                Ok(result) => result,
+                Err(_) => CheckResult::down(format!("timed out after {:?}", self.timeout)),
+            };
+            result.duration_ms = started.elapsed().as_millis() as u64;
+            (registered, result)
+        }))
+        .await;
+
+        let mut status = HealthStatus::Up;
+        let mut checks = BTreeMap::new();
+        for (registered, result) in results {
+            let effective = match result.status {
+                HealthStatus::Down if !registered.critical => HealthStatus::Degraded,
+                other => other,
+            };
+            if result.status != HealthStatus::Up {
+                warn!(
+                    "⚠️ Health check '{}' is {:?}: {}",
+                    registered.check.name(),
+                    result.status,
+                    result.details.as_deref().unwrap_or("-")
+                );
+            }
+            status = status.max(effective);
+            checks.insert(registered.check.name().to_string(), result);
+        }
+
+        HealthReport { status, checks, checked_at: Utc::now() }
+    }
+
+    /// Mount `/health`, `/health/live` and `/health/ready`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone()))
+            .route("/health", web::get().to(readiness))
+            .route("/health/live", web::get().to(liveness))
+            .route("/health/ready", web::get().to(readiness));
+    }
+}
+
+async fn liveness(registry: web::Data<HealthRegistry>) -> HttpResponse {
+    respond(registry.report(Probe::Liveness).await)
+}
+
+async fn readiness(registry: web::Data<HealthRegistry>) -> HttpResponse {
+    respond(registry.report(Probe::Readiness).await)
+}
+
+fn respond(report: HealthReport) -> HttpResponse {
+    match report.status {
+        HealthStatus::Down => HttpResponse::ServiceUnavailable().json(report),
+        _ => HttpResponse::Ok().json(report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct Fixed {
+        name: &'static str,
+        result: CheckResult,
+        delay: Duration,
+        calls: Arc<AtomicU32>,
+    }
+
+    impl Fixed {
+        fn new(name: &'static str, result: CheckResult) -> Self {
+            Self { name, result, delay: Duration::ZERO, calls: Arc::new(AtomicU32::new(0)) }
+        }
+    }
+
+    #[async_trait]
+    impl HealthCheck for Fixed {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn check(&self) -> CheckResult {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.result.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aggregation_by_probe_and_criticality() {
+        let registry = HealthRegistry::new()
+            .register_liveness(Fixed::new("process", CheckResult::up()))
+            .register_non_critical(Fixed::new("payments", CheckResult::down("circuit open")));
+
+        assert_eq!(registry.report(Probe::Liveness).await.checks.len(), 1);
+        let ready = registry.report(Probe::Readiness).await;
+        assert_eq!(ready.status, HealthStatus::Degraded);
+        assert_eq!(ready.checks["payments"].status, HealthStatus::Down);
+
+        let registry = HealthRegistry::new().register(Fixed::new("db", CheckResult::down("refused")));
+        assert_eq!(registry.report(Probe::Readiness).await.status, HealthStatus::Down);
+        assert_eq!(registry.report(Probe::Liveness).await.status, HealthStatus::Up);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_and_caching() {
+        let mut slow = Fixed::new("slow", CheckResult::up());
+        slow.delay = Duration::from_millis(200);
+        let calls = slow.calls.clone();
+
+        let registry = HealthRegistry::new()
+            .with_timeout(Duration::from_millis(20))
+            .with_cache_ttl(Duration::from_secs(60))
+            .register(slow);
+
+        let report = registry.report(Probe::Readiness).await;
+        assert_eq!(report.status, HealthStatus::Down);
+        assert!(report.checks["slow"].details.as_deref().unwrap().starts_with("timed out"));
+
+        registry.report(Probe::Readiness).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,201 @@
+//! Readiness gate
+//!
+//! `ReadinessController` takes a pod out of rotation while it cannot serve well, without
+//! restarting it: NATS disconnected, the database pool exhausted, or a critical circuit
+//! breaker open for too long. Services can also flip it themselves, e.g. while draining.
+//! Register it as a readiness check:
+//!
+//! ```ignore
+//! let readiness = ReadinessController::new()
+//!     .nats(Duration::from_secs(10))
+//!     .db_pool("postgres", pool.clone(), Duration::from_secs(15))
+//!     .breaker("payments", payments_breaker.clone(), Duration::from_secs(60));
+//! let health = HealthRegistry::new().register(readiness.clone());
+//!
+//! readiness.set_not_ready("draining");
+//! ```
+//!
+//! Each condition must hold for its grace period before the pod is reported not ready, so
+//! a reconnect or a burst of traffic does not flap readiness. The period is measured from
+//! the first probe that saw the failure.
+
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use super::{CheckResult, HealthCheck};
+use crate::messaging::NatsClient;
+use crate::resilience::CircuitBreaker;
+
+type Probe = Box<dyn Fn() -> BoxFuture<'static, Option<String>> + Send + Sync>;
+
+struct Condition {
+    name: String,
+    grace: Duration,
+    /// Returns why the condition is failing, if it is.
+    probe: Probe,
+    failing_since: Mutex<Option<Instant>>,
+}
+
+impl Condition {
+    /// The failure, once it has lasted for the grace period.
+    async fn evaluate(&self) -> Option<String> {
+        let failure = (self.probe)().await;
+        let mut since = self.failing_since.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(reason) = failure else {
+            if since.take().is_some() {
+                info!("✅ Readiness condition '{}' recovered", self.name);
+            }
+            return None;
+        };
+        let since = *since.get_or_insert_with(Instant::now);
+        (since.elapsed() >= self.grace).then(|| format!("{}: {}", self.name, reason))
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    conditions: Vec<Condition>,
+    forced: RwLock<Option<String>>,
+}
+
+/// Readiness from dependency and breaker state; clones share state.
+#[derive(Clone, Default)]
+pub struct ReadinessController {
+    inner: Arc<Inner>,
+}
+
+impl ReadinessController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("ReadinessController is configured before it is shared"));
+        self
+    }
+
+    /// Not ready after failing for `grace`. `probe` returns why it fails, if it does.
+    pub fn condition<F, Fut>(self, name: &str, grace: Duration, probe: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Option<String>> + Send + 'static,
+    {
+        let probe: Probe = Box::new(move || Box::pin(probe()));
+        self.update(|inner| {
+            inner.conditions.push(Condition {
+                name: name.to_string(),
+                grace,
+                probe,
+                failing_since: Mutex::new(None),
+            })
+        })
+    }
+
+    /// Not ready while the global NATS client has been disconnected for `grace`.
+    pub fn nats(self, grace: Duration) -> Self {
+        self.condition("nats", grace, || async {
+            (!NatsClient::is_connected()).then(|| NatsClient::connection_status().to_string())
+        })
+    }
+
+    /// Not ready while every connection of `pool` has been busy for `grace`.
+    pub fn db_pool(self, name: &str, pool: PgPool, grace: Duration) -> Self {
+        self.condition(name, grace, move || {
+            let pool = pool.clone();
+            async move {
+                let max = pool.options().get_max_connections();
+                (pool.size() >= max && pool.num_idle() == 0).then(|| format!("all {} connections in use", max))
+            }
+        })
+    }
+
+    /// Not ready once `breaker` has been out of Closed for `max_open`.
+    pub fn breaker(self, name: &str, breaker: Arc<CircuitBreaker>, max_open: Duration) -> Self {
+        self.condition(&format!("circuit:{}", name), Duration::ZERO, move || {
+            let breaker = breaker.clone();
+            async move {
+                let open_for = breaker.open_for().await?;
+                (open_for >= max_open).then(|| format!("open for {}s", open_for.as_secs()))
+            }
+        })
+    }
+
+    /// Report not ready regardless of the conditions until `set_ready`.
+    pub fn set_not_ready(&self, reason: &str) {
+        warn!("🚫 Marked not ready: {}", reason);
+        *self.inner.forced.write().unwrap_or_else(|e| e.into_inner()) = Some(reason.to_string());
+    }
+
+    pub fn set_ready(&self) {
+        if self.inner.forced.write().unwrap_or_else(|e| e.into_inner()).take().is_some() {
+            info!("✅ Readiness no longer forced off");
+        }
+    }
+
+    /// Reasons the pod is not ready; empty when it is.
+    pub async fn failures(&self) -> Vec<String> {
+        let mut failures: Vec<String> =
+            self.inner.forced.read().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect();
+        for condition in &self.inner.conditions {
+            failures.extend(condition.evaluate().await);
+        }
+        failures
+    }
+}
+
+#[async_trait]
+impl HealthCheck for ReadinessController {
+    fn name(&self) -> &str {
+        "readiness"
+    }
+
+    async fn check(&self) -> CheckResult {
+        let failures = self.failures().await;
+        if failures.is_empty() {
+            CheckResult::up()
+        } else {
+            CheckResult::down(failures.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::HealthStatus;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_conditions_and_forced_state() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let flag = failing.clone();
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let readiness = ReadinessController::new()
+            .condition("queue", Duration::from_millis(30), move || {
+                let failing = flag.load(Ordering::SeqCst);
+                async move { failing.then(|| "backlog".to_string()) }
+            })
+            .breaker("payments", breaker.clone(), Duration::ZERO);
+
+        // Within the grace period
+        assert_eq!(readiness.check().await.status, HealthStatus::Up);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(readiness.failures().await, vec!["queue: backlog".to_string()]);
+
+        failing.store(false, Ordering::SeqCst);
+        let _ = breaker.call(|| async { Err::<(), _>("boom") }).await;
+        assert_eq!(readiness.failures().await, vec!["circuit:payments: open for 0s".to_string()]);
+        breaker.reset().await;
+        assert!(breaker.open_for().await.is_none());
+
+        readiness.set_not_ready("draining");
+        let result = readiness.check().await;
+        assert_eq!((result.status, result.details.as_deref()), (HealthStatus::Down, Some("draining")));
+        readiness.set_ready();
+        assert_eq!(readiness.check().await.status, HealthStatus::Up);
+    }
+}
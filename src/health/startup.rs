@@ -0,0 +1,296 @@
+//! Waiting for dependencies before serving
+//!
+//! During a cluster cold start a service often comes up before Postgres, Redis or NATS
+//! accept connections; without a gate it crashes, backs off, and comes back at a random
+//! time. `wait_for_dependencies()` probes every configured dependency with bounded
+//! retries and fails with a report naming each one that never became reachable:
+//!
+//! ```ignore
+//! // DATABASE_URL, REDIS_URL, NATS_URL and LANAI_JWKS_URL, when set
+//! health::wait_for_dependencies().await?;
+//!
+//! // or explicitly, e.g. through ServerBuilder (runs before migrations and binding)
+//! let gate = DependencyGate::new().postgres(&db_url).nats(&nats_url).attempts(30);
+//! ServerBuilder::new("lanai-billing").wait_for(gate).run(routes).await
+//! ```
+
+use async_trait::async_trait;
+use log::{error, info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use super::{CheckResult, HealthCheck, HealthStatus};
+use crate::db::pool::DATABASE_URL_ENV;
+use crate::env::{EnvVar, VarType};
+use crate::messaging::{NatsClient, NATS_URL_ENV};
+use crate::rate_limit::REDIS_URL_ENV;
+
+/// URL of the identity provider's JWKS, probed when set
+pub const JWKS_URL_ENV: &str = "LANAI_JWKS_URL";
+
+/// Variables read by `DependencyGate::from_env` besides those of the probed subsystems
+pub const ENV_VARS: &[EnvVar] = &[EnvVar::new(JWKS_URL_ENV, VarType::Url).description("Identity provider JWKS URL")];
+
+/// Startup error types
+#[derive(Debug, Error)]
+pub enum StartupError {
+    #[error("Dependencies unavailable after {attempts} attempts: {}", format_failures(.failures))]
+    DependenciesUnavailable { attempts: u32, failures: Vec<(String, String)> },
+}
+
+fn format_failures(failures: &[(String, String)]) -> String {
+    failures.iter().map(|(name, reason)| format!("{} ({})", name, reason)).collect::<Vec<_>>().join(", ")
+}
+
+/// Dependencies to probe and how long to keep trying.
+#[derive(Clone)]
+pub struct DependencyGate {
+    checks: Vec<Arc<dyn HealthCheck>>,
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    attempt_timeout: Duration,
+}
+
+impl Default for DependencyGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DependencyGate {
+    /// 20 attempts, backing off from 500ms to 10s (about two and a half minutes in total),
+    /// 5s per probe.
+    pub fn new() -> Self {
+        Self {
+            checks: Vec::new(),
+            attempts: 20,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            attempt_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Probe `DATABASE_URL`, `REDIS_URL`, `NATS_URL` (unless `NATS_MODE=embedded`) and
+    /// `LANAI_JWKS_URL`, each only if set.
+    pub fn from_env() -> Self {
+        let mut gate = Self::new();
+        if let Ok(url) = std::env::var(DATABASE_URL_ENV) {
+            gate = gate.postgres(&url);
+        }
+        if let Ok(url) = std::env::var(REDIS_URL_ENV) {
+            gate = gate.redis(&url);
+        }
+        if let Ok(url) = std::env::var(NATS_URL_ENV) {
+            if !crate::messaging::NatsConfig::default().embedded {
+                gate = gate.nats(&url);
+            }
+        }
+        if let Ok(url) = std::env::var(JWKS_URL_ENV) {
+            gate = gate.jwks(&url);
+        }
+        gate
+    }
+
+    pub fn postgres(self, url: &str) -> Self {
+        self.check(Arc::new(UrlProbe { name: "postgres", url: url.to_string() }))
+    }
+
+    pub fn redis(self, url: &str) -> Self {
+        self.check(Arc::new(UrlProbe { name: "redis", url: url.to_string() }))
+    }
+
+    pub fn nats(self, url: &str) -> Self {
+        self.check(Arc::new(UrlProbe { name: "nats", url: url.to_string() }))
+    }
+
+    /// The JWKS must answer with a JSON document holding `keys`.
+    pub fn jwks(self, url: &str) -> Self {
+        self.check(Arc::new(UrlProbe { name: "jwks", url: url.to_string() }))
+    }
+
+    /// Any other dependency, e.g. a `HealthCheck` of a downstream service.
+    pub fn check(mut self, check: Arc<dyn HealthCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn backoff(mut self, initial_delay: Duration, max_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = timeout;
+        self
+    }
+
+    /// Probe until every dependency is up. Dependencies that are up are not probed again.
+    pub async fn wait(&self) -> Result<(), StartupError> {
+        if self.checks.is_empty() {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let mut pending: Vec<Arc<dyn HealthCheck>> = self.checks.clone();
+        let mut delay = self.initial_delay;
+
+        for attempt in 1..=self.attempts {
+            let results = futures_util::future::join_all(pending.iter().map(|check| {
+                let check = check.clone();
+                let timeout = self.attempt_timeout;
+                async move {
+                    match tokio::time::timeout(timeout, check.check()).await {
+                        Ok(result) => result,
+                        Err(_) => CheckResult::down(format!("no answer within {:?}", timeout)),
+                    }
+                }
+            }))
+            .await;
+
+            let mut failures = Vec::new();
+            let mut still_pending = Vec::new();
+            for (check, result) in pending.into_iter().zip(results) {
+                if result.status == HealthStatus::Down {
+                    failures.push((check.name().to_string(), result.details.unwrap_or_default()));
+                    still_pending.push(check);
+                } else {
+                    info!("✅ Dependency '{}' is reachable", check.name());
+                }
+            }
+
+            if failures.is_empty() {
+                info!("✅ All {} dependencies reachable after {:?}", self.checks.len(), started.elapsed());
+                return Ok(());
+            }
+            if attempt == self.attempts {
+                error!("❌ Giving up on dependencies: {}", format_failures(&failures));
+                return Err(StartupError::DependenciesUnavailable { attempts: self.attempts, failures });
+            }
+
+            warn!(
+                "⏳ Waiting for {} (attempt {}/{}, retrying in {:?})",
+                format_failures(&failures),
+                attempt,
+                self.attempts,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(self.max_delay);
+            pending = still_pending;
+        }
+        Ok(())
+    }
+}
+
+/// Wait for the dependencies configured in the environment (see `DependencyGate::from_env`).
+pub async fn wait_for_dependencies() -> Result<(), StartupError> {
+    DependencyGate::from_env().wait().await
+}
+
+/// Connects to a dependency by URL, before any pool or client exists.
+struct UrlProbe {
+    name: &'static str,
+    url: String,
+}
+
+#[async_trait]
+impl HealthCheck for UrlProbe {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn check(&self) -> CheckResult {
+        let outcome = match self.name {
+            "postgres" => probe_postgres(&self.url).await,
+            "redis" => probe_redis(&self.url).await,
+            "nats" => probe_nats(&self.url).await,
+            _ => probe_jwks(&self.url).await,
+        };
+        match outcome {
+            Ok(()) => CheckResult::up(),
+            Err(reason) => CheckResult::down(reason),
+        }
+    }
+}
+
+async fn probe_postgres(url: &str) -> Result<(), String> {
+    use sqlx::Connection;
+    let mut conn = sqlx::PgConnection::connect(url).await.map_err(|e| e.to_string())?;
+    sqlx::query("SELECT 1").execute(&mut conn).await.map_err(|e| e.to_string())?;
+    conn.close().await.map_err(|e| e.to_string())
+}
+
+async fn probe_redis(url: &str) -> Result<(), String> {
+    let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+    let mut conn = client.get_async_connection().await.map_err(|e| e.to_string())?;
+    redis::cmd("PING").query_async::<_, String>(&mut conn).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+async fn probe_nats(url: &str) -> Result<(), String> {
+    if NatsClient::embedded().is_some() {
+        return Ok(());
+    }
+    let client = async_nats::connect(url).await.map_err(|e| e.to_string())?;
+    client.flush().await.map_err(|e| e.to_string())
+}
+
+async fn probe_jwks(url: &str) -> Result<(), String> {
+    let response = reqwest::get(url).await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let jwks: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    match jwks.get("keys").and_then(|keys| keys.as_array()) {
+        Some(keys) if !keys.is_empty() => Ok(()),
+        _ => Err("no keys in JWKS".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Down for the first `failures` probes.
+    struct Flaky {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl HealthCheck for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn check(&self) -> CheckResult {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                CheckResult::down("connection refused")
+            } else {
+                CheckResult::up()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gate_retries_then_reports() {
+        let flaky = Arc::new(Flaky { failures: 2, calls: AtomicU32::new(0) });
+        let gate = DependencyGate::new().check(flaky.clone()).backoff(Duration::from_millis(1), Duration::from_millis(2));
+        gate.clone().attempts(3).wait().await.unwrap();
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+
+        let down = Arc::new(Flaky { failures: u32::MAX, calls: AtomicU32::new(0) });
+        let err = gate.check(down).attempts(2).wait().await.unwrap_err();
+        let StartupError::DependenciesUnavailable { attempts, failures } = &err;
+        assert_eq!(*attempts, 2);
+        assert_eq!(failures, &vec![("flaky".to_string(), "connection refused".to_string())]);
+        assert!(err.to_string().contains("flaky (connection refused)"));
+    }
+}
@@ -0,0 +1,405 @@
+//! Outbound HTTP egress policy
+//!
+//! Webhook receivers and tenant-supplied callbacks are URLs an attacker can choose, so a
+//! client that calls them must not reach the cloud metadata service, localhost or the
+//! cluster network (SSRF). An `EgressPolicy` is enforced where it cannot be bypassed:
+//!
+//! - before the request, on the URL (scheme, allowed hosts, literal IP addresses);
+//! - at connect time, on every address DNS returns, so a public name resolving to
+//!   `169.254.169.254` (or rebinding to it later) is refused;
+//! - on every redirect, which is checked like the original URL and counted.
+//!
+//! ```ignore
+//! let policy = EgressPolicy::from_env();
+//! policy.check_url(&body.url)?; // when the tenant registers the URL
+//! let client = policy.apply(reqwest::Client::builder().timeout(Duration::from_secs(10))).build()?;
+//! ```
+//!
+//! By default any public host is allowed and private, loopback, link-local, CGNAT,
+//! multicast and unspecified addresses are denied, including when embedded in IPv6
+//! (IPv4-mapped, IPv4-compatible, NAT64 and 6to4). `allow_cidr` re-opens specific ranges.
+//! With a host allowlist, URLs naming an IP address are refused unless an allowed CIDR
+//! contains it.
+//!
+//! With the `resilient-http` feature, `resilient::ResilientClient` adds per-host circuit
+//! breakers, retries and trace propagation on top of a client built here.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[cfg(feature = "resilient-http")]
+pub mod resilient;
+
+/// Comma-separated hosts (`api.partner.com`, `*.partner.com`); unset allows any public host
+pub const EGRESS_ALLOWED_HOSTS_ENV: &str = "LANAI_EGRESS_ALLOWED_HOSTS";
+/// Comma-separated CIDRs allowed even though they are private (e.g. `10.20.0.0/16`)
+pub const EGRESS_ALLOWED_CIDRS_ENV: &str = "LANAI_EGRESS_ALLOWED_CIDRS";
+/// `true` disables the private address checks (local development only)
+pub const EGRESS_ALLOW_PRIVATE_ENV: &str = "LANAI_EGRESS_ALLOW_PRIVATE";
+
+/// Names of metadata services that resolve to link-local addresses inside clouds.
+const METADATA_HOSTS: &[&str] = &["metadata.google.internal", "metadata", "instance-data"];
+
+/// Egress error types
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum EgressError {
+    #[error("Invalid URL '{0}'")]
+    InvalidUrl(String),
+
+    #[error("Scheme '{0}' is not allowed")]
+    SchemeNotAllowed(String),
+
+    #[error("Host '{0}' is not allowed")]
+    HostNotAllowed(String),
+
+    #[error("Address {1} of '{0}' is not allowed")]
+    AddressDenied(String, IpAddr),
+
+    #[error("Invalid CIDR '{0}'")]
+    InvalidCidr(String),
+
+    #[error("More than {0} redirects")]
+    TooManyRedirects(usize),
+}
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = EgressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EgressError::InvalidCidr(s.to_string());
+        let (network, prefix) = match s.trim().split_once('/') {
+            Some((network, prefix)) => {
+                (network.parse::<IpAddr>().map_err(|_| invalid())?, prefix.parse::<u8>().map_err(|_| invalid())?)
+            }
+            None => {
+                let network = s.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+                (network, if network.is_ipv4() { 32 } else { 128 })
+            }
+        };
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { network, prefix })
+    }
+}
+
+/// Addresses that are never public: private, loopback, link-local (cloud metadata), CGNAT,
+/// documentation, multicast, broadcast and unspecified.
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => is_internal_v6(ip),
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        || ip.is_broadcast()
+        || ip.is_unspecified()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // CGNAT 100.64.0.0/10
+        || a >= 240 // reserved
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    if embedded_ipv4(ip).is_some_and(is_internal_v4) {
+        return true;
+    }
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local fc00::/7
+        || (first & 0xffc0) == 0xfe80 // link-local fe80::/10
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8) // documentation
+}
+
+/// The IPv4 address carried by an IPv4-mapped (`::ffff:a.b.c.d`), IPv4-compatible
+/// (`::a.b.c.d`), NAT64 (`64:ff9b::/96`) or 6to4 (`2002::/16`) address.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., a, b, c, d] = ip.octets();
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff | 0, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(a, b, c, d)),
+        [0x2002, high, low, ..] => {
+            let [a, b] = high.to_be_bytes();
+            let [c, d] = low.to_be_bytes();
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rules {
+    allowed_hosts: Vec<String>,
+    allowed_cidrs: Vec<Cidr>,
+    allow_private: bool,
+    https_only: bool,
+    max_redirects: usize,
+}
+
+/// Where outbound calls may go.
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    rules: Arc<Rules>,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EgressPolicy {
+    /// Any public host over http(s), at most 3 redirects.
+    pub fn new() -> Self {
+        Self {
+            rules: Arc::new(Rules {
+                allowed_hosts: Vec::new(),
+                allowed_cidrs: Vec::new(),
+                allow_private: false,
+                https_only: false,
+                max_redirects: 3,
+            }),
+        }
+    }
+
+    /// `new()` adjusted by `LANAI_EGRESS_ALLOWED_HOSTS`, `LANAI_EGRESS_ALLOWED_CIDRS` and
+    /// `LANAI_EGRESS_ALLOW_PRIVATE`. Invalid CIDRs are logged and skipped.
+    pub fn from_env() -> Self {
+        let mut policy = Self::new();
+        if let Ok(hosts) = std::env::var(EGRESS_ALLOWED_HOSTS_ENV) {
+            for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+                policy = policy.allow_host(host);
+            }
+        }
+        if let Ok(cidrs) = std::env::var(EGRESS_ALLOWED_CIDRS_ENV) {
+            for cidr in cidrs.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                match cidr.parse() {
+                    Ok(cidr) => policy = policy.allow_cidr(cidr),
+                    Err(e) => log::warn!("⚠️ Ignoring {}: {}", EGRESS_ALLOWED_CIDRS_ENV, e),
+                }
+            }
+        }
+        if std::env::var(EGRESS_ALLOW_PRIVATE_ENV).is_ok_and(|v| v.eq_ignore_ascii_case("true")) {
+            log::warn!("⚠️ Egress to private addresses is allowed ({}=true)", EGRESS_ALLOW_PRIVATE_ENV);
+            policy = policy.allow_private();
+        }
+        policy
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Rules)) -> Self {
+        f(Arc::make_mut(&mut self.rules));
+        self
+    }
+
+    /// Only allow listed hosts (`api.partner.com`, or `*.partner.com` for its subdomains).
+    pub fn allow_host(self, host: &str) -> Self {
+        let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+        self.update(|rules| rules.allowed_hosts.push(host))
+    }
+
+    /// Allow addresses in `cidr` even if they are internal.
+    pub fn allow_cidr(self, cidr: Cidr) -> Self {
+        self.update(|rules| rules.allowed_cidrs.push(cidr))
+    }
+
+    /// Skip the internal address checks. For local development only.
+    pub fn allow_private(self) -> Self {
+        self.update(|rules| rules.allow_private = true)
+    }
+
+    pub fn https_only(self) -> Self {
+        self.update(|rules| rules.https_only = true)
+    }
+
+    pub fn max_redirects(self, max_redirects: usize) -> Self {
+        self.update(|rules| rules.max_redirects = max_redirects)
+    }
+
+    /// Check `ip`, reached for `host`.
+    pub fn check_ip(&self, host: &str, ip: IpAddr) -> Result<(), EgressError> {
+        let rules = &self.rules;
+        if rules.allow_private || rules.allowed_cidrs.iter().any(|cidr| cidr.contains(ip)) || !is_internal(ip) {
+            Ok(())
+        } else {
+            Err(EgressError::AddressDenied(host.to_string(), ip))
+        }
+    }
+
+    /// Check a host name against the allowlist (addresses are checked when resolved).
+    pub fn check_host(&self, host: &str) -> Result<(), EgressError> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if !self.rules.allow_private && (METADATA_HOSTS.contains(&host.as_str()) || host == "localhost" || host.ends_with(".localhost")) {
+            return Err(EgressError::HostNotAllowed(host));
+        }
+        let allowed = self.rules.allowed_hosts.is_empty()
+            || self.rules.allowed_hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{}", domain)),
+                None => *allowed == host,
+            });
+        if allowed {
+            Ok(())
+        } else {
+            Err(EgressError::HostNotAllowed(host))
+        }
+    }
+
+    /// Check everything that can be known before connecting: scheme, host, literal IPs.
+    pub fn check_url(&self, url: &str) -> Result<reqwest::Url, EgressError> {
+        let url = reqwest::Url::parse(url).map_err(|_| EgressError::InvalidUrl(url.to_string()))?;
+        self.check_parsed(&url)?;
+        Ok(url)
+    }
+
+    fn check_parsed(&self, url: &reqwest::Url) -> Result<(), EgressError> {
+        match url.scheme() {
+            "https" => {}
+            "http" if !self.rules.https_only => {}
+            scheme => return Err(EgressError::SchemeNotAllowed(scheme.to_string())),
+        }
+        let host = url.host_str().ok_or_else(|| EgressError::InvalidUrl(url.to_string()))?;
+        // IPv6 literals keep their brackets in `host_str`
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => {
+                // An address cannot match a host allowlist, only an allowed CIDR.
+                let listed = self.rules.allowed_cidrs.iter().any(|cidr| cidr.contains(ip));
+                if !self.rules.allowed_hosts.is_empty() && !listed {
+                    return Err(EgressError::HostNotAllowed(host.to_string()));
+                }
+                self.check_ip(host, ip)
+            }
+            Err(_) => self.check_host(host),
+        }
+    }
+
+    /// Enforce the policy on a client: resolved addresses and redirects. Requests should
+    /// still go through `check_url` first, since literal IPs are not resolved.
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let policy = self.clone();
+        let max_redirects = self.rules.max_redirects;
+        builder
+            .dns_resolver(Arc::new(EgressResolver { policy: self.clone() }))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > max_redirects {
+                    return attempt.error(EgressError::TooManyRedirects(max_redirects));
+                }
+                match policy.check_parsed(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e),
+                }
+            }))
+    }
+
+    /// A client with this policy applied and a `timeout`. Fails rather than fall back to a
+    /// client without the policy.
+    pub fn client(&self, timeout: std::time::Duration) -> reqwest::Result<reqwest::Client> {
+        self.apply(reqwest::Client::builder().timeout(timeout)).build()
+    }
+}
+
+/// Resolves with the system resolver and drops addresses the policy denies.
+struct EgressResolver {
+    policy: EgressPolicy,
+}
+
+impl Resolve for EgressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            policy.check_host(&host)?;
+            let lookup = host.clone();
+            let addrs: Vec<SocketAddr> =
+                tokio::task::spawn_blocking(move || (lookup.as_str(), 0).to_socket_addrs().map(|a| a.collect()))
+                    .await??;
+
+            // Refuse the host if any address is denied, so round-robin DNS cannot sneak one in.
+            for addr in &addrs {
+                policy.check_ip(&host, addr.ip())?;
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_addresses_are_denied() {
+        let policy = EgressPolicy::new();
+        for url in [
+            "http://169.254.169.254/latest/meta-data/",
+            "http://127.0.0.1:8080/",
+            "http://10.0.0.5/",
+            "http://[::1]/",
+            "http://[::ffff:192.168.1.1]/",
+            "http://[::a9fe:a9fe]/",
+            "http://[64:ff9b::a9fe:a9fe]/",
+            "http://[2002:0a00:0001::1]/",
+            "http://100.64.0.1/",
+            "http://metadata.google.internal/",
+            "gopher://example.com/",
+        ] {
+            assert!(policy.check_url(url).is_err(), "{} should be denied", url);
+        }
+        assert!(policy.check_url("https://hooks.example.com/lanai").is_ok());
+        assert!(policy.check_url("http://93.184.216.34/").is_ok());
+
+        let policy = policy.allow_cidr("10.20.0.0/16".parse().unwrap());
+        assert!(policy.check_url("http://10.20.3.4/").is_ok());
+        assert!(policy.check_url("http://10.21.3.4/").is_err());
+    }
+
+    #[test]
+    fn test_host_allowlist() {
+        let policy = EgressPolicy::new().allow_host("*.partner.com").allow_host("api.example.com").https_only();
+        assert!(policy.check_url("https://hooks.partner.com/x").is_ok());
+        assert!(policy.check_url("https://api.example.com/x").is_ok());
+        assert_eq!(
+            policy.check_url("https://evil.com/x").unwrap_err(),
+            EgressError::HostNotAllowed("evil.com".to_string())
+        );
+        assert!(policy.check_url("http://api.example.com/x").is_err());
+        assert!(policy.check_url("https://93.184.216.34/x").is_err());
+        let policy = policy.allow_cidr("93.184.216.0/24".parse().unwrap());
+        assert!(policy.check_url("https://93.184.216.34/x").is_ok());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    }
+}
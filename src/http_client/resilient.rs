@@ -0,0 +1,196 @@
+//! Outbound HTTP with circuit breaking, retries and trace propagation
+//!
+//! A `ResilientClient` sends requests through one circuit breaker per host (from a
+//! `CircuitBreakerRegistry`, so they show up in its metrics and the admin API), retries
+//! transient failures of idempotent requests and injects the current trace context into
+//! every attempt:
+//!
+//! ```ignore
+//! let partners = ResilientClient::new(EgressPolicy::from_env().client(Duration::from_secs(10))?)
+//!     .with_breakers(breakers.clone());
+//!
+//! let response = partners.send(partners.request(Method::GET, "https://api.partner.com/rates")).await?;
+//! ```
+//!
+//! Connection errors, timeouts, 5xx and 429 answers are failures: they count against the
+//! host's circuit and are retried. Other answers, 4xx included, are returned as they are.
+//! Requests with a streaming body cannot be repeated and are sent once.
+
+use log::warn;
+use opentelemetry::global;
+use reqwest::{Method, Request, RequestBuilder, Response, StatusCode, Url};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::error::LanaiError;
+use crate::resilience::{CircuitBreakerOutcome, CircuitBreakerRegistry, RetryPolicy};
+
+/// Outbound HTTP error types
+#[derive(Debug, Error)]
+pub enum HttpCallError {
+    #[error("Circuit for '{0}' is open")]
+    CircuitOpen(String),
+
+    #[error("Request to '{host}' failed: {source}")]
+    Request { host: String, source: reqwest::Error },
+
+    /// 5xx or 429 from the host.
+    #[error("'{host}' answered {status}")]
+    Status { host: String, status: StatusCode },
+
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+}
+
+impl HttpCallError {
+    /// Worth another attempt: connection errors, timeouts, 5xx and 429.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request { source, .. } => source.is_connect() || source.is_timeout(),
+            Self::Status { .. } => true,
+            Self::CircuitOpen(_) | Self::Invalid(_) => false,
+        }
+    }
+}
+
+impl From<HttpCallError> for LanaiError {
+    fn from(e: HttpCallError) -> Self {
+        match e {
+            HttpCallError::Request { source, .. } if source.is_timeout() => {
+                Self::Timeout("Dependency did not respond in time".to_string())
+            }
+            HttpCallError::Invalid(message) => Self::Internal(message),
+            _ => Self::Unavailable("Dependency unavailable, please retry later".to_string()),
+        }
+    }
+}
+
+/// `reqwest::Client` with a circuit breaker per host and retries; clones share both.
+#[derive(Clone)]
+pub struct ResilientClient {
+    client: reqwest::Client,
+    breakers: CircuitBreakerRegistry,
+    retry: RetryPolicy<HttpCallError>,
+}
+
+impl ResilientClient {
+    /// Breakers with the registry defaults; up to 3 attempts of transient failures.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            breakers: CircuitBreakerRegistry::new(),
+            retry: RetryPolicy::exponential(3, Duration::from_millis(200)).retry_if(HttpCallError::is_transient),
+        }
+    }
+
+    /// Take the per-host breakers from `breakers`, named after the host (`host[:port]`).
+    pub fn with_breakers(mut self, breakers: CircuitBreakerRegistry) -> Self {
+        self.breakers = breakers;
+        self
+    }
+
+    /// Retry idempotent requests with `retry` instead; `RetryPolicy::none()` disables retries.
+    pub fn with_retry(mut self, retry: RetryPolicy<HttpCallError>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn breakers(&self) -> &CircuitBreakerRegistry {
+        &self.breakers
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Start a request on the underlying client; send it with `send`.
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub async fn send(&self, builder: RequestBuilder) -> Result<Response, HttpCallError> {
+        let request = builder.build().map_err(|e| HttpCallError::Invalid(e.to_string()))?;
+        self.execute(request).await
+    }
+
+    /// Send `request` through its host's breaker, retrying if it is idempotent and repeatable.
+    pub async fn execute(&self, request: Request) -> Result<Response, HttpCallError> {
+        let host = host_key(request.url())
+            .ok_or_else(|| HttpCallError::Invalid(format!("No host in '{}'", request.url())))?;
+        let breaker = self.breakers.get_or_create(&host);
+        let span = tracing::info_span!("outbound_http", http.method = %request.method(), http.host = %host);
+
+        let repeatable = request.method().is_idempotent() && request.try_clone().is_some();
+        let result = async {
+            if repeatable {
+                self.retry
+                    .run_with_breaker(&breaker, || {
+                        let attempt = request.try_clone().expect("request body was checked to be repeatable");
+                        self.attempt(&host, attempt)
+                    })
+                    .await
+            } else {
+                breaker.call(|| self.attempt(&host, request)).await
+            }
+        }
+        .instrument(span)
+        .await;
+
+        result.map_err(|outcome| match outcome {
+            CircuitBreakerOutcome::CircuitOpen => HttpCallError::CircuitOpen(host.clone()),
+            CircuitBreakerOutcome::OperationError(e) => e,
+        })
+    }
+
+    async fn attempt(&self, host: &str, mut request: Request) -> Result<Response, HttpCallError> {
+        let cx = tracing::Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut opentelemetry_http::HeaderInjector(request.headers_mut()))
+        });
+
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(|source| HttpCallError::Request { host: host.to_string(), source })?;
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            warn!("⚠️ '{}' answered {}", host, status);
+            return Err(HttpCallError::Status { host: host.to_string(), status });
+        }
+        Ok(response)
+    }
+}
+
+/// `host` or `host:port` for explicit ports, naming the host's breaker.
+fn host_key(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_key() {
+        assert_eq!(host_key(&Url::parse("https://api.partner.com/rates").unwrap()).unwrap(), "api.partner.com");
+        assert_eq!(host_key(&Url::parse("http://10.0.0.5:8443/v1").unwrap()).unwrap(), "10.0.0.5:8443");
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_rejects_requests() {
+        let client = ResilientClient::new(reqwest::Client::new());
+        let breaker = client.breakers().get_or_create("api.partner.com");
+        breaker.force_open(Duration::from_secs(60)).await;
+
+        let result = client.send(client.request(Method::GET, "https://api.partner.com/rates")).await;
+
+        assert!(matches!(result, Err(HttpCallError::CircuitOpen(host)) if host == "api.partner.com"));
+    }
+}
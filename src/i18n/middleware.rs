@@ -0,0 +1,177 @@
+//! Per-request locale selection
+//!
+//! The locale is, in order: the best `Accept-Language` match, the tenant's configured
+//! language, the catalog default. It is stored in the request extensions (read it with
+//! the `Locale` extractor), echoed as `Content-Language`, and `LanaiError` responses are
+//! re-rendered in it.
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderValue},
+    Error, HttpMessage,
+};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{AcceptLanguage, Catalog, Locale};
+use crate::error::LanaiError;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Resolves the language a tenant operates in.
+#[async_trait]
+pub trait TenantLocaleProvider: Send + Sync {
+    async fn locale_for(&self, org_id: Uuid) -> Option<Locale>;
+}
+
+/// Fixed tenant languages.
+#[derive(Debug, Clone, Default)]
+pub struct StaticTenantLocales {
+    locales: HashMap<Uuid, Locale>,
+}
+
+impl StaticTenantLocales {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn assign(mut self, org_id: Uuid, locale: Locale) -> Self {
+        self.locales.insert(org_id, locale);
+        self
+    }
+}
+
+#[async_trait]
+impl TenantLocaleProvider for StaticTenantLocales {
+    async fn locale_for(&self, org_id: Uuid) -> Option<Locale> {
+        self.locales.get(&org_id).copied()
+    }
+}
+
+/// Selects the request locale and localizes error responses. Tenant languages need the
+/// `TenantContext`, so register this middleware before `TenantMiddleware` (actix runs the
+/// last registered middleware first).
+#[derive(Clone, Default)]
+pub struct LocalizationMiddleware {
+    tenants: Option<Arc<dyn TenantLocaleProvider>>,
+}
+
+impl LocalizationMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tenant_locales(mut self, tenants: Arc<dyn TenantLocaleProvider>) -> Self {
+        self.tenants = Some(tenants);
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LocalizationMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LocalizationMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocalizationMiddlewareService { service: Rc::new(service), tenants: self.tenants.clone() }))
+    }
+}
+
+pub struct LocalizationMiddlewareService<S> {
+    service: Rc<S>,
+    tenants: Option<Arc<dyn TenantLocaleProvider>>,
+}
+
+impl<S, B> Service<ServiceRequest> for LocalizationMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let tenants = self.tenants.clone();
+
+        Box::pin(async move {
+            let requested = req
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| AcceptLanguage::parse(v).negotiate(&Locale::ALL));
+            let tenant = req.extensions().get::<TenantContext>().copied();
+            let locale = match (requested, tenant, tenants) {
+                (Some(locale), _, _) => Some(locale),
+                (None, Some(tenant), Some(tenants)) => tenants.locale_for(tenant.org_id).await,
+                _ => None,
+            }
+            .unwrap_or_else(|| Catalog::global().default_locale());
+            req.extensions_mut().insert(locale);
+
+            let res = service.call(req).await?;
+            let localized = res
+                .response()
+                .error()
+                .and_then(|e| e.as_error::<LanaiError>())
+                .map(|e| e.localized_response(locale));
+
+            let mut res = match localized {
+                Some(response) => res.into_response(response),
+                None => res.map_into_boxed_body(),
+            };
+            res.headers_mut().insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale.as_str()));
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    async fn missing_order() -> Result<&'static str, LanaiError> {
+        Err(LanaiError::NotFound("Resource not found".to_string()))
+    }
+
+    #[actix_web::test]
+    async fn test_error_responses_follow_accept_language() {
+        let app = test::init_service(
+            App::new().wrap(LocalizationMiddleware::new()).route("/orders/1", web::get().to(missing_order)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/orders/1")
+            .insert_header((header::ACCEPT_LANGUAGE, "pt-BR,pt;q=0.9"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.headers().get(header::CONTENT_LANGUAGE).unwrap(), "pt");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["title"], "Não encontrado");
+        assert_eq!(body["detail"], "Recurso não encontrado");
+        assert_eq!(body["code"], "not_found");
+
+        let req = test::TestRequest::get().uri("/orders/1").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["title"], "Not found");
+    }
+}
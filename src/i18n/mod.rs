@@ -0,0 +1,367 @@
+//! Localization of user-facing messages (ES / PT / EN)
+//!
+//! A `Catalog` maps message IDs to translations per `Locale`. It ships with translations
+//! of every infrastructure error (problem titles, fixed details, validation messages);
+//! services add their own messages from JSON or Fluent (`.ftl`) files embedded in the binary:
+//!
+//! ```ignore
+//! Catalog::builtin()
+//!     .with_ftl(Locale::Es, include_str!("../locales/es.ftl"))?
+//!     .with_ftl(Locale::Pt, include_str!("../locales/pt.ftl"))?
+//!     .with_ftl(Locale::En, include_str!("../locales/en.ftl"))?
+//!     .install();
+//! ```
+//!
+//! Messages can take arguments written as Fluent placeables (`{ $limit }`). The ID of a
+//! message may also be its English text, so `LanaiError::NotFound("Order not found".into())`
+//! is translated by an `"Order not found"` entry.
+//!
+//! `LocalizationMiddleware` picks the request's locale (see `middleware`) and re-renders
+//! `LanaiError` responses in it; handlers get the locale with the `Locale` extractor.
+
+use actix_web::{dev::Payload, http::header, FromRequest, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+use crate::error::{LanaiError, ProblemDetails};
+
+pub mod middleware;
+
+pub use middleware::{LocalizationMiddleware, StaticTenantLocales, TenantLocaleProvider};
+
+/// Localization error types
+#[derive(Debug, Error)]
+pub enum I18nError {
+    #[error("Invalid JSON catalog: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid FTL catalog at line {line}: {reason}")]
+    Ftl { line: usize, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    Es,
+    Pt,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::Es, Locale::Pt, Locale::En];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Es => "es",
+            Self::Pt => "pt",
+            Self::En => "en",
+        }
+    }
+
+    /// Primary language of a BCP 47 tag: `es-PE`, `pt_BR` and `EN` all parse.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "es" => Some(Self::Es),
+            "pt" => Some(Self::Pt),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The locale chosen by `LocalizationMiddleware`; without the middleware, the best match
+/// for `Accept-Language`, else the catalog's default.
+impl FromRequest for Locale {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let locale = req.extensions().get::<Locale>().copied().unwrap_or_else(|| {
+            AcceptLanguage::from_request_headers(req)
+                .negotiate(&Locale::ALL)
+                .unwrap_or_else(|| Catalog::global().default_locale())
+        });
+        ready(Ok(locale))
+    }
+}
+
+/// Parsed `Accept-Language` header, highest preference first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AcceptLanguage(pub Vec<(String, f32)>);
+
+impl AcceptLanguage {
+    /// `es-PE,es;q=0.9,en;q=0.8`. Malformed entries are skipped; `q=0` entries dropped.
+    pub fn parse(header: &str) -> Self {
+        let mut ranges: Vec<(String, f32)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                if tag.is_empty() {
+                    return None;
+                }
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q=").map(|q| q.trim().parse::<f32>().ok()))
+                    .unwrap_or(Some(1.0))?;
+                (quality > 0.0).then(|| (tag.to_string(), quality))
+            })
+            .collect();
+        // Stable sort keeps header order between equal weights
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        Self(ranges)
+    }
+
+    fn from_request_headers(req: &HttpRequest) -> Self {
+        req.headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(Self::parse)
+            .unwrap_or_default()
+    }
+
+    /// Most preferred of `supported`, if the client accepts any of them.
+    pub fn negotiate(&self, supported: &[Locale]) -> Option<Locale> {
+        self.0.iter().filter_map(|(tag, _)| Locale::parse(tag)).find(|locale| supported.contains(locale))
+    }
+}
+
+impl FromRequest for AcceptLanguage {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Self::from_request_headers(req)))
+    }
+}
+
+/// Message translations per locale. Lookups fall back to the default locale.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    messages: HashMap<Locale, HashMap<String, String>>,
+    default_locale: Locale,
+}
+
+static GLOBAL: OnceLock<Catalog> = OnceLock::new();
+
+impl Catalog {
+    /// Empty catalog.
+    pub fn new(default_locale: Locale) -> Self {
+        Self { messages: HashMap::new(), default_locale }
+    }
+
+    /// Translations of the infrastructure's own messages, defaulting to English.
+    pub fn builtin() -> Self {
+        let parse = |json: &str| serde_json::from_str::<HashMap<String, String>>(json).expect("valid builtin catalog");
+        let mut catalog = Self::new(Locale::En);
+        catalog.messages.insert(Locale::En, parse(include_str!("locales/en.json")));
+        catalog.messages.insert(Locale::Es, parse(include_str!("locales/es.json")));
+        catalog.messages.insert(Locale::Pt, parse(include_str!("locales/pt.json")));
+        catalog
+    }
+
+    pub fn default_locale(&self) -> Locale {
+        self.default_locale
+    }
+
+    pub fn with_default_locale(mut self, locale: Locale) -> Self {
+        self.default_locale = locale;
+        self
+    }
+
+    pub fn with_message(mut self, locale: Locale, id: &str, text: &str) -> Self {
+        self.messages.entry(locale).or_default().insert(id.to_string(), text.to_string());
+        self
+    }
+
+    /// Merge a flat `{"id": "text"}` JSON object.
+    pub fn with_json(mut self, locale: Locale, json: &str) -> Result<Self, I18nError> {
+        let messages: HashMap<String, String> = serde_json::from_str(json)?;
+        self.messages.entry(locale).or_default().extend(messages);
+        Ok(self)
+    }
+
+    /// Merge the messages of a Fluent file. Only simple messages are supported: `id = text`
+    /// with indented continuation lines and `{ $arg }` placeables; comments, terms and
+    /// attributes are ignored.
+    pub fn with_ftl(mut self, locale: Locale, ftl: &str) -> Result<Self, I18nError> {
+        let messages = self.messages.entry(locale).or_default();
+        let mut current: Option<(String, String)> = None;
+
+        for (index, line) in ftl.lines().enumerate() {
+            if line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+                match current.as_mut() {
+                    Some((_, text)) => {
+                        if !text.is_empty() {
+                            text.push('\n');
+                        }
+                        text.push_str(line.trim());
+                    }
+                    None => {
+                        return Err(I18nError::Ftl { line: index + 1, reason: "continuation without a message".to_string() })
+                    }
+                }
+                continue;
+            }
+            if let Some((id, text)) = current.take() {
+                messages.insert(id, text);
+            }
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('-') || trimmed.starts_with('.') {
+                continue;
+            }
+            let (id, text) = trimmed
+                .split_once('=')
+                .ok_or_else(|| I18nError::Ftl { line: index + 1, reason: "expected `id = text`".to_string() })?;
+            current = Some((id.trim().to_string(), text.trim().to_string()));
+        }
+        if let Some((id, text)) = current {
+            messages.insert(id, text);
+        }
+        Ok(self)
+    }
+
+    /// Use this catalog process-wide. Returns false if one was already installed.
+    pub fn install(self) -> bool {
+        GLOBAL.set(self).is_ok()
+    }
+
+    /// The installed catalog, or `builtin()`.
+    pub fn global() -> &'static Catalog {
+        GLOBAL.get_or_init(Catalog::builtin)
+    }
+
+    /// Translation of `id` in `locale` (else the default locale) with `args` substituted.
+    pub fn translate(&self, locale: Locale, id: &str, args: &[(&str, String)]) -> Option<String> {
+        let lookup = |locale: Locale| self.messages.get(&locale).and_then(|messages| messages.get(id));
+        lookup(locale).or_else(|| lookup(self.default_locale)).map(|text| format_message(text, args))
+    }
+
+    /// `translate`, or `id` itself when there is no translation.
+    pub fn message(&self, locale: Locale, id: &str, args: &[(&str, String)]) -> String {
+        self.translate(locale, id, args).unwrap_or_else(|| format_message(id, args))
+    }
+
+    /// Translate the title, detail and field messages of `error`'s problem body.
+    pub fn localize_problem(&self, error: &LanaiError, mut problem: ProblemDetails, locale: Locale) -> ProblemDetails {
+        let code = error.code();
+        if let Some(title) = self.translate(locale, &format!("error-{}", code), &[]) {
+            problem.title = title;
+        }
+
+        let args = match error {
+            LanaiError::QuotaExceeded { metric, limit, .. } => vec![("metric", metric.clone()), ("limit", limit.to_string())],
+            _ => Vec::new(),
+        };
+        let detail = match error {
+            LanaiError::Validation(_) | LanaiError::RateLimited { .. } | LanaiError::QuotaExceeded { .. } | LanaiError::Internal(_) => {
+                self.translate(locale, &format!("error-{}-detail", code), &args)
+            }
+            _ => self.translate(locale, &problem.detail, &args),
+        };
+        if let Some(detail) = detail {
+            problem.detail = detail;
+        }
+
+        for violation in &mut problem.errors {
+            let params: Vec<(&str, String)> = violation
+                .params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string())))
+                .collect();
+            let translated = violation
+                .message
+                .as_deref()
+                .and_then(|message| self.translate(locale, message, &params))
+                .or_else(|| self.translate(locale, &format!("validation-{}", violation.code), &params));
+            if let Some(message) = translated {
+                violation.message = Some(message);
+            }
+        }
+        problem
+    }
+}
+
+/// Substitute `{ $name }` (or `{name}`) placeables; unknown ones are left as written.
+fn format_message(text: &str, args: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let placeable = &rest[start..start + len + 1];
+        let name = placeable[1..placeable.len() - 1].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(placeable),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FieldViolation;
+    use actix_web::ResponseError;
+
+    #[test]
+    fn test_accept_language_negotiation() {
+        let accept = AcceptLanguage::parse("fr-FR, pt-BR;q=0.9, es;q=0.95, en;q=0");
+        assert_eq!(accept.0[0].0, "fr-FR");
+        assert_eq!(accept.negotiate(&Locale::ALL), Some(Locale::Es));
+        assert_eq!(accept.negotiate(&[Locale::Pt, Locale::En]), Some(Locale::Pt));
+        assert_eq!(AcceptLanguage::parse("de").negotiate(&Locale::ALL), None);
+        assert_eq!(Locale::parse("pt_BR"), Some(Locale::Pt));
+    }
+
+    #[test]
+    fn test_ftl_catalog() {
+        let catalog = Catalog::new(Locale::En)
+            .with_ftl(
+                Locale::Es,
+                "# Orders\norder-not-found = Pedido no encontrado\norder-limit =\n    Máximo { $max } pedidos\n    por día\n",
+            )
+            .unwrap();
+        assert_eq!(catalog.message(Locale::Es, "order-not-found", &[]), "Pedido no encontrado");
+        assert_eq!(catalog.message(Locale::Es, "order-limit", &[("max", "5".to_string())]), "Máximo 5 pedidos\npor día");
+        assert_eq!(catalog.message(Locale::Pt, "missing {x}", &[]), "missing {x}");
+        assert!(Catalog::new(Locale::En).with_ftl(Locale::Es, "no equals sign").is_err());
+    }
+
+    #[test]
+    fn test_localized_problem() {
+        let catalog = Catalog::builtin();
+
+        let error = LanaiError::Unavailable("Database unavailable".to_string());
+        let problem = catalog.localize_problem(&error, error.problem(), Locale::Es);
+        assert_eq!(problem.title, "Servicio no disponible");
+        assert_eq!(problem.detail, "Base de datos no disponible");
+        assert_eq!(problem.code, "service_unavailable");
+
+        let error = LanaiError::QuotaExceeded { metric: "api_calls".to_string(), limit: 1000, retry_after_secs: None };
+        let problem = catalog.localize_problem(&error, error.problem(), Locale::Pt);
+        assert_eq!(problem.detail, "Cota de api_calls excedida (limite 1000)");
+        assert_eq!(problem.status, error.status_code().as_u16());
+
+        let error = LanaiError::Validation(vec![FieldViolation::new("name", "length")
+            .param("min", serde_json::json!(2))
+            .param("max", serde_json::json!(50))]);
+        let problem = catalog.localize_problem(&error, error.problem(), Locale::Es);
+        assert_eq!(problem.errors[0].message.as_deref(), Some("Debe tener entre 2 y 50 caracteres"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Idempotency
+//!
+//! One store abstraction for "do this at most once per key": the first caller `claim`s a key
+//! and runs the operation, then `complete`s it with the result, which later callers get back
+//! instead of running it again. A claim that is never completed (the holder crashed) expires
+//! after its lock TTL so the key can be claimed again.
+//!
+//! `Idempotency::run` wraps that protocol around an async operation; it is what
+//! `middleware::idempotency_key::IdempotencyKeyMiddleware` and `saga::IdempotentStep` build
+//! on, and idempotent consumers (`messaging::Inbox`) use the store directly.
+//!
+//! ```ignore
+//! let idempotency = Idempotency::new(Arc::new(RedisIdempotencyStore::new(shared_connection().await?)))
+//!     .with_retention(Duration::from_secs(24 * 3600));
+//!
+//! let receipt: Receipt = idempotency
+//!     .run(&format!("charge:{}", order_id), || payments.charge(order_id, amount))
+//!     .await?;
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+pub mod postgres;
+pub mod store;
+
+pub use postgres::PostgresIdempotencyStore;
+pub use store::RedisIdempotencyStore;
+
+/// Idempotency store error types
+#[derive(Debug, Error)]
+pub enum IdempotencyError {
+    #[error("Operation for idempotency key '{0}' is already in progress")]
+    InProgress(String),
+
+    #[error("Failed to serialize idempotent result: {0}")]
+    Serialization(String),
+
+    #[error("Idempotency store error: {0}")]
+    Store(String),
+}
+
+/// Error from `Idempotency::run`: either the protocol or the operation itself failed.
+#[derive(Debug, Error)]
+pub enum RunError<E> {
+    #[error(transparent)]
+    Idempotency(#[from] IdempotencyError),
+
+    #[error("{0}")]
+    Operation(E),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdempotencyStatus {
+    /// Claimed; the operation is running.
+    Pending,
+    /// Finished; `response` holds the result.
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub key: String,
+    pub status: IdempotencyStatus,
+    pub response: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl IdempotencyRecord {
+    pub(crate) fn pending(key: &str) -> Self {
+        Self { key: key.to_string(), status: IdempotencyStatus::Pending, response: None, created_at: Utc::now() }
+    }
+}
+
+/// Result of `IdempotencyStore::claim`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Claim {
+    /// The caller owns the key and must `complete` or `remove` it.
+    Acquired,
+    /// Another caller holds the key and has not finished yet.
+    InProgress,
+    /// The operation already ran; this is its stored result.
+    Completed(serde_json::Value),
+}
+
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claim `key` for `lock_ttl`, unless it is held or already completed.
+    async fn claim(&self, key: &str, lock_ttl: Duration) -> Result<Claim, IdempotencyError>;
+
+    /// Store the result of a claimed key and keep it for `ttl`.
+    async fn complete(&self, key: &str, response: &serde_json::Value, ttl: Duration) -> Result<(), IdempotencyError>;
+
+    async fn fetch(&self, key: &str) -> Result<Option<IdempotencyRecord>, IdempotencyError>;
+
+    /// Forget `key`, e.g. after the operation failed or was rolled back.
+    async fn remove(&self, key: &str) -> Result<(), IdempotencyError>;
+}
+
+/// Runs operations at most once per key against an `IdempotencyStore`.
+#[derive(Clone)]
+pub struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    lock_ttl: Duration,
+    retention: Duration,
+}
+
+impl Idempotency {
+    /// Claims are held for up to 60s; results are kept for 24h.
+    pub fn new(store: Arc<dyn IdempotencyStore>) -> Self {
+        Self { store, lock_ttl: Duration::from_secs(60), retention: Duration::from_secs(24 * 3600) }
+    }
+
+    /// How long a claim blocks other callers if its holder never completes it.
+    pub fn with_lock_ttl(mut self, lock_ttl: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self
+    }
+
+    /// How long completed results are remembered.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn IdempotencyStore> {
+        &self.store
+    }
+
+    /// Run `operation` unless `key` already completed, in which case its stored result is
+    /// returned. A failed operation releases the key so it can be retried.
+    pub async fn run<T, E, F, Fut>(&self, key: &str, operation: F) -> Result<T, RunError<E>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        match self.store.claim(key, self.lock_ttl).await? {
+            Claim::Completed(response) => {
+                return serde_json::from_value(response)
+                    .map_err(|e| IdempotencyError::Serialization(e.to_string()).into());
+            }
+            Claim::InProgress => return Err(IdempotencyError::InProgress(key.to_string()).into()),
+            Claim::Acquired => {}
+        }
+
+        match operation().await {
+            Ok(result) => {
+                let response =
+                    serde_json::to_value(&result).map_err(|e| IdempotencyError::Serialization(e.to_string()))?;
+                self.store.complete(key, &response, self.retention).await?;
+                Ok(result)
+            }
+            Err(e) => {
+                self.store.remove(key).await?;
+                Err(RunError::Operation(e))
+            }
+        }
+    }
+}
+
+/// In-memory idempotency store (for tests and single-instance development).
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    records: Mutex<HashMap<String, (IdempotencyRecord, Instant)>>,
+}
+
+impl InMemoryIdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn claim(&self, key: &str, lock_ttl: Duration) -> Result<Claim, IdempotencyError> {
+        let mut records = self.records.lock().await;
+        match records.get(key) {
+            Some((record, expires_at)) if *expires_at > Instant::now() => Ok(match record.status {
+                IdempotencyStatus::Pending => Claim::InProgress,
+                IdempotencyStatus::Completed => Claim::Completed(record.response.clone().unwrap_or_default()),
+            }),
+            _ => {
+                records.insert(key.to_string(), (IdempotencyRecord::pending(key), Instant::now() + lock_ttl));
+                Ok(Claim::Acquired)
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, response: &serde_json::Value, ttl: Duration) -> Result<(), IdempotencyError> {
+        let mut records = self.records.lock().await;
+        let created_at = records.get(key).map_or_else(Utc::now, |(record, _)| record.created_at);
+        let record = IdempotencyRecord {
+            key: key.to_string(),
+            status: IdempotencyStatus::Completed,
+            response: Some(response.clone()),
+            created_at,
+        };
+        records.insert(key.to_string(), (record, Instant::now() + ttl));
+        Ok(())
+    }
+
+    async fn fetch(&self, key: &str) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        Ok(self
+            .records
+            .lock()
+            .await
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(record, _)| record.clone()))
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), IdempotencyError> {
+        self.records.lock().await.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_run_executes_once_per_key() {
+        let idempotency = Idempotency::new(Arc::new(InMemoryIdempotencyStore::new()));
+        let calls = AtomicU32::new(0);
+
+        for _ in 0..3 {
+            let result: Result<u32, RunError<String>> = idempotency
+                .run("charge:1", || async { Ok(calls.fetch_add(1, Ordering::SeqCst) + 100) })
+                .await;
+            assert_eq!(result.unwrap(), 100);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failure_releases_key() {
+        let idempotency = Idempotency::new(Arc::new(InMemoryIdempotencyStore::new()));
+
+        let failed: Result<u32, _> = idempotency.run("k", || async { Err("boom") }).await;
+        assert!(matches!(failed, Err(RunError::Operation("boom"))));
+
+        let retried: Result<u32, RunError<&str>> = idempotency.run("k", || async { Ok(7) }).await;
+        assert_eq!(retried.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_claim_states() {
+        let store = InMemoryIdempotencyStore::new();
+        assert_eq!(store.claim("k", Duration::from_secs(60)).await.unwrap(), Claim::Acquired);
+        assert_eq!(store.claim("k", Duration::from_secs(60)).await.unwrap(), Claim::InProgress);
+
+        store.complete("k", &serde_json::json!({"ok": true}), Duration::from_secs(60)).await.unwrap();
+        assert_eq!(
+            store.claim("k", Duration::from_secs(60)).await.unwrap(),
+            Claim::Completed(serde_json::json!({"ok": true}))
+        );
+        assert_eq!(store.fetch("k").await.unwrap().unwrap().status, IdempotencyStatus::Completed);
+
+        // An abandoned claim can be taken over once its lock expires.
+        assert_eq!(store.claim("stale", Duration::ZERO).await.unwrap(), Claim::Acquired);
+        assert_eq!(store.claim("stale", Duration::from_secs(60)).await.unwrap(), Claim::Acquired);
+    }
+}
@@ -0,0 +1,129 @@
+//! PostgreSQL-backed idempotency store
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+use super::{Claim, IdempotencyError, IdempotencyRecord, IdempotencyStatus, IdempotencyStore};
+
+/// Table used by `PostgresIdempotencyStore`; include it in a service migration or call
+/// `ensure_schema` at startup.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_idempotency (
+    key TEXT PRIMARY KEY,
+    status TEXT NOT NULL,
+    response JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+)";
+
+/// Rows expire through `expires_at`: a pending row's lock TTL, or a completed row's retention.
+/// Expired rows are ignored and overwritten by the next claim; `purge_expired` deletes them.
+pub struct PostgresIdempotencyStore {
+    pool: PgPool,
+}
+
+impl PostgresIdempotencyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), IdempotencyError> {
+        sqlx::query(SCHEMA).execute(&self.pool).await.map_err(store_error)?;
+        Ok(())
+    }
+
+    /// Delete expired rows; returns how many were removed.
+    pub async fn purge_expired(&self) -> Result<u64, IdempotencyError> {
+        let result = sqlx::query("DELETE FROM lanai_idempotency WHERE expires_at < now()")
+            .execute(&self.pool)
+            .await
+            .map_err(store_error)?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn store_error(e: sqlx::Error) -> IdempotencyError {
+    IdempotencyError::Store(e.to_string())
+}
+
+fn status_of(status: &str) -> IdempotencyStatus {
+    match status {
+        "completed" => IdempotencyStatus::Completed,
+        _ => IdempotencyStatus::Pending,
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for PostgresIdempotencyStore {
+    async fn claim(&self, key: &str, lock_ttl: Duration) -> Result<Claim, IdempotencyError> {
+        let acquired = sqlx::query(
+            "INSERT INTO lanai_idempotency (key, status, response, created_at, expires_at)
+             VALUES ($1, 'pending', NULL, now(), now() + make_interval(secs => $2))
+             ON CONFLICT (key) DO UPDATE
+                SET status = 'pending', response = NULL, created_at = now(), expires_at = EXCLUDED.expires_at
+                WHERE lanai_idempotency.expires_at < now()
+             RETURNING key",
+        )
+        .bind(key)
+        .bind(lock_ttl.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(store_error)?;
+        if acquired.is_some() {
+            return Ok(Claim::Acquired);
+        }
+
+        Ok(match self.fetch(key).await? {
+            Some(IdempotencyRecord { status: IdempotencyStatus::Completed, response, .. }) => {
+                Claim::Completed(response.unwrap_or_default())
+            }
+            _ => Claim::InProgress,
+        })
+    }
+
+    async fn complete(&self, key: &str, response: &serde_json::Value, ttl: Duration) -> Result<(), IdempotencyError> {
+        sqlx::query(
+            "INSERT INTO lanai_idempotency (key, status, response, created_at, expires_at)
+             VALUES ($1, 'completed', $2, now(), now() + make_interval(secs => $3))
+             ON CONFLICT (key) DO UPDATE
+                SET status = 'completed', response = EXCLUDED.response, expires_at = EXCLUDED.expires_at",
+        )
+        .bind(key)
+        .bind(response)
+        .bind(ttl.as_secs_f64())
+        .execute(&self.pool)
+        .await
+        .map_err(store_error)?;
+        Ok(())
+    }
+
+    async fn fetch(&self, key: &str) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        let row = sqlx::query(
+            "SELECT key, status, response, created_at FROM lanai_idempotency WHERE key = $1 AND expires_at > now()",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(store_error)?;
+
+        row.map(|row| {
+            Ok(IdempotencyRecord {
+                key: row.try_get("key").map_err(store_error)?,
+                status: status_of(row.try_get::<&str, _>("status").map_err(store_error)?),
+                response: row.try_get("response").map_err(store_error)?,
+                created_at: row.try_get::<DateTime<Utc>, _>("created_at").map_err(store_error)?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), IdempotencyError> {
+        sqlx::query("DELETE FROM lanai_idempotency WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+}
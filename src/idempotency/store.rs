@@ -0,0 +1,98 @@
+//! Redis-backed idempotency store
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+use super::{Claim, IdempotencyError, IdempotencyRecord, IdempotencyStatus, IdempotencyStore};
+
+/// Records are JSON under `lanai:idempotency:{key}`. A claim is a pending record written with
+/// `SET NX PX`, so an abandoned claim disappears with its lock TTL.
+pub struct RedisIdempotencyStore {
+    conn: ConnectionManager,
+}
+
+impl RedisIdempotencyStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    fn redis_key(key: &str) -> String {
+        format!("lanai:idempotency:{}", key)
+    }
+}
+
+fn store_error(e: redis::RedisError) -> IdempotencyError {
+    IdempotencyError::Store(e.to_string())
+}
+
+fn encode(record: &IdempotencyRecord) -> Result<String, IdempotencyError> {
+    serde_json::to_string(record).map_err(|e| IdempotencyError::Serialization(e.to_string()))
+}
+
+fn ttl_millis(ttl: Duration) -> u64 {
+    (ttl.as_millis() as u64).max(1)
+}
+
+#[async_trait]
+impl IdempotencyStore for RedisIdempotencyStore {
+    async fn claim(&self, key: &str, lock_ttl: Duration) -> Result<Claim, IdempotencyError> {
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(Self::redis_key(key))
+            .arg(encode(&IdempotencyRecord::pending(key))?)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_millis(lock_ttl))
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        if acquired.is_some() {
+            return Ok(Claim::Acquired);
+        }
+
+        Ok(match self.fetch(key).await? {
+            Some(IdempotencyRecord { status: IdempotencyStatus::Completed, response, .. }) => {
+                Claim::Completed(response.unwrap_or_default())
+            }
+            // Still pending, or it expired between the two commands: let the caller retry.
+            _ => Claim::InProgress,
+        })
+    }
+
+    async fn complete(&self, key: &str, response: &serde_json::Value, ttl: Duration) -> Result<(), IdempotencyError> {
+        let created_at = self.fetch(key).await?.map(|record| record.created_at).unwrap_or_else(chrono::Utc::now);
+        let record = IdempotencyRecord {
+            key: key.to_string(),
+            status: IdempotencyStatus::Completed,
+            response: Some(response.clone()),
+            created_at,
+        };
+        redis::cmd("SET")
+            .arg(Self::redis_key(key))
+            .arg(encode(&record)?)
+            .arg("PX")
+            .arg(ttl_millis(ttl))
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)
+    }
+
+    async fn fetch(&self, key: &str) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        let payload: Option<String> = redis::cmd("GET")
+            .arg(Self::redis_key(key))
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        payload
+            .map(|p| serde_json::from_str(&p).map_err(|e| IdempotencyError::Serialization(e.to_string())))
+            .transpose()
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), IdempotencyError> {
+        redis::cmd("DEL")
+            .arg(Self::redis_key(key))
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)
+    }
+}
@@ -0,0 +1,158 @@
+//! Background Jobs backed by Redis Streams
+//!
+//! Jobs are typed, JSON-serialized payloads enqueued on a named queue and executed by a
+//! `WorkerPool` on any replica:
+//! - At-least-once delivery via a consumer group; jobs of crashed workers are reclaimed
+//! - Priority lanes (`High`, `Normal`, `Low`): workers always drain higher lanes first
+//! - Bounded concurrency per worker pool, and optionally per job type
+//! - Scheduled jobs (`enqueue_in`, `enqueue_at`) and cancellation
+//! - Exponential-backoff retries through a delayed set, then a dead-letter stream
+//! - Inspection and retry of dead letters (`JobQueue::stats`, `AdminApi::jobs`)
+//! - One tracing span per job execution, linked to the span that enqueued the job
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct SendInvoiceEmail { invoice_id: Uuid }
+//! impl Job for SendInvoiceEmail { const NAME: &'static str = "send_invoice_email"; }
+//!
+//! impl Job for MonthlyReport {
+//!     const NAME: &'static str = "monthly_report";
+//!     const PRIORITY: Priority = Priority::Low;
+//! }
+//!
+//! let queue = JobQueue::new(shared_connection().await?, "billing");
+//! queue.enqueue(&SendInvoiceEmail { invoice_id }).await?;
+//! queue.enqueue_at(&MonthlyReport { org_id }, first_of_month).await?;
+//!
+//! WorkerPool::new(queue)
+//!     .concurrency(8)
+//!     .register(move |job: SendInvoiceEmail| mailer.send_invoice(job.invoice_id))
+//!     .register(move |job: MonthlyReport| reports.generate(job.org_id))
+//!     .job_concurrency::<MonthlyReport>(2) // never more than 2 reports per worker
+//!     .start()
+//!     .await?;
+//! ```
+//!
+//! Handlers must be idempotent: a job can run more than once if a worker dies mid-way.
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod queue;
+pub mod worker;
+
+pub use queue::{DeadLetter, JobQueue, QueueStats, ScheduledJob};
+pub use worker::{WorkerPool, WorkersHandle};
+
+/// A job payload. `NAME` routes it to its handler and must stay stable across deploys.
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    const NAME: &'static str;
+
+    /// Lane the job is enqueued on unless the caller picks another.
+    const PRIORITY: Priority = Priority::Normal;
+}
+
+/// Execution lane of a job. Workers take a job from a lower lane only when every higher
+/// lane is empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    /// Lanes in the order workers drain them.
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+}
+
+/// Stored form of an enqueued job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEnvelope {
+    pub id: Uuid,
+    pub name: String,
+    pub payload: serde_json::Value,
+    /// 1-based number of the attempt this envelope is queued for.
+    pub attempt: u32,
+    pub enqueued_at: DateTime<Utc>,
+    /// Lane the job runs on, retries included.
+    #[serde(default)]
+    pub priority: Priority,
+    /// Error of the previous attempt (retries and dead letters).
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// W3C `traceparent` of the span that enqueued the job; each execution span links to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+}
+
+impl JobEnvelope {
+    pub fn new<J: Job>(job: &J) -> Result<Self, JobError> {
+        Ok(Self {
+            id: Uuid::new_v4(),
+            name: J::NAME.to_string(),
+            payload: serde_json::to_value(job).map_err(|e| JobError::Serialization(e.to_string()))?,
+            attempt: 1,
+            enqueued_at: Utc::now(),
+            priority: J::PRIORITY,
+            last_error: None,
+            traceparent: crate::observability::current_traceparent(),
+        })
+    }
+}
+
+/// Job subsystem error types
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("Failed to serialize job: {0}")]
+    Serialization(String),
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Ping {
+        n: u32,
+    }
+
+    impl Job for Ping {
+        const NAME: &'static str = "ping";
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope = JobEnvelope::new(&Ping { n: 7 }).unwrap();
+        assert_eq!(envelope.name, "ping");
+        assert_eq!(envelope.attempt, 1);
+
+        let raw = serde_json::to_string(&envelope).unwrap();
+        let decoded: JobEnvelope = serde_json::from_str(&raw).unwrap();
+        assert_eq!(decoded.id, envelope.id);
+        assert_eq!(serde_json::from_value::<Ping>(decoded.payload).unwrap().n, 7);
+        assert!(decoded.last_error.is_none());
+        assert_eq!(decoded.priority, Priority::Normal);
+
+        // Envelopes enqueued before priorities existed run on the normal lane
+        let legacy = serde_json::json!({
+            "id": envelope.id, "name": "ping", "payload": {"n": 1}, "attempt": 1, "enqueued_at": envelope.enqueued_at
+        });
+        assert_eq!(serde_json::from_value::<JobEnvelope>(legacy).unwrap().priority, Priority::Normal);
+    }
+}
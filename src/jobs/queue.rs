@@ -0,0 +1,299 @@
+//! Producer side of a job queue
+//!
+//! Redis layout for queue `{name}`:
+//! - `lanai:jobs:{name}` — stream of ready `Normal` jobs, consumed by the `workers` group
+//! - `lanai:jobs:{name}:high`, `lanai:jobs:{name}:low` — the `High` and `Low` lanes
+//! - `lanai:jobs:{name}:delayed` — sorted set of jobs waiting for their retry/run time
+//! - `lanai:jobs:{name}:dead` — stream of jobs that exhausted their retries
+//! - `lanai:jobs:{name}:cancelled:{id}` — marks a ready job to be skipped (expires after a week)
+
+use chrono::{DateTime, Utc};
+use log::info;
+use redis::aio::ConnectionManager;
+use redis::streams::StreamRangeReply;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{Job, JobEnvelope, JobError, Priority};
+
+/// Consumer group shared by every worker of a queue.
+pub(crate) const GROUP: &str = "workers";
+
+/// How long a cancellation waits for its job to reach a worker.
+const CANCEL_TTL_SECS: u64 = 7 * 24 * 3600;
+
+/// Job counts of a queue.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueStats {
+    pub queue: String,
+    /// Jobs in each lane's stream, including ones being processed.
+    pub ready: BTreeMap<Priority, u64>,
+    /// Jobs waiting for their run time or retry.
+    pub scheduled: u64,
+    pub dead: u64,
+}
+
+/// A job in the delayed set.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledJob {
+    pub run_at: DateTime<Utc>,
+    pub job: JobEnvelope,
+}
+
+/// A job in the dead-letter stream; `entry_id` identifies it for `retry_dead`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub entry_id: String,
+    pub job: JobEnvelope,
+}
+
+#[derive(Clone)]
+pub struct JobQueue {
+    pub(crate) conn: ConnectionManager,
+    pub(crate) name: String,
+}
+
+impl JobQueue {
+    pub fn new(conn: ConnectionManager, name: &str) -> Self {
+        Self { conn, name: name.to_string() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stream of the `Normal` lane.
+    pub(crate) fn stream_key(&self) -> String {
+        format!("lanai:jobs:{}", self.name)
+    }
+
+    pub(crate) fn lane_key(&self, priority: Priority) -> String {
+        match priority {
+            Priority::Normal => self.stream_key(),
+            other => format!("lanai:jobs:{}:{}", self.name, other.as_str()),
+        }
+    }
+
+    pub(crate) fn delayed_key(&self) -> String {
+        format!("lanai:jobs:{}:delayed", self.name)
+    }
+
+    pub(crate) fn dead_key(&self) -> String {
+        format!("lanai:jobs:{}:dead", self.name)
+    }
+
+    pub(crate) fn cancelled_key(&self, id: Uuid) -> String {
+        format!("lanai:jobs:{}:cancelled:{}", self.name, id)
+    }
+
+    /// Enqueue `job` for immediate execution on its type's lane. Returns the job ID.
+    pub async fn enqueue<J: Job>(&self, job: &J) -> Result<Uuid, JobError> {
+        self.enqueue_with_priority(job, J::PRIORITY).await
+    }
+
+    /// Enqueue `job` for immediate execution on the `priority` lane.
+    pub async fn enqueue_with_priority<J: Job>(&self, job: &J, priority: Priority) -> Result<Uuid, JobError> {
+        let envelope = JobEnvelope { priority, ..JobEnvelope::new(job)? };
+        self.push_ready(&envelope).await?;
+        info!("📥 Enqueued {} job '{}' ({}) on '{}'", priority.as_str(), envelope.name, envelope.id, self.name);
+        Ok(envelope.id)
+    }
+
+    /// Enqueue `job` to run once `delay` has elapsed.
+    pub async fn enqueue_in<J: Job>(&self, job: &J, delay: Duration) -> Result<Uuid, JobError> {
+        let envelope = JobEnvelope::new(job)?;
+        self.push_delayed(&envelope, delay).await?;
+        info!("📥 Enqueued job '{}' ({}) on '{}' to run in {:?}", envelope.name, envelope.id, self.name, delay);
+        Ok(envelope.id)
+    }
+
+    /// Enqueue `job` to run at `run_at` (immediately if it is in the past).
+    pub async fn enqueue_at<J: Job>(&self, job: &J, run_at: DateTime<Utc>) -> Result<Uuid, JobError> {
+        let envelope = JobEnvelope::new(job)?;
+        self.push_at(&envelope, run_at.timestamp_millis()).await?;
+        info!("📥 Enqueued job '{}' ({}) on '{}' to run at {}", envelope.name, envelope.id, self.name, run_at);
+        Ok(envelope.id)
+    }
+
+    /// Number of jobs waiting in the ready streams of every lane (including ones being processed).
+    pub async fn len(&self) -> Result<u64, JobError> {
+        let mut total = 0;
+        for priority in Priority::ALL {
+            total += self.lane_len(priority).await?;
+        }
+        Ok(total)
+    }
+
+    pub async fn lane_len(&self, priority: Priority) -> Result<u64, JobError> {
+        Ok(redis::cmd("XLEN").arg(self.lane_key(priority)).query_async(&mut self.conn.clone()).await?)
+    }
+
+    pub async fn stats(&self) -> Result<QueueStats, JobError> {
+        let mut ready = BTreeMap::new();
+        for priority in Priority::ALL {
+            ready.insert(priority, self.lane_len(priority).await?);
+        }
+        let mut conn = self.conn.clone();
+        Ok(QueueStats {
+            queue: self.name.clone(),
+            ready,
+            scheduled: redis::cmd("ZCARD").arg(self.delayed_key()).query_async(&mut conn).await?,
+            dead: redis::cmd("XLEN").arg(self.dead_key()).query_async(&mut conn).await?,
+        })
+    }
+
+    /// The next `limit` jobs of the delayed set, soonest first.
+    pub async fn scheduled(&self, limit: usize) -> Result<Vec<ScheduledJob>, JobError> {
+        let entries: Vec<(String, i64)> = redis::cmd("ZRANGE")
+            .arg(self.delayed_key())
+            .arg(0)
+            .arg(limit.max(1) as i64 - 1)
+            .arg("WITHSCORES")
+            .query_async(&mut self.conn.clone())
+            .await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(raw, run_at)| {
+                let job = serde_json::from_str(&raw).ok()?;
+                Some(ScheduledJob { run_at: DateTime::from_timestamp_millis(run_at)?, job })
+            })
+            .collect())
+    }
+
+    /// The `limit` most recent dead letters, newest first.
+    pub async fn dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, JobError> {
+        let reply: StreamRangeReply = redis::cmd("XREVRANGE")
+            .arg(self.dead_key())
+            .arg("+")
+            .arg("-")
+            .arg("COUNT")
+            .arg(limit.max(1))
+            .query_async(&mut self.conn.clone())
+            .await?;
+        Ok(reply
+            .ids
+            .into_iter()
+            .filter_map(|entry| {
+                let job = serde_json::from_str(&entry.get::<String>("job")?).ok()?;
+                Some(DeadLetter { entry_id: entry.id, job })
+            })
+            .collect())
+    }
+
+    /// Re-enqueue a dead letter with a fresh retry budget. Returns the job ID, or `None`
+    /// if there is no such entry.
+    pub async fn retry_dead(&self, entry_id: &str) -> Result<Option<Uuid>, JobError> {
+        let mut conn = self.conn.clone();
+        let reply: StreamRangeReply =
+            redis::cmd("XRANGE").arg(self.dead_key()).arg(entry_id).arg(entry_id).query_async(&mut conn).await?;
+        let Some(raw) = reply.ids.into_iter().next().and_then(|entry| entry.get::<String>("job")) else {
+            return Ok(None);
+        };
+        let mut envelope: JobEnvelope =
+            serde_json::from_str(&raw).map_err(|e| JobError::Serialization(e.to_string()))?;
+        envelope.attempt = 1;
+        self.push_ready(&envelope).await?;
+        redis::cmd("XDEL").arg(self.dead_key()).arg(entry_id).query_async::<_, i64>(&mut conn).await?;
+        info!("🔁 Retrying dead job '{}' ({}) on '{}'", envelope.name, envelope.id, self.name);
+        Ok(Some(envelope.id))
+    }
+
+    /// Cancel job `id`. A scheduled job is removed from the delayed set (returns true); a
+    /// ready job is skipped when a worker picks it up. Running jobs are not interrupted.
+    pub async fn cancel(&self, id: Uuid) -> Result<bool, JobError> {
+        let mut conn = self.conn.clone();
+        let pattern = format!("*\"id\":\"{}\"*", id);
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, entries): (u64, Vec<String>) = redis::cmd("ZSCAN")
+                .arg(self.delayed_key())
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            // ZSCAN returns members and scores interleaved
+            for raw in entries.iter().step_by(2) {
+                if serde_json::from_str::<JobEnvelope>(raw).is_ok_and(|envelope| envelope.id == id) {
+                    let removed: i64 =
+                        redis::cmd("ZREM").arg(self.delayed_key()).arg(raw).query_async(&mut conn).await?;
+                    if removed > 0 {
+                        info!("🚫 Cancelled scheduled job {} on '{}'", id, self.name);
+                        return Ok(true);
+                    }
+                }
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        redis::cmd("SET")
+            .arg(self.cancelled_key(id))
+            .arg(1)
+            .arg("EX")
+            .arg(CANCEL_TTL_SECS)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        info!("🚫 Job {} on '{}' will be skipped", id, self.name);
+        Ok(false)
+    }
+
+    /// Whether job `id` was cancelled; the mark is consumed.
+    pub(crate) async fn take_cancellation(&self, id: Uuid) -> Result<bool, JobError> {
+        let removed: i64 = redis::cmd("DEL").arg(self.cancelled_key(id)).query_async(&mut self.conn.clone()).await?;
+        Ok(removed > 0)
+    }
+
+    /// Whether no job is waiting.
+    pub async fn is_empty(&self) -> Result<bool, JobError> {
+        Ok(self.len().await? == 0)
+    }
+
+    pub(crate) async fn push_ready(&self, envelope: &JobEnvelope) -> Result<(), JobError> {
+        let payload = serde_json::to_string(envelope).map_err(|e| JobError::Serialization(e.to_string()))?;
+        redis::cmd("XADD")
+            .arg(self.lane_key(envelope.priority))
+            .arg("*")
+            .arg("job")
+            .arg(payload)
+            .query_async::<_, String>(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn push_delayed(&self, envelope: &JobEnvelope, delay: Duration) -> Result<(), JobError> {
+        self.push_at(envelope, Utc::now().timestamp_millis() + delay.as_millis() as i64).await
+    }
+
+    async fn push_at(&self, envelope: &JobEnvelope, run_at_ms: i64) -> Result<(), JobError> {
+        let payload = serde_json::to_string(envelope).map_err(|e| JobError::Serialization(e.to_string()))?;
+        redis::cmd("ZADD")
+            .arg(self.delayed_key())
+            .arg(run_at_ms)
+            .arg(payload)
+            .query_async::<_, i64>(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn push_dead(&self, envelope: &JobEnvelope) -> Result<(), JobError> {
+        let payload = serde_json::to_string(envelope).map_err(|e| JobError::Serialization(e.to_string()))?;
+        redis::cmd("XADD")
+            .arg(self.dead_key())
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(10_000)
+            .arg("*")
+            .arg("job")
+            .arg(payload)
+            .query_async::<_, String>(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,574 @@
+//! Consumer side of a job queue
+//!
+//! A `WorkerPool` reads its queue through the shared `workers` consumer group, so any
+//! number of replicas can process the same queue. Lanes are read in priority order, so a
+//! `Low` job only starts when no `High` or `Normal` job is waiting. Each job runs in its
+//! own task, bounded by the pool's concurrency (and its type's, if capped), inside a `job`
+//! tracing span. Failed jobs go back to the delayed set with exponential backoff until the
+//! retry policy gives up, then to the dead-letter stream. Jobs left pending by a crashed
+//! worker are reclaimed after the visibility timeout.
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use log::{debug, error, info, warn};
+use redis::streams::{StreamClaimReply, StreamId, StreamPendingCountReply, StreamReadReply};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::queue::GROUP;
+use super::{Job, JobEnvelope, JobError, JobQueue, Priority};
+use crate::saga::RetryPolicy;
+
+/// Moves due jobs from the delayed set to their lane's stream (high, normal, low) atomically.
+const PROMOTE_SCRIPT: &str = r#"
+local due = redis.call("ZRANGEBYSCORE", KEYS[1], "-inf", ARGV[1], "LIMIT", 0, ARGV[2])
+for _, job in ipairs(due) do
+    local ok, envelope = pcall(cjson.decode, job)
+    local lane = KEYS[3]
+    if ok and envelope["priority"] == "high" then
+        lane = KEYS[2]
+    elseif ok and envelope["priority"] == "low" then
+        lane = KEYS[4]
+    end
+    redis.call("ZREM", KEYS[1], job)
+    redis.call("XADD", lane, "*", "job", job)
+end
+return #due
+"#;
+
+type Handler = Arc<dyn Fn(serde_json::Value) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+pub struct WorkerPool {
+    queue: JobQueue,
+    handlers: HashMap<&'static str, Handler>,
+    concurrency: usize,
+    job_limits: HashMap<&'static str, usize>,
+    retry: RetryPolicy<String>,
+    poll_interval: Duration,
+    visibility_timeout: Duration,
+}
+
+/// Background tasks of a started `WorkerPool`.
+pub struct WorkersHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl WorkersHandle {
+    /// Stop fetching new jobs. Jobs in flight are abandoned and reclaimed by another worker.
+    pub fn abort(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl WorkerPool {
+    /// 4 concurrent jobs, 5 attempts with backoff from 1s up to 5 minutes.
+    pub fn new(queue: JobQueue) -> Self {
+        Self {
+            queue,
+            handlers: HashMap::new(),
+            concurrency: 4,
+            job_limits: HashMap::new(),
+            retry: RetryPolicy::exponential(5, Duration::from_secs(1)).with_max_backoff(Duration::from_secs(300)),
+            poll_interval: Duration::from_millis(500),
+            visibility_timeout: Duration::from_secs(300),
+        }
+    }
+
+    /// Handle jobs of type `J`. Returning `Err` (or panicking) schedules a retry.
+    pub fn register<J, F, Fut, E>(mut self, handler: F) -> Self
+    where
+        J: Job,
+        F: Fn(J) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.handlers.insert(J::NAME, erase(handler));
+        self
+    }
+
+    /// Maximum number of jobs this pool runs at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Maximum number of `J` jobs this pool runs at once. Jobs over the cap are put back
+    /// on the queue for another worker or a later poll, so they do not hold pool slots.
+    pub fn job_concurrency<J: Job>(mut self, concurrency: usize) -> Self {
+        self.job_limits.insert(J::NAME, concurrency.max(1));
+        self
+    }
+
+    /// Retry policy applied to failed jobs; the predicate receives the error message.
+    pub fn retry(mut self, retry: RetryPolicy<String>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// How long to wait before polling again when the queue is empty.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Jobs pending longer than this are assumed abandoned and reclaimed.
+    /// Must exceed the longest expected job duration.
+    pub fn visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout = timeout;
+        self
+    }
+
+    /// Create the consumer group if needed and start processing.
+    pub async fn start(self) -> Result<WorkersHandle, JobError> {
+        for priority in Priority::ALL {
+            let created: Result<String, redis::RedisError> = redis::cmd("XGROUP")
+                .arg("CREATE")
+                .arg(self.queue.lane_key(priority))
+                .arg(GROUP)
+                .arg("0")
+                .arg("MKSTREAM")
+                .query_async(&mut self.queue.conn.clone())
+                .await;
+            match created {
+                Ok(_) => {}
+                Err(e) if e.code() == Some("BUSYGROUP") => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let worker = Arc::new(Worker {
+            consumer: format!(
+                "{}:{}",
+                std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string()),
+                Uuid::new_v4()
+            ),
+            permits: Arc::new(Semaphore::new(self.concurrency)),
+            job_permits: self.job_limits.into_iter().map(|(name, n)| (name, Arc::new(Semaphore::new(n)))).collect(),
+            queue: self.queue,
+            handlers: self.handlers,
+            retry: self.retry,
+            poll_interval: self.poll_interval,
+            visibility_timeout: self.visibility_timeout,
+        });
+
+        info!(
+            "👷 Worker '{}' started on queue '{}' ({} job types, concurrency {})",
+            worker.consumer,
+            worker.queue.name,
+            worker.handlers.len(),
+            worker.permits.available_permits()
+        );
+
+        Ok(WorkersHandle {
+            tasks: vec![
+                tokio::spawn(worker.clone().fetch_loop()),
+                tokio::spawn(worker.clone().promote_loop()),
+                tokio::spawn(worker.reclaim_loop()),
+            ],
+        })
+    }
+}
+
+struct Worker {
+    consumer: String,
+    permits: Arc<Semaphore>,
+    job_permits: HashMap<&'static str, Arc<Semaphore>>,
+    queue: JobQueue,
+    handlers: HashMap<&'static str, Handler>,
+    retry: RetryPolicy<String>,
+    poll_interval: Duration,
+    visibility_timeout: Duration,
+}
+
+impl Worker {
+    async fn fetch_loop(self: Arc<Self>) {
+        let mut conn = self.queue.conn.clone();
+        loop {
+            // Only take a job off the stream once there is capacity to run it.
+            let permit = match self.permits.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+
+            let message = self.next_message(&mut conn).await;
+            match message {
+                Some((stream, message)) => {
+                    let worker = self.clone();
+                    tokio::spawn(async move {
+                        worker.process(&stream, message, 1).await;
+                        drop(permit);
+                    });
+                }
+                None => {
+                    drop(permit);
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// The next new message of the highest non-empty lane, with its stream.
+    async fn next_message(&self, conn: &mut redis::aio::ConnectionManager) -> Option<(String, StreamId)> {
+        for priority in Priority::ALL {
+            let stream = self.queue.lane_key(priority);
+            let reply: Result<Option<StreamReadReply>, _> = redis::cmd("XREADGROUP")
+                .arg("GROUP")
+                .arg(GROUP)
+                .arg(&self.consumer)
+                .arg("COUNT")
+                .arg(1)
+                .arg("STREAMS")
+                .arg(&stream)
+                .arg(">")
+                .query_async(conn)
+                .await;
+            match reply {
+                Ok(reply) => {
+                    if let Some(message) = reply.into_iter().flat_map(|r| r.keys).flat_map(|k| k.ids).next() {
+                        return Some((stream, message));
+                    }
+                }
+                Err(e) => warn!("⚠️ Queue '{}': failed to read {} jobs: {}", self.queue.name, priority.as_str(), e),
+            }
+        }
+        None
+    }
+
+    async fn promote_loop(self: Arc<Self>) {
+        let script = redis::Script::new(PROMOTE_SCRIPT);
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let promoted: Result<i64, _> = script
+                .key(self.queue.delayed_key())
+                .key(self.queue.lane_key(Priority::High))
+                .key(self.queue.lane_key(Priority::Normal))
+                .key(self.queue.lane_key(Priority::Low))
+                .arg(chrono::Utc::now().timestamp_millis())
+                .arg(100)
+                .invoke_async(&mut self.queue.conn.clone())
+                .await;
+            match promoted {
+                Ok(0) => {}
+                Ok(n) => debug!("⏰ Queue '{}': {} delayed jobs are due", self.queue.name, n),
+                Err(e) => warn!("⚠️ Queue '{}': failed to promote delayed jobs: {}", self.queue.name, e),
+            }
+        }
+    }
+
+    async fn reclaim_loop(self: Arc<Self>) {
+        let min_idle = self.visibility_timeout.as_millis() as u64;
+        let mut ticker = tokio::time::interval((self.visibility_timeout / 2).max(Duration::from_secs(1)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            for priority in Priority::ALL {
+                if let Err(e) = self.reclaim(&self.queue.lane_key(priority), min_idle).await {
+                    let lane = priority.as_str();
+                    warn!("⚠️ Queue '{}': failed to reclaim stale {} jobs: {}", self.queue.name, lane, e);
+                }
+            }
+        }
+    }
+
+    async fn reclaim(self: &Arc<Self>, stream: &str, min_idle: u64) -> Result<(), redis::RedisError> {
+        let mut conn = self.queue.conn.clone();
+        let pending: StreamPendingCountReply = redis::cmd("XPENDING")
+            .arg(stream)
+            .arg(GROUP)
+            .arg("IDLE")
+            .arg(min_idle)
+            .arg("-")
+            .arg("+")
+            .arg(100)
+            .query_async(&mut conn)
+            .await?;
+
+        for stale in pending.ids {
+            let permit = match self.permits.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return Ok(()),
+            };
+
+            // XCLAIM re-checks the idle time, so only one worker wins each job.
+            let claimed: StreamClaimReply = redis::cmd("XCLAIM")
+                .arg(stream)
+                .arg(GROUP)
+                .arg(&self.consumer)
+                .arg(min_idle)
+                .arg(&stale.id)
+                .query_async(&mut conn)
+                .await?;
+
+            if let Some(message) = claimed.ids.into_iter().next() {
+                warn!(
+                    "♻️ Queue '{}': reclaimed job {} from '{}' (delivered {} times)",
+                    self.queue.name, message.id, stale.consumer, stale.times_delivered
+                );
+                let worker = self.clone();
+                let deliveries = stale.times_delivered as u32 + 1;
+                let stream = stream.to_string();
+                tokio::spawn(async move {
+                    worker.process(&stream, message, deliveries).await;
+                    drop(permit);
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Run one stream message. `deliveries` counts how often it was handed to a worker.
+    async fn process(&self, stream: &str, message: StreamId, deliveries: u32) {
+        let envelope = message
+            .get::<String>("job")
+            .and_then(|raw| serde_json::from_str::<JobEnvelope>(&raw).ok());
+        let mut envelope = match envelope {
+            Some(envelope) => envelope,
+            None => {
+                error!("❌ Queue '{}': dropping malformed job message {}", self.queue.name, message.id);
+                self.ack(stream, &message.id).await;
+                return;
+            }
+        };
+
+        match self.queue.take_cancellation(envelope.id).await {
+            Ok(true) => {
+                info!("🚫 Skipping cancelled job '{}' ({})", envelope.name, envelope.id);
+                self.ack(stream, &message.id).await;
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!("⚠️ Queue '{}': failed to check cancellation of {}: {}", self.queue.name, envelope.id, e)
+            }
+        }
+
+        // Over its type's cap: hand the job back without spending an attempt
+        let _job_permit = match self.job_permits.get(envelope.name.as_str()) {
+            Some(permits) => match permits.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    debug!("⏳ Job '{}' ({}) is at its concurrency cap, deferring", envelope.name, envelope.id);
+                    match self.queue.push_delayed(&envelope, self.poll_interval).await {
+                        Ok(()) => self.ack(stream, &message.id).await,
+                        Err(e) => error!("❌ Queue '{}': failed to defer job {}: {}", self.queue.name, envelope.id, e),
+                    }
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        let result = if deliveries > self.retry.max_attempts {
+            // Keeps crashing its worker: running it again will not help.
+            Err(format!("abandoned by a worker {} times", deliveries - 1))
+        } else {
+            match self.handlers.get(envelope.name.as_str()) {
+                Some(handler) => self.run(handler, &envelope).await,
+                None => Err(format!("no handler registered for job '{}'", envelope.name)),
+            }
+        };
+
+        let settled = match settle(&self.retry, envelope.attempt, result) {
+            Settlement::Completed => Ok(()),
+            Settlement::Retry(delay, reason) => {
+                warn!(
+                    "🔄 Job '{}' ({}) attempt {} failed: {}. Retrying in {:?}",
+                    envelope.name, envelope.id, envelope.attempt, reason, delay
+                );
+                envelope.attempt += 1;
+                envelope.last_error = Some(reason);
+                self.queue.push_delayed(&envelope, delay).await
+            }
+            Settlement::Dead(reason) => {
+                error!(
+                    "💀 Job '{}' ({}) failed after {} attempts: {}. Moved to dead letters",
+                    envelope.name, envelope.id, envelope.attempt, reason
+                );
+                envelope.last_error = Some(reason);
+                self.queue.push_dead(&envelope).await
+            }
+        };
+
+        // If the job could not be rescheduled, leave it pending so it gets reclaimed.
+        match settled {
+            Ok(()) => self.ack(stream, &message.id).await,
+            Err(e) => error!("❌ Queue '{}': failed to settle job {}: {}", self.queue.name, envelope.id, e),
+        }
+    }
+
+    async fn run(&self, handler: &Handler, envelope: &JobEnvelope) -> Result<(), String> {
+        let span = tracing::info_span!(
+            "job",
+            job.queue = %self.queue.name,
+            job.name = %envelope.name,
+            job.id = %envelope.id,
+            job.attempt = envelope.attempt,
+        );
+        crate::observability::link_span_to_traceparent(&span, envelope.traceparent.as_deref());
+        let started = Instant::now();
+        let result = AssertUnwindSafe(handler(envelope.payload.clone()))
+            .catch_unwind()
+            .instrument(span)
+            .await
+            .unwrap_or_else(|panic| Err(panic_message(panic)));
+
+        if result.is_ok() {
+            info!("✅ Job '{}' ({}) completed in {:?}", envelope.name, envelope.id, started.elapsed());
+        }
+        result
+    }
+
+    async fn ack(&self, stream: &str, id: &str) {
+        let result: Result<(), _> = redis::pipe()
+            .cmd("XACK")
+            .arg(stream)
+            .arg(GROUP)
+            .arg(id)
+            .ignore()
+            .cmd("XDEL")
+            .arg(stream)
+            .arg(id)
+            .ignore()
+            .query_async(&mut self.queue.conn.clone())
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Queue '{}': failed to acknowledge message {}: {}", self.queue.name, id, e);
+        }
+    }
+}
+
+/// What happens to a job after an attempt.
+#[derive(Debug, PartialEq)]
+enum Settlement {
+    Completed,
+    /// Back to the delayed set, due after the delay.
+    Retry(Duration, String),
+    /// To the dead-letter stream.
+    Dead(String),
+}
+
+fn settle(retry: &RetryPolicy<String>, attempt: u32, result: Result<(), String>) -> Settlement {
+    match result {
+        Ok(()) => Settlement::Completed,
+        Err(reason) if retry.should_retry(&reason, attempt) => Settlement::Retry(retry.backoff(attempt), reason),
+        Err(reason) => Settlement::Dead(reason),
+    }
+}
+
+/// Wrap a typed handler into one taking the JSON payload.
+fn erase<J, F, Fut, E>(handler: F) -> Handler
+where
+    J: Job,
+    F: Fn(J) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display,
+{
+    let handler = Arc::new(handler);
+    Arc::new(move |payload| {
+        let handler = handler.clone();
+        async move {
+            let job: J = serde_json::from_value(payload).map_err(|e| format!("invalid payload: {}", e))?;
+            handler(job).await.map_err(|e| e.to_string())
+        }
+        .boxed()
+    })
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    let detail = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("job panicked: {}", detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Resize {
+        width: u32,
+    }
+
+    impl Job for Resize {
+        const NAME: &'static str = "resize";
+    }
+
+    #[tokio::test]
+    async fn test_erased_handler_decodes_payload() {
+        let handler = erase(|job: Resize| async move {
+            if job.width > 0 { Ok(()) } else { Err("zero width") }
+        });
+
+        assert!(handler(serde_json::json!({ "width": 10 })).await.is_ok());
+        assert_eq!(handler(serde_json::json!({ "width": 0 })).await.unwrap_err(), "zero width");
+        assert!(handler(serde_json::json!({ "height": 10 }))
+            .await
+            .unwrap_err()
+            .starts_with("invalid payload"));
+    }
+
+    #[test]
+    fn test_failed_attempts_back_off_then_dead_letter() {
+        let retry = RetryPolicy::exponential(4, Duration::from_secs(1)).with_max_backoff(Duration::from_secs(3));
+        let failed = || Err("smtp timeout".to_string());
+
+        let delays: Vec<Duration> = (1..4)
+            .map(|attempt| match settle(&retry, attempt, failed()) {
+                Settlement::Retry(delay, reason) => {
+                    assert_eq!(reason, "smtp timeout");
+                    delay
+                }
+                other => panic!("attempt {} settled as {:?}", attempt, other),
+            })
+            .collect();
+        // Doubling from 1s, capped at 3s, plus up to 25% jitter
+        for (delay, base) in delays.into_iter().zip([1.0, 2.0, 3.0]) {
+            assert!(delay.as_secs_f64() >= base && delay.as_secs_f64() <= base * 1.25, "{:?}", delay);
+        }
+
+        assert_eq!(settle(&retry, 4, failed()), Settlement::Dead("smtp timeout".to_string()));
+        assert_eq!(settle(&retry, 4, Ok(())), Settlement::Completed);
+    }
+
+    #[test]
+    fn test_non_retryable_failure_goes_straight_to_dead_letter() {
+        let retry = RetryPolicy::exponential(5, Duration::from_secs(1)).retry_if(|reason: &String| !reason.starts_with("invalid payload"));
+
+        assert_eq!(
+            settle(&retry, 1, Err("invalid payload: missing field `width`".to_string())),
+            Settlement::Dead("invalid payload: missing field `width`".to_string())
+        );
+        assert!(matches!(settle(&retry, 1, Err("timeout".to_string())), Settlement::Retry(..)));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_handler_becomes_error() {
+        let handler = erase(|_: Resize| async move {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<(), String>(())
+        });
+
+        let result = AssertUnwindSafe(handler(serde_json::json!({ "width": 1 })))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|panic| Err(panic_message(panic)));
+        assert_eq!(result.unwrap_err(), "job panicked: boom");
+    }
+}
@@ -0,0 +1,187 @@
+//! Leader Election via Redis leases
+//!
+//! Replicas compete for a lease key (`lanai:leader:{name}`) with `SET NX PX`; the holder
+//! renews it well before it expires. If the leader dies, the lease lapses and another
+//! replica takes over within one lease period. A leader that cannot reach Redis steps down
+//! once its lease may have expired, so two replicas never both believe they lead.
+//!
+//! ```ignore
+//! let election = LeaderElection::new(shared_connection().await?, "outbox-relay");
+//! election.on_change(|leader| info!("outbox relay leadership: {}", leader));
+//! let _task = election.start();
+//!
+//! if election.am_i_leader() {
+//!     relay_outbox().await;
+//! }
+//! ```
+
+use log::{info, warn};
+use redis::aio::ConnectionManager;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+const RENEW_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+type Callback = Box<dyn Fn(bool) + Send + Sync>;
+
+struct Inner {
+    name: String,
+    key: String,
+    instance_id: String,
+    conn: ConnectionManager,
+    lease: Duration,
+    state: watch::Sender<bool>,
+    callbacks: Mutex<Vec<Callback>>,
+}
+
+#[derive(Clone)]
+pub struct LeaderElection {
+    inner: Arc<Inner>,
+}
+
+impl LeaderElection {
+    /// Compete for leadership of `name` with a 15 second lease.
+    pub fn new(conn: ConnectionManager, name: &str) -> Self {
+        Self::with_lease(conn, name, Duration::from_secs(15))
+    }
+
+    pub fn with_lease(conn: ConnectionManager, name: &str, lease: Duration) -> Self {
+        let (state, _) = watch::channel(false);
+        Self {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                key: format!("lanai:leader:{}", name),
+                instance_id: format!("{}:{}", hostname(), Uuid::new_v4()),
+                conn,
+                lease,
+                state,
+                callbacks: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn am_i_leader(&self) -> bool {
+        *self.inner.state.borrow()
+    }
+
+    /// Identifier this replica writes into the lease.
+    pub fn instance_id(&self) -> &str {
+        &self.inner.instance_id
+    }
+
+    /// Call `callback` with the new state whenever leadership is gained or lost.
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: Fn(bool) + Send + Sync + 'static,
+    {
+        self.inner.callbacks.lock().unwrap_or_else(|e| e.into_inner()).push(Box::new(callback));
+    }
+
+    /// Watch leadership changes, e.g. to wait until this replica becomes leader.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.inner.state.subscribe()
+    }
+
+    /// Start competing for the lease. Abort the handle (and call `resign`) to stop.
+    pub fn start(&self) -> JoinHandle<()> {
+        let election = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(election.inner.lease / 3);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut last_renewed = Instant::now();
+
+            loop {
+                ticker.tick().await;
+                let leader = election.am_i_leader();
+                let result = if leader { election.renew().await } else { election.try_acquire().await };
+
+                match result {
+                    Ok(true) => {
+                        last_renewed = Instant::now();
+                        election.set_leader(true);
+                    }
+                    Ok(false) => election.set_leader(false),
+                    Err(e) => {
+                        warn!("⚠️ Leader election '{}': Redis error: {}", election.inner.name, e);
+                        // Our lease may have lapsed without us noticing: stop acting as leader.
+                        if leader && last_renewed.elapsed() >= election.inner.lease {
+                            election.set_leader(false);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Give up leadership (e.g. on shutdown) so another replica can take over immediately.
+    pub async fn resign(&self) {
+        if !self.am_i_leader() {
+            return;
+        }
+        let result: Result<i64, _> = redis::Script::new(RELEASE_SCRIPT)
+            .key(&self.inner.key)
+            .arg(&self.inner.instance_id)
+            .invoke_async(&mut self.inner.conn.clone())
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Leader election '{}': failed to release lease: {}", self.inner.name, e);
+        }
+        self.set_leader(false);
+    }
+
+    async fn try_acquire(&self) -> Result<bool, redis::RedisError> {
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(&self.inner.key)
+            .arg(&self.inner.instance_id)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.inner.lease.as_millis() as u64)
+            .query_async(&mut self.inner.conn.clone())
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    async fn renew(&self) -> Result<bool, redis::RedisError> {
+        let renewed: i64 = redis::Script::new(RENEW_SCRIPT)
+            .key(&self.inner.key)
+            .arg(&self.inner.instance_id)
+            .arg(self.inner.lease.as_millis() as u64)
+            .invoke_async(&mut self.inner.conn.clone())
+            .await?;
+        Ok(renewed == 1)
+    }
+
+    fn set_leader(&self, leader: bool) {
+        if self.inner.state.send_replace(leader) == leader {
+            return;
+        }
+        if leader {
+            info!("👑 '{}': this replica ({}) is now the leader", self.inner.name, self.inner.instance_id);
+        } else {
+            warn!("👋 '{}': this replica ({}) is no longer the leader", self.inner.name, self.inner.instance_id);
+        }
+        for callback in self.inner.callbacks.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            callback(leader);
+        }
+    }
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string())
+}
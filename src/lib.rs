@@ -1,3 +1,6 @@
+// Lets derive macros name `::lanai_infrastructure` from inside this crate too
+extern crate self as lanai_infrastructure;
+
 pub mod middleware;
 pub mod messaging;
 pub mod resilience;
@@ -6,5 +9,48 @@ pub mod observability;
 pub mod grpc;
 pub mod cors;
 pub mod rate_limit;
+pub mod cache;
 pub mod common;
+pub mod config;
+pub mod env;
+pub mod db;
+pub mod health;
+pub mod leader;
+pub mod notifications;
+pub mod jobs;
+pub mod scheduler;
+pub mod secrets;
 pub mod server;
+pub mod storage;
+pub mod webhooks;
+pub mod sse;
+pub mod realtime;
+pub mod idempotency;
+pub mod eventstore;
+pub mod projections;
+pub mod audit;
+pub mod error;
+pub mod validation;
+pub mod chaos;
+pub mod metering;
+pub mod crypto;
+pub mod privacy;
+pub mod flags;
+pub mod admin;
+pub mod i18n;
+pub mod search;
+pub mod gateway;
+pub mod http_client;
+pub mod tenant_config;
+pub mod bulk;
+pub mod session;
+pub mod mfa;
+pub mod fx;
+pub mod uom;
+pub mod sync;
+pub mod dto;
+pub mod autoscale;
+pub mod templates;
+pub mod retention;
+#[cfg(feature = "test-utils")]
+pub mod testing;
@@ -0,0 +1,166 @@
+//! Subscriptions with a bounded buffer
+//!
+//! A bounded subscription holds at most `capacity` received messages. When the consumer
+//! falls that far behind, its `Backpressure` strategy decides what happens to the next
+//! message, so a slow handler costs messages instead of memory:
+//!
+//! - `Pause` stops reading from the client until there is room. The client's own buffer for
+//!   the subscription then fills, and the client drops messages once it is full, reporting a
+//!   slow consumer.
+//! - `Drop` discards the message.
+//! - `Error` discards the message, and the subscription's next item is a
+//!   `NatsError::SlowConsumer` with the number of messages discarded, so the consumer knows
+//!   it has missed some.
+//!
+//! ```ignore
+//! let mut ticks = NatsClient::subscribe_bounded("lanai.inventory.stock.>", 1_000, Backpressure::Drop).await?;
+//! while let Some(message) = ticks.next().await {
+//!     refresh_dashboard(message?).await;
+//! }
+//! ```
+//!
+//! Discarded messages count in `nats_subscription_dropped_total{subject, strategy}`.
+
+use futures_util::stream::{BoxStream, StreamExt};
+use opentelemetry::{global, KeyValue};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::sync::mpsc;
+
+use super::{NatsClient, NatsError, Subscription};
+
+/// What a bounded subscription does with a message once its buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Stop reading until the consumer catches up.
+    #[default]
+    Pause,
+    /// Discard the message.
+    Drop,
+    /// Discard the message and report it with `NatsError::SlowConsumer`.
+    Error,
+}
+
+impl Backpressure {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Drop => "drop",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Messages from `NatsClient::subscribe_bounded`/`queue_subscribe_bounded`.
+pub type BoundedSubscription = BoxStream<'static, Result<async_nats::Message, NatsError>>;
+
+impl NatsClient {
+    /// `subscribe`, buffering at most `capacity` messages
+    pub async fn subscribe_bounded(
+        subject: &str,
+        capacity: usize,
+        strategy: Backpressure,
+    ) -> Result<BoundedSubscription, NatsError> {
+        Ok(bounded(Self::subscribe(subject).await?, subject, capacity, strategy))
+    }
+
+    /// `queue_subscribe`, buffering at most `capacity` messages
+    pub async fn queue_subscribe_bounded(
+        subject: &str,
+        group: &str,
+        capacity: usize,
+        strategy: Backpressure,
+    ) -> Result<BoundedSubscription, NatsError> {
+        Ok(bounded(Self::queue_subscribe(subject, group).await?, subject, capacity, strategy))
+    }
+}
+
+/// Move `messages` through a channel of `capacity`, applying `strategy` when it is full.
+fn bounded(mut messages: Subscription, subject: &str, capacity: usize, strategy: Backpressure) -> BoundedSubscription {
+    let (sender, mut receiver) = mpsc::channel(capacity.max(1));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let counter = global::meter("lanai.messaging")
+        .u64_counter("nats_subscription_dropped_total")
+        .with_description("Messages discarded because a bounded subscription was full")
+        .build();
+    let labels = [KeyValue::new("subject", subject.to_string()), KeyValue::new("strategy", strategy.as_str())];
+
+    let unreported = dropped.clone();
+    tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                _ = sender.closed() => break,
+                message = messages.next() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+            match strategy {
+                Backpressure::Pause => {
+                    if sender.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                Backpressure::Drop | Backpressure::Error => match sender.try_send(message) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        counter.add(1, &labels);
+                        if strategy == Backpressure::Error {
+                            unreported.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => break,
+                },
+            }
+        }
+    });
+
+    futures_util::stream::poll_fn(move |cx| {
+        // Report discarded messages at the next poll, ahead of the buffered ones.
+        let missed = dropped.swap(0, Ordering::Relaxed);
+        if missed > 0 {
+            return Poll::Ready(Some(Err(NatsError::SlowConsumer(missed))));
+        }
+        receiver.poll_recv(cx).map(|message| message.map(Ok))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn message(n: u8) -> async_nats::Message {
+        async_nats::Message {
+            subject: "lanai.inventory.stock.1".into(),
+            reply: None,
+            payload: Bytes::from(vec![n]),
+            headers: None,
+            status: None,
+            description: None,
+            length: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_strategy_reports_dropped_messages() {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let source = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|message| (message, receiver))
+        })
+        .boxed();
+        let mut bounded = bounded(source, "lanai.inventory.stock.>", 2, Backpressure::Error);
+
+        for n in 0..5 {
+            sender.send(message(n)).unwrap();
+        }
+        // Let the forwarding task fill the buffer and discard the rest.
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        assert!(matches!(bounded.next().await, Some(Err(NatsError::SlowConsumer(3)))));
+        assert_eq!(bounded.next().await.unwrap().unwrap().payload, Bytes::from(vec![0]));
+        assert_eq!(bounded.next().await.unwrap().unwrap().payload, Bytes::from(vec![1]));
+    }
+}
@@ -0,0 +1,211 @@
+//! Publishing events in batches
+//!
+//! `NatsClient::publish_batch` sends a slice of events on one subject with a single Trace
+//! Context header map and one flush, instead of one of each per event. For a steady flow of
+//! small events (stock ticks, telemetry), a `BatchPublisher` coalesces what is published
+//! within a short window:
+//!
+//! ```ignore
+//! let ticks = BatchPublisher::<StockTick>::new().window(Duration::from_millis(5)).max_batch(512).start();
+//! // In the hot path; waits only if the publisher is `max_batch * 4` events behind.
+//! ticks.publish(&format!("lanai.inventory.stock.{}", tick.product_id), tick).await?;
+//! ```
+//!
+//! Events are serialized and published when the window closes or `max_batch` are waiting,
+//! so publish errors are only logged. Batched events carry the trace context of the batch,
+//! not of their caller. `NatsClient::shutdown` and `close` publish what is still waiting.
+
+use async_nats::HeaderMap;
+use bytes::Bytes;
+use log::warn;
+use std::marker::PhantomData;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::shutdown::{self, ConsumerGuard};
+use super::{inject_trace_context, serialize_pooled, NatsClient, NatsError, EMBEDDED_BROKER, PUBLISH_BUFFER};
+
+impl NatsClient {
+    /// Publish `events` on `subject` as JSON, sharing one set of headers (Trace Context)
+    /// and flushing once. Nothing is published if an event fails to serialize.
+    pub async fn publish_batch<T: serde::Serialize>(subject: &str, events: &[T]) -> Result<(), NatsError> {
+        let messages = events
+            .iter()
+            .map(|event| Ok((subject.to_string(), serialize_pooled(event)?)))
+            .collect::<Result<Vec<_>, NatsError>>()?;
+        publish_serialized(messages).await
+    }
+}
+
+/// Publish prepared messages with shared headers, flushing once at the end.
+async fn publish_serialized(messages: Vec<(String, Bytes)>) -> Result<(), NatsError> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let mut headers = HeaderMap::new();
+    inject_trace_context(&mut headers);
+
+    if let Some(broker) = EMBEDDED_BROKER.get() {
+        for (subject, payload) in messages {
+            broker.publish(&subject, None, headers.clone(), payload);
+        }
+        return Ok(());
+    }
+    let client = NatsClient::global().ok_or(NatsError::NotInitialized)?;
+    if let Some(buffer) = PUBLISH_BUFFER.get() {
+        for (subject, payload) in messages {
+            buffer.publish(&client, &subject, headers.clone(), payload).await?;
+        }
+        return Ok(());
+    }
+    for (subject, payload) in messages {
+        client
+            .publish_with_headers(subject, headers.clone(), payload)
+            .await
+            .map_err(|e| NatsError::PublishError(e.to_string()))?;
+    }
+    client.flush().await.map_err(|e| NatsError::PublishError(e.to_string()))
+}
+
+/// Coalesces events published within a window into batches; `start` it to publish.
+pub struct BatchPublisher<T> {
+    window: Duration,
+    max_batch: usize,
+    _event: PhantomData<fn(T)>,
+}
+
+impl<T: serde::Serialize + Send + 'static> BatchPublisher<T> {
+    /// Batches of up to 256 events, collected for at most 5 milliseconds.
+    pub fn new() -> Self {
+        Self { window: Duration::from_millis(5), max_batch: 256, _event: PhantomData }
+    }
+
+    /// How long the first event of a batch waits for others.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Publish as soon as this many events wait.
+    pub fn max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Start the background task publishing the batches.
+    pub fn start(self) -> RunningBatchPublisher<T> {
+        let (sender, mut receiver) = mpsc::channel(self.max_batch * 4);
+        let (window, max_batch) = (self.window, self.max_batch);
+        let draining = ConsumerGuard::register();
+        let task = tokio::spawn(async move {
+            let _draining = draining;
+            let mut batch = Vec::with_capacity(max_batch);
+            while next_batch(&mut receiver, &mut batch, window, max_batch).await {
+                publish_events(&mut batch).await;
+            }
+        });
+        RunningBatchPublisher { sender, task }
+    }
+}
+
+impl<T: serde::Serialize + Send + 'static> Default for BatchPublisher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A started `BatchPublisher`; dropping it publishes the events still waiting.
+pub struct RunningBatchPublisher<T> {
+    sender: mpsc::Sender<(String, T)>,
+    task: JoinHandle<()>,
+}
+
+impl<T> RunningBatchPublisher<T> {
+    /// Queue `event` for `subject`; fails once the publisher has stopped.
+    pub async fn publish(&self, subject: &str, event: T) -> Result<(), NatsError> {
+        self.sender
+            .send((subject.to_string(), event))
+            .await
+            .map_err(|_| NatsError::PublishError("batch publisher stopped".to_string()))
+    }
+
+    /// Publish the events still waiting and stop.
+    pub async fn close(self) {
+        drop(self.sender);
+        let _ = self.task.await;
+    }
+}
+
+/// Wait for an event, then add the ones arriving within `window` to `batch`, up to
+/// `max_batch`. Returns false once no more events can arrive. On shutdown, the events
+/// already queued make up the last batches.
+async fn next_batch<E>(
+    receiver: &mut mpsc::Receiver<E>,
+    batch: &mut Vec<E>,
+    window: Duration,
+    max_batch: usize,
+) -> bool {
+    let first = tokio::select! {
+        first = receiver.recv() => first,
+        _ = shutdown::requested() => {
+            receiver.close();
+            receiver.recv().await
+        }
+    };
+    let Some(first) = first else { return false };
+    batch.push(first);
+
+    let deadline = Instant::now() + window;
+    while batch.len() < max_batch {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(event)) => batch.push(event),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    true
+}
+
+/// Serialize and publish `batch`, leaving it empty; failures are logged.
+async fn publish_events<T: serde::Serialize>(batch: &mut Vec<(String, T)>) {
+    let count = batch.len();
+    let messages = batch
+        .drain(..)
+        .filter_map(|(subject, event)| match serialize_pooled(&event) {
+            Ok(payload) => Some((subject, payload)),
+            Err(e) => {
+                warn!("⚠️ Dropping a batched event for '{}': {}", subject, e);
+                None
+            }
+        })
+        .collect();
+    if let Err(e) = publish_serialized(messages).await {
+        warn!("⚠️ Failed to publish a batch of {} events: {}", count, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_next_batch_respects_max_batch() {
+        let (sender, mut receiver) = mpsc::channel(8);
+        for tick in 1..=3 {
+            sender.send(tick).await.unwrap();
+        }
+        drop(sender);
+
+        let mut batch = Vec::new();
+        assert!(next_batch(&mut receiver, &mut batch, Duration::from_millis(50), 2).await);
+        assert_eq!(batch, [1, 2]);
+
+        batch.clear();
+        assert!(next_batch(&mut receiver, &mut batch, Duration::from_millis(50), 2).await);
+        assert_eq!(batch, [3]);
+
+        batch.clear();
+        assert!(!next_batch(&mut receiver, &mut batch, Duration::from_millis(50), 2).await);
+    }
+}
@@ -0,0 +1,304 @@
+//! Bridging between HTTP routes and NATS request-reply endpoints
+//!
+//! Used while clients and services migrate from HTTP to messaging. `HttpToNats` mounts HTTP
+//! routes that forward to NATS subjects; they are ordinary routes of the app, so the
+//! server's auth, tenant, rate limiting and tracing middleware apply to them. `NatsToHttp`
+//! answers NATS requests by calling an HTTP service with a service token.
+//!
+//! Both directions carry the tenant (`Lanai-Org-Id` on NATS, `X-Organization-ID` on HTTP),
+//! Trace Context, and the HTTP status (`Lanai-Status` on NATS replies).
+//!
+//! ```ignore
+//! let bridge = HttpToNats::new()
+//!     .route(Method::POST, "/api/v1/stock/reserve", "lanai.inventory.stock.reserve")
+//!     .route(Method::GET, "/api/v1/products/{id}", "lanai.inventory.product.get");
+//! ServerBuilder::new().run(move |cfg| bridge.configure(cfg)).await?;
+//!
+//! let _legacy = NatsToHttp::new("http://billing-legacy:8080")
+//!     .endpoint("lanai.billing.invoice.get", Method::GET, "/invoices/{invoice_id}")
+//!     .start()
+//!     .await?;
+//! ```
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::{extract_trace_context, inject_trace_context, NatsClient, NatsError};
+use crate::error::LanaiError;
+use crate::middleware::service_token::SERVICE_TOKEN_ENV;
+use crate::middleware::tenant_context::TenantContext;
+
+/// HTTP status of a bridged reply.
+pub const STATUS_HEADER: &str = "Lanai-Status";
+/// Tenant of a bridged request.
+pub const ORG_HEADER: &str = "Lanai-Org-Id";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct HttpRoute {
+    method: Method,
+    path: String,
+    subject: String,
+}
+
+/// HTTP routes answered by NATS request-reply endpoints.
+///
+/// The request body is sent as the NATS payload; bodiless requests (e.g. `GET`) send their
+/// path and query parameters as a JSON object instead. The reply payload is returned as
+/// JSON with the status from `Lanai-Status` (200 if absent).
+pub struct HttpToNats {
+    routes: Vec<HttpRoute>,
+    timeout: Duration,
+}
+
+impl Default for HttpToNats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpToNats {
+    pub fn new() -> Self {
+        Self { routes: Vec::new(), timeout: DEFAULT_TIMEOUT }
+    }
+
+    pub fn route(mut self, method: Method, path: &str, subject: &str) -> Self {
+        self.routes.push(HttpRoute { method, path: path.to_string(), subject: subject.to_string() });
+        self
+    }
+
+    /// How long to wait for a reply (default 10s); exceeding it returns 504.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        for route in &self.routes {
+            let subject: Arc<str> = Arc::from(route.subject.as_str());
+            let timeout = self.timeout;
+            cfg.route(
+                &route.path,
+                web::method(route.method.clone()).to(move |req: HttpRequest, body: web::Bytes| {
+                    forward_to_nats(req, body, subject.clone(), timeout)
+                }),
+            );
+            info!("🌉 Bridging {} {} to '{}'", route.method, route.path, route.subject);
+        }
+    }
+}
+
+/// Path and query parameters of a bodiless request, as a JSON object.
+fn params_payload(req: &HttpRequest) -> Vec<u8> {
+    let mut params = serde_json::Map::new();
+    for (name, value) in web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(|q| q.into_inner())
+        .unwrap_or_default()
+    {
+        params.insert(name, serde_json::Value::String(value));
+    }
+    for (name, value) in req.match_info().iter() {
+        params.insert(name.to_string(), serde_json::Value::String(value.to_string()));
+    }
+    serde_json::to_vec(&params).unwrap_or_default()
+}
+
+async fn forward_to_nats(
+    req: HttpRequest,
+    body: web::Bytes,
+    subject: Arc<str>,
+    timeout: Duration,
+) -> Result<HttpResponse, LanaiError> {
+    let payload = if body.is_empty() { params_payload(&req).into() } else { body };
+    let mut headers = async_nats::HeaderMap::new();
+    if let Some(tenant) = req.extensions().get::<TenantContext>() {
+        headers.insert(ORG_HEADER, tenant.org_id.to_string().as_str());
+    }
+    inject_trace_context(&mut headers);
+
+    let reply = tokio::time::timeout(timeout, NatsClient::request_with_headers(&subject, headers, payload))
+        .await
+        .map_err(|_| NatsError::Timeout(subject.to_string(), timeout))?
+        .map_err(|e| {
+            warn!("⚠️ Bridged request to '{}' failed: {}", subject, e);
+            LanaiError::Unavailable("Service unavailable, please retry later".to_string())
+        })?;
+
+    let status = reply
+        .headers
+        .as_ref()
+        .and_then(|h| h.get(STATUS_HEADER))
+        .and_then(|v| v.as_str().parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::OK);
+
+    Ok(HttpResponse::build(status).content_type("application/json").body(reply.payload))
+}
+
+struct NatsEndpoint {
+    subject: String,
+    method: Method,
+    path: String,
+}
+
+/// NATS request-reply endpoints answered by an HTTP service.
+///
+/// The payload is sent as the JSON body (omitted for `GET`/`DELETE`); `{name}` segments in
+/// the path are filled from the payload's top-level fields. Replicas share endpoints
+/// through the `lanai-bridge` queue group.
+pub struct NatsToHttp {
+    base_url: String,
+    endpoints: Vec<NatsEndpoint>,
+    client: reqwest::Client,
+    service_token: Option<String>,
+}
+
+impl NatsToHttp {
+    /// Calls authenticate with the first token in `LANAI_SERVICE_TOKEN`, if set.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            endpoints: Vec::new(),
+            client: reqwest::Client::builder().timeout(DEFAULT_TIMEOUT).build().unwrap_or_default(),
+            service_token: std::env::var(SERVICE_TOKEN_ENV)
+                .ok()
+                .and_then(|tokens| tokens.split(',').next().map(|t| t.trim().to_string()))
+                .filter(|t| !t.is_empty()),
+        }
+    }
+
+    pub fn endpoint(mut self, subject: &str, method: Method, path: &str) -> Self {
+        self.endpoints.push(NatsEndpoint { subject: subject.to_string(), method, path: path.to_string() });
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = reqwest::Client::builder().timeout(timeout).build().unwrap_or_default();
+        self
+    }
+
+    pub fn service_token(mut self, token: &str) -> Self {
+        self.service_token = Some(token.to_string());
+        self
+    }
+
+    /// Answer requests until the handles are aborted.
+    pub async fn start(self) -> Result<Vec<JoinHandle<()>>, NatsError> {
+        let bridge = Arc::new(self);
+        let mut tasks = Vec::new();
+
+        for (index, endpoint) in bridge.endpoints.iter().enumerate() {
+            let mut subscriber = NatsClient::queue_subscribe(&endpoint.subject, "lanai-bridge").await?;
+            info!("🌉 Bridging '{}' to {} {}{}", endpoint.subject, endpoint.method, bridge.base_url, endpoint.path);
+
+            let bridge = bridge.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let Some(reply) = message.reply.clone() else { continue };
+                    let endpoint = &bridge.endpoints[index];
+
+                    let span = tracing::info_span!("nats_http_bridge", subject = %message.subject);
+                    span.set_parent(extract_trace_context(message.headers.as_ref()));
+                    let (status, body) = bridge.forward(endpoint, &message).instrument(span).await;
+
+                    let mut headers = async_nats::HeaderMap::new();
+                    headers.insert(STATUS_HEADER, status.as_str());
+                    if let Err(e) = NatsClient::publish_with_headers(&reply, headers, body.into()).await {
+                        error!("❌ Failed to reply to bridged request on '{}': {}", message.subject, e);
+                    }
+                }
+            }));
+        }
+        Ok(tasks)
+    }
+
+    async fn forward(&self, endpoint: &NatsEndpoint, message: &async_nats::Message) -> (StatusCode, Vec<u8>) {
+        let payload: serde_json::Value = serde_json::from_slice(&message.payload).unwrap_or(serde_json::Value::Null);
+        let url = format!("{}{}", self.base_url, fill_path(&endpoint.path, &payload));
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        let cx = tracing::Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut opentelemetry_http::HeaderInjector(&mut headers))
+        });
+
+        // actix and reqwest use different `http` crate versions
+        let method = reqwest::Method::from_bytes(endpoint.method.as_str().as_bytes()).unwrap_or(reqwest::Method::POST);
+        let mut request = self.client.request(method, &url).headers(headers);
+        if let Some(org_id) = message.headers.as_ref().and_then(|h| h.get(ORG_HEADER)) {
+            request = request.header("X-Organization-ID", org_id.as_str());
+        }
+        if let Some(token) = &self.service_token {
+            request = request.bearer_auth(token);
+        }
+        if !matches!(endpoint.method, Method::GET | Method::DELETE) {
+            request = request.header(reqwest::header::CONTENT_TYPE, "application/json").body(message.payload.to_vec());
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+                (status, response.bytes().await.map(|b| b.to_vec()).unwrap_or_default())
+            }
+            Err(e) => {
+                warn!("⚠️ Bridged call {} {} failed: {}", endpoint.method, url, e);
+                let status = if e.is_timeout() { StatusCode::GATEWAY_TIMEOUT } else { StatusCode::BAD_GATEWAY };
+                (status, serde_json::to_vec(&serde_json::json!({"error": e.to_string()})).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// `/invoices/{invoice_id}` with `invoice_id` taken from the payload object.
+fn fill_path(path: &str, payload: &serde_json::Value) -> String {
+    path.split('/')
+        .map(|segment| {
+            let name = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}'));
+            match name.and_then(|name| payload.get(name)) {
+                Some(serde_json::Value::String(value)) => encode_segment(value),
+                Some(value) => value.to_string(),
+                None => segment.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn encode_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+
+    #[test]
+    fn test_fill_path() {
+        let payload = serde_json::json!({"invoice_id": "INV 7", "line": 2});
+        assert_eq!(fill_path("/invoices/{invoice_id}/lines/{line}", &payload), "/invoices/INV%207/lines/2");
+        assert_eq!(fill_path("/invoices/{missing}", &payload), "/invoices/{missing}");
+    }
+
+    #[actix_web::test]
+    async fn test_bridged_route_without_nats_is_unavailable() {
+        let bridge = HttpToNats::new().route(Method::GET, "/api/v1/products/{id}", "lanai.inventory.product.get");
+        let app = actix_test::init_service(App::new().configure(|cfg| bridge.configure(cfg))).await;
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/products/42?fields=name").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,216 @@
+//! Buffering publishes while NATS is unreachable
+//!
+//! With `NatsConfig::publish_buffer` set (or `NATS_PUBLISH_BUFFER=<capacity>`), messages
+//! published while the client is disconnected, or whose publish fails, are kept in memory
+//! instead of failing, and sent in order once the client reconnects. Later publishes queue
+//! behind them until the buffer is empty, so subscribers still see them in publish order.
+//!
+//! The buffer is bounded: once `capacity` messages wait, `OverflowPolicy::DropOldest` drops
+//! the oldest one for each new message and `OverflowPolicy::Error` fails the new publish with
+//! `NatsError::BufferFull`. Buffered messages are lost if the process exits first; use
+//! `NatsClient::publish_durable` for events that must not be. `publish_event_bytes` and
+//! requests bypass the buffer.
+
+use async_nats::HeaderMap;
+use bytes::Bytes;
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::{NatsClient, NatsError, PendingPublish};
+
+/// Environment variable enabling the publish buffer with this capacity
+pub const NATS_PUBLISH_BUFFER_ENV: &str = "NATS_PUBLISH_BUFFER";
+
+/// What a full buffer does with another message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop the oldest buffered message to make room.
+    #[default]
+    DropOldest,
+    /// Fail the publish with `NatsError::BufferFull`.
+    Error,
+}
+
+/// Settings of the publish buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishBufferConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl PublishBufferConfig {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, overflow: OverflowPolicy::default() }
+    }
+
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// A buffer of `NATS_PUBLISH_BUFFER` messages, if set to a positive number.
+    pub fn from_env() -> Option<Self> {
+        let capacity = std::env::var(NATS_PUBLISH_BUFFER_ENV).ok()?.trim().parse::<usize>().ok()?;
+        (capacity > 0).then(|| Self::new(capacity))
+    }
+}
+
+struct Buffered {
+    subject: String,
+    headers: HeaderMap,
+    payload: Bytes,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Buffered>,
+    /// A flush is sending the queue; publishes wait behind it.
+    flushing: bool,
+}
+
+/// Messages waiting for the connection; clones share them.
+#[derive(Clone)]
+pub struct PublishBuffer {
+    config: PublishBufferConfig,
+    state: Arc<Mutex<State>>,
+}
+
+impl PublishBuffer {
+    pub fn new(config: PublishBufferConfig) -> Self {
+        Self { config, state: Arc::new(Mutex::new(State::default())) }
+    }
+
+    /// Messages waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Payload bytes waiting to be sent.
+    pub fn bytes(&self) -> usize {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.queue.iter().map(|message| message.payload.len()).sum()
+    }
+
+    /// Publish through the buffer: straight to `client` if connected and nothing waits,
+    /// buffered otherwise.
+    pub(crate) async fn publish(
+        &self,
+        client: &async_nats::Client,
+        subject: &str,
+        headers: HeaderMap,
+        payload: Bytes,
+    ) -> Result<(), NatsError> {
+        let connected = matches!(client.connection_state(), async_nats::connection::State::Connected);
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if !connected || state.flushing || !state.queue.is_empty() {
+                self.push(&mut state, Buffered { subject: subject.to_string(), headers, payload })?;
+                let idle = connected && !state.flushing;
+                drop(state);
+                if idle {
+                    tokio::spawn(self.clone().flush());
+                }
+                return Ok(());
+            }
+        }
+
+        let pending = PendingPublish::start(&payload);
+        let result = client.publish_with_headers(subject.to_string(), headers.clone(), payload.clone()).await;
+        drop(pending);
+        if let Err(e) = result {
+            warn!("⚠️ Publish to '{}' failed, buffering it: {}", subject, e);
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.push(&mut state, Buffered { subject: subject.to_string(), headers, payload })?;
+        }
+        Ok(())
+    }
+
+    fn push(&self, state: &mut State, message: Buffered) -> Result<(), NatsError> {
+        if state.queue.len() >= self.config.capacity {
+            match self.config.overflow {
+                OverflowPolicy::Error => return Err(NatsError::BufferFull(self.config.capacity)),
+                OverflowPolicy::DropOldest => {
+                    if let Some(dropped) = state.queue.pop_front() {
+                        warn!("🗑️ Publish buffer full, dropped a message for '{}'", dropped.subject);
+                    }
+                }
+            }
+        }
+        state.queue.push_back(message);
+        Ok(())
+    }
+
+    /// Send the buffered messages in order, stopping at the first failure.
+    pub async fn flush(self) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.flushing || state.queue.is_empty() {
+                return;
+            }
+            state.flushing = true;
+        }
+
+        let mut sent = 0;
+        loop {
+            let next = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                let next = state.queue.pop_front();
+                if next.is_none() {
+                    state.flushing = false;
+                }
+                next
+            };
+            let Some(message) = next else { break };
+
+            let _pending = PendingPublish::start(&message.payload);
+            let result = match NatsClient::global() {
+                Some(client) => client
+                    .publish_with_headers(message.subject.clone(), message.headers.clone(), message.payload.clone())
+                    .await
+                    .map_err(|e| e.to_string()),
+                None => Err("client not initialized".to_string()),
+            };
+            if let Err(e) = result {
+                warn!("⚠️ Flushing the publish buffer stopped at '{}': {}", message.subject, e);
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                state.queue.push_front(message);
+                state.flushing = false;
+                break;
+            }
+            sent += 1;
+        }
+        if sent > 0 {
+            info!("📤 Flushed {} buffered messages", sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(subject: &str) -> Buffered {
+        Buffered { subject: subject.to_string(), headers: HeaderMap::new(), payload: Bytes::new() }
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        let dropping = PublishBuffer::new(PublishBufferConfig::new(2));
+        let mut state = State::default();
+        for subject in ["a", "b", "c"] {
+            dropping.push(&mut state, message(subject)).unwrap();
+        }
+        let subjects: Vec<_> = state.queue.iter().map(|m| m.subject.as_str()).collect();
+        assert_eq!(subjects, ["b", "c"]);
+
+        let failing = PublishBuffer::new(PublishBufferConfig::new(1).overflow(OverflowPolicy::Error));
+        let mut state = State::default();
+        failing.push(&mut state, message("a")).unwrap();
+        assert!(matches!(failing.push(&mut state, message("b")), Err(NatsError::BufferFull(1))));
+    }
+}
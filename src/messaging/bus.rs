@@ -0,0 +1,104 @@
+//! Publishing and subscribing through an injectable bus
+//!
+//! Code that takes an `Arc<dyn MessageBus>` instead of calling `NatsClient` directly can be
+//! unit-tested with `testing::InMemoryMessageBus`, which captures what was published, and
+//! can run on Kafka with the `kafka` feature (see `kafka`), for services migrating from it.
+//! `message_bus` picks the backend from `MESSAGE_BUS`:
+//!
+//! ```ignore
+//! let bus = message_bus()?; // NatsMessageBus unless MESSAGE_BUS=kafka
+//! bus.publish_event("lanai.orders.created.42", &event).await?;
+//!
+//! let mut orders = bus.queue_subscribe("lanai.orders.created.>", "billing").await?;
+//! while let Some(message) = orders.next().await { ... }
+//! ```
+//!
+//! Subscriptions yield `async_nats::Message`s whatever the backend, so `TypedMessage::decode`
+//! and existing handlers work unchanged.
+
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::Serialize;
+use std::sync::Arc;
+
+use super::dedup::{with_message_id, Identified};
+use super::{inject_trace_context, NatsClient, NatsError, Subscription};
+
+/// Selects the backend of `message_bus`: `nats` (default) or `kafka`
+pub const MESSAGE_BUS_ENV: &str = "MESSAGE_BUS";
+
+#[async_trait]
+pub trait MessageBus: Send + Sync {
+    /// Publish `payload` with `headers` to `subject`.
+    async fn publish_raw(&self, subject: &str, headers: HeaderMap, payload: Bytes) -> Result<(), NatsError>;
+
+    /// Every message on `subject` (NATS wildcards allowed). Publish-only buses fail with
+    /// `ConnectionError`.
+    async fn subscribe(&self, subject: &str) -> Result<Subscription, NatsError> {
+        Err(NatsError::ConnectionError(format!("cannot subscribe to '{}' on a publish-only bus", subject)))
+    }
+
+    /// Messages on `subject`, each delivered to one member of `group`.
+    async fn queue_subscribe(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        Err(NatsError::ConnectionError(format!("cannot join '{}' on '{}' on a publish-only bus", group, subject)))
+    }
+}
+
+/// Typed publishing for any `MessageBus`.
+#[async_trait]
+pub trait MessageBusExt: MessageBus {
+    /// Publish `event` as JSON.
+    async fn publish_event<T: Serialize + Sync>(&self, subject: &str, event: &T) -> Result<(), NatsError> {
+        self.publish_event_with_headers(subject, event, HeaderMap::new()).await
+    }
+
+    async fn publish_event_with_headers<T: Serialize + Sync>(
+        &self,
+        subject: &str,
+        event: &T,
+        headers: HeaderMap,
+    ) -> Result<(), NatsError> {
+        let payload = serde_json::to_vec(event).map_err(|e| NatsError::SerializationError(e.to_string()))?;
+        self.publish_raw(subject, headers, payload.into()).await
+    }
+
+    /// Publish `event` with its ID as `Nats-Msg-Id` (see `dedup`).
+    async fn publish_identified<T: Serialize + Identified + Sync>(&self, subject: &str, event: &T) -> Result<(), NatsError> {
+        let headers = with_message_id(HeaderMap::new(), &event.message_id());
+        self.publish_event_with_headers(subject, event, headers).await
+    }
+}
+
+impl<B: MessageBus + ?Sized> MessageBusExt for B {}
+
+/// Publishes through the global `NatsClient`, with Trace Context headers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NatsMessageBus;
+
+#[async_trait]
+impl MessageBus for NatsMessageBus {
+    async fn publish_raw(&self, subject: &str, mut headers: HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+        inject_trace_context(&mut headers);
+        NatsClient::publish_with_headers(subject, headers, payload).await
+    }
+
+    async fn subscribe(&self, subject: &str) -> Result<Subscription, NatsError> {
+        NatsClient::subscribe(subject).await
+    }
+
+    async fn queue_subscribe(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        NatsClient::queue_subscribe(subject, group).await
+    }
+}
+
+/// The bus `MESSAGE_BUS` selects: the global `NatsClient` by default, or a `KafkaMessageBus`
+/// from `KAFKA_BROKERS` with `MESSAGE_BUS=kafka` (`kafka` feature).
+pub fn message_bus() -> Result<Arc<dyn MessageBus>, NatsError> {
+    match std::env::var(MESSAGE_BUS_ENV).unwrap_or_default().trim() {
+        "" | "nats" => Ok(Arc::new(NatsMessageBus)),
+        #[cfg(feature = "kafka")]
+        "kafka" => Ok(Arc::new(super::kafka::KafkaMessageBus::from_env()?)),
+        other => Err(NatsError::ConnectionError(format!("unsupported {} '{}'", MESSAGE_BUS_ENV, other))),
+    }
+}
@@ -0,0 +1,234 @@
+//! Connection lifecycle events
+//!
+//! The client reports what happens to its connection: disconnects, reconnects, slow
+//! consumers, and a server entering lame duck mode (about to shut down, so clients should
+//! move). Each event is logged, counted in `nats_connection_events_total{connection, event}`,
+//! broadcast to `NatsClient::connection_events` and passed to the callbacks registered with
+//! `NatsClient::on_connection_event`:
+//!
+//! ```ignore
+//! NatsClient::on_connection_event(|event| {
+//!     if event.kind == ConnectionEventKind::LameDuck {
+//!         warn!("NATS server for '{}' is shutting down", event.connection);
+//!     }
+//! });
+//!
+//! let mut events = NatsClient::connection_events();
+//! while let Ok(event) = events.recv().await {
+//!     dashboard.record(event);
+//! }
+//! ```
+//!
+//! Callbacks run on the client's connection task: keep them short and spawn anything slow.
+//! `NatsCheck` reports a connected client degraded while its connection flaps (3 disconnects
+//! within 5 minutes), after a lame duck notice until it reconnects, and for a minute after
+//! a slow consumer.
+
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use super::NatsClient;
+
+/// Disconnects within `FLAP_WINDOW` that make a connection flapping.
+const FLAP_DISCONNECTS: usize = 3;
+const FLAP_WINDOW: Duration = Duration::from_secs(5 * 60);
+/// How long a slow consumer keeps the connection degraded.
+const SLOW_CONSUMER_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEventKind {
+    /// First connection of the client.
+    Connected,
+    /// Connected again after a disconnect.
+    Reconnected,
+    Disconnected,
+    /// The server is shutting down and clients should reconnect elsewhere.
+    LameDuck,
+    /// The client dropped messages for the subscription with this ID.
+    SlowConsumer(u64),
+    ServerError(String),
+    ClientError(String),
+}
+
+impl ConnectionEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::Reconnected => "reconnected",
+            Self::Disconnected => "disconnected",
+            Self::LameDuck => "lame_duck",
+            Self::SlowConsumer(_) => "slow_consumer",
+            Self::ServerError(_) => "server_error",
+            Self::ClientError(_) => "client_error",
+        }
+    }
+}
+
+/// Something that happened to the connection named `connection`.
+#[derive(Debug, Clone)]
+pub struct ConnectionEvent {
+    pub connection: String,
+    pub kind: ConnectionEventKind,
+    pub occurred_at: DateTime<Utc>,
+}
+
+type Callback = Arc<dyn Fn(&ConnectionEvent) + Send + Sync>;
+
+struct ConnectionEvents {
+    sender: broadcast::Sender<ConnectionEvent>,
+    callbacks: Mutex<Vec<Callback>>,
+    state: Mutex<ConnectionState>,
+    counter: Counter<u64>,
+}
+
+static EVENTS: OnceLock<ConnectionEvents> = OnceLock::new();
+
+fn events() -> &'static ConnectionEvents {
+    EVENTS.get_or_init(|| ConnectionEvents {
+        sender: broadcast::channel(64).0,
+        callbacks: Mutex::new(Vec::new()),
+        state: Mutex::new(ConnectionState::default()),
+        counter: global::meter("lanai.messaging")
+            .u64_counter("nats_connection_events_total")
+            .with_description("NATS connection events (disconnects, reconnects, slow consumers...)")
+            .build(),
+    })
+}
+
+/// What the connection events say about its health.
+#[derive(Debug, Default)]
+struct ConnectionState {
+    connected_before: bool,
+    disconnects: VecDeque<Instant>,
+    lame_duck: bool,
+    slow_consumer_at: Option<Instant>,
+}
+
+impl ConnectionState {
+    /// Record `event`, telling a reconnect from the first connect.
+    fn apply(&mut self, event: async_nats::Event, now: Instant) -> ConnectionEventKind {
+        match event {
+            async_nats::Event::Connected => {
+                self.lame_duck = false;
+                if std::mem::replace(&mut self.connected_before, true) {
+                    ConnectionEventKind::Reconnected
+                } else {
+                    ConnectionEventKind::Connected
+                }
+            }
+            async_nats::Event::Disconnected => {
+                self.disconnects.push_back(now);
+                while self.disconnects.len() > FLAP_DISCONNECTS {
+                    self.disconnects.pop_front();
+                }
+                ConnectionEventKind::Disconnected
+            }
+            async_nats::Event::LameDuckMode => {
+                self.lame_duck = true;
+                ConnectionEventKind::LameDuck
+            }
+            async_nats::Event::SlowConsumer(sid) => {
+                self.slow_consumer_at = Some(now);
+                ConnectionEventKind::SlowConsumer(sid)
+            }
+            async_nats::Event::ServerError(e) => ConnectionEventKind::ServerError(e.to_string()),
+            async_nats::Event::ClientError(e) => ConnectionEventKind::ClientError(e.to_string()),
+        }
+    }
+
+    /// Why a connected client should be reported degraded, if it should.
+    fn degradation(&self, now: Instant) -> Option<String> {
+        let recent = self.disconnects.iter().filter(|at| now.duration_since(**at) < FLAP_WINDOW).count();
+        if recent >= FLAP_DISCONNECTS {
+            Some(format!("{} disconnects in the last {} minutes", recent, FLAP_WINDOW.as_secs() / 60))
+        } else if self.lame_duck {
+            Some("server in lame duck mode".to_string())
+        } else if self.slow_consumer_at.is_some_and(|at| now.duration_since(at) < SLOW_CONSUMER_WINDOW) {
+            Some("slow consumer dropping messages".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Handle an event from the client of `connection` (its `event_callback`).
+pub(super) fn record(connection: &str, event: async_nats::Event) {
+    let events = events();
+    let kind = events.state.lock().unwrap_or_else(|e| e.into_inner()).apply(event, Instant::now());
+    match &kind {
+        ConnectionEventKind::Connected => {}
+        ConnectionEventKind::Reconnected => info!("🔌 NATS connection '{}' restored", connection),
+        ConnectionEventKind::Disconnected => warn!("🔌 NATS connection '{}' lost, reconnecting", connection),
+        ConnectionEventKind::LameDuck => warn!("🦆 NATS server of '{}' entered lame duck mode", connection),
+        ConnectionEventKind::SlowConsumer(sid) => {
+            warn!("🐢 NATS connection '{}' dropping messages for slow subscription {}", connection, sid)
+        }
+        ConnectionEventKind::ServerError(e) | ConnectionEventKind::ClientError(e) => {
+            error!("❌ NATS connection '{}': {}", connection, e)
+        }
+    }
+    events.counter.add(
+        1,
+        &[KeyValue::new("connection", connection.to_string()), KeyValue::new("event", kind.as_str())],
+    );
+
+    let event = ConnectionEvent { connection: connection.to_string(), kind, occurred_at: Utc::now() };
+    let callbacks = events.callbacks.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for callback in callbacks {
+        callback(&event);
+    }
+    let _ = events.sender.send(event);
+}
+
+impl NatsClient {
+    /// Connection events from now on; a receiver more than 64 events behind skips ahead.
+    pub fn connection_events() -> broadcast::Receiver<ConnectionEvent> {
+        events().sender.subscribe()
+    }
+
+    /// Call `callback` with every connection event from now on.
+    pub fn on_connection_event<F>(callback: F)
+    where
+        F: Fn(&ConnectionEvent) + Send + Sync + 'static,
+    {
+        events().callbacks.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::new(callback));
+    }
+
+    /// Why the connection, though up, should be reported degraded: flapping, a server in
+    /// lame duck mode or a recent slow consumer.
+    pub fn connection_degradation() -> Option<String> {
+        events().state.lock().unwrap_or_else(|e| e.into_inner()).degradation(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_reports_flaps_and_lame_duck() {
+        let mut state = ConnectionState::default();
+        let start = Instant::now();
+        assert_eq!(state.apply(async_nats::Event::Connected, start), ConnectionEventKind::Connected);
+        assert_eq!(state.degradation(start), None);
+
+        for minute in 0..3 {
+            let at = start + Duration::from_secs(60 * minute);
+            assert_eq!(state.apply(async_nats::Event::Disconnected, at), ConnectionEventKind::Disconnected);
+            assert_eq!(state.apply(async_nats::Event::Connected, at), ConnectionEventKind::Reconnected);
+        }
+        assert!(state.degradation(start + Duration::from_secs(180)).unwrap().contains("3 disconnects"));
+        assert_eq!(state.degradation(start + Duration::from_secs(600)), None);
+
+        state.apply(async_nats::Event::LameDuckMode, start + Duration::from_secs(600));
+        assert_eq!(state.degradation(start + Duration::from_secs(600)).as_deref(), Some("server in lame duck mode"));
+        state.apply(async_nats::Event::Connected, start + Duration::from_secs(601));
+        assert_eq!(state.degradation(start + Duration::from_secs(601)), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Competing consumers on a queue group
+//!
+//! A `QueueSubscriber` reads a subject through a queue group, so replicas of a service share
+//! its messages, and runs a handler for each of them:
+//!
+//! ```ignore
+//! let consumer = QueueSubscriber::new("lanai.orders.created.>", "billing", |received: TypedMessage<OrderCreatedEvent>| async move {
+//!     bill(&received.event).await
+//! })
+//! .concurrency(32)
+//! .start()
+//! .await?;
+//!
+//! // On shutdown: stop taking messages and let the ones in flight finish.
+//! consumer.shutdown().await;
+//! ```
+//!
+//! `NatsClient::shutdown` does the same for every running `QueueSubscriber` and waits for them.
+//!
+//! Handlers run in their own tasks, at most `concurrency` at once, inside the message's
+//! `nats_consume` span. An error or a panic is logged and affects only that message. Core NATS
+//! does not redeliver: use a `DurableConsumer` for events that must be processed.
+
+use futures_util::{FutureExt, StreamExt};
+use log::{error, info, warn};
+use std::fmt::Display;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::Instrument;
+
+use super::shutdown::{self, ConsumerGuard};
+use super::{NatsClient, NatsError, TypedMessage};
+
+/// Runs a handler for each message of a queue-group subscription.
+pub struct QueueSubscriber<T, F> {
+    subject: String,
+    group: String,
+    handler: F,
+    concurrency: usize,
+    drain_timeout: Duration,
+    _event: PhantomData<fn() -> T>,
+}
+
+impl<T, F, Fut, E> QueueSubscriber<T, F>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    F: Fn(TypedMessage<T>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display + 'static,
+{
+    /// 16 handlers at once; `shutdown` waits up to 30 seconds for them.
+    pub fn new(subject: &str, group: &str, handler: F) -> Self {
+        Self {
+            subject: subject.to_string(),
+            group: group.to_string(),
+            handler,
+            concurrency: 16,
+            drain_timeout: Duration::from_secs(30),
+            _event: PhantomData,
+        }
+    }
+
+    /// Maximum number of messages handled at once; further messages wait in the subscription.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How long `shutdown` waits for handlers in flight before aborting them.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Subscribe and handle messages until shut down.
+    pub async fn start(self) -> Result<RunningQueueSubscriber, NatsError> {
+        let mut messages = NatsClient::queue_subscribe_typed::<T>(&self.subject, &self.group).await?;
+        let (stop, mut stopped) = watch::channel(false);
+        let handler = Arc::new(self.handler);
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let (subject, group, drain_timeout) = (self.subject.clone(), self.group.clone(), self.drain_timeout);
+        info!("📥 Consuming '{}' in queue group '{}' (concurrency {})", self.subject, self.group, self.concurrency);
+
+        let draining = ConsumerGuard::register();
+        let task = tokio::spawn(async move {
+            let _draining = draining;
+            let mut in_flight = JoinSet::new();
+            loop {
+                let permit = tokio::select! {
+                    _ = stop_requested(&mut stopped) => break,
+                    permit = permits.clone().acquire_owned() => permit.expect("permits are never closed"),
+                };
+                let received = tokio::select! {
+                    _ = stop_requested(&mut stopped) => break,
+                    received = messages.next() => received,
+                };
+                // Reap finished handlers so the set does not grow with every message.
+                while in_flight.try_join_next().is_some() {}
+
+                let received = match received {
+                    Some(Ok(received)) => received,
+                    // Already logged by `TypedMessage::decode`.
+                    Some(Err(_)) => continue,
+                    // The subscription ends when `NatsClient::shutdown` starts.
+                    None if shutdown::is_requested() => break,
+                    None => {
+                        warn!("⚠️ Subscription to '{}' in queue group '{}' ended", subject, group);
+                        break;
+                    }
+                };
+                let handler = handler.clone();
+                let subject = received.message.subject.to_string();
+                let span = received.span.clone();
+                in_flight.spawn(async move {
+                    let _permit = permit;
+                    match AssertUnwindSafe(handler(received)).catch_unwind().instrument(span).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!("❌ Handler failed on '{}': {}", subject, e),
+                        Err(panic) => error!("❌ Handler panicked on '{}': {}", subject, panic_message(panic)),
+                    }
+                });
+            }
+
+            // Unsubscribe first, so the group's other members get the messages from now on.
+            drop(messages);
+            let drained = tokio::time::timeout(drain_timeout, async {
+                while in_flight.join_next().await.is_some() {}
+            })
+            .await;
+            if drained.is_err() {
+                warn!("⚠️ Aborting {} handlers on '{}' still running after {:?}", in_flight.len(), subject, drain_timeout);
+            }
+            info!("🛑 Stopped consuming '{}' in queue group '{}'", subject, group);
+        });
+
+        Ok(RunningQueueSubscriber { stop, task })
+    }
+}
+
+/// A started `QueueSubscriber`; it keeps running when dropped.
+pub struct RunningQueueSubscriber {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl RunningQueueSubscriber {
+    /// Stop taking messages and wait for the handlers in flight, up to the drain timeout.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+
+    /// Stop at once, abandoning the handlers in flight.
+    pub fn abort(self) {
+        self.task.abort();
+    }
+}
+
+impl NatsClient {
+    /// Handle `subject` in `queue_group` with `handler`, with the `QueueSubscriber` defaults.
+    pub async fn subscribe_queue_typed<T, F, Fut, E>(
+        subject: &str,
+        queue_group: &str,
+        handler: F,
+    ) -> Result<RunningQueueSubscriber, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+        F: Fn(TypedMessage<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + 'static,
+    {
+        QueueSubscriber::new(subject, queue_group, handler).start().await
+    }
+}
+
+/// Resolves once a stop is requested; never if the handle was dropped without one.
+async fn stop_requested(stopped: &mut watch::Receiver<bool>) {
+    if stopped.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+pub(super) fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let panic = std::panic::catch_unwind(|| panic!("stock for {} went negative", "sku-1")).unwrap_err();
+        assert_eq!(panic_message(panic), "stock for sku-1 went negative");
+    }
+}
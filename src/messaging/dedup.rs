@@ -0,0 +1,151 @@
+//! Duplicate suppression with `Nats-Msg-Id`
+//!
+//! JetStream drops a message whose `Nats-Msg-Id` it has already stored within the stream's
+//! duplicate window, so a publish that is retried after a lost acknowledgement is stored
+//! once. Events implementing `Identified` are published with their own ID as the message
+//! ID; `NatsClient::publish_event` uses its envelope's event ID, and
+//! `publish_event_with_retry` republishes the same envelope on every attempt.
+//!
+//! Redeliveries still reach consumers (an ack can be lost too), so consumers check
+//! `DuplicateDetector::is_duplicate` before handling a message:
+//!
+//! ```ignore
+//! ensure_duplicate_window("ORDERS", Duration::from_secs(600)).await?;
+//! NatsClient::publish_identified("lanai.orders.created", &event).await?;
+//!
+//! let detector = DuplicateDetector::new("billing", Arc::new(RedisReplayCache::new(conn)));
+//! while let Some(message) = messages.next().await {
+//!     if detector.is_duplicate(&message).await {
+//!         continue;
+//!     }
+//!     handle(message).await;
+//! }
+//! ```
+
+use async_nats::HeaderMap;
+use log::{debug, info};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{NatsClient, NatsError};
+use crate::webhooks::replay::ReplayCache;
+
+pub const MSG_ID_HEADER: &str = "Nats-Msg-Id";
+
+/// JetStream's default duplicate window.
+pub const DEFAULT_DUPLICATE_WINDOW: Duration = Duration::from_secs(120);
+
+/// An event with a stable identity, used as its `Nats-Msg-Id`.
+pub trait Identified {
+    fn message_id(&self) -> String;
+}
+
+impl Identified for Uuid {
+    fn message_id(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// `headers` with `Nats-Msg-Id` set to `id`.
+pub fn with_message_id(mut headers: HeaderMap, id: &str) -> HeaderMap {
+    headers.insert(MSG_ID_HEADER, id);
+    headers
+}
+
+/// The `Nats-Msg-Id` of a received message, if it has one.
+pub fn message_id(message: &async_nats::Message) -> Option<String> {
+    message.headers.as_ref().and_then(|h| h.get(MSG_ID_HEADER)).map(|v| v.as_str().to_string())
+}
+
+/// Make `stream` drop duplicate message IDs seen within `window`.
+pub async fn ensure_duplicate_window(stream: &str, window: Duration) -> Result<(), NatsError> {
+    let context = NatsClient::jetstream()?;
+    let mut info = context
+        .get_stream(stream)
+        .await
+        .map_err(|e| NatsError::ConnectionError(e.to_string()))?
+        .cached_info()
+        .clone();
+
+    if info.config.duplicate_window != window {
+        info.config.duplicate_window = window;
+        context.update_stream(&info.config).await.map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        info!("🔁 Stream '{}' duplicate window set to {:?}", stream, window);
+    }
+    Ok(())
+}
+
+/// Consumer-side detection of redelivered or republished messages, by `Nats-Msg-Id`.
+///
+/// Exports `nats_consumed_messages_total{consumer, outcome}` with outcome `unique`,
+/// `duplicate` or `unidentified` (no message ID; never treated as a duplicate).
+#[derive(Clone)]
+pub struct DuplicateDetector {
+    consumer: Arc<str>,
+    seen: Arc<dyn ReplayCache>,
+    window: Duration,
+    consumed: Counter<u64>,
+}
+
+impl DuplicateDetector {
+    /// Remembers IDs for `DEFAULT_DUPLICATE_WINDOW`. Use a shared cache (e.g.
+    /// `RedisReplayCache`) when replicas share a queue group or durable consumer.
+    pub fn new(consumer: &str, seen: Arc<dyn ReplayCache>) -> Self {
+        let consumed = global::meter("lanai.messaging")
+            .u64_counter("nats_consumed_messages_total")
+            .with_description("Messages checked for duplicates, by outcome")
+            .build();
+        Self { consumer: Arc::from(consumer), seen, window: DEFAULT_DUPLICATE_WINDOW, consumed }
+    }
+
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// True if a message with the same ID was already seen by this consumer.
+    pub async fn is_duplicate(&self, message: &async_nats::Message) -> bool {
+        self.check(message_id(message).as_deref(), message.subject.as_str()).await
+    }
+
+    async fn check(&self, id: Option<&str>, subject: &str) -> bool {
+        let outcome = match id {
+            None => "unidentified",
+            Some(id) if self.seen.first_seen(&format!("nats:{}:{}", self.consumer, id), self.window).await => "unique",
+            Some(id) => {
+                debug!("🔁 Consumer '{}' skipping duplicate message {} on '{}'", self.consumer, id, subject);
+                "duplicate"
+            }
+        };
+        self.consumed.add(1, &[KeyValue::new("consumer", self.consumer.to_string()), KeyValue::new("outcome", outcome)]);
+        outcome == "duplicate"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::replay::InMemoryReplayCache;
+
+    #[tokio::test]
+    async fn test_duplicates_are_detected_per_consumer() {
+        let cache: Arc<dyn ReplayCache> = Arc::new(InMemoryReplayCache::new());
+        let billing = DuplicateDetector::new("billing", cache.clone());
+        let shipping = DuplicateDetector::new("shipping", cache);
+
+        assert!(!billing.check(Some("evt-1"), "lanai.orders.created").await);
+        assert!(billing.check(Some("evt-1"), "lanai.orders.created").await);
+        assert!(!shipping.check(Some("evt-1"), "lanai.orders.created").await);
+        assert!(!billing.check(None, "lanai.orders.created").await);
+        assert!(!billing.check(None, "lanai.orders.created").await);
+    }
+
+    #[test]
+    fn test_message_id_header() {
+        let headers = with_message_id(HeaderMap::new(), "evt-1");
+        assert_eq!(headers.get(MSG_ID_HEADER).unwrap().as_str(), "evt-1");
+    }
+}
@@ -0,0 +1,268 @@
+//! Dead-letter queues for durable consumers
+//!
+//! A `DurableConsumer` given a `DeadLetterQueue` stops redelivering a message that failed
+//! `max_deliveries` times: `DurableMessage::fail` moves it to the queue's subject, stored in
+//! its own stream, with headers saying where it came from and why it failed. Payloads that do
+//! not deserialize are moved on their first delivery.
+//!
+//! ```ignore
+//! let dead_letters = DeadLetterQueue::new("ORDERS_DLQ", "lanai.dlq.billing").max_deliveries(5);
+//! dead_letters.ensure_stream().await?;
+//!
+//! let billing = DurableConsumer::<OrderCreatedEvent>::bind("ORDERS", "billing", "lanai.orders.created.>")
+//!     .await?
+//!     .with_dead_letters(dead_letters.clone());
+//! // ...
+//! match bill(&message.event).await {
+//!     Ok(()) => message.ack().await?,
+//!     Err(e) => message.fail(&e).await?,
+//! }
+//!
+//! // Once the bug is fixed:
+//! for dead in dead_letters.list(100).await? {
+//!     dead_letters.replay(dead.sequence).await?;
+//! }
+//! ```
+//!
+//! A consumer with a retry policy (`DurableConsumer::with_retry`) dead-letters a message once
+//! the policy stops retrying it, instead of after `max_deliveries`.
+//!
+//! Replayed messages go back to their original subject without the dead-letter headers,
+//! marked like re-published ones (see `republish`), and leave the queue.
+
+use async_nats::jetstream::{consumer::pull, consumer::AckPolicy, consumer::DeliverPolicy, stream};
+use async_nats::HeaderMap;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{info, warn};
+use std::time::Duration;
+
+use super::dedup::MSG_ID_HEADER;
+use super::jetstream::ensure_stream;
+use super::republish::{REPLAYED_HEADER, REPLAY_OF_HEADER};
+use super::{NatsClient, NatsError};
+
+/// Subject the message was first published on.
+pub const ORIGINAL_SUBJECT_HEADER: &str = "Lanai-Dead-Letter-Subject";
+/// Durable consumer that gave up on the message.
+pub const CONSUMER_HEADER: &str = "Lanai-Dead-Letter-Consumer";
+/// The last processing error.
+pub const ERROR_HEADER: &str = "Lanai-Dead-Letter-Error";
+/// Deliveries before the message was dead-lettered.
+pub const DELIVERIES_HEADER: &str = "Lanai-Dead-Letter-Deliveries";
+
+const DEAD_LETTER_HEADERS: &[&str] = &[ORIGINAL_SUBJECT_HEADER, CONSUMER_HEADER, ERROR_HEADER, DELIVERIES_HEADER];
+
+fn jetstream_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::JetStreamError(e.to_string())
+}
+
+/// Where a consumer's failed messages go: `subject`, held by the JetStream `stream`.
+#[derive(Debug, Clone)]
+pub struct DeadLetterQueue {
+    stream: String,
+    subject: String,
+    pub(crate) max_deliveries: u64,
+    pub(crate) retry_delay: Duration,
+}
+
+/// A message in a dead-letter queue; `sequence` identifies it for `replay` and `discard`.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub sequence: u64,
+    /// Subject the message was first published on.
+    pub subject: String,
+    pub consumer: Option<String>,
+    pub error: Option<String>,
+    pub deliveries: Option<u64>,
+    pub dead_at: DateTime<Utc>,
+    pub payload: Bytes,
+    pub headers: Option<HeaderMap>,
+}
+
+impl DeadLetterQueue {
+    /// Dead-letter after 5 deliveries; earlier failures are redelivered after 30 seconds.
+    pub fn new(stream: &str, subject: &str) -> Self {
+        Self { stream: stream.to_string(), subject: subject.to_string(), max_deliveries: 5, retry_delay: Duration::from_secs(30) }
+    }
+
+    pub fn max_deliveries(mut self, max_deliveries: u64) -> Self {
+        self.max_deliveries = max_deliveries.max(1);
+        self
+    }
+
+    /// How long a failed message waits before it is delivered again.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Create the queue's stream unless it exists.
+    pub async fn ensure_stream(&self) -> Result<stream::Stream, NatsError> {
+        ensure_stream(stream::Config {
+            name: self.stream.clone(),
+            subjects: vec![self.subject.clone()],
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Store `message` in the queue and wait for the stream to acknowledge it.
+    pub(crate) async fn send(
+        &self,
+        consumer: &str,
+        message: &async_nats::Message,
+        deliveries: u64,
+        error: &str,
+    ) -> Result<(), NatsError> {
+        let mut headers = message.headers.clone().unwrap_or_default();
+        headers.insert(ORIGINAL_SUBJECT_HEADER, message.subject.as_str());
+        headers.insert(CONSUMER_HEADER, consumer);
+        headers.insert(ERROR_HEADER, error.replace(['\r', '\n'], " ").as_str());
+        headers.insert(DELIVERIES_HEADER, deliveries.to_string().as_str());
+
+        NatsClient::jetstream()?
+            .publish_with_headers(self.subject.clone(), headers, message.payload.clone())
+            .await
+            .map_err(|e| NatsError::PublishError(e.to_string()))?
+            .await
+            .map_err(|e| NatsError::PublishError(e.to_string()))?;
+        warn!("☠️ '{}' dead-lettered a message from '{}' after {} deliveries: {}", consumer, message.subject, deliveries, error);
+        Ok(())
+    }
+
+    /// The `limit` oldest dead letters.
+    pub async fn list(&self, limit: usize) -> Result<Vec<DeadLetter>, NatsError> {
+        let stream = NatsClient::jetstream()?.get_stream(&self.stream).await.map_err(jetstream_error)?;
+        let mut consumer = stream
+            .create_consumer(pull::Config {
+                deliver_policy: DeliverPolicy::All,
+                filter_subject: self.subject.clone(),
+                ack_policy: AckPolicy::None,
+                inactive_threshold: Duration::from_secs(60),
+                ..Default::default()
+            })
+            .await
+            .map_err(jetstream_error)?;
+
+        let pending = consumer.info().await.map_err(jetstream_error)?.num_pending as usize;
+        let mut messages = consumer.messages().await.map_err(jetstream_error)?.take(pending.min(limit));
+        let mut dead_letters = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.map_err(jetstream_error)?;
+            let info = message.info().map_err(jetstream_error)?;
+            let (sequence, published) = (info.stream_sequence, info.published);
+            dead_letters.push(DeadLetter::from_message(sequence, published, message.message));
+        }
+        Ok(dead_letters)
+    }
+
+    /// Publish dead letter `sequence` again on its original subject and remove it from the
+    /// queue. Returns false if there is no such dead letter.
+    pub async fn replay(&self, sequence: u64) -> Result<bool, NatsError> {
+        let stream = NatsClient::jetstream()?.get_stream(&self.stream).await.map_err(jetstream_error)?;
+        let Ok(raw) = stream.get_raw_message(sequence).await else {
+            return Ok(false);
+        };
+        let published = raw.time;
+        let message = async_nats::Message::try_from(raw).map_err(jetstream_error)?;
+        let dead = DeadLetter::from_message(sequence, published, message);
+
+        NatsClient::publish_with_headers(&dead.subject, dead.replay_headers(), dead.payload.clone()).await?;
+        stream.delete_message(sequence).await.map_err(jetstream_error)?;
+        info!("🔁 Replayed dead letter {} of '{}' on '{}'", sequence, self.stream, dead.subject);
+        Ok(true)
+    }
+
+    /// Remove dead letter `sequence` for good. Returns false if there is no such dead letter.
+    pub async fn discard(&self, sequence: u64) -> Result<bool, NatsError> {
+        let stream = NatsClient::jetstream()?.get_stream(&self.stream).await.map_err(jetstream_error)?;
+        Ok(stream.delete_message(sequence).await.is_ok())
+    }
+}
+
+impl DeadLetter {
+    fn from_message(sequence: u64, published: time::OffsetDateTime, message: async_nats::Message) -> Self {
+        let header = |name: &str| message.headers.as_ref().and_then(|h| h.get(name)).map(|v| v.as_str().to_string());
+        Self {
+            sequence,
+            subject: header(ORIGINAL_SUBJECT_HEADER).unwrap_or_else(|| message.subject.to_string()),
+            consumer: header(CONSUMER_HEADER),
+            error: header(ERROR_HEADER),
+            deliveries: header(DELIVERIES_HEADER).and_then(|v| v.parse().ok()),
+            dead_at: DateTime::from_timestamp_nanos(published.unix_timestamp_nanos() as i64),
+            payload: message.payload,
+            headers: message.headers,
+        }
+    }
+
+    /// The original headers, with the dead-letter ones replaced by the replay markers.
+    fn replay_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(original) = &self.headers {
+            for (name, values) in original.iter() {
+                let name = name.to_string();
+                if name.eq_ignore_ascii_case(MSG_ID_HEADER) {
+                    if let Some(id) = values.first() {
+                        headers.insert(REPLAY_OF_HEADER, id.as_str());
+                    }
+                    continue;
+                }
+                if DEAD_LETTER_HEADERS.iter().any(|dead| name.eq_ignore_ascii_case(dead)) {
+                    continue;
+                }
+                for value in values {
+                    headers.append(name.as_str(), value.as_str());
+                }
+            }
+        }
+        headers.insert(REPLAYED_HEADER, "true");
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_restores_original_message() {
+        let mut headers = HeaderMap::new();
+        headers.insert(MSG_ID_HEADER, "evt-1");
+        headers.insert("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01");
+        headers.insert(ORIGINAL_SUBJECT_HEADER, "lanai.orders.created.42");
+        headers.insert(CONSUMER_HEADER, "billing");
+        headers.insert(ERROR_HEADER, "customer not found");
+        headers.insert(DELIVERIES_HEADER, "5");
+        let message = async_nats::Message {
+            subject: "lanai.dlq.billing".into(),
+            reply: None,
+            payload: Bytes::from_static(b"{}"),
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        };
+
+        let dead = DeadLetter::from_message(7, time::OffsetDateTime::UNIX_EPOCH, message);
+        assert_eq!(dead.subject, "lanai.orders.created.42");
+        assert_eq!(dead.consumer.as_deref(), Some("billing"));
+        assert_eq!(dead.deliveries, Some(5));
+
+        let replay = dead.replay_headers();
+        assert_eq!(replay.get(REPLAY_OF_HEADER).unwrap().as_str(), "evt-1");
+        assert_eq!(replay.get(REPLAYED_HEADER).unwrap().as_str(), "true");
+        assert!(replay.get("traceparent").is_some());
+        assert!(replay.get(MSG_ID_HEADER).is_none());
+        assert!(replay.get(ERROR_HEADER).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+//! In-process NATS broker for local development
+//!
+//! With `NATS_MODE=embedded`, `NatsClient::init_with_config` starts this broker instead of
+//! connecting to a server. Publishes, subscriptions (with `*`/`>` wildcards), queue groups
+//! and request-reply stay inside the process, so a single service runs with no external
+//! dependencies through the same `NatsClient` calls:
+//!
+//! ```ignore
+//! // NATS_MODE=embedded
+//! NatsClient::init_with_config(NatsConfig::for_service("lanai-inventory-service")).await?;
+//! let mut created = NatsClient::subscribe("lanai.inventory.product.created.*").await?;
+//! NatsClient::publish_event(&event.subject(), &event).await?;
+//! ```
+//!
+//! JetStream (durable consumers, `publish_acked`'s deduplication, replays) needs a real
+//! server; those features report `NatsError::Unsupported` or behave as plain publishes.
+
+use async_nats::{HeaderMap, Message};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use super::republish::subject_matches;
+use super::{NatsError, Subscription};
+
+/// Environment variable selecting the NATS mode (`embedded` or a real server, the default)
+pub const NATS_MODE_ENV: &str = "NATS_MODE";
+
+struct Subscriber {
+    id: u64,
+    pattern: String,
+    queue: Option<String>,
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+#[derive(Default)]
+struct Inner {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+    /// Round-robin position per queue group.
+    cursors: Mutex<HashMap<String, usize>>,
+}
+
+/// Subject-based routing between the subscriptions of this process.
+#[derive(Clone, Default)]
+pub struct EmbeddedBroker {
+    inner: Arc<Inner>,
+}
+
+impl EmbeddedBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every message matching `pattern`.
+    pub fn subscribe(&self, pattern: &str) -> Subscription {
+        self.add(pattern, None)
+    }
+
+    /// Messages matching `pattern`, each delivered to one member of `group`.
+    pub fn queue_subscribe(&self, pattern: &str, group: &str) -> Subscription {
+        self.add(pattern, Some(group.to_string()))
+    }
+
+    fn add(&self, pattern: &str, queue: Option<String>) -> Subscription {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers().push(Subscriber { id, pattern: pattern.to_string(), queue, sender });
+        into_subscription(receiver)
+    }
+
+    fn subscribers(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber>> {
+        self.inner.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Deliver a message; returns how many subscriptions received it.
+    pub fn publish(&self, subject: &str, reply: Option<String>, headers: HeaderMap, payload: Bytes) -> usize {
+        let message = Message {
+            subject: subject.into(),
+            reply: reply.map(Into::into),
+            length: payload.len(),
+            payload,
+            headers: Some(headers),
+            status: None,
+            description: None,
+        };
+
+        let mut subscribers = self.subscribers();
+        subscribers.retain(|s| !s.sender.is_closed());
+
+        let mut groups: HashMap<&str, Vec<&Subscriber>> = HashMap::new();
+        let mut delivered = 0;
+        for subscriber in subscribers.iter().filter(|s| subject_matches(&s.pattern, subject)) {
+            match &subscriber.queue {
+                Some(group) => groups.entry(group.as_str()).or_default().push(subscriber),
+                None => delivered += subscriber.sender.send(message.clone()).is_ok() as usize,
+            }
+        }
+
+        let mut cursors = self.inner.cursors.lock().unwrap_or_else(|e| e.into_inner());
+        for (group, mut members) in groups {
+            members.sort_by_key(|s| s.id);
+            let cursor = cursors.entry(group.to_string()).or_default();
+            let member = members[*cursor % members.len()];
+            *cursor = cursor.wrapping_add(1);
+            delivered += member.sender.send(message.clone()).is_ok() as usize;
+        }
+        delivered
+    }
+
+    /// Publish with a reply inbox and wait for the first reply.
+    pub async fn request(&self, subject: &str, headers: HeaderMap, payload: Bytes) -> Result<Message, NatsError> {
+        let inbox = format!("_INBOX.{}", uuid::Uuid::new_v4().simple());
+        let mut replies = self.subscribe(&inbox);
+        if self.publish(subject, Some(inbox), headers, payload) == 0 {
+            return Err(NatsError::RequestError(format!("no responders on '{}'", subject)));
+        }
+        replies.next().await.ok_or_else(|| NatsError::RequestError("reply inbox closed".to_string()))
+    }
+}
+
+fn into_subscription(mut receiver: mpsc::UnboundedReceiver<Message>) -> Subscription {
+    futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx)).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wildcards_and_queue_groups() {
+        let broker = EmbeddedBroker::new();
+        let mut all = broker.subscribe("lanai.inventory.>");
+        let mut first = broker.queue_subscribe("lanai.inventory.product.*", "workers");
+        let mut second = broker.queue_subscribe("lanai.inventory.product.*", "workers");
+
+        for n in 0..2 {
+            let delivered = broker.publish("lanai.inventory.product.created", None, HeaderMap::new(), format!("{n}").into());
+            assert_eq!(delivered, 2);
+        }
+        assert_eq!(broker.publish("lanai.sales.order.created", None, HeaderMap::new(), Bytes::new()), 0);
+
+        assert_eq!(all.next().await.unwrap().payload, "0");
+        assert_eq!(all.next().await.unwrap().payload, "1");
+        assert_eq!(first.next().await.unwrap().payload, "0");
+        assert_eq!(second.next().await.unwrap().payload, "1");
+    }
+
+    #[tokio::test]
+    async fn test_request_reply() {
+        let broker = EmbeddedBroker::new();
+        let mut requests = broker.subscribe("lanai.echo");
+        let responder = broker.clone();
+        tokio::spawn(async move {
+            let request = requests.next().await.unwrap();
+            responder.publish(&request.reply.unwrap(), None, HeaderMap::new(), request.payload);
+        });
+
+        let reply = broker.request("lanai.echo", HeaderMap::new(), "hola".into()).await.unwrap();
+        assert_eq!(reply.payload, "hola");
+        assert!(broker.request("lanai.nobody", HeaderMap::new(), Bytes::new()).await.is_err());
+    }
+}
@@ -1,73 +1,363 @@
-use serde::{Deserialize, Serialize};
+//! Event types shared between services
+//!
+//! Events derive `LanaiEvent` instead of writing `subject()` by hand, so subjects follow
+//! `lanai.{domain}.{entity}.{action}.{org_id}` and carry a version and schema:
+//!
+//! ```ignore
+//! #[derive(Debug, Serialize, Deserialize, LanaiEvent)]
+//! #[event(domain = "inventory", entity = "product", action = "created", version = 2)]
+//! pub struct ProductCreatedEvent { ... }
+//!
+//! NatsClient::publish_envelope(&event.into_envelope()).await?;
+//! let schemas = export_schemas([ProductCreatedEvent::event_schema()]);
+//! ```
+//!
+//! Events published in reaction to another carry its ID as `causation_id` and share its
+//! `correlation_id`, so every event of one business flow can be found together:
+//!
+//! ```ignore
+//! let reserved = StockReservedEvent { .. }.into_envelope().caused_by(&order_created);
+//! ```
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 use rust_decimal::Decimal;
 
-/// Base trait for all Lanai events
+use crate::common::{OrderId, OrgId, ProductId, Timestamp};
+use super::dedup::Identified;
+use super::subject::{EventSubject, SubjectError};
+
+pub use lanai_infrastructure_derive::LanaiEvent;
+
+/// Header carrying an envelope's correlation ID, read by saga choreographies.
+pub const CORRELATION_ID_HEADER: &str = "Lanai-Correlation-Id";
+
 /// Base trait for all Lanai events
 pub trait LanaiEvent {
     fn subject(&self) -> String;
+
+    /// Payload schema version, bumped on breaking changes
+    fn version(&self) -> u32 {
+        1
+    }
+
+    /// Name of the event type in envelopes and schemas
+    fn event_type(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Organization the event belongs to
+    fn org_id(&self) -> Option<Uuid> {
+        None
+    }
+
+    /// `subject`, parsed into its domain, entity, action and organization
+    fn event_subject(&self) -> Result<EventSubject, SubjectError> {
+        EventSubject::parse(&self.subject())
+    }
+
+    /// Wrap in an envelope with a new event ID
+    fn into_envelope(self) -> EventEnvelope<Self>
+    where
+        Self: Sized,
+    {
+        EventEnvelope::wrap(self)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ProductCreatedEvent {
-    pub product_id: Uuid,
-    pub org_id: Uuid,
-    pub name: String,
-    pub description: Option<String>,
+/// An event with its metadata, as published
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope<T> {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub version: u32,
+    pub occurred_at: Timestamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<Uuid>,
+    /// Shared by every event of one flow; the first event's own ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<Uuid>,
+    /// ID of the event this one was published in reaction to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<Uuid>,
+    pub data: T,
+}
+
+impl<T: LanaiEvent> EventEnvelope<T> {
+    pub fn wrap(event: T) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            event_type: event.event_type().to_string(),
+            version: event.version(),
+            occurred_at: Timestamp::now(),
+            org_id: event.org_id(),
+            correlation_id: None,
+            causation_id: None,
+            data: event,
+        }
+    }
+}
+
+impl<T> EventEnvelope<T> {
+    /// Wrap a payload that is not a `LanaiEvent`: its type name as `event_type`, version 1
+    /// and no organization.
+    pub fn new(data: T) -> Self {
+        let name = std::any::type_name::<T>();
+        let name = name.split('<').next().unwrap_or(name);
+        Self {
+            event_id: Uuid::new_v4(),
+            event_type: name.rsplit("::").next().unwrap_or(name).trim_start_matches('&').to_string(),
+            version: 1,
+            occurred_at: Timestamp::now(),
+            org_id: None,
+            correlation_id: None,
+            causation_id: None,
+            data,
+        }
+    }
+
+    /// The same envelope around `f(data)`.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> EventEnvelope<U> {
+        EventEnvelope {
+            event_id: self.event_id,
+            event_type: self.event_type,
+            version: self.version,
+            occurred_at: self.occurred_at,
+            org_id: self.org_id,
+            correlation_id: self.correlation_id,
+            causation_id: self.causation_id,
+            data: f(self.data),
+        }
+    }
+
+    /// Mark this event as published in reaction to `cause`, continuing its correlation.
+    pub fn caused_by<C>(mut self, cause: &EventEnvelope<C>) -> Self {
+        self.causation_id = Some(cause.event_id);
+        self.correlation_id = Some(cause.correlation());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: Uuid) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
+    /// The flow this event belongs to; an event that starts one correlates with itself.
+    pub fn correlation(&self) -> Uuid {
+        self.correlation_id.unwrap_or(self.event_id)
+    }
+}
+
+/// Decode a payload published either enveloped or bare.
+pub fn decode_event<T: DeserializeOwned>(payload: &[u8]) -> Result<T, serde_json::Error> {
+    serde_json::from_slice::<EventEnvelope<T>>(payload)
+        .map(|envelope| envelope.data)
+        .or_else(|_| serde_json::from_slice::<T>(payload))
+}
+
+/// The envelope's event ID is the NATS message ID, so republishing it is deduplicated.
+impl<T> Identified for EventEnvelope<T> {
+    fn message_id(&self) -> String {
+        self.event_id.to_string()
+    }
+}
+
+/// Description of an event type, from `#[derive(LanaiEvent)]`
+pub trait DescribeEvent {
+    fn event_schema() -> EventSchema;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventSchema {
+    pub event_type: &'static str,
+    /// Subject template, e.g. `lanai.inventory.product.created.{org_id}`
+    pub subject: &'static str,
+    /// Subject with placeholders as wildcards, for subscribing
+    pub subject_pattern: &'static str,
+    pub version: u32,
+    pub fields: Vec<EventField>,
 }
 
-impl LanaiEvent for ProductCreatedEvent {
-    fn subject(&self) -> String {
-        format!("lanai.inventory.product.created.{}", self.org_id)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventField {
+    pub name: &'static str,
+    /// Rust type as written in the struct
+    pub ty: &'static str,
+}
+
+/// Schemas keyed by event type, for publishing to other teams.
+pub fn export_schemas(schemas: impl IntoIterator<Item = EventSchema>) -> serde_json::Value {
+    let map = schemas
+        .into_iter()
+        .map(|schema| (schema.event_type.to_string(), serde_json::to_value(&schema).unwrap_or_default()))
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use uuid::Uuid;
+
+    /// Types a derived event's `org_id` field can have.
+    pub trait OrgIdField {
+        fn org_uuid(&self) -> Option<Uuid>;
+    }
+
+    impl OrgIdField for Uuid {
+        fn org_uuid(&self) -> Option<Uuid> {
+            Some(*self)
+        }
+    }
+
+    impl OrgIdField for crate::common::OrgId {
+        fn org_uuid(&self) -> Option<Uuid> {
+            Some(self.into_uuid())
+        }
+    }
+
+    impl OrgIdField for String {
+        fn org_uuid(&self) -> Option<Uuid> {
+            Uuid::parse_str(self).ok()
+        }
+    }
+
+    impl<T: OrgIdField> OrgIdField for Option<T> {
+        fn org_uuid(&self) -> Option<Uuid> {
+            self.as_ref().and_then(OrgIdField::org_uuid)
+        }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, LanaiEvent)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
+#[event(domain = "inventory", entity = "product", action = "created")]
+pub struct ProductCreatedEvent {
+    pub product_id: ProductId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
 pub struct StockItem {
-    pub product_id: Uuid,
+    pub product_id: ProductId,
     /// Quantity supports fractional values (kg, L) for Restaurant/Agro verticals
     pub quantity: Decimal,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
 pub struct ReserveStockRequest {
-    pub order_id: Uuid,
-    pub org_id: Uuid,
+    pub order_id: OrderId,
+    pub org_id: OrgId,
     pub items: Vec<StockItem>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
 pub struct ReserveStockResponse {
-    pub order_id: Uuid,
+    pub order_id: OrderId,
     pub success: bool,
     pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
 pub struct ReleaseStockRequest {
-    pub order_id: Uuid,
-    pub org_id: Uuid,
+    pub order_id: OrderId,
+    pub org_id: OrgId,
     pub items: Vec<StockItem>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, LanaiEvent)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
+#[event(domain = "sales", entity = "return", action = "completed")]
 pub struct ReturnCompletedEvent {
     pub return_id: Uuid,
-    pub order_id: Uuid,
-    pub org_id: Uuid,
+    pub order_id: OrderId,
+    pub org_id: OrgId,
     pub items: Vec<ReturnItemEvent>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
 pub struct ReturnItemEvent {
-    pub product_id: Uuid,
+    pub product_id: ProductId,
     /// Quantity supports fractional values (kg, L) for Restaurant/Agro verticals
     pub quantity: Decimal,
     pub inventory_action: String, // RESTOCK, QUARANTINE, DISPOSE
 }
 
-impl LanaiEvent for ReturnCompletedEvent {
-    fn subject(&self) -> String {
-        format!("lanai.sales.return.completed.{}", self.org_id)
+/// A new organization was provisioned in the account service.
+#[derive(Debug, Serialize, Deserialize, Clone, LanaiEvent)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
+#[event(domain = "tenancy", entity = "organization", action = "created")]
+pub struct OrganizationCreatedEvent {
+    pub org_id: OrgId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertical: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// Default locale (`es`, `pt`, `en`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    pub created_at: Timestamp,
+}
+
+/// The organization lost access (e.g. unpaid); its data is kept.
+#[derive(Debug, Serialize, Deserialize, Clone, LanaiEvent)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
+#[event(domain = "tenancy", entity = "organization", action = "suspended")]
+pub struct OrganizationSuspendedEvent {
+    pub org_id: OrgId,
+    pub reason: String,
+    pub suspended_at: Timestamp,
+}
+
+/// The organization was closed; services delete or anonymize its data.
+#[derive(Debug, Serialize, Deserialize, Clone, LanaiEvent)]
+#[cfg_attr(feature = "contracts", derive(schemars::JsonSchema))]
+#[event(domain = "tenancy", entity = "organization", action = "deleted")]
+pub struct OrganizationDeletedEvent {
+    pub org_id: OrgId,
+    pub deleted_at: Timestamp,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_event() {
+        let org_id = OrgId::generate();
+        let event = ProductCreatedEvent {
+            product_id: ProductId::generate(),
+            org_id,
+            name: "Coffee".to_string(),
+            description: None,
+        };
+        assert_eq!(event.subject(), format!("lanai.inventory.product.created.{}", org_id));
+        assert_eq!(event.event_subject().unwrap().org_id(), Some(org_id.to_string().as_str()));
+
+        let schema = ProductCreatedEvent::event_schema();
+        assert_eq!(schema.subject_pattern, "lanai.inventory.product.created.*");
+        assert_eq!(schema.fields[0], EventField { name: "product_id", ty: "ProductId" });
+
+        let envelope = event.into_envelope();
+        assert_eq!(envelope.event_type, "ProductCreatedEvent");
+        assert_eq!(envelope.org_id, Some(org_id.into_uuid()));
+        assert_eq!(envelope.message_id(), envelope.event_id.to_string());
+
+        let reaction = event_after(&envelope);
+        assert_eq!(reaction.causation_id, Some(envelope.event_id));
+        assert_eq!(reaction.correlation(), envelope.event_id);
+        assert_eq!(event_after(&reaction).correlation(), envelope.event_id);
+    }
+
+    fn event_after<C>(cause: &EventEnvelope<C>) -> EventEnvelope<OrganizationDeletedEvent> {
+        OrganizationDeletedEvent { org_id: OrgId::generate(), deleted_at: Timestamp::now() }.into_envelope().caused_by(cause)
     }
 }
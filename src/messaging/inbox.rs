@@ -0,0 +1,184 @@
+//! Idempotent consumers
+//!
+//! Delivery is at least once: a redelivered or re-published event must not reserve stock
+//! twice. An `Inbox` records the `Nats-Msg-Id` of every event a consumer has processed in an
+//! `IdempotencyStore` (`RedisIdempotencyStore` or `PostgresIdempotencyStore`, shared by the
+//! consumer's replicas) and skips the ones it has already seen:
+//!
+//! ```ignore
+//! let inbox = Inbox::new("inventory-reservations", Arc::new(PostgresIdempotencyStore::new(pool)));
+//!
+//! QueueSubscriber::new("lanai.orders.created.>", "inventory", inbox.wrap(|received: TypedMessage<OrderCreatedEvent>| async move {
+//!     reserve_stock(&received.event).await
+//! }))
+//! .start()
+//! .await?;
+//! ```
+//!
+//! An event is recorded once its handler succeeds; a failed handler leaves it unrecorded so
+//! a redelivery runs it again. While one replica handles an event, a copy reaching another is
+//! reported as `InboxOutcome::InProgress` and not handled (`DurableMessage` users should `nak`
+//! it). Events without a message ID are always handled. Outcomes are counted in
+//! `nats_consumed_messages_total{consumer, outcome}`, like `DuplicateDetector`'s.
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use log::debug;
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::dedup::message_id;
+use super::TypedMessage;
+use crate::idempotency::{Claim, IdempotencyStore, RunError};
+
+/// What `Inbox::process` did with an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboxOutcome {
+    /// The handler ran and succeeded (or the event had no ID to record).
+    Processed,
+    /// The event was already processed; the handler did not run.
+    Duplicate,
+    /// Another delivery of the event is being handled; the handler did not run.
+    InProgress,
+}
+
+/// Records the events a consumer has processed; clones share the store.
+#[derive(Clone)]
+pub struct Inbox {
+    consumer: Arc<str>,
+    store: Arc<dyn IdempotencyStore>,
+    lock_ttl: Duration,
+    retention: Duration,
+    consumed: Counter<u64>,
+}
+
+impl Inbox {
+    /// Event IDs are kept for 24h; a delivery being handled blocks copies for up to 60s.
+    pub fn new(consumer: &str, store: Arc<dyn IdempotencyStore>) -> Self {
+        let consumed = global::meter("lanai.messaging")
+            .u64_counter("nats_consumed_messages_total")
+            .with_description("Messages checked for duplicates, by outcome")
+            .build();
+        Self {
+            consumer: Arc::from(consumer),
+            store,
+            lock_ttl: Duration::from_secs(60),
+            retention: Duration::from_secs(24 * 3600),
+            consumed,
+        }
+    }
+
+    /// How long a delivery being handled blocks copies if its handler never finishes.
+    pub fn with_lock_ttl(mut self, lock_ttl: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self
+    }
+
+    /// How long processed event IDs are remembered; at least the longest redelivery or
+    /// replay window.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Run `handler` for the event identified by `id`, unless it was already processed.
+    pub async fn process<F, Fut, E>(&self, id: Option<&str>, handler: F) -> Result<InboxOutcome, RunError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        let Some(id) = id else {
+            self.record("unidentified");
+            handler().await.map_err(RunError::Operation)?;
+            return Ok(InboxOutcome::Processed);
+        };
+
+        let key = format!("inbox:{}:{}", self.consumer, id);
+        match self.store.claim(&key, self.lock_ttl).await? {
+            Claim::Completed(_) => {
+                debug!("🔁 Consumer '{}' skipping already processed event {}", self.consumer, id);
+                self.record("duplicate");
+                return Ok(InboxOutcome::Duplicate);
+            }
+            Claim::InProgress => {
+                debug!("🔁 Consumer '{}' skipping event {}, already being handled", self.consumer, id);
+                self.record("in_progress");
+                return Ok(InboxOutcome::InProgress);
+            }
+            Claim::Acquired => self.record("unique"),
+        }
+
+        match handler().await {
+            Ok(()) => {
+                self.store.complete(&key, &serde_json::Value::Null, self.retention).await?;
+                Ok(InboxOutcome::Processed)
+            }
+            Err(e) => {
+                self.store.remove(&key).await?;
+                Err(RunError::Operation(e))
+            }
+        }
+    }
+
+    /// `process` for a received message, identified by its `Nats-Msg-Id`.
+    pub async fn handle<T, F, Fut, E>(&self, message: TypedMessage<T>, handler: F) -> Result<InboxOutcome, RunError<E>>
+    where
+        F: FnOnce(TypedMessage<T>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        let id = message_id(&message.message);
+        self.process(id.as_deref(), || handler(message)).await
+    }
+
+    /// `handler` behind this inbox, as a `QueueSubscriber` handler.
+    pub fn wrap<T, F, Fut, E>(
+        self,
+        handler: F,
+    ) -> impl Fn(TypedMessage<T>) -> BoxFuture<'static, Result<(), RunError<E>>> + Send + Sync + 'static
+    where
+        T: Send + 'static,
+        F: Fn(TypedMessage<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        move |message| {
+            let (inbox, handler) = (self.clone(), handler.clone());
+            async move { inbox.handle(message, |message| handler(message)).await.map(|_| ()) }.boxed()
+        }
+    }
+
+    fn record(&self, outcome: &'static str) {
+        self.consumed.add(1, &[KeyValue::new("consumer", self.consumer.to_string()), KeyValue::new("outcome", outcome)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idempotency::InMemoryIdempotencyStore;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_events_are_processed_once() {
+        let inbox = Inbox::new("inventory", Arc::new(InMemoryIdempotencyStore::new()));
+        let reservations = AtomicU32::new(0);
+        let count = &reservations;
+        let reserve = move || async move {
+            count.fetch_add(1, Ordering::SeqCst);
+            Ok::<(), &str>(())
+        };
+
+        assert_eq!(inbox.process(Some("evt-1"), reserve).await.unwrap(), InboxOutcome::Processed);
+        assert_eq!(inbox.process(Some("evt-1"), reserve).await.unwrap(), InboxOutcome::Duplicate);
+        assert_eq!(inbox.process(None, reserve).await.unwrap(), InboxOutcome::Processed);
+        assert_eq!(reservations.load(Ordering::SeqCst), 2);
+
+        let failed = inbox.process(Some("evt-2"), || async { Err("out of stock") }).await;
+        assert!(matches!(failed, Err(RunError::Operation("out of stock"))));
+        assert_eq!(inbox.process(Some("evt-2"), reserve).await.unwrap(), InboxOutcome::Processed);
+    }
+}
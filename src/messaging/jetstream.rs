@@ -0,0 +1,322 @@
+//! JetStream streams and durable consumers
+//!
+//! Streams are declared at startup with `ensure_stream`, events are written with
+//! `NatsClient::publish_durable` (which waits for the stream's acknowledgement) and read by a
+//! `DurableConsumer`, a pull consumer whose messages are settled explicitly:
+//!
+//! ```ignore
+//! ensure_stream(stream::Config {
+//!     name: "ORDERS".to_string(),
+//!     subjects: vec!["lanai.orders.>".to_string()],
+//!     ..Default::default()
+//! })
+//! .await?;
+//! NatsClient::publish_durable("lanai.orders.created.42", &event).await?;
+//!
+//! let billing = DurableConsumer::<OrderCreatedEvent>::bind("ORDERS", "billing", "lanai.orders.created.>").await?;
+//! let mut messages = billing.messages().await?;
+//! while let Some(message) = messages.next().await {
+//!     let message = message?;
+//!     match bill(&message.event).instrument(message.span.clone()).await {
+//!         Ok(()) => message.ack().await?,
+//!         Err(BillingError::UnknownCustomer) => message.term().await?,
+//!         Err(_) => message.nak(Some(Duration::from_secs(30))).await?,
+//!     }
+//! }
+//! ```
+//!
+//! A message left unsettled is redelivered once the consumer's ack wait runs out. Payloads
+//! that do not deserialize are logged and terminated, since redelivering them cannot help,
+//! or moved to the consumer's dead-letter queue if it has one (see `dlq`).
+//!
+//! `DurableMessage::fail` leaves the redelivery schedule to the consumer. With `with_retry`,
+//! a failed message is redelivered after the policy's backoff (a nak with delay) while the
+//! policy retries its error, so a database blip costs a few seconds instead of a dead letter:
+//!
+//! ```ignore
+//! let billing = DurableConsumer::<OrderCreatedEvent>::bind("ORDERS", "billing", "lanai.orders.created.>")
+//!     .await?
+//!     .with_retry(
+//!         RetryPolicy::exponential(6, Duration::from_secs(1))
+//!             .with_max_backoff(Duration::from_secs(60))
+//!             .retry_if(|e: &String| !e.contains("unknown customer")),
+//!     )
+//!     .with_dead_letters(dead_letters);
+//! ```
+//!
+//! The policy's attempts count deliveries, including redeliveries after the ack wait; keep
+//! them within the consumer's `max_deliver`.
+//! JetStream needs a real server: in embedded mode these fail with `Unsupported`.
+
+use async_nats::jetstream::consumer::{pull, AckPolicy, Consumer};
+use async_nats::jetstream::{self, stream, AckKind};
+use futures_util::stream::{BoxStream, StreamExt};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::dlq::DeadLetterQueue;
+use super::{consume, NatsClient, NatsError};
+use crate::resilience::RetryPolicy;
+
+/// Messages of a `DurableConsumer`; consumer errors come through as `JetStreamError`.
+pub type DurableMessages<T> = BoxStream<'static, Result<DurableMessage<T>, NatsError>>;
+
+fn jetstream_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::JetStreamError(e.to_string())
+}
+
+/// Create the stream described by `config` unless it exists; an existing stream is
+/// returned as it is, without updating its configuration.
+pub async fn ensure_stream(config: stream::Config) -> Result<stream::Stream, NatsError> {
+    let name = config.name.clone();
+    let stream = NatsClient::jetstream()?.get_or_create_stream(config).await.map_err(jetstream_error)?;
+    info!("🗄️ JetStream stream '{}' ready", name);
+    Ok(stream)
+}
+
+/// Durable pull consumer deserializing each message as JSON into `T`.
+pub struct DurableConsumer<T> {
+    consumer: Consumer<pull::Config>,
+    failures: Arc<Failures>,
+    _event: PhantomData<fn() -> T>,
+}
+
+/// What a consumer does with failed messages, shared with its messages.
+#[derive(Clone)]
+struct Failures {
+    consumer: String,
+    dead_letters: Option<DeadLetterQueue>,
+    retry: Option<RetryPolicy<String>>,
+}
+
+impl<T: DeserializeOwned + Send + 'static> DurableConsumer<T> {
+    /// The durable consumer `durable` of `stream` for `filter_subject`, with explicit acks;
+    /// created on first use. Replicas binding the same name share its messages.
+    pub async fn bind(stream: &str, durable: &str, filter_subject: &str) -> Result<Self, NatsError> {
+        Self::bind_with(
+            stream,
+            pull::Config {
+                durable_name: Some(durable.to_string()),
+                filter_subject: filter_subject.to_string(),
+                ack_policy: AckPolicy::Explicit,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// `bind` with a full consumer configuration (ack wait, max deliveries...); it must
+    /// name a `durable_name`.
+    pub async fn bind_with(stream: &str, config: pull::Config) -> Result<Self, NatsError> {
+        let durable = config
+            .durable_name
+            .clone()
+            .ok_or_else(|| NatsError::JetStreamError("consumer config has no durable_name".to_string()))?;
+        let consumer = NatsClient::jetstream()?
+            .get_stream(stream)
+            .await
+            .map_err(jetstream_error)?
+            .get_or_create_consumer(&durable, config)
+            .await
+            .map_err(jetstream_error)?;
+        info!("📥 Bound to durable consumer '{}' on stream '{}'", durable, stream);
+        let failures = Arc::new(Failures { consumer: durable, dead_letters: None, retry: None });
+        Ok(Self { consumer, failures, _event: PhantomData })
+    }
+
+    /// Move messages that keep failing, and malformed ones, to `queue` (see `DurableMessage::fail`).
+    pub fn with_dead_letters(mut self, queue: DeadLetterQueue) -> Self {
+        self.failures_mut().dead_letters = Some(queue);
+        self
+    }
+
+    /// Redeliver failed messages on `policy`'s backoff schedule while it retries their error
+    /// (see `DurableMessage::fail`).
+    pub fn with_retry(mut self, policy: RetryPolicy<String>) -> Self {
+        self.failures_mut().retry = Some(policy);
+        self
+    }
+
+    fn failures_mut(&mut self) -> &mut Failures {
+        Arc::make_mut(&mut self.failures)
+    }
+
+    /// Messages as they arrive, until the stream is dropped.
+    pub async fn messages(&self) -> Result<DurableMessages<T>, NatsError> {
+        let messages = self.consumer.messages().await.map_err(jetstream_error)?;
+        let failures = self.failures.clone();
+        Ok(messages
+            .filter_map(move |message| {
+                let failures = failures.clone();
+                async move {
+                    match message {
+                        Ok(message) => DurableMessage::decode(message, failures).await.map(Ok),
+                        Err(e) => Some(Err(jetstream_error(e))),
+                    }
+                }
+            })
+            .boxed())
+    }
+
+    /// Up to `max` messages, waiting at most `expires` for them to arrive.
+    pub async fn fetch(&self, max: usize, expires: Duration) -> Result<Vec<DurableMessage<T>>, NatsError> {
+        let mut batch = self
+            .consumer
+            .batch()
+            .max_messages(max)
+            .expires(expires)
+            .messages()
+            .await
+            .map_err(jetstream_error)?;
+        let mut messages = Vec::new();
+        while let Some(message) = batch.next().await {
+            let message = message.map_err(jetstream_error)?;
+            if let Some(message) = DurableMessage::decode(message, self.failures.clone()).await {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+}
+
+/// A deserialized event from a `DurableConsumer`, to be settled with `ack`, `nak`, `term`
+/// or `fail`.
+pub struct DurableMessage<T> {
+    pub event: T,
+    pub message: jetstream::Message,
+    /// `nats_consume` span continuing the publisher's trace, as on `TypedMessage`.
+    pub span: tracing::Span,
+    failures: Arc<Failures>,
+}
+
+impl<T: DeserializeOwned> DurableMessage<T> {
+    /// `None` for a malformed payload, which is dead-lettered or terminated.
+    async fn decode(message: jetstream::Message, failures: Arc<Failures>) -> Option<Self> {
+        let error = match consume(&message.message) {
+            Ok((event, span)) => return Some(Self { event, message, span, failures }),
+            Err(e) => e,
+        };
+        if let Some(dead_letters) = &failures.dead_letters {
+            let delivered = message.info().map(|info| info.delivered as u64).unwrap_or(1);
+            if let Err(e) = dead_letters.send(&failures.consumer, &message.message, delivered, &error.to_string()).await {
+                // Left unacknowledged, it comes back once the ack wait runs out.
+                warn!("⚠️ Failed to dead-letter malformed message on '{}': {}", message.subject, e);
+                return None;
+            }
+        }
+        if let Err(e) = message.ack_with(AckKind::Term).await {
+            warn!("⚠️ Failed to terminate malformed message on '{}': {}", message.subject, e);
+        }
+        None
+    }
+}
+
+impl<T> DurableMessage<T> {
+    /// Handled: the stream will not deliver it again.
+    pub async fn ack(&self) -> Result<(), NatsError> {
+        self.message.ack().await.map_err(jetstream_error)
+    }
+
+    /// Not handled: redeliver it after `delay`, or right away.
+    pub async fn nak(&self, delay: Option<Duration>) -> Result<(), NatsError> {
+        self.message.ack_with(AckKind::Nak(delay)).await.map_err(jetstream_error)
+    }
+
+    /// Cannot be handled: never deliver it again.
+    pub async fn term(&self) -> Result<(), NatsError> {
+        self.message.ack_with(AckKind::Term).await.map_err(jetstream_error)
+    }
+
+    /// Handling failed with `error`. With a retry policy, the message is redelivered after the
+    /// policy's backoff while the policy retries the error. Otherwise, without a dead-letter
+    /// queue it is redelivered right away; with one, after the queue's retry delay until it has
+    /// been delivered `max_deliveries` times. A message that is not retried is moved to the
+    /// dead-letter queue, or terminated if there is none.
+    pub async fn fail(&self, error: impl std::fmt::Display) -> Result<(), NatsError> {
+        let error = error.to_string();
+        let delivered = self.delivered();
+        match redelivery(&self.failures, &error, delivered) {
+            Redelivery::After(delay) => return self.nak(delay).await,
+            Redelivery::Never => {}
+        }
+        match &self.failures.dead_letters {
+            Some(dead_letters) => {
+                dead_letters.send(&self.failures.consumer, &self.message.message, delivered, &error).await?;
+            }
+            None => warn!(
+                "⚠️ '{}' giving up on a message from '{}' after {} deliveries: {}",
+                self.failures.consumer, self.message.subject, delivered, error
+            ),
+        }
+        self.term().await
+    }
+
+    /// Still being handled: restart the consumer's ack wait.
+    pub async fn in_progress(&self) -> Result<(), NatsError> {
+        self.message.ack_with(AckKind::Progress).await.map_err(jetstream_error)
+    }
+
+    /// How many times the message has been delivered, this delivery included.
+    pub fn delivered(&self) -> u64 {
+        self.message.info().map(|info| info.delivered as u64).unwrap_or(1)
+    }
+}
+
+/// Whether a message failing with `error` on its `delivered`th delivery comes back.
+enum Redelivery {
+    After(Option<Duration>),
+    Never,
+}
+
+fn redelivery(failures: &Failures, error: &str, delivered: u64) -> Redelivery {
+    if let Some(policy) = &failures.retry {
+        let attempt = u32::try_from(delivered).unwrap_or(u32::MAX);
+        return if policy.should_retry(&error.to_string(), attempt) {
+            Redelivery::After(Some(policy.backoff(attempt)))
+        } else {
+            Redelivery::Never
+        };
+    }
+    match &failures.dead_letters {
+        None => Redelivery::After(None),
+        Some(dead_letters) if delivered < dead_letters.max_deliveries => Redelivery::After(Some(dead_letters.retry_delay)),
+        Some(_) => Redelivery::Never,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consumer_needs_durable_name() {
+        let result = DurableConsumer::<serde_json::Value>::bind_with("ORDERS", pull::Config::default()).await;
+
+        assert!(matches!(result, Err(NatsError::JetStreamError(_))));
+    }
+
+    #[test]
+    fn test_retry_policy_schedules_redeliveries() {
+        let failures = Failures {
+            consumer: "billing".to_string(),
+            dead_letters: Some(DeadLetterQueue::new("ORDERS_DLQ", "lanai.dlq.billing").max_deliveries(2)),
+            retry: Some(
+                RetryPolicy::exponential(3, Duration::from_secs(1))
+                    .with_max_backoff(Duration::from_secs(1))
+                    .retry_if(|e: &String| e != "unknown customer"),
+            ),
+        };
+        let delay = |delivered| match redelivery(&failures, "db unavailable", delivered) {
+            Redelivery::After(delay) => delay,
+            Redelivery::Never => None,
+        };
+
+        // The policy, not the queue's max_deliveries, decides.
+        assert!(delay(2).is_some_and(|d| d >= Duration::from_secs(1) && d <= Duration::from_millis(1250)));
+        assert!(matches!(redelivery(&failures, "db unavailable", 3), Redelivery::Never));
+        assert!(matches!(redelivery(&failures, "unknown customer", 1), Redelivery::Never));
+    }
+}
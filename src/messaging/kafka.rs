@@ -0,0 +1,196 @@
+//! Kafka backend for `MessageBus` (`kafka` feature)
+//!
+//! Lets services moving over from Kafka use the crate's publishing and subscribing API
+//! before their events move to NATS:
+//!
+//! ```ignore
+//! let bus: Arc<dyn MessageBus> = Arc::new(KafkaMessageBus::new("kafka-1:9092,kafka-2:9092")?);
+//! bus.publish_identified("lanai.orders.created.42", &event).await?;
+//!
+//! let mut orders = bus.queue_subscribe("lanai.orders.created.*", "billing").await?;
+//! ```
+//!
+//! Subjects are topic names. Subscription patterns keep their NATS wildcards and become
+//! topic regexes, so topics created later are picked up at the next metadata refresh.
+//! Headers travel as Kafka headers (Trace Context included), and the `Nats-Msg-Id` header,
+//! when set, is the record key, keeping an event's copies on one partition. `subscribe`
+//! reads from a group of its own, starting at the latest offset; `queue_subscribe` joins
+//! `group` as a Kafka consumer group, with automatic offset commits. Errors keep the trait's
+//! `NatsError` type.
+
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Header, Headers, Message, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::bus::MessageBus;
+use super::dedup::MSG_ID_HEADER;
+use super::{inject_trace_context, shutdown, NatsError, Subscription};
+
+/// Comma-separated Kafka bootstrap servers for `KafkaMessageBus::from_env`
+pub const KAFKA_BROKERS_ENV: &str = "KAFKA_BROKERS";
+
+/// A `MessageBus` on a Kafka cluster; clones share the producer.
+#[derive(Clone)]
+pub struct KafkaMessageBus {
+    config: ClientConfig,
+    producer: FutureProducer,
+    send_timeout: Duration,
+}
+
+impl KafkaMessageBus {
+    /// Connect to the bootstrap servers `brokers`.
+    pub fn new(brokers: &str) -> Result<Self, NatsError> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        Self::from_config(config)
+    }
+
+    /// Connect to `KAFKA_BROKERS`.
+    pub fn from_env() -> Result<Self, NatsError> {
+        let brokers = std::env::var(KAFKA_BROKERS_ENV)
+            .map_err(|_| NatsError::ConnectionError(format!("{} is not set", KAFKA_BROKERS_ENV)))?;
+        Self::new(&brokers)
+    }
+
+    /// Use `config` (brokers, security, client ID...) for the producer and every consumer.
+    pub fn from_config(config: ClientConfig) -> Result<Self, NatsError> {
+        let producer = config.create().map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        info!("📡 Kafka message bus ready");
+        Ok(Self { config, producer, send_timeout: Duration::from_secs(5) })
+    }
+
+    /// How long a publish may wait for room in the producer queue (5 seconds by default).
+    pub fn with_send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = timeout;
+        self
+    }
+
+    async fn consume(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        let consumer: StreamConsumer = self
+            .config
+            .clone()
+            .set("group.id", group)
+            .set("enable.auto.commit", "true")
+            .set("auto.offset.reset", "latest")
+            .create()
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        consumer
+            .subscribe(&[topic_pattern(subject).as_str()])
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        info!("📥 Kafka consumer group '{}' reading '{}'", group, subject);
+
+        let messages = stream::unfold(Arc::new(consumer), |consumer| async move {
+            loop {
+                // Converted right away: a received record borrows the consumer.
+                let received = consumer.recv().await.map(|message| to_nats_message(&message));
+                match received {
+                    Ok(message) => return Some((message, consumer)),
+                    Err(e) => warn!("⚠️ Kafka consumer error: {}", e),
+                }
+            }
+        });
+        Ok(messages.take_until(shutdown::requested()).boxed())
+    }
+}
+
+#[async_trait]
+impl MessageBus for KafkaMessageBus {
+    async fn publish_raw(&self, subject: &str, mut headers: HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+        inject_trace_context(&mut headers);
+        let key = headers.get(MSG_ID_HEADER).map(|id| id.as_str().to_string());
+
+        let mut kafka_headers = OwnedHeaders::new();
+        for (name, values) in headers.iter() {
+            let name = name.to_string();
+            for value in values {
+                kafka_headers = kafka_headers.insert(Header { key: &name, value: Some(value.as_str()) });
+            }
+        }
+        let mut record = FutureRecord::to(subject).payload(&payload[..]).headers(kafka_headers);
+        if let Some(key) = &key {
+            record = record.key(key.as_str());
+        }
+        self.producer
+            .send(record, self.send_timeout)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| NatsError::PublishError(e.to_string()))
+    }
+
+    async fn subscribe(&self, subject: &str) -> Result<Subscription, NatsError> {
+        self.consume(subject, &format!("lanai-{}", uuid::Uuid::new_v4())).await
+    }
+
+    async fn queue_subscribe(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        self.consume(subject, group).await
+    }
+}
+
+/// The topic for a subject, or a topic regex (`^...`) for a pattern with wildcards.
+fn topic_pattern(subject: &str) -> String {
+    if !subject.split('.').any(|token| token == "*" || token == ">") {
+        return subject.to_string();
+    }
+    let tokens: Vec<String> = subject
+        .split('.')
+        .map(|token| match token {
+            "*" => "[^.]+".to_string(),
+            ">" => ".+".to_string(),
+            literal => regex_escape(literal),
+        })
+        .collect();
+    format!("^{}$", tokens.join("\\."))
+}
+
+fn regex_escape(literal: &str) -> String {
+    literal.chars().fold(String::new(), |mut escaped, c| {
+        if ".^$*+?()[]{}|\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// A Kafka record as the NATS message the rest of the crate expects: topic as subject.
+fn to_nats_message(message: &BorrowedMessage<'_>) -> async_nats::Message {
+    let headers = message.headers().map(|kafka_headers| {
+        let mut headers = HeaderMap::new();
+        for header in kafka_headers.iter() {
+            if let Some(value) = header.value.and_then(|value| std::str::from_utf8(value).ok()) {
+                headers.append(header.key, value);
+            }
+        }
+        headers
+    });
+    let payload = Bytes::copy_from_slice(message.payload().unwrap_or_default());
+    async_nats::Message {
+        subject: message.topic().into(),
+        reply: None,
+        length: payload.len(),
+        payload,
+        headers,
+        status: None,
+        description: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_pattern() {
+        assert_eq!(topic_pattern("lanai.orders.created.42"), "lanai.orders.created.42");
+        assert_eq!(topic_pattern("lanai.orders.*.42"), "^lanai\\.orders\\.[^.]+\\.42$");
+        assert_eq!(topic_pattern("lanai.inventory-v2.>"), "^lanai\\.inventory-v2\\..+$");
+    }
+}
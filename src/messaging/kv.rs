@@ -0,0 +1,202 @@
+//! Typed JetStream key-value buckets
+//!
+//! A `KvStore<T>` keeps JSON values of type `T` in a bucket, for small shared state such as
+//! feature flags or service discovery metadata. The bucket is created on first use:
+//!
+//! ```ignore
+//! let instances = KvStore::<InstanceInfo>::open("service_instances").await?;
+//! instances.put("billing.pod-7f9c", &InstanceInfo { address, version }).await?;
+//!
+//! let mut changes = instances.watch("billing.>").await?;
+//! while let Some(change) = changes.next().await {
+//!     match change? {
+//!         KvChange::Put { key, value, .. } => registry.insert(key, value),
+//!         KvChange::Delete { key, .. } => registry.remove(&key),
+//!     }
+//! }
+//! ```
+//!
+//! Keys are dot-separated tokens of letters, digits and `-/_=`; `watch` takes `*` and `>`
+//! wildcards like subjects. Key-value buckets are JetStream streams, so they need a real
+//! server: in embedded mode `open` fails with `Unsupported`.
+
+use async_nats::jetstream::kv;
+use futures_util::stream::{BoxStream, StreamExt};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use tokio::sync::mpsc;
+
+use super::{NatsClient, NatsError};
+
+fn jetstream_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::JetStreamError(e.to_string())
+}
+
+/// A change to a watched key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KvChange<T> {
+    Put { key: String, value: T, revision: u64 },
+    /// The key was deleted or purged.
+    Delete { key: String, revision: u64 },
+}
+
+/// Changes from `KvStore::watch`; values that do not deserialize come through as
+/// `DeserializationError`.
+pub type KvWatch<T> = BoxStream<'static, Result<KvChange<T>, NatsError>>;
+
+/// A key-value bucket holding JSON values of type `T`; clones share the bucket.
+pub struct KvStore<T> {
+    store: kv::Store,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for KvStore<T> {
+    fn clone(&self) -> Self {
+        Self { store: self.store.clone(), _value: PhantomData }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + 'static> KvStore<T> {
+    /// The bucket named `bucket`, created with the default settings (latest value only,
+    /// file storage) unless it exists.
+    pub async fn open(bucket: &str) -> Result<Self, NatsError> {
+        Self::open_with(kv::Config { bucket: bucket.to_string(), ..Default::default() }).await
+    }
+
+    /// The bucket named in `config`, created with `config` (history, TTL, replicas...)
+    /// unless it exists; an existing bucket keeps its settings.
+    pub async fn open_with(config: kv::Config) -> Result<Self, NatsError> {
+        let jetstream = NatsClient::jetstream()?;
+        let bucket = config.bucket.clone();
+        let store = match jetstream.get_key_value(&bucket).await {
+            Ok(store) => store,
+            Err(_) => {
+                let store = jetstream.create_key_value(config).await.map_err(jetstream_error)?;
+                info!("🗄️ Created key-value bucket '{}'", bucket);
+                store
+            }
+        };
+        Ok(Self { store, _value: PhantomData })
+    }
+
+    /// Name of the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.store.name
+    }
+
+    /// The current value of `key`, if it has one.
+    pub async fn get(&self, key: &str) -> Result<Option<T>, NatsError> {
+        match self.store.get(key).await.map_err(jetstream_error)? {
+            Some(value) => serde_json::from_slice(&value)
+                .map(Some)
+                .map_err(|e| NatsError::DeserializationError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Set `key` to `value`, returning the new revision.
+    pub async fn put(&self, key: &str, value: &T) -> Result<u64, NatsError> {
+        let payload = serde_json::to_vec(value).map_err(|e| NatsError::SerializationError(e.to_string()))?;
+        self.store.put(key, payload.into()).await.map_err(jetstream_error)
+    }
+
+    /// Remove `key`; watchers see a `KvChange::Delete`.
+    pub async fn delete(&self, key: &str) -> Result<(), NatsError> {
+        self.store.delete(key).await.map_err(jetstream_error)
+    }
+
+    /// The keys that currently have a value.
+    pub async fn keys(&self) -> Result<Vec<String>, NatsError> {
+        let mut keys = self.store.keys().await.map_err(jetstream_error)?;
+        let mut all = Vec::new();
+        while let Some(key) = keys.next().await {
+            all.push(key.map_err(jetstream_error)?);
+        }
+        Ok(all)
+    }
+
+    /// Changes to the keys matching `key` from now on, until the stream is dropped.
+    pub async fn watch(&self, key: &str) -> Result<KvWatch<T>, NatsError> {
+        // `kv::Watch` borrows its store, so a task owning a clone of it forwards the changes.
+        let store = self.store.clone();
+        let key = key.to_string();
+        let (sender, mut receiver) = mpsc::channel(64);
+        let (ready, started) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let mut watch = match store.watch(&key).await {
+                Ok(watch) => {
+                    let _ = ready.send(Ok(()));
+                    watch
+                }
+                Err(e) => {
+                    let _ = ready.send(Err(jetstream_error(e)));
+                    return;
+                }
+            };
+            loop {
+                let change = tokio::select! {
+                    _ = sender.closed() => break,
+                    entry = watch.next() => match entry {
+                        Some(Ok(entry)) => decode_change(entry),
+                        Some(Err(e)) => Err(jetstream_error(e)),
+                        None => break,
+                    },
+                };
+                if sender.send(change).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        started.await.map_err(jetstream_error)??;
+        Ok(futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx)).boxed())
+    }
+}
+
+fn decode_change<T: DeserializeOwned>(entry: kv::Entry) -> Result<KvChange<T>, NatsError> {
+    match entry.operation {
+        kv::Operation::Put => serde_json::from_slice(&entry.value)
+            .map(|value| KvChange::Put { key: entry.key, value, revision: entry.revision })
+            .map_err(|e| NatsError::DeserializationError(e.to_string())),
+        kv::Operation::Delete | kv::Operation::Purge => {
+            Ok(KvChange::Delete { key: entry.key, revision: entry.revision })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn entry(operation: kv::Operation, value: &'static [u8]) -> kv::Entry {
+        kv::Entry {
+            bucket: "feature_flags".to_string(),
+            key: "checkout.new_flow".to_string(),
+            value: Bytes::from_static(value),
+            revision: 3,
+            delta: 0,
+            created: time::OffsetDateTime::UNIX_EPOCH,
+            operation,
+        }
+    }
+
+    #[test]
+    fn test_decode_change() {
+        assert_eq!(
+            decode_change::<bool>(entry(kv::Operation::Put, b"true")).unwrap(),
+            KvChange::Put { key: "checkout.new_flow".to_string(), value: true, revision: 3 }
+        );
+        assert_eq!(
+            decode_change::<bool>(entry(kv::Operation::Purge, b"")).unwrap(),
+            KvChange::Delete { key: "checkout.new_flow".to_string(), revision: 3 }
+        );
+        assert!(matches!(
+            decode_change::<bool>(entry(kv::Operation::Put, b"\"yes\"")),
+            Err(NatsError::DeserializationError(_))
+        ));
+    }
+}
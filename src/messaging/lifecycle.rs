@@ -0,0 +1,259 @@
+//! Tenant provisioning lifecycle
+//!
+//! The account service publishes `OrganizationCreated/Suspended/Deleted` on
+//! `lanai.tenancy.organization.{created|suspended|deleted}.{org_id}` with
+//! `publish_lifecycle_event`. Every other service reacts through a `TenantLifecycleHandler`:
+//!
+//! ```ignore
+//! struct InventoryTenants { pool: PgPool }
+//!
+//! #[async_trait]
+//! impl TenantLifecycleHandler for InventoryTenants {
+//!     fn service(&self) -> &str { "inventory" }
+//!
+//!     async fn on_created(&self, event: &OrganizationCreatedEvent) -> Result<(), TenantLifecycleError> {
+//!         seed_default_warehouse(&self.pool, event.org_id).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // Durable: events published while the service was down are handled when it starts.
+//! let _subscriber = TenantLifecycleSubscriber::new(Arc::new(InventoryTenants { pool }))
+//!     .durable("TENANCY")
+//!     .start()
+//!     .await?;
+//! ```
+//!
+//! Handlers must be idempotent: events are redelivered after failures and replays.
+
+use async_nats::jetstream::{consumer::pull, consumer::AckPolicy, AckKind};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use super::events::{
+    decode_event, LanaiEvent, OrganizationCreatedEvent, OrganizationDeletedEvent, OrganizationSuspendedEvent,
+};
+use super::{NatsClient, NatsError};
+
+/// Every lifecycle event, for subscriptions and stream configuration.
+pub const ORGANIZATION_SUBJECTS: &str = "lanai.tenancy.organization.>";
+
+/// Tenant lifecycle error types
+#[derive(Debug, Error)]
+pub enum TenantLifecycleError {
+    #[error("Tenant lifecycle handler failed: {0}")]
+    Handler(String),
+
+    #[error("Malformed lifecycle event on '{0}': {1}")]
+    Malformed(String, String),
+
+    #[error(transparent)]
+    Nats(#[from] NatsError),
+}
+
+impl From<sqlx::Error> for TenantLifecycleError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Handler(e.to_string())
+    }
+}
+
+/// Implemented by services that keep per-tenant state.
+#[async_trait]
+pub trait TenantLifecycleHandler: Send + Sync {
+    /// Name used for the queue group and durable consumer.
+    fn service(&self) -> &str;
+
+    /// Provision the tenant: create its schema, seed defaults.
+    async fn on_created(&self, event: &OrganizationCreatedEvent) -> Result<(), TenantLifecycleError>;
+
+    async fn on_suspended(&self, _event: &OrganizationSuspendedEvent) -> Result<(), TenantLifecycleError> {
+        Ok(())
+    }
+
+    async fn on_deleted(&self, _event: &OrganizationDeletedEvent) -> Result<(), TenantLifecycleError> {
+        Ok(())
+    }
+}
+
+/// Publish a lifecycle event in an `EventEnvelope`, deduplicated by its event ID.
+pub async fn publish_lifecycle_event<T>(event: T) -> Result<(), NatsError>
+where
+    T: LanaiEvent + serde::Serialize,
+{
+    NatsClient::publish_envelope(&event.into_envelope()).await
+}
+
+fn decode<T: DeserializeOwned>(subject: &str, payload: &[u8]) -> Result<T, TenantLifecycleError> {
+    decode_event(payload).map_err(|e| TenantLifecycleError::Malformed(subject.to_string(), e.to_string()))
+}
+
+/// Route one message to the handler by its subject's action segment.
+pub async fn dispatch(
+    handler: &dyn TenantLifecycleHandler,
+    subject: &str,
+    payload: &[u8],
+) -> Result<(), TenantLifecycleError> {
+    match subject.split('.').nth(3) {
+        Some("created") => handler.on_created(&decode(subject, payload)?).await,
+        Some("suspended") => handler.on_suspended(&decode(subject, payload)?).await,
+        Some("deleted") => handler.on_deleted(&decode(subject, payload)?).await,
+        _ => {
+            warn!("⚠️ Ignoring unknown tenant lifecycle event on '{}'", subject);
+            Ok(())
+        }
+    }
+}
+
+/// Feeds lifecycle events to a handler. Replicas of a service share the work.
+pub struct TenantLifecycleSubscriber {
+    handler: Arc<dyn TenantLifecycleHandler>,
+    stream: Option<String>,
+    retry_delay: Duration,
+}
+
+impl TenantLifecycleSubscriber {
+    /// Core NATS queue subscription: only events published while running are seen.
+    pub fn new(handler: Arc<dyn TenantLifecycleHandler>) -> Self {
+        Self { handler, stream: None, retry_delay: Duration::from_secs(30) }
+    }
+
+    /// Read from a durable consumer on the JetStream `stream` holding `ORGANIZATION_SUBJECTS`;
+    /// failed events are redelivered after the retry delay.
+    pub fn durable(mut self, stream: &str) -> Self {
+        self.stream = Some(stream.to_string());
+        self
+    }
+
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Handle events until the handle is aborted.
+    pub async fn start(self) -> Result<JoinHandle<()>, NatsError> {
+        let service = self.handler.service().to_string();
+        let handler = self.handler;
+
+        let Some(stream) = self.stream else {
+            let mut subscriber =
+                NatsClient::queue_subscribe(ORGANIZATION_SUBJECTS, &format!("lanai-tenancy-{}", service)).await?;
+            info!("🏢 '{}' handling tenant lifecycle events", service);
+
+            return Ok(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    if let Err(e) = dispatch(handler.as_ref(), &message.subject, &message.payload).await {
+                        error!("❌ '{}' failed to handle '{}': {}", service, message.subject, e);
+                    }
+                }
+            }));
+        };
+
+        let durable = format!("tenancy-{}", service);
+        let consumer = NatsClient::jetstream()?
+            .get_stream(&stream)
+            .await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?
+            .get_or_create_consumer(
+                &durable,
+                pull::Config {
+                    durable_name: Some(durable.clone()),
+                    filter_subject: ORGANIZATION_SUBJECTS.to_string(),
+                    ack_policy: AckPolicy::Explicit,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        let mut messages = consumer.messages().await.map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        info!("🏢 '{}' handling tenant lifecycle events from stream '{}'", service, stream);
+
+        let retry_delay = self.retry_delay;
+        Ok(tokio::spawn(async move {
+            while let Some(message) = messages.next().await {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("⚠️ Tenant lifecycle consumer '{}' error: {}", durable, e);
+                        continue;
+                    }
+                };
+                let ack = match dispatch(handler.as_ref(), &message.subject, &message.payload).await {
+                    Ok(()) => message.ack().await,
+                    // Retrying cannot fix a payload that does not parse.
+                    Err(e @ TenantLifecycleError::Malformed(..)) => {
+                        error!("❌ '{}' dropping lifecycle event: {}", service, e);
+                        message.ack_with(AckKind::Term).await
+                    }
+                    Err(e) => {
+                        error!("❌ '{}' failed to handle '{}', retrying: {}", service, message.subject, e);
+                        message.ack_with(AckKind::Nak(Some(retry_delay))).await
+                    }
+                };
+                if let Err(e) = ack {
+                    warn!("⚠️ Failed to acknowledge '{}': {}", message.subject, e);
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{OrgId, Timestamp};
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        seen: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl TenantLifecycleHandler for Recorder {
+        fn service(&self) -> &str {
+            "test"
+        }
+
+        async fn on_created(&self, event: &OrganizationCreatedEvent) -> Result<(), TenantLifecycleError> {
+            self.seen.lock().await.push(format!("created {}", event.name));
+            Ok(())
+        }
+
+        async fn on_deleted(&self, event: &OrganizationDeletedEvent) -> Result<(), TenantLifecycleError> {
+            self.seen.lock().await.push(format!("deleted {}", event.org_id));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_enveloped_and_bare_events() {
+        let recorder = Recorder::default();
+        let org_id = OrgId::generate();
+        let created = OrganizationCreatedEvent {
+            org_id,
+            name: "Panadería Sol".to_string(),
+            vertical: None,
+            plan: None,
+            locale: Some("es".to_string()),
+            created_at: Timestamp::now(),
+        };
+        let subject = created.subject();
+        let payload = serde_json::to_vec(&created.into_envelope()).unwrap();
+        dispatch(&recorder, &subject, &payload).await.unwrap();
+
+        let deleted = OrganizationDeletedEvent { org_id, deleted_at: Timestamp::now() };
+        dispatch(&recorder, &deleted.subject(), &serde_json::to_vec(&deleted).unwrap()).await.unwrap();
+
+        let malformed = dispatch(&recorder, &subject, b"{}").await;
+        assert!(matches!(malformed, Err(TenantLifecycleError::Malformed(..))));
+
+        let seen = recorder.seen.lock().await.clone();
+        assert_eq!(seen, vec!["created Panadería Sol".to_string(), format!("deleted {}", org_id)]);
+    }
+}
@@ -1,31 +1,173 @@
 //! NATS Messaging Client for Lanai Services
 //!
 //! Provides a singleton NATS client with:
-//! - Automatic reconnection with backoff
-//! - Connection status monitoring
-//! - Typed event publishing
-//! - Optional JetStream support for durable messaging
+//! - Automatic reconnection with backoff, optionally buffering publishes meanwhile (see `buffer`)
+//! - Credentials file, nkey, token or user/password authentication and TLS (see `security`)
+//! - Connection status, and connection events (disconnects, reconnects, slow consumers, lame
+//!   duck) broadcast to callbacks, metrics and health checks (see `connection`)
+//! - Typed event publishing in an `EventEnvelope` carrying IDs for deduplication and
+//!   correlation, with an allocation-free path for high-frequency events and batching
+//!   (see `batch`)
+//! - Typed subscriptions whose messages carry a span continuing the publisher's trace
+//! - Publishing and subscribing through an injectable `MessageBus`, on NATS or, with the
+//!   `kafka` feature, Kafka (see `bus`)
+//! - Building, parsing and matching `lanai.{domain}.{entity}.{action}` subjects (see `subject`)
+//! - Subscriptions with a bounded buffer and a strategy for slow consumers (see `backpressure`)
+//! - Managed competing consumers on a queue group (see `consumer`)
+//! - Routing events to typed handlers by subject pattern, with shared middleware (see `router`)
+//! - Event validation on publish and quarantine of invalid inbound events (see `schema`)
+//! - Typed request-reply with `request_typed` (or `service::call` for `NatsService` endpoints)
+//! - JetStream streams, acknowledged publishing and durable pull consumers (see `jetstream`)
+//! - Replaying stored envelopes from a sequence number or a time (see `replay`)
+//! - Typed key-value buckets for small shared state (see `kv`)
+//! - Object store buckets for internal artifacts, streamed in chunks (see `objects`)
+//! - `Nats-Msg-Id` deduplication (see `dedup`) and idempotent consumers (see `inbox`)
+//! - Redelivery with backoff for failed durable messages, then dead-letter queues (see `dlq`)
+//! - An in-process broker for local development with `NATS_MODE=embedded` (see `embedded`)
+//! - Discoverable request-reply services with stats (see `service`)
+//! - JetStream consumer lag and stall monitoring (see `monitor`)
+//! - Draining subscriptions and pending publishes on shutdown (see `shutdown`)
+//! - Failover to DR-region servers listed in `NATS_FALLBACK_URLS` (see `resilience::failover`)
 
-use async_nats::{Client, ConnectOptions};
+use async_nats::{Client, ConnectOptions, Subject};
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::stream::{BoxStream, StreamExt};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::OnceCell;
-use log::{info, warn, error};
+use log::{info, warn};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
-use opentelemetry::propagation::Injector;
+use opentelemetry::propagation::{Extractor, Injector};
 
+use crate::env::{EnvVar, VarType};
+use crate::resilience::failover::{parse_endpoints, Connector, Failover, FailoverPolicy};
+use crate::resilience::RetryPolicy;
+
+pub mod backpressure;
+pub mod batch;
+pub mod bridge;
+pub mod buffer;
+pub mod bus;
+pub mod connection;
+pub mod consumer;
+pub mod dedup;
+pub mod dlq;
+pub mod embedded;
 pub mod events;
+pub mod inbox;
+pub mod jetstream;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod kv;
+pub mod lifecycle;
+pub mod monitor;
+pub mod objects;
+pub mod replay;
+pub mod republish;
+pub mod router;
+pub mod schema;
+pub mod security;
+pub mod service;
+pub mod shutdown;
+pub mod subject;
+
+pub use bridge::{HttpToNats, NatsToHttp};
+pub use backpressure::{Backpressure, BoundedSubscription};
+pub use batch::{BatchPublisher, RunningBatchPublisher};
+pub use buffer::{OverflowPolicy, PublishBuffer, PublishBufferConfig};
+pub use bus::{message_bus, MessageBus, MessageBusExt, NatsMessageBus, MESSAGE_BUS_ENV};
+pub use connection::{ConnectionEvent, ConnectionEventKind};
+pub use consumer::{QueueSubscriber, RunningQueueSubscriber};
+pub use dedup::{DuplicateDetector, Identified};
+pub use dlq::DeadLetterQueue;
+pub use embedded::EmbeddedBroker;
+pub use events::{EventEnvelope, LanaiEvent, CORRELATION_ID_HEADER};
+pub use inbox::{Inbox, InboxOutcome};
+pub use jetstream::{DurableConsumer, DurableMessage, DurableMessages};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaMessageBus;
+pub use kv::{KvChange, KvStore, KvWatch};
+pub use lifecycle::{TenantLifecycleHandler, TenantLifecycleSubscriber};
+pub use monitor::{MessagingStatus, StreamMonitor};
+pub use objects::{NatsObjectStore, ObjectMeta, ObjectReader};
+pub use replay::{Replay, ReplayStart, ReplayedEvent};
+pub use republish::{RepublishFilter, Republisher};
+pub use router::EventRouter;
+pub use security::{NatsAuth, NatsTls};
+pub use service::{Endpoint, EndpointRequest, NatsService, RunningService, ServiceError};
+pub use subject::{EventSubject, SubjectError};
 
 /// Environment variable for NATS URL
 pub const NATS_URL_ENV: &str = "NATS_URL";
 /// Default NATS URL
 pub const DEFAULT_NATS_URL: &str = "nats://localhost:4222";
+/// Environment variable listing fallback NATS URLs, tried in order when `NATS_URL` is down
+pub const NATS_FALLBACK_URLS_ENV: &str = "NATS_FALLBACK_URLS";
+
+/// Variables read by `NatsConfig::default`
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar::new(NATS_URL_ENV, VarType::String).description("NATS server URL(s)").default_value(DEFAULT_NATS_URL),
+    EnvVar::new(NATS_FALLBACK_URLS_ENV, VarType::UrlList).description("Fallback NATS endpoints, in order"),
+    EnvVar::new(embedded::NATS_MODE_ENV, VarType::String).description("`embedded` for an in-process broker"),
+    EnvVar::new(buffer::NATS_PUBLISH_BUFFER_ENV, VarType::Integer).description("Messages to buffer while disconnected"),
+];
 
 /// Singleton-like NATS client for Lanai services
 #[derive(Clone)]
 pub struct NatsClient;
 
 static NATS_INSTANCE: OnceCell<Arc<Client>> = OnceCell::const_new();
+static NATS_FAILOVER: OnceCell<Failover<NatsConnector>> = OnceCell::const_new();
+static EMBEDDED_BROKER: OnceCell<EmbeddedBroker> = OnceCell::const_new();
+static PUBLISH_BUFFER: OnceCell<PublishBuffer> = OnceCell::const_new();
+
+/// Messages from `NatsClient::subscribe`/`queue_subscribe`, in either mode.
+pub type Subscription = BoxStream<'static, async_nats::Message>;
+
+/// Events from `NatsClient::subscribe_typed`/`queue_subscribe_typed`; payloads that do not
+/// deserialize come through as `DeserializationError`.
+pub type TypedSubscription<T> = BoxStream<'static, Result<TypedMessage<T>, NatsError>>;
+
+/// A deserialized event and the message it arrived in.
+///
+/// ```ignore
+/// let mut orders = NatsClient::subscribe_typed::<OrderCreatedEvent>("lanai.orders.created.>").await?;
+/// while let Some(received) = orders.next().await {
+///     let received = received?;
+///     reserve_stock(&received.event).instrument(received.span.clone()).await?;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TypedMessage<T> {
+    pub event: T,
+    pub message: async_nats::Message,
+    /// `nats_consume` span, a child of the publisher's span when the message carried Trace
+    /// Context; handle the event inside it so its spans join the publisher's trace.
+    pub span: tracing::Span,
+}
+
+impl<T: serde::de::DeserializeOwned> TypedMessage<T> {
+    fn decode(message: async_nats::Message) -> Result<Self, NatsError> {
+        let (event, span) = consume(&message)?;
+        Ok(Self { event, message, span })
+    }
+}
+
+/// Deserialize a JSON event (the data of an `EventEnvelope`, or a bare payload), with a `nats_consume` span continuing the publisher's trace.
+pub(crate) fn consume<T: serde::de::DeserializeOwned>(
+    message: &async_nats::Message,
+) -> Result<(T, tracing::Span), NatsError> {
+    let span = tracing::info_span!("nats_consume", subject = %message.subject);
+    span.set_parent(extract_trace_context(message.headers.as_ref()));
+    let event = events::decode_event(&message.payload).map_err(|e| {
+        warn!("⚠️ Malformed event on '{}': {}", message.subject, e);
+        NatsError::DeserializationError(e.to_string())
+    })?;
+    Ok((event, span))
+}
 
 /// Configuration for NATS connection
 #[derive(Debug, Clone)]
@@ -40,6 +182,19 @@ pub struct NatsConfig {
     pub max_reconnect_delay: Duration,
     /// Connection name for identification
     pub connection_name: String,
+    /// Route everything through the in-process `EmbeddedBroker` instead of a server
+    pub embedded: bool,
+    /// Fallback endpoints, comma-separated `region=url` or `url` entries in order of
+    /// preference; empty disables failover
+    pub fallback_urls: String,
+    /// Probing and fail-back thresholds when `fallback_urls` is set
+    pub failover_policy: FailoverPolicy,
+    /// Buffer publishes while disconnected instead of failing them; `None` disables it
+    pub publish_buffer: Option<PublishBufferConfig>,
+    /// How to authenticate, from `NATS_CREDS_FILE`, `NATS_TOKEN`... by default
+    pub auth: NatsAuth,
+    /// CA and client certificates, from `NATS_TLS_*` by default
+    pub tls: NatsTls,
 }
 
 impl Default for NatsConfig {
@@ -50,6 +205,12 @@ impl Default for NatsConfig {
             reconnect_delay: Duration::from_millis(500),
             max_reconnect_delay: Duration::from_secs(30),
             connection_name: "lanai-service".to_string(),
+            embedded: std::env::var(embedded::NATS_MODE_ENV).is_ok_and(|mode| mode.eq_ignore_ascii_case("embedded")),
+            fallback_urls: std::env::var(NATS_FALLBACK_URLS_ENV).unwrap_or_default(),
+            failover_policy: FailoverPolicy::default(),
+            publish_buffer: PublishBufferConfig::from_env(),
+            auth: NatsAuth::from_env(),
+            tls: NatsTls::from_env(),
         }
     }
 }
@@ -66,7 +227,7 @@ impl NatsConfig {
 
 impl NatsClient {
     /// Initialize the global NATS connection with default config
-    pub async fn init(url: &str) -> Result<(), async_nats::ConnectError> {
+    pub async fn init(url: &str) -> Result<(), NatsError> {
         let config = NatsConfig {
             url: url.to_string(),
             ..Default::default()
@@ -75,39 +236,98 @@ impl NatsClient {
     }
 
     /// Initialize the global NATS connection with custom config
-    pub async fn init_with_config(config: NatsConfig) -> Result<(), async_nats::ConnectError> {
-        let connect_options = ConnectOptions::new()
-            .name(&config.connection_name)
-            .retry_on_initial_connect()
-
-            .reconnect_delay_callback(move |attempts| {
-                // Exponential backoff with jitter
-                let base_delay = config.reconnect_delay.as_millis() as u64;
-                let max_delay = config.max_reconnect_delay.as_millis() as u64;
-                let delay = std::cmp::min(base_delay * 2u64.saturating_pow(attempts as u32), max_delay);
-                // Add jitter (up to 25%)
-                let jitter = (delay as f64 * 0.25 * rand::random::<f64>()) as u64;
-                Duration::from_millis(delay + jitter)
-            })
-;
+    pub async fn init_with_config(config: NatsConfig) -> Result<(), NatsError> {
+        if config.embedded {
+            Self::init_embedded();
+            return Ok(());
+        }
+
+        if let Some(buffer) = config.publish_buffer {
+            let _ = PUBLISH_BUFFER.set(PublishBuffer::new(buffer));
+        }
+
+        if !config.fallback_urls.trim().is_empty() {
+            return Self::init_failover(config).await;
+        }
+
+        let connect_options = connect_options(&config)?.retry_on_initial_connect();
 
         info!("📡 Connecting to NATS at {} as '{}'...", config.url, config.connection_name);
         
-        let client = connect_options.connect(&config.url).await?;
+        let client = connect_options
+            .connect(&config.url)
+            .await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
         
         info!("✅ NATS Client connected to {} with auto-reconnect enabled", config.url);
+
+        register_client_metrics(&config.connection_name);
         
         let _ = NATS_INSTANCE.set(Arc::new(client));
         Ok(())
     }
 
-    /// Get the shared NATS client instance
+    /// Connect to the first reachable of `url` and `fallback_urls` and keep monitoring them.
+    /// Unlike a single endpoint, the initial connect is not retried: an unreachable region
+    /// is skipped.
+    async fn init_failover(config: NatsConfig) -> Result<(), NatsError> {
+        let endpoints = parse_endpoints(&config.url, &config.fallback_urls);
+        let connection_name = config.connection_name.clone();
+        let policy = config.failover_policy.clone();
+        let failover = Failover::connect("nats", endpoints, NatsConnector { config }, policy)
+            .await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+
+        register_client_metrics(&connection_name);
+
+        if NATS_FAILOVER.set(failover.clone()).is_ok() {
+            failover.start();
+        }
+        Ok(())
+    }
+
+    /// Use an in-process broker instead of a NATS server (what `NATS_MODE=embedded` selects)
+    pub fn init_embedded() {
+        if EMBEDDED_BROKER.set(EmbeddedBroker::new()).is_ok() {
+            warn!("🧪 NATS running in embedded mode: messages stay inside this process");
+        }
+    }
+
+    /// Get the shared NATS client instance (`None` in embedded mode; prefer the
+    /// `NatsClient` methods, which work in both modes)
     pub fn global() -> Option<Client> {
-        NATS_INSTANCE.get().map(|c| (**c).clone())
+        NATS_FAILOVER
+            .get()
+            .map(Failover::current)
+            .or_else(|| NATS_INSTANCE.get().map(|c| (**c).clone()))
+    }
+
+    /// The failover monitor, if `fallback_urls` were configured; subscribe to it to rebuild
+    /// subscriptions after the client is replaced
+    pub fn failover() -> Option<Failover<NatsConnector>> {
+        NATS_FAILOVER.get().cloned()
+    }
+
+    /// The publish buffer, if `publish_buffer` was configured
+    pub fn publish_buffer() -> Option<PublishBuffer> {
+        PUBLISH_BUFFER.get().cloned()
+    }
+
+    /// The in-process broker, if running in embedded mode
+    pub fn embedded() -> Option<EmbeddedBroker> {
+        EMBEDDED_BROKER.get().cloned()
+    }
+
+    /// Whether `init`/`init_embedded` has run
+    pub fn is_initialized() -> bool {
+        NATS_INSTANCE.get().is_some() || NATS_FAILOVER.get().is_some() || EMBEDDED_BROKER.get().is_some()
     }
 
     /// Check if NATS is connected
     pub fn is_connected() -> bool {
+        if EMBEDDED_BROKER.get().is_some() {
+            return true;
+        }
         if let Some(client) = Self::global() {
             // Check connection state
             matches!(client.connection_state(), async_nats::connection::State::Connected)
@@ -118,7 +338,9 @@ impl NatsClient {
 
     /// Get the NATS connection state as a string
     pub fn connection_status() -> &'static str {
-        if let Some(client) = Self::global() {
+        if EMBEDDED_BROKER.get().is_some() {
+            "embedded"
+        } else if let Some(client) = Self::global() {
             match client.connection_state() {
                 async_nats::connection::State::Connected => "connected",
                 async_nats::connection::State::Pending => "connecting",
@@ -129,45 +351,257 @@ impl NatsClient {
         }
     }
 
-    /// Convenience wrapper to publish a JSON event with Trace Context
-    pub async fn publish_event<T: serde::Serialize>(subject: &str, event: &T) -> Result<(), NatsError> {
+    /// Publish raw bytes with headers as given
+    pub async fn publish_with_headers(subject: &str, headers: async_nats::HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+        if let Some(broker) = EMBEDDED_BROKER.get() {
+            broker.publish(subject, None, headers, payload);
+            return Ok(());
+        }
         let client = Self::global().ok_or(NatsError::NotInitialized)?;
+        if let Some(buffer) = PUBLISH_BUFFER.get() {
+            return buffer.publish(&client, subject, headers, payload).await;
+        }
+        let _pending = PendingPublish::start(&payload);
+        client.publish_with_headers(subject.to_string(), headers, payload).await
+            .map_err(|e| NatsError::PublishError(e.to_string()))
+    }
+
+    /// Fast path for high-frequency events (telemetry, stock ticks): publish a prepared
+    /// subject and payload as is. No header map is built, so there is no Trace Context or
+    /// `Nats-Msg-Id`; `Subject` is reference counted, so create it once and reuse it.
+    pub async fn publish_event_bytes(subject: &Subject, payload: Bytes) -> Result<(), NatsError> {
+        if let Some(broker) = EMBEDDED_BROKER.get() {
+            broker.publish(subject, None, async_nats::HeaderMap::new(), payload);
+            return Ok(());
+        }
+        let client = Self::global().ok_or(NatsError::NotInitialized)?;
+        let _pending = PendingPublish::start(&payload);
+        client.publish(subject.clone(), payload).await.map_err(|e| NatsError::PublishError(e.to_string()))
+    }
+
+    /// `publish_event_bytes` with the event serialized to JSON into a reused buffer.
+    pub async fn publish_event_pooled<T: serde::Serialize>(subject: &Subject, event: &T) -> Result<(), NatsError> {
+        let payload = serialize_pooled(event)?;
+        Self::publish_event_bytes(subject, payload).await
+    }
+
+    /// Send raw bytes and wait for the reply (no timeout; wrap in `tokio::time::timeout`)
+    pub async fn request_with_headers(
+        subject: &str,
+        headers: async_nats::HeaderMap,
+        payload: Bytes,
+    ) -> Result<async_nats::Message, NatsError> {
+        if let Some(broker) = EMBEDDED_BROKER.get() {
+            return broker.request(subject, headers, payload).await;
+        }
+        let client = Self::global().ok_or(NatsError::NotInitialized)?;
+        client.request_with_headers(subject.to_string(), headers, payload).await
+            .map_err(|e| NatsError::RequestError(e.to_string()))
+    }
+
+    /// Every message on `subject` (wildcards allowed), until `shutdown`
+    pub async fn subscribe(subject: &str) -> Result<Subscription, NatsError> {
+        if let Some(broker) = EMBEDDED_BROKER.get() {
+            return Ok(broker.subscribe(subject).take_until(shutdown::requested()).boxed());
+        }
+        let client = Self::global().ok_or(NatsError::NotInitialized)?;
+        let subscriber = client.subscribe(subject.to_string()).await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        Ok(subscriber.take_until(shutdown::requested()).boxed())
+    }
+
+    /// Messages on `subject`, each delivered to one member of the queue `group`, until `shutdown`
+    pub async fn queue_subscribe(subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        if let Some(broker) = EMBEDDED_BROKER.get() {
+            return Ok(broker.queue_subscribe(subject, group).take_until(shutdown::requested()).boxed());
+        }
+        let client = Self::global().ok_or(NatsError::NotInitialized)?;
+        let subscriber = client.queue_subscribe(subject.to_string(), group.to_string()).await
+            .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+        Ok(subscriber.take_until(shutdown::requested()).boxed())
+    }
+
+    /// `subscribe`, deserializing each message's event (see `decode_event`) into `T`
+    pub async fn subscribe_typed<T>(subject: &str) -> Result<TypedSubscription<T>, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        Ok(Self::subscribe(subject).await?.map(TypedMessage::decode).boxed())
+    }
+
+    /// `subscribe` to events published in an `EventEnvelope`, keeping their metadata
+    pub async fn subscribe_envelopes<T>(subject: &str) -> Result<TypedSubscription<EventEnvelope<T>>, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        Self::subscribe_typed(subject).await
+    }
+
+    /// `queue_subscribe`, deserializing each message's event (see `decode_event`) into `T`
+    pub async fn queue_subscribe_typed<T>(subject: &str, group: &str) -> Result<TypedSubscription<T>, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        Ok(Self::queue_subscribe(subject, group).await?.map(TypedMessage::decode).boxed())
+    }
+
+    /// JetStream context of the global client; JetStream needs a real server
+    pub fn jetstream() -> Result<async_nats::jetstream::Context, NatsError> {
+        if EMBEDDED_BROKER.get().is_some() {
+            return Err(NatsError::Unsupported("JetStream"));
+        }
+        Self::global().map(async_nats::jetstream::new).ok_or(NatsError::NotInitialized)
+    }
+
+    /// Publish a JSON event in a new `EventEnvelope`, with Trace Context
+    pub async fn publish_event<T: serde::Serialize>(subject: &str, event: &T) -> Result<(), NatsError> {
+        Self::publish_event_with_headers(subject, event, async_nats::HeaderMap::new()).await
+    }
+
+    /// `publish_event` with caller-provided headers. A `Lanai-Correlation-Id` header becomes
+    /// the envelope's correlation ID.
+    pub async fn publish_event_with_headers<T: serde::Serialize>(
+        subject: &str,
+        event: &T,
+        headers: async_nats::HeaderMap,
+    ) -> Result<(), NatsError> {
+        let mut envelope = EventEnvelope::new(event);
+        envelope.correlation_id = headers
+            .get(CORRELATION_ID_HEADER)
+            .and_then(|value| uuid::Uuid::parse_str(value.as_str()).ok());
+        Self::publish_envelope_with_headers(subject, &envelope, headers).await
+    }
+
+    /// Publish an event as is (typically an `EventEnvelope`) with its own ID as `Nats-Msg-Id`,
+    /// so JetStream stores it once
+    pub async fn publish_identified<T: serde::Serialize + Identified>(subject: &str, event: &T) -> Result<(), NatsError> {
+        let headers = dedup::with_message_id(async_nats::HeaderMap::new(), &event.message_id());
+        Self::publish_json(subject, event, headers).await
+    }
+
+    /// Publish an event in its envelope on the event's subject
+    pub async fn publish_envelope<T>(envelope: &EventEnvelope<T>) -> Result<(), NatsError>
+    where
+        T: LanaiEvent + serde::Serialize,
+    {
+        Self::publish_envelope_with_headers(&envelope.data.subject(), envelope, async_nats::HeaderMap::new()).await
+    }
+
+    /// Publish an envelope on `subject` with its event ID as `Nats-Msg-Id` and its correlation
+    /// ID as `Lanai-Correlation-Id` (read by choreographies), unless `headers` set them
+    pub async fn publish_envelope_with_headers<T: serde::Serialize>(
+        subject: &str,
+        envelope: &EventEnvelope<T>,
+        mut headers: async_nats::HeaderMap,
+    ) -> Result<(), NatsError> {
+        if headers.get(dedup::MSG_ID_HEADER).is_none() {
+            headers = dedup::with_message_id(headers, &envelope.message_id());
+        }
+        if headers.get(CORRELATION_ID_HEADER).is_none() {
+            headers.insert(CORRELATION_ID_HEADER, envelope.correlation().to_string().as_str());
+        }
+        Self::publish_json(subject, envelope, headers).await
+    }
+
+    async fn publish_json<T: serde::Serialize>(
+        subject: &str,
+        payload: &T,
+        mut headers: async_nats::HeaderMap,
+    ) -> Result<(), NatsError> {
+        let payload = serde_json::to_vec(payload)
+            .map_err(|e| NatsError::SerializationError(e.to_string()))?;
+
+        inject_trace_context(&mut headers);
+
+        Self::publish_with_headers(subject, headers, payload.into()).await
+    }
+
+    /// Publish to a JetStream stream and wait for its acknowledgement. Returns false if the
+    /// stream had already stored the event's ID (a duplicate). In embedded mode there is no
+    /// stream: the event is published and reported as new.
+    pub async fn publish_acked<T: serde::Serialize + Identified>(subject: &str, event: &T) -> Result<bool, NatsError> {
+        if EMBEDDED_BROKER.get().is_some() {
+            Self::publish_identified(subject, event).await?;
+            return Ok(true);
+        }
+        let ack = Self::publish_to_stream(subject, event, &event.message_id()).await?;
+        Ok(!ack.duplicate)
+    }
+
+    /// Publish a JSON event in a new `EventEnvelope` to a JetStream stream and wait until the
+    /// stream has stored it. The envelope's ID is the `Nats-Msg-Id`; fails with `Unsupported` in embedded mode and
+    /// with `PublishError` if no stream holds `subject` or the stream rejects the event.
+    pub async fn publish_durable<T: serde::Serialize>(
+        subject: &str,
+        event: &T,
+    ) -> Result<async_nats::jetstream::publish::PublishAck, NatsError> {
+        let envelope = EventEnvelope::new(event);
+        Self::publish_to_stream(subject, &envelope, &envelope.message_id()).await
+    }
+
+    async fn publish_to_stream<T: serde::Serialize>(
+        subject: &str,
+        event: &T,
+        msg_id: &str,
+    ) -> Result<async_nats::jetstream::publish::PublishAck, NatsError> {
+        let jetstream = Self::jetstream()?;
 
         let payload = serde_json::to_vec(event)
             .map_err(|e| NatsError::SerializationError(e.to_string()))?;
-        
-        // Inject Trace Context
-        let mut headers = async_nats::HeaderMap::new();
-        let cx = tracing::Span::current().context();
-        
-        opentelemetry::global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(&cx, &mut NatsHeaderInjector(&mut headers));
-        });
 
-        client.publish_with_headers(subject.to_string(), headers, payload.into()).await
+        let mut headers = dedup::with_message_id(async_nats::HeaderMap::new(), msg_id);
+        inject_trace_context(&mut headers);
+
+        let ack = jetstream
+            .publish_with_headers(subject.to_string(), headers, payload.into())
+            .await
+            .map_err(|e| NatsError::PublishError(e.to_string()))?
+            .await
             .map_err(|e| NatsError::PublishError(e.to_string()))?;
-        
-        Ok(())
+
+        if ack.duplicate {
+            info!("🔁 Event {} on '{}' was already stored in '{}'", msg_id, subject, ack.stream);
+        }
+        Ok(ack)
     }
 
-    /// Publish with retry logic
+    /// Send a JSON request with Trace Context and wait up to `timeout` for a JSON reply.
+    /// No reply in time is a `Timeout`; a reply that is not a `Resp` a `DeserializationError`.
+    ///
+    /// ```ignore
+    /// let reply: ReserveStockResponse =
+    ///     NatsClient::request_typed("lanai.inventory.stock.reserve", &reserve, Duration::from_secs(5)).await?;
+    /// ```
+    pub async fn request_typed<Req, Resp>(subject: &str, request: &Req, timeout: Duration) -> Result<Resp, NatsError>
+    where
+        Req: serde::Serialize,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let payload = serde_json::to_vec(request)
+            .map_err(|e| NatsError::SerializationError(e.to_string()))?;
+
+        let mut headers = async_nats::HeaderMap::new();
+        inject_trace_context(&mut headers);
+
+        let reply = tokio::time::timeout(timeout, Self::request_with_headers(subject, headers, payload.into()))
+            .await
+            .map_err(|_| NatsError::Timeout(subject.to_string(), timeout))??;
+
+        serde_json::from_slice(&reply.payload)
+            .map_err(|e| NatsError::DeserializationError(e.to_string()))
+    }
+
+    /// `publish_event` with retry logic. Every attempt publishes the same envelope, so a
+    /// stream stores the event once even if an earlier attempt reached it.
     pub async fn publish_event_with_retry<T: serde::Serialize>(
         subject: &str, 
         event: &T,
         max_retries: u32,
     ) -> Result<(), NatsError> {
-        let mut attempts = 0;
-        loop {
-            match Self::publish_event(subject, event).await {
-                Ok(()) => return Ok(()),
-                Err(e) if attempts < max_retries => {
-                    attempts += 1;
-                    warn!("NATS publish failed (attempt {}/{}): {}. Retrying...", attempts, max_retries, e);
-                    tokio::time::sleep(Duration::from_millis(100 * 2u64.pow(attempts))).await;
-                }
-                Err(e) => return Err(e),
-            }
-        }
+        let envelope = EventEnvelope::new(event);
+        RetryPolicy::exponential(max_retries.saturating_add(1), Duration::from_millis(200))
+            .run(|| Self::publish_envelope_with_headers(subject, &envelope, async_nats::HeaderMap::new()))
+            .await
     }
 }
 
@@ -185,6 +619,190 @@ pub enum NatsError {
     
     #[error("Connection error: {0}")]
     ConnectionError(String),
+
+    #[error("Request failed: {0}")]
+    RequestError(String),
+
+    #[error("No reply on '{0}' within {1:?}")]
+    Timeout(String, Duration),
+
+    #[error("Failed to deserialize reply: {0}")]
+    DeserializationError(String),
+
+    #[error("Invalid event: {0}")]
+    InvalidEvent(String),
+
+    #[error("Publish buffer is full ({0} messages)")]
+    BufferFull(usize),
+
+    #[error("JetStream error: {0}")]
+    JetStreamError(String),
+
+    #[error("Slow consumer: {0} messages were dropped")]
+    SlowConsumer(u64),
+
+    #[error("{0} is not available in embedded mode")]
+    Unsupported(&'static str),
+}
+
+/// Capacity reserved for each pooled payload.
+const PAYLOAD_BUFFER_SIZE: usize = 4 * 1024;
+
+thread_local! {
+    /// Payloads are split off this buffer; once a published payload is dropped its space is
+    /// reclaimed by the next `reserve`, so steady-state publishing does not allocate.
+    static PAYLOAD_BUFFER: RefCell<BytesMut> = RefCell::new(BytesMut::with_capacity(PAYLOAD_BUFFER_SIZE));
+}
+
+/// Serialize `event` as JSON into this thread's payload buffer.
+pub fn serialize_pooled<T: serde::Serialize>(event: &T) -> Result<Bytes, NatsError> {
+    PAYLOAD_BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.reserve(PAYLOAD_BUFFER_SIZE);
+        let result = serde_json::to_writer((&mut *buffer).writer(), event);
+        let payload = buffer.split();
+        result.map(|_| payload.freeze()).map_err(|e| NatsError::SerializationError(e.to_string()))
+    })
+}
+
+/// Inject the current span's OTEL context into outgoing NATS headers
+fn inject_trace_context(headers: &mut async_nats::HeaderMap) {
+    let cx = tracing::Span::current().context();
+
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut NatsHeaderInjector(headers));
+    });
+}
+
+/// Connection options shared by the single-endpoint and failover clients; fails if the
+/// credentials file cannot be read
+fn connect_options(config: &NatsConfig) -> Result<ConnectOptions, NatsError> {
+    let base_delay = config.reconnect_delay.as_millis() as u64;
+    let max_delay = config.max_reconnect_delay.as_millis() as u64;
+    let connection_name = config.connection_name.clone();
+    let options = ConnectOptions::new()
+        .name(&config.connection_name)
+        .reconnect_delay_callback(move |attempts| {
+            // Exponential backoff with jitter
+            let delay = std::cmp::min(base_delay * 2u64.saturating_pow(attempts as u32), max_delay);
+            // Add jitter (up to 25%)
+            let jitter = (delay as f64 * 0.25 * rand::random::<f64>()) as u64;
+            Duration::from_millis(delay + jitter)
+        })
+        .event_callback(move |event| {
+            if matches!(event, async_nats::Event::Connected) {
+                if let Some(buffer) = PUBLISH_BUFFER.get() {
+                    tokio::spawn(buffer.clone().flush());
+                }
+            }
+            connection::record(&connection_name, event);
+            async {}
+        });
+    config
+        .auth
+        .apply(config.tls.apply(options))
+        .map_err(|e| NatsError::ConnectionError(format!("Cannot load NATS credentials: {}", e)))
+}
+
+/// Connects `Failover` to NATS endpoints; healthy means connected and able to flush.
+pub struct NatsConnector {
+    config: NatsConfig,
+}
+
+#[async_trait]
+impl Connector for NatsConnector {
+    type Connection = Client;
+
+    async fn connect(&self, url: &str) -> Result<Client, String> {
+        connect_options(&self.config).map_err(|e| e.to_string())?.connect(url).await.map_err(|e| e.to_string())
+    }
+
+    async fn ping(&self, client: &Client) -> bool {
+        matches!(client.connection_state(), async_nats::connection::State::Connected) && client.flush().await.is_ok()
+    }
+}
+
+/// Payload bytes of publishes waiting for room in the client's outgoing queue
+static PUBLISHING_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a payload in `nats_pending_bytes` until dropped, once its publish has returned.
+pub(crate) struct PendingPublish(u64);
+
+impl PendingPublish {
+    pub(crate) fn start(payload: &[u8]) -> Self {
+        let bytes = payload.len() as u64;
+        PUBLISHING_BYTES.fetch_add(bytes, Ordering::Relaxed);
+        Self(bytes)
+    }
+}
+
+impl Drop for PendingPublish {
+    fn drop(&mut self) {
+        PUBLISHING_BYTES.fetch_sub(self.0, Ordering::Relaxed);
+    }
+}
+
+/// Export `nats_pending_bytes` and `nats_connection_up`, labelled by connection name.
+///
+/// Pending bytes are the payloads held by the publish buffer plus those of publishes
+/// waiting for room in the client's outgoing queue, which fills once the connection cannot
+/// keep up. The client's own write buffer is not exposed by async-nats.
+fn register_client_metrics(connection_name: &str) {
+    let meter = opentelemetry::global::meter("lanai.messaging");
+    let attrs = [opentelemetry::KeyValue::new("connection", connection_name.to_string())];
+
+    let labels = attrs.clone();
+    meter
+        .u64_observable_gauge("nats_pending_bytes")
+        .with_description("Payload bytes published but not yet handed to the NATS connection")
+        .with_unit("By")
+        .with_callback(move |observer| {
+            let buffered = PUBLISH_BUFFER.get().map_or(0, |buffer| buffer.bytes() as u64);
+            observer.observe(PUBLISHING_BYTES.load(Ordering::Relaxed) + buffered, &labels);
+        })
+        .build();
+
+    meter
+        .u64_observable_gauge("nats_connection_up")
+        .with_description("Whether the NATS client is connected")
+        .with_callback(move |observer| {
+            let up = NatsClient::global()
+                .is_some_and(|client| matches!(client.connection_state(), async_nats::connection::State::Connected));
+            observer.observe(up as u64, &attrs);
+        })
+        .build();
+}
+
+/// OTEL context propagated in incoming NATS headers (empty if there is none)
+pub(crate) fn extract_trace_context(headers: Option<&async_nats::HeaderMap>) -> opentelemetry::Context {
+    match headers {
+        Some(headers) => opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&NatsHeaderExtractor(headers))
+        }),
+        None => opentelemetry::Context::new(),
+    }
+}
+
+/// Link `span` to the span that published `message`, for work it triggers that runs in its
+/// own trace (see `observability::link_span`)
+pub fn link_span_to_message(span: &tracing::Span, message: &async_nats::Message) {
+    use opentelemetry::trace::TraceContextExt;
+
+    let context = extract_trace_context(message.headers.as_ref());
+    crate::observability::link_span(span, context.span().span_context().clone());
+}
+
+/// Helper for reading OTEL context from NATS headers
+struct NatsHeaderExtractor<'a>(&'a async_nats::HeaderMap);
+
+impl<'a> Extractor for NatsHeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|v| v.as_str())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.iter().map(|(name, _)| name.as_ref()).collect()
+    }
 }
 
 /// Helper for injecting OTEL context into NATS headers
@@ -204,6 +822,26 @@ impl<'a> Injector for NatsHeaderInjector<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_typed_message_continues_publisher_trace() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert("traceparent", traceparent);
+        let message = async_nats::Message {
+            subject: "lanai.orders.created".into(),
+            reply: None,
+            payload: Bytes::from_static(br#"{"order_id":7}"#),
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        };
+
+        let received = TypedMessage::<serde_json::Value>::decode(message).unwrap();
+        assert_eq!(received.event["order_id"], 7);
+        assert!(TypedMessage::<u32>::decode(received.message).is_err());
+    }
+
     #[test]
     fn test_default_config() {
         let config = NatsConfig::default();
@@ -211,6 +849,18 @@ mod tests {
         assert_eq!(config.reconnect_delay, Duration::from_millis(500));
     }
 
+    #[test]
+    fn test_serialize_pooled_reuses_buffer() {
+        let first = serialize_pooled(&serde_json::json!({"sensor": "temp-1", "value": 4.5})).unwrap();
+        assert_eq!(&first[..], br#"{"sensor":"temp-1","value":4.5}"#);
+        let address = first.as_ptr();
+        drop(first);
+
+        let second = serialize_pooled(&[1, 2, 3]).unwrap();
+        assert_eq!(&second[..], b"[1,2,3]");
+        assert_eq!(second.as_ptr(), address);
+    }
+
     #[test]
     fn test_service_config() {
         let config = NatsConfig::for_service("lanai-inventory-service");
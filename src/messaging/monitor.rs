@@ -0,0 +1,317 @@
+//! JetStream stream and consumer monitoring
+//!
+//! `StreamMonitor` reads the state of JetStream streams and their consumers: how many
+//! messages each consumer has yet to receive (`pending`) or to acknowledge (`ack_pending`),
+//! and whether it is still making progress. A consumer is *stalled* when it has pending
+//! messages but its ack floor has not moved for `stall_after`, and *lagging* when more than
+//! `max_pending` messages wait for it.
+//!
+//! ```ignore
+//! let monitor = StreamMonitor::new().stream("ORDERS").stream("BILLING").max_pending(5_000);
+//! monitor.spawn(Duration::from_secs(30)); // metrics
+//!
+//! ServerBuilder::new("lanai-orders")
+//!     .run(move |cfg| monitor.configure(cfg)) // GET /internal/messaging/status
+//!     .await
+//! ```
+//!
+//! Exported gauges (labelled by `stream` and `consumer`): `nats_consumer_pending_messages`,
+//! `nats_consumer_ack_pending_messages`, `nats_consumer_stalled` and, per stream,
+//! `nats_stream_messages`.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::warn;
+use opentelemetry::metrics::Gauge;
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use super::{NatsClient, NatsError};
+use crate::error::LanaiError;
+
+/// State of one consumer.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsumerStatus {
+    pub name: String,
+    /// Messages not yet delivered
+    pub pending: u64,
+    /// Messages delivered but not acknowledged
+    pub ack_pending: u64,
+    pub redelivered: u64,
+    /// Pull requests waiting for messages
+    pub waiting: u64,
+    pub last_active: Option<DateTime<Utc>>,
+    pub stalled: bool,
+    pub lagging: bool,
+}
+
+/// State of one stream and its consumers.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamStatus {
+    pub name: String,
+    pub messages: u64,
+    pub bytes: u64,
+    pub first_sequence: u64,
+    pub last_sequence: u64,
+    pub consumers: Vec<ConsumerStatus>,
+}
+
+/// What `/internal/messaging/status` returns.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagingStatus {
+    pub connection: &'static str,
+    /// No consumer is stalled or lagging
+    pub healthy: bool,
+    pub streams: Vec<StreamStatus>,
+}
+
+struct MonitorMetrics {
+    pending: Gauge<u64>,
+    ack_pending: Gauge<u64>,
+    stalled: Gauge<u64>,
+    messages: Gauge<u64>,
+}
+
+impl MonitorMetrics {
+    fn new() -> Self {
+        let meter = global::meter("lanai.messaging");
+        Self {
+            pending: meter
+                .u64_gauge("nats_consumer_pending_messages")
+                .with_description("Messages in a stream not yet delivered to a consumer")
+                .build(),
+            ack_pending: meter
+                .u64_gauge("nats_consumer_ack_pending_messages")
+                .with_description("Messages delivered to a consumer and not yet acknowledged")
+                .build(),
+            stalled: meter
+                .u64_gauge("nats_consumer_stalled")
+                .with_description("Whether a consumer has pending messages but stopped acknowledging")
+                .build(),
+            messages: meter
+                .u64_gauge("nats_stream_messages")
+                .with_description("Messages stored in a stream")
+                .build(),
+        }
+    }
+
+    fn record(&self, stream: &StreamStatus) {
+        self.messages.record(stream.messages, &[KeyValue::new("stream", stream.name.clone())]);
+        for consumer in &stream.consumers {
+            let labels = [
+                KeyValue::new("stream", stream.name.clone()),
+                KeyValue::new("consumer", consumer.name.clone()),
+            ];
+            self.pending.record(consumer.pending, &labels);
+            self.ack_pending.record(consumer.ack_pending, &labels);
+            self.stalled.record(consumer.stalled as u64, &labels);
+        }
+    }
+}
+
+struct Inner {
+    /// Empty means every stream
+    streams: Vec<String>,
+    max_pending: u64,
+    stall_after: Duration,
+    /// Last ack floor of each (stream, consumer) and when it last moved
+    progress: Mutex<HashMap<(String, String), (u64, Instant)>>,
+    metrics: MonitorMetrics,
+}
+
+/// Lag and progress of JetStream consumers; clones share state.
+#[derive(Clone)]
+pub struct StreamMonitor {
+    inner: Arc<Inner>,
+}
+
+impl Default for StreamMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamMonitor {
+    /// Watches every stream, flags consumers 10 000 messages behind or without
+    /// progress for 5 minutes.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                streams: Vec::new(),
+                max_pending: 10_000,
+                stall_after: Duration::from_secs(300),
+                progress: Mutex::new(HashMap::new()),
+                metrics: MonitorMetrics::new(),
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("StreamMonitor is configured before it is shared"));
+        self
+    }
+
+    /// Watch only the given streams (call once per stream).
+    pub fn stream(self, name: &str) -> Self {
+        self.update(|inner| inner.streams.push(name.to_string()))
+    }
+
+    pub fn max_pending(self, max_pending: u64) -> Self {
+        self.update(|inner| inner.max_pending = max_pending)
+    }
+
+    pub fn stall_after(self, stall_after: Duration) -> Self {
+        self.update(|inner| inner.stall_after = stall_after)
+    }
+
+    /// Query the watched streams and record the gauges. In embedded mode there is no
+    /// JetStream, and no streams are reported.
+    pub async fn status(&self) -> Result<MessagingStatus, NatsError> {
+        let connection = NatsClient::connection_status();
+        let context = match NatsClient::jetstream() {
+            Ok(context) => context,
+            Err(NatsError::Unsupported(_)) => {
+                return Ok(MessagingStatus { connection, healthy: true, streams: Vec::new() })
+            }
+            Err(e) => return Err(e),
+        };
+
+        let names = if self.inner.streams.is_empty() {
+            context.stream_names().map(|name| name.map_err(request_error)).collect::<Vec<_>>().await
+        } else {
+            self.inner.streams.iter().cloned().map(Ok).collect()
+        };
+
+        let mut streams = Vec::new();
+        for name in names {
+            let mut stream = context.get_stream(name?).await.map_err(request_error)?;
+            let info = stream.info().await.map_err(request_error)?.clone();
+            let consumer_names: Vec<_> = stream.consumer_names().collect().await;
+
+            let mut consumers = Vec::new();
+            for consumer in consumer_names {
+                let details = stream.consumer_info(consumer.map_err(request_error)?).await.map_err(request_error)?;
+                let last_active = details.delivered.last_active.map(|at| at.unix_timestamp_nanos() as i64);
+                let mut consumer = ConsumerStatus {
+                    name: details.name,
+                    pending: details.num_pending,
+                    ack_pending: details.num_ack_pending as u64,
+                    redelivered: details.num_redelivered as u64,
+                    waiting: details.num_waiting as u64,
+                    last_active: last_active.map(DateTime::from_timestamp_nanos),
+                    stalled: false,
+                    lagging: false,
+                };
+                self.assess(&details.stream_name, &mut consumer, details.ack_floor.stream_sequence, Instant::now());
+                consumers.push(consumer);
+            }
+
+            let status = StreamStatus {
+                name: info.config.name,
+                messages: info.state.messages,
+                bytes: info.state.bytes,
+                first_sequence: info.state.first_sequence,
+                last_sequence: info.state.last_sequence,
+                consumers,
+            };
+            self.inner.metrics.record(&status);
+            streams.push(status);
+        }
+
+        let healthy = streams.iter().flat_map(|s| &s.consumers).all(|c| !c.stalled && !c.lagging);
+        Ok(MessagingStatus { connection, healthy, streams })
+    }
+
+    /// Flag `consumer` as lagging or stalled, given its ack floor at `now`.
+    fn assess(&self, stream: &str, consumer: &mut ConsumerStatus, ack_floor: u64, now: Instant) {
+        let key = (stream.to_string(), consumer.name.clone());
+        let mut progress = self.inner.progress.lock().unwrap_or_else(|e| e.into_inner());
+        let (floor, moved_at) = progress.entry(key).or_insert((ack_floor, now));
+        if *floor != ack_floor {
+            *floor = ack_floor;
+            *moved_at = now;
+        }
+
+        let outstanding = consumer.pending + consumer.ack_pending;
+        consumer.lagging = consumer.pending > self.inner.max_pending;
+        consumer.stalled = outstanding > 0 && now.duration_since(*moved_at) >= self.inner.stall_after;
+        if consumer.stalled {
+            warn!("⚠️ Consumer {}/{} stalled with {} messages outstanding", stream, consumer.name, outstanding);
+        }
+    }
+
+    /// Refresh the gauges every `interval`.
+    pub fn spawn(&self, interval: Duration) -> JoinHandle<()> {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = monitor.status().await {
+                    warn!("⚠️ Failed to read JetStream status: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Mount `GET /internal/messaging/status`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone()))
+            .route("/internal/messaging/status", web::get().to(messaging_status));
+    }
+}
+
+async fn messaging_status(monitor: web::Data<StreamMonitor>) -> Result<HttpResponse, LanaiError> {
+    Ok(HttpResponse::Ok().json(monitor.status().await?))
+}
+
+fn request_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::RequestError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consumer(pending: u64, ack_pending: u64) -> ConsumerStatus {
+        ConsumerStatus {
+            name: "billing".to_string(),
+            pending,
+            ack_pending,
+            redelivered: 0,
+            waiting: 0,
+            last_active: None,
+            stalled: false,
+            lagging: false,
+        }
+    }
+
+    #[test]
+    fn test_stalled_and_lagging_consumers() {
+        let monitor = StreamMonitor::new().max_pending(100).stall_after(Duration::from_secs(60));
+        let start = Instant::now();
+
+        let mut status = consumer(5, 1);
+        monitor.assess("ORDERS", &mut status, 10, start);
+        assert!(!status.stalled && !status.lagging);
+
+        // No progress for the stall period
+        monitor.assess("ORDERS", &mut status, 10, start + Duration::from_secs(61));
+        assert!(status.stalled);
+
+        // Progress resets the clock; a backlog over the limit is lagging
+        let mut status = consumer(500, 0);
+        monitor.assess("ORDERS", &mut status, 11, start + Duration::from_secs(62));
+        assert!(!status.stalled && status.lagging);
+
+        // Idle consumers with nothing outstanding are fine
+        let mut status = consumer(0, 0);
+        monitor.assess("ORDERS", &mut status, 11, start + Duration::from_secs(600));
+        assert!(!status.stalled);
+    }
+}
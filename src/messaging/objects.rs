@@ -0,0 +1,193 @@
+//! Blobs in JetStream object store buckets
+//!
+//! For internal artifacts (reports, exports) a service can keep in NATS instead of S3. Objects
+//! are written and read as streams, in chunks, so they never have to fit in memory:
+//!
+//! ```ignore
+//! let exports = NatsObjectStore::open("exports").await?;
+//!
+//! let mut file = tokio::fs::File::open(&path).await?;
+//! exports.put_with("orders/2026-10.csv", Some("Monthly orders export"), &mut file).await?;
+//!
+//! if let Some(mut object) = exports.get("orders/2026-10.csv").await? {
+//!     tokio::io::copy(&mut object, &mut response_body).await?;
+//! }
+//! ```
+//!
+//! Putting an existing name replaces the object. Reads check the object's SHA-256 digest.
+//! Object stores are JetStream streams, so they need a real server: in embedded mode `open`
+//! fails with `Unsupported`. For tenant data, presigned URLs or large media, use `storage`.
+
+use async_nats::jetstream::object_store::{self, GetErrorKind, InfoErrorKind, ObjectMetadata};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::info;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::{NatsClient, NatsError};
+
+fn jetstream_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::JetStreamError(e.to_string())
+}
+
+/// An object being read; implements `tokio::io::AsyncRead`, with its metadata in `info`.
+pub type ObjectReader = object_store::Object<'static>;
+
+/// What is known about a stored object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMeta {
+    pub name: String,
+    pub description: Option<String>,
+    /// Size in bytes
+    pub size: u64,
+    /// `SHA-256=<base64url>` of the content
+    pub digest: Option<String>,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+impl From<object_store::ObjectInfo> for ObjectMeta {
+    fn from(info: object_store::ObjectInfo) -> Self {
+        Self {
+            name: info.name,
+            description: info.description.filter(|d| !d.is_empty()),
+            size: info.size as u64,
+            digest: info.digest,
+            modified: info.modified.map(|t| DateTime::from_timestamp_nanos(t.unix_timestamp_nanos() as i64)),
+        }
+    }
+}
+
+/// An object store bucket; clones share the bucket.
+#[derive(Clone)]
+pub struct NatsObjectStore {
+    bucket: String,
+    store: object_store::ObjectStore,
+}
+
+impl NatsObjectStore {
+    /// The bucket named `bucket`, created with the default settings (file storage, no
+    /// expiry) unless it exists.
+    pub async fn open(bucket: &str) -> Result<Self, NatsError> {
+        Self::open_with(object_store::Config { bucket: bucket.to_string(), ..Default::default() }).await
+    }
+
+    /// The bucket named in `config`, created with `config` (max age, replicas...) unless it
+    /// exists; an existing bucket keeps its settings.
+    pub async fn open_with(config: object_store::Config) -> Result<Self, NatsError> {
+        let jetstream = NatsClient::jetstream()?;
+        let bucket = config.bucket.clone();
+        let store = match jetstream.get_object_store(&bucket).await {
+            Ok(store) => store,
+            Err(_) => {
+                let store = jetstream.create_object_store(config).await.map_err(jetstream_error)?;
+                info!("🗄️ Created object store bucket '{}'", bucket);
+                store
+            }
+        };
+        Ok(Self { bucket, store })
+    }
+
+    /// Name of the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Store everything `data` yields as `name`.
+    pub async fn put(&self, name: &str, data: &mut (impl AsyncRead + Unpin)) -> Result<ObjectMeta, NatsError> {
+        self.put_with(name, None, data).await
+    }
+
+    /// `put` with a human-readable description of the object.
+    pub async fn put_with(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        data: &mut (impl AsyncRead + Unpin),
+    ) -> Result<ObjectMeta, NatsError> {
+        let metadata = ObjectMetadata {
+            name: name.to_string(),
+            description: description.map(str::to_string),
+            ..Default::default()
+        };
+        let stored: ObjectMeta = self.store.put(metadata, data).await.map_err(jetstream_error)?.into();
+        info!("📦 Stored '{}' in '{}' ({} bytes)", name, self.bucket, stored.size);
+        Ok(stored)
+    }
+
+    /// Store `content` as `name`.
+    pub async fn put_bytes(&self, name: &str, content: Bytes) -> Result<ObjectMeta, NatsError> {
+        self.put(name, &mut &content[..]).await
+    }
+
+    /// A reader for `name`, if it exists.
+    pub async fn get(&self, name: &str) -> Result<Option<ObjectReader>, NatsError> {
+        match self.store.get(name).await {
+            Ok(object) => Ok(Some(object)),
+            Err(e) if e.kind() == GetErrorKind::NotFound => Ok(None),
+            Err(e) => Err(jetstream_error(e)),
+        }
+    }
+
+    /// The whole content of `name`, if it exists; use `get` for large objects.
+    pub async fn get_bytes(&self, name: &str) -> Result<Option<Bytes>, NatsError> {
+        let Some(mut object) = self.get(name).await? else {
+            return Ok(None);
+        };
+        let mut content = Vec::with_capacity(object.info.size);
+        object.read_to_end(&mut content).await.map_err(jetstream_error)?;
+        Ok(Some(content.into()))
+    }
+
+    /// The metadata of `name`, if it exists.
+    pub async fn info(&self, name: &str) -> Result<Option<ObjectMeta>, NatsError> {
+        match self.store.info(name).await {
+            Ok(info) if info.deleted => Ok(None),
+            Ok(info) => Ok(Some(info.into())),
+            Err(e) if e.kind() == InfoErrorKind::NotFound => Ok(None),
+            Err(e) => Err(jetstream_error(e)),
+        }
+    }
+
+    /// Remove `name` and its content.
+    pub async fn delete(&self, name: &str) -> Result<(), NatsError> {
+        self.store.delete(name).await.map_err(jetstream_error)
+    }
+
+    /// Every object in the bucket.
+    pub async fn list(&self) -> Result<Vec<ObjectMeta>, NatsError> {
+        let mut objects = self.store.list().await.map_err(jetstream_error)?;
+        let mut all = Vec::new();
+        while let Some(object) = objects.next().await {
+            all.push(object.map_err(jetstream_error)?.into());
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_meta_from_info() {
+        let info = object_store::ObjectInfo {
+            name: "orders/2026-10.csv".to_string(),
+            description: Some(String::new()),
+            options: None,
+            bucket: "exports".to_string(),
+            nuid: "6n5GJ2Y1MUdYbPvp1xtZXq".to_string(),
+            size: 2048,
+            chunks: 1,
+            modified: Some(time::OffsetDateTime::UNIX_EPOCH),
+            digest: Some("SHA-256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU=".to_string()),
+            deleted: false,
+        };
+
+        let meta = ObjectMeta::from(info);
+        assert_eq!(meta.name, "orders/2026-10.csv");
+        assert_eq!(meta.description, None);
+        assert_eq!(meta.size, 2048);
+        assert_eq!(meta.modified, Some(DateTime::UNIX_EPOCH));
+    }
+}
@@ -0,0 +1,152 @@
+//! Replaying stored events
+//!
+//! `NatsClient::replay` reads the envelopes a JetStream stream holds for a subject, oldest
+//! first, from a sequence number or a point in time, to rebuild a read model or backfill a
+//! new service:
+//!
+//! ```ignore
+//! let mut events = NatsClient::replay::<StockAdjustedEvent>("lanai.inventory.stock.>", ReplayStart::Beginning).await?;
+//! while let Some(replayed) = events.next().await {
+//!     let replayed = replayed?;
+//!     projection.apply(&replayed.envelope).await?;
+//!     checkpoint.save(replayed.sequence).await?; // resume with ReplayStart::Sequence(saved + 1)
+//! }
+//! ```
+//!
+//! The replay reads through an ephemeral ordered consumer, which acknowledges nothing and
+//! leaves the stream's durable consumers alone, and ends with the last event stored when it
+//! began. Events are not re-published: other consumers do not see them again (see
+//! `republish` for that). Payloads that are not envelopes of `T` come through as
+//! `DeserializationError`s, so a rebuild can decide whether to stop.
+
+use async_nats::jetstream::consumer::{pull, DeliverPolicy};
+use async_nats::jetstream::response::Response;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{BoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::events::EventEnvelope;
+use super::{consume, NatsClient, NatsError};
+
+fn jetstream_error(e: impl std::fmt::Display) -> NatsError {
+    NatsError::JetStreamError(e.to_string())
+}
+
+/// Where a replay starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayStart {
+    /// The oldest event the stream still holds.
+    Beginning,
+    /// The event stored with this stream sequence number, or the next one.
+    Sequence(u64),
+    /// The first event stored at or after this time.
+    Time(DateTime<Utc>),
+}
+
+impl ReplayStart {
+    fn deliver_policy(self) -> Result<DeliverPolicy, NatsError> {
+        Ok(match self {
+            Self::Beginning => DeliverPolicy::All,
+            Self::Sequence(start_sequence) => DeliverPolicy::ByStartSequence { start_sequence: start_sequence.max(1) },
+            Self::Time(at) => {
+                let nanos = at.timestamp_nanos_opt().ok_or_else(|| jetstream_error("replay start out of range"))?;
+                DeliverPolicy::ByStartTime {
+                    start_time: time::OffsetDateTime::from_unix_timestamp_nanos(nanos as i128).map_err(jetstream_error)?,
+                }
+            }
+        })
+    }
+}
+
+/// A stored event, with where it sits in its stream.
+pub struct ReplayedEvent<T> {
+    pub envelope: EventEnvelope<T>,
+    pub subject: String,
+    /// Stream sequence number; replaying from the next one resumes after this event.
+    pub sequence: u64,
+    /// When the stream stored the event.
+    pub stored_at: DateTime<Utc>,
+    /// `nats_consume` span continuing the publisher's trace, as on `TypedMessage`.
+    pub span: tracing::Span,
+}
+
+/// Events of `NatsClient::replay`.
+pub type Replay<T> = BoxStream<'static, Result<ReplayedEvent<T>, NatsError>>;
+
+#[derive(Deserialize)]
+struct StreamNames {
+    streams: Option<Vec<String>>,
+}
+
+impl NatsClient {
+    /// The stored envelopes on `subject` (wildcards allowed) from `from` on, oldest first.
+    /// Fails unless exactly one stream holds the subject.
+    pub async fn replay<T>(subject: &str, from: ReplayStart) -> Result<Replay<T>, NatsError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let context = Self::jetstream()?;
+        let names = match context
+            .request::<_, _, Response<StreamNames>>("STREAM.NAMES", &serde_json::json!({ "subject": subject }))
+            .await
+            .map_err(jetstream_error)?
+        {
+            Response::Ok(names) => names.streams.unwrap_or_default(),
+            Response::Err { error } => return Err(jetstream_error(error)),
+        };
+        let stream = match names.as_slice() {
+            [stream] => stream,
+            [] => return Err(jetstream_error(format!("no stream holds '{}'", subject))),
+            _ => return Err(jetstream_error(format!("'{}' spans streams {}", subject, names.join(", ")))),
+        };
+
+        let consumer = context
+            .get_stream(stream)
+            .await
+            .map_err(jetstream_error)?
+            .create_consumer(pull::OrderedConfig {
+                filter_subject: subject.to_string(),
+                deliver_policy: from.deliver_policy()?,
+                ..Default::default()
+            })
+            .await
+            .map_err(jetstream_error)?;
+        // Events stored after the replay began are left to live consumers.
+        let pending = consumer.cached_info().num_pending as usize;
+        let messages = consumer.messages().await.map_err(jetstream_error)?;
+
+        Ok(messages.take(pending).map(|message| replayed(message.map_err(jetstream_error)?)).boxed())
+    }
+}
+
+fn replayed<T: DeserializeOwned>(message: async_nats::jetstream::Message) -> Result<ReplayedEvent<T>, NatsError> {
+    let info = message.info().map_err(jetstream_error)?;
+    let sequence = info.stream_sequence;
+    let stored_at = DateTime::from_timestamp_nanos(info.published.unix_timestamp_nanos() as i64);
+    let (envelope, span) = consume(&message.message).map_err(|e| match e {
+        NatsError::DeserializationError(e) => NatsError::DeserializationError(format!("sequence {}: {}", sequence, e)),
+        e => e,
+    })?;
+    Ok(ReplayedEvent { envelope, subject: message.subject.to_string(), sequence, stored_at, span })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_start_policies() {
+        assert!(matches!(ReplayStart::Beginning.deliver_policy(), Ok(DeliverPolicy::All)));
+        assert!(matches!(
+            ReplayStart::Sequence(0).deliver_policy(),
+            Ok(DeliverPolicy::ByStartSequence { start_sequence: 1 })
+        ));
+
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        match ReplayStart::Time(at).deliver_policy() {
+            Ok(DeliverPolicy::ByStartTime { start_time }) => assert_eq!(start_time.unix_timestamp(), 1_700_000_000),
+            other => panic!("unexpected policy {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! Re-publishing stored events for operational recovery
+//!
+//! After a consumer bug corrupts a projection: fix the consumer, reset its read model, and
+//! re-publish the affected events from a JetStream stream or from the event store table.
+//!
+//! ```ignore
+//! let filter = RepublishFilter::new()
+//!     .subject("lanai.inventory.>")
+//!     .org(org_id)
+//!     .since(incident_start)
+//!     .until(incident_end);
+//! let republisher = Republisher::new(Arc::new(JetStreamRepublishSource::new("INVENTORY")));
+//! let preview = republisher.clone().dry_run().run(&filter).await?;
+//! let report = republisher.run(&filter).await?;
+//! ```
+//!
+//! Re-published messages keep their headers, except `Nats-Msg-Id` (JetStream would drop them
+//! as duplicates), and add `Lanai-Replayed: true` and `Lanai-Replay-Of` with the original ID.
+//! The `republish-cli` feature builds `lanai-republish`, the same for JetStream from a shell.
+
+use async_nats::jetstream::{self, consumer::pull, consumer::AckPolicy, consumer::DeliverPolicy};
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, BoxStream};
+use futures_util::{StreamExt, TryStreamExt};
+use log::info;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::bridge::ORG_HEADER;
+use super::dedup::MSG_ID_HEADER;
+use super::NatsClient;
+use crate::eventstore::{EventStore, StoredEvent};
+
+/// Set to `true` on re-published messages.
+pub const REPLAYED_HEADER: &str = "Lanai-Replayed";
+/// The original `Nats-Msg-Id` of a re-published message.
+pub const REPLAY_OF_HEADER: &str = "Lanai-Replay-Of";
+
+/// Republish error types
+#[derive(Debug, Error)]
+pub enum RepublishError {
+    #[error("NATS client not initialized. Call NatsClient::init() first.")]
+    NotInitialized,
+
+    #[error("Failed to read events: {0}")]
+    Source(String),
+
+    #[error("Failed to publish to '{0}': {1}")]
+    Publish(String, String),
+}
+
+/// Which events to re-publish. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct RepublishFilter {
+    /// NATS subject pattern (`*` and `>` wildcards).
+    pub subject: Option<String>,
+    pub org_id: Option<Uuid>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl RepublishFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subject(mut self, pattern: &str) -> Self {
+        self.subject = Some(pattern.to_string());
+        self
+    }
+
+    pub fn org(mut self, org_id: Uuid) -> Self {
+        self.org_id = Some(org_id);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, record: &RepublishRecord) -> bool {
+        self.subject.as_deref().is_none_or(|pattern| subject_matches(pattern, &record.subject))
+            && self.org_id.is_none_or(|org_id| record.org_id == Some(org_id))
+            && self.since.is_none_or(|since| record.occurred_at >= since)
+            && self.until.is_none_or(|until| record.occurred_at < until)
+    }
+}
+
+/// True if `subject` matches the NATS `pattern`.
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut tokens = subject.split('.');
+    for expected in pattern.split('.') {
+        match (expected, tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (expected, Some(token)) if expected == token => {}
+            _ => return false,
+        }
+    }
+    tokens.next().is_none()
+}
+
+/// A stored event as it will be re-published.
+#[derive(Debug, Clone)]
+pub struct RepublishRecord {
+    pub subject: String,
+    pub payload: Bytes,
+    pub headers: Option<HeaderMap>,
+    pub org_id: Option<Uuid>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl RepublishRecord {
+    /// Organization from the `Lanai-Org-Id` header, an `org_id` in the payload (as in
+    /// `EventEnvelope`), or a UUID as the last subject token, in that order.
+    fn detect_org(subject: &str, headers: Option<&HeaderMap>, payload: &[u8]) -> Option<Uuid> {
+        headers
+            .and_then(|h| h.get(ORG_HEADER))
+            .and_then(|v| Uuid::parse_str(v.as_str()).ok())
+            .or_else(|| {
+                serde_json::from_slice::<serde_json::Value>(payload)
+                    .ok()
+                    .and_then(|value| value.get("org_id").and_then(|v| v.as_str()).and_then(|v| Uuid::parse_str(v).ok()))
+            })
+            .or_else(|| subject.rsplit('.').next().and_then(|token| Uuid::parse_str(token).ok()))
+    }
+
+    /// Headers for the re-published message.
+    fn replay_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(original) = &self.headers {
+            for (name, values) in original.iter() {
+                let name = name.to_string();
+                if name.eq_ignore_ascii_case(MSG_ID_HEADER) {
+                    if let Some(id) = values.first() {
+                        headers.insert(REPLAY_OF_HEADER, id.as_str());
+                    }
+                    continue;
+                }
+                for value in values {
+                    headers.append(name.as_str(), value.as_str());
+                }
+            }
+        }
+        headers.insert(REPLAYED_HEADER, "true");
+        headers
+    }
+}
+
+/// Where events are re-published from.
+#[async_trait]
+pub trait RepublishSource: Send + Sync {
+    /// Stored events in their original order. Sources narrow by `filter` where they can;
+    /// `Republisher` applies the full filter to what they return.
+    async fn read(
+        &self,
+        filter: &RepublishFilter,
+    ) -> Result<BoxStream<'static, Result<RepublishRecord, RepublishError>>, RepublishError>;
+}
+
+/// Reads a JetStream stream with an ephemeral consumer, from `since` (or the start of the
+/// stream) to the message that was last when the read began. Requires `NatsClient::init`.
+pub struct JetStreamRepublishSource {
+    stream: String,
+}
+
+impl JetStreamRepublishSource {
+    pub fn new(stream: &str) -> Self {
+        Self { stream: stream.to_string() }
+    }
+}
+
+fn source_error(e: impl std::fmt::Display) -> RepublishError {
+    RepublishError::Source(e.to_string())
+}
+
+#[async_trait]
+impl RepublishSource for JetStreamRepublishSource {
+    async fn read(
+        &self,
+        filter: &RepublishFilter,
+    ) -> Result<BoxStream<'static, Result<RepublishRecord, RepublishError>>, RepublishError> {
+        let client = NatsClient::global().ok_or(RepublishError::NotInitialized)?;
+        let stream = jetstream::new(client).get_stream(&self.stream).await.map_err(source_error)?;
+
+        let deliver_policy = match filter.since.and_then(|since| since.timestamp_nanos_opt()) {
+            Some(nanos) => DeliverPolicy::ByStartTime {
+                start_time: time::OffsetDateTime::from_unix_timestamp_nanos(nanos as i128).map_err(source_error)?,
+            },
+            None => DeliverPolicy::All,
+        };
+        let mut consumer = stream
+            .create_consumer(pull::Config {
+                deliver_policy,
+                filter_subject: filter.subject.clone().unwrap_or_default(),
+                ack_policy: AckPolicy::None,
+                inactive_threshold: Duration::from_secs(60),
+                ..Default::default()
+            })
+            .await
+            .map_err(source_error)?;
+
+        // Messages published after the read began are not part of the recovery.
+        let pending = consumer.info().await.map_err(source_error)?.num_pending as usize;
+        let messages = consumer.messages().await.map_err(source_error)?;
+
+        Ok(messages
+            .take(pending)
+            .map(|message| {
+                let message = message.map_err(source_error)?;
+                let published = message.info().map_err(source_error)?.published;
+                let occurred_at = DateTime::from_timestamp_nanos(published.unix_timestamp_nanos() as i64);
+                let subject = message.subject.to_string();
+                Ok(RepublishRecord {
+                    org_id: RepublishRecord::detect_org(&subject, message.headers.as_ref(), &message.payload),
+                    subject,
+                    payload: message.payload.clone(),
+                    headers: message.headers.clone(),
+                    occurred_at,
+                })
+            })
+            .boxed())
+    }
+}
+
+type SubjectFn = dyn Fn(&StoredEvent) -> Option<String> + Send + Sync;
+
+/// Reads every stream of an `EventStore` in position order. Events are re-published as their
+/// JSON payload on the subject `subject_of` returns; events without one are skipped.
+pub struct EventStoreRepublishSource {
+    store: Arc<dyn EventStore>,
+    subject_of: Arc<SubjectFn>,
+    batch_size: usize,
+}
+
+impl EventStoreRepublishSource {
+    /// Subjects come from the `subject` field of each event's metadata.
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self {
+            store,
+            subject_of: Arc::new(|event: &StoredEvent| event.metadata.get("subject")?.as_str().map(str::to_string)),
+            batch_size: 500,
+        }
+    }
+
+    pub fn subject_of<F>(mut self, subject_of: F) -> Self
+    where
+        F: Fn(&StoredEvent) -> Option<String> + Send + Sync + 'static,
+    {
+        self.subject_of = Arc::new(subject_of);
+        self
+    }
+}
+
+#[async_trait]
+impl RepublishSource for EventStoreRepublishSource {
+    async fn read(
+        &self,
+        _filter: &RepublishFilter,
+    ) -> Result<BoxStream<'static, Result<RepublishRecord, RepublishError>>, RepublishError> {
+        let store = self.store.clone();
+        let subject_of = self.subject_of.clone();
+        let batch_size = self.batch_size;
+        let head = store.head().await.map_err(source_error)?;
+
+        let batches = stream::try_unfold(0i64, move |after| {
+            let store = store.clone();
+            async move {
+                if after >= head {
+                    return Ok(None);
+                }
+                let events = store.read_all(after, batch_size).await.map_err(source_error)?;
+                match events.last().map(|last| last.position) {
+                    Some(next) => Ok(Some((stream::iter(events.into_iter().map(Ok::<_, RepublishError>)), next))),
+                    None => Ok(None),
+                }
+            }
+        });
+
+        Ok(batches
+            .try_flatten()
+            .try_filter_map(move |event: StoredEvent| {
+                let record = subject_of(&event).map(|subject| {
+                    let payload = Bytes::from(serde_json::to_vec(&event.payload).unwrap_or_default());
+                    let org_id = event
+                        .metadata
+                        .get("org_id")
+                        .and_then(|v| v.as_str())
+                        .and_then(|v| Uuid::parse_str(v).ok())
+                        .or_else(|| RepublishRecord::detect_org(&subject, None, &payload));
+                    RepublishRecord { subject, payload, headers: None, org_id, occurred_at: event.recorded_at }
+                });
+                async move { Ok(record) }
+            })
+            .boxed())
+    }
+}
+
+/// Outcome of a run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepublishReport {
+    pub dry_run: bool,
+    /// Events that matched the filter (and were published, unless a dry run).
+    pub matched: usize,
+    /// Events read from the source but outside the filter.
+    pub skipped: usize,
+    pub first_at: Option<DateTime<Utc>>,
+    pub last_at: Option<DateTime<Utc>>,
+}
+
+/// Re-publishes the events of a source that match a filter.
+#[derive(Clone)]
+pub struct Republisher {
+    source: Arc<dyn RepublishSource>,
+    dry_run: bool,
+}
+
+impl Republisher {
+    pub fn new(source: Arc<dyn RepublishSource>) -> Self {
+        Self { source, dry_run: false }
+    }
+
+    /// Count and log what would be re-published without publishing.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    pub async fn run(&self, filter: &RepublishFilter) -> Result<RepublishReport, RepublishError> {
+        if !self.dry_run && !NatsClient::is_initialized() {
+            return Err(RepublishError::NotInitialized);
+        }
+        let mut report = RepublishReport { dry_run: self.dry_run, ..Default::default() };
+        let mut records = self.source.read(filter).await?;
+
+        while let Some(record) = records.next().await {
+            let record = record?;
+            if filter.until.is_some_and(|until| record.occurred_at >= until) {
+                break;
+            }
+            if !filter.matches(&record) {
+                report.skipped += 1;
+                continue;
+            }
+
+            if !self.dry_run {
+                NatsClient::publish_with_headers(&record.subject, record.replay_headers(), record.payload.clone())
+                    .await
+                    .map_err(|e| RepublishError::Publish(record.subject.clone(), e.to_string()))?;
+            }
+            report.first_at.get_or_insert(record.occurred_at);
+            report.last_at = Some(record.occurred_at);
+            report.matched += 1;
+            if filter.limit.is_some_and(|limit| report.matched >= limit) {
+                break;
+            }
+        }
+
+        if let Some(client) = NatsClient::global().filter(|_| !self.dry_run) {
+            client.flush().await.map_err(|e| RepublishError::Publish("flush".to_string(), e.to_string()))?;
+        }
+        info!(
+            "🔁 {} {} events ({} skipped)",
+            if self.dry_run { "Would re-publish" } else { "Re-published" },
+            report.matched,
+            report.skipped
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_matches() {
+        assert!(subject_matches("lanai.inventory.>", "lanai.inventory.product.created.abc"));
+        assert!(subject_matches("lanai.*.product.created.*", "lanai.inventory.product.created.abc"));
+        assert!(!subject_matches("lanai.inventory.*", "lanai.inventory.product.created"));
+        assert!(!subject_matches("lanai.sales.>", "lanai.inventory.product"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_filters_event_store() {
+        use crate::eventstore::{InMemoryEventStore, NewEvent};
+
+        let store = Arc::new(InMemoryEventStore::new());
+        let (org_a, org_b) = (Uuid::new_v4(), Uuid::new_v4());
+        for org_id in [org_a, org_b, org_a] {
+            let event = NewEvent {
+                event_type: "ProductCreated".to_string(),
+                payload: serde_json::json!({ "org_id": org_id }),
+                metadata: serde_json::json!({ "subject": format!("lanai.inventory.product.created.{}", org_id) }),
+            };
+            store.append("product", Uuid::new_v4(), 0, vec![event]).await.unwrap();
+        }
+
+        let source = EventStoreRepublishSource::new(store);
+        let filter = RepublishFilter::new().subject("lanai.inventory.>").org(org_a);
+        let report = Republisher::new(Arc::new(source)).dry_run().run(&filter).await.unwrap();
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.skipped, 1);
+    }
+}
@@ -0,0 +1,246 @@
+//! Routing events to handlers by subject
+//!
+//! An `EventRouter` is to consumers what `ServerBuilder` is to HTTP services: handlers are
+//! registered per subject pattern with the event type they expect, the same middleware wraps
+//! all of them, and `run` consumes until `NatsClient::shutdown`:
+//!
+//! ```ignore
+//! EventRouter::new("inventory")
+//!     .queue_group("inventory")
+//!     .with_inbox(Inbox::new("inventory", idempotency_store))
+//!     .with_retry(RetryPolicy::exponential(3, Duration::from_millis(200)))
+//!     .route("lanai.orders.created.>", |received: TypedMessage<OrderCreatedEvent>| async move {
+//!         reserve_stock(&received.event).await
+//!     })
+//!     .route("lanai.orders.cancelled.>", |received: TypedMessage<OrderCancelledEvent>| async move {
+//!         release_stock(&received.event).await
+//!     })
+//!     .run()
+//!     .await?;
+//! ```
+//!
+//! Every handler runs inside its message's `nats_consume` span, then:
+//!
+//! - with an `Inbox`, only for events the consumer has not processed yet (see `inbox`)
+//! - with a `RetryPolicy`, again after a failure, until it succeeds or the policy gives up
+//!
+//! Payloads that do not deserialize are logged and skipped; handler errors and panics are
+//! logged and affect only their message. A message matching several patterns goes to each of
+//! their handlers.
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, StreamExt};
+use futures_util::FutureExt;
+use log::{error, info, warn};
+use std::fmt::Display;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use super::consumer::panic_message;
+use super::dedup::message_id;
+use super::inbox::Inbox;
+use super::shutdown::ConsumerGuard;
+use super::{consume, NatsClient, NatsError, TypedMessage};
+use crate::resilience::RetryPolicy;
+
+/// Middleware shared by every route.
+struct Middleware {
+    inbox: Option<Inbox>,
+    retry: RetryPolicy<String>,
+}
+
+type Handler = Arc<dyn Fn(async_nats::Message, Arc<Middleware>) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+struct Route {
+    pattern: String,
+    handler: Handler,
+}
+
+/// Handlers for the events of a service, keyed by subject pattern.
+pub struct EventRouter {
+    name: String,
+    queue_group: Option<String>,
+    routes: Vec<Route>,
+    inbox: Option<Inbox>,
+    retry: RetryPolicy<String>,
+    concurrency: usize,
+    drain_timeout: Duration,
+}
+
+impl EventRouter {
+    /// No queue group, middleware or retries; 16 handlers at once.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            queue_group: None,
+            routes: Vec::new(),
+            inbox: None,
+            retry: RetryPolicy::none(),
+            concurrency: 16,
+            drain_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Share the messages with the other members of `group` (the service's replicas).
+    pub fn queue_group(mut self, group: &str) -> Self {
+        self.queue_group = Some(group.to_string());
+        self
+    }
+
+    /// Handle the events on subjects matching `pattern` (`*`/`>` wildcards) with `handler`.
+    pub fn route<T, F, Fut, E>(mut self, pattern: &str, handler: F) -> Self
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+        F: Fn(TypedMessage<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + 'static,
+    {
+        let handler = Arc::new(handler);
+        let handler: Handler = Arc::new(move |message: async_nats::Message, middleware: Arc<Middleware>| {
+            let handler = handler.clone();
+            async move {
+                // Malformed payloads are not retried: another attempt cannot decode them.
+                let mut decoded = Some(consume::<T>(&message).map_err(|e| e.to_string())?);
+                let attempts = || {
+                    let decoded = decoded.take().map_or_else(|| consume::<T>(&message), Ok);
+                    let (handler, message) = (handler.clone(), message.clone());
+                    async move {
+                        let (event, span) = decoded.map_err(|e| e.to_string())?;
+                        let received = TypedMessage { event, message, span: span.clone() };
+                        handler(received).instrument(span).await.map_err(|e| e.to_string())
+                    }
+                };
+                match &middleware.inbox {
+                    Some(inbox) => inbox
+                        .process(message_id(&message).as_deref(), || middleware.retry.run(attempts))
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string()),
+                    None => middleware.retry.run(attempts).await,
+                }
+            }
+            .boxed()
+        });
+        self.routes.push(Route { pattern: pattern.to_string(), handler });
+        self
+    }
+
+    /// Skip events already processed, recording them in `inbox`.
+    pub fn with_inbox(mut self, inbox: Inbox) -> Self {
+        self.inbox = Some(inbox);
+        self
+    }
+
+    /// Retry failed handlers with `policy`.
+    pub fn with_retry(mut self, policy: RetryPolicy<String>) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Maximum number of messages handled at once, across routes.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How long the router waits for handlers in flight once its subscriptions end.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Subscribe to every route and handle messages until `NatsClient::shutdown`.
+    pub async fn run(self) -> Result<(), NatsError> {
+        let mut subscriptions = Vec::with_capacity(self.routes.len());
+        for (index, route) in self.routes.iter().enumerate() {
+            let messages = match &self.queue_group {
+                Some(group) => NatsClient::queue_subscribe(&route.pattern, group).await?,
+                None => NatsClient::subscribe(&route.pattern).await?,
+            };
+            subscriptions.push(messages.map(move |message| (index, message)).boxed());
+        }
+        let _draining = ConsumerGuard::register();
+        info!("📥 Event router '{}' handling {} routes", self.name, self.routes.len());
+
+        let handlers: Vec<Handler> = self.routes.iter().map(|route| route.handler.clone()).collect();
+        let middleware = Arc::new(Middleware { inbox: self.inbox, retry: self.retry });
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let mut messages = stream::select_all(subscriptions);
+        let mut in_flight = JoinSet::new();
+        loop {
+            let permit = permits.clone().acquire_owned().await.expect("permits are never closed");
+            let Some((index, message)) = messages.next().await else { break };
+            while in_flight.try_join_next().is_some() {}
+
+            let (handler, middleware) = (handlers[index].clone(), middleware.clone());
+            in_flight.spawn(async move {
+                let _permit = permit;
+                let subject = message.subject.to_string();
+                match AssertUnwindSafe(handler(message, middleware)).catch_unwind().await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => error!("❌ Handler failed on '{}': {}", subject, e),
+                    Err(panic) => error!("❌ Handler panicked on '{}': {}", subject, panic_message(panic)),
+                }
+            });
+        }
+
+        if tokio::time::timeout(self.drain_timeout, async { while in_flight.join_next().await.is_some() {} })
+            .await
+            .is_err()
+        {
+            warn!("⚠️ Aborting {} handlers of '{}' still running after {:?}", in_flight.len(), self.name, self.drain_timeout);
+        }
+        info!("🛑 Event router '{}' stopped", self.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idempotency::InMemoryIdempotencyStore;
+    use crate::messaging::dedup::MSG_ID_HEADER;
+    use async_nats::HeaderMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_middleware_retries_then_deduplicates() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counted = attempts.clone();
+        let router = EventRouter::new("inventory").route("lanai.orders.created.>", move |received: TypedMessage<u32>| {
+            let attempt = counted.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert_eq!(received.event, 42);
+                if attempt == 0 { Err("stock service down") } else { Ok(()) }
+            }
+        });
+        let middleware = Arc::new(Middleware {
+            inbox: Some(Inbox::new("inventory", Arc::new(InMemoryIdempotencyStore::new()))),
+            retry: RetryPolicy::exponential(2, Duration::ZERO),
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.insert(MSG_ID_HEADER, "evt-1");
+        let message = async_nats::Message {
+            subject: "lanai.orders.created.7".into(),
+            reply: None,
+            payload: "42".into(),
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        };
+
+        let handler = router.routes[0].handler.clone();
+        handler(message.clone(), middleware.clone()).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        handler(message, middleware).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,130 @@
+//! Checking events against their schema on publish and consume
+//!
+//! Events that derive `validator::Validate` (the rules `ValidatedJson` applies to request
+//! bodies) can be checked on both ends of a subject:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize, Validate)]
+//! pub struct StockAdjustedEvent {
+//!     pub product_id: ProductId,
+//!     #[validate(range(min = -10000, max = 10000))]
+//!     pub delta: i32,
+//! }
+//!
+//! NatsClient::publish_validated("lanai.inventory.stock.adjusted", &event).await?;
+//! let mut adjustments = NatsClient::subscribe_validated::<StockAdjustedEvent>("lanai.inventory.stock.>").await?;
+//! ```
+//!
+//! An invalid event is not published: the publish fails with `NatsError::InvalidEvent`. An
+//! inbound message that does not deserialize or validate is quarantined, re-published on
+//! `lanai.quarantine.{subject}` with the reason in `Lanai-Quarantine-Reason`, and comes
+//! through the subscription as an error. Keep quarantined messages with a JetStream stream
+//! on `lanai.quarantine.>`. Both count in `nats_event_validation_failures_total`, labelled
+//! with the `direction` (`publish` or `consume`) and the `event` type.
+
+use futures_util::StreamExt;
+use log::warn;
+use opentelemetry::{global, KeyValue};
+use validator::Validate;
+
+use super::{consume, NatsClient, NatsError, TypedMessage, TypedSubscription};
+
+/// Prefix of the subjects invalid inbound messages are moved to.
+pub const QUARANTINE_PREFIX: &str = "lanai.quarantine";
+/// Why a message was quarantined.
+pub const QUARANTINE_REASON_HEADER: &str = "Lanai-Quarantine-Reason";
+
+/// Check `event` before it is published on `subject`.
+pub fn validate_outbound<T: Validate>(subject: &str, event: &T) -> Result<(), NatsError> {
+    event.validate().map_err(|errors| {
+        record_failure("publish", event_type::<T>());
+        warn!("⚠️ Refusing to publish an invalid {} on '{}': {}", event_type::<T>(), subject, errors);
+        NatsError::InvalidEvent(errors.to_string())
+    })
+}
+
+/// Deserialize and validate an inbound message, quarantining it if either fails.
+pub(crate) async fn validate_inbound<T>(message: async_nats::Message) -> Result<TypedMessage<T>, NatsError>
+where
+    T: serde::de::DeserializeOwned + Validate,
+{
+    let reason = match consume::<T>(&message) {
+        Ok((event, span)) => match event.validate() {
+            Ok(()) => return Ok(TypedMessage { event, message, span }),
+            Err(errors) => errors.to_string(),
+        },
+        Err(e) => e.to_string(),
+    };
+    record_failure("consume", event_type::<T>());
+    warn!("⚠️ Quarantining an invalid {} from '{}': {}", event_type::<T>(), message.subject, reason);
+    quarantine(&message, &reason).await;
+    Err(NatsError::InvalidEvent(reason))
+}
+
+async fn quarantine(message: &async_nats::Message, reason: &str) {
+    let mut headers = message.headers.clone().unwrap_or_default();
+    headers.insert(QUARANTINE_REASON_HEADER, reason.replace(['\r', '\n'], " ").as_str());
+    let subject = format!("{}.{}", QUARANTINE_PREFIX, message.subject);
+    if let Err(e) = NatsClient::publish_with_headers(&subject, headers, message.payload.clone()).await {
+        warn!("⚠️ Failed to quarantine a message from '{}': {}", message.subject, e);
+    }
+}
+
+fn event_type<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn record_failure(direction: &'static str, event: &'static str) {
+    global::meter("lanai.messaging")
+        .u64_counter("nats_event_validation_failures_total")
+        .with_description("Events rejected on publish or quarantined on consume by schema validation")
+        .build()
+        .add(1, &[KeyValue::new("direction", direction), KeyValue::new("event", event)]);
+}
+
+impl NatsClient {
+    /// `publish_event`, failing with `InvalidEvent` instead if `event` does not validate
+    pub async fn publish_validated<T>(subject: &str, event: &T) -> Result<(), NatsError>
+    where
+        T: serde::Serialize + Validate,
+    {
+        validate_outbound(subject, event)?;
+        Self::publish_event(subject, event).await
+    }
+
+    /// `subscribe_typed`, quarantining messages that do not deserialize or validate
+    pub async fn subscribe_validated<T>(subject: &str) -> Result<TypedSubscription<T>, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Validate + Send + 'static,
+    {
+        Ok(Self::subscribe(subject).await?.then(validate_inbound::<T>).boxed())
+    }
+
+    /// `queue_subscribe_typed`, quarantining messages that do not deserialize or validate
+    pub async fn queue_subscribe_validated<T>(subject: &str, group: &str) -> Result<TypedSubscription<T>, NatsError>
+    where
+        T: serde::de::DeserializeOwned + Validate + Send + 'static,
+    {
+        Ok(Self::queue_subscribe(subject, group).await?.then(validate_inbound::<T>).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize, Validate)]
+    struct StockAdjusted {
+        #[validate(range(min = -100, max = 100))]
+        delta: i32,
+    }
+
+    #[test]
+    fn test_invalid_event_is_not_published() {
+        assert!(validate_outbound("lanai.inventory.stock.adjusted", &StockAdjusted { delta: 5 }).is_ok());
+
+        let rejected = validate_outbound("lanai.inventory.stock.adjusted", &StockAdjusted { delta: 500 });
+        assert!(matches!(rejected, Err(NatsError::InvalidEvent(reason)) if reason.contains("delta")));
+    }
+}
@@ -0,0 +1,146 @@
+//! Authentication and TLS for NATS connections
+//!
+//! `NatsConfig::default` reads them from the environment; the first of these that is set
+//! selects the authentication method:
+//!
+//! | Variable | Method |
+//! |---|---|
+//! | `NATS_CREDS_FILE` | `.creds` file with a user JWT and its nkey seed (NGS, operator mode) |
+//! | `NATS_NKEY_SEED` | nkey seed (`SU...`) |
+//! | `NATS_TOKEN` | token |
+//! | `NATS_USER` + `NATS_PASSWORD` | user and password |
+//!
+//! TLS is used when the server asks for it or `NATS_TLS_REQUIRED=true`. `NATS_TLS_CA_FILE`
+//! adds a CA to trust (for a private CA), and `NATS_TLS_CERT_FILE` with `NATS_TLS_KEY_FILE`
+//! present a client certificate (for mutual TLS).
+
+use async_nats::ConnectOptions;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::env::{EnvVar, VarType};
+use crate::secrets::SecretValue;
+
+pub const NATS_CREDS_FILE_ENV: &str = "NATS_CREDS_FILE";
+pub const NATS_NKEY_SEED_ENV: &str = "NATS_NKEY_SEED";
+pub const NATS_TOKEN_ENV: &str = "NATS_TOKEN";
+pub const NATS_USER_ENV: &str = "NATS_USER";
+pub const NATS_PASSWORD_ENV: &str = "NATS_PASSWORD";
+pub const NATS_TLS_REQUIRED_ENV: &str = "NATS_TLS_REQUIRED";
+pub const NATS_TLS_CA_FILE_ENV: &str = "NATS_TLS_CA_FILE";
+pub const NATS_TLS_CERT_FILE_ENV: &str = "NATS_TLS_CERT_FILE";
+pub const NATS_TLS_KEY_FILE_ENV: &str = "NATS_TLS_KEY_FILE";
+
+/// Variables read by `NatsAuth::from_env` and `NatsTls::from_env`
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar::new(NATS_CREDS_FILE_ENV, VarType::String).description("NATS credentials (.creds) file"),
+    EnvVar::new(NATS_NKEY_SEED_ENV, VarType::String).description("NATS nkey seed").secret(),
+    EnvVar::new(NATS_TOKEN_ENV, VarType::String).description("NATS auth token").secret(),
+    EnvVar::new(NATS_USER_ENV, VarType::String).description("NATS user"),
+    EnvVar::new(NATS_PASSWORD_ENV, VarType::String).description("NATS password").secret(),
+    EnvVar::new(NATS_TLS_REQUIRED_ENV, VarType::Bool).description("Always connect to NATS over TLS"),
+    EnvVar::new(NATS_TLS_CA_FILE_ENV, VarType::String).description("CA certificate (PEM) to trust for NATS"),
+    EnvVar::new(NATS_TLS_CERT_FILE_ENV, VarType::String).description("Client certificate (PEM) for NATS mutual TLS"),
+    EnvVar::new(NATS_TLS_KEY_FILE_ENV, VarType::String).description("Client key (PEM) for NATS mutual TLS"),
+];
+
+/// How the client authenticates; `Debug` never prints secrets.
+#[derive(Clone, Default)]
+pub enum NatsAuth {
+    #[default]
+    None,
+    CredentialsFile(PathBuf),
+    NKey(SecretValue),
+    Token(SecretValue),
+    UserPassword { user: String, password: SecretValue },
+}
+
+impl NatsAuth {
+    /// The first method configured by the `NATS_*` variables, in the order of the table above.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        if let Some(path) = var(NATS_CREDS_FILE_ENV) {
+            Self::CredentialsFile(PathBuf::from(path))
+        } else if let Some(seed) = var(NATS_NKEY_SEED_ENV) {
+            Self::NKey(SecretValue::new(seed))
+        } else if let Some(token) = var(NATS_TOKEN_ENV) {
+            Self::Token(SecretValue::new(token))
+        } else if let (Some(user), Some(password)) = (var(NATS_USER_ENV), var(NATS_PASSWORD_ENV)) {
+            Self::UserPassword { user, password: SecretValue::new(password) }
+        } else {
+            Self::None
+        }
+    }
+
+    /// Add the credentials to `options`; fails if the credentials file cannot be read.
+    pub fn apply(&self, options: ConnectOptions) -> std::io::Result<ConnectOptions> {
+        Ok(match self {
+            Self::None => options,
+            Self::CredentialsFile(path) => options.credentials(&std::fs::read_to_string(path)?)?,
+            Self::NKey(seed) => options.nkey(seed.expose().to_string()),
+            Self::Token(token) => options.token(token.expose().to_string()),
+            Self::UserPassword { user, password } => {
+                options.user_and_password(user.clone(), password.expose().to_string())
+            }
+        })
+    }
+}
+
+impl fmt::Debug for NatsAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("None"),
+            Self::CredentialsFile(path) => f.debug_tuple("CredentialsFile").field(path).finish(),
+            Self::NKey(_) => f.write_str("NKey(***)"),
+            Self::Token(_) => f.write_str("Token(***)"),
+            Self::UserPassword { user, .. } => f.debug_struct("UserPassword").field("user", user).finish_non_exhaustive(),
+        }
+    }
+}
+
+/// TLS settings; with none set, TLS is still used if the server requires it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NatsTls {
+    pub required: bool,
+    pub ca_file: Option<PathBuf>,
+    /// Client certificate and key, for mutual TLS
+    pub client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+impl NatsTls {
+    pub fn from_env() -> Self {
+        let path = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty()).map(PathBuf::from);
+        Self {
+            required: std::env::var(NATS_TLS_REQUIRED_ENV)
+                .is_ok_and(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes")),
+            ca_file: path(NATS_TLS_CA_FILE_ENV),
+            client_cert: path(NATS_TLS_CERT_FILE_ENV).zip(path(NATS_TLS_KEY_FILE_ENV)),
+        }
+    }
+
+    pub fn apply(&self, mut options: ConnectOptions) -> ConnectOptions {
+        if let Some(ca_file) = &self.ca_file {
+            options = options.add_root_certificates(ca_file.clone());
+        }
+        if let Some((cert, key)) = &self.client_cert {
+            options = options.add_client_certificate(cert.clone(), key.clone());
+        }
+        // Client certificates are only presented over TLS.
+        options.require_tls(self.required || self.ca_file.is_some() || self.client_cert.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_debug_hides_secrets() {
+        let auth = NatsAuth::UserPassword { user: "billing".to_string(), password: SecretValue::new("hunter2") };
+        let printed = format!("{:?}", auth);
+
+        assert!(printed.contains("billing"));
+        assert!(!printed.contains("hunter2"));
+        assert_eq!(format!("{:?}", NatsAuth::Token(SecretValue::new("s3cr3t"))), "Token(***)");
+    }
+}
@@ -0,0 +1,524 @@
+//! NATS services (the "micro" protocol)
+//!
+//! `NatsService` groups request-reply endpoints under a name and version and answers the
+//! discovery subjects every NATS service framework understands, so `nats micro ls`,
+//! `nats micro info` and `nats micro stats` show Rust services next to Go and Node ones:
+//!
+//! | Subject | Reply |
+//! |---|---|
+//! | `$SRV.PING[.{name}[.{id}]]` | name, id, version, metadata |
+//! | `$SRV.INFO[.{name}[.{id}]]` | plus description and endpoints |
+//! | `$SRV.STATS[.{name}[.{id}]]` | plus request/error counts and processing times per endpoint |
+//!
+//! ```ignore
+//! let _service = NatsService::new("inventory", "1.4.0")
+//!     .description("Products and stock")
+//!     .metadata("team", "core")
+//!     .endpoint(Endpoint::new("product-get", "lanai.inventory.product.get", get_product))
+//!     .start()
+//!     .await?;
+//!
+//! async fn get_product(req: EndpointRequest<GetProduct>) -> Result<ProductDto, LanaiError> {
+//!     let product = load(req.org_id, req.data.product_id).await?;
+//!     Ok(ProductDto::from(product))
+//! }
+//! ```
+//!
+//! Handlers return `ServiceError`s (or `LanaiError`s, converted with `?`), which are sent as
+//! the `Nats-Service-Error` and `Nats-Service-Error-Code` headers. `call` turns them back
+//! into errors on the client side. Works in embedded mode too.
+
+use async_nats::{HeaderMap, Message};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+use super::bridge::ORG_HEADER;
+use super::{extract_trace_context, inject_trace_context, NatsClient, NatsError};
+use crate::error::LanaiError;
+
+pub const SERVICE_ERROR_HEADER: &str = "Nats-Service-Error";
+pub const SERVICE_ERROR_CODE_HEADER: &str = "Nats-Service-Error-Code";
+/// Queue group of endpoints that do not set one, as in the other NATS service frameworks.
+pub const DEFAULT_QUEUE_GROUP: &str = "q";
+
+/// An error reply: a status code (HTTP semantics) and a description.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{code} {description}")]
+pub struct ServiceError {
+    pub code: u16,
+    pub description: String,
+}
+
+impl ServiceError {
+    pub fn new(code: u16, description: impl Into<String>) -> Self {
+        Self { code, description: description.into() }
+    }
+
+    /// The error carried by a reply's headers, if any.
+    pub fn from_reply(message: &Message) -> Option<Self> {
+        let headers = message.headers.as_ref()?;
+        let description = headers.get(SERVICE_ERROR_HEADER)?.as_str().to_string();
+        let code = headers.get(SERVICE_ERROR_CODE_HEADER).and_then(|code| code.as_str().parse().ok()).unwrap_or(500);
+        Some(Self { code, description })
+    }
+}
+
+/// Details of internal errors are logged, never sent.
+impl From<LanaiError> for ServiceError {
+    fn from(e: LanaiError) -> Self {
+        let code = actix_web::ResponseError::status_code(&e).as_u16();
+        match e {
+            LanaiError::Internal(message) => {
+                error!("❌ Service endpoint failed: {}", message);
+                Self::new(code, "Internal server error")
+            }
+            e => Self::new(code, e.to_string()),
+        }
+    }
+}
+
+impl From<ServiceError> for LanaiError {
+    fn from(e: ServiceError) -> Self {
+        match e.code {
+            400 => Self::BadRequest(e.description),
+            401 => Self::Unauthorized(e.description),
+            403 => Self::Forbidden(e.description),
+            404 => Self::NotFound(e.description),
+            409 => Self::Conflict(e.description),
+            408 | 504 => Self::Timeout(e.description),
+            503 => Self::Unavailable(e.description),
+            _ => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<NatsError> for ServiceError {
+    fn from(e: NatsError) -> Self {
+        match e {
+            NatsError::Timeout(..) => Self::new(504, e.to_string()),
+            NatsError::NotInitialized | NatsError::ConnectionError(_) | NatsError::RequestError(_) => {
+                Self::new(503, e.to_string())
+            }
+            e => Self::new(500, e.to_string()),
+        }
+    }
+}
+
+/// A decoded request.
+#[derive(Debug, Clone)]
+pub struct EndpointRequest<T> {
+    pub subject: String,
+    /// Tenant from `Lanai-Org-Id`.
+    pub org_id: Option<Uuid>,
+    pub data: T,
+}
+
+type Handler = Arc<dyn Fn(&Message) -> BoxFuture<'static, Result<Bytes, ServiceError>> + Send + Sync>;
+
+/// One request-reply endpoint of a service.
+pub struct Endpoint {
+    name: String,
+    subject: String,
+    queue_group: String,
+    metadata: BTreeMap<String, String>,
+    handler: Handler,
+}
+
+impl Endpoint {
+    /// JSON in, JSON out. An empty payload decodes as `null`, so `()` and `Option` requests
+    /// need no body; payloads that do not decode are answered with a 400.
+    pub fn new<Req, Res, F, Fut, E>(name: &str, subject: &str, handler: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize,
+        F: Fn(EndpointRequest<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res, E>> + Send + 'static,
+        E: Into<ServiceError>,
+    {
+        let handler = Arc::new(handler);
+        let decode = move |message: &Message| -> Result<Fut, ServiceError> {
+            let payload: &[u8] = if message.payload.is_empty() { b"null" } else { &message.payload };
+            let data = serde_json::from_slice(payload)
+                .map_err(|e| ServiceError::new(400, format!("Invalid request payload: {}", e)))?;
+            let org_id = message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(ORG_HEADER))
+                .and_then(|org_id| Uuid::parse_str(org_id.as_str()).ok());
+            Ok(handler(EndpointRequest { subject: message.subject.to_string(), org_id, data }))
+        };
+        Self::raw(name, subject, move |message| {
+            let future = decode(message);
+            Box::pin(async move {
+                let response = future?.await.map_err(Into::into)?;
+                serde_json::to_vec(&response).map(Bytes::from).map_err(|e| ServiceError::new(500, e.to_string()))
+            })
+        })
+    }
+
+    /// Bytes in, bytes out.
+    pub fn raw<F>(name: &str, subject: &str, handler: F) -> Self
+    where
+        F: Fn(&Message) -> BoxFuture<'static, Result<Bytes, ServiceError>> + Send + Sync + 'static,
+    {
+        assert!(valid_name(name), "invalid endpoint name '{}': use letters, digits, '-' and '_'", name);
+        Self {
+            name: name.to_string(),
+            subject: subject.to_string(),
+            queue_group: DEFAULT_QUEUE_GROUP.to_string(),
+            metadata: BTreeMap::new(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    pub fn queue_group(mut self, queue_group: &str) -> Self {
+        self.queue_group = queue_group.to_string();
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    fn info(&self) -> Value {
+        json!({
+            "name": self.name,
+            "subject": self.subject,
+            "queue_group": self.queue_group,
+            "metadata": self.metadata,
+        })
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `MAJOR.MINOR.PATCH`, optionally with a pre-release or build suffix.
+fn valid_version(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Counters of one endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EndpointStats {
+    pub name: String,
+    pub subject: String,
+    pub queue_group: String,
+    pub num_requests: u64,
+    pub num_errors: u64,
+    pub last_error: String,
+    /// Total, in nanoseconds
+    pub processing_time: u64,
+    /// In nanoseconds
+    pub average_processing_time: u64,
+}
+
+impl EndpointStats {
+    fn record(&mut self, elapsed: Duration, result: &Result<Bytes, ServiceError>) {
+        self.num_requests += 1;
+        self.processing_time += elapsed.as_nanos() as u64;
+        self.average_processing_time = self.processing_time / self.num_requests;
+        if let Err(e) = result {
+            self.num_errors += 1;
+            self.last_error = e.to_string();
+        }
+    }
+}
+
+struct Inner {
+    name: String,
+    id: String,
+    version: String,
+    description: String,
+    metadata: BTreeMap<String, String>,
+    endpoints: Vec<Endpoint>,
+    stats: Vec<Mutex<EndpointStats>>,
+    started: DateTime<Utc>,
+}
+
+impl Inner {
+    fn ping(&self) -> Value {
+        json!({
+            "type": "io.nats.micro.v1.ping_response",
+            "name": self.name,
+            "id": self.id,
+            "version": self.version,
+            "metadata": self.metadata,
+        })
+    }
+
+    fn info(&self) -> Value {
+        let mut info = self.ping();
+        info["type"] = json!("io.nats.micro.v1.info_response");
+        info["description"] = json!(self.description);
+        info["endpoints"] = self.endpoints.iter().map(Endpoint::info).collect();
+        info
+    }
+
+    fn stats(&self) -> Vec<EndpointStats> {
+        self.stats.iter().map(|stats| stats.lock().unwrap_or_else(|e| e.into_inner()).clone()).collect()
+    }
+
+    fn stats_response(&self) -> Value {
+        let mut stats = self.ping();
+        stats["type"] = json!("io.nats.micro.v1.stats_response");
+        stats["started"] = json!(self.started.to_rfc3339());
+        stats["endpoints"] = json!(self.stats());
+        stats
+    }
+}
+
+/// A named, versioned group of endpoints.
+pub struct NatsService {
+    inner: Inner,
+}
+
+impl NatsService {
+    /// Panics unless `name` is letters, digits, `-` and `_` and `version` is semver.
+    pub fn new(name: &str, version: &str) -> Self {
+        assert!(valid_name(name), "invalid service name '{}': use letters, digits, '-' and '_'", name);
+        assert!(valid_version(version), "invalid service version '{}': use MAJOR.MINOR.PATCH", version);
+        Self {
+            inner: Inner {
+                name: name.to_string(),
+                id: Uuid::new_v4().simple().to_string(),
+                version: version.to_string(),
+                description: String::new(),
+                metadata: BTreeMap::new(),
+                endpoints: Vec::new(),
+                stats: Vec::new(),
+                started: Utc::now(),
+            },
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.inner.description = description.to_string();
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.inner.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.inner.endpoints.push(endpoint);
+        self
+    }
+
+    /// Subscribe the endpoints and the discovery subjects.
+    pub async fn start(mut self) -> Result<RunningService, NatsError> {
+        let inner = &mut self.inner;
+        inner.started = Utc::now();
+        inner.stats = inner
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                Mutex::new(EndpointStats {
+                    name: endpoint.name.clone(),
+                    subject: endpoint.subject.clone(),
+                    queue_group: endpoint.queue_group.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let inner = Arc::new(self.inner);
+        let mut tasks = Vec::new();
+
+        for index in 0..inner.endpoints.len() {
+            let endpoint = &inner.endpoints[index];
+            let mut subscriber = NatsClient::queue_subscribe(&endpoint.subject, &endpoint.queue_group).await?;
+            let inner = inner.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let inner = inner.clone();
+                    tokio::spawn(async move { handle(&inner, index, message).await });
+                }
+            }));
+        }
+
+        for subject in discovery_subjects(&inner.name, &inner.id) {
+            let mut subscriber = NatsClient::subscribe(&subject).await?;
+            let inner = inner.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let Some(reply) = message.reply.clone() else { continue };
+                    let response = match message.subject.split('.').nth(1) {
+                        Some("PING") => inner.ping(),
+                        Some("INFO") => inner.info(),
+                        Some("STATS") => inner.stats_response(),
+                        _ => continue,
+                    };
+                    let payload = serde_json::to_vec(&response).unwrap_or_default();
+                    if let Err(e) = NatsClient::publish_with_headers(&reply, HeaderMap::new(), payload.into()).await {
+                        warn!("⚠️ Failed to answer '{}': {}", message.subject, e);
+                    }
+                }
+            }));
+        }
+
+        let endpoints = inner.endpoints.len();
+        info!("🛰️ Service '{}' v{} ({}) serving {} endpoints", inner.name, inner.version, inner.id, endpoints);
+        Ok(RunningService { inner, tasks })
+    }
+}
+
+/// `$SRV.{verb}`, `$SRV.{verb}.{name}` and `$SRV.{verb}.{name}.{id}` for each verb.
+fn discovery_subjects(name: &str, id: &str) -> Vec<String> {
+    ["PING", "INFO", "STATS"]
+        .iter()
+        .flat_map(|verb| {
+            [format!("$SRV.{}", verb), format!("$SRV.{}.{}", verb, name), format!("$SRV.{}.{}.{}", verb, name, id)]
+        })
+        .collect()
+}
+
+async fn handle(inner: &Inner, index: usize, message: Message) {
+    let endpoint = &inner.endpoints[index];
+    let span = tracing::info_span!("nats_service", service = %inner.name, endpoint = %endpoint.name);
+    span.set_parent(extract_trace_context(message.headers.as_ref()));
+
+    let started = Instant::now();
+    let result = (endpoint.handler)(&message).instrument(span).await;
+    inner.stats[index].lock().unwrap_or_else(|e| e.into_inner()).record(started.elapsed(), &result);
+
+    let Some(reply) = message.reply else { return };
+    let mut headers = HeaderMap::new();
+    let payload = match result {
+        Ok(payload) => payload,
+        Err(e) => {
+            headers.insert(SERVICE_ERROR_HEADER, e.description.as_str());
+            headers.insert(SERVICE_ERROR_CODE_HEADER, e.code.to_string().as_str());
+            Bytes::new()
+        }
+    };
+    if let Err(e) = NatsClient::publish_with_headers(&reply, headers, payload).await {
+        error!("❌ Failed to reply on '{}' for '{}': {}", reply, endpoint.subject, e);
+    }
+}
+
+/// A started service; endpoints keep running until `stop`.
+pub struct RunningService {
+    inner: Arc<Inner>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl RunningService {
+    /// Instance id, unique per start.
+    pub fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        self.inner.stats()
+    }
+
+    pub fn stop(self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        info!("🛑 Service '{}' ({}) stopped", self.inner.name, self.inner.id);
+    }
+}
+
+/// Call a service endpoint with a JSON request, within `timeout`. Error replies come back
+/// as the `ServiceError` the handler returned.
+pub async fn call<Req, Res>(
+    subject: &str,
+    request: &Req,
+    org_id: Option<Uuid>,
+    timeout: Duration,
+) -> Result<Res, ServiceError>
+where
+    Req: Serialize,
+    Res: DeserializeOwned,
+{
+    let payload = serde_json::to_vec(request).map_err(|e| ServiceError::new(400, e.to_string()))?;
+    let mut headers = HeaderMap::new();
+    if let Some(org_id) = org_id {
+        headers.insert(ORG_HEADER, org_id.to_string().as_str());
+    }
+    inject_trace_context(&mut headers);
+
+    let reply = tokio::time::timeout(timeout, NatsClient::request_with_headers(subject, headers, payload.into()))
+        .await
+        .map_err(|_| NatsError::Timeout(subject.to_string(), timeout))??;
+    if let Some(e) = ServiceError::from_reply(&reply) {
+        return Err(e);
+    }
+    serde_json::from_slice(&reply.payload).map_err(|e| ServiceError::new(502, format!("Invalid reply: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_responses() {
+        assert!(valid_version("1.4.0") && valid_version("2.0.0-rc.1") && !valid_version("1.4"));
+        assert_eq!(discovery_subjects("inventory", "abc")[2], "$SRV.PING.inventory.abc");
+
+        let service = NatsService::new("inventory", "1.4.0")
+            .metadata("team", "core")
+            .endpoint(Endpoint::new("echo", "lanai.inventory.echo", |req: EndpointRequest<String>| async move {
+                Ok::<_, ServiceError>(req.data)
+            }));
+        let mut inner = service.inner;
+        inner.stats = vec![Mutex::new(EndpointStats { name: "echo".to_string(), ..Default::default() })];
+        inner.stats[0].lock().unwrap().record(Duration::from_micros(3), &Err(ServiceError::new(400, "bad")));
+        inner.stats[0].lock().unwrap().record(Duration::from_micros(1), &Ok(Bytes::new()));
+
+        let info = inner.info();
+        assert_eq!(info["type"], "io.nats.micro.v1.info_response");
+        assert_eq!(info["endpoints"][0]["queue_group"], DEFAULT_QUEUE_GROUP);
+        assert_eq!(info["metadata"]["team"], "core");
+
+        let stats = inner.stats_response();
+        assert_eq!(stats["endpoints"][0]["num_requests"], 2);
+        assert_eq!(stats["endpoints"][0]["average_processing_time"], 2_000);
+        assert_eq!(stats["endpoints"][0]["last_error"], "400 bad");
+    }
+
+    #[tokio::test]
+    async fn test_typed_endpoint() {
+        let endpoint = Endpoint::new("double", "lanai.test.double", |req: EndpointRequest<u32>| async move {
+            if req.data == 0 {
+                return Err(LanaiError::BadRequest("zero".to_string()));
+            }
+            Ok(req.data * 2)
+        });
+        let message = |payload: &'static str| Message {
+            subject: "lanai.test.double".into(),
+            reply: None,
+            payload: Bytes::from_static(payload.as_bytes()),
+            headers: None,
+            status: None,
+            description: None,
+            length: payload.len(),
+        };
+
+        assert_eq!((endpoint.handler)(&message("21")).await.unwrap(), Bytes::from_static(b"42"));
+        assert_eq!((endpoint.handler)(&message("0")).await.unwrap_err(), ServiceError::new(400, "zero"));
+        assert_eq!((endpoint.handler)(&message("x")).await.unwrap_err().code, 400);
+    }
+}
@@ -0,0 +1,132 @@
+//! Draining NATS before the process exits
+//!
+//! `NatsClient::shutdown` (which `ServerBuilder::run` calls once the HTTP server has stopped)
+//! lets a terminated pod finish what it has in flight instead of losing it:
+//!
+//! 1. Subscriptions from `NatsClient::subscribe`/`queue_subscribe` (and everything built on
+//!    them) end, unsubscribing, so the queue group's other members get the messages from
+//!    now on.
+//! 2. Running `QueueSubscriber`s wait for their handlers in flight, and `BatchPublisher`s
+//!    publish the events they hold.
+//! 3. Messages held by the publish buffer are sent.
+//! 4. The client is flushed, so the server has received every publish.
+//!
+//! All of it within one timeout. async-nats closes the connection once its last handle is
+//! dropped, when the process exits.
+
+use log::{info, warn};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use super::{NatsClient, NatsError, EMBEDDED_BROKER, PUBLISH_BUFFER};
+
+/// How long `NatsClient::shutdown` waits for the drain
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Shutdown {
+    requested: watch::Sender<bool>,
+    /// Consumers still draining
+    consumers: watch::Sender<usize>,
+}
+
+fn state() -> &'static Shutdown {
+    static STATE: OnceLock<Shutdown> = OnceLock::new();
+    STATE.get_or_init(|| Shutdown { requested: watch::Sender::new(false), consumers: watch::Sender::new(0) })
+}
+
+/// Whether `NatsClient::shutdown` has started.
+pub fn is_requested() -> bool {
+    *state().requested.borrow()
+}
+
+/// Resolves once `NatsClient::shutdown` starts.
+pub(crate) async fn requested() {
+    let mut requested = state().requested.subscribe();
+    let _ = requested.wait_for(|requested| *requested).await;
+}
+
+/// Held by a consumer or batching task until it has drained; `shutdown` waits for all of them.
+pub(crate) struct ConsumerGuard(());
+
+impl ConsumerGuard {
+    pub(crate) fn register() -> Self {
+        state().consumers.send_modify(|consumers| *consumers += 1);
+        Self(())
+    }
+}
+
+impl Drop for ConsumerGuard {
+    fn drop(&mut self) {
+        state().consumers.send_modify(|consumers| *consumers -= 1);
+    }
+}
+
+impl NatsClient {
+    /// Drain subscriptions and pending publishes, waiting up to `DEFAULT_DRAIN_TIMEOUT`.
+    pub async fn shutdown() -> Result<(), NatsError> {
+        Self::shutdown_within(DEFAULT_DRAIN_TIMEOUT).await
+    }
+
+    /// Drain subscriptions and pending publishes (see the module docs), failing with
+    /// `Timeout` if that takes longer than `timeout`.
+    pub async fn shutdown_within(timeout: Duration) -> Result<(), NatsError> {
+        let deadline = Instant::now() + timeout;
+        let timed_out = |step: &str| NatsError::Timeout(step.to_string(), timeout);
+        info!("🛑 Draining NATS...");
+        state().requested.send_replace(true);
+
+        let mut consumers = state().consumers.subscribe();
+        let _ = tokio::time::timeout_at(deadline, consumers.wait_for(|consumers| *consumers == 0))
+            .await
+            .map_err(|_| timed_out("draining consumers"))?;
+
+        if EMBEDDED_BROKER.get().is_some() {
+            info!("✅ NATS drained");
+            return Ok(());
+        }
+        let client = Self::global().ok_or(NatsError::NotInitialized)?;
+
+        if let Some(buffer) = PUBLISH_BUFFER.get() {
+            tokio::time::timeout_at(deadline, async {
+                // A flush stops at the first failure (or returns if one is already running).
+                while !buffer.is_empty() {
+                    buffer.clone().flush().await;
+                    if !buffer.is_empty() {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            })
+            .await
+            .map_err(|_| {
+                warn!("⚠️ {} buffered messages were not sent before shutdown", buffer.len());
+                timed_out("flushing the publish buffer")
+            })?;
+        }
+
+        tokio::time::timeout_at(deadline, client.flush())
+            .await
+            .map_err(|_| timed_out("flushing the connection"))?
+            .map_err(|e| NatsError::PublishError(e.to_string()))?;
+        info!("✅ NATS drained");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumer_guard_counts_draining_consumers() {
+        let before = *state().consumers.borrow();
+        let first = ConsumerGuard::register();
+        let second = ConsumerGuard::register();
+        assert_eq!(*state().consumers.borrow(), before + 2);
+
+        drop(first);
+        drop(second);
+        assert_eq!(*state().consumers.borrow(), before);
+    }
+}
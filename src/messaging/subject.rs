@@ -0,0 +1,232 @@
+//! Building and parsing event subjects
+//!
+//! Event subjects are hierarchical: `lanai.{domain}.{entity}.{action}`, optionally followed
+//! by the organization and further tokens. `EventSubject` builds them from their parts,
+//! checking every token, instead of `format!`, and reads them back from received messages:
+//!
+//! ```ignore
+//! let subject = EventSubject::new("inventory", "product", "created")?.org(org_id)?;
+//! NatsClient::publish_event(&subject.to_string(), &event).await?;
+//!
+//! // Wildcards, for subscriptions
+//! let all_orgs = EventSubject::new("inventory", "product", "created")?.any();
+//! let whole_domain = EventSubject::whole_domain("inventory")?;
+//!
+//! let received = EventSubject::parse(&message.subject)?;
+//! if received.action() == Some("deleted") { ... }
+//! ```
+//!
+//! Domain, entity and action are lowercase letters, digits, `_` and `-`, like the subjects of
+//! `#[derive(LanaiEvent)]`; other tokens may be any NATS token (no spaces, `.`, `*` or `>`).
+
+use std::fmt;
+use thiserror::Error;
+
+use super::republish::subject_matches;
+
+/// Root token of every event subject.
+pub const SUBJECT_ROOT: &str = "lanai";
+
+/// Why a subject or token was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SubjectError {
+    #[error("Invalid subject token '{0}'")]
+    InvalidToken(String),
+
+    #[error("Subject '{0}' is not a lanai.{{domain}}.{{entity}}.{{action}} subject")]
+    NotAnEventSubject(String),
+
+    #[error("Nothing can follow '>' in subject '{0}'")]
+    AfterFullWildcard(String),
+}
+
+/// An event subject or subscription pattern, without the `lanai` root.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventSubject {
+    tokens: Vec<String>,
+}
+
+impl EventSubject {
+    /// `lanai.{domain}.{entity}.{action}`
+    pub fn new(domain: &str, entity: &str, action: &str) -> Result<Self, SubjectError> {
+        let tokens = [domain, entity, action]
+            .into_iter()
+            .map(|token| fixed_token(token).map(str::to_string))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tokens })
+    }
+
+    /// `lanai.{domain}.>`: every event of a domain.
+    pub fn whole_domain(domain: &str) -> Result<Self, SubjectError> {
+        Ok(Self { tokens: vec![fixed_token(domain)?.to_string(), ">".to_string()] })
+    }
+
+    /// Append the organization's token.
+    pub fn org(self, org_id: impl fmt::Display) -> Result<Self, SubjectError> {
+        self.token(&org_id.to_string())
+    }
+
+    /// Append a token.
+    pub fn token(mut self, token: &str) -> Result<Self, SubjectError> {
+        if self.ends_with_full_wildcard() {
+            return Err(SubjectError::AfterFullWildcard(self.to_string()));
+        }
+        if token.is_empty() || token.contains(['.', '*', '>']) || token.chars().any(char::is_whitespace) {
+            return Err(SubjectError::InvalidToken(token.to_string()));
+        }
+        self.tokens.push(token.to_string());
+        Ok(self)
+    }
+
+    /// Append `*`, matching any single token (e.g. any organization).
+    pub fn any(mut self) -> Self {
+        if !self.ends_with_full_wildcard() {
+            self.tokens.push("*".to_string());
+        }
+        self
+    }
+
+    /// Append `>`, matching one or more further tokens.
+    pub fn rest(mut self) -> Self {
+        if !self.ends_with_full_wildcard() {
+            self.tokens.push(">".to_string());
+        }
+        self
+    }
+
+    /// Read a subject (or pattern) such as `lanai.inventory.product.created.{org_id}`.
+    pub fn parse(subject: &str) -> Result<Self, SubjectError> {
+        let not_an_event = || SubjectError::NotAnEventSubject(subject.to_string());
+        let rest = subject.strip_prefix(SUBJECT_ROOT).and_then(|rest| rest.strip_prefix('.')).ok_or_else(not_an_event)?;
+
+        let mut parsed = Self { tokens: Vec::new() };
+        for (position, token) in rest.split('.').enumerate() {
+            parsed = match token {
+                "*" => parsed.any(),
+                ">" if !parsed.ends_with_full_wildcard() => parsed.rest(),
+                ">" => return Err(SubjectError::AfterFullWildcard(subject.to_string())),
+                _ if position < 3 => {
+                    if parsed.ends_with_full_wildcard() {
+                        return Err(SubjectError::AfterFullWildcard(subject.to_string()));
+                    }
+                    parsed.tokens.push(fixed_token(token)?.to_string());
+                    parsed
+                }
+                _ => parsed.token(token)?,
+            };
+        }
+        if parsed.tokens.len() < 3 && !parsed.ends_with_full_wildcard() {
+            return Err(not_an_event());
+        }
+        Ok(parsed)
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        self.literal(0)
+    }
+
+    pub fn entity(&self) -> Option<&str> {
+        self.literal(1)
+    }
+
+    pub fn action(&self) -> Option<&str> {
+        self.literal(2)
+    }
+
+    /// The token after the action, where event subjects carry the organization.
+    pub fn org_id(&self) -> Option<&str> {
+        self.literal(3)
+    }
+
+    /// Tokens after `lanai`.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.tokens.iter().map(String::as_str)
+    }
+
+    /// Whether this contains `*` or `>`, so it can only be subscribed to.
+    pub fn is_pattern(&self) -> bool {
+        self.tokens.iter().any(|token| token == "*" || token == ">")
+    }
+
+    /// Whether `subject` is matched by this pattern (or is this subject).
+    pub fn matches(&self, subject: &str) -> bool {
+        subject_matches(&self.to_string(), subject)
+    }
+
+    fn literal(&self, index: usize) -> Option<&str> {
+        self.tokens.get(index).map(String::as_str).filter(|token| *token != "*" && *token != ">")
+    }
+
+    fn ends_with_full_wildcard(&self) -> bool {
+        self.tokens.last().is_some_and(|token| token == ">")
+    }
+}
+
+impl fmt::Display for EventSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SUBJECT_ROOT)?;
+        for token in &self.tokens {
+            write!(f, ".{}", token)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for EventSubject {
+    type Err = SubjectError;
+
+    fn from_str(subject: &str) -> Result<Self, Self::Err> {
+        Self::parse(subject)
+    }
+}
+
+impl From<&EventSubject> for async_nats::Subject {
+    fn from(subject: &EventSubject) -> Self {
+        subject.to_string().into()
+    }
+}
+
+/// A domain, entity or action token.
+fn fixed_token(token: &str) -> Result<&str, SubjectError> {
+    let valid = !token.is_empty()
+        && token.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(token)
+    } else {
+        Err(SubjectError::InvalidToken(token.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_parse() {
+        let subject = EventSubject::new("inventory", "product", "created").unwrap().org("0b7e").unwrap();
+        assert_eq!(subject.to_string(), "lanai.inventory.product.created.0b7e");
+        assert_eq!(EventSubject::parse("lanai.inventory.product.created.0b7e").unwrap(), subject);
+        assert_eq!(subject.action(), Some("created"));
+        assert_eq!(subject.org_id(), Some("0b7e"));
+        assert!(!subject.is_pattern());
+
+        assert!(matches!(EventSubject::new("Inventory", "product", "created"), Err(SubjectError::InvalidToken(_))));
+        assert!(matches!(subject.clone().token("a.b"), Err(SubjectError::InvalidToken(_))));
+        assert!(matches!(EventSubject::parse("orders.created"), Err(SubjectError::NotAnEventSubject(_))));
+        assert!(matches!(EventSubject::parse("lanai.inventory.>.x"), Err(SubjectError::AfterFullWildcard(_))));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let all_orgs = EventSubject::new("inventory", "product", "created").unwrap().any();
+        assert!(all_orgs.is_pattern());
+        assert!(all_orgs.matches("lanai.inventory.product.created.0b7e"));
+        assert!(!all_orgs.matches("lanai.inventory.product.deleted.0b7e"));
+
+        let domain = EventSubject::whole_domain("inventory").unwrap();
+        assert_eq!(domain.to_string(), "lanai.inventory.>");
+        assert_eq!(EventSubject::parse("lanai.inventory.>").unwrap(), domain);
+        assert!(domain.matches("lanai.inventory.stock.adjusted.0b7e"));
+        assert_eq!(domain.entity(), None);
+    }
+}
@@ -0,0 +1,308 @@
+//! Usage Metering
+//!
+//! Counts billable operations per tenant and billing period (calendar month, UTC). Every
+//! `record` adds to the period total, which quota checks read, and to a pending delta that
+//! `Meter::flush` drains and publishes as `UsageReported` events on
+//! `lanai.metering.usage.{metric}` for billing to aggregate.
+//!
+//! ```ignore
+//! let meter = Meter::new(Arc::new(RedisUsageStore::new(shared_connection().await?)));
+//! let _flusher = meter.start_flusher(Arc::new(NatsMessageBus), Duration::from_secs(60));
+//!
+//! meter.record(org_id, Metric::StorageBytes, upload.len() as u64).await?;
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::common::{Clock, SystemClock};
+use crate::messaging::{MessageBus, MessageBusExt, NatsError};
+
+pub mod quota;
+pub mod store;
+
+pub use quota::{consume, Plan, QuotaGuard, QuotaProvider, StaticQuotas};
+pub use store::RedisUsageStore;
+
+/// Metering error types
+#[derive(Debug, Error)]
+pub enum MeteringError {
+    #[error("Usage store error: {0}")]
+    Store(String),
+
+    #[error("Failed to publish usage: {0}")]
+    Publish(#[from] NatsError),
+}
+
+/// A billable operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    ApiCalls,
+    Messages,
+    StorageBytes,
+}
+
+impl Metric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ApiCalls => "api_calls",
+            Self::Messages => "messages",
+            Self::StorageBytes => "storage_bytes",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "api_calls" => Some(Self::ApiCalls),
+            "messages" => Some(Self::Messages),
+            "storage_bytes" => Some(Self::StorageBytes),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Billing period key (`2024-03`) containing `at`.
+pub fn period_of(at: DateTime<Utc>) -> String {
+    format!("{:04}-{:02}", at.year(), at.month())
+}
+
+/// Start of the billing period after the one containing `at`.
+pub fn next_period_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = if at.month() == 12 { (at.year() + 1, 1) } else { (at.year(), at.month() + 1) };
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single().unwrap_or(at)
+}
+
+/// Usage accumulated since the last flush.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageDelta {
+    pub org_id: Uuid,
+    pub metric: Metric,
+    pub period: String,
+    pub quantity: u64,
+}
+
+/// Published on every flush, once per tenant, metric and period with new usage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReported {
+    pub org_id: Uuid,
+    pub metric: Metric,
+    pub period: String,
+    pub quantity: u64,
+    pub reported_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    /// Add `amount` to the period total and the pending delta; returns the new period total.
+    async fn increment(&self, org_id: Uuid, metric: Metric, period: &str, amount: u64) -> Result<u64, MeteringError>;
+
+    async fn usage(&self, org_id: Uuid, metric: Metric, period: &str) -> Result<u64, MeteringError>;
+
+    /// Take every pending delta, resetting them to zero.
+    async fn drain(&self) -> Result<Vec<UsageDelta>, MeteringError>;
+
+    /// Put a drained delta back, to be reported by the next flush.
+    async fn restore(&self, delta: &UsageDelta) -> Result<(), MeteringError>;
+}
+
+/// Records usage and reports it. Clones share the store.
+#[derive(Clone)]
+pub struct Meter {
+    store: Arc<dyn UsageStore>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Meter {
+    pub fn new(store: Arc<dyn UsageStore>) -> Self {
+        Self { store, clock: Arc::new(SystemClock) }
+    }
+
+    /// Assign periods with `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn current_period(&self) -> String {
+        period_of(self.clock.utc_now())
+    }
+
+    /// Seconds until the current period ends and quotas reset.
+    pub fn seconds_until_reset(&self) -> u64 {
+        let now = self.clock.utc_now();
+        (next_period_start(now) - now).num_seconds().max(0) as u64
+    }
+
+    /// Count `amount` units of `metric` for `org_id`; returns the period total.
+    pub async fn record(&self, org_id: Uuid, metric: Metric, amount: u64) -> Result<u64, MeteringError> {
+        self.store.increment(org_id, metric, &self.current_period(), amount).await
+    }
+
+    /// Usage of `metric` in the current period.
+    pub async fn usage(&self, org_id: Uuid, metric: Metric) -> Result<u64, MeteringError> {
+        self.store.usage(org_id, metric, &self.current_period()).await
+    }
+
+    /// Publish pending usage. Deltas that fail to publish are restored for the next flush
+    /// and the error is returned after trying the rest.
+    pub async fn flush(&self, bus: &dyn MessageBus) -> Result<usize, MeteringError> {
+        let deltas = self.store.drain().await?;
+        let reported_at = self.clock.utc_now();
+        let mut published = 0;
+        let mut failure = None;
+
+        for delta in deltas {
+            let event = UsageReported {
+                org_id: delta.org_id,
+                metric: delta.metric,
+                period: delta.period.clone(),
+                quantity: delta.quantity,
+                reported_at,
+            };
+            match bus.publish_event(&format!("lanai.metering.usage.{}", event.metric), &event).await {
+                Ok(()) => published += 1,
+                Err(e) => {
+                    error!("❌ Failed to report {} {} for org {}: {}", event.quantity, event.metric, event.org_id, e);
+                    self.store.restore(&delta).await?;
+                    failure = Some(e);
+                }
+            }
+        }
+
+        debug!("📊 Flushed {} usage reports", published);
+        match failure {
+            Some(e) => Err(e.into()),
+            None => Ok(published),
+        }
+    }
+
+    /// Flush every `interval`. Abort the handle to stop.
+    pub fn start_flusher(&self, bus: Arc<dyn MessageBus>, interval: Duration) -> JoinHandle<()> {
+        let meter = self.clone();
+        info!("📊 Reporting usage every {:?}", interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = meter.flush(bus.as_ref()).await {
+                    error!("❌ Usage flush failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Process-local store for tests and single-instance development.
+#[derive(Default)]
+pub struct InMemoryUsageStore {
+    state: Mutex<InMemoryState>,
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    totals: HashMap<(Uuid, Metric, String), u64>,
+    pending: HashMap<(Uuid, Metric, String), u64>,
+}
+
+impl InMemoryUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UsageStore for InMemoryUsageStore {
+    async fn increment(&self, org_id: Uuid, metric: Metric, period: &str, amount: u64) -> Result<u64, MeteringError> {
+        let mut state = self.state.lock().await;
+        let key = (org_id, metric, period.to_string());
+        *state.pending.entry(key.clone()).or_default() += amount;
+        let total = state.totals.entry(key).or_default();
+        *total += amount;
+        Ok(*total)
+    }
+
+    async fn usage(&self, org_id: Uuid, metric: Metric, period: &str) -> Result<u64, MeteringError> {
+        let state = self.state.lock().await;
+        Ok(state.totals.get(&(org_id, metric, period.to_string())).copied().unwrap_or(0))
+    }
+
+    async fn drain(&self) -> Result<Vec<UsageDelta>, MeteringError> {
+        let mut state = self.state.lock().await;
+        Ok(state
+            .pending
+            .drain()
+            .filter(|(_, quantity)| *quantity > 0)
+            .map(|((org_id, metric, period), quantity)| UsageDelta { org_id, metric, period, quantity })
+            .collect())
+    }
+
+    async fn restore(&self, delta: &UsageDelta) -> Result<(), MeteringError> {
+        let mut state = self.state.lock().await;
+        *state.pending.entry((delta.org_id, delta.metric, delta.period.clone())).or_default() += delta.quantity;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_nats::HeaderMap;
+    use bytes::Bytes;
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct Capture(StdMutex<Vec<(String, Bytes)>>);
+
+    #[async_trait]
+    impl MessageBus for Capture {
+        async fn publish_raw(&self, subject: &str, _: HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+            self.0.lock().unwrap().push((subject.to_string(), payload));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_periods() {
+        let at = Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap();
+        assert_eq!(period_of(at), "2024-12");
+        assert_eq!(next_period_start(at), Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_flush_reports_deltas_once() {
+        let meter = Meter::new(Arc::new(InMemoryUsageStore::new()));
+        let org = Uuid::new_v4();
+        meter.record(org, Metric::ApiCalls, 1).await.unwrap();
+        assert_eq!(meter.record(org, Metric::ApiCalls, 2).await.unwrap(), 3);
+
+        let bus = Capture::default();
+        assert_eq!(meter.flush(&bus).await.unwrap(), 1);
+        assert_eq!(meter.flush(&bus).await.unwrap(), 0);
+
+        let event: UsageReported = {
+            let published = bus.0.lock().unwrap();
+            assert_eq!(published[0].0, "lanai.metering.usage.api_calls");
+            serde_json::from_slice(&published[0].1).unwrap()
+        };
+        assert_eq!(event.quantity, 3);
+        assert_eq!(meter.usage(org, Metric::ApiCalls).await.unwrap(), 3);
+    }
+}
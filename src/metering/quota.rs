@@ -0,0 +1,212 @@
+//! Plan quotas and the `QuotaGuard` middleware
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, ResponseError,
+};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{Meter, Metric};
+use crate::error::LanaiError;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Per-period limits of a subscription plan. Metrics without a limit are unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub name: String,
+    pub limits: HashMap<Metric, u64>,
+}
+
+impl Plan {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), limits: HashMap::new() }
+    }
+
+    pub fn limit(mut self, metric: Metric, limit: u64) -> Self {
+        self.limits.insert(metric, limit);
+        self
+    }
+}
+
+/// Resolves the plan a tenant is on.
+#[async_trait]
+pub trait QuotaProvider: Send + Sync {
+    /// `None` means no quotas apply.
+    async fn plan_for(&self, org_id: Uuid) -> Option<Plan>;
+}
+
+/// Fixed plan assignments, with a default for unassigned tenants.
+#[derive(Debug, Clone, Default)]
+pub struct StaticQuotas {
+    default: Option<Plan>,
+    assignments: HashMap<Uuid, Plan>,
+}
+
+impl StaticQuotas {
+    pub fn new(default: Option<Plan>) -> Self {
+        Self { default, assignments: HashMap::new() }
+    }
+
+    pub fn assign(mut self, org_id: Uuid, plan: Plan) -> Self {
+        self.assignments.insert(org_id, plan);
+        self
+    }
+}
+
+#[async_trait]
+impl QuotaProvider for StaticQuotas {
+    async fn plan_for(&self, org_id: Uuid) -> Option<Plan> {
+        self.assignments.get(&org_id).or(self.default.as_ref()).cloned()
+    }
+}
+
+/// Record `amount` of `metric` unless it would exceed the tenant's quota. Usage store
+/// failures fail open (logged), like rate limiting.
+///
+/// ```ignore
+/// consume(&meter, quotas.as_ref(), tenant.org_id, Metric::StorageBytes, file.len() as u64).await?;
+/// ```
+pub async fn consume(
+    meter: &Meter,
+    quotas: &dyn QuotaProvider,
+    org_id: Uuid,
+    metric: Metric,
+    amount: u64,
+) -> Result<(), LanaiError> {
+    let limit = quotas.plan_for(org_id).await.and_then(|plan| plan.limits.get(&metric).copied());
+
+    if let Some(limit) = limit {
+        match meter.usage(org_id, metric).await {
+            Ok(used) if used.saturating_add(amount) > limit => {
+                return Err(LanaiError::QuotaExceeded {
+                    metric: metric.to_string(),
+                    limit,
+                    retry_after_secs: Some(meter.seconds_until_reset()),
+                });
+            }
+            Ok(_) => {}
+            Err(e) => warn!("⚠️ Could not read {} usage for org {}, allowing: {}", metric, org_id, e),
+        }
+    }
+
+    if let Err(e) = meter.record(org_id, metric, amount).await {
+        warn!("⚠️ Failed to meter {} {} for org {}: {}", amount, metric, org_id, e);
+    }
+    Ok(())
+}
+
+/// Counts each request as one `Metric::ApiCalls` for the tenant and answers 429
+/// `quota_exceeded` once the plan's limit for the period is reached. Requests without a
+/// `TenantContext` and health/internal routes are neither counted nor limited.
+///
+/// Needs the tenant resolved, so register it before `TenantMiddleware` (actix runs the
+/// last `wrap` first):
+///
+/// ```ignore
+/// App::new()
+///     .wrap(QuotaGuard::new(meter.clone(), Arc::new(quotas)))
+///     .wrap(TenantMiddleware)
+/// ```
+pub struct QuotaGuard {
+    meter: Meter,
+    quotas: Arc<dyn QuotaProvider>,
+}
+
+impl QuotaGuard {
+    pub fn new(meter: Meter, quotas: Arc<dyn QuotaProvider>) -> Self {
+        Self { meter, quotas }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for QuotaGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = QuotaGuardService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(QuotaGuardService {
+            service: Rc::new(service),
+            meter: self.meter.clone(),
+            quotas: Arc::clone(&self.quotas),
+        }))
+    }
+}
+
+pub struct QuotaGuardService<S> {
+    service: Rc<S>,
+    meter: Meter,
+    quotas: Arc<dyn QuotaProvider>,
+}
+
+impl<S, B> Service<ServiceRequest> for QuotaGuardService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let meter = self.meter.clone();
+        let quotas = Arc::clone(&self.quotas);
+
+        Box::pin(async move {
+            let path = req.path();
+            let exempt = path.starts_with("/internal") || path.starts_with("/health") || path.starts_with("/metrics");
+            let tenant = req.extensions().get::<TenantContext>().copied();
+
+            if let (false, Some(tenant)) = (exempt, tenant) {
+                if let Err(e) = consume(&meter, quotas.as_ref(), tenant.org_id, Metric::ApiCalls, 1).await {
+                    return Ok(req.into_response(e.error_response()));
+                }
+            }
+
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metering::InMemoryUsageStore;
+
+    #[tokio::test]
+    async fn test_consume_enforces_plan_limit() {
+        let meter = Meter::new(Arc::new(InMemoryUsageStore::new()));
+        let org = Uuid::new_v4();
+        let quotas = StaticQuotas::new(Some(Plan::new("free").limit(Metric::ApiCalls, 2)));
+
+        for _ in 0..2 {
+            consume(&meter, &quotas, org, Metric::ApiCalls, 1).await.unwrap();
+        }
+        let err = consume(&meter, &quotas, org, Metric::ApiCalls, 1).await.unwrap_err();
+        assert_eq!(err.code(), "quota_exceeded");
+        assert_eq!(meter.usage(org, Metric::ApiCalls).await.unwrap(), 2);
+
+        // Unlimited metrics are still counted.
+        consume(&meter, &quotas, org, Metric::Messages, 5).await.unwrap();
+        assert_eq!(meter.usage(org, Metric::Messages).await.unwrap(), 5);
+    }
+}
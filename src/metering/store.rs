@@ -0,0 +1,121 @@
+//! Redis-backed usage store
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use uuid::Uuid;
+
+use super::{MeteringError, Metric, UsageDelta, UsageStore};
+
+const DIRTY_KEY: &str = "lanai:metering:dirty";
+
+/// Period totals live under `lanai:metering:{org}:{metric}:{period}` (kept for 90 days),
+/// pending deltas under `lanai:metering:pending:...`, and `lanai:metering:dirty` is the set of
+/// deltas waiting to be flushed, so any replica's flusher reports every replica's usage.
+pub struct RedisUsageStore {
+    conn: ConnectionManager,
+}
+
+impl RedisUsageStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    fn member(org_id: Uuid, metric: Metric, period: &str) -> String {
+        format!("{}:{}:{}", org_id, metric, period)
+    }
+
+    fn total_key(member: &str) -> String {
+        format!("lanai:metering:{}", member)
+    }
+
+    fn pending_key(member: &str) -> String {
+        format!("lanai:metering:pending:{}", member)
+    }
+
+    fn parse_member(member: &str) -> Option<(Uuid, Metric, String)> {
+        let mut parts = member.splitn(3, ':');
+        let org_id = parts.next()?.parse().ok()?;
+        let metric = Metric::parse(parts.next()?)?;
+        Some((org_id, metric, parts.next()?.to_string()))
+    }
+}
+
+fn store_error(e: redis::RedisError) -> MeteringError {
+    MeteringError::Store(e.to_string())
+}
+
+#[async_trait]
+impl UsageStore for RedisUsageStore {
+    async fn increment(&self, org_id: Uuid, metric: Metric, period: &str, amount: u64) -> Result<u64, MeteringError> {
+        let member = Self::member(org_id, metric, period);
+        let total_key = Self::total_key(&member);
+        let (total, _, _, _): (u64, i64, u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("INCRBY").arg(&total_key).arg(amount)
+            .cmd("EXPIRE").arg(&total_key).arg(90 * 24 * 3600)
+            .cmd("INCRBY").arg(Self::pending_key(&member)).arg(amount)
+            .cmd("SADD").arg(DIRTY_KEY).arg(&member)
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        Ok(total)
+    }
+
+    async fn usage(&self, org_id: Uuid, metric: Metric, period: &str) -> Result<u64, MeteringError> {
+        let total: Option<u64> = redis::cmd("GET")
+            .arg(Self::total_key(&Self::member(org_id, metric, period)))
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        Ok(total.unwrap_or(0))
+    }
+
+    async fn drain(&self) -> Result<Vec<UsageDelta>, MeteringError> {
+        let mut conn = self.conn.clone();
+        let mut deltas = Vec::new();
+
+        loop {
+            // An increment racing with this re-adds its member, so nothing is skipped.
+            let members: Vec<String> = redis::cmd("SPOP")
+                .arg(DIRTY_KEY)
+                .arg(100)
+                .query_async(&mut conn)
+                .await
+                .map_err(store_error)?;
+            if members.is_empty() {
+                break;
+            }
+
+            for member in members {
+                let Some((org_id, metric, period)) = Self::parse_member(&member) else {
+                    log::warn!("⚠️ Ignoring malformed metering member '{}'", member);
+                    continue;
+                };
+                let pending_key = Self::pending_key(&member);
+                let (quantity, _): (Option<u64>, i64) = redis::pipe()
+                    .atomic()
+                    .cmd("GET").arg(&pending_key)
+                    .cmd("DEL").arg(&pending_key)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(store_error)?;
+                if let Some(quantity) = quantity.filter(|q| *q > 0) {
+                    deltas.push(UsageDelta { org_id, metric, period, quantity });
+                }
+            }
+        }
+
+        Ok(deltas)
+    }
+
+    async fn restore(&self, delta: &UsageDelta) -> Result<(), MeteringError> {
+        let member = Self::member(delta.org_id, delta.metric, &delta.period);
+        redis::pipe()
+            .atomic()
+            .cmd("INCRBY").arg(Self::pending_key(&member)).arg(delta.quantity)
+            .cmd("SADD").arg(DIRTY_KEY).arg(&member)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)
+    }
+}
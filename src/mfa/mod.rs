@@ -0,0 +1,341 @@
+//! Two-factor authentication primitives
+//!
+//! TOTP (RFC 6238) as understood by authenticator apps (Google Authenticator, 1Password,
+//! Authy...): HMAC-SHA1, 6 digits, 30 second steps. Enrollment stores a `TotpSecret`,
+//! shows its provisioning URI as a QR code and confirms with a first code; login verifies
+//! codes with a small clock-drift window. Recovery codes are shown once and only their
+//! hashes are stored:
+//!
+//! ```ignore
+//! // enrollment
+//! let secret = TotpSecret::generate();
+//! let totp = Totp::new(secret.clone());
+//! let qr_payload = totp.provisioning_uri("Lanai", &user.email);
+//! save_pending_secret(user.id, secret.to_base32()).await?;
+//!
+//! // login: remember the accepted step so a code cannot be replayed
+//! let totp = Totp::new(TotpSecret::from_base32(&stored.secret)?);
+//! let step = totp.verify_after(&form.code, stored.last_step)?;
+//! update_last_step(user.id, step).await?;
+//!
+//! // recovery codes
+//! let codes = RecoveryCodes::generate(10);
+//! store_hashes(user.id, codes.hashes()).await?;
+//! if let Some(index) = verify_recovery_code(&form.code, &stored_hashes) { /* burn stored_hashes[index] */ }
+//! ```
+
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::error::LanaiError;
+
+type HmacSha1 = Hmac<Sha1>;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+/// 160 bits, the size RFC 4226 recommends for HMAC-SHA1.
+const SECRET_LEN: usize = 20;
+/// Recovery codes avoid characters that are easy to confuse (0/O, 1/I/L).
+const RECOVERY_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+/// MFA error types
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MfaError {
+    #[error("Invalid TOTP secret: {0}")]
+    InvalidSecret(String),
+
+    #[error("Invalid verification code")]
+    InvalidCode,
+
+    /// The code is valid but its time step was already used.
+    #[error("Verification code was already used")]
+    CodeReused,
+}
+
+impl From<MfaError> for LanaiError {
+    fn from(err: MfaError) -> Self {
+        match err {
+            MfaError::InvalidSecret(_) => LanaiError::internal(err.to_string()),
+            MfaError::InvalidCode | MfaError::CodeReused => LanaiError::Unauthorized("Invalid verification code".to_string()),
+        }
+    }
+}
+
+/// Shared TOTP key. Store it encrypted; `Debug` does not print it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn generate() -> Self {
+        let mut bytes = vec![0u8; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+
+    /// Accepts lower case, spaces and `=` padding, as users type secrets in by hand.
+    pub fn from_base32(encoded: &str) -> Result<Self, MfaError> {
+        let bytes = base32_decode(encoded).ok_or_else(|| MfaError::InvalidSecret("not valid base32".to_string()))?;
+        if bytes.len() < 10 {
+            return Err(MfaError::InvalidSecret(format!("secret must be at least 80 bits, got {}", bytes.len() * 8)));
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Unpadded base32, the form authenticator apps expect.
+    pub fn to_base32(&self) -> String {
+        base32_encode(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret(..)")
+    }
+}
+
+/// TOTP generator and verifier for one secret.
+#[derive(Debug, Clone)]
+pub struct Totp {
+    secret: TotpSecret,
+    digits: u32,
+    period: u64,
+    skew: u64,
+}
+
+impl Totp {
+    /// 6 digits, 30 second steps, accepting codes one step either side of now.
+    pub fn new(secret: TotpSecret) -> Self {
+        Self { secret, digits: 6, period: 30, skew: 1 }
+    }
+
+    /// 6 to 8; most apps only support 6.
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(6, 8);
+        self
+    }
+
+    pub fn period(mut self, seconds: u64) -> Self {
+        self.period = seconds.max(1);
+        self
+    }
+
+    /// Steps of clock drift tolerated in each direction.
+    pub fn skew(mut self, steps: u64) -> Self {
+        self.skew = steps;
+        self
+    }
+
+    /// Time step of a Unix timestamp.
+    pub fn step_at(&self, unix_secs: u64) -> u64 {
+        unix_secs / self.period
+    }
+
+    /// The code for a time step (RFC 4226 HOTP with the step as counter).
+    pub fn code_for_step(&self, step: u64) -> String {
+        let mut mac = HmacSha1::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+        format!("{:0width$}", binary % 10u32.pow(self.digits), width = self.digits as usize)
+    }
+
+    pub fn code_at(&self, unix_secs: u64) -> String {
+        self.code_for_step(self.step_at(unix_secs))
+    }
+
+    pub fn current_code(&self) -> String {
+        self.code_at(now())
+    }
+
+    /// The time step `code` matches at `unix_secs`, within the drift window. Spaces and
+    /// dashes in the code are ignored.
+    pub fn verify_at(&self, code: &str, unix_secs: u64) -> Result<u64, MfaError> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        if code.len() != self.digits as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(MfaError::InvalidCode);
+        }
+        let current = self.step_at(unix_secs);
+        let mut matched = None;
+        // Check the whole window so timing does not reveal which step matched.
+        for step in current.saturating_sub(self.skew)..=current + self.skew {
+            if constant_time_eq(self.code_for_step(step).as_bytes(), code.as_bytes()) {
+                matched = Some(step);
+            }
+        }
+        matched.ok_or(MfaError::InvalidCode)
+    }
+
+    pub fn verify(&self, code: &str) -> Result<u64, MfaError> {
+        self.verify_at(code, now())
+    }
+
+    /// `verify`, rejecting codes whose step is not after the last accepted one, so an
+    /// observed code cannot be used a second time.
+    pub fn verify_after(&self, code: &str, last_step: Option<u64>) -> Result<u64, MfaError> {
+        let step = self.verify(code)?;
+        match last_step {
+            Some(last) if step <= last => Err(MfaError::CodeReused),
+            _ => Ok(step),
+        }
+    }
+
+    /// `otpauth://totp/{issuer}:{account}?secret=...`, the payload of the enrollment QR code.
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            uri_encode(issuer),
+            uri_encode(account),
+            self.secret.to_base32(),
+            uri_encode(issuer),
+            self.digits,
+            self.period
+        )
+    }
+}
+
+/// One-time recovery codes, formatted `xxxxx-xxxxx` (about 50 bits each).
+#[derive(Debug, Clone)]
+pub struct RecoveryCodes {
+    codes: Vec<String>,
+}
+
+impl RecoveryCodes {
+    pub fn generate(count: usize) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut part = || -> String {
+            (0..5).map(|_| RECOVERY_ALPHABET[rng.gen_range(0..RECOVERY_ALPHABET.len())] as char).collect()
+        };
+        let codes = (0..count).map(|_| format!("{}-{}", part(), part())).collect();
+        Self { codes }
+    }
+
+    /// The codes to show the user, once.
+    pub fn codes(&self) -> &[String] {
+        &self.codes
+    }
+
+    /// What to store, in the same order as `codes`.
+    pub fn hashes(&self) -> Vec<String> {
+        self.codes.iter().map(|code| hash_recovery_code(code)).collect()
+    }
+}
+
+/// Hex SHA-256 of the normalized code (lower case, without spaces or dashes). Codes are
+/// random, so a fast hash is enough.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String =
+        code.chars().filter(|c| !c.is_whitespace() && *c != '-').map(|c| c.to_ascii_lowercase()).collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Index of the stored hash `code` matches; the caller deletes it so it cannot be reused.
+pub fn verify_recovery_code(code: &str, hashes: &[String]) -> Option<usize> {
+    let hash = hash_recovery_code(code);
+    let mut matched = None;
+    for (index, stored) in hashes.iter().enumerate() {
+        if constant_time_eq(stored.as_bytes(), hash.as_bytes()) {
+            matched = Some(index);
+        }
+    }
+    matched
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=' && *c != '-') {
+        let value = BASE32_ALPHABET.iter().position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_rfc6238_vectors_and_drift() {
+        let totp = Totp::new(TotpSecret::from_bytes(b"12345678901234567890")).digits(8);
+        assert_eq!(totp.code_at(59), "94287082");
+        assert_eq!(totp.code_at(1111111109), "07081804");
+        assert_eq!(totp.code_at(2000000000), "69279037");
+
+        let totp = Totp::new(TotpSecret::from_bytes(b"12345678901234567890"));
+        let code = totp.code_at(1_000_000);
+        assert_eq!(totp.verify_at(&code, 1_000_000 + 30), Ok(totp.step_at(1_000_000)));
+        assert_eq!(totp.verify_at(&code, 1_000_000 + 90), Err(MfaError::InvalidCode));
+        assert_eq!(totp.verify_at("12345", 1_000_000), Err(MfaError::InvalidCode));
+
+        let secret = TotpSecret::from_base32("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+        assert_eq!(secret.as_bytes(), b"12345678901234567890");
+        assert_eq!(secret.to_base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            Totp::new(secret).provisioning_uri("Lanai POS", "ana@lanai.io"),
+            "otpauth://totp/Lanai%20POS:ana%40lanai.io?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Lanai%20POS&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = RecoveryCodes::generate(10);
+        let hashes = codes.hashes();
+        assert_eq!(codes.codes().len(), 10);
+        assert_eq!(codes.codes()[0].len(), 11);
+        assert_eq!(verify_recovery_code(&codes.codes()[3].to_uppercase().replace('-', " "), &hashes), Some(3));
+        assert_eq!(verify_recovery_code("aaaaa-aaaaa", &hashes), None);
+    }
+}
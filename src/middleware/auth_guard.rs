@@ -1,14 +1,21 @@
 use actix_web::{
     body::{BoxBody, MessageBody},
     dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
     Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::{ok, LocalBoxFuture, Ready};
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, TokenData, Validation};
 use serde::{Deserialize, Serialize};
-use std::{rc::Rc, sync::Arc};
+use std::{rc::Rc, sync::Arc, time::Duration};
 use log::{warn, error};
 
+use crate::common::{Clock, SystemClock};
+
+/// Seconds until the access token expires, set on responses once it is within the
+/// guard's refresh window so clients can refresh before they get a 401.
+pub const TOKEN_EXPIRES_IN_HEADER: &str = "x-token-expires-in";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String,
@@ -25,6 +32,10 @@ pub struct Claims {
 
 pub struct AuthGuard {
     pub public_key_pem: String,
+    insecure: bool,
+    leeway: Duration,
+    refresh_window: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl AuthGuard {
@@ -32,6 +43,40 @@ impl AuthGuard {
     pub fn new(public_key_pem: String) -> Self {
         Self {
             public_key_pem,
+            insecure: false,
+            leeway: Duration::from_secs(60),
+            refresh_window: Duration::from_secs(300),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Clock skew tolerated on `exp` (default 60 seconds).
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Set `X-Token-Expires-In` on responses when the token expires within `window`
+    /// (default 5 minutes). `Duration::ZERO` disables the header.
+    pub fn refresh_window(mut self, window: Duration) -> Self {
+        self.refresh_window = window;
+        self
+    }
+
+    /// Check `exp` against `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Accept any well-formed token without checking its signature (issuer, subject and
+    /// expiry are still checked), so local development works without the auth service's keys.
+    /// Only compiled with the `insecure-dev-auth` feature.
+    #[cfg(feature = "insecure-dev-auth")]
+    pub fn insecure_dev_mode() -> Self {
+        Self {
+            insecure: true,
+            ..Self::new(String::new())
         }
     }
 }
@@ -49,6 +94,24 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&["lanai-auth"]);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        // Expiry is checked against `clock` once the token is decoded
+        validation.validate_exp = false;
+
+        if self.insecure {
+            warn!("🚨 AuthGuard is in insecure dev mode: token signatures are NOT verified");
+            return ok(AuthGuardMiddleware {
+                service: Rc::new(service),
+                decoding_key: None,
+                validation: Arc::new(validation),
+                leeway: self.leeway,
+                refresh_window: self.refresh_window,
+                clock: self.clock.clone(),
+            });
+        }
+
         // Support for single-line env variables with \n
         let pem_str = self.public_key_pem.replace("\\n", "\n");
         let decoding_key = match DecodingKey::from_rsa_pem(pem_str.as_bytes()) {
@@ -62,14 +125,23 @@ where
 
         ok(AuthGuardMiddleware {
             service: Rc::new(service),
-            decoding_key: Arc::new(decoding_key),
+            decoding_key: Some(Arc::new(decoding_key)),
+            validation: Arc::new(validation),
+            leeway: self.leeway,
+            refresh_window: self.refresh_window,
+            clock: self.clock.clone(),
         })
     }
 }
 
 pub struct AuthGuardMiddleware<S> {
     service: Rc<S>,
-    decoding_key: Arc<DecodingKey>,
+    /// `None` in insecure dev mode, where signatures are not verified.
+    decoding_key: Option<Arc<DecodingKey>>,
+    validation: Arc<Validation>,
+    leeway: Duration,
+    refresh_window: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl<S, B> Service<ServiceRequest> for AuthGuardMiddleware<S>
@@ -89,6 +161,10 @@ where
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
         let decoding_key = self.decoding_key.clone();
+        let validation = self.validation.clone();
+        let leeway = self.leeway.as_secs() as i64;
+        let refresh_window = self.refresh_window;
+        let now = self.clock.utc_now().timestamp();
 
         Box::pin(async move {
             // Allow OPTIONS for CORS preflight
@@ -111,38 +187,81 @@ where
                 }
             };
 
-            let mut validation = Validation::new(Algorithm::RS256);
-            validation.set_issuer(&["lanai-auth"]);
-            validation.set_required_spec_claims(&["exp", "sub"]);
-
-            match decode::<Claims>(&token, &decoding_key, &validation) {
+            let decoded = decode_claims(&token, decoding_key.as_deref(), &validation).and_then(|token_data| {
+                if token_data.claims.exp + leeway < now {
+                    return Err(ErrorKind::ExpiredSignature.into());
+                }
+                Ok(token_data)
+            });
+            match decoded {
                 Ok(token_data) => {
+                    let expires_in = token_data.claims.exp - now;
                     req.extensions_mut().insert(token_data.claims);
-                    let res = service.call(req).await?;
+                    let mut res = service.call(req).await?;
+                    if !refresh_window.is_zero() && expires_in <= refresh_window.as_secs() as i64 {
+                        // Within the leeway a token may be past `exp`; report 0 rather than a negative count
+                        res.headers_mut().insert(
+                            HeaderName::from_static(TOKEN_EXPIRES_IN_HEADER),
+                            HeaderValue::from(expires_in.max(0)),
+                        );
+                    }
                     Ok(res.map_into_boxed_body())
                 }
                 Err(e) => {
                     warn!("Token validation failed for path {}: {}", req.path(), e);
-                    Ok(req.into_response(
-                        HttpResponse::Unauthorized()
-                            .json(serde_json::json!({
-                                "error": format!("Invalid or expired token: {}", e),
-                                "code": "AUTH_INVALID_TOKEN"
-                            }))
-                    ).map_into_boxed_body())
+                    Ok(req.into_response(token_rejection(&e)).map_into_boxed_body())
                 }
             }
         })
     }
 }
 
+/// Verify `token` against `decoding_key`, or without a key (insecure dev mode) decode it
+/// unverified and check the issuer and subject by hand. Expiry is checked by the caller.
+fn decode_claims(
+    token: &str,
+    decoding_key: Option<&DecodingKey>,
+    validation: &Validation,
+) -> jsonwebtoken::errors::Result<TokenData<Claims>> {
+    let Some(decoding_key) = decoding_key else {
+        let token_data = jsonwebtoken::dangerous::insecure_decode::<Claims>(token)?;
+        if validation.iss.as_ref().is_some_and(|issuers| !issuers.contains(&token_data.claims.iss)) {
+            return Err(ErrorKind::InvalidIssuer.into());
+        }
+        if token_data.claims.sub.is_empty() {
+            return Err(ErrorKind::MissingRequiredClaim("sub".to_string()).into());
+        }
+        return Ok(token_data);
+    };
+    decode::<Claims>(token, decoding_key, validation)
+}
+
+/// 401 for a token that failed validation. `reason` tells clients whether a refresh can
+/// help (`expired`) or the user has to sign in again (`invalid`).
+fn token_rejection(e: &jsonwebtoken::errors::Error) -> HttpResponse {
+    let (reason, code, message) = match e.kind() {
+        ErrorKind::ExpiredSignature => ("expired", "AUTH_TOKEN_EXPIRED", "Token expired".to_string()),
+        _ => ("invalid", "AUTH_INVALID_TOKEN", format!("Invalid token: {}", e)),
+    };
+    HttpResponse::Unauthorized()
+        .insert_header((
+            "WWW-Authenticate",
+            format!("Bearer error=\"invalid_token\", error_description=\"{}\"", reason),
+        ))
+        .json(serde_json::json!({
+            "error": message,
+            "code": code,
+            "reason": reason
+        }))
+}
+
 /// Extract token from request headers or cookies
 pub fn extract_token_from_request(req: &ServiceRequest) -> Option<String> {
     // 1. Try Authorization header
     if let Some(auth_header) = req.headers().get("Authorization") {
         if let Ok(auth_str) = auth_header.to_str() {
-            if auth_str.starts_with("Bearer ") {
-                return Some(auth_str[7..].to_string());
+            if let Some(token) = auth_str.strip_prefix("Bearer ") {
+                return Some(token.to_string());
             }
         }
     }
@@ -183,3 +302,56 @@ pub fn extract_token_from_request(req: &ServiceRequest) -> Option<String> {
 
     None
 }
+
+#[cfg(all(test, feature = "insecure-dev-auth"))]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, web, App};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn claims(iss: &str, exp: i64) -> Claims {
+        Claims {
+            sub: "user-1".to_string(),
+            email: "dev@lanai.local".to_string(),
+            username: "dev".to_string(),
+            role: "admin".to_string(),
+            org_id: None,
+            vertical: None,
+            exp,
+            iat: chrono::Utc::now().timestamp(),
+            iss: iss.to_string(),
+            jti: "jti-1".to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_insecure_dev_mode_skips_signature_only() {
+        let app = actix_test::init_service(
+            App::new().wrap(AuthGuard::insecure_dev_mode()).route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let call = |token: String| {
+            actix_test::TestRequest::get().uri("/").insert_header(("Authorization", format!("Bearer {}", token))).to_request()
+        };
+        let in_an_hour = chrono::Utc::now().timestamp() + 3600;
+
+        // Signed with a key the guard has never seen
+        let wrong_key = EncodingKey::from_secret(b"not-the-auth-service-key");
+        let token = encode(&Header::default(), &claims("lanai-auth", in_an_hour), &wrong_key).unwrap();
+        assert_eq!(actix_test::call_service(&app, call(token)).await.status(), 200);
+
+        // Unsigned: an RS256 header and an empty signature
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims("lanai-auth", in_an_hour)).unwrap());
+        let unsigned = format!("{}.{}.", header, payload);
+        assert_eq!(actix_test::call_service(&app, call(unsigned)).await.status(), 200);
+
+        let token = encode(&Header::default(), &claims("someone-else", in_an_hour), &wrong_key).unwrap();
+        assert_eq!(actix_test::call_service(&app, call(token)).await.status(), 401);
+
+        let expired = chrono::Utc::now().timestamp() - 3600;
+        let token = encode(&Header::default(), &claims("lanai-auth", expired), &wrong_key).unwrap();
+        assert_eq!(actix_test::call_service(&app, call(token)).await.status(), 401);
+    }
+}
@@ -0,0 +1,336 @@
+//! Bot scoring
+//!
+//! `BotScoreMiddleware` asks one or more `BotScorer`s how likely a request is automated and
+//! stores the result as a `BotAssessment` in the request extensions, alongside a device
+//! fingerprint derived from stable client headers. The rate limiter tightens the limit of
+//! suspicious clients and rejects likely bots outright; handlers such as login can check
+//! the assessment themselves:
+//!
+//! ```ignore
+//! App::new()
+//!     .wrap(RateLimitMiddleware { .. })
+//!     // Registered after the rate limiter so it runs first
+//!     .wrap(BotScoreMiddleware::new(HeaderHeuristics).scorer(ExternalScorer::new(url)))
+//!
+//! async fn login(bot: BotAssessment, form: web::Json<Login>) -> LanaiResult<HttpResponse> {
+//!     bot.ensure_below(0.7)?;
+//!     ...
+//! }
+//! ```
+//!
+//! Scores range from 0.0 (human) to 1.0 (bot); with several scorers the highest wins.
+//! A scorer that cannot decide (or fails) returns `None` and is ignored.
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::LanaiError;
+
+/// From this score the rate limiter scales the client's limit down.
+pub const PENALTY_THRESHOLD: f32 = 0.5;
+/// From this score the rate limiter rejects the request.
+pub const BAN_THRESHOLD: f32 = 0.9;
+
+/// Headers that identify a client build, hashed into the device fingerprint.
+const FINGERPRINT_HEADERS: [&str; 7] = [
+    "user-agent",
+    "accept",
+    "accept-language",
+    "accept-encoding",
+    "sec-ch-ua",
+    "sec-ch-ua-platform",
+    "sec-ch-ua-mobile",
+];
+
+/// What scorers see of a request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestSignals {
+    pub method: String,
+    pub path: String,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub fingerprint: String,
+    /// Lowercase header names and values, without credentials and cookies
+    pub headers: Vec<(String, String)>,
+}
+
+impl RequestSignals {
+    fn from_request(req: &ServiceRequest) -> Self {
+        let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let mut hasher = Sha256::new();
+        for name in FINGERPRINT_HEADERS {
+            hasher.update(header(name).unwrap_or_default());
+            hasher.update([0]);
+        }
+        let headers = req
+            .headers()
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "authorization" | "cookie" | "x-api-key"))
+            .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Self {
+            method: req.method().to_string(),
+            path: req.path().to_string(),
+            ip: req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string(),
+            user_agent: header("user-agent").map(str::to_string),
+            fingerprint: hex::encode(&hasher.finalize()[..16]),
+            headers,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// A bot scoring provider.
+#[async_trait]
+pub trait BotScorer: Send + Sync {
+    /// Likelihood in `0.0..=1.0` that the request is automated; `None` when undecided.
+    async fn score(&self, signals: &RequestSignals) -> Option<f32>;
+}
+
+/// Built-in scorer looking for headers real browsers send and automation tools do not.
+pub struct HeaderHeuristics;
+
+const AUTOMATION_AGENTS: [&str; 12] = [
+    "curl", "wget", "python-requests", "python-urllib", "go-http-client", "okhttp", "java/",
+    "headless", "phantomjs", "selenium", "scrapy", "libwww",
+];
+const CRAWLER_AGENTS: [&str; 3] = ["bot", "spider", "crawler"];
+
+#[async_trait]
+impl BotScorer for HeaderHeuristics {
+    async fn score(&self, signals: &RequestSignals) -> Option<f32> {
+        let mut score: f32 = 0.0;
+        match signals.user_agent.as_deref().map(str::to_ascii_lowercase) {
+            None => score += 0.6,
+            Some(agent) if AUTOMATION_AGENTS.iter().any(|a| agent.contains(a)) => score += 0.6,
+            Some(agent) if CRAWLER_AGENTS.iter().any(|a| agent.contains(a)) => score += 0.4,
+            Some(agent) if !agent.starts_with("mozilla/") => score += 0.2,
+            Some(_) => {}
+        }
+        if signals.header("accept-language").is_none() {
+            score += 0.2;
+        }
+        if signals.header("accept").is_none() {
+            score += 0.1;
+        }
+        if signals.header("accept-encoding").is_none() {
+            score += 0.1;
+        }
+        Some(score.min(1.0))
+    }
+}
+
+/// Scores with an external service: POSTs the `RequestSignals` as JSON and expects
+/// `{"score": <f32>}` back. Unreachable or slow services leave the request unscored.
+pub struct ExternalScorer {
+    url: String,
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+#[derive(Deserialize)]
+struct ExternalScore {
+    score: f32,
+}
+
+impl ExternalScorer {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), client: reqwest::Client::new(), timeout: Duration::from_millis(150) }
+    }
+
+    /// How long a request waits for the service (150ms by default).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl BotScorer for ExternalScorer {
+    async fn score(&self, signals: &RequestSignals) -> Option<f32> {
+        let response = self.client.post(&self.url).json(signals).timeout(self.timeout).send().await;
+        let result = match response {
+            Ok(response) => response.error_for_status().map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let body = match result {
+            Ok(response) => response.json::<ExternalScore>().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        match body {
+            Ok(body) => Some(body.score.clamp(0.0, 1.0)),
+            Err(e) => {
+                warn!("⚠️ Bot scoring service {} failed: {}", self.url, e);
+                None
+            }
+        }
+    }
+}
+
+/// The bot score of a request, set by `BotScoreMiddleware`. As an extractor it is
+/// unscored (0.0) when the middleware is not installed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BotAssessment {
+    pub score: f32,
+    pub fingerprint: String,
+}
+
+impl BotAssessment {
+    pub fn is_suspicious(&self) -> bool {
+        self.score >= PENALTY_THRESHOLD
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.score >= BAN_THRESHOLD
+    }
+
+    /// Forbidden unless the score is below `max`, for endpoints bots must not reach.
+    pub fn ensure_below(&self, max: f32) -> Result<(), LanaiError> {
+        if self.score >= max {
+            warn!("🤖 Rejected likely bot (score {:.2}, device {})", self.score, self.fingerprint);
+            return Err(LanaiError::Forbidden("Automated requests are not allowed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl FromRequest for BotAssessment {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let assessment = req.extensions().get::<BotAssessment>().cloned();
+        ready(Ok(assessment.unwrap_or(BotAssessment { score: 0.0, fingerprint: String::new() })))
+    }
+}
+
+/// Scores each request and stores a `BotAssessment` in its extensions. Without scorers
+/// (the default) requests pass through unscored.
+#[derive(Clone, Default)]
+pub struct BotScoreMiddleware {
+    scorers: Vec<Arc<dyn BotScorer>>,
+}
+
+impl BotScoreMiddleware {
+    pub fn new(scorer: impl BotScorer + 'static) -> Self {
+        Self::default().scorer(scorer)
+    }
+
+    /// Add a provider; the highest score of all providers is used.
+    pub fn scorer(mut self, scorer: impl BotScorer + 'static) -> Self {
+        self.scorers.push(Arc::new(scorer));
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BotScoreMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = BotScoreMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(BotScoreMiddlewareService { service: Rc::new(service), scorers: self.scorers.clone().into() }))
+    }
+}
+
+pub struct BotScoreMiddlewareService<S> {
+    service: Rc<S>,
+    scorers: Rc<[Arc<dyn BotScorer>]>,
+}
+
+impl<S, B> Service<ServiceRequest> for BotScoreMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let scorers = Rc::clone(&self.scorers);
+
+        Box::pin(async move {
+            if !scorers.is_empty() {
+                let signals = RequestSignals::from_request(&req);
+                let mut score = None;
+                for scorer in scorers.iter() {
+                    if let Some(s) = scorer.score(&signals).await {
+                        score = Some(score.map_or(s, |current: f32| current.max(s)));
+                    }
+                }
+                if let Some(score) = score {
+                    debug!("🤖 Bot score {:.2} for {} {}", score, signals.method, signals.path);
+                    req.extensions_mut().insert(BotAssessment { score, fingerprint: signals.fingerprint });
+                }
+            }
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_header_heuristics_annotate_requests() {
+        let app = test::init_service(
+            App::new().wrap(BotScoreMiddleware::new(HeaderHeuristics)).route(
+                "/login",
+                web::post().to(|bot: BotAssessment| async move {
+                    bot.ensure_below(0.7)?;
+                    Ok::<_, LanaiError>(HttpResponse::Ok().body(bot.fingerprint))
+                }),
+            ),
+        )
+        .await;
+
+        let browser = || {
+            test::TestRequest::post()
+                .uri("/login")
+                .insert_header(("user-agent", "Mozilla/5.0 (X11; Linux x86_64) Firefox/131.0"))
+                .insert_header(("accept", "text/html"))
+                .insert_header(("accept-language", "pt-BR"))
+                .insert_header(("accept-encoding", "gzip"))
+                .to_request()
+        };
+        let res = test::call_service(&app, browser()).await;
+        assert_eq!(res.status(), 200);
+        let fingerprint = test::read_body(res).await;
+        assert_eq!(fingerprint.len(), 32);
+        // Same headers, same device
+        assert_eq!(test::read_body(test::call_service(&app, browser()).await).await, fingerprint);
+
+        let req = test::TestRequest::post().uri("/login").insert_header(("user-agent", "curl/8.5.0")).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+    }
+}
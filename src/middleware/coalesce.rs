@@ -0,0 +1,330 @@
+//! Request coalescing (single-flight) for identical GETs
+//!
+//! When a dashboard refreshes for every user of a tenant at once, the same report query
+//! arrives dozens of times within a few milliseconds. `CoalesceMiddleware` lets the first
+//! of a group of identical in-flight GETs run and hands its response to the others, which
+//! wait instead of hitting the database themselves.
+//!
+//! Requests are identical when they share the path, the query string, the tenant
+//! (`TenantContext`) and any headers named with `vary_header`; with `per_user`, the caller
+//! (`Claims::sub`) as well. Only requests that overlap are coalesced: nothing is cached
+//! once the first response has been sent.
+//!
+//! ```ignore
+//! let coalescer = Coalescer::new().route("/api/v1/dashboard").vary_header("Accept-Language");
+//! web::scope("/api/v1")
+//!     .wrap(CoalesceMiddleware::new(coalescer.clone()))
+//!     .wrap(AuthGuard::new(public_key))
+//! ```
+//!
+//! The middleware must run after authentication, so wrap it inside `AuthGuard`. Shared responses are buffered in memory, and ones setting cookies
+//! are never shared; waiters whose leader fails run their own request.
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, SET_COOKIE},
+        Method, StatusCode,
+    },
+    web::Bytes,
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use log::debug;
+use opentelemetry::global;
+use opentelemetry::metrics::Counter;
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use super::auth_guard::Claims;
+use super::tenant_context::TenantContext;
+
+/// Set on responses that were produced for another, identical request.
+pub const COALESCED_HEADER: &str = "x-coalesced";
+
+/// A buffered response that can be replayed to waiting requests.
+#[derive(Debug)]
+struct SharedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl SharedResponse {
+    fn to_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::with_body(self.status, BoxBody::new(self.body.clone()));
+        for (name, value) in self.headers.iter() {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+        response.headers_mut().insert(HeaderName::from_static(COALESCED_HEADER), HeaderValue::from_static("true"));
+        response
+    }
+}
+
+/// `None` until the leader finishes; a closed channel without a value means it failed.
+type Flight = watch::Receiver<Option<Arc<SharedResponse>>>;
+
+struct Inner {
+    /// Empty means every GET
+    routes: Vec<String>,
+    vary_headers: Vec<HeaderName>,
+    per_user: bool,
+    flights: Mutex<HashMap<String, Flight>>,
+    coalesced: Counter<u64>,
+}
+
+/// In-flight GETs by key; clones share state, so one `Coalescer` covers every worker.
+#[derive(Clone)]
+pub struct Coalescer {
+    inner: Arc<Inner>,
+}
+
+impl Default for Coalescer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a request leads its flight or follows another one.
+enum Role {
+    Leader(String, watch::Sender<Option<Arc<SharedResponse>>>),
+    Follower(Flight),
+}
+
+impl Coalescer {
+    /// Coalesces every GET by path, query and tenant.
+    pub fn new() -> Self {
+        let coalesced = global::meter("lanai.http")
+            .u64_counter("http_requests_coalesced_total")
+            .with_description("GET requests answered with the response of an identical in-flight request")
+            .build();
+        Self {
+            inner: Arc::new(Inner {
+                routes: Vec::new(),
+                vary_headers: Vec::new(),
+                per_user: false,
+                flights: Mutex::new(HashMap::new()),
+                coalesced,
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("Coalescer is configured before it is shared"));
+        self
+    }
+
+    /// Coalesce only paths starting with `prefix` (call once per prefix).
+    pub fn route(self, prefix: &str) -> Self {
+        self.update(|inner| inner.routes.push(prefix.to_string()))
+    }
+
+    /// Treat requests differing in header `name` as different, e.g. `Accept-Language`.
+    pub fn vary_header(self, name: &str) -> Self {
+        let name = HeaderName::try_from(name).expect("vary_header takes a valid header name");
+        self.update(|inner| inner.vary_headers.push(name))
+    }
+
+    /// Never share responses between users, for routes whose output depends on permissions.
+    pub fn per_user(self) -> Self {
+        self.update(|inner| inner.per_user = true)
+    }
+
+    /// Requests currently leading a flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner.flights.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// The coalescing key of `req`, or `None` if it must run on its own.
+    fn key(&self, req: &ServiceRequest) -> Option<String> {
+        let path = req.path();
+        let routed = self.inner.routes.is_empty() || self.inner.routes.iter().any(|p| path.starts_with(p.as_str()));
+        if req.method() != Method::GET || !routed {
+            return None;
+        }
+
+        let extensions = req.extensions();
+        // AuthGuard on a scope runs after the app-level TenantMiddleware, so fall back to the claims
+        let tenant = match extensions.get::<TenantContext>() {
+            Some(tenant) => tenant.org_id.to_string(),
+            None => extensions.get::<Claims>().and_then(|c| c.org_id.clone()).unwrap_or_default(),
+        };
+        let mut key = format!("{}\n{}?{}", tenant, path, req.query_string());
+        if self.inner.per_user {
+            key.push('\n');
+            key.push_str(extensions.get::<Claims>().map_or("", |c| c.sub.as_str()));
+        }
+        for name in &self.inner.vary_headers {
+            key.push('\n');
+            key.extend(req.headers().get(name).and_then(|v| v.to_str().ok()));
+        }
+        Some(key)
+    }
+
+    fn join(&self, key: String) -> Role {
+        let mut flights = self.inner.flights.lock().unwrap_or_else(|e| e.into_inner());
+        match flights.get(&key) {
+            Some(flight) => Role::Follower(flight.clone()),
+            None => {
+                let (sender, receiver) = watch::channel(None);
+                flights.insert(key.clone(), receiver);
+                Role::Leader(key, sender)
+            }
+        }
+    }
+
+    /// End the flight of `key`; the response is shared only if given.
+    fn land(&self, key: &str, sender: watch::Sender<Option<Arc<SharedResponse>>>, shared: Option<SharedResponse>) {
+        self.inner.flights.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+        if let Some(shared) = shared {
+            let _ = sender.send(Some(Arc::new(shared)));
+        }
+    }
+}
+
+/// Shares the response of one in-flight GET with identical concurrent GETs.
+pub struct CoalesceMiddleware {
+    coalescer: Coalescer,
+}
+
+impl CoalesceMiddleware {
+    pub fn new(coalescer: Coalescer) -> Self {
+        Self { coalescer }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CoalesceMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CoalesceMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CoalesceMiddlewareService { service: Rc::new(service), coalescer: self.coalescer.clone() }))
+    }
+}
+
+pub struct CoalesceMiddlewareService<S> {
+    service: Rc<S>,
+    coalescer: Coalescer,
+}
+
+impl<S, B> Service<ServiceRequest> for CoalesceMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let coalescer = self.coalescer.clone();
+        let key = coalescer.key(&req);
+
+        Box::pin(async move {
+            let Some(key) = key else {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            };
+
+            let (key, sender) = match coalescer.join(key) {
+                Role::Leader(key, sender) => (key, sender),
+                Role::Follower(mut flight) => {
+                    if let Ok(shared) = flight.wait_for(Option::is_some).await {
+                        if let Some(shared) = shared.as_ref() {
+                            debug!("🔗 Coalesced GET {}", req.path());
+                            coalescer.inner.coalesced.add(1, &[]);
+                            let response = shared.to_response();
+                            return Ok(req.into_response(response));
+                        }
+                    }
+                    // The leader failed or its response is not shareable
+                    return service.call(req).await.map(|res| res.map_into_boxed_body());
+                }
+            };
+
+            // Dropping `sender` without a value (on error or cancellation) releases the followers
+            let res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    coalescer.land(&key, sender, None);
+                    return Err(e);
+                }
+            };
+            let (req, response) = res.into_parts();
+            let (head, body) = response.into_parts();
+            let bytes = match body::to_bytes(body).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    coalescer.land(&key, sender, None);
+                    let e: Box<dyn std::error::Error> = e.into();
+                    return Err(actix_web::error::ErrorInternalServerError(e.to_string()));
+                }
+            };
+
+            let shared = (!head.headers().contains_key(SET_COOKIE)).then(|| SharedResponse {
+                status: head.status(),
+                headers: head.headers().clone(),
+                body: bytes.clone(),
+            });
+            coalescer.land(&key, sender, shared);
+            Ok(ServiceResponse::new(req, head.set_body(BoxBody::new(bytes))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_identical_gets_share_one_execution() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let coalescer = Coalescer::new().route("/dashboard");
+        let app = test::init_service(
+            App::new().wrap(CoalesceMiddleware::new(coalescer.clone())).route(
+                "/dashboard",
+                web::get().to(move || {
+                    let calls = counter.clone();
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+                        HttpResponse::Ok().body("totals")
+                    }
+                }),
+            ),
+        )
+        .await;
+
+        let request = |query: &str| test::TestRequest::get().uri(&format!("/dashboard?{}", query)).to_request();
+        let (first, second, other) = futures_util::join!(
+            test::call_service(&app, request("range=week")),
+            test::call_service(&app, request("range=week")),
+            test::call_service(&app, request("range=month")),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(first.headers().get(COALESCED_HEADER).is_none());
+        assert_eq!(second.headers().get(COALESCED_HEADER).unwrap(), "true");
+        assert!(other.headers().get(COALESCED_HEADER).is_none());
+        assert_eq!(test::read_body(second).await, "totals");
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+}
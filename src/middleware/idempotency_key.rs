@@ -0,0 +1,295 @@
+//! `Idempotency-Key` handling for POST and PATCH
+//!
+//! A client retrying a POST after a timeout cannot tell whether the first attempt went
+//! through. `IdempotencyKeyMiddleware` runs a request carrying an `Idempotency-Key` header
+//! once per key, through `Idempotency::run` (the store shared with idempotent consumers and
+//! saga steps), and answers retries with the stored response, marked `Idempotent-Replayed`:
+//!
+//! ```ignore
+//! let idempotency = Idempotency::new(Arc::new(RedisIdempotencyStore::new(shared_connection().await?)));
+//! web::scope("/api/v1/payments")
+//!     .wrap(IdempotencyKeyMiddleware::new(idempotency).required())
+//!     .wrap(AuthGuard::new(public_key))
+//! ```
+//!
+//! Keys are scoped to the tenant, method and path, so wrap the middleware inside `AuthGuard`.
+//! A retry arriving while the first request still runs gets 409, and a key reused with a
+//! different body gets 422. Server errors (5xx) are not stored, so the request can be
+//! retried. Other methods pass through.
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{HeaderName, HeaderValue},
+        Method, StatusCode,
+    },
+    web, Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use super::auth_guard::Claims;
+use super::tenant_context::TenantContext;
+use crate::idempotency::{Idempotency, IdempotencyError, RunError};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses stored for an earlier request with the same key.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// A response as stored for replays.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResponse {
+    /// SHA-256 of the request body it answered
+    fingerprint: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Base64
+    body: String,
+}
+
+impl StoredResponse {
+    fn to_response(&self, replayed: bool) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let body = BASE64.decode(&self.body).unwrap_or_default();
+        let mut response = HttpResponse::with_body(status, BoxBody::new(body));
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value.as_str())) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        if replayed {
+            response.headers_mut().insert(HeaderName::from_static(REPLAYED_HEADER), HeaderValue::from_static("true"));
+        }
+        response
+    }
+}
+
+/// Why a response was not stored.
+enum NotStored {
+    Failed(Error),
+    ServerError(ServiceResponse<BoxBody>),
+}
+
+/// Runs POST and PATCH requests once per `Idempotency-Key`.
+pub struct IdempotencyKeyMiddleware {
+    idempotency: Idempotency,
+    required: bool,
+}
+
+impl IdempotencyKeyMiddleware {
+    pub fn new(idempotency: Idempotency) -> Self {
+        Self { idempotency, required: false }
+    }
+
+    /// Reject POST and PATCH requests without an `Idempotency-Key` header (400).
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for IdempotencyKeyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = IdempotencyKeyMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyKeyMiddlewareService {
+            service: Rc::new(service),
+            idempotency: self.idempotency.clone(),
+            required: self.required,
+        }))
+    }
+}
+
+pub struct IdempotencyKeyMiddlewareService<S> {
+    service: Rc<S>,
+    idempotency: Idempotency,
+    required: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyKeyMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let idempotency = self.idempotency.clone();
+        let required = self.required;
+
+        Box::pin(async move {
+            if !matches!(*req.method(), Method::POST | Method::PATCH) {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            }
+            let key = req.headers().get(IDEMPOTENCY_KEY_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string);
+            let key = match key {
+                Some(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => scoped_key(&req, &key),
+                Some(_) => {
+                    let message = format!("{} must be 1 to {} characters", IDEMPOTENCY_KEY_HEADER, MAX_KEY_LENGTH);
+                    return Ok(req.into_response(rejection(StatusCode::BAD_REQUEST, &message)));
+                }
+                None if required => {
+                    let message = format!("Missing {} header", IDEMPOTENCY_KEY_HEADER);
+                    return Ok(req.into_response(rejection(StatusCode::BAD_REQUEST, &message)));
+                }
+                None => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+            };
+
+            let body = req.extract::<web::Bytes>().await?;
+            let fingerprint = hex::encode(Sha256::digest(&body));
+            req.set_payload(Payload::from(body));
+
+            // The request goes to the service only if the key is new; routing needs it unshared
+            let (mut unhandled, mut handled) = (Some(req), None);
+            let (request, answered) = (&mut unhandled, &mut handled);
+            let stored_fingerprint = fingerprint.clone();
+            let result = idempotency
+                .run(&key, move || {
+                    let req = request.take().expect("the operation runs at most once");
+                    run_and_store(service, req, stored_fingerprint, answered)
+                })
+                .await;
+
+            let response = match result {
+                Ok(stored) if stored.fingerprint != fingerprint => rejection(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    &format!("{} was already used for a different request", IDEMPOTENCY_KEY_HEADER),
+                ),
+                Ok(stored) => stored.to_response(handled.is_none()),
+                Err(RunError::Operation(NotStored::ServerError(res))) => return Ok(res),
+                Err(RunError::Operation(NotStored::Failed(e))) => return Err(e),
+                Err(RunError::Idempotency(IdempotencyError::InProgress(_))) => rejection(
+                    StatusCode::CONFLICT,
+                    &format!("A request with this {} is still being processed", IDEMPOTENCY_KEY_HEADER),
+                ),
+                Err(RunError::Idempotency(e)) => {
+                    warn!("⚠️ Idempotency store unavailable for {}: {}", key, e);
+                    rejection(StatusCode::SERVICE_UNAVAILABLE, "Idempotency store unavailable")
+                }
+            };
+            let http_request = handled
+                .or_else(|| unhandled.map(|req| req.into_parts().0))
+                .expect("a request that did not reach the service is still unhandled");
+            Ok(ServiceResponse::new(http_request, response))
+        })
+    }
+}
+
+/// Call the service and buffer its response for storage, unless it failed.
+async fn run_and_store<S, B>(
+    service: Rc<S>,
+    req: ServiceRequest,
+    fingerprint: String,
+    answered: &mut Option<HttpRequest>,
+) -> Result<StoredResponse, NotStored>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody + 'static,
+{
+    let res = service.call(req).await.map_err(NotStored::Failed)?.map_into_boxed_body();
+    if res.status().is_server_error() {
+        return Err(NotStored::ServerError(res));
+    }
+    *answered = Some(res.request().clone());
+    let status = res.status().as_u16();
+    let headers = res
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = body::to_bytes(res.into_body())
+        .await
+        .map_err(|e| NotStored::Failed(actix_web::error::ErrorInternalServerError(e.to_string())))?;
+    Ok(StoredResponse { fingerprint, status, headers, body: BASE64.encode(body) })
+}
+
+/// `key`, scoped to the caller's tenant and the route.
+fn scoped_key(req: &ServiceRequest, key: &str) -> String {
+    let extensions = req.extensions();
+    // AuthGuard on a scope runs after the app-level TenantMiddleware, so fall back to the claims
+    let tenant = match extensions.get::<TenantContext>() {
+        Some(tenant) => tenant.org_id.to_string(),
+        None => extensions.get::<Claims>().and_then(|c| c.org_id.clone()).unwrap_or_default(),
+    };
+    format!("http:{}:{} {}:{}", tenant, req.method(), req.path(), key)
+}
+
+fn rejection(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idempotency::InMemoryIdempotencyStore;
+    use actix_web::{test, App};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_retries_get_the_stored_response() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let idempotency = Idempotency::new(Arc::new(InMemoryIdempotencyStore::new()));
+        let app = test::init_service(App::new().wrap(IdempotencyKeyMiddleware::new(idempotency)).route(
+            "/payments",
+            web::post().to(move |body: web::Bytes| {
+                let calls = counter.clone();
+                async move {
+                    let call = calls.fetch_add(1, Ordering::SeqCst);
+                    HttpResponse::Created().body(format!("payment {} for {}", call, String::from_utf8_lossy(&body)))
+                }
+            }),
+        ))
+        .await;
+
+        let request = |key: Option<&str>, body: &'static str| {
+            let mut request = test::TestRequest::post().uri("/payments").set_payload(body);
+            if let Some(key) = key {
+                request = request.insert_header((IDEMPOTENCY_KEY_HEADER, key));
+            }
+            request.to_request()
+        };
+
+        let first = test::call_service(&app, request(Some("k1"), "10.00")).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get(REPLAYED_HEADER).is_none());
+        assert_eq!(test::read_body(first).await, "payment 0 for 10.00");
+
+        let retry = test::call_service(&app, request(Some("k1"), "10.00")).await;
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers().get(REPLAYED_HEADER).unwrap(), "true");
+        assert_eq!(test::read_body(retry).await, "payment 0 for 10.00");
+
+        let reused = test::call_service(&app, request(Some("k1"), "99.00")).await;
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let unkeyed = test::call_service(&app, request(None, "10.00")).await;
+        assert_eq!(test::read_body(unkeyed).await, "payment 1 for 10.00");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
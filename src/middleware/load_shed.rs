@@ -0,0 +1,347 @@
+//! Priority-aware load shedding
+//!
+//! `LoadShedder` tracks requests in flight and the event-loop lag of each worker and turns
+//! them into a pressure between 0 and 1+ (the larger of `in_flight / max_in_flight` and
+//! `lag / max_lag`). As pressure rises, routes are rejected with 503 lowest priority
+//! first, so checkout keeps working while reports and exports back off:
+//!
+//! | Priority | Rejected from pressure (default) |
+//! |---|---|
+//! | `Low` | 0.6 |
+//! | `Normal` | 0.8 |
+//! | `High` | 1.0 (the concurrency limit) |
+//! | `Critical` | never (health, metrics and `/internal` by default) |
+//!
+//! ```ignore
+//! let shedder = LoadShedder::new(512)
+//!     .max_lag(Duration::from_millis(200))
+//!     .route("/api/v1/sales", Priority::High)
+//!     .route("/api/v1/reports", Priority::Low);
+//! App::new().wrap(LoadShedMiddleware::new(shedder.clone()))
+//! ```
+//!
+//! Other work sharing the process (job workers, NATS endpoints) can take slots with
+//! `try_enter`, so it counts against the same limit.
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use std::cell::Cell;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::LanaiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+    /// Never shed.
+    Critical,
+}
+
+impl Priority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Current load, as reported by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadStatus {
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+    /// Most recent event-loop lag sample of any worker.
+    pub lag_ms: u64,
+    pub pressure: f64,
+    /// Highest priority currently rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shedding: Option<Priority>,
+}
+
+struct Inner {
+    max_in_flight: usize,
+    max_lag: Duration,
+    probe_interval: Duration,
+    /// Pressure at which `Low`, `Normal` and `High` are rejected.
+    thresholds: [f64; 3],
+    routes: Vec<(String, Priority)>,
+    default_priority: Priority,
+    in_flight: AtomicUsize,
+    lag_micros: AtomicU64,
+    shed: Counter<u64>,
+}
+
+/// Shared admission state; clones share counters.
+#[derive(Clone)]
+pub struct LoadShedder {
+    inner: Arc<Inner>,
+}
+
+impl LoadShedder {
+    /// At most `max_in_flight` requests below `Critical`; lag of 250ms counts as full pressure.
+    pub fn new(max_in_flight: usize) -> Self {
+        let shed = global::meter("lanai.http")
+            .u64_counter("http_requests_shed_total")
+            .with_description("Requests rejected by the load shedder, by priority")
+            .build();
+        let routes = ["/health", "/api/v1/health", "/metrics", "/internal"]
+            .iter()
+            .map(|prefix| (prefix.to_string(), Priority::Critical))
+            .collect();
+        Self {
+            inner: Arc::new(Inner {
+                max_in_flight: max_in_flight.max(1),
+                max_lag: Duration::from_millis(250),
+                probe_interval: Duration::from_millis(100),
+                thresholds: [0.6, 0.8, 1.0],
+                routes,
+                default_priority: Priority::Normal,
+                in_flight: AtomicUsize::new(0),
+                lag_micros: AtomicU64::new(0),
+                shed,
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("LoadShedder is configured before it is shared"));
+        self
+    }
+
+    /// Event-loop lag that counts as full pressure.
+    pub fn max_lag(self, max_lag: Duration) -> Self {
+        self.update(|inner| inner.max_lag = max_lag.max(Duration::from_millis(1)))
+    }
+
+    /// How often each worker measures its lag.
+    pub fn probe_interval(self, interval: Duration) -> Self {
+        self.update(|inner| inner.probe_interval = interval.max(Duration::from_millis(1)))
+    }
+
+    /// Priority of paths starting with `prefix`; the longest matching prefix wins.
+    pub fn route(self, prefix: &str, priority: Priority) -> Self {
+        self.update(|inner| {
+            inner.routes.retain(|(existing, _)| existing != prefix);
+            inner.routes.push((prefix.to_string(), priority));
+            inner.routes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        })
+    }
+
+    /// Priority of routes without a matching prefix (`Normal` by default).
+    pub fn default_priority(self, priority: Priority) -> Self {
+        self.update(|inner| inner.default_priority = priority)
+    }
+
+    /// Reject `priority` from `pressure` on. Has no effect on `Critical`.
+    pub fn shed_at(self, priority: Priority, pressure: f64) -> Self {
+        self.update(|inner| {
+            if priority != Priority::Critical {
+                inner.thresholds[priority as usize] = pressure.max(0.0);
+            }
+        })
+    }
+
+    pub fn priority_of(&self, path: &str) -> Priority {
+        let routes = &self.inner.routes;
+        routes.iter().find(|(prefix, _)| path.starts_with(prefix.as_str())).map_or(self.inner.default_priority, |r| r.1)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::Relaxed)
+    }
+
+    fn pressure(&self, lag: Duration) -> f64 {
+        let concurrency = self.in_flight() as f64 / self.inner.max_in_flight as f64;
+        concurrency.max(lag.as_secs_f64() / self.inner.max_lag.as_secs_f64())
+    }
+
+    fn admits(&self, priority: Priority, pressure: f64) -> bool {
+        priority == Priority::Critical || pressure < self.inner.thresholds[priority as usize]
+    }
+
+    /// Take a slot for work of `priority`, given the caller's event-loop lag, unless it
+    /// should be shed. The slot is released when the guard is dropped.
+    pub fn try_enter(&self, priority: Priority, lag: Duration) -> Option<InFlight> {
+        if !self.admits(priority, self.pressure(lag)) {
+            self.inner.shed.add(1, &[KeyValue::new("priority", priority.as_str())]);
+            return None;
+        }
+        self.inner.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(InFlight { inner: self.inner.clone() })
+    }
+
+    pub fn status(&self) -> LoadStatus {
+        let lag = Duration::from_micros(self.inner.lag_micros.load(Ordering::Relaxed));
+        let pressure = self.pressure(lag);
+        let shedding = [Priority::High, Priority::Normal, Priority::Low]
+            .into_iter()
+            .find(|priority| !self.admits(*priority, pressure));
+        LoadStatus {
+            in_flight: self.in_flight(),
+            max_in_flight: self.inner.max_in_flight,
+            lag_ms: lag.as_millis() as u64,
+            pressure,
+            shedding,
+        }
+    }
+}
+
+/// A slot taken with `LoadShedder::try_enter`.
+pub struct InFlight {
+    inner: Arc<Inner>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.inner.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Measure how late a timer on this worker fires, until `lag` is dropped.
+fn spawn_lag_probe(shedder: LoadShedder, lag: &Rc<Cell<Duration>>) {
+    let lag = Rc::downgrade(lag);
+    let interval = shedder.inner.probe_interval;
+    actix_web::rt::spawn(async move {
+        loop {
+            let started = Instant::now();
+            actix_web::rt::time::sleep(interval).await;
+            let Some(lag) = lag.upgrade() else { break };
+            let sample = started.elapsed().saturating_sub(interval);
+            lag.set(sample);
+            shedder.inner.lag_micros.store(sample.as_micros() as u64, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Rejects requests the `LoadShedder` does not admit with 503 and `Retry-After`.
+pub struct LoadShedMiddleware {
+    shedder: LoadShedder,
+}
+
+impl LoadShedMiddleware {
+    pub fn new(shedder: LoadShedder) -> Self {
+        Self { shedder }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LoadShedMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LoadShedMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        // Called once per worker: each worker measures its own event loop.
+        let lag = Rc::new(Cell::new(Duration::ZERO));
+        spawn_lag_probe(self.shedder.clone(), &lag);
+        ready(Ok(LoadShedMiddlewareService { service: Rc::new(service), shedder: self.shedder.clone(), lag }))
+    }
+}
+
+pub struct LoadShedMiddlewareService<S> {
+    service: Rc<S>,
+    shedder: LoadShedder,
+    lag: Rc<Cell<Duration>>,
+}
+
+impl<S, B> Service<ServiceRequest> for LoadShedMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let priority = self.shedder.priority_of(req.path());
+        let slot = self.shedder.try_enter(priority, self.lag.get());
+
+        Box::pin(async move {
+            let Some(_slot) = slot else {
+                warn!("🚦 Shedding {} priority request {} {}", priority.as_str(), req.method(), req.path());
+                let mut response = LanaiError::Unavailable("Service is overloaded, please retry".to_string())
+                    .error_response();
+                response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from_static("1"));
+                return Ok(req.into_response(response));
+            };
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, web, App, HttpResponse};
+
+    #[test]
+    fn test_admission_by_priority() {
+        let shedder =
+            LoadShedder::new(10).route("/api/v1/reports", Priority::Low).route("/api/v1/sales", Priority::High);
+        assert_eq!(shedder.priority_of("/api/v1/reports/daily"), Priority::Low);
+        assert_eq!(shedder.priority_of("/api/v1/products"), Priority::Normal);
+        assert_eq!(shedder.priority_of("/health/ready"), Priority::Critical);
+
+        let slots: Vec<InFlight> = (0..7).filter_map(|_| shedder.try_enter(Priority::High, Duration::ZERO)).collect();
+        assert_eq!(shedder.in_flight(), 7);
+        assert!(shedder.try_enter(Priority::Low, Duration::ZERO).is_none());
+        assert!(shedder.try_enter(Priority::Normal, Duration::ZERO).is_some());
+        assert!(shedder.try_enter(Priority::Normal, Duration::from_millis(220)).is_none());
+        assert_eq!(shedder.status().shedding, Some(Priority::Low));
+
+        drop(slots);
+        assert_eq!(shedder.in_flight(), 0);
+        assert!(shedder.try_enter(Priority::High, Duration::from_millis(300)).is_none());
+        assert!(shedder.try_enter(Priority::Critical, Duration::from_secs(5)).is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_sheds_low_priority_routes() {
+        let shedder = LoadShedder::new(4).route("/api/v1/reports", Priority::Low);
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(LoadShedMiddleware::new(shedder.clone()))
+                .route("/api/v1/reports", web::get().to(HttpResponse::Ok))
+                .route("/api/v1/orders", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let _busy: Vec<InFlight> = (0..3).filter_map(|_| shedder.try_enter(Priority::High, Duration::ZERO)).collect();
+        let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/api/v1/reports").to_request()).await;
+        assert_eq!(res.status(), 503);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "1");
+        let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/api/v1/orders").to_request()).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(shedder.in_flight(), 3);
+    }
+}
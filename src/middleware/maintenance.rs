@@ -0,0 +1,165 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use log::info;
+use serde::Serialize;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::error::LanaiError;
+
+/// Process-wide maintenance switch. While enabled, `MaintenanceMiddleware` answers public
+/// routes with 503; internal, health and metrics routes keep working so the service can
+/// be observed and switched back.
+#[derive(Clone, Default)]
+pub struct MaintenanceMode {
+    inner: Arc<RwLock<Option<String>>>,
+}
+
+/// Current maintenance state, as reported by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+static GLOBAL: OnceLock<MaintenanceMode> = OnceLock::new();
+
+impl MaintenanceMode {
+    pub fn global() -> &'static MaintenanceMode {
+        GLOBAL.get_or_init(MaintenanceMode::default)
+    }
+
+    /// Enter maintenance; `message` is returned to callers.
+    pub fn enable(&self, message: &str) {
+        info!("🚧 Maintenance mode enabled: {}", message);
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = Some(message.to_string());
+    }
+
+    pub fn disable(&self) {
+        info!("✅ Maintenance mode disabled");
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        let message = self.inner.read().unwrap_or_else(|e| e.into_inner()).clone();
+        MaintenanceStatus { enabled: message.is_some(), message }
+    }
+}
+
+fn is_exempt(path: &str) -> bool {
+    path.starts_with("/internal")
+        || path.starts_with("/health")
+        || path.starts_with("/api/v1/health")
+        || path.starts_with("/metrics")
+}
+
+/// Rejects public routes while maintenance mode is on.
+pub struct MaintenanceMiddleware {
+    mode: MaintenanceMode,
+}
+
+impl MaintenanceMiddleware {
+    /// Follows `MaintenanceMode::global()`.
+    pub fn new() -> Self {
+        Self { mode: MaintenanceMode::global().clone() }
+    }
+
+    pub fn with_mode(mode: MaintenanceMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl Default for MaintenanceMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for MaintenanceMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MaintenanceMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddlewareService { service: Rc::new(service), mode: self.mode.clone() }))
+    }
+}
+
+pub struct MaintenanceMiddlewareService<S> {
+    service: Rc<S>,
+    mode: MaintenanceMode,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let status = self.mode.status();
+
+        Box::pin(async move {
+            if let Some(message) = status.message.filter(|_| !is_exempt(req.path())) {
+                let response = LanaiError::Unavailable(message).error_response();
+                return Ok(req.into_response(response));
+            }
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_maintenance_blocks_public_routes_only() {
+        let mode = MaintenanceMode::default();
+        let app = test::init_service(
+            App::new()
+                .wrap(MaintenanceMiddleware::with_mode(mode.clone()))
+                .route("/api/v1/orders", web::get().to(HttpResponse::Ok))
+                .route("/internal/admin/maintenance", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/v1/orders").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        mode.enable("Database migration in progress");
+        let req = test::TestRequest::get().uri("/api/v1/orders").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 503);
+        let req = test::TestRequest::get().uri("/internal/admin/maintenance").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        mode.disable();
+        let req = test::TestRequest::get().uri("/api/v1/orders").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+}
@@ -3,3 +3,13 @@ pub mod tenant_context;
 pub mod security_headers;
 pub mod request_size;
 pub mod rate_limit;
+pub mod webhook_signature;
+pub mod idempotency_key;
+pub mod service_token;
+pub mod maintenance;
+pub mod user_context;
+pub mod signed_url;
+pub mod load_shed;
+pub mod tenant_scoped;
+pub mod bot_score;
+pub mod coalesce;
@@ -6,7 +6,9 @@ use actix_web::{
 use futures_util::future::LocalBoxFuture;
 use std::future::{ready, Ready};
 use std::sync::Arc;
-use crate::rate_limit::RateLimiterBackend;
+use std::rc::Rc;
+use crate::middleware::bot_score::BotAssessment;
+use crate::rate_limit::{LocalOverrides, RateLimitOverrides, RateLimiterBackend};
 
 /// Rate limiting middleware
 pub struct RateLimitMiddleware {
@@ -34,6 +36,7 @@ where
             limiter: Arc::clone(&self.limiter),
             max_requests: self.max_requests,
             window_seconds: self.window_seconds,
+            overrides: Rc::new(LocalOverrides::new(RateLimitOverrides::global().clone())),
         }))
     }
 }
@@ -43,6 +46,8 @@ pub struct RateLimitMiddlewareService<S> {
     limiter: Arc<dyn RateLimiterBackend>,
     max_requests: u32,
     window_seconds: u64,
+    /// This worker's copy of the global overrides
+    overrides: Rc<LocalOverrides>,
 }
 
 impl<S, B> Service<ServiceRequest> for RateLimitMiddlewareService<S>
@@ -63,6 +68,7 @@ where
         let limiter = Arc::clone(&self.limiter);
         let max_requests = self.max_requests;
         let window_seconds = self.window_seconds;
+        let overrides = Rc::clone(&self.overrides);
 
         Box::pin(async move {
             // Skip rate limiting for internal and health routes
@@ -75,12 +81,13 @@ where
                 return service.call(req).await.map(|res| res.map_body(|_, body| body.boxed()));
             }
 
-            // Get client IP for rate limiting
-            let ip = req
-                .connection_info()
-                .peer_addr()
-                .unwrap_or("unknown")
-                .to_string();
+            // Get client IP for rate limiting (batch items count against the batch's client)
+            let ip = crate::server::batch::batch_client(req.headers()).unwrap_or_else(|| {
+                req.connection_info()
+                    .peer_addr()
+                    .unwrap_or("unknown")
+                    .to_string()
+            });
 
             // Try to extract identifying key
             let mut key_parts: Vec<String> = Vec::new();
@@ -95,8 +102,7 @@ where
             // Auth Header (simple extraction, no validation here to avoid overhead/coupling)
             if let Some(auth_val) = req.headers().get("authorization") {
                 if let Ok(auth_str) = auth_val.to_str() {
-                    if auth_str.starts_with("Bearer ") {
-                        let token = &auth_str[7..];
+                    if let Some(token) = auth_str.strip_prefix("Bearer ") {
                         // Use a short hash or prefix of the token
                         let short = &token[..std::cmp::min(16, token.len())];
                         key_parts.push(format!("token:{}", short));
@@ -104,11 +110,34 @@ where
                 }
             }
 
+            // Likely bots (see `BotScoreMiddleware`) are rejected; suspicious clients are keyed
+            // by device instead of IP, so rotating addresses does not reset their window
+            let bot = req.extensions().get::<BotAssessment>().cloned().filter(|bot| bot.is_suspicious());
+            if bot.as_ref().is_some_and(|bot| bot.is_bot()) {
+                let response = HttpResponse::Forbidden().json(
+                    serde_json::json!({"error": "Automated requests are not allowed."}),
+                );
+                return Ok(req.into_response(response));
+            }
+
             // Build final key
-            let key = if !key_parts.is_empty() {
-                format!("{}|ip:{}", key_parts.join("+"), ip)
-            } else {
-                ip.clone()
+            let key = match (key_parts.is_empty(), &bot) {
+                (true, None) => ip.clone(),
+                (true, Some(bot)) => format!("device:{}", bot.fingerprint),
+                (false, None) => format!("{}|ip:{}", key_parts.join("+"), ip),
+                (false, Some(bot)) => format!("{}|device:{}", key_parts.join("+"), bot.fingerprint),
+            };
+
+            // Runtime overrides (admin API) replace the default limit for matching keys
+            let (max_requests, window_seconds) = overrides
+                .resolve(&key)
+                .map(|policy| (policy.max_requests, policy.window_seconds))
+                .unwrap_or((max_requests, window_seconds));
+
+            // Suspicious clients get a share of the limit shrinking with their score
+            let max_requests = match bot {
+                Some(bot) => ((max_requests as f32 * (1.0 - bot.score)).floor() as u32).max(1),
+                None => max_requests,
             };
 
             // Check rate limit
@@ -7,9 +7,29 @@ use futures_util::future::LocalBoxFuture;
 use std::future::{ready, Ready};
 use std::sync::Arc;
 
+/// Body size limit that applies to the current request, stored in the request
+/// extensions. Streaming readers enforce it on bodies without a `Content-Length`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestSizeLimit(pub usize);
+
 /// Request size limiting middleware
 pub struct RequestSizeLimitMiddleware {
     pub max_size: usize,
+    /// Limits for paths starting with a prefix (e.g. bulk imports), longest prefix first.
+    pub route_limits: Vec<(String, usize)>,
+}
+
+impl RequestSizeLimitMiddleware {
+    pub fn new(max_size: usize) -> Self {
+        Self { max_size, route_limits: Vec::new() }
+    }
+
+    /// Allow up to `max_size` bytes for paths under `prefix`.
+    pub fn route_limit(mut self, prefix: &str, max_size: usize) -> Self {
+        self.route_limits.push((prefix.to_string(), max_size));
+        self.route_limits.sort_by_key(|(route, _)| std::cmp::Reverse(route.len()));
+        self
+    }
 }
 
 impl<S, B> Transform<S, ServiceRequest> for RequestSizeLimitMiddleware
@@ -29,6 +49,7 @@ where
         ready(Ok(RequestSizeLimitMiddlewareService {
             service: Arc::new(service),
             max_size: self.max_size,
+            route_limits: Arc::new(self.route_limits.clone()),
         }))
     }
 }
@@ -36,6 +57,7 @@ where
 pub struct RequestSizeLimitMiddlewareService<S> {
     service: Arc<S>,
     max_size: usize,
+    route_limits: Arc<Vec<(String, usize)>>,
 }
 
 impl<S, B> Service<ServiceRequest> for RequestSizeLimitMiddlewareService<S>
@@ -53,7 +75,12 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = Arc::clone(&self.service);
-        let max_size = self.max_size;
+        let max_size = self
+            .route_limits
+            .iter()
+            .find(|(prefix, _)| req.path().starts_with(prefix.as_str()))
+            .map_or(self.max_size, |(_, limit)| *limit);
+        req.extensions_mut().insert(RequestSizeLimit(max_size));
 
         Box::pin(async move {
             // Check Content-Length header
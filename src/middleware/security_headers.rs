@@ -1,5 +1,4 @@
 use actix_web::{
-    body::BoxBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     http::header,
     Error,
@@ -121,9 +120,9 @@ where
                 }),
             );
 
-            // Content Security Policy (optional)
+            // Content Security Policy (optional); a policy set by the handler (e.g. `Spa`) wins
             if let Some(csp) = &content_security_policy {
-                if !csp.trim().is_empty() {
+                if !csp.trim().is_empty() && !headers.contains_key(header::CONTENT_SECURITY_POLICY) {
                     headers.insert(
                         header::HeaderName::from_static("content-security-policy"),
                         header::HeaderValue::from_str(csp).unwrap_or_else(|_| {
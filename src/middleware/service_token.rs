@@ -0,0 +1,145 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use log::{error, warn};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::env::{EnvVar, VarType};
+use crate::error::LanaiError;
+
+/// Comma-separated accepted tokens, so a new token can be rolled out before the old one is removed.
+pub const SERVICE_TOKEN_ENV: &str = "LANAI_SERVICE_TOKEN";
+pub const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
+
+/// Variables read by `ServiceTokenGuard::from_env`
+pub const ENV_VARS: &[EnvVar] = &[EnvVar::new(SERVICE_TOKEN_ENV, VarType::List)
+    .description("Accepted service-to-service tokens")
+    .secret()];
+
+/// Guard for internal, service-to-service routes: the request must carry one of the shared
+/// service tokens as `Authorization: Bearer <token>` or `X-Service-Token`. With no tokens
+/// configured every request is rejected.
+#[derive(Clone)]
+pub struct ServiceTokenGuard {
+    tokens: Arc<Vec<String>>,
+}
+
+impl ServiceTokenGuard {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self { tokens: Arc::new(tokens.into_iter().filter(|t| !t.is_empty()).collect()) }
+    }
+
+    pub fn from_env() -> Self {
+        let tokens: Vec<String> = std::env::var(SERVICE_TOKEN_ENV)
+            .map(|v| v.split(',').map(|t| t.trim().to_string()).collect())
+            .unwrap_or_default();
+        let guard = Self::new(tokens);
+        if guard.tokens.is_empty() {
+            warn!("⚠️ {} is not set; internal routes will reject every request", SERVICE_TOKEN_ENV);
+        }
+        guard
+    }
+
+    fn accepts(&self, presented: &str) -> bool {
+        self.tokens.iter().any(|token| constant_time_eq(token.as_bytes(), presented.as_bytes()))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn presented_token(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    if let Some(token) = headers.get(SERVICE_TOKEN_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(token.to_string());
+    }
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string)
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ServiceTokenGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ServiceTokenGuardMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ServiceTokenGuardMiddleware { service: Rc::new(service), guard: self.clone() }))
+    }
+}
+
+pub struct ServiceTokenGuardMiddleware<S> {
+    service: Rc<S>,
+    guard: ServiceTokenGuard,
+}
+
+impl<S, B> Service<ServiceRequest> for ServiceTokenGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let accepted = presented_token(&req).is_some_and(|token| self.guard.accepts(&token));
+
+        Box::pin(async move {
+            if !accepted {
+                error!("⛔ Rejected service-token request to {} {}", req.method(), req.path());
+                let response = LanaiError::Unauthorized("A valid service token is required".to_string()).error_response();
+                return Ok(req.into_response(response));
+            }
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_requires_configured_token() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ServiceTokenGuard::new(vec!["s3cret".to_string()]))
+                .route("/internal/ping", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let denied = test::TestRequest::get().uri("/internal/ping").to_request();
+        assert_eq!(test::call_service(&app, denied).await.status(), 401);
+
+        let wrong = test::TestRequest::get().uri("/internal/ping").insert_header((SERVICE_TOKEN_HEADER, "nope")).to_request();
+        assert_eq!(test::call_service(&app, wrong).await.status(), 401);
+
+        let allowed = test::TestRequest::get()
+            .uri("/internal/ping")
+            .insert_header((header::AUTHORIZATION, "Bearer s3cret"))
+            .to_request();
+        assert_eq!(test::call_service(&app, allowed).await.status(), 200);
+    }
+}
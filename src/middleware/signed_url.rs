@@ -0,0 +1,325 @@
+//! Time-limited signed URLs
+//!
+//! For links that must work without a bearer token: file downloads opened by the browser
+//! and callbacks handed to payment providers. The URL carries its expiry, the tenant it was
+//! minted for and an HMAC over the path and query:
+//!
+//! ```text
+//! /files/receipts/42.pdf?expires=1767225600&org=6f1c...&sig=3q2-7w...
+//! ```
+//!
+//! ```ignore
+//! let signer = UrlSigner::global().ok_or_else(|| LanaiError::internal("URL signing is not configured"))?;
+//! let link = signer.sign(&format!("/files/receipts/{}.pdf", id), Some(tenant.org_id), Duration::from_secs(600));
+//!
+//! web::scope("/files").wrap(SignedUrlMiddleware::global()).route("/receipts/{id}.pdf", web::get().to(download));
+//!
+//! async fn download(link: SignedUrl, tenant: TenantContext) -> LanaiResult<HttpResponse> { ... }
+//! ```
+//!
+//! Signing and verifying services share `LANAI_URL_SIGNING_SECRET`. Without a request
+//! tenant, the middleware sets the `TenantContext` from the URL; with one, the two must match.
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, FromRequest, HttpMessage, HttpRequest, ResponseError,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures_util::future::LocalBoxFuture;
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::tenant_context::TenantContext;
+use crate::error::LanaiError;
+
+/// Shared secret for signing and verifying URLs.
+pub const URL_SIGNING_SECRET_ENV: &str = "LANAI_URL_SIGNING_SECRET";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signed URL error types
+#[derive(Debug, Error, PartialEq)]
+pub enum SignedUrlError {
+    #[error("URL is not signed")]
+    Missing,
+
+    #[error("Malformed signed URL")]
+    Malformed,
+
+    #[error("Invalid URL signature")]
+    InvalidSignature,
+
+    #[error("Link expired")]
+    Expired,
+
+    #[error("Link was issued for another organization")]
+    WrongTenant,
+
+    #[error("{} is not set", URL_SIGNING_SECRET_ENV)]
+    NotConfigured,
+}
+
+impl From<SignedUrlError> for LanaiError {
+    fn from(e: SignedUrlError) -> Self {
+        match e {
+            SignedUrlError::NotConfigured => LanaiError::Internal(e.to_string()),
+            _ => LanaiError::Forbidden(e.to_string()),
+        }
+    }
+}
+
+/// A verified signed URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedUrl {
+    pub org_id: Option<Uuid>,
+    /// Unix seconds.
+    pub expires_at: i64,
+}
+
+/// Mints and verifies signed URLs.
+#[derive(Clone)]
+pub struct UrlSigner {
+    keys: Arc<[Arc<[u8]>]>,
+}
+
+impl UrlSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self { keys: Arc::from(vec![Arc::from(secret)]) }
+    }
+
+    /// Also accept URLs signed with `secret` (for rotation); new URLs use the first secret.
+    pub fn also_accept(self, secret: &[u8]) -> Self {
+        let mut keys = self.keys.to_vec();
+        keys.push(Arc::from(secret));
+        Self { keys: Arc::from(keys) }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var(URL_SIGNING_SECRET_ENV).ok().filter(|s| !s.is_empty()).map(|s| Self::new(s.as_bytes()))
+    }
+
+    /// Signer from `LANAI_URL_SIGNING_SECRET`, read once.
+    pub fn global() -> Option<&'static Self> {
+        static SIGNER: OnceLock<Option<UrlSigner>> = OnceLock::new();
+        SIGNER
+            .get_or_init(|| {
+                let signer = Self::from_env();
+                if signer.is_none() {
+                    warn!("⚠️ {} is not set; signed URLs are unavailable", URL_SIGNING_SECRET_ENV);
+                }
+                signer
+            })
+            .as_ref()
+    }
+
+    fn mac(key: &[u8], signed: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(signed.as_bytes());
+        mac
+    }
+
+    /// `path` (already percent-encoded, optionally with a query) plus `expires`, `org` and
+    /// `sig` parameters. Prefix the service's public base URL as needed.
+    pub fn sign(&self, path: &str, org_id: Option<Uuid>, ttl: Duration) -> String {
+        self.sign_until(path, org_id, chrono::Utc::now().timestamp() + ttl.as_secs() as i64)
+    }
+
+    pub fn sign_until(&self, path: &str, org_id: Option<Uuid>, expires_at: i64) -> String {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let mut signed = format!("{}{}expires={}", path, separator, expires_at);
+        if let Some(org_id) = org_id {
+            signed.push_str(&format!("&org={}", org_id));
+        }
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(&self.keys[0], &signed).finalize().into_bytes());
+        format!("{}&sig={}", signed, signature)
+    }
+
+    /// Verify a request's raw path and query string. `sig` must be the last parameter.
+    pub fn verify(&self, path: &str, query: &str) -> Result<SignedUrl, SignedUrlError> {
+        self.verify_at(path, query, chrono::Utc::now().timestamp())
+    }
+
+    fn verify_at(&self, path: &str, query: &str, now: i64) -> Result<SignedUrl, SignedUrlError> {
+        let (signed_query, signature) = match query.rsplit_once("&sig=") {
+            Some(parts) => parts,
+            None => return Err(SignedUrlError::Missing),
+        };
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| SignedUrlError::Malformed)?;
+        let signed = format!("{}?{}", path, signed_query);
+        if !self.keys.iter().any(|key| Self::mac(key, &signed).verify_slice(&signature).is_ok()) {
+            return Err(SignedUrlError::InvalidSignature);
+        }
+
+        let mut expires_at = None;
+        let mut org_id = None;
+        for (name, value) in signed_query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name {
+                "expires" => expires_at = Some(value.parse().map_err(|_| SignedUrlError::Malformed)?),
+                "org" => org_id = Some(Uuid::parse_str(value).map_err(|_| SignedUrlError::Malformed)?),
+                _ => {}
+            }
+        }
+        let expires_at = expires_at.ok_or(SignedUrlError::Malformed)?;
+        if expires_at <= now {
+            return Err(SignedUrlError::Expired);
+        }
+        Ok(SignedUrl { org_id, expires_at })
+    }
+
+    /// Verify the request's URL and bind its tenant.
+    fn verify_request(&self, req: &HttpRequest) -> Result<SignedUrl, SignedUrlError> {
+        let link = self.verify(req.path(), req.query_string())?;
+        let tenant = req.extensions().get::<TenantContext>().map(|tenant| tenant.org_id);
+        match (link.org_id, tenant) {
+            (Some(link_org), Some(org_id)) if link_org != org_id => Err(SignedUrlError::WrongTenant),
+            (Some(org_id), None) => {
+                req.extensions_mut().insert(TenantContext { org_id });
+                Ok(link)
+            }
+            _ => Ok(link),
+        }
+    }
+}
+
+/// Rejects requests without a valid signed URL (403) and provides the `SignedUrl`.
+pub struct SignedUrlMiddleware {
+    signer: Option<UrlSigner>,
+}
+
+impl SignedUrlMiddleware {
+    pub fn new(signer: UrlSigner) -> Self {
+        Self { signer: Some(signer) }
+    }
+
+    /// With the global signer; every request fails with 500 if it is not configured.
+    pub fn global() -> Self {
+        Self { signer: UrlSigner::global().cloned() }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SignedUrlMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SignedUrlMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SignedUrlMiddlewareService { service: Rc::new(service), signer: self.signer.clone() }))
+    }
+}
+
+pub struct SignedUrlMiddlewareService<S> {
+    service: Rc<S>,
+    signer: Option<UrlSigner>,
+}
+
+impl<S, B> Service<ServiceRequest> for SignedUrlMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let verified = match &self.signer {
+            Some(signer) => signer.verify_request(req.request()),
+            None => Err(SignedUrlError::NotConfigured),
+        };
+
+        Box::pin(async move {
+            match verified {
+                Ok(link) => {
+                    req.extensions_mut().insert(link);
+                    service.call(req).await.map(ServiceResponse::map_into_boxed_body)
+                }
+                Err(e) => {
+                    warn!("🚫 Rejected signed URL {}: {}", req.path(), e);
+                    let error = LanaiError::from(e);
+                    Ok(req.into_response(error.error_response()))
+                }
+            }
+        })
+    }
+}
+
+/// Set by `SignedUrlMiddleware`; without it, verified here with the global signer.
+impl FromRequest for SignedUrl {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(link) = req.extensions().get::<SignedUrl>() {
+            return ready(Ok(*link));
+        }
+        let result = UrlSigner::global().ok_or(SignedUrlError::NotConfigured).and_then(|signer| signer.verify_request(req));
+        ready(result.map_err(LanaiError::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = UrlSigner::new(b"link-secret");
+        let org_id = Uuid::new_v4();
+        let url = signer.sign_until("/files/receipts/42.pdf?download=1", Some(org_id), 2_000);
+        let (path, query) = url.split_once('?').unwrap();
+
+        assert_eq!(signer.verify_at(path, query, 1_000), Ok(SignedUrl { org_id: Some(org_id), expires_at: 2_000 }));
+        assert_eq!(signer.verify_at(path, query, 2_000), Err(SignedUrlError::Expired));
+        assert_eq!(signer.verify_at("/files/receipts/43.pdf", query, 1_000), Err(SignedUrlError::InvalidSignature));
+        assert_eq!(
+            signer.verify_at(path, &query.replace("expires=2000", "expires=9000"), 1_000),
+            Err(SignedUrlError::InvalidSignature)
+        );
+        assert_eq!(signer.verify_at(path, "download=1", 1_000), Err(SignedUrlError::Missing));
+
+        let rotated = UrlSigner::new(b"new-secret").also_accept(b"link-secret");
+        assert!(rotated.verify_at(path, query, 1_000).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_binds_tenant() {
+        use actix_web::{test, web, App, HttpResponse};
+
+        let signer = UrlSigner::new(b"link-secret");
+        let org_id = Uuid::new_v4();
+        let app = test::init_service(
+            App::new().wrap(SignedUrlMiddleware::new(signer.clone())).route(
+                "/files/{name}",
+                web::get().to(|tenant: TenantContext| async move { HttpResponse::Ok().body(tenant.org_id.to_string()) }),
+            ),
+        )
+        .await;
+
+        let url = signer.sign("/files/a.pdf", Some(org_id), Duration::from_secs(60));
+        let req = test::TestRequest::get().uri(&url).to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, org_id.to_string());
+
+        let req = test::TestRequest::get().uri("/files/a.pdf").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+    }
+}
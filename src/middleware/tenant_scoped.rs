@@ -0,0 +1,206 @@
+//! Tenant-scoped path IDs
+//!
+//! `TenantScoped<T>` parses a resource ID from the path and checks that it belongs to the
+//! caller's tenant before the handler runs, so a handler cannot forget the check and serve
+//! another organization's record by ID:
+//!
+//! ```ignore
+//! // Once per resource type
+//! app.app_data(Ownership::<ProductId>::new(TableOwnership::new(pool.clone(), "products")?))
+//!
+//! // GET /products/{id}
+//! async fn get_product(product: TenantScoped<ProductId>) -> LanaiResult<HttpResponse> {
+//!     let product = repo.load(product.org_id, product.id).await?;
+//!     ...
+//! }
+//! ```
+//!
+//! The ID comes from the `{id}` segment, or the only segment when there is one. IDs of
+//! other tenants answer 404, like missing ones, so their existence is not revealed.
+
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use log::{error, warn};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::LanaiError;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Decides whether a resource belongs to an organization.
+#[async_trait]
+pub trait OwnershipChecker<T>: Send + Sync {
+    /// `false` for resources of other tenants and for missing ones.
+    async fn owns(&self, org_id: Uuid, id: T) -> Result<bool, LanaiError>;
+}
+
+/// The checker for IDs of type `T`, registered as app data with `Ownership::new`.
+pub struct Ownership<T> {
+    checker: Arc<dyn OwnershipChecker<T>>,
+}
+
+impl<T: 'static> Ownership<T> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(checker: impl OwnershipChecker<T> + 'static) -> web::Data<Self> {
+        web::Data::new(Self { checker: Arc::new(checker) })
+    }
+}
+
+/// Looks the ID up in `table`, whose rows carry an `org_id` column.
+pub struct TableOwnership {
+    pool: PgPool,
+    query: String,
+}
+
+impl TableOwnership {
+    /// Match on the `id` column.
+    pub fn new(pool: PgPool, table: &str) -> Result<Self, LanaiError> {
+        Self::with_column(pool, table, "id")
+    }
+
+    pub fn with_column(pool: PgPool, table: &str, column: &str) -> Result<Self, LanaiError> {
+        let identifier = |name: &str| {
+            !name.is_empty() && name.split('.').all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        };
+        if !identifier(table) || !identifier(column) {
+            return Err(LanaiError::Internal(format!("Invalid ownership table '{}.{}'", table, column)));
+        }
+        let query = format!("SELECT EXISTS (SELECT 1 FROM {} WHERE {} = $1 AND org_id = $2)", table, column);
+        Ok(Self { pool, query })
+    }
+}
+
+#[async_trait]
+impl<T> OwnershipChecker<T> for TableOwnership
+where
+    T: Into<Uuid> + Send + 'static,
+{
+    async fn owns(&self, org_id: Uuid, id: T) -> Result<bool, LanaiError> {
+        let owned = sqlx::query_scalar(&self.query).bind(id.into()).bind(org_id).fetch_one(&self.pool).await?;
+        Ok(owned)
+    }
+}
+
+/// A path ID verified to belong to the request's tenant.
+#[derive(Debug, Clone, Copy)]
+pub struct TenantScoped<T> {
+    pub id: T,
+    pub org_id: Uuid,
+}
+
+impl<T> TenantScoped<T> {
+    pub fn into_inner(self) -> T {
+        self.id
+    }
+}
+
+impl<T> std::ops::Deref for TenantScoped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.id
+    }
+}
+
+/// The `{id}` segment, or the only one.
+fn path_id(req: &HttpRequest) -> Option<&str> {
+    let info = req.match_info();
+    info.get("id").or_else(|| {
+        let mut segments = info.iter();
+        match (segments.next(), segments.next()) {
+            (Some((_, value)), None) => Some(value),
+            _ => None,
+        }
+    })
+}
+
+impl<T> FromRequest for TenantScoped<T>
+where
+    T: FromStr + Copy + 'static,
+{
+    type Error = LanaiError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let tenant = req.extensions().get::<TenantContext>().copied();
+        let raw = path_id(req).map(str::to_string);
+        let ownership = req.app_data::<web::Data<Ownership<T>>>().map(|data| data.checker.clone());
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            let tenant = tenant.ok_or_else(|| LanaiError::Forbidden("Tenant context required".to_string()))?;
+            let raw = raw.ok_or_else(|| LanaiError::internal(format!("No ID segment in route for {}", path)))?;
+            let id = T::from_str(&raw).map_err(|_| LanaiError::BadRequest(format!("Invalid ID '{}'", raw)))?;
+            let Some(checker) = ownership else {
+                error!("❌ No Ownership<{}> registered for {}", std::any::type_name::<T>(), path);
+                return Err(LanaiError::internal("ownership checker not configured"));
+            };
+
+            if !checker.owns(tenant.org_id, id).await? {
+                warn!("🚫 {} is not accessible to org {}", path, tenant.org_id);
+                return Err(LanaiError::NotFound(format!("Resource '{}' not found", raw)));
+            }
+            Ok(TenantScoped { id, org_id: tenant.org_id })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ProductId;
+    use actix_web::{test as actix_test, App, HttpResponse};
+
+    struct Owned(ProductId, Uuid);
+
+    #[async_trait]
+    impl OwnershipChecker<ProductId> for Owned {
+        async fn owns(&self, org_id: Uuid, id: ProductId) -> Result<bool, LanaiError> {
+            Ok(id == self.0 && org_id == self.1)
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_tenant_scoped_extractor() {
+        let (product, org_id, other_org) = (ProductId::generate(), Uuid::new_v4(), Uuid::new_v4());
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(Ownership::<ProductId>::new(Owned(product, org_id)))
+                .route(
+                    "/products/{id}",
+                    web::get().to(|p: TenantScoped<ProductId>| async move {
+                        HttpResponse::Ok().body(p.id.to_string())
+                    }),
+                ),
+        )
+        .await;
+
+        let request = |org: Uuid, id: String| {
+            let req = actix_test::TestRequest::get().uri(&format!("/products/{}", id)).to_request();
+            req.extensions_mut().insert(TenantContext { org_id: org });
+            req
+        };
+        let res = actix_test::call_service(&app, request(org_id, product.to_string())).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(actix_test::read_body(res).await, product.to_string());
+
+        let res = actix_test::call_service(&app, request(other_org, product.to_string())).await;
+        assert_eq!(res.status(), 404);
+        let res = actix_test::call_service(&app, request(org_id, "not-a-uuid".to_string())).await;
+        assert_eq!(res.status(), 400);
+        let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/products/x").to_request()).await;
+        assert_eq!(res.status(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_table_ownership_rejects_bad_identifiers() {
+        let pool = sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/lanai").unwrap();
+        assert!(TableOwnership::new(pool.clone(), "inventory.products").is_ok());
+        assert!(TableOwnership::new(pool, "products; DROP TABLE orgs").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Forwarding the authenticated user to downstream services
+//!
+//! A handler that calls another service takes a `ForwardedUser` and attaches it to the
+//! outbound HTTP request, gRPC call or NATS message, either as the original JWT
+//! (`Forwarding::Token`) or as a short-lived, HMAC-signed `X-Lanai-User-Context` header with
+//! only the fields needed for authorization (`Forwarding::Signed`). The signed form keeps
+//! the user's token out of internal traffic and logs.
+//!
+//! ```ignore
+//! async fn reserve(user: ForwardedUser, body: web::Json<Reserve>) -> LanaiResult<HttpResponse> {
+//!     let request = user.apply_http(http.post(inventory_url).json(&*body), Forwarding::Signed)?;
+//!     ...
+//! }
+//!
+//! // Downstream, behind the service token guard instead of AuthGuard:
+//! async fn reserve_stock(user: UserContext) -> LanaiResult<HttpResponse> {
+//!     if user.role != "admin" { return Err(LanaiError::Forbidden("admins only".into())); }
+//!     ...
+//! }
+//! ```
+//!
+//! Both sides share `LANAI_USER_CONTEXT_SECRET`.
+
+use actix_web::{dev::Payload, http::header, FromRequest, HttpMessage, HttpRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::future::{ready, Ready};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::auth_guard::Claims;
+use crate::error::LanaiError;
+
+/// Shared secret for signing and verifying user context headers.
+pub const USER_CONTEXT_SECRET_ENV: &str = "LANAI_USER_CONTEXT_SECRET";
+pub const USER_CONTEXT_HEADER: &str = "X-Lanai-User-Context";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// User context error types
+#[derive(Debug, Error, PartialEq)]
+pub enum UserContextError {
+    #[error("No authenticated user")]
+    Missing,
+
+    #[error("Malformed user context")]
+    Malformed,
+
+    #[error("Invalid user context signature")]
+    InvalidSignature,
+
+    #[error("User context expired")]
+    Expired,
+
+    #[error("{} is not set", USER_CONTEXT_SECRET_ENV)]
+    NotConfigured,
+}
+
+impl From<UserContextError> for LanaiError {
+    fn from(e: UserContextError) -> Self {
+        match e {
+            UserContextError::NotConfigured => LanaiError::Internal(e.to_string()),
+            _ => LanaiError::Unauthorized(e.to_string()),
+        }
+    }
+}
+
+/// What downstream services need to authorize a user's request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserContext {
+    pub sub: String,
+    pub org_id: Option<Uuid>,
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertical: Option<String>,
+    /// Unix seconds; never later than the original token's expiry.
+    pub exp: i64,
+}
+
+impl UserContext {
+    pub fn from_claims(claims: &Claims) -> Self {
+        Self {
+            sub: claims.sub.clone(),
+            org_id: claims.org_id.as_deref().and_then(|id| Uuid::parse_str(id).ok()),
+            role: claims.role.clone(),
+            vertical: claims.vertical.clone(),
+            exp: claims.exp,
+        }
+    }
+
+    /// Verify a context carried in gRPC metadata.
+    pub fn from_grpc<T>(request: &tonic::Request<T>, signer: &UserContextSigner) -> Result<Self, UserContextError> {
+        let value = request.metadata().get("x-lanai-user-context").ok_or(UserContextError::Missing)?;
+        signer.verify(value.to_str().map_err(|_| UserContextError::Malformed)?)
+    }
+
+    /// Verify a context carried in NATS headers.
+    pub fn from_nats(headers: Option<&async_nats::HeaderMap>, signer: &UserContextSigner) -> Result<Self, UserContextError> {
+        let value = headers.and_then(|h| h.get(USER_CONTEXT_HEADER)).ok_or(UserContextError::Missing)?;
+        signer.verify(value.as_str())
+    }
+}
+
+/// Signs and verifies `X-Lanai-User-Context` values: `base64url(json).base64url(hmac)`.
+#[derive(Clone)]
+pub struct UserContextSigner {
+    key: Arc<[u8]>,
+    ttl: Duration,
+}
+
+impl UserContextSigner {
+    /// Signed contexts expire after 60 seconds (or with the user's token, if sooner).
+    pub fn new(secret: &[u8]) -> Self {
+        Self { key: Arc::from(secret), ttl: Duration::from_secs(60) }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var(USER_CONTEXT_SECRET_ENV).ok().filter(|s| !s.is_empty()).map(|s| Self::new(s.as_bytes()))
+    }
+
+    /// Signer from `LANAI_USER_CONTEXT_SECRET`, read once.
+    pub fn global() -> Option<&'static Self> {
+        static SIGNER: OnceLock<Option<UserContextSigner>> = OnceLock::new();
+        SIGNER
+            .get_or_init(|| {
+                let signer = Self::from_env();
+                if signer.is_none() {
+                    warn!("⚠️ {} is not set; signed user contexts are unavailable", USER_CONTEXT_SECRET_ENV);
+                }
+                signer
+            })
+            .as_ref()
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    pub fn sign(&self, context: &UserContext) -> String {
+        let mut context = context.clone();
+        context.exp = context.exp.min(chrono::Utc::now().timestamp() + self.ttl.as_secs() as i64);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&context).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    pub fn verify(&self, value: &str) -> Result<UserContext, UserContextError> {
+        let (payload, signature) = value.split_once('.').ok_or(UserContextError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| UserContextError::Malformed)?;
+        self.mac(payload).verify_slice(&signature).map_err(|_| UserContextError::InvalidSignature)?;
+
+        let json = URL_SAFE_NO_PAD.decode(payload).map_err(|_| UserContextError::Malformed)?;
+        let context: UserContext = serde_json::from_slice(&json).map_err(|_| UserContextError::Malformed)?;
+        if context.exp <= chrono::Utc::now().timestamp() {
+            return Err(UserContextError::Expired);
+        }
+        Ok(context)
+    }
+}
+
+/// How the user is passed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forwarding {
+    /// The caller's JWT as `Authorization: Bearer`. Falls back to `Signed` when the request
+    /// itself arrived with a signed context.
+    Token,
+    /// A signed `X-Lanai-User-Context` with the global signer.
+    Signed,
+}
+
+/// The authenticated caller of the current request, ready to forward.
+///
+/// Extracted from the claims `AuthGuard` verified (with the bearer token they came in), or
+/// from a verified `X-Lanai-User-Context` header when the request came from another service.
+#[derive(Debug, Clone)]
+pub struct ForwardedUser {
+    context: UserContext,
+    token: Option<String>,
+}
+
+impl ForwardedUser {
+    pub fn new(context: UserContext, token: Option<String>) -> Self {
+        Self { context, token }
+    }
+
+    pub fn context(&self) -> &UserContext {
+        &self.context
+    }
+
+    /// The header to send, as (lowercase name, value).
+    pub fn header(&self, forwarding: Forwarding) -> Result<(&'static str, String), UserContextError> {
+        if let (Forwarding::Token, Some(token)) = (forwarding, &self.token) {
+            return Ok(("authorization", format!("Bearer {}", token)));
+        }
+        let signer = UserContextSigner::global().ok_or(UserContextError::NotConfigured)?;
+        Ok(("x-lanai-user-context", signer.sign(&self.context)))
+    }
+
+    pub fn apply_http(
+        &self,
+        request: reqwest::RequestBuilder,
+        forwarding: Forwarding,
+    ) -> Result<reqwest::RequestBuilder, UserContextError> {
+        let (name, value) = self.header(forwarding)?;
+        Ok(request.header(name, value))
+    }
+
+    pub fn apply_grpc<T>(&self, request: &mut tonic::Request<T>, forwarding: Forwarding) -> Result<(), UserContextError> {
+        let (name, value) = self.header(forwarding)?;
+        let value = value.parse().map_err(|_| UserContextError::Malformed)?;
+        request.metadata_mut().insert(name, value);
+        Ok(())
+    }
+
+    pub fn nats_headers(
+        &self,
+        mut headers: async_nats::HeaderMap,
+        forwarding: Forwarding,
+    ) -> Result<async_nats::HeaderMap, UserContextError> {
+        let (name, value) = self.header(forwarding)?;
+        let name = if name == "authorization" { "Authorization" } else { USER_CONTEXT_HEADER };
+        headers.insert(name, value.as_str());
+        Ok(headers)
+    }
+
+    fn from_http(req: &HttpRequest) -> Result<Self, UserContextError> {
+        if let Some(claims) = req.extensions().get::<Claims>() {
+            let token = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(str::to_string);
+            return Ok(Self { context: UserContext::from_claims(claims), token });
+        }
+
+        let value = req.headers().get(USER_CONTEXT_HEADER).ok_or(UserContextError::Missing)?;
+        let signer = UserContextSigner::global().ok_or(UserContextError::NotConfigured)?;
+        let context = signer.verify(value.to_str().map_err(|_| UserContextError::Malformed)?)?;
+        Ok(Self { context, token: None })
+    }
+}
+
+impl FromRequest for ForwardedUser {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Self::from_http(req).map_err(LanaiError::from))
+    }
+}
+
+impl FromRequest for UserContext {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(ForwardedUser::from_http(req).map(|user| user.context).map_err(LanaiError::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = UserContextSigner::new(b"shared-secret");
+        let context = UserContext {
+            sub: "user-1".to_string(),
+            org_id: Some(Uuid::new_v4()),
+            role: "cashier".to_string(),
+            vertical: None,
+            exp: chrono::Utc::now().timestamp() + 3600,
+        };
+
+        let value = signer.sign(&context);
+        let verified = signer.verify(&value).unwrap();
+        assert_eq!(verified.sub, "user-1");
+        assert!(verified.exp <= chrono::Utc::now().timestamp() + 60);
+
+        let other = UserContextSigner::new(b"other-secret");
+        assert_eq!(other.verify(&value), Err(UserContextError::InvalidSignature));
+
+        let expired = UserContext { exp: chrono::Utc::now().timestamp() - 1, ..context };
+        assert_eq!(signer.verify(&signer.sign(&expired)), Err(UserContextError::Expired));
+    }
+}
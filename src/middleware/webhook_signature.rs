@@ -0,0 +1,225 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::webhooks::replay::{InMemoryReplayCache, ReplayCache};
+use crate::webhooks::signing::{self, SignatureError, Verified, SIGNATURE_HEADER};
+
+/// Inbound webhook verification middleware
+///
+/// Rejects requests whose `t=...,v1=...` signature header (see `webhooks::signing`) does not
+/// match the raw body under one of the configured secrets (401), is outside the timestamp
+/// tolerance (401), or has already been accepted (409). Verified bodies are passed on intact.
+///
+/// ```ignore
+/// web::scope("/webhooks/payments").wrap(
+///     WebhookSignatureMiddleware::new(secret)
+///         .header("Stripe-Signature")
+///         .replay_cache(Arc::new(RedisReplayCache::new(shared_connection().await?))),
+/// )
+/// ```
+#[derive(Clone)]
+pub struct WebhookSignatureMiddleware {
+    config: VerifierConfig,
+}
+
+#[derive(Clone)]
+struct VerifierConfig {
+    secrets: Vec<String>,
+    header: String,
+    tolerance: Duration,
+    replay_cache: Arc<dyn ReplayCache>,
+}
+
+impl WebhookSignatureMiddleware {
+    /// Reads `Lanai-Signature` with a 5 minute tolerance and an in-memory replay cache.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            config: VerifierConfig {
+                secrets: vec![secret.into()],
+                header: SIGNATURE_HEADER.to_string(),
+                tolerance: Duration::from_secs(300),
+                replay_cache: Arc::new(InMemoryReplayCache::new()),
+            },
+        }
+    }
+
+    /// Also accept signatures made with `secret` (for rotation).
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.config.secrets.push(secret.into());
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.config.header = name.into();
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.config.tolerance = tolerance;
+        self
+    }
+
+    /// Use a shared cache (e.g. `RedisReplayCache`) when running several replicas.
+    pub fn replay_cache(mut self, cache: Arc<dyn ReplayCache>) -> Self {
+        self.config.replay_cache = cache;
+        self
+    }
+}
+
+impl VerifierConfig {
+    fn verify(&self, header: &str, body: &[u8]) -> Result<Verified, SignatureError> {
+        let now = chrono::Utc::now().timestamp();
+        let mut error = SignatureError::Mismatch;
+        for secret in &self.secrets {
+            match signing::verify(secret, header, body, self.tolerance, now) {
+                Ok(verified) => return Ok(verified),
+                // Malformed or expired headers fail the same way for every secret.
+                Err(e @ (SignatureError::Malformed | SignatureError::Expired(_))) => return Err(e),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for WebhookSignatureMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    S: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = WebhookSignatureMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(WebhookSignatureMiddlewareService {
+            service: Rc::new(service),
+            config: Arc::new(self.config.clone()),
+        }))
+    }
+}
+
+pub struct WebhookSignatureMiddlewareService<S> {
+    service: Rc<S>,
+    config: Arc<VerifierConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for WebhookSignatureMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    S: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let config = Arc::clone(&self.config);
+
+        Box::pin(async move {
+            let header = req
+                .headers()
+                .get(config.header.as_str())
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let Some(header) = header else {
+                let response = HttpResponse::Unauthorized()
+                    .json(serde_json::json!({"error": format!("Missing {} header", config.header)}));
+                return Ok(req.into_response(response));
+            };
+
+            let body = req.extract::<web::Bytes>().await?;
+
+            let verified = match config.verify(&header, &body) {
+                Ok(verified) => verified,
+                Err(e) => {
+                    warn!("🚫 Rejected webhook to {}: {}", req.path(), e);
+                    let response = HttpResponse::Unauthorized().json(serde_json::json!({"error": e.to_string()}));
+                    return Ok(req.into_response(response));
+                }
+            };
+
+            // Remember the signature until its timestamp falls out of tolerance anyway. Keyed
+            // on what was signed, not the header text, which a replay can reformat.
+            if !config.replay_cache.first_seen(&verified.replay_key(), config.tolerance * 2).await {
+                warn!("🚫 Rejected replayed webhook to {}", req.path());
+                let response = HttpResponse::Conflict().json(serde_json::json!({"error": "Webhook already received"}));
+                return Ok(req.into_response(response));
+            }
+
+            req.set_payload(Payload::from(body));
+            service.call(req).await.map(|res| res.map_body(|_, body| body.boxed()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    const SECRET: &str = "whsec_test";
+
+    fn delivery(signature: Option<String>, body: &'static str) -> test::TestRequest {
+        let request = test::TestRequest::post().uri("/webhooks").set_payload(body);
+        match signature {
+            Some(signature) => request.insert_header((SIGNATURE_HEADER, signature)),
+            None => request,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_accepts_signed_delivery_once() {
+        let app = test::init_service(App::new().wrap(WebhookSignatureMiddleware::new(SECRET)).route(
+            "/webhooks",
+            web::post().to(|body: web::Bytes| async move { HttpResponse::Ok().body(body) }),
+        ))
+        .await;
+        let signature = signing::sign(SECRET, chrono::Utc::now().timestamp(), br#"{"id":1}"#);
+
+        let res = test::call_service(&app, delivery(Some(signature.clone()), r#"{"id":1}"#).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, r#"{"id":1}"#);
+
+        let replay = test::call_service(&app, delivery(Some(signature), r#"{"id":1}"#).to_request()).await;
+        assert_eq!(replay.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_missing_bad_and_stale_signatures() {
+        let app = test::init_service(
+            App::new().wrap(WebhookSignatureMiddleware::new(SECRET)).route("/webhooks", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let now = chrono::Utc::now().timestamp();
+        let rejections = [
+            delivery(None, "{}"),
+            delivery(Some(signing::sign("whsec_other", now, b"{}")), "{}"),
+            delivery(Some(signing::sign(SECRET, now, b"{}")), r#"{"id":2}"#),
+            delivery(Some(signing::sign(SECRET, now - 600, b"{}")), "{}"),
+        ];
+
+        for request in rejections {
+            let res = test::call_service(&app, request.to_request()).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
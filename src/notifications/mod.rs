@@ -0,0 +1,399 @@
+//! Notifications (email, SMS, push)
+//!
+//! Services describe *what* to send as a `Notification` (channel, recipient, template
+//! name, variables); the `Notifier` renders the template, picks the tenant's sender
+//! identity and hands the message to the provider registered for the channel. Templates
+//! not registered on the notifier come from its `TemplateEngine` (see `templates`) as
+//! `{channel}/{name}`, so tenants can override them and they can be localized.
+//!
+//! Notifications are normally enqueued on a job queue so delivery gets the jobs
+//! subsystem's retries and dead-letter stream:
+//!
+//! ```ignore
+//! let notifier = Arc::new(
+//!     Notifier::new()
+//!         .provider(SmtpProvider::from_env()?)
+//!         .provider(WebhookProvider::new(Channel::Sms, &sms_gateway_url))
+//!         .default_sender(Channel::Email, Sender::named("no-reply@lanai.app", "Lanai"))
+//!         .template("order_shipped", Channel::Email, Template::text("Order {{number}} shipped").with_subject("Your order is on its way")),
+//! );
+//!
+//! let queue = JobQueue::new(shared_connection().await?, "notifications");
+//! notifier.enqueue(&queue, &Notification::email(&customer.email, "order_shipped", json!({ "number": 42 })).for_tenant(&tenant)).await?;
+//!
+//! notifier.clone().register(WorkerPool::new(queue)).start().await?;
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod smtp;
+pub mod template;
+pub mod webhook;
+
+pub use smtp::SmtpProvider;
+pub use template::{Rendered, Template};
+pub use webhook::WebhookProvider;
+
+use crate::i18n::Locale;
+use crate::jobs::{Job, JobError, JobQueue, WorkerPool};
+use crate::middleware::tenant_context::TenantContext;
+use crate::templates::{TemplateEngine, TemplateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Sms,
+    Push,
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Sms => "sms",
+            Self::Push => "push",
+        }
+    }
+}
+
+/// Sender identity: email address, SMS sender ID or push app identifier.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sender {
+    pub address: String,
+    pub name: Option<String>,
+}
+
+impl Sender {
+    pub fn new(address: &str) -> Self {
+        Self { address: address.to_string(), name: None }
+    }
+
+    pub fn named(address: &str, name: &str) -> Self {
+        Self { address: address.to_string(), name: Some(name.to_string()) }
+    }
+}
+
+/// Request to notify someone; the payload of the notification job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub channel: Channel,
+    pub to: String,
+    pub template: String,
+    #[serde(default)]
+    pub vars: serde_json::Value,
+    /// Organization whose sender configuration applies.
+    #[serde(default)]
+    pub org_id: Option<Uuid>,
+    /// Locale of the recipient, for localized templates.
+    #[serde(default)]
+    pub locale: Option<Locale>,
+}
+
+impl Notification {
+    pub fn new(channel: Channel, to: &str, template: &str, vars: serde_json::Value) -> Self {
+        Self { channel, to: to.to_string(), template: template.to_string(), vars, org_id: None, locale: None }
+    }
+
+    pub fn email(to: &str, template: &str, vars: serde_json::Value) -> Self {
+        Self::new(Channel::Email, to, template, vars)
+    }
+
+    pub fn sms(to: &str, template: &str, vars: serde_json::Value) -> Self {
+        Self::new(Channel::Sms, to, template, vars)
+    }
+
+    pub fn push(device_token: &str, template: &str, vars: serde_json::Value) -> Self {
+        Self::new(Channel::Push, device_token, template, vars)
+    }
+
+    pub fn for_tenant(mut self, tenant: &TenantContext) -> Self {
+        self.org_id = Some(tenant.org_id);
+        self
+    }
+
+    pub fn in_locale(mut self, locale: Locale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+}
+
+impl Job for Notification {
+    const NAME: &'static str = "lanai.notification";
+}
+
+/// A rendered message ready for a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub channel: Channel,
+    pub to: String,
+    pub from: Sender,
+    pub subject: Option<String>,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+/// Notification error types
+#[derive(Debug, Error)]
+pub enum NotificationError {
+    #[error("Notification provider is not configured: {0}")]
+    NotConfigured(String),
+
+    #[error("No provider registered for {0:?}")]
+    NoProvider(Channel),
+
+    #[error("No sender configured for {0:?}")]
+    NoSender(Channel),
+
+    #[error("Unknown template '{0}' for {1:?}")]
+    UnknownTemplate(String, Channel),
+
+    #[error("Template variable '{0}' is missing")]
+    MissingVariable(String),
+
+    #[error("Invalid template: {0}")]
+    Template(String),
+
+    #[error("Invalid recipient: {0}")]
+    InvalidRecipient(String),
+
+    #[error("Provider failed: {0}")]
+    Provider(String),
+
+    #[error("Failed to enqueue notification: {0}")]
+    Queue(#[from] JobError),
+}
+
+#[async_trait]
+pub trait NotificationProvider: Send + Sync {
+    fn channel(&self) -> Channel;
+
+    async fn send(&self, message: &Message) -> Result<(), NotificationError>;
+}
+
+/// Per-tenant sender identities (e.g. loaded from an `org_settings` table).
+#[async_trait]
+pub trait SenderDirectory: Send + Sync {
+    async fn sender(&self, org_id: Uuid, channel: Channel) -> Option<Sender>;
+}
+
+/// In-memory `SenderDirectory`.
+#[derive(Debug, Clone, Default)]
+pub struct StaticSenders {
+    senders: HashMap<(Uuid, Channel), Sender>,
+}
+
+impl StaticSenders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, org_id: Uuid, channel: Channel, sender: Sender) -> Self {
+        self.senders.insert((org_id, channel), sender);
+        self
+    }
+}
+
+#[async_trait]
+impl SenderDirectory for StaticSenders {
+    async fn sender(&self, org_id: Uuid, channel: Channel) -> Option<Sender> {
+        self.senders.get(&(org_id, channel)).cloned()
+    }
+}
+
+#[derive(Default)]
+pub struct Notifier {
+    providers: HashMap<Channel, Arc<dyn NotificationProvider>>,
+    templates: HashMap<(String, Channel), Template>,
+    engine: Option<TemplateEngine>,
+    default_senders: HashMap<Channel, Sender>,
+    senders: Option<Arc<dyn SenderDirectory>>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `provider` for its channel, replacing any previous one.
+    pub fn provider<P: NotificationProvider + 'static>(mut self, provider: P) -> Self {
+        self.providers.insert(provider.channel(), Arc::new(provider));
+        self
+    }
+
+    pub fn template(mut self, name: &str, channel: Channel, template: Template) -> Self {
+        self.templates.insert((name.to_string(), channel), template);
+        self
+    }
+
+    /// Render templates not registered with `template` from `engine`, as `{channel}/{name}`.
+    pub fn templates(mut self, engine: TemplateEngine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Sender used when the tenant has none configured (or the notification has no tenant).
+    pub fn default_sender(mut self, channel: Channel, sender: Sender) -> Self {
+        self.default_senders.insert(channel, sender);
+        self
+    }
+
+    pub fn senders<D: SenderDirectory + 'static>(mut self, directory: D) -> Self {
+        self.senders = Some(Arc::new(directory));
+        self
+    }
+
+    /// Render `notification` into the message its provider will receive.
+    pub async fn render(&self, notification: &Notification) -> Result<Message, NotificationError> {
+        let template = self.templates.get(&(notification.template.clone(), notification.channel));
+        let rendered = match (template, &self.engine) {
+            (Some(template), _) => template.render(&notification.vars)?,
+            (None, Some(engine)) => {
+                let key = format!("{}/{}", notification.channel.as_str(), notification.template);
+                let rendered = engine
+                    .render(notification.org_id, &key, notification.locale, &notification.vars)
+                    .await
+                    .map_err(|e| match e {
+                        TemplateError::NotFound(_) => {
+                            NotificationError::UnknownTemplate(notification.template.clone(), notification.channel)
+                        }
+                        other => NotificationError::Template(other.to_string()),
+                    })?;
+                Rendered { subject: rendered.subject, text: rendered.body, html: rendered.html }
+            }
+            (None, None) => {
+                return Err(NotificationError::UnknownTemplate(notification.template.clone(), notification.channel))
+            }
+        };
+
+        let tenant_sender = match (&self.senders, notification.org_id) {
+            (Some(directory), Some(org_id)) => directory.sender(org_id, notification.channel).await,
+            _ => None,
+        };
+        let from = tenant_sender
+            .or_else(|| self.default_senders.get(&notification.channel).cloned())
+            .ok_or(NotificationError::NoSender(notification.channel))?;
+
+        Ok(Message {
+            channel: notification.channel,
+            to: notification.to.clone(),
+            from,
+            subject: rendered.subject,
+            text: rendered.text,
+            html: rendered.html,
+        })
+    }
+
+    /// Render and deliver immediately, without retries.
+    pub async fn send(&self, notification: &Notification) -> Result<(), NotificationError> {
+        let provider = self
+            .providers
+            .get(&notification.channel)
+            .ok_or(NotificationError::NoProvider(notification.channel))?;
+        let message = self.render(notification).await?;
+        provider.send(&message).await?;
+        log::info!("📨 Sent '{}' {:?} notification", notification.template, notification.channel);
+        Ok(())
+    }
+
+    /// Queue `notification` for delivery by a worker registered with `register`.
+    pub async fn enqueue(&self, queue: &JobQueue, notification: &Notification) -> Result<Uuid, NotificationError> {
+        // Fail fast on notifications that could never be delivered.
+        self.render(notification).await?;
+        Ok(queue.enqueue(notification).await?)
+    }
+
+    /// Handle notification jobs on `pool`; failures follow the pool's retry policy and dead-letter stream.
+    pub fn register(self: Arc<Self>, pool: WorkerPool) -> WorkerPool {
+        pool.register(move |notification: Notification| {
+            let notifier = self.clone();
+            async move { notifier.send(&notification).await }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::MessageTemplate;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recording {
+        sent: Mutex<Vec<Message>>,
+    }
+
+    #[async_trait]
+    impl NotificationProvider for Arc<Recording> {
+        fn channel(&self) -> Channel {
+            Channel::Email
+        }
+
+        async fn send(&self, message: &Message) -> Result<(), NotificationError> {
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_uses_tenant_sender() {
+        let org_id = Uuid::new_v4();
+        let recording = Arc::new(Recording::default());
+        let notifier = Notifier::new()
+            .provider(recording.clone())
+            .template("welcome", Channel::Email, Template::text("Welcome {{name}}").with_subject("Hi"))
+            .default_sender(Channel::Email, Sender::new("no-reply@lanai.app"))
+            .senders(StaticSenders::new().with(org_id, Channel::Email, Sender::named("hello@acme.test", "Acme")));
+
+        let welcome = Notification::email("ana@example.com", "welcome", json!({ "name": "Ana" }));
+        notifier.send(&welcome).await.unwrap();
+        notifier.send(&welcome.clone().for_tenant(&TenantContext { org_id })).await.unwrap();
+
+        let sent = recording.sent.lock().unwrap();
+        assert_eq!(sent[0].from.address, "no-reply@lanai.app");
+        assert_eq!(sent[1].from, Sender::named("hello@acme.test", "Acme"));
+        assert_eq!(sent[1].text, "Welcome Ana");
+    }
+
+    #[tokio::test]
+    async fn test_engine_templates_are_localized() {
+        let recording = Arc::new(Recording::default());
+        let notifier = Notifier::new()
+            .provider(recording.clone())
+            .templates(
+                TemplateEngine::new()
+                    .default_template("email/welcome", MessageTemplate::new("Welcome {{ name }}"))
+                    .default_template("email/welcome.es", MessageTemplate::new("Bienvenida {{ name }}")),
+            )
+            .default_sender(Channel::Email, Sender::new("no-reply@lanai.app"));
+
+        let welcome = Notification::email("ana@example.com", "welcome", json!({ "name": "Ana" }));
+        notifier.send(&welcome).await.unwrap();
+        notifier.send(&welcome.in_locale(Locale::Es)).await.unwrap();
+
+        let sent = recording.sent.lock().unwrap();
+        assert_eq!(sent[0].text, "Welcome Ana");
+        assert_eq!(sent[1].text, "Bienvenida Ana");
+    }
+
+    #[tokio::test]
+    async fn test_undeliverable_notifications_are_rejected() {
+        let notifier = Notifier::new().template("welcome", Channel::Email, Template::text("Hi"));
+        let sms = Notification::sms("+5511999999999", "welcome", json!({}));
+        assert!(matches!(notifier.send(&sms).await, Err(NotificationError::NoProvider(Channel::Sms))));
+        assert!(matches!(
+            notifier.render(&sms).await,
+            Err(NotificationError::UnknownTemplate(_, Channel::Sms))
+        ));
+        assert!(matches!(
+            notifier.render(&Notification::email("a@b.c", "welcome", json!({}))).await,
+            Err(NotificationError::NoSender(Channel::Email))
+        ));
+    }
+}
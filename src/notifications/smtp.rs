@@ -0,0 +1,79 @@
+//! Email over SMTP
+
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+use super::{Channel, Message, NotificationError, NotificationProvider};
+
+pub const SMTP_HOST_ENV: &str = "SMTP_HOST";
+pub const SMTP_PORT_ENV: &str = "SMTP_PORT";
+pub const SMTP_USERNAME_ENV: &str = "SMTP_USERNAME";
+pub const SMTP_PASSWORD_ENV: &str = "SMTP_PASSWORD";
+/// `starttls` (default), `tls`, or `none` for local catch-all servers such as MailHog.
+pub const SMTP_TLS_ENV: &str = "SMTP_TLS";
+
+pub struct SmtpProvider {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpProvider {
+    pub fn new(transport: AsyncSmtpTransport<Tokio1Executor>) -> Self {
+        Self { transport }
+    }
+
+    pub fn from_env() -> Result<Self, NotificationError> {
+        let host = std::env::var(SMTP_HOST_ENV)
+            .map_err(|_| NotificationError::NotConfigured(SMTP_HOST_ENV.to_string()))?;
+        let tls = std::env::var(SMTP_TLS_ENV).unwrap_or_else(|_| "starttls".to_string());
+
+        let mut builder = match tls.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+        }
+        .map_err(|e| NotificationError::NotConfigured(format!("{}: {}", SMTP_HOST_ENV, e)))?;
+
+        if let Some(port) = std::env::var(SMTP_PORT_ENV).ok().and_then(|p| p.parse().ok()) {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (std::env::var(SMTP_USERNAME_ENV), std::env::var(SMTP_PASSWORD_ENV)) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(Self::new(builder.build()))
+    }
+}
+
+fn mailbox(name: Option<&str>, address: &str) -> Result<Mailbox, NotificationError> {
+    let address = address
+        .parse()
+        .map_err(|e| NotificationError::InvalidRecipient(format!("{}: {}", address, e)))?;
+    Ok(Mailbox::new(name.map(str::to_string), address))
+}
+
+#[async_trait]
+impl NotificationProvider for SmtpProvider {
+    fn channel(&self) -> Channel {
+        Channel::Email
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), NotificationError> {
+        let builder = lettre::Message::builder()
+            .from(mailbox(message.from.name.as_deref(), &message.from.address)?)
+            .to(mailbox(None, &message.to)?)
+            .subject(message.subject.clone().unwrap_or_default());
+
+        let email = match &message.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(message.text.clone(), html.clone())),
+            None => builder.header(ContentType::TEXT_PLAIN).body(message.text.clone()),
+        }
+        .map_err(|e| NotificationError::Provider(e.to_string()))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| NotificationError::Provider(e.to_string()))?;
+        Ok(())
+    }
+}
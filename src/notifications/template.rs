@@ -0,0 +1,113 @@
+//! Minimal `{{ variable }}` templates
+//!
+//! Placeholders name a (dotted) path into the JSON variables, e.g. `{{ order.number }}`.
+//! A missing variable is an error rather than an empty string, so broken templates are
+//! caught before a customer receives "Hello ,".
+
+use serde_json::Value;
+
+use super::NotificationError;
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub subject: Option<String>,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+/// Rendered subject and bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub subject: Option<String>,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+impl Template {
+    pub fn text(text: &str) -> Self {
+        Self { subject: None, text: text.to_string(), html: None }
+    }
+
+    pub fn with_subject(mut self, subject: &str) -> Self {
+        self.subject = Some(subject.to_string());
+        self
+    }
+
+    pub fn with_html(mut self, html: &str) -> Self {
+        self.html = Some(html.to_string());
+        self
+    }
+
+    pub fn render(&self, vars: &Value) -> Result<Rendered, NotificationError> {
+        Ok(Rendered {
+            subject: self.subject.as_deref().map(|s| render(s, vars, false)).transpose()?,
+            text: render(&self.text, vars, false)?,
+            html: self.html.as_deref().map(|h| render(h, vars, true)).transpose()?,
+        })
+    }
+}
+
+fn render(source: &str, vars: &Value, escape: bool) -> Result<String, NotificationError> {
+    let mut output = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| NotificationError::Template("unclosed '{{'".to_string()))?;
+        let name = after[..end].trim();
+
+        let value = name
+            .split('.')
+            .try_fold(vars, |node, key| node.get(key))
+            .ok_or_else(|| NotificationError::MissingVariable(name.to_string()))?;
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        output.push_str(&if escape { escape_html(&value) } else { value });
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_nested_and_escaped() {
+        let template = Template::text("Hi {{ customer.name }}, order #{{order}} shipped")
+            .with_subject("Order {{order}}")
+            .with_html("<p>Hi {{ customer.name }}</p>");
+        let rendered = template
+            .render(&json!({ "customer": { "name": "Ana <3" }, "order": 42 }))
+            .unwrap();
+
+        assert_eq!(rendered.subject.as_deref(), Some("Order 42"));
+        assert_eq!(rendered.text, "Hi Ana <3, order #42 shipped");
+        assert_eq!(rendered.html.as_deref(), Some("<p>Hi Ana &lt;3</p>"));
+    }
+
+    #[test]
+    fn test_missing_variable_is_an_error() {
+        let result = Template::text("Hi {{ name }}").render(&json!({}));
+        assert!(matches!(result, Err(NotificationError::MissingVariable(name)) if name == "name"));
+        assert!(Template::text("Hi {{ name").render(&json!({ "name": "x" })).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+//! Delivery through an HTTP gateway (SMS aggregators, push relays, internal notifiers)
+//!
+//! Posts the rendered message as JSON:
+//! `{"channel": "sms", "to": "+5511...", "from": {...}, "subject": null, "text": "...", "html": null}`.
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::{Channel, Message, NotificationError, NotificationProvider};
+
+pub struct WebhookProvider {
+    channel: Channel,
+    url: String,
+    bearer_token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookProvider {
+    pub fn new(channel: Channel, url: &str) -> Self {
+        Self {
+            channel,
+            url: url.to_string(),
+            bearer_token: None,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.bearer_token = Some(token.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl NotificationProvider for WebhookProvider {
+    fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), NotificationError> {
+        let mut request = self.client.post(&self.url).json(message);
+        if let Some(token) = &self.bearer_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| NotificationError::Provider(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            return Err(NotificationError::Provider(format!("{} from {}: {}", status, self.url, detail)));
+        }
+        Ok(())
+    }
+}
@@ -3,12 +3,23 @@ use opentelemetry::{global, KeyValue, trace::TracerProvider as _};
 use opentelemetry_sdk::{Resource, trace::TracerProvider as SdkTracerProvider};
 use opentelemetry_otlp::WithExportConfig;
 
+use crate::env::{EnvVar, VarType};
+
+/// OTLP collector endpoint traces are exported to
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Variables read by `init_tracing`
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar::new(OTLP_ENDPOINT_ENV, VarType::Url).description("OTLP collector endpoint").default_value("http://localhost:4317"),
+    EnvVar::new("RUST_LOG", VarType::String).description("Log filter").default_value("info,actix_web=info"),
+];
+
 pub fn init_tracing(service_name: &str) {
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info,actix_web=info"));
 
     // Check if OTLP endpoint is set, otherwise default to localhost
-    let otlp_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+    let otlp_endpoint = std::env::var(OTLP_ENDPOINT_ENV)
         .unwrap_or_else(|_| "http://localhost:4317".to_string());
 
     // Create OTLP exporter using SpanExporter::builder (v0.27+)
@@ -51,3 +62,71 @@ pub fn init_tracing(service_name: &str) {
 pub fn shutdown_tracing() {
     global::shutdown_tracer_provider();
 }
+
+/// Trace ID of the current span, if it is part of a sampled or propagated trace.
+pub fn current_trace_id() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+/// W3C `traceparent` of the current span, to store with work that runs after the request
+/// or message that started it (see `link_span`).
+pub fn current_traceparent() -> Option<String> {
+    use opentelemetry::propagation::TextMapPropagator;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let mut carrier = std::collections::HashMap::new();
+    opentelemetry_sdk::propagation::TraceContextPropagator::new()
+        .inject_context(&tracing::Span::current().context(), &mut carrier);
+    carrier.remove("traceparent")
+}
+
+/// Span context described by a W3C `traceparent` value, if it is valid.
+pub fn span_context_from_traceparent(traceparent: &str) -> Option<opentelemetry::trace::SpanContext> {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::TraceContextExt;
+
+    let carrier = std::collections::HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+    let context = opentelemetry_sdk::propagation::TraceContextPropagator::new().extract(&carrier);
+    let span_context = context.span().span_context().clone();
+    span_context.is_valid().then_some(span_context)
+}
+
+/// Link `span` to `origin`, the span of the request or message that caused it. Unlike a
+/// parent, a link keeps deferred work (jobs, resumed sagas) in its own trace while pointing
+/// back to where it came from. Links within `span`'s own trace are skipped, since
+/// parent/child already connects them.
+pub fn link_span(span: &tracing::Span, origin: opentelemetry::trace::SpanContext) {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if !origin.is_valid() || span.context().span().span_context().trace_id() == origin.trace_id() {
+        return;
+    }
+    span.add_link(origin);
+}
+
+/// `link_span` to a stored `traceparent`; does nothing for `None` or an invalid value.
+pub fn link_span_to_traceparent(span: &tracing::Span, traceparent: Option<&str>) {
+    if let Some(origin) = traceparent.and_then(span_context_from_traceparent) {
+        link_span(span, origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_context_from_traceparent() {
+        let span_context = span_context_from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+        assert!(span_context_from_traceparent("not-a-traceparent").is_none());
+    }
+}
@@ -0,0 +1,289 @@
+//! Data Subject Requests (GDPR / LGPD / Ley 29733)
+//!
+//! A data export or erasure request is published once, on `lanai.privacy.export.requested`
+//! or `lanai.privacy.erasure.requested`. The `PrivacyOrchestrator` (run by one service,
+//! usually the account service) fans it out to every registered service over
+//! `lanai.privacy.{service}.{export|erase}` as a saga, collects each service's result and
+//! publishes a `PrivacyReport` on `lanai.privacy.{export|erasure}.completed`.
+//!
+//! Every service holding personal data implements `PrivacyHandler` and serves it with
+//! `PrivacyResponder`:
+//!
+//! ```ignore
+//! struct OrdersPrivacy { pool: PgPool }
+//!
+//! #[async_trait]
+//! impl PrivacyHandler for OrdersPrivacy {
+//!     fn service(&self) -> &str { "orders" }
+//!
+//!     async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value, PrivacyError> {
+//!         let orders = load_orders(&self.pool, subject.org_id, subject.user_id).await?;
+//!         Ok(serde_json::json!({ "orders": orders }))
+//!     }
+//!
+//!     async fn erase(&self, subject: &DataSubject) -> Result<ErasureSummary, PrivacyError> {
+//!         let erased = anonymize_orders(&self.pool, subject.org_id, subject.user_id).await?;
+//!         Ok(ErasureSummary::erased(erased).retained("invoices kept 10 years for tax law"))
+//!     }
+//! }
+//!
+//! let _responder = PrivacyResponder::new(Arc::new(OrdersPrivacy { pool })).start().await?;
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::messaging::events::decode_event;
+use crate::messaging::{NatsClient, NatsError};
+
+pub mod orchestrator;
+
+pub use orchestrator::PrivacyOrchestrator;
+
+pub const EXPORT_REQUESTED_SUBJECT: &str = "lanai.privacy.export.requested";
+pub const ERASURE_REQUESTED_SUBJECT: &str = "lanai.privacy.erasure.requested";
+pub const EXPORT_COMPLETED_SUBJECT: &str = "lanai.privacy.export.completed";
+pub const ERASURE_COMPLETED_SUBJECT: &str = "lanai.privacy.erasure.completed";
+
+/// Privacy request error types
+#[derive(Debug, Error)]
+pub enum PrivacyError {
+    #[error("Privacy handler failed: {0}")]
+    Handler(String),
+
+    #[error(transparent)]
+    Nats(#[from] NatsError),
+}
+
+impl From<sqlx::Error> for PrivacyError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Handler(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyRequestKind {
+    Export,
+    Erasure,
+}
+
+impl PrivacyRequestKind {
+    /// Verb used in per-service subjects.
+    fn action(&self) -> &'static str {
+        match self {
+            Self::Export => "export",
+            Self::Erasure => "erase",
+        }
+    }
+
+    pub fn completed_subject(&self) -> &'static str {
+        match self {
+            Self::Export => EXPORT_COMPLETED_SUBJECT,
+            Self::Erasure => ERASURE_COMPLETED_SUBJECT,
+        }
+    }
+}
+
+/// The person whose data is requested.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataSubject {
+    pub org_id: Uuid,
+    pub user_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+/// Payload of `lanai.privacy.{export|erasure}.requested`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyRequest {
+    pub request_id: Uuid,
+    pub kind: PrivacyRequestKind,
+    pub subject: DataSubject,
+    pub requested_at: DateTime<Utc>,
+    /// Who filed the request (the subject, or support staff on their behalf).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_by: Option<String>,
+}
+
+impl PrivacyRequest {
+    pub fn export(subject: DataSubject) -> Self {
+        Self::new(PrivacyRequestKind::Export, subject)
+    }
+
+    pub fn erasure(subject: DataSubject) -> Self {
+        Self::new(PrivacyRequestKind::Erasure, subject)
+    }
+
+    fn new(kind: PrivacyRequestKind, subject: DataSubject) -> Self {
+        Self { request_id: Uuid::new_v4(), kind, subject, requested_at: Utc::now(), requested_by: None }
+    }
+
+    pub fn requested_by(mut self, requested_by: &str) -> Self {
+        self.requested_by = Some(requested_by.to_string());
+        self
+    }
+
+    pub fn subject(&self) -> &'static str {
+        match self.kind {
+            PrivacyRequestKind::Export => EXPORT_REQUESTED_SUBJECT,
+            PrivacyRequestKind::Erasure => ERASURE_REQUESTED_SUBJECT,
+        }
+    }
+
+    /// File the request.
+    pub async fn submit(&self) -> Result<(), NatsError> {
+        NatsClient::publish_event(self.subject(), self).await
+    }
+}
+
+/// What a service erased, and what it had to keep.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErasureSummary {
+    pub records_erased: u64,
+    /// Legal reasons for data that was kept (e.g. tax records).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retained: Vec<String>,
+}
+
+impl ErasureSummary {
+    pub fn erased(records: u64) -> Self {
+        Self { records_erased: records, retained: Vec::new() }
+    }
+
+    pub fn retained(mut self, reason: &str) -> Self {
+        self.retained.push(reason.to_string());
+        self
+    }
+}
+
+/// One service's result for a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ServiceOutcome {
+    Exported { data: serde_json::Value },
+    Erased(ErasureSummary),
+    Failed { reason: String },
+}
+
+impl ServiceOutcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed { .. })
+    }
+}
+
+/// Implemented by every service that stores personal data.
+#[async_trait]
+pub trait PrivacyHandler: Send + Sync {
+    /// Name the orchestrator registers this service under.
+    fn service(&self) -> &str;
+
+    /// Everything the service holds about `subject`, as JSON.
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value, PrivacyError>;
+
+    /// Delete or anonymize `subject`'s data. Must be idempotent: requests are retried.
+    async fn erase(&self, subject: &DataSubject) -> Result<ErasureSummary, PrivacyError>;
+}
+
+/// Per-service request subject, e.g. `lanai.privacy.orders.erase`.
+pub fn service_subject(service: &str, kind: PrivacyRequestKind) -> String {
+    format!("lanai.privacy.{}.{}", service, kind.action())
+}
+
+/// Serves a `PrivacyHandler` on its request-reply subjects. Replicas share the work
+/// through a queue group.
+pub struct PrivacyResponder {
+    handler: Arc<dyn PrivacyHandler>,
+}
+
+impl PrivacyResponder {
+    pub fn new(handler: Arc<dyn PrivacyHandler>) -> Self {
+        Self { handler }
+    }
+
+    /// Answer requests until the handles are aborted.
+    pub async fn start(self) -> Result<Vec<JoinHandle<()>>, NatsError> {
+        let client = NatsClient::global().ok_or(NatsError::NotInitialized)?;
+        let service = self.handler.service().to_string();
+        let mut tasks = Vec::new();
+
+        for kind in [PrivacyRequestKind::Export, PrivacyRequestKind::Erasure] {
+            let subject = service_subject(&service, kind);
+            let mut subscriber = client
+                .queue_subscribe(subject.clone(), format!("lanai-privacy-{}", service))
+                .await
+                .map_err(|e| NatsError::ConnectionError(e.to_string()))?;
+            info!("🔏 Serving {:?} requests on '{}'", kind, subject);
+
+            let (client, handler) = (client.clone(), self.handler.clone());
+            tasks.push(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let Some(reply) = message.reply.clone() else { continue };
+                    let outcome = match decode_event::<PrivacyRequest>(&message.payload) {
+                        Ok(request) => handle(handler.as_ref(), &request).await,
+                        Err(e) => ServiceOutcome::Failed { reason: format!("malformed request: {}", e) },
+                    };
+                    let payload = serde_json::to_vec(&outcome).unwrap_or_default();
+                    if let Err(e) = client.publish(reply, payload.into()).await {
+                        error!("❌ Failed to reply to privacy request on '{}': {}", message.subject, e);
+                    }
+                }
+            }));
+        }
+
+        Ok(tasks)
+    }
+}
+
+async fn handle(handler: &dyn PrivacyHandler, request: &PrivacyRequest) -> ServiceOutcome {
+    let result = match request.kind {
+        PrivacyRequestKind::Export => handler.export(&request.subject).await.map(|data| ServiceOutcome::Exported { data }),
+        PrivacyRequestKind::Erasure => handler.erase(&request.subject).await.map(ServiceOutcome::Erased),
+    };
+    result.unwrap_or_else(|e| {
+        warn!("⚠️ Privacy {:?} request {} failed in '{}': {}", request.kind, request.request_id, handler.service(), e);
+        ServiceOutcome::Failed { reason: e.to_string() }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Orders;
+
+    #[async_trait]
+    impl PrivacyHandler for Orders {
+        fn service(&self) -> &str {
+            "orders"
+        }
+
+        async fn export(&self, _: &DataSubject) -> Result<serde_json::Value, PrivacyError> {
+            Ok(serde_json::json!({"orders": []}))
+        }
+
+        async fn erase(&self, _: &DataSubject) -> Result<ErasureSummary, PrivacyError> {
+            Err(PrivacyError::Handler("database down".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_outcomes() {
+        let subject = DataSubject { org_id: Uuid::new_v4(), user_id: Uuid::new_v4(), email: None };
+
+        let exported = handle(&Orders, &PrivacyRequest::export(subject.clone())).await;
+        assert_eq!(exported, ServiceOutcome::Exported { data: serde_json::json!({"orders": []}) });
+
+        let failed = handle(&Orders, &PrivacyRequest::erasure(subject)).await;
+        assert!(failed.is_failed());
+        assert_eq!(serde_json::to_value(&failed).unwrap()["status"], "failed");
+        assert_eq!(service_subject("orders", PrivacyRequestKind::Erasure), "lanai.privacy.orders.erase");
+    }
+}
@@ -0,0 +1,238 @@
+//! Fan-out of data subject requests to every service, as a saga
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::{
+    service_subject, DataSubject, PrivacyError, PrivacyRequest, PrivacyRequestKind, ServiceOutcome,
+    ERASURE_REQUESTED_SUBJECT, EXPORT_REQUESTED_SUBJECT,
+};
+use crate::messaging::events::decode_event;
+use crate::messaging::{NatsClient, NatsError};
+use crate::saga::{ParallelGroup, RetryPolicy, Saga, SagaError, SagaOrchestrator, SagaStep, SagaStore};
+
+/// Saga context: the request and the results collected so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyContext {
+    pub request: PrivacyRequest,
+    pub results: BTreeMap<String, ServiceOutcome>,
+}
+
+/// Payload of `lanai.privacy.{export|erasure}.completed`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyReport {
+    pub request_id: Uuid,
+    pub kind: PrivacyRequestKind,
+    pub subject: DataSubject,
+    pub results: BTreeMap<String, ServiceOutcome>,
+    /// True when every service succeeded; otherwise the request must be retried or
+    /// handled manually within the legal deadline.
+    pub complete: bool,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl PrivacyReport {
+    fn from_context(context: PrivacyContext) -> Self {
+        Self {
+            request_id: context.request.request_id,
+            kind: context.request.kind,
+            subject: context.request.subject,
+            complete: context.results.values().all(|outcome| !outcome.is_failed()),
+            results: context.results,
+            completed_at: Utc::now(),
+        }
+    }
+
+    pub fn failed_services(&self) -> Vec<&str> {
+        self.results.iter().filter(|(_, outcome)| outcome.is_failed()).map(|(service, _)| service.as_str()).collect()
+    }
+}
+
+/// Asks one service to process the request. Failures are recorded as the service's
+/// outcome rather than failing the saga, so one unavailable service does not hide the
+/// others' results; an erasure cannot be rolled back anyway.
+struct ServiceStep {
+    service: String,
+    timeout: Duration,
+    retry: RetryPolicy<NatsError>,
+}
+
+#[async_trait]
+impl SagaStep for ServiceStep {
+    type Context = PrivacyContext;
+    type Error = PrivacyError;
+
+    async fn execute(&self, context: &mut PrivacyContext) -> Result<(), PrivacyError> {
+        let subject = service_subject(&self.service, context.request.kind);
+        let mut attempt = 1;
+        let outcome = loop {
+            match NatsClient::request_typed::<_, ServiceOutcome>(&subject, &context.request, self.timeout).await {
+                Ok(outcome) => break outcome,
+                Err(e) if self.retry.should_retry(&e, attempt) => {
+                    warn!("🔄 Privacy request to '{}' failed: {}, retrying (attempt {})", self.service, e, attempt);
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => break ServiceOutcome::Failed { reason: e.to_string() },
+            }
+        };
+        context.results.insert(self.service.clone(), outcome);
+        Ok(())
+    }
+
+    async fn compensate(&self, _context: &mut PrivacyContext) -> Result<(), PrivacyError> {
+        Ok(())
+    }
+}
+
+/// Runs each request as a saga (ID = request ID) over the registered services, in parallel.
+///
+/// ```ignore
+/// let privacy = PrivacyOrchestrator::new()
+///     .service("accounts")
+///     .service("orders")
+///     .service("billing")
+///     .store(Arc::new(RedisSagaStore::new(shared_connection().await?)));
+/// let _listener = privacy.start().await?;
+/// ```
+pub struct PrivacyOrchestrator {
+    services: Vec<String>,
+    timeout: Duration,
+    retry: RetryPolicy<NatsError>,
+    store: Option<Arc<dyn SagaStore>>,
+}
+
+impl Default for PrivacyOrchestrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrivacyOrchestrator {
+    /// 30s per service call, 3 attempts.
+    pub fn new() -> Self {
+        Self {
+            services: Vec::new(),
+            timeout: Duration::from_secs(30),
+            retry: RetryPolicy::exponential(3, Duration::from_secs(1)),
+            store: None,
+        }
+    }
+
+    pub fn service(mut self, name: &str) -> Self {
+        self.services.push(name.to_string());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy<NatsError>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Persist runs so an interrupted request can be resumed.
+    pub fn store(mut self, store: Arc<dyn SagaStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    fn saga(&self) -> SagaOrchestrator<PrivacyContext, PrivacyError> {
+        let group = self.services.iter().fold(
+            ParallelGroup::new(|context: &mut PrivacyContext, member: PrivacyContext| {
+                context.results.extend(member.results)
+            }),
+            |group, service| {
+                group.step(
+                    service,
+                    ServiceStep { service: service.clone(), timeout: self.timeout, retry: self.retry.clone() },
+                )
+            },
+        );
+        let builder = Saga::builder("privacy_request").parallel("services", group);
+        match &self.store {
+            Some(store) => builder.store(store.clone()).build(),
+            None => builder.build(),
+        }
+    }
+
+    /// Process `request` and publish its report.
+    pub async fn process(&self, request: PrivacyRequest) -> Result<PrivacyReport, SagaError<PrivacyError>> {
+        let request_id = request.request_id;
+        info!("🔏 Processing {:?} request {} for {}", request.kind, request_id, request.subject.user_id);
+
+        let context = PrivacyContext { request, results: BTreeMap::new() };
+        let report = PrivacyReport::from_context(self.saga().run_with_id(request_id, context).await?);
+
+        if !report.complete {
+            warn!("⚠️ {:?} request {} incomplete, failed in {:?}", report.kind, request_id, report.failed_services());
+        }
+        if let Err(e) = NatsClient::publish_event(report.kind.completed_subject(), &report).await {
+            error!("❌ Failed to publish privacy report {}: {}", request_id, e);
+        }
+        Ok(report)
+    }
+
+    /// Listen for requested events; one replica of the orchestrating service handles each.
+    pub async fn start(self) -> Result<JoinHandle<()>, NatsError> {
+        let client = NatsClient::global().ok_or(NatsError::NotInitialized)?;
+        let mut requests = futures_util::stream::select(
+            client
+                .queue_subscribe(EXPORT_REQUESTED_SUBJECT.to_string(), "lanai-privacy".to_string())
+                .await
+                .map_err(|e| NatsError::ConnectionError(e.to_string()))?,
+            client
+                .queue_subscribe(ERASURE_REQUESTED_SUBJECT.to_string(), "lanai-privacy".to_string())
+                .await
+                .map_err(|e| NatsError::ConnectionError(e.to_string()))?,
+        );
+
+        let orchestrator = Arc::new(self);
+        Ok(tokio::spawn(async move {
+            while let Some(message) = requests.next().await {
+                let request = match decode_event::<PrivacyRequest>(&message.payload) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        error!("❌ Ignoring malformed privacy request on '{}': {}", message.subject, e);
+                        continue;
+                    }
+                };
+                let orchestrator = orchestrator.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = orchestrator.process(request).await {
+                        error!("❌ Privacy request failed: {}", e);
+                    }
+                });
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_services_are_reported_not_fatal() {
+        let orchestrator = PrivacyOrchestrator::new()
+            .service("orders")
+            .service("billing")
+            .retry(RetryPolicy::none());
+        let subject = DataSubject { org_id: Uuid::new_v4(), user_id: Uuid::new_v4(), email: None };
+
+        let report = orchestrator.process(PrivacyRequest::erasure(subject)).await.unwrap();
+        assert!(!report.complete);
+        assert_eq!(report.failed_services(), vec!["billing", "orders"]);
+    }
+}
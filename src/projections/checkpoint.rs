@@ -0,0 +1,94 @@
+//! Per-projection checkpoints
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use super::ProjectionError;
+
+/// Table used by `PostgresCheckpointStore`.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_projection_checkpoints (
+    projection TEXT PRIMARY KEY,
+    position BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Position of the last event the projection handled (0 if it never ran).
+    async fn load(&self, projection: &str) -> Result<i64, ProjectionError>;
+
+    async fn save(&self, projection: &str, position: i64) -> Result<(), ProjectionError>;
+}
+
+/// In-memory checkpoints (for tests and development).
+#[derive(Default)]
+pub struct InMemoryCheckpointStore {
+    positions: RwLock<HashMap<String, i64>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    async fn load(&self, projection: &str) -> Result<i64, ProjectionError> {
+        Ok(self.positions.read().await.get(projection).copied().unwrap_or(0))
+    }
+
+    async fn save(&self, projection: &str, position: i64) -> Result<(), ProjectionError> {
+        self.positions.write().await.insert(projection.to_string(), position);
+        Ok(())
+    }
+}
+
+/// Checkpoints in PostgreSQL. Keep them in the read model's database so a projection can
+/// update both in one transaction if it needs exactly-once effects.
+pub struct PostgresCheckpointStore {
+    pool: PgPool,
+}
+
+impl PostgresCheckpointStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ensure_schema(&self) -> Result<(), ProjectionError> {
+        sqlx::query(SCHEMA).execute(&self.pool).await.map_err(checkpoint_error)?;
+        Ok(())
+    }
+}
+
+fn checkpoint_error(e: sqlx::Error) -> ProjectionError {
+    ProjectionError::Checkpoint(e.to_string())
+}
+
+#[async_trait]
+impl CheckpointStore for PostgresCheckpointStore {
+    async fn load(&self, projection: &str) -> Result<i64, ProjectionError> {
+        let position: Option<i64> =
+            sqlx::query_scalar("SELECT position FROM lanai_projection_checkpoints WHERE projection = $1")
+                .bind(projection)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(checkpoint_error)?;
+        Ok(position.unwrap_or(0))
+    }
+
+    async fn save(&self, projection: &str, position: i64) -> Result<(), ProjectionError> {
+        sqlx::query(
+            "INSERT INTO lanai_projection_checkpoints (projection, position, updated_at) VALUES ($1, $2, now())
+             ON CONFLICT (projection) DO UPDATE SET position = EXCLUDED.position, updated_at = now()",
+        )
+        .bind(projection)
+        .bind(position)
+        .execute(&self.pool)
+        .await
+        .map_err(checkpoint_error)?;
+        Ok(())
+    }
+}
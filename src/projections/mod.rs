@@ -0,0 +1,356 @@
+//! CQRS Projections
+//!
+//! A `Projection` turns events into a read model. The `ProjectionRunner` feeds each
+//! registered projection from an `EventSource` (the event store, or a JetStream stream) in
+//! position order and stores a checkpoint per projection after every batch, so a restarted
+//! service continues where it stopped. Delivery is at-least-once: after a crash the events
+//! since the last checkpoint are handled again, so handlers must be idempotent.
+//!
+//! `rebuild` resets a projection's read model and replays it from the first event. Lag
+//! (events between the checkpoint and the head of the source) is exported as the
+//! `projection_lag_events` gauge and available from `status`.
+//!
+//! ```ignore
+//! let runner = ProjectionRunner::new(
+//!         Arc::new(EventStoreSource::new(event_store.clone())),
+//!         Arc::new(PostgresCheckpointStore::new(pool.clone())),
+//!     )
+//!     .projection(CartTotals::new(pool.clone()))
+//!     .with_leader(LeaderElection::new(shared_connection().await?, "cart-projections"));
+//! let _projections = runner.start();
+//! ```
+
+use async_trait::async_trait;
+use log::{error, info};
+use opentelemetry::metrics::{Counter, Gauge};
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+pub mod checkpoint;
+pub mod source;
+
+pub use checkpoint::{CheckpointStore, InMemoryCheckpointStore, PostgresCheckpointStore};
+pub use source::{EventSource, EventStoreSource, JetStreamSource};
+
+use crate::eventstore::StoredEvent;
+use crate::leader::LeaderElection;
+
+/// Projection error types
+#[derive(Debug, Error)]
+pub enum ProjectionError {
+    #[error("Projection handler failed: {0}")]
+    Handler(String),
+
+    #[error("Event source error: {0}")]
+    Source(String),
+
+    #[error("Checkpoint store error: {0}")]
+    Checkpoint(String),
+
+    #[error("Unknown projection '{0}'")]
+    UnknownProjection(String),
+}
+
+#[async_trait]
+pub trait Projection: Send + Sync {
+    /// Unique name; also the checkpoint key.
+    fn name(&self) -> &str;
+
+    /// Apply one event to the read model. Events the projection does not care about should
+    /// be ignored (return `Ok`).
+    async fn handle(&self, event: &StoredEvent) -> Result<(), ProjectionError>;
+
+    /// Clear the read model before a rebuild.
+    async fn reset(&self) -> Result<(), ProjectionError>;
+}
+
+/// Progress of one projection on this replica.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectionStatus {
+    pub name: String,
+    pub position: i64,
+    pub head: i64,
+    pub lag: i64,
+}
+
+#[derive(Default)]
+struct Progress {
+    position: AtomicI64,
+    head: AtomicI64,
+    rebuild: AtomicBool,
+}
+
+#[derive(Clone)]
+struct Entry {
+    projection: Arc<dyn Projection>,
+    progress: Arc<Progress>,
+}
+
+struct ProjectionMetrics {
+    events: Counter<u64>,
+    lag: Gauge<u64>,
+}
+
+impl ProjectionMetrics {
+    fn new() -> Self {
+        let meter = global::meter("lanai.projections");
+        Self {
+            events: meter
+                .u64_counter("projection_events_total")
+                .with_description("Events applied by each projection")
+                .build(),
+            lag: meter
+                .u64_gauge("projection_lag_events")
+                .with_description("Events between a projection's checkpoint and the head of its source")
+                .build(),
+        }
+    }
+}
+
+pub struct ProjectionsHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ProjectionsHandle {
+    /// Stop all projections. Work since the last checkpoint is redone on the next start.
+    pub fn abort(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProjectionRunner {
+    source: Arc<dyn EventSource>,
+    checkpoints: Arc<dyn CheckpointStore>,
+    entries: Vec<Entry>,
+    batch_size: usize,
+    poll_interval: Duration,
+    retry_delay: Duration,
+    leader: Option<LeaderElection>,
+}
+
+impl ProjectionRunner {
+    /// Batches of 100 events; polls every second once caught up, retries failures after 5s.
+    pub fn new(source: Arc<dyn EventSource>, checkpoints: Arc<dyn CheckpointStore>) -> Self {
+        Self {
+            source,
+            checkpoints,
+            entries: Vec::new(),
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            retry_delay: Duration::from_secs(5),
+            leader: None,
+        }
+    }
+
+    pub fn projection<P: Projection + 'static>(mut self, projection: P) -> Self {
+        self.entries.push(Entry { projection: Arc::new(projection), progress: Arc::new(Progress::default()) });
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Only run projections while this replica holds leadership (the election must be started).
+    pub fn with_leader(mut self, election: LeaderElection) -> Self {
+        self.leader = Some(election);
+        self
+    }
+
+    /// Spawn one loop per projection.
+    pub fn start(&self) -> ProjectionsHandle {
+        let metrics = Arc::new(ProjectionMetrics::new());
+        let tasks = self
+            .entries
+            .iter()
+            .map(|entry| tokio::spawn(self.clone().run(entry.clone(), metrics.clone())))
+            .collect();
+        info!("📽️ Started {} projections", self.entries.len());
+        ProjectionsHandle { tasks }
+    }
+
+    /// Reset `name`'s read model and replay it from the first event. Takes effect on the
+    /// projection's next batch (or its first, if the runner is not started yet).
+    pub fn rebuild(&self, name: &str) -> Result<(), ProjectionError> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.projection.name() == name)
+            .ok_or_else(|| ProjectionError::UnknownProjection(name.to_string()))?;
+        entry.progress.rebuild.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn status(&self) -> Vec<ProjectionStatus> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let position = entry.progress.position.load(Ordering::Relaxed);
+                let head = entry.progress.head.load(Ordering::Relaxed);
+                ProjectionStatus {
+                    name: entry.projection.name().to_string(),
+                    position,
+                    head,
+                    lag: (head - position).max(0),
+                }
+            })
+            .collect()
+    }
+
+    async fn run(self, entry: Entry, metrics: Arc<ProjectionMetrics>) {
+        let name = entry.projection.name().to_string();
+        let attributes = [KeyValue::new("projection", name.clone())];
+        // Loaded lazily, and again after losing leadership, since another replica may have moved it.
+        let mut position: Option<i64> = None;
+
+        loop {
+            if self.leader.as_ref().is_some_and(|leader| !leader.am_i_leader()) {
+                position = None;
+                tokio::time::sleep(self.poll_interval).await;
+                continue;
+            }
+
+            if entry.progress.rebuild.swap(false, Ordering::SeqCst) {
+                info!("🔁 Rebuilding projection '{}' from the first event", name);
+                let reset = async {
+                    entry.projection.reset().await?;
+                    self.checkpoints.save(&name, 0).await
+                };
+                if let Err(e) = reset.await {
+                    error!("❌ Failed to reset projection '{}': {}", name, e);
+                    entry.progress.rebuild.store(true, Ordering::SeqCst);
+                    tokio::time::sleep(self.retry_delay).await;
+                    continue;
+                }
+                position = Some(0);
+            }
+
+            let current = match position {
+                Some(current) => current,
+                None => match self.checkpoints.load(&name).await {
+                    Ok(loaded) => *position.insert(loaded),
+                    Err(e) => {
+                        error!("❌ Failed to load checkpoint of projection '{}': {}", name, e);
+                        tokio::time::sleep(self.retry_delay).await;
+                        continue;
+                    }
+                },
+            };
+
+            let batch = match self.source.read(current, self.batch_size).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!("❌ Projection '{}' failed to read events: {}", name, e);
+                    tokio::time::sleep(self.retry_delay).await;
+                    continue;
+                }
+            };
+
+            let mut handled = current;
+            let mut failed = false;
+            for event in &batch {
+                if let Err(e) = entry.projection.handle(event).await {
+                    error!("❌ Projection '{}' failed on event {} ({}): {}", name, event.position, event.event_type, e);
+                    failed = true;
+                    break;
+                }
+                handled = event.position;
+                metrics.events.add(1, &attributes);
+            }
+
+            if handled != current {
+                if let Err(e) = self.checkpoints.save(&name, handled).await {
+                    error!("❌ Failed to save checkpoint of projection '{}': {}", name, e);
+                }
+                position = Some(handled);
+                entry.progress.position.store(handled, Ordering::Relaxed);
+            }
+
+            if let Ok(head) = self.source.head().await {
+                entry.progress.head.store(head, Ordering::Relaxed);
+                metrics.lag.record((head - handled).max(0) as u64, &attributes);
+            }
+
+            if failed {
+                tokio::time::sleep(self.retry_delay).await;
+            } else if batch.len() < self.batch_size {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventstore::{EventStore, InMemoryEventStore, NewEvent};
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    struct Recorder {
+        seen: Arc<Mutex<Vec<i64>>>,
+    }
+
+    #[async_trait]
+    impl Projection for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn handle(&self, event: &StoredEvent) -> Result<(), ProjectionError> {
+            self.seen.lock().unwrap().push(event.position);
+            Ok(())
+        }
+
+        async fn reset(&self) -> Result<(), ProjectionError> {
+            self.seen.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runner_checkpoints_and_rebuilds() {
+        let store = Arc::new(InMemoryEventStore::new());
+        let event = NewEvent { event_type: "x".to_string(), payload: serde_json::json!({}), metadata: serde_json::json!({}) };
+        store.append("thing", Uuid::new_v4(), 0, vec![event.clone(), event.clone(), event]).await.unwrap();
+
+        let checkpoints = Arc::new(InMemoryCheckpointStore::new());
+        checkpoints.save("recorder", 1).await.unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let runner = ProjectionRunner::new(Arc::new(EventStoreSource::new(store)), checkpoints.clone())
+            .projection(Recorder { seen: seen.clone() })
+            .poll_interval(Duration::from_millis(10));
+        let handle = runner.start();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*seen.lock().unwrap(), vec![2, 3]);
+        assert_eq!(checkpoints.load("recorder").await.unwrap(), 3);
+        assert_eq!(runner.status()[0].lag, 0);
+
+        runner.rebuild("recorder").unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3]);
+        assert!(runner.rebuild("missing").is_err());
+        handle.abort();
+    }
+}
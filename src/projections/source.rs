@@ -0,0 +1,132 @@
+//! Where projections read events from
+
+use async_nats::jetstream::{self, consumer::pull, consumer::AckPolicy, consumer::DeliverPolicy};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::ProjectionError;
+use crate::eventstore::{EventStore, StoredEvent};
+use crate::messaging::events::decode_event;
+use crate::messaging::NatsClient;
+
+/// An ordered, replayable log of events addressed by position.
+#[async_trait]
+pub trait EventSource: Send + Sync {
+    /// Up to `limit` events with a position greater than `after`, in order.
+    async fn read(&self, after: i64, limit: usize) -> Result<Vec<StoredEvent>, ProjectionError>;
+
+    /// Position of the latest event, used for lag.
+    async fn head(&self) -> Result<i64, ProjectionError>;
+}
+
+/// Reads every stream of an `EventStore` in position order.
+pub struct EventStoreSource {
+    store: Arc<dyn EventStore>,
+}
+
+impl EventStoreSource {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl EventSource for EventStoreSource {
+    async fn read(&self, after: i64, limit: usize) -> Result<Vec<StoredEvent>, ProjectionError> {
+        self.store.read_all(after, limit).await.map_err(|e| ProjectionError::Source(e.to_string()))
+    }
+
+    async fn head(&self) -> Result<i64, ProjectionError> {
+        self.store.head().await.map_err(|e| ProjectionError::Source(e.to_string()))
+    }
+}
+
+/// Reads a JetStream stream; positions are stream sequence numbers.
+///
+/// Messages become `StoredEvent`s with the subject as `event_type` and the JSON body as
+/// `payload`. `Lanai-Aggregate-Type` / `Lanai-Aggregate-Id` headers are used when present.
+pub struct JetStreamSource {
+    stream: String,
+    consumer: Mutex<Option<(i64, jetstream::consumer::Consumer<pull::Config>)>>,
+}
+
+impl JetStreamSource {
+    /// Requires `NatsClient::init`.
+    pub fn new(stream: &str) -> Self {
+        Self { stream: stream.to_string(), consumer: Mutex::new(None) }
+    }
+
+    fn context() -> Result<jetstream::Context, ProjectionError> {
+        let client = NatsClient::global().ok_or_else(|| ProjectionError::Source("NATS client is not initialized".to_string()))?;
+        Ok(jetstream::new(client))
+    }
+}
+
+fn source_error(e: impl std::fmt::Display) -> ProjectionError {
+    ProjectionError::Source(e.to_string())
+}
+
+#[async_trait]
+impl EventSource for JetStreamSource {
+    async fn read(&self, after: i64, limit: usize) -> Result<Vec<StoredEvent>, ProjectionError> {
+        let mut cached = self.consumer.lock().await;
+
+        // An ephemeral consumer is reused while reads continue where the last one ended.
+        if !matches!(&*cached, Some((next, _)) if *next == after) {
+            let stream = Self::context()?.get_stream(&self.stream).await.map_err(source_error)?;
+            let consumer = stream
+                .create_consumer(pull::Config {
+                    deliver_policy: DeliverPolicy::ByStartSequence { start_sequence: after as u64 + 1 },
+                    ack_policy: AckPolicy::None,
+                    inactive_threshold: Duration::from_secs(60),
+                    ..Default::default()
+                })
+                .await
+                .map_err(source_error)?;
+            *cached = Some((after, consumer));
+        }
+        let Some((next, consumer)) = cached.as_mut() else {
+            return Ok(Vec::new());
+        };
+
+        let mut batch = consumer
+            .fetch()
+            .max_messages(limit)
+            .expires(Duration::from_millis(500))
+            .messages()
+            .await
+            .map_err(source_error)?;
+
+        let mut events = Vec::new();
+        while let Some(message) = batch.next().await {
+            let message = message.map_err(source_error)?;
+            let position = message.info().map_err(source_error)?.stream_sequence as i64;
+            let header = |name: &str| {
+                message.headers.as_ref().and_then(|h| h.get(name)).map(|v| v.as_str().to_string())
+            };
+
+            events.push(StoredEvent {
+                position,
+                aggregate_type: header("Lanai-Aggregate-Type").unwrap_or_default(),
+                aggregate_id: header("Lanai-Aggregate-Id").and_then(|id| Uuid::parse_str(&id).ok()).unwrap_or_default(),
+                version: 0,
+                event_type: message.subject.to_string(),
+                payload: decode_event(&message.payload).unwrap_or(serde_json::Value::Null),
+                metadata: serde_json::json!({}),
+                recorded_at: Utc::now(),
+            });
+            *next = position;
+        }
+        Ok(events)
+    }
+
+    async fn head(&self) -> Result<i64, ProjectionError> {
+        let stream = Self::context()?.get_stream(&self.stream).await.map_err(source_error)?;
+        Ok(stream.cached_info().state.last_sequence as i64)
+    }
+}
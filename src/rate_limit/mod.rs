@@ -5,11 +5,16 @@
 //! or if Redis is not configured at all.
 
 use redis::AsyncCommands;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use log::{info, warn, error};
 
+use crate::common::{Clock, SystemClock};
+
 /// Environment variable for Redis URL
 pub const REDIS_URL_ENV: &str = "REDIS_URL";
 
@@ -82,41 +87,186 @@ impl RateLimiterBackend for RedisRateLimiter {
     }
 }
 
+/// Number of independently locked shards of `InMemoryRateLimiter` (a power of two).
+const SHARDS: usize = 64;
+/// Calls to a shard between sweeps of its idle keys.
+const SWEEP_EVERY: u32 = 1024;
+
+/// Request timestamps of one key, oldest first.
+struct Window {
+    hits: VecDeque<i64>,
+    window_ms: i64,
+}
+
+#[derive(Default)]
+struct Shard {
+    windows: HashMap<String, Window>,
+    calls: u32,
+}
+
 /// In-memory fallback (for dev or if Redis is missing)
+///
+/// Keys are spread over independently locked shards, so concurrent requests from
+/// different clients rarely wait on each other; keys whose window has passed are
+/// dropped periodically.
 pub struct InMemoryRateLimiter {
-    // Key -> sorted list of timestamps
-    store: Arc<RwLock<HashMap<String, Vec<i64>>>>,
+    shards: Box<[Mutex<Shard>]>,
+    hasher: RandomState,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for InMemoryRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InMemoryRateLimiter {
     pub fn new() -> Self {
         Self {
-            store: Arc::new(RwLock::new(HashMap::new())),
+            shards: (0..SHARDS).map(|_| Mutex::new(Shard::default())).collect(),
+            hasher: RandomState::new(),
+            clock: Arc::new(SystemClock),
         }
     }
+
+    /// Measure windows with `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn shard(&self, key: &str) -> std::sync::MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(key) as usize & (SHARDS - 1);
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keys currently tracked, across shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()).windows.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 #[async_trait::async_trait]
 impl RateLimiterBackend for InMemoryRateLimiter {
     async fn is_allowed(&self, key: &str, limit: u32, window_secs: u64) -> bool {
-        let now = chrono::Utc::now().timestamp_millis();
-        let window_start = now - (window_secs * 1000) as i64;
+        let now = self.clock.utc_now().timestamp_millis();
+        let window_ms = (window_secs * 1000) as i64;
+
+        let mut shard = self.shard(key);
+        shard.calls += 1;
+        if shard.calls >= SWEEP_EVERY {
+            shard.calls = 0;
+            shard.windows.retain(|_, window| window.hits.back().is_some_and(|&last| last > now - window.window_ms));
+        }
 
-        let mut store = self.store.write().await;
-        let history = store.entry(key.to_string()).or_default();
+        let window = shard
+            .windows
+            .entry(key.to_string())
+            .or_insert_with(|| Window { hits: VecDeque::new(), window_ms });
+        window.window_ms = window_ms;
 
         // Cleanup old
-        history.retain(|&ts| ts > window_start);
+        while window.hits.front().is_some_and(|&ts| ts <= now - window_ms) {
+            window.hits.pop_front();
+        }
 
-        if history.len() >= limit as usize {
+        if window.hits.len() >= limit as usize {
             return false;
         }
 
-        history.push(now);
+        window.hits.push_back(now);
         true
     }
 }
 
+/// A limit applied in place of the server-wide default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RateLimitPolicy {
+    pub max_requests: u32,
+    pub window_seconds: u64,
+}
+
+/// Runtime rate limit overrides, keyed by client key prefix (`api:<key>`, `token:<prefix>`,
+/// an IP address). The longest matching prefix wins; `*` overrides the default for everyone.
+#[derive(Clone, Default)]
+pub struct RateLimitOverrides {
+    policies: Arc<std::sync::RwLock<HashMap<String, RateLimitPolicy>>>,
+    /// Bumped on every change, so `LocalOverrides` copies know when to refresh.
+    version: Arc<AtomicU64>,
+}
+
+static OVERRIDES: std::sync::OnceLock<RateLimitOverrides> = std::sync::OnceLock::new();
+
+impl RateLimitOverrides {
+    /// Overrides consulted by `RateLimitMiddleware`.
+    pub fn global() -> &'static RateLimitOverrides {
+        OVERRIDES.get_or_init(RateLimitOverrides::default)
+    }
+
+    pub fn set(&self, prefix: &str, policy: RateLimitPolicy) {
+        info!("🚦 Rate limit override for '{}': {} requests / {}s", prefix, policy.max_requests, policy.window_seconds);
+        self.policies.write().unwrap_or_else(|e| e.into_inner()).insert(prefix.to_string(), policy);
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Returns true if an override was removed.
+    pub fn remove(&self, prefix: &str) -> bool {
+        let removed = self.policies.write().unwrap_or_else(|e| e.into_inner()).remove(prefix).is_some();
+        self.version.fetch_add(1, Ordering::Release);
+        removed
+    }
+
+    pub fn list(&self) -> HashMap<String, RateLimitPolicy> {
+        self.policies.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Policy for a client key, if one is overridden.
+    pub fn resolve(&self, key: &str) -> Option<RateLimitPolicy> {
+        resolve_in(&self.policies.read().unwrap_or_else(|e| e.into_inner()), key)
+    }
+}
+
+fn resolve_in(policies: &HashMap<String, RateLimitPolicy>, key: &str) -> Option<RateLimitPolicy> {
+    policies
+        .iter()
+        .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, policy)| *policy)
+        .or_else(|| policies.get("*").copied())
+}
+
+/// A worker's copy of `RateLimitOverrides`, so resolving a key on the hot path takes no
+/// shared lock. It is refreshed when the overrides' version changes.
+pub struct LocalOverrides {
+    overrides: RateLimitOverrides,
+    version: Cell<u64>,
+    policies: RefCell<HashMap<String, RateLimitPolicy>>,
+}
+
+impl LocalOverrides {
+    pub fn new(overrides: RateLimitOverrides) -> Self {
+        Self { overrides, version: Cell::new(u64::MAX), policies: RefCell::new(HashMap::new()) }
+    }
+
+    pub fn resolve(&self, key: &str) -> Option<RateLimitPolicy> {
+        let version = self.overrides.version();
+        if version != self.version.get() {
+            *self.policies.borrow_mut() = self.overrides.list();
+            self.version.set(version);
+        }
+        resolve_in(&self.policies.borrow(), key)
+    }
+}
+
 /// Factory to get the configured rate limiter
 pub async fn create_limiter() -> Arc<dyn RateLimiterBackend> {
     if let Ok(redis_url) = std::env::var(REDIS_URL_ENV) {
@@ -135,3 +285,27 @@ pub async fn create_limiter() -> Arc<dyn RateLimiterBackend> {
     
     Arc::new(InMemoryRateLimiter::new())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sharded_limiter_and_local_overrides() {
+        let limiter = InMemoryRateLimiter::new();
+        for client in 0..100 {
+            assert!(limiter.is_allowed(&format!("ip:{}", client), 1, 60).await);
+        }
+        assert!(!limiter.is_allowed("ip:7", 1, 60).await);
+        assert_eq!(limiter.len(), 100);
+
+        let overrides = RateLimitOverrides::default();
+        let local = LocalOverrides::new(overrides.clone());
+        assert_eq!(local.resolve("api:pos"), None);
+        let policy = RateLimitPolicy { max_requests: 1000, window_seconds: 60 };
+        overrides.set("api:", policy);
+        assert_eq!(local.resolve("api:pos"), Some(policy));
+        overrides.remove("api:");
+        assert_eq!(local.resolve("api:pos"), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Realtime WebSocket Hub
+//!
+//! Each WebSocket connection belongs to one organization. Clients subscribe to topics, which
+//! map to the tenant-scoped NATS subjects `{prefix}.{org_id}.{topic}`, and receive every
+//! message published there — by services through `RealtimeHub::publish` or by other clients.
+//! A client can never reach another organization's subjects: the org segment comes from
+//! the authenticated `TenantContext`, not from the client.
+//!
+//! Each connection has an inbound rate limit and a bounded outbound buffer; a client that
+//! cannot keep up is disconnected (close code 1013, "try again later") rather than letting
+//! messages pile up in memory. See `protocol` for the frame format.
+//!
+//! ```ignore
+//! let hub = RealtimeHub::new("lanai.realtime").with_rate_limit(20, Duration::from_secs(1));
+//!
+//! // inventory service, after a stock change
+//! hub.publish(tenant.org_id, "stock.updated", &StockItem { .. }).await?;
+//!
+//! // POS terminal: ws://.../ws then {"type": "subscribe", "topic": "stock.*"}
+//! ServerBuilder::new("lanai-realtime").run(move |cfg| hub.configure(cfg)).await
+//! ```
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::{error, info};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod protocol;
+mod session;
+
+pub use protocol::{ClientMessage, ServerMessage};
+
+use crate::messaging::{NatsClient, NatsError};
+use crate::middleware::tenant_context::TenantContext;
+
+/// Realtime hub error types
+#[derive(Debug, Error)]
+pub enum RealtimeError {
+    #[error("NATS client is not initialized")]
+    NotConfigured,
+
+    #[error("Invalid topic: {0}")]
+    InvalidTopic(String),
+
+    #[error("Failed to serialize message: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("NATS publish failed: {0}")]
+    Nats(String),
+}
+
+struct Inner {
+    prefix: String,
+    max_messages: u32,
+    per: Duration,
+    buffer: usize,
+    max_subscriptions: usize,
+    client_publish: bool,
+    ping_interval: Duration,
+    max_frame_size: usize,
+}
+
+#[derive(Clone)]
+pub struct RealtimeHub {
+    inner: Arc<Inner>,
+}
+
+impl RealtimeHub {
+    /// Defaults: 10 inbound messages per second, 256 buffered outbound messages, 32
+    /// subscriptions and 64 KiB frames per connection, pings every 30s, client publishing on.
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                prefix: prefix.trim_end_matches('.').to_string(),
+                max_messages: 10,
+                per: Duration::from_secs(1),
+                buffer: 256,
+                max_subscriptions: 32,
+                client_publish: true,
+                ping_interval: Duration::from_secs(30),
+                max_frame_size: 64 * 1024,
+            }),
+        }
+    }
+
+    /// Must be called before the hub is cloned.
+    pub fn with_rate_limit(mut self, max_messages: u32, per: Duration) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.max_messages = max_messages;
+            inner.per = per;
+        }
+        self
+    }
+
+    /// Outbound messages buffered per connection before it is dropped as a slow consumer.
+    /// Must be called before cloning.
+    pub fn with_buffer(mut self, messages: usize) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.buffer = messages.max(1);
+        }
+        self
+    }
+
+    /// Must be called before cloning.
+    pub fn with_max_subscriptions(mut self, subscriptions: usize) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.max_subscriptions = subscriptions;
+        }
+        self
+    }
+
+    /// Only relay NATS → clients; `publish` frames are rejected. Must be called before cloning.
+    pub fn read_only(mut self) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.client_publish = false;
+        }
+        self
+    }
+
+    /// NATS subject for `topic` within `org_id`.
+    pub fn subject(&self, org_id: Uuid, topic: &str) -> String {
+        format!("{}.{}.{}", self.inner.prefix, org_id, topic)
+    }
+
+    /// Send `data` to every client of `org_id` subscribed to `topic`, on any replica.
+    pub async fn publish<T: Serialize>(&self, org_id: Uuid, topic: &str, data: &T) -> Result<(), RealtimeError> {
+        protocol::validate_topic(topic, false).map_err(RealtimeError::InvalidTopic)?;
+        let payload = serde_json::to_vec(data)?;
+        NatsClient::publish_with_headers(&self.subject(org_id, topic), async_nats::HeaderMap::new(), payload.into())
+            .await
+            .map_err(|e| match e {
+                NatsError::NotInitialized => RealtimeError::NotConfigured,
+                e => RealtimeError::Nats(e.to_string()),
+            })
+    }
+
+    /// Mount `GET /ws` (mount behind `AuthGuard`; browsers authenticate with the
+    /// `access_token` cookie since they cannot set headers on WebSocket requests).
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone())).route("/ws", web::get().to(connect));
+    }
+}
+
+async fn connect(
+    hub: web::Data<RealtimeHub>,
+    tenant: TenantContext,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !NatsClient::is_initialized() {
+        error!("❌ WebSocket connection refused: NATS client is not initialized");
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "Realtime is unavailable"})));
+    }
+
+    let (response, ws_session, stream) = actix_ws::handle(&req, body)?;
+    let stream = stream.max_frame_size(hub.inner.max_frame_size);
+
+    info!("🔌 WebSocket connected for org {}", tenant.org_id);
+    actix_web::rt::spawn(session::run(hub.get_ref().clone(), tenant.org_id, ws_session, stream));
+    Ok(response)
+}
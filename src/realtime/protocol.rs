@@ -0,0 +1,88 @@
+//! JSON frames exchanged with WebSocket clients
+//!
+//! Client → server:
+//! - `{"type": "subscribe", "topic": "stock.*"}`
+//! - `{"type": "unsubscribe", "topic": "stock.*"}`
+//! - `{"type": "publish", "topic": "cart.updated", "data": {...}}`
+//!
+//! Server → client:
+//! - `{"type": "message", "topic": "stock.updated", "data": {...}}`
+//! - `{"type": "subscribed", "topic": "stock.*"}` / `{"type": "unsubscribed", ...}`
+//! - `{"type": "error", "message": "..."}`
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe { topic: String },
+    Unsubscribe { topic: String },
+    Publish { topic: String, data: serde_json::Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Message { topic: String, data: serde_json::Value },
+    Subscribed { topic: String },
+    Unsubscribed { topic: String },
+    Error { message: String },
+}
+
+impl ServerMessage {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error { message: message.into() }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Topics are dot-separated tokens of `[A-Za-z0-9_-]`. Subscriptions may also use the NATS
+/// wildcards `*` (one token) and `>` (the rest, last token only).
+pub fn validate_topic(topic: &str, allow_wildcards: bool) -> Result<(), String> {
+    if topic.is_empty() || topic.len() > 128 {
+        return Err("topic must be 1-128 characters".to_string());
+    }
+
+    let tokens: Vec<&str> = topic.split('.').collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let wildcard = *token == "*" || (*token == ">" && i == tokens.len() - 1);
+        if wildcard {
+            if !allow_wildcards {
+                return Err(format!("wildcards are not allowed in '{}'", topic));
+            }
+            continue;
+        }
+        if token.is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid topic '{}'", topic));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let message: ClientMessage = serde_json::from_str(r#"{"type":"subscribe","topic":"stock.*"}"#).unwrap();
+        assert_eq!(message, ClientMessage::Subscribe { topic: "stock.*".to_string() });
+
+        let json = ServerMessage::Message { topic: "stock.updated".to_string(), data: serde_json::json!({"sku": "A1"}) }.to_json();
+        assert_eq!(json, r#"{"type":"message","topic":"stock.updated","data":{"sku":"A1"}}"#);
+    }
+
+    #[test]
+    fn test_topic_validation() {
+        assert!(validate_topic("stock.updated", false).is_ok());
+        assert!(validate_topic("stock.*", true).is_ok());
+        assert!(validate_topic("stock.>", true).is_ok());
+        assert!(validate_topic("stock.*", false).is_err());
+        assert!(validate_topic("stock.>.x", true).is_err());
+        assert!(validate_topic("stock..updated", true).is_err());
+        assert!(validate_topic("stock updated", true).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! One WebSocket connection: reader loop, writer task and NATS subscriptions
+
+use actix_web::web::Bytes;
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use futures_util::StreamExt;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::protocol::{validate_topic, ClientMessage, ServerMessage};
+use super::RealtimeHub;
+use crate::messaging::NatsClient;
+
+enum Outbound {
+    Frame(String),
+    Pong(Bytes),
+}
+
+/// Bounded outbound queue shared by the reader and subscription tasks.
+#[derive(Clone)]
+struct Outbox {
+    tx: mpsc::Sender<Outbound>,
+    overflow: Arc<Notify>,
+}
+
+impl Outbox {
+    /// Returns false once the connection is closing. A full buffer marks the client as a
+    /// slow consumer, which makes the writer close the connection.
+    fn send(&self, outbound: Outbound) -> bool {
+        match self.tx.try_send(outbound) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.overflow.notify_one();
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+
+    fn reply(&self, message: ServerMessage) {
+        self.send(Outbound::Frame(message.to_json()));
+    }
+}
+
+/// Token bucket for inbound frames.
+struct RateLimiter {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    fn new(max_messages: u32, per: Duration) -> Self {
+        let capacity = f64::from(max_messages.max(1));
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / per.as_secs_f64().max(0.001),
+            last: Instant::now(),
+        }
+    }
+
+    fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub(super) async fn run(
+    hub: RealtimeHub,
+    org_id: Uuid,
+    session: Session,
+    mut stream: MessageStream,
+) {
+    let config = &hub.inner;
+    let (tx, rx) = mpsc::channel(config.buffer);
+    let outbox = Outbox { tx, overflow: Arc::new(Notify::new()) };
+    let mut writer = actix_web::rt::spawn(write(session, rx, outbox.overflow.clone(), config.ping_interval));
+
+    let mut subscriptions: HashMap<String, JoinHandle<()>> = HashMap::new();
+    let mut limiter = RateLimiter::new(config.max_messages, config.per);
+
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            // The writer stopped: the client went away or was dropped as a slow consumer.
+            _ = &mut writer => break,
+        };
+
+        let text = match message {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Ping(bytes))) => {
+                outbox.send(Outbound::Pong(bytes));
+                continue;
+            }
+            Some(Ok(Message::Binary(_))) => {
+                outbox.reply(ServerMessage::error("binary frames are not supported"));
+                continue;
+            }
+            Some(Ok(Message::Close(_))) | None => break,
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => {
+                debug!("🔌 WebSocket protocol error for org {}: {}", org_id, e);
+                break;
+            }
+        };
+
+        if !limiter.allow() {
+            outbox.reply(ServerMessage::error("rate limit exceeded"));
+            continue;
+        }
+
+        match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(ClientMessage::Subscribe { topic }) => {
+                if let Err(e) = validate_topic(&topic, true) {
+                    outbox.reply(ServerMessage::error(e));
+                } else if subscriptions.contains_key(&topic) {
+                    outbox.reply(ServerMessage::Subscribed { topic });
+                } else if subscriptions.len() >= config.max_subscriptions {
+                    outbox.reply(ServerMessage::error("too many subscriptions"));
+                } else {
+                    match subscribe(&hub, org_id, &topic, outbox.clone()).await {
+                        Ok(task) => {
+                            subscriptions.insert(topic.clone(), task);
+                            outbox.reply(ServerMessage::Subscribed { topic });
+                        }
+                        Err(e) => {
+                            warn!("⚠️ Failed to subscribe org {} to '{}': {}", org_id, topic, e);
+                            outbox.reply(ServerMessage::error("subscription failed"));
+                        }
+                    }
+                }
+            }
+            Ok(ClientMessage::Unsubscribe { topic }) => {
+                if let Some(task) = subscriptions.remove(&topic) {
+                    task.abort();
+                }
+                outbox.reply(ServerMessage::Unsubscribed { topic });
+            }
+            Ok(ClientMessage::Publish { topic, data }) => {
+                if !config.client_publish {
+                    outbox.reply(ServerMessage::error("publishing is disabled"));
+                } else if let Err(e) = validate_topic(&topic, false) {
+                    outbox.reply(ServerMessage::error(e));
+                } else {
+                    let payload = serde_json::to_vec(&data).unwrap_or_default();
+                    let subject = hub.subject(org_id, &topic);
+                    if let Err(e) = NatsClient::publish_with_headers(&subject, async_nats::HeaderMap::new(), payload.into()).await {
+                        warn!("⚠️ Failed to relay client message to '{}': {}", topic, e);
+                        outbox.reply(ServerMessage::error("publish failed"));
+                    }
+                }
+            }
+            Err(e) => outbox.reply(ServerMessage::error(format!("invalid message: {}", e))),
+        }
+    }
+
+    // Dropping the NATS subscribers unsubscribes; dropping the outbox lets the writer close.
+    for task in subscriptions.into_values() {
+        task.abort();
+    }
+    drop(outbox);
+    info!("🔌 WebSocket disconnected for org {}", org_id);
+}
+
+/// Relay messages on the tenant subject for `topic` into the connection's outbox.
+async fn subscribe(
+    hub: &RealtimeHub,
+    org_id: Uuid,
+    topic: &str,
+    outbox: Outbox,
+) -> Result<JoinHandle<()>, String> {
+    let mut subscriber = NatsClient::subscribe(&hub.subject(org_id, topic)).await.map_err(|e| e.to_string())?;
+    let tenant_prefix = hub.subject(org_id, "");
+
+    Ok(actix_web::rt::spawn(async move {
+        while let Some(message) = subscriber.next().await {
+            let subject = message.subject.to_string();
+            let topic = subject.strip_prefix(&tenant_prefix).unwrap_or(&subject).to_string();
+            let data = serde_json::from_slice(&message.payload)
+                .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&message.payload).into_owned()));
+            if !outbox.send(Outbound::Frame(ServerMessage::Message { topic, data }.to_json())) {
+                break;
+            }
+        }
+    }))
+}
+
+/// Single writer for the socket: frames, pongs and periodic pings.
+async fn write(mut session: Session, mut rx: mpsc::Receiver<Outbound>, overflow: Arc<Notify>, ping_interval: Duration) {
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+
+    let reason = loop {
+        let sent = tokio::select! {
+            outbound = rx.recv() => match outbound {
+                Some(Outbound::Frame(text)) => session.text(text).await,
+                Some(Outbound::Pong(bytes)) => session.pong(&bytes).await,
+                None => break None,
+            },
+            _ = ping.tick() => session.ping(b"").await,
+            _ = overflow.notified() => {
+                warn!("🐢 Closing WebSocket: client is not keeping up");
+                break Some(CloseReason { code: CloseCode::Again, description: Some("slow consumer".to_string()) });
+            }
+        };
+        if sent.is_err() {
+            return;
+        }
+    };
+    let _ = session.close(reason).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(3, Duration::from_secs(60));
+        assert!(limiter.allow());
+        assert!(limiter.allow());
+        assert!(limiter.allow());
+        assert!(!limiter.allow());
+    }
+
+    #[tokio::test]
+    async fn test_full_outbox_signals_overflow() {
+        let (tx, _rx) = mpsc::channel(1);
+        let outbox = Outbox { tx, overflow: Arc::new(Notify::new()) };
+        assert!(outbox.send(Outbound::Frame("1".to_string())));
+        assert!(!outbox.send(Outbound::Frame("2".to_string())));
+        // The permit stored by notify_one completes immediately.
+        outbox.overflow.notified().await;
+    }
+}
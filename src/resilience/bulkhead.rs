@@ -0,0 +1,168 @@
+//! Bulkheads: bounded concurrency per dependency
+//!
+//! A slow downstream service should not tie up every worker of the caller. A `Bulkhead`
+//! lets at most `max_concurrent` calls run at once; further callers wait in a bounded queue
+//! (if configured) and are turned away with `BulkheadFull` once it is full or their wait
+//! runs out:
+//!
+//! ```ignore
+//! let reports = Bulkhead::new("reports", 8).with_queue(16, Duration::from_millis(500));
+//!
+//! match reports.call(|| client.render(&report)).await {
+//!     Ok(rendered) => rendered?,
+//!     Err(full) => return Err(LanaiError::Unavailable(full.to_string())),
+//! }
+//! ```
+
+use log::warn;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Returned when a bulkhead has no free slot and no room (or time) left to wait.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Bulkhead '{name}' is full ({max_concurrent} running, queue full or wait expired)")]
+pub struct BulkheadFull {
+    pub name: String,
+    pub max_concurrent: usize,
+}
+
+struct Inner {
+    name: String,
+    semaphore: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_waiting: usize,
+    max_wait: Duration,
+    waiting: AtomicUsize,
+}
+
+/// Concurrency limit shared by its clones.
+#[derive(Clone)]
+pub struct Bulkhead {
+    inner: Arc<Inner>,
+}
+
+/// A slot in a bulkhead, freed when dropped.
+pub struct BulkheadPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Bulkhead {
+    /// At most `max_concurrent` calls at once; without a queue, others fail immediately.
+    pub fn new(name: &str, max_concurrent: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                semaphore: Arc::new(Semaphore::new(max_concurrent)),
+                max_concurrent,
+                max_waiting: 0,
+                max_wait: Duration::ZERO,
+                waiting: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Let up to `max_waiting` callers wait for a slot, each for at most `max_wait`.
+    pub fn with_queue(mut self, max_waiting: usize, max_wait: Duration) -> Self {
+        let inner = Arc::get_mut(&mut self.inner).expect("Bulkhead is configured before it is shared");
+        inner.max_waiting = max_waiting;
+        inner.max_wait = max_wait;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Free slots right now.
+    pub fn available(&self) -> usize {
+        self.inner.semaphore.available_permits()
+    }
+
+    /// Callers currently queued for a slot.
+    pub fn waiting(&self) -> usize {
+        self.inner.waiting.load(Ordering::Acquire)
+    }
+
+    /// Take a slot, waiting in the queue if there is room.
+    pub async fn acquire(&self) -> Result<BulkheadPermit, BulkheadFull> {
+        if let Ok(permit) = self.inner.semaphore.clone().try_acquire_owned() {
+            return Ok(BulkheadPermit { _permit: permit });
+        }
+
+        let queued = self.inner.waiting.fetch_update(Ordering::AcqRel, Ordering::Acquire, |waiting| {
+            (waiting < self.inner.max_waiting).then_some(waiting + 1)
+        });
+        if queued.is_err() {
+            return Err(self.full());
+        }
+        let in_queue = InQueue(&self.inner.waiting);
+        let acquired = tokio::time::timeout(self.inner.max_wait, self.inner.semaphore.clone().acquire_owned()).await;
+        drop(in_queue);
+        match acquired {
+            Ok(Ok(permit)) => Ok(BulkheadPermit { _permit: permit }),
+            _ => Err(self.full()),
+        }
+    }
+
+    /// Run `f` in a slot, or fail with `BulkheadFull` without running it.
+    pub async fn call<F, Fut>(&self, f: F) -> Result<Fut::Output, BulkheadFull>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future,
+    {
+        let _permit = self.acquire().await?;
+        Ok(f().await)
+    }
+
+    fn full(&self) -> BulkheadFull {
+        warn!("🚧 Bulkhead '{}' rejected a call: {} running, {} waiting", self.inner.name, self.inner.max_concurrent, self.waiting());
+        BulkheadFull { name: self.inner.name.clone(), max_concurrent: self.inner.max_concurrent }
+    }
+}
+
+/// A queued caller; leaves the queue when dropped, even if the caller gives up.
+struct InQueue<'a>(&'a AtomicUsize);
+
+impl Drop for InQueue<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rejects_when_full() {
+        let bulkhead = Bulkhead::new("reports", 1);
+        let permit = bulkhead.acquire().await.unwrap();
+
+        assert_eq!(bulkhead.call(|| async { 1 }).await.unwrap_err().name, "reports");
+        drop(permit);
+        assert_eq!(bulkhead.call(|| async { 1 }).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_queued_caller_gets_freed_slot() {
+        let bulkhead = Bulkhead::new("reports", 1).with_queue(1, Duration::from_secs(1));
+        let permit = bulkhead.acquire().await.unwrap();
+
+        let queued = tokio::spawn({
+            let bulkhead = bulkhead.clone();
+            async move { bulkhead.call(|| async { 1 }).await }
+        });
+        while bulkhead.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+        // The queue holds one caller
+        assert!(bulkhead.acquire().await.is_err());
+
+        drop(permit);
+        assert_eq!(queued.await.unwrap(), Ok(1));
+    }
+}
@@ -0,0 +1,113 @@
+//! Circuit breaker settings from config files and environment variables
+//!
+//! `CircuitBreakerConfig` deserializes from a section of a service's config file (durations
+//! as `"30s"`, `"250ms"`...) or loads on its own from `{PREFIX}_*` variables:
+//!
+//! ```text
+//! PAYMENTS_BREAKER_FAILURE_THRESHOLD=3
+//! PAYMENTS_BREAKER_RESET_TIMEOUT=1m
+//! PAYMENTS_BREAKER_SLOW_CALL_THRESHOLD=2s
+//! PAYMENTS_BREAKER_FAILURE_RATE__THRESHOLD=0.5
+//! ```
+//!
+//! ```ignore
+//! let payments = CircuitBreaker::from_config(&CircuitBreakerConfig::from_env("PAYMENTS_BREAKER")?);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::{CircuitBreaker, FailureRate, RollingWindow};
+use crate::config::{ConfigError, ConfigLoader, Validate, Validator};
+
+/// Settings of a circuit breaker; unset fields keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    #[serde(with = "crate::config::duration")]
+    pub reset_timeout: Duration,
+    /// Consecutive successes in HalfOpen before closing.
+    pub success_threshold: u32,
+    /// Probe calls allowed at once in HalfOpen (see `CircuitBreaker::with_half_open_max_calls`).
+    pub half_open_max_calls: u32,
+    /// Also open on a high failure share (see `CircuitBreaker::with_failure_rate`).
+    pub failure_rate: Option<FailureRate>,
+    /// Count successes this slow as failures (see `CircuitBreaker::with_slow_call_threshold`).
+    #[serde(with = "crate::config::duration::option")]
+    pub slow_call_threshold: Option<Duration>,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+            success_threshold: 2,
+            half_open_max_calls: u32::MAX,
+            failure_rate: None,
+            slow_call_threshold: None,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Defaults overridden by the `{prefix}_*` variables, e.g. `{prefix}_RESET_TIMEOUT=1m`.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        ConfigLoader::new().env_prefix(prefix).load()
+    }
+}
+
+impl Validate for CircuitBreakerConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.check("failure_threshold", self.failure_threshold > 0, "must be at least 1");
+        v.check("success_threshold", self.success_threshold > 0, "must be at least 1");
+        v.check("half_open_max_calls", self.half_open_max_calls > 0, "must be at least 1");
+        if let Some(rate) = &self.failure_rate {
+            v.range("failure_rate.threshold", rate.threshold, 0.0..=1.0);
+            let window_ok = match rate.window {
+                RollingWindow::Calls(calls) => calls > 0,
+                RollingWindow::Time(window) => !window.is_zero(),
+            };
+            v.check("failure_rate.window", window_ok, "must not be empty");
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// A breaker with the settings of `config`.
+    pub fn from_config(config: &CircuitBreakerConfig) -> Self {
+        let mut breaker = CircuitBreaker::new(config.failure_threshold, config.reset_timeout)
+            .with_success_threshold(config.success_threshold)
+            .with_half_open_max_calls(config.half_open_max_calls);
+        if let Some(rate) = &config.failure_rate {
+            breaker = breaker.with_failure_rate(rate.clone());
+        }
+        if let Some(threshold) = config.slow_call_threshold {
+            breaker = breaker.with_slow_call_threshold(threshold);
+        }
+        breaker
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_deserializes_with_defaults() {
+        let config: CircuitBreakerConfig = serde_json::from_value(serde_json::json!({
+            "failure_threshold": 3,
+            "reset_timeout": "1m",
+            "slow_call_threshold": "2s",
+            "failure_rate": {"threshold": 0.5, "window": {"time": "30s"}}
+        }))
+        .unwrap();
+
+        assert_eq!(config.failure_threshold, 3);
+        assert_eq!(config.reset_timeout, Duration::from_secs(60));
+        assert_eq!(config.success_threshold, 2);
+        assert_eq!(config.slow_call_threshold, Some(Duration::from_secs(2)));
+        assert_eq!(config.failure_rate.unwrap().window, RollingWindow::Time(Duration::from_secs(30)));
+    }
+}
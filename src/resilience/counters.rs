@@ -0,0 +1,226 @@
+//! Per-worker outcome counters of a circuit breaker
+//!
+//! Every call through a breaker records its outcome, so a single shared counter or window
+//! makes all workers of a busy service contend on it. Outcomes go to the calling thread's
+//! shard instead: `CallCounts` sums the shards when read, and `FailureWindow` moves them
+//! into its rolling window on every failure and otherwise at most once per
+//! `AGGREGATE_INTERVAL`.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{FailureRate, RollingWindow};
+
+/// Shards per counter; threads beyond this many share one.
+const SHARDS: usize = 16;
+
+/// Longest a success waits in its shard before it reaches the rolling window.
+const AGGREGATE_INTERVAL: Duration = Duration::from_millis(100);
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/// The calling thread's shard.
+fn shard<T>(shards: &[Padded<T>]) -> &T {
+    &shards[SHARD.with(|shard| *shard)].0
+}
+
+fn shards<T: Default>() -> Box<[Padded<T>]> {
+    (0..SHARDS).map(|_| Padded::default()).collect()
+}
+
+/// One shard per cache line, so neighbouring shards do not contend either.
+#[derive(Default)]
+#[repr(align(64))]
+struct Padded<T>(T);
+
+#[derive(Default)]
+struct Calls {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    rejections: AtomicU64,
+}
+
+/// Calls since the breaker was built, by outcome.
+pub(super) struct CallCounts {
+    shards: Box<[Padded<Calls>]>,
+}
+
+impl Default for CallCounts {
+    fn default() -> Self {
+        Self { shards: shards() }
+    }
+}
+
+impl CallCounts {
+    pub(super) fn success(&self) {
+        shard(&self.shards).successes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn failure(&self) {
+        shard(&self.shards).failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn rejection(&self) {
+        shard(&self.shards).rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Successes, failures and rejections, summed over the shards.
+    pub(super) fn totals(&self) -> (u64, u64, u64) {
+        self.shards.iter().fold((0, 0, 0), |(successes, failures, rejections), shard| {
+            (
+                successes + shard.0.successes.load(Ordering::Relaxed),
+                failures + shard.0.failures.load(Ordering::Relaxed),
+                rejections + shard.0.rejections.load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+/// A shard's pending outcomes: calls in the low half, failures in the high half, so
+/// aggregation takes both in one swap.
+const FAILURE: u64 = 1 << 32;
+const CALLS: u64 = FAILURE - 1;
+
+/// Aggregated outcomes as (tick, calls, failures), oldest first.
+#[derive(Default)]
+struct Buckets {
+    buckets: VecDeque<(u64, u64, u64)>,
+    calls: u64,
+    failures: u64,
+}
+
+impl Buckets {
+    fn evict(&mut self, window: RollingWindow, now: u64) {
+        match window {
+            RollingWindow::Calls(n) => {
+                let n = n as u64;
+                while self.calls > n {
+                    let excess = self.calls - n;
+                    let Some((_, calls, failures)) = self.buckets.front_mut() else { break };
+                    if *calls <= excess {
+                        self.calls -= *calls;
+                        self.failures -= *failures;
+                        self.buckets.pop_front();
+                    } else {
+                        // Keep the newer part of the bucket, with its share of the failures
+                        let expired = *failures * excess / *calls;
+                        *calls -= excess;
+                        *failures -= expired;
+                        self.calls -= excess;
+                        self.failures -= expired;
+                    }
+                }
+            }
+            RollingWindow::Time(window) => {
+                while let Some(&(tick, calls, failures)) = self.buckets.front() {
+                    if now.saturating_sub(tick) <= window.as_nanos() as u64 {
+                        break;
+                    }
+                    self.calls -= calls;
+                    self.failures -= failures;
+                    self.buckets.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Closed-state outcomes over the rolling window of a `FailureRate`.
+pub(super) struct FailureWindow {
+    rate: FailureRate,
+    pending: Box<[Padded<AtomicU64>]>,
+    buckets: Mutex<Buckets>,
+    /// Tick of the last aggregation.
+    aggregated_at: AtomicU64,
+}
+
+impl FailureWindow {
+    pub(super) fn new(rate: FailureRate) -> Self {
+        Self { rate, pending: shards(), buckets: Mutex::new(Buckets::default()), aggregated_at: AtomicU64::new(0) }
+    }
+
+    /// Record an outcome at `now` (a breaker tick); true if the failure rate now calls for
+    /// opening. Only failures can raise the rate, so only they aggregate right away.
+    pub(super) fn record(&self, now: u64, failed: bool) -> bool {
+        shard(&self.pending).fetch_add(if failed { FAILURE + 1 } else { 1 }, Ordering::Relaxed);
+        if !failed {
+            let last = self.aggregated_at.load(Ordering::Acquire);
+            if now.saturating_sub(last) < AGGREGATE_INTERVAL.as_nanos() as u64 {
+                return false;
+            }
+            // Whichever success gets there first aggregates
+            if self.aggregated_at.compare_exchange(last, now, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                return false;
+            }
+        }
+        self.aggregate(now)
+    }
+
+    fn aggregate(&self, now: u64) -> bool {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        self.aggregated_at.store(now, Ordering::Release);
+        let (calls, failures) = self.pending.iter().fold((0, 0), |(calls, failures), shard| {
+            let pending = shard.0.swap(0, Ordering::AcqRel);
+            (calls + (pending & CALLS), failures + (pending >> 32))
+        });
+        if calls > 0 {
+            buckets.buckets.push_back((now, calls, failures));
+            buckets.calls += calls;
+            buckets.failures += failures;
+        }
+        buckets.evict(self.rate.window, now);
+        buckets.calls > 0
+            && buckets.calls >= self.rate.minimum_calls as u64
+            && buckets.failures as f64 / buckets.calls as f64 >= self.rate.threshold
+    }
+
+    /// Forget every outcome, so a closed circuit is judged on fresh calls only.
+    pub(super) fn clear(&self) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        *buckets = Buckets::default();
+        for shard in self.pending.iter() {
+            shard.0.store(0, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_window_aggregates_shards() {
+        let window = FailureWindow::new(FailureRate::over_calls(0.5, 4));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| window.record(1, false));
+            }
+        });
+        // Successes wait in their shards until a failure aggregates them; the window then
+        // keeps the last 4 calls
+        assert!(!window.record(2, true));
+        assert_eq!(window.buckets.lock().unwrap().calls, 4);
+        assert!(window.record(3, true));
+
+        window.clear();
+        assert!(!window.record(4, true));
+
+        let counts = CallCounts::default();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    counts.success();
+                    counts.rejection();
+                });
+            }
+        });
+        counts.failure();
+        assert_eq!(counts.totals(), (4, 1, 4));
+    }
+}
@@ -0,0 +1,393 @@
+//! Ordered endpoint failover for shared connections
+//!
+//! A `Failover` holds one connection to the best reachable endpoint of a dependency, from
+//! an ordered list (primary region first, then DR regions). A background monitor probes it:
+//! - after `failure_threshold` failed probes of the active endpoint, it connects to the
+//!   first healthy endpoint in order and switches to it
+//! - while a fallback is active, it probes the preferred endpoints and fails back once one
+//!   passed `recovery_threshold` probes in a row, so a flapping primary is not reused
+//!
+//! Every switch is logged, counted in `failover_switches_total`, broadcast to
+//! `Failover::subscribe` and published on `lanai.infra.failover.{dependency}` when NATS is
+//! initialized. `NatsClient` and `cache::shared_connection` use it when
+//! `NATS_FALLBACK_URLS` / `REDIS_FALLBACK_URLS` list fallback endpoints:
+//!
+//! ```text
+//! REDIS_URL=redis://redis.sa-east-1.internal:6379
+//! REDIS_FALLBACK_URLS=us-east-1=redis://redis.us-east-1.internal:6379
+//! ```
+//!
+//! Connections are replaced, not redirected: callers should fetch the current one
+//! (`Failover::current`, `NatsClient::global`, `shared_connection`) when they need it, or
+//! rebuild long-lived clients and subscriptions when notified.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::messaging::NatsClient;
+
+/// Prefix of the subjects failover events are published on.
+pub const FAILOVER_SUBJECT: &str = "lanai.infra.failover";
+
+/// Failover error types
+#[derive(Debug, Error)]
+pub enum FailoverError {
+    #[error("No endpoints configured for {0}")]
+    NoEndpoints(String),
+
+    #[error("No {dependency} endpoint is reachable: {reasons}")]
+    Unreachable { dependency: String, reasons: String },
+}
+
+/// One endpoint of a dependency, labelled with its region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub region: String,
+    pub url: String,
+}
+
+impl Endpoint {
+    pub fn new(region: &str, url: &str) -> Self {
+        Self { region: region.to_string(), url: url.to_string() }
+    }
+}
+
+/// `primary` (labelled `primary`) followed by the comma-separated `fallbacks`, each either
+/// `url` or `region=url`. Unlabelled fallbacks are named `fallback-1`, `fallback-2`...
+pub fn parse_endpoints(primary: &str, fallbacks: &str) -> Vec<Endpoint> {
+    let fallbacks = fallbacks.split(',').map(str::trim).filter(|entry| !entry.is_empty());
+    std::iter::once(Endpoint::new("primary", primary.trim()))
+        .chain(fallbacks.enumerate().map(|(i, entry)| match entry.split_once('=') {
+            // `=` may also appear in a URL's query string, which starts after `://`
+            Some((region, url)) if !region.contains("://") => Endpoint::new(region.trim(), url.trim()),
+            _ => Endpoint::new(&format!("fallback-{}", i + 1), entry),
+        }))
+        .collect()
+}
+
+/// Connects to and health-checks endpoints of one kind of dependency.
+#[async_trait]
+pub trait Connector: Send + Sync + 'static {
+    type Connection: Clone + Send + Sync + 'static;
+
+    async fn connect(&self, url: &str) -> Result<Self::Connection, String>;
+
+    async fn ping(&self, connection: &Self::Connection) -> bool;
+}
+
+/// Probe timing and thresholds.
+#[derive(Debug, Clone)]
+pub struct FailoverPolicy {
+    pub probe_interval: Duration,
+    /// A connect or probe taking longer than this fails.
+    pub probe_timeout: Duration,
+    pub failure_threshold: u32,
+    pub recovery_threshold: u32,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            probe_interval: Duration::from_secs(5),
+            probe_timeout: Duration::from_secs(2),
+            failure_threshold: 3,
+            recovery_threshold: 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverKind {
+    /// Moved away from an unhealthy endpoint.
+    Failover,
+    /// Moved back to a preferred endpoint that recovered.
+    Failback,
+}
+
+/// Published whenever a dependency switches endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverEvent {
+    pub dependency: String,
+    pub kind: FailoverKind,
+    pub from: Endpoint,
+    pub to: Endpoint,
+    pub occurred_at: DateTime<Utc>,
+}
+
+struct Inner<C: Connector> {
+    dependency: String,
+    endpoints: Vec<Endpoint>,
+    connector: C,
+    policy: FailoverPolicy,
+    active: RwLock<(usize, C::Connection)>,
+    events: broadcast::Sender<FailoverEvent>,
+    switches: Counter<u64>,
+}
+
+/// The connection to the best reachable endpoint of a dependency; clones share it.
+pub struct Failover<C: Connector> {
+    inner: Arc<Inner<C>>,
+}
+
+impl<C: Connector> Clone for Failover<C> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<C: Connector> Failover<C> {
+    /// Connect to the first reachable endpoint in order. Call `start` to monitor it.
+    pub async fn connect(
+        dependency: &str,
+        endpoints: Vec<Endpoint>,
+        connector: C,
+        policy: FailoverPolicy,
+    ) -> Result<Self, FailoverError> {
+        if endpoints.is_empty() {
+            return Err(FailoverError::NoEndpoints(dependency.to_string()));
+        }
+
+        let mut reasons = Vec::new();
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            match within(policy.probe_timeout, connector.connect(&endpoint.url)).await {
+                Ok(connection) => {
+                    if index == 0 {
+                        info!("✅ {} connected to {} ({})", dependency, endpoint.region, endpoint.url);
+                    } else {
+                        let reasons = reasons.join("; ");
+                        warn!("⚠️ {} started on fallback {} ({}): {}", dependency, endpoint.region, endpoint.url, reasons);
+                    }
+                    let (events, _) = broadcast::channel(16);
+                    let switches = global::meter("lanai.resilience")
+                        .u64_counter("failover_switches_total")
+                        .with_description("Endpoint switches by dependency, direction and target region")
+                        .build();
+                    return Ok(Self {
+                        inner: Arc::new(Inner {
+                            dependency: dependency.to_string(),
+                            endpoints,
+                            connector,
+                            policy,
+                            active: RwLock::new((index, connection)),
+                            events,
+                            switches,
+                        }),
+                    });
+                }
+                Err(e) => reasons.push(format!("{}: {}", endpoint.region, e)),
+            }
+        }
+        Err(FailoverError::Unreachable { dependency: dependency.to_string(), reasons: reasons.join("; ") })
+    }
+
+    /// Connection to the active endpoint.
+    pub fn current(&self) -> C::Connection {
+        self.inner.active.read().unwrap_or_else(|e| e.into_inner()).1.clone()
+    }
+
+    pub fn active_endpoint(&self) -> &Endpoint {
+        &self.inner.endpoints[self.active_index()]
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.inner.endpoints
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<FailoverEvent> {
+        self.inner.events.subscribe()
+    }
+
+    fn active_index(&self) -> usize {
+        self.inner.active.read().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    /// Probe in the background until the handle is aborted.
+    pub fn start(&self) -> JoinHandle<()> {
+        let failover = self.clone();
+        tokio::spawn(async move {
+            let policy = failover.inner.policy.clone();
+            let mut failures = 0;
+            // Consecutive healthy probes of each endpoint preferred over the active one
+            let mut recoveries = vec![0u32; failover.inner.endpoints.len()];
+            let mut interval = tokio::time::interval(policy.probe_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let active = failover.active_index();
+
+                if failover.probe(&failover.current()).await {
+                    failures = 0;
+                } else {
+                    failures += 1;
+                    let region = &failover.inner.endpoints[active].region;
+                    let threshold = policy.failure_threshold;
+                    warn!("⚠️ {} probe of {} failed ({}/{})", failover.inner.dependency, region, failures, threshold);
+                    if failures >= policy.failure_threshold && failover.fail_over(active).await {
+                        failures = 0;
+                        recoveries.iter_mut().for_each(|count| *count = 0);
+                    }
+                    continue;
+                }
+
+                for preferred in 0..active {
+                    let url = &failover.inner.endpoints[preferred].url;
+                    let Ok(connection) = within(policy.probe_timeout, failover.inner.connector.connect(url)).await else {
+                        recoveries[preferred] = 0;
+                        continue;
+                    };
+                    if !failover.probe(&connection).await {
+                        recoveries[preferred] = 0;
+                        continue;
+                    }
+                    recoveries[preferred] += 1;
+                    if recoveries[preferred] >= policy.recovery_threshold {
+                        failover.switch(preferred, connection, FailoverKind::Failback).await;
+                        recoveries.iter_mut().for_each(|count| *count = 0);
+                    }
+                    break;
+                }
+            }
+        })
+    }
+
+    async fn probe(&self, connection: &C::Connection) -> bool {
+        tokio::time::timeout(self.inner.policy.probe_timeout, self.inner.connector.ping(connection))
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Switch to the first healthy endpoint other than `active`; false if none is.
+    async fn fail_over(&self, active: usize) -> bool {
+        for (index, endpoint) in self.inner.endpoints.iter().enumerate().filter(|(i, _)| *i != active) {
+            let connection = match within(self.inner.policy.probe_timeout, self.inner.connector.connect(&endpoint.url)).await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("⚠️ {} fallback {} is unreachable: {}", self.inner.dependency, endpoint.region, e);
+                    continue;
+                }
+            };
+            if !self.probe(&connection).await {
+                warn!("⚠️ {} fallback {} is not healthy", self.inner.dependency, endpoint.region);
+                continue;
+            }
+            let kind = if index < active { FailoverKind::Failback } else { FailoverKind::Failover };
+            self.switch(index, connection, kind).await;
+            return true;
+        }
+        error!("❌ No healthy {} endpoint; staying on {}", self.inner.dependency, self.inner.endpoints[active].region);
+        false
+    }
+
+    async fn switch(&self, index: usize, connection: C::Connection, kind: FailoverKind) {
+        let previous = {
+            let mut active = self.inner.active.write().unwrap_or_else(|e| e.into_inner());
+            std::mem::replace(&mut *active, (index, connection)).0
+        };
+        let event = FailoverEvent {
+            dependency: self.inner.dependency.clone(),
+            kind,
+            from: self.inner.endpoints[previous].clone(),
+            to: self.inner.endpoints[index].clone(),
+            occurred_at: Utc::now(),
+        };
+        warn!("🔀 {} {:?} from {} to {}", event.dependency, kind, event.from.region, event.to.region);
+        self.inner.switches.add(
+            1,
+            &[
+                KeyValue::new("dependency", event.dependency.clone()),
+                KeyValue::new("kind", if kind == FailoverKind::Failover { "failover" } else { "failback" }),
+                KeyValue::new("region", event.to.region.clone()),
+            ],
+        );
+        let _ = self.inner.events.send(event.clone());
+        if NatsClient::is_initialized() {
+            let subject = format!("{}.{}", FAILOVER_SUBJECT, event.dependency);
+            if let Err(e) = NatsClient::publish_event(&subject, &event).await {
+                warn!("⚠️ Failed to publish {} failover event: {}", event.dependency, e);
+            }
+        }
+    }
+}
+
+async fn within<T>(limit: Duration, future: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(limit, future).await.unwrap_or_else(|_| Err(format!("timed out after {:?}", limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    /// Endpoints are healthy unless their URL is in `down`.
+    #[derive(Clone, Default)]
+    struct FakeConnector {
+        down: Arc<Mutex<HashSet<String>>>,
+    }
+
+    #[async_trait]
+    impl Connector for FakeConnector {
+        type Connection = String;
+
+        async fn connect(&self, url: &str) -> Result<String, String> {
+            if self.down.lock().unwrap().contains(url) {
+                return Err("connection refused".to_string());
+            }
+            Ok(url.to_string())
+        }
+
+        async fn ping(&self, connection: &String) -> bool {
+            !self.down.lock().unwrap().contains(connection)
+        }
+    }
+
+    #[test]
+    fn test_parse_endpoints() {
+        let endpoints = parse_endpoints("redis://sa:6379", "us-east-1=redis://us:6379, redis://eu:6379?db=1");
+        assert_eq!(endpoints, vec![
+            Endpoint::new("primary", "redis://sa:6379"),
+            Endpoint::new("us-east-1", "redis://us:6379"),
+            Endpoint::new("fallback-2", "redis://eu:6379?db=1"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_back() {
+        let connector = FakeConnector::default();
+        let down = connector.down.clone();
+        let policy = FailoverPolicy {
+            probe_interval: Duration::from_millis(10),
+            probe_timeout: Duration::from_millis(50),
+            failure_threshold: 2,
+            recovery_threshold: 3,
+        };
+        let endpoints = parse_endpoints("nats://sa:4222", "us-east-1=nats://us:4222");
+        down.lock().unwrap().insert("nats://sa:4222".to_string());
+        let failover = Failover::connect("nats", endpoints, connector, policy).await.unwrap();
+        assert_eq!(failover.active_endpoint().region, "us-east-1");
+
+        let mut events = failover.subscribe();
+        let monitor = failover.start();
+        down.lock().unwrap().clear();
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+        assert_eq!((event.kind, event.to.region.as_str()), (FailoverKind::Failback, "primary"));
+        assert_eq!(failover.current(), "nats://sa:4222");
+
+        down.lock().unwrap().insert("nats://sa:4222".to_string());
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+        assert_eq!((event.kind, event.from.region.as_str()), (FailoverKind::Failover, "primary"));
+        assert_eq!(failover.current(), "nats://us:4222");
+        monitor.abort();
+    }
+}
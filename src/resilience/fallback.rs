@@ -0,0 +1,223 @@
+//! Serve-stale fallback for calls behind a circuit breaker
+//!
+//! For reads where stale data beats a 503: `CachedCall` remembers the last successful result
+//! of each call and, while the breaker is open, returns it marked as stale instead of failing.
+//! Operation errors with the circuit closed are still returned as errors.
+//!
+//! ```ignore
+//! let catalog = CachedCall::new(catalog_breaker.clone(), Arc::new(RedisFallbackStore::new(conn)));
+//!
+//! async fn get_product(tenant: TenantContext, id: web::Path<Uuid>) -> LanaiResult<Served<Product>> {
+//!     let key = format!("catalog:{}:product:{}", tenant.org_id, id);
+//!     Ok(catalog.call(&key, || client.get_product(*id)).await?)
+//! }
+//! ```
+
+use actix_web::{body::BoxBody, http::header, HttpRequest, HttpResponse, Responder};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use redis::aio::ConnectionManager;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{CircuitBreaker, CircuitBreakerOutcome, CircuitBreakerResult};
+
+/// Set on stale responses, with the time the value was stored (RFC 3339).
+pub const STALE_SINCE_HEADER: &str = "X-Lanai-Stale-Since";
+
+/// A remembered result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackEntry {
+    pub value: serde_json::Value,
+    pub stored_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait FallbackStore: Send + Sync {
+    async fn load(&self, key: &str) -> Option<FallbackEntry>;
+
+    async fn save(&self, key: &str, entry: &FallbackEntry, ttl: Duration);
+}
+
+/// In-process store; each replica keeps its own copies.
+pub struct InMemoryFallbackStore {
+    entries: moka::future::Cache<String, FallbackEntry>,
+}
+
+impl InMemoryFallbackStore {
+    /// Keeps up to `max_entries` results for up to `ttl`.
+    pub fn new(max_entries: u64, ttl: Duration) -> Self {
+        Self { entries: moka::future::Cache::builder().max_capacity(max_entries).time_to_live(ttl).build() }
+    }
+}
+
+impl Default for InMemoryFallbackStore {
+    fn default() -> Self {
+        Self::new(10_000, Duration::from_secs(24 * 3600))
+    }
+}
+
+#[async_trait]
+impl FallbackStore for InMemoryFallbackStore {
+    async fn load(&self, key: &str) -> Option<FallbackEntry> {
+        self.entries.get(key).await
+    }
+
+    async fn save(&self, key: &str, entry: &FallbackEntry, _ttl: Duration) {
+        self.entries.insert(key.to_string(), entry.clone()).await;
+    }
+}
+
+/// Redis store shared by all replicas, under `lanai:fallback:`. Failures are logged and
+/// treated as misses.
+pub struct RedisFallbackStore {
+    conn: ConnectionManager,
+}
+
+impl RedisFallbackStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl FallbackStore for RedisFallbackStore {
+    async fn load(&self, key: &str) -> Option<FallbackEntry> {
+        let raw: Result<Option<String>, _> =
+            redis::cmd("GET").arg(format!("lanai:fallback:{}", key)).query_async(&mut self.conn.clone()).await;
+        match raw {
+            Ok(raw) => raw.and_then(|raw| serde_json::from_str(&raw).ok()),
+            Err(e) => {
+                warn!("⚠️ Failed to load fallback for '{}': {}", key, e);
+                None
+            }
+        }
+    }
+
+    async fn save(&self, key: &str, entry: &FallbackEntry, ttl: Duration) {
+        let Ok(raw) = serde_json::to_string(entry) else { return };
+        let result: Result<(), _> = redis::cmd("SET")
+            .arg(format!("lanai:fallback:{}", key))
+            .arg(raw)
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query_async(&mut self.conn.clone())
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Failed to store fallback for '{}': {}", key, e);
+        }
+    }
+}
+
+/// A result, and when it was stored if it is a stale fallback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Served<T> {
+    pub value: T,
+    pub stale_since: Option<DateTime<Utc>>,
+}
+
+impl<T> Served<T> {
+    pub fn is_stale(&self) -> bool {
+        self.stale_since.is_some()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// JSON body; stale values add `Warning: 110` and `X-Lanai-Stale-Since`.
+impl<T: Serialize> Responder for Served<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse {
+        let mut response = HttpResponse::Ok();
+        if let Some(since) = self.stale_since {
+            response.insert_header((header::WARNING, "110 - \"Response is Stale\""));
+            response.insert_header((STALE_SINCE_HEADER, since.to_rfc3339()));
+        }
+        response.json(self.value)
+    }
+}
+
+/// Circuit-breaker-protected calls with a last-known-good fallback.
+#[derive(Clone)]
+pub struct CachedCall {
+    breaker: Arc<CircuitBreaker>,
+    store: Arc<dyn FallbackStore>,
+    retention: Duration,
+}
+
+impl CachedCall {
+    /// Results are kept for 24 hours.
+    pub fn new(breaker: Arc<CircuitBreaker>, store: Arc<dyn FallbackStore>) -> Self {
+        Self { breaker, store, retention: Duration::from_secs(24 * 3600) }
+    }
+
+    /// How long a result can be served as a fallback.
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Run `f` through the breaker, remembering its result under `key` (include the tenant
+    /// in it). While the circuit is open, the remembered result is served if there is one.
+    pub async fn call<T, E, F, Fut>(&self, key: &str, f: F) -> CircuitBreakerResult<Served<T>, E>
+    where
+        T: Serialize + DeserializeOwned,
+        E: std::fmt::Display,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        match self.breaker.call(f).await {
+            Ok(value) => {
+                match serde_json::to_value(&value) {
+                    Ok(json) => {
+                        let entry = FallbackEntry { value: json, stored_at: Utc::now() };
+                        self.store.save(key, &entry, self.retention).await;
+                    }
+                    Err(e) => warn!("⚠️ Result for '{}' is not serializable, no fallback kept: {}", key, e),
+                }
+                Ok(Served { value, stale_since: None })
+            }
+            Err(CircuitBreakerOutcome::CircuitOpen) => {
+                let entry = self.store.load(key).await.ok_or(CircuitBreakerOutcome::CircuitOpen)?;
+                let value = serde_json::from_value(entry.value).map_err(|e| {
+                    warn!("⚠️ Fallback for '{}' no longer deserializes: {}", key, e);
+                    CircuitBreakerOutcome::CircuitOpen
+                })?;
+                info!("🧊 Circuit open, serving '{}' stored at {}", key, entry.stored_at);
+                Ok(Served { value, stale_since: Some(entry.stored_at) })
+            }
+            Err(outcome) => Err(outcome),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_stale_while_open() {
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let call = CachedCall::new(breaker.clone(), Arc::new(InMemoryFallbackStore::default()));
+
+        let fresh = call.call("product:1", || async { Ok::<_, String>(vec![1, 2, 3]) }).await.unwrap();
+        assert!(!fresh.is_stale());
+
+        // The failure that opens the circuit is still reported as an error.
+        let failed = call.call("product:1", || async { Err::<Vec<i32>, _>("down".to_string()) }).await;
+        assert!(matches!(failed, Err(CircuitBreakerOutcome::OperationError(_))));
+
+        let stale = call.call("product:1", || async { Ok::<_, String>(vec![9]) }).await.unwrap();
+        assert!(stale.is_stale());
+        assert_eq!(stale.value, vec![1, 2, 3]);
+
+        let missing = call.call("product:2", || async { Ok::<_, String>(vec![9]) }).await;
+        assert!(matches!(missing, Err(CircuitBreakerOutcome::CircuitOpen)));
+    }
+}
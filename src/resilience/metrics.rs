@@ -0,0 +1,112 @@
+//! Circuit breaker metrics
+//!
+//! Breakers built with `CircuitBreaker::with_metrics` (every breaker the
+//! `CircuitBreakerRegistry` creates) export through the global OpenTelemetry meter,
+//! labelled `breaker`:
+//! - `circuit_breaker_calls_total`, by `outcome` (`success`, `failure`, `rejected`, `slow`
+//!   for successes over the slow-call threshold, and `ignored` for errors `call_classified`
+//!   does not count)
+//! - `circuit_breaker_transitions_total`, by target `state`
+//! - `circuit_breaker_state`: 0 closed, 1 open, 2 half-open; alert on `== 1`
+//! - `circuit_breaker_open_seconds`: time since the breaker left Closed (0 while closed)
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use super::CircuitState;
+
+/// State and since when it left Closed, of every instrumented breaker, read by the gauges.
+type States = Arc<Mutex<BTreeMap<String, (CircuitState, Option<Instant>)>>>;
+
+static STATES: OnceLock<States> = OnceLock::new();
+
+/// Registers the gauges on first use; they observe every instrumented breaker.
+fn states() -> &'static States {
+    STATES.get_or_init(|| {
+        let states: States = Arc::default();
+        let meter = global::meter("lanai.resilience");
+
+        let observed = states.clone();
+        meter
+            .u64_observable_gauge("circuit_breaker_state")
+            .with_description("Circuit breaker state: 0 closed, 1 open, 2 half-open")
+            .with_callback(move |observer| {
+                for (name, (state, _)) in observed.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+                    let value = match state {
+                        CircuitState::Closed => 0,
+                        CircuitState::Open => 1,
+                        CircuitState::HalfOpen => 2,
+                    };
+                    observer.observe(value, &[KeyValue::new("breaker", name.clone())]);
+                }
+            })
+            .build();
+
+        let observed = states.clone();
+        meter
+            .f64_observable_gauge("circuit_breaker_open_seconds")
+            .with_description("Time since the circuit breaker left Closed, 0 while closed")
+            .with_unit("s")
+            .with_callback(move |observer| {
+                for (name, (_, since)) in observed.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+                    let open = since.map(|since| since.elapsed().as_secs_f64()).unwrap_or(0.0);
+                    observer.observe(open, &[KeyValue::new("breaker", name.clone())]);
+                }
+            })
+            .build();
+
+        states
+    })
+}
+
+pub(crate) struct BreakerMetrics {
+    name: String,
+    calls: Counter<u64>,
+    transitions: Counter<u64>,
+}
+
+impl BreakerMetrics {
+    pub(crate) fn new(name: &str) -> Self {
+        states()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), (CircuitState::Closed, None));
+        let meter = global::meter("lanai.resilience");
+        Self {
+            name: name.to_string(),
+            calls: meter
+                .u64_counter("circuit_breaker_calls_total")
+                .with_description("Calls through a circuit breaker, by outcome")
+                .build(),
+            transitions: meter
+                .u64_counter("circuit_breaker_transitions_total")
+                .with_description("Circuit breaker state changes, by target state")
+                .build(),
+        }
+    }
+
+    /// `outcome` is `success`, `failure`, `rejected`, `slow` or `ignored`.
+    pub(crate) fn record_call(&self, outcome: &'static str) {
+        self.calls.add(1, &[KeyValue::new("breaker", self.name.clone()), KeyValue::new("outcome", outcome)]);
+    }
+
+    pub(crate) fn record_transition(&self, from: CircuitState, to: CircuitState) {
+        let label = match to {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        };
+        self.transitions.add(1, &[KeyValue::new("breaker", self.name.clone()), KeyValue::new("state", label)]);
+
+        let mut states = states().lock().unwrap_or_else(|e| e.into_inner());
+        let since = match (from, to) {
+            (_, CircuitState::Closed) => None,
+            (CircuitState::Closed, _) => Some(Instant::now()),
+            _ => states.get(&self.name).and_then(|(_, since)| *since).or_else(|| Some(Instant::now())),
+        };
+        states.insert(self.name.clone(), (to, since));
+    }
+}
@@ -2,16 +2,49 @@
 //!
 //! This module implements the Circuit Breaker pattern to prevent cascading failures
 //! in distributed systems. When a service is failing, the circuit "opens" to prevent
-//! further calls and allow the service time to recover.
+//! further calls and allow the service time to recover. `fallback::CachedCall` serves the
+//! last good result of a read while its circuit is open. `failover::Failover` moves a
+//! shared connection to a DR region while the primary one is unhealthy.
+//! `registry::CircuitBreakerRegistry` keeps one breaker per downstream service by name,
+//! configured with `config::CircuitBreakerConfig` from code, config files or env vars, and
+//! `retry::RetryPolicy` retries calls with backoff, optionally through a breaker.
+//! `bulkhead::Bulkhead` caps concurrent calls to a dependency. `store::CircuitBreakerStore`
+//! shares open circuits between replicas, e.g. through Redis. `pipeline::ResiliencePipeline`
+//! layers timeout, retry, circuit breaker and bulkhead around a call in a fixed order.
 
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 use std::time::{Duration, Instant};
 use log::{info, warn, error};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio::sync::broadcast;
+
+use crate::common::{Clock, SystemClock};
+
+pub mod bulkhead;
+pub mod config;
+mod counters;
+pub mod failover;
+pub mod fallback;
+pub mod metrics;
+pub mod pipeline;
+pub mod registry;
+pub mod retry;
+pub mod store;
+
+pub use bulkhead::{Bulkhead, BulkheadFull, BulkheadPermit};
+pub use config::CircuitBreakerConfig;
+pub use failover::{Endpoint, Failover, FailoverError, FailoverEvent, FailoverKind, FailoverPolicy};
+pub use fallback::{CachedCall, Served};
+pub use pipeline::{PipelineError, ResiliencePipeline};
+pub use registry::CircuitBreakerRegistry;
+pub use retry::RetryPolicy;
+pub use store::{CircuitBreakerStore, InProcessCircuitBreakerStore, RedisCircuitBreakerStore};
 
 /// Represents the current state of the circuit breaker.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum CircuitState {
     /// Normal operation - requests are allowed through.
     Closed,
@@ -21,6 +54,35 @@ pub enum CircuitState {
     HalfOpen,
 }
 
+/// A transition of a circuit breaker, e.g. `Closed` to `Open` when it trips.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateChange {
+    pub from: CircuitState,
+    pub to: CircuitState,
+}
+
+/// Point-in-time view of a circuit breaker, for admin and diagnostics endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitBreakerStats {
+    pub state: CircuitState,
+    /// Set by `force_open` / `force_closed`.
+    pub forced: bool,
+    /// Failures since the last success (or since the circuit closed).
+    pub consecutive_failures: u32,
+    /// Successes towards closing, while HalfOpen.
+    pub half_open_successes: u32,
+    /// Calls since the breaker was built, by outcome. Slow calls count as failures; errors
+    /// `call_classified` ignores are not counted.
+    pub successes: u64,
+    pub failures: u64,
+    pub rejections: u64,
+    pub last_transition_at: Option<DateTime<Utc>>,
+    /// Seconds since the circuit left Closed, while it is Open or HalfOpen.
+    pub open_seconds: Option<f64>,
+}
+
+type StateListener = Box<dyn Fn(CircuitState, CircuitState) + Send + Sync>;
+
 /// Error types specific to the Circuit Breaker.
 #[derive(Debug, Error, Clone)]
 pub enum CircuitBreakerError {
@@ -58,6 +120,52 @@ impl<E: std::fmt::Display> std::fmt::Display for CircuitBreakerOutcome<E> {
 
 impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for CircuitBreakerOutcome<E> {}
 
+/// The calls a failure rate is measured over; `{"calls": 100}` or `{"time": "60s"}` in config.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollingWindow {
+    /// The last `n` calls.
+    Calls(usize),
+    /// Calls made within the last duration.
+    Time(#[serde(with = "crate::config::duration")] Duration),
+}
+
+/// Open the circuit when the share of failed calls in a rolling window reaches `threshold`.
+/// Deserializes with the fields of `FailureRate::default()` as defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureRate {
+    /// Failed share of calls, from 0.0 to 1.0, that opens the circuit.
+    pub threshold: f64,
+    pub window: RollingWindow,
+    /// Calls the window must hold before the rate is judged.
+    pub minimum_calls: usize,
+}
+
+impl FailureRate {
+    /// `threshold` of the last `calls` calls failed; judged once the window is full.
+    pub fn over_calls(threshold: f64, calls: usize) -> Self {
+        Self { threshold, window: RollingWindow::Calls(calls), minimum_calls: calls }
+    }
+
+    /// `threshold` of the calls in the last `window` failed; judged from 10 calls.
+    pub fn over_time(threshold: f64, window: Duration) -> Self {
+        Self { threshold, window: RollingWindow::Time(window), minimum_calls: 10 }
+    }
+
+    pub fn with_minimum_calls(mut self, minimum_calls: usize) -> Self {
+        self.minimum_calls = minimum_calls;
+        self
+    }
+}
+
+/// Half of the last 100 calls.
+impl Default for FailureRate {
+    fn default() -> Self {
+        Self::over_calls(0.5, 100)
+    }
+}
+
 /// A thread-safe Circuit Breaker implementation.
 ///
 /// # Example
@@ -74,14 +182,69 @@ impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for CircuitBreake
 ///     Err(CircuitBreakerOutcome::OperationError(e)) => println!("Call failed: {}", e),
 /// }
 /// ```
+///
+/// Hot-path bookkeeping is lock-free. The state and its two counters share one atomic word
+/// that only changes by compare-and-swap, so a transition and its counters are never seen
+/// apart, and call outcomes are counted per worker thread and aggregated (see `counters`).
+///
+/// By default the circuit opens after `failure_threshold` consecutive failures, which mixed
+/// traffic may never reach. `with_failure_rate` also opens it on a high failure share:
+///
+/// ```ignore
+/// // Open when half of the last 100 calls failed, or after 20 failures in a row
+/// let cb = CircuitBreaker::new(20, Duration::from_secs(30))
+///     .with_failure_rate(FailureRate::over_calls(0.5, 100));
+/// ```
+///
+/// A degraded dependency may answer slowly without failing; `with_slow_call_threshold`
+/// counts such calls as failures while still returning their result.
 pub struct CircuitBreaker {
-    state: Arc<Mutex<CircuitState>>,
+    /// State, consecutive failures and HalfOpen successes, combined by `pack`.
+    state: AtomicU64,
     failure_threshold: u32,
-    failure_count: Arc<Mutex<u32>>,
     success_threshold: u32,
-    success_count: Arc<Mutex<u32>>,
+    /// Calls allowed to run at once while HalfOpen; the others are rejected.
+    half_open_max_calls: u32,
+    half_open_calls: AtomicU32,
     reset_timeout: Duration,
-    last_failure_time: Arc<Mutex<Option<Instant>>>,
+    /// Successful calls taking at least this long count as failures.
+    slow_call_threshold: Option<Duration>,
+    /// When the circuit last opened, as a tick (0 = never).
+    last_failure_time: AtomicU64,
+    /// When the circuit last left Closed; kept while it flips between Open and HalfOpen.
+    opened_at: AtomicU64,
+    epoch: Instant,
+    clock: Arc<dyn Clock>,
+    failure_rate: Option<counters::FailureWindow>,
+    listeners: Vec<StateListener>,
+    changes: broadcast::Sender<StateChange>,
+    metrics: Option<metrics::BreakerMetrics>,
+    store: Option<store::SharedState>,
+    /// State set by `force_open` / `force_closed`, or `NOT_FORCED`.
+    forced: AtomicU8,
+    /// When a forced state ends, as a tick (0 = when released).
+    forced_until: AtomicU64,
+    calls: counters::CallCounts,
+    /// When the state last changed, as a tick (0 = never).
+    last_transition: AtomicU64,
+}
+
+const CLOSED: u8 = 0;
+const OPEN: u8 = 1;
+const HALF_OPEN: u8 = 2;
+/// Claimed for opening by one call, which sets the timestamps before publishing `OPEN`.
+/// Rejects calls like Open.
+const OPENING: u8 = 3;
+const NOT_FORCED: u8 = u8::MAX;
+
+/// The breaker's state word: the state in the top byte, HalfOpen successes in the next three
+/// and consecutive failures in the low half.
+fn pack(state: u8, failures: u32, successes: u32) -> u64 {
+    (state as u64) << 56 | (successes.min(0xFF_FFFF) as u64) << 32 | failures as u64
+}
+
+fn unpack(word: u64) -> (u8, u32, u32) {
+    ((word >> 56) as u8, word as u32, (word >> 32) as u32 & 0xFF_FFFF)
 }
 
 impl CircuitBreaker {
@@ -91,14 +254,28 @@ impl CircuitBreaker {
     /// * `failure_threshold` - Number of consecutive failures before opening the circuit.
     /// * `reset_timeout` - Duration to wait before transitioning from Open to HalfOpen.
     pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
         Self {
-            state: Arc::new(Mutex::new(CircuitState::Closed)),
+            state: AtomicU64::new(pack(CLOSED, 0, 0)),
             failure_threshold,
-            failure_count: Arc::new(Mutex::new(0)),
             success_threshold: 2, // Require 2 consecutive successes in HalfOpen to close
-            success_count: Arc::new(Mutex::new(0)),
+            half_open_max_calls: u32::MAX,
+            half_open_calls: AtomicU32::new(0),
             reset_timeout,
-            last_failure_time: Arc::new(Mutex::new(None)),
+            slow_call_threshold: None,
+            last_failure_time: AtomicU64::new(0),
+            opened_at: AtomicU64::new(0),
+            epoch: clock.now(),
+            clock,
+            failure_rate: None,
+            listeners: Vec::new(),
+            changes: broadcast::channel(16).0,
+            metrics: None,
+            store: None,
+            forced: AtomicU8::new(NOT_FORCED),
+            forced_until: AtomicU64::new(0),
+            calls: counters::CallCounts::default(),
+            last_transition: AtomicU64::new(0),
         }
     }
 
@@ -108,9 +285,261 @@ impl CircuitBreaker {
         self
     }
 
+    /// Let at most `max_calls` probe calls run at once while HalfOpen; concurrent callers
+    /// beyond that get `CircuitOpen`, sparing a recovering service a burst of traffic.
+    /// Unlimited by default.
+    pub fn with_half_open_max_calls(mut self, max_calls: u32) -> Self {
+        self.half_open_max_calls = max_calls;
+        self
+    }
+
+    /// Count successful calls that take at least `threshold` as failures towards opening the
+    /// circuit (and reopening it from HalfOpen). Callers still get their result.
+    pub fn with_slow_call_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_call_threshold = Some(threshold);
+        self
+    }
+
+    /// Call `listener` with the old and new state on every transition, from the task that
+    /// caused it; keep it quick (e.g. log, bump a metric or spawn a NATS publish).
+    pub fn on_state_change(mut self, listener: impl Fn(CircuitState, CircuitState) + Send + Sync + 'static) -> Self {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Transitions from now on, for alerting or dashboards. Slow receivers miss the
+    /// oldest changes rather than holding up calls.
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChange> {
+        self.changes.subscribe()
+    }
+
+    /// Export calls, transitions, state and time open under `breaker="{name}"` (see `metrics`).
+    pub fn with_metrics(mut self, name: &str) -> Self {
+        self.metrics = Some(metrics::BreakerMetrics::new(name));
+        self
+    }
+
+    fn record_call(&self, outcome: &'static str) {
+        match outcome {
+            "success" => self.calls.success(),
+            "failure" | "slow" => self.calls.failure(),
+            "rejected" => self.calls.rejection(),
+            _ => {}
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_call(outcome);
+        }
+    }
+
+    /// Share opening and closing with other breakers named `name` through `store`, e.g.
+    /// the same dependency's breaker on every replica (see `store`).
+    pub fn with_store(mut self, name: &str, store: Arc<dyn CircuitBreakerStore>) -> Self {
+        self.store = Some(store::SharedState { name: name.to_string(), store, last_sync: AtomicU64::new(0) });
+        self
+    }
+
+    /// Write a transition to the store in the background, so calls never wait on it.
+    fn share(&self, to: u8) {
+        let Some(shared) = &self.store else { return };
+        // A check right after closing could still see the old Open
+        shared.last_sync.store(self.now_tick(), Ordering::Release);
+        let (store, name, reset_timeout) = (shared.store.clone(), shared.name.clone(), self.reset_timeout);
+        tokio::spawn(async move {
+            match to {
+                OPEN => store.set_open(&name, reset_timeout).await,
+                _ => store.set_closed(&name).await,
+            }
+        });
+    }
+
+    /// While closed, open if another breaker sharing the store has; at most once per
+    /// `store::SYNC_INTERVAL`, by whichever call gets there first.
+    async fn sync_from_store(&self) {
+        let Some(shared) = &self.store else { return };
+        let now = self.now_tick();
+        let last = shared.last_sync.load(Ordering::Acquire);
+        if last != 0 && now.saturating_sub(last) < store::SYNC_INTERVAL.as_nanos() as u64 {
+            return;
+        }
+        if shared.last_sync.compare_exchange(last, now, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return;
+        }
+        if shared.store.is_open(&shared.name).await && self.trip_if(|state| state == CLOSED) {
+            self.clear_outcomes();
+            warn!("Circuit Breaker: '{}' was opened by another replica. Transitioning to Open.", shared.name);
+        }
+    }
+
+    /// Measures the reset timeout with `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.epoch = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Also open when the failure share over a rolling window reaches `rate.threshold`.
+    pub fn with_failure_rate(mut self, rate: FailureRate) -> Self {
+        self.failure_rate = Some(counters::FailureWindow::new(rate));
+        self
+    }
+
+    /// Record a Closed-state outcome; true if the failure rate now calls for opening.
+    fn record(&self, failed: bool) -> bool {
+        self.failure_rate.as_ref().is_some_and(|window| window.record(self.now_tick(), failed))
+    }
+
+    /// Forget the rolling window, so a closed circuit is judged on fresh calls only.
+    fn clear_outcomes(&self) {
+        if let Some(window) = &self.failure_rate {
+            window.clear();
+        }
+    }
+
+    /// `clock` nanoseconds since `epoch` plus one, so that 0 can stand for never.
+    fn now_tick(&self) -> u64 {
+        self.clock.now().saturating_duration_since(self.epoch).as_nanos() as u64 + 1
+    }
+
+    /// Time since a tick stored by `now_tick`, if any.
+    fn since(&self, tick: &AtomicU64) -> Option<Duration> {
+        match tick.load(Ordering::Acquire) {
+            0 => None,
+            tick => Some(Duration::from_nanos(self.now_tick().saturating_sub(tick))),
+        }
+    }
+
+    /// Take one of the `half_open_max_calls` probe slots, released when the permit drops.
+    fn acquire_probe(&self) -> Option<ProbePermit<'_>> {
+        self.half_open_calls
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |calls| {
+                (calls < self.half_open_max_calls).then_some(calls + 1)
+            })
+            .ok()
+            .map(|_| ProbePermit(&self.half_open_calls))
+    }
+
+    fn notify(&self, from: u8, to: u8) {
+        if from == to {
+            return;
+        }
+        self.last_transition.store(self.now_tick(), Ordering::Release);
+        let change = StateChange { from: to_state(from), to: to_state(to) };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_transition(change.from, change.to);
+        }
+        for listener in &self.listeners {
+            listener(change.from, change.to);
+        }
+        let _ = self.changes.send(change);
+    }
+
+    /// Finish an opening claimed by moving the state word from `from` to `OPENING`. The
+    /// timestamps are stored once the claim has won and before Open is published, so a call
+    /// that sees Open never judges the reset timeout by an older opening.
+    fn finish_opening(&self, from: u8, failures: u32) {
+        let now = self.now_tick();
+        self.last_failure_time.store(now, Ordering::Release);
+        if from == CLOSED {
+            self.opened_at.store(now, Ordering::Release);
+        }
+        // Fails only if a reset closed the circuit in between, which then stands
+        let published = self
+            .state
+            .compare_exchange(pack(OPENING, failures, 0), pack(OPEN, failures, 0), Ordering::AcqRel, Ordering::Acquire);
+        if published.is_ok() {
+            self.notify(from, OPEN);
+        }
+    }
+
+    /// Open the circuit (or restart the reset timeout of an open one) if its state passes
+    /// `from`; false if it did not, or if another call is opening it.
+    fn trip_if(&self, from: impl Fn(u8) -> bool) -> bool {
+        let previous = self.state.fetch_update(Ordering::AcqRel, Ordering::Acquire, |word| {
+            let (state, failures, _) = unpack(word);
+            (state != OPENING && from(state)).then_some(pack(OPENING, failures, 0))
+        });
+        match previous.map(unpack) {
+            Ok((state, failures, _)) => {
+                self.finish_opening(state, failures);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// `trip_if`, shared with the store.
+    fn open_if(&self, from: impl Fn(u8) -> bool) -> bool {
+        let opened = self.trip_if(from);
+        if opened {
+            self.share(OPEN);
+        }
+        opened
+    }
+
+    /// The forced state, if any; a `force_open` that ran out is cleared here.
+    fn forced(&self) -> Option<u8> {
+        let forced = self.forced.load(Ordering::Acquire);
+        if forced == NOT_FORCED {
+            return None;
+        }
+        let until = self.forced_until.load(Ordering::Acquire);
+        if until != 0 && self.now_tick() >= until {
+            if self.forced.compare_exchange(forced, NOT_FORCED, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                info!("Circuit Breaker: Forced state expired. Resuming normal operation.");
+            }
+            return None;
+        }
+        Some(forced)
+    }
+
+    /// Close the circuit and forget its failures.
+    fn close(&self) {
+        let (previous, ..) = unpack(self.state.swap(pack(CLOSED, 0, 0), Ordering::AcqRel));
+        self.clear_outcomes();
+        self.notify(previous, CLOSED);
+    }
+
     /// Returns the current state of the circuit breaker.
     pub async fn state(&self) -> CircuitState {
-        *self.state.lock().await
+        to_state(unpack(self.state.load(Ordering::Acquire)).0)
+    }
+
+    /// Whether the state was set by `force_open` or `force_closed` and still holds.
+    pub async fn is_forced(&self) -> bool {
+        self.forced().is_some()
+    }
+
+    /// Snapshot of state and call counts.
+    pub async fn stats(&self) -> CircuitBreakerStats {
+        let (state, failures, successes) = unpack(self.state.load(Ordering::Acquire));
+        let (calls_succeeded, calls_failed, rejections) = self.calls.totals();
+        CircuitBreakerStats {
+            state: to_state(state),
+            forced: self.forced().is_some(),
+            consecutive_failures: failures,
+            half_open_successes: successes,
+            successes: calls_succeeded,
+            failures: calls_failed,
+            rejections,
+            last_transition_at: self
+                .since(&self.last_transition)
+                .and_then(|elapsed| chrono::Duration::from_std(elapsed).ok())
+                .map(|elapsed| Utc::now() - elapsed),
+            open_seconds: self.open_since(state).map(|open| open.as_secs_f64()),
+        }
+    }
+
+    /// How long the circuit has been out of Closed (Open or HalfOpen), or `None` while closed.
+    pub async fn open_for(&self) -> Option<Duration> {
+        self.open_since(unpack(self.state.load(Ordering::Acquire)).0)
+    }
+
+    /// Time out of Closed, for a circuit in `state`.
+    fn open_since(&self, state: u8) -> Option<Duration> {
+        match state {
+            CLOSED => None,
+            _ => self.since(&self.opened_at),
+        }
     }
 
     /// Executes an async operation through the circuit breaker.
@@ -123,95 +552,283 @@ impl CircuitBreaker {
         Fut: std::future::Future<Output = Result<T, E>>,
         E: std::fmt::Display,
     {
-        // Check if circuit should transition from Open to HalfOpen
-        {
-            let mut state = self.state.lock().await;
-            if *state == CircuitState::Open {
-                let last_failure = self.last_failure_time.lock().await;
-                if let Some(instant) = *last_failure {
-                    if instant.elapsed() >= self.reset_timeout {
-                        *state = CircuitState::HalfOpen;
-                        // Reset success count for HalfOpen testing
-                        let mut success_count = self.success_count.lock().await;
-                        *success_count = 0;
-                        warn!("Circuit Breaker: Reset timeout elapsed. State transitioning to HalfOpen.");
-                    } else {
-                        error!("Circuit Breaker: Operation rejected. State is Open. Retry in {:?}", 
-                               self.reset_timeout - instant.elapsed());
-                        return Err(CircuitBreakerOutcome::CircuitOpen);
+        self.call_classified(f, |_| true).await
+    }
+
+    /// `call` where only errors matching `record_failure_if` count against the circuit, so
+    /// business errors (validation, not found) pass through without tripping it:
+    ///
+    /// ```ignore
+    /// cb.call_classified(|| client.get_product(id), |e: &HttpClientError| e.is_server_error()).await
+    /// ```
+    ///
+    /// Other errors are returned as `OperationError` but change nothing: they neither count
+    /// as failures nor as the successes that close a HalfOpen circuit.
+    pub async fn call_classified<F, Fut, T, E, P>(&self, f: F, record_failure_if: P) -> CircuitBreakerResult<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+        P: Fn(&E) -> bool,
+    {
+        match self.forced() {
+            Some(OPEN) => {
+                warn!("Circuit Breaker: Operation rejected. State is forced Open.");
+                self.record_call("rejected");
+                return Err(CircuitBreakerOutcome::CircuitOpen);
+            }
+            // Outcomes of a forced closed circuit are not counted
+            Some(_) => {
+                return match f().await {
+                    Ok(res) => {
+                        self.record_call("success");
+                        Ok(res)
                     }
-                }
+                    Err(e) => {
+                        self.record_call("ignored");
+                        Err(CircuitBreakerOutcome::OperationError(e))
+                    }
+                };
+            }
+            None => {}
+        }
+
+        if unpack(self.state.load(Ordering::Acquire)).0 == CLOSED {
+            self.sync_from_store().await;
+        }
+
+        // Check if circuit should transition from Open to HalfOpen
+        let word = self.state.load(Ordering::Acquire);
+        if let (state @ (OPEN | OPENING), failures, _) = unpack(word) {
+            // An opening still in progress counts as just opened
+            let elapsed = match state {
+                OPEN => self.since(&self.last_failure_time).unwrap_or_default(),
+                _ => Duration::ZERO,
+            };
+            if state == OPENING || elapsed < self.reset_timeout {
+                error!("Circuit Breaker: Operation rejected. State is Open. Retry in {:?}",
+                       self.reset_timeout - elapsed);
+                self.record_call("rejected");
+                return Err(CircuitBreakerOutcome::CircuitOpen);
+            }
+            // Only one caller performs the transition; HalfOpen starts without successes
+            let half_open = pack(HALF_OPEN, failures, 0);
+            if self.state.compare_exchange(word, half_open, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                warn!("Circuit Breaker: Reset timeout elapsed. State transitioning to HalfOpen.");
+                self.notify(OPEN, HALF_OPEN);
             }
         }
 
+        // Only a limited number of probes may run while HalfOpen
+        let _probe = match unpack(self.state.load(Ordering::Acquire)).0 {
+            HALF_OPEN => match self.acquire_probe() {
+                Some(permit) => Some(permit),
+                None => {
+                    warn!("Circuit Breaker: Operation rejected. {} probe call(s) already running in HalfOpen.",
+                          self.half_open_max_calls);
+                    self.record_call("rejected");
+                    return Err(CircuitBreakerOutcome::CircuitOpen);
+                }
+            },
+            _ => None,
+        };
+
         // Execute the operation
-        match f().await {
+        let started = self.clock.now();
+        let result = f().await;
+        let elapsed = self.clock.now().saturating_duration_since(started);
+        match result {
+            Ok(res) if self.slow_call_threshold.is_some_and(|threshold| elapsed >= threshold) => {
+                self.count_failure(&format_args!("Slow call took {:?}", elapsed));
+                self.record_call("slow");
+                Ok(res)
+            }
             Ok(res) => {
-                let mut state = self.state.lock().await;
-                
-                if *state == CircuitState::HalfOpen {
-                    let mut success_count = self.success_count.lock().await;
-                    *success_count += 1;
-                    
-                    if *success_count >= self.success_threshold {
-                        info!("Circuit Breaker: {} consecutive successes in HalfOpen. Transitioning to Closed.", 
-                              self.success_threshold);
-                        *state = CircuitState::Closed;
-                        let mut failures = self.failure_count.lock().await;
-                        *failures = 0;
-                        *success_count = 0;
-                    } else {
-                        info!("Circuit Breaker: Success in HalfOpen ({}/{})", 
-                              *success_count, self.success_threshold);
-                    }
-                } else if *state == CircuitState::Closed {
-                    // Reset failure count on success in Closed state
-                    let mut failures = self.failure_count.lock().await;
-                    *failures = 0;
-                }
-                
+                self.count_success();
+                self.record_call("success");
                 Ok(res)
             }
+            Err(e) if !record_failure_if(&e) => {
+                self.record_call("ignored");
+                Err(CircuitBreakerOutcome::OperationError(e))
+            }
             Err(e) => {
-                let mut failures = self.failure_count.lock().await;
-                *failures += 1;
+                self.count_failure(&e);
+                self.record_call("failure");
+                Err(CircuitBreakerOutcome::OperationError(e))
+            }
+        }
+    }
+
+    /// Count a successful call, closing a HalfOpen circuit once it has enough of them.
+    fn count_success(&self) {
+        let previous = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |word| match unpack(word) {
+                (HALF_OPEN, _, successes) if successes + 1 >= self.success_threshold => Some(pack(CLOSED, 0, 0)),
+                (HALF_OPEN, failures, successes) => Some(pack(HALF_OPEN, failures, successes + 1)),
+                // Reset failure count on success in Closed state; a clean word is left alone
+                (CLOSED, failures, _) if failures > 0 => Some(pack(CLOSED, 0, 0)),
+                _ => None,
+            })
+            .unwrap_or_else(|word| word);
+
+        match unpack(previous) {
+            (HALF_OPEN, _, successes) if successes + 1 >= self.success_threshold => {
+                info!("Circuit Breaker: {} consecutive successes in HalfOpen. Transitioning to Closed.",
+                      self.success_threshold);
+                self.clear_outcomes();
+                self.notify(HALF_OPEN, CLOSED);
+                self.share(CLOSED);
+            }
+            (HALF_OPEN, _, successes) => {
+                info!("Circuit Breaker: Success in HalfOpen ({}/{})", successes + 1, self.success_threshold);
+            }
+            (CLOSED, ..) => {
+                // Successes only lower the rate, so they never open the circuit
+                self.record(false);
+            }
+            _ => {}
+        }
+    }
 
-                let mut state = self.state.lock().await;
-                
+    /// Count a failed (or slow) call, opening the circuit if it calls for it.
+    fn count_failure(&self, e: &dyn std::fmt::Display) {
+        // Only the state word changes here; `finish_opening` sets the timestamps once the
+        // swap has won, as this closure may run more than once
+        let previous = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |word| match unpack(word) {
                 // In HalfOpen, any failure immediately opens the circuit
-                if *state == CircuitState::HalfOpen {
-                    *state = CircuitState::Open;
-                    let mut last_failure = self.last_failure_time.lock().await;
-                    *last_failure = Some(Instant::now());
-                    error!("Circuit Breaker: Failure in HalfOpen. Reopening circuit. Error: {}", e);
-                } else if *failures >= self.failure_threshold {
-                    *state = CircuitState::Open;
-                    let mut last_failure = self.last_failure_time.lock().await;
-                    *last_failure = Some(Instant::now());
-                    error!("Circuit Breaker: Failure threshold reached ({}). Transitioning to Open. Error: {}", 
-                           self.failure_threshold, e);
+                (HALF_OPEN, failures, _) => Some(pack(OPENING, failures, 0)),
+                (CLOSED, failures, _) if failures.saturating_add(1) >= self.failure_threshold => {
+                    Some(pack(OPENING, failures.saturating_add(1), 0))
                 }
-                
-                Err(CircuitBreakerOutcome::OperationError(e))
+                (CLOSED, failures, _) => Some(pack(CLOSED, failures.saturating_add(1), 0)),
+                _ => None,
+            })
+            .unwrap_or_else(|word| word);
+
+        match unpack(previous) {
+            (HALF_OPEN, failures, _) => {
+                self.finish_opening(HALF_OPEN, failures);
+                self.share(OPEN);
+                error!("Circuit Breaker: Failure in HalfOpen. Reopening circuit. Error: {}", e);
+            }
+            (CLOSED, failures, _) if failures.saturating_add(1) >= self.failure_threshold => {
+                self.finish_opening(CLOSED, failures.saturating_add(1));
+                self.share(OPEN);
+                self.clear_outcomes();
+                error!("Circuit Breaker: Failure threshold reached ({}). Transitioning to Open. Error: {}",
+                       self.failure_threshold, e);
+            }
+            (CLOSED, ..) if self.record(true) && self.open_if(|state| state == CLOSED) => {
+                self.clear_outcomes();
+                error!("Circuit Breaker: Failure rate threshold reached. Transitioning to Open. Error: {}", e);
+            }
+            _ => {}
+        }
+    }
+
+    /// `call`, answering with `fallback` when the circuit is open or `f` fails. The fallback
+    /// gets the reason, e.g. to log it or to only degrade on `CircuitOpen`:
+    ///
+    /// ```ignore
+    /// let recommendations = cb
+    ///     .call_with_fallback(|| client.recommendations(user_id), |_| async { Vec::new() })
+    ///     .await;
+    /// ```
+    ///
+    /// The fallback's outcome does not affect the circuit. To serve the last good result of a
+    /// read instead, see `fallback::CachedCall`.
+    pub async fn call_with_fallback<F, Fut, T, E, G, GFut>(&self, f: F, fallback: G) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+        G: FnOnce(CircuitBreakerOutcome<E>) -> GFut,
+        GFut: std::future::Future<Output = T>,
+    {
+        match self.call(f).await {
+            Ok(value) => value,
+            Err(outcome) => {
+                warn!("Circuit Breaker: Serving fallback. {}", outcome);
+                fallback(outcome).await
             }
         }
     }
 
-    /// Manually reset the circuit breaker to Closed state.
+    /// `call` with `f` cut off after `timeout`. A timeout counts as a failure and comes back as
+    /// `CircuitBreakerError::Timeout`; an open circuit as `Open` and an error of `f` as
+    /// `OperationFailed`.
+    pub async fn call_with_timeout<F, Fut, T, E>(&self, timeout: Duration, f: F) -> Result<T, CircuitBreakerError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let result = self
+            .call(|| async move {
+                match tokio::time::timeout(timeout, f()).await {
+                    Ok(result) => result.map_err(|e| CircuitBreakerError::OperationFailed(e.to_string())),
+                    Err(_) => Err(CircuitBreakerError::Timeout),
+                }
+            })
+            .await;
+        result.map_err(|outcome| match outcome {
+            CircuitBreakerOutcome::CircuitOpen => CircuitBreakerError::Open,
+            CircuitBreakerOutcome::OperationError(e) => e,
+        })
+    }
+
+    /// Reject every call for `duration`, e.g. to isolate a dependency during an incident.
+    /// Afterwards the circuit is Open as if it had just tripped, so it probes before closing.
+    pub async fn force_open(&self, duration: Duration) {
+        self.forced_until.store(self.now_tick() + duration.as_nanos() as u64, Ordering::Release);
+        self.forced.store(OPEN, Ordering::Release);
+        self.trip_if(|_| true);
+        warn!("Circuit Breaker: Forced Open for {:?}.", duration);
+    }
+
+    /// Let every call through without counting its outcome, e.g. to override a flapping
+    /// breaker, until `reset`.
+    pub async fn force_closed(&self) {
+        self.forced_until.store(0, Ordering::Release);
+        self.forced.store(CLOSED, Ordering::Release);
+        self.close();
+        warn!("Circuit Breaker: Forced Closed until reset.");
+    }
+
+    /// Manually reset the circuit breaker to Closed state, ending any forced state.
     pub async fn reset(&self) {
-        let mut state = self.state.lock().await;
-        *state = CircuitState::Closed;
-        let mut failures = self.failure_count.lock().await;
-        *failures = 0;
-        let mut successes = self.success_count.lock().await;
-        *successes = 0;
+        self.forced.store(NOT_FORCED, Ordering::Release);
+        self.close();
+        self.share(CLOSED);
         info!("Circuit Breaker: Manually reset to Closed state.");
     }
 }
 
+fn to_state(state: u8) -> CircuitState {
+    match state {
+        OPEN | OPENING => CircuitState::Open,
+        HALF_OPEN => CircuitState::HalfOpen,
+        _ => CircuitState::Closed,
+    }
+}
+
+/// A running HalfOpen probe call; frees its slot when dropped, even if the call is cancelled.
+struct ProbePermit<'a>(&'a AtomicU32);
+
+impl Drop for ProbePermit<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex;
 
     #[tokio::test]
     async fn test_circuit_breaker_stays_closed_on_success() {
@@ -247,4 +864,201 @@ mod tests {
         let result: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(42) }).await;
         assert!(matches!(result, Err(CircuitBreakerOutcome::CircuitOpen)));
     }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_on_failure_rate() {
+        let cb = CircuitBreaker::new(10, Duration::from_secs(60))
+            .with_failure_rate(FailureRate::over_calls(0.5, 4));
+
+        // Alternating outcomes never reach 10 consecutive failures
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(1) }).await;
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Err("fail") }).await;
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(1) }).await;
+        assert_eq!(cb.state().await, CircuitState::Closed);
+
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Err("fail") }).await;
+        assert_eq!(cb.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_limits_half_open_probes() {
+        let cb = CircuitBreaker::new(1, Duration::from_millis(10)).with_half_open_max_calls(1);
+        let _: CircuitBreakerResult<(), &str> = cb.call(|| async { Err("fail") }).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The first probe holds the only slot until the second call has been rejected
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let probe = cb.call(move || async move { rx.await.map_err(|_| "dropped") });
+        let second = async {
+            let result: CircuitBreakerResult<(), &str> = cb.call(|| async { Ok(()) }).await;
+            tx.send(()).unwrap();
+            result
+        };
+        let (probe, second) = tokio::join!(probe, second);
+
+        assert!(probe.is_ok());
+        assert!(matches!(second, Err(CircuitBreakerOutcome::CircuitOpen)));
+        assert_eq!(cb.state().await, CircuitState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_reports_state_changes() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        let cb = CircuitBreaker::new(1, Duration::from_secs(60))
+            .on_state_change(move |from, to| recorded.lock().unwrap().push((from, to)));
+        let mut changes = cb.subscribe();
+
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Err("fail") }).await;
+        cb.reset().await;
+
+        assert_eq!(changes.recv().await.unwrap(), StateChange { from: CircuitState::Closed, to: CircuitState::Open });
+        assert_eq!(changes.recv().await.unwrap(), StateChange { from: CircuitState::Open, to: CircuitState::Closed });
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (CircuitState::Closed, CircuitState::Open),
+            (CircuitState::Open, CircuitState::Closed),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_times_out_calls() {
+        let cb = CircuitBreaker::new(1, Duration::from_secs(60));
+
+        let result = cb
+            .call_with_timeout(Duration::from_millis(10), || async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok::<_, &str>(42)
+            })
+            .await;
+        assert!(matches!(result, Err(CircuitBreakerError::Timeout)));
+        assert_eq!(cb.state().await, CircuitState::Open);
+
+        let result = cb.call_with_timeout(Duration::from_millis(10), || async { Ok::<_, &str>(42) }).await;
+        assert!(matches!(result, Err(CircuitBreakerError::Open)));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_ignores_unclassified_errors() {
+        let cb = CircuitBreaker::new(1, Duration::from_secs(60));
+        let is_server_error = |status: &u16| *status >= 500;
+
+        let result: CircuitBreakerResult<(), u16> = cb.call_classified(|| async { Err(404) }, is_server_error).await;
+        assert!(matches!(result, Err(CircuitBreakerOutcome::OperationError(404))));
+        assert_eq!(cb.state().await, CircuitState::Closed);
+
+        let _: CircuitBreakerResult<(), u16> = cb.call_classified(|| async { Err(503) }, is_server_error).await;
+        assert_eq!(cb.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_circuit_breaker_opens_once_under_concurrent_failures() {
+        let cb = Arc::new(CircuitBreaker::new(5, Duration::from_secs(60)));
+        let mut changes = cb.subscribe();
+
+        let calls: Vec<_> = (0..64)
+            .map(|_| {
+                let cb = cb.clone();
+                tokio::spawn(async move {
+                    let _: CircuitBreakerResult<(), &str> = cb.call(|| async { Err("fail") }).await;
+                })
+            })
+            .collect();
+        for call in calls {
+            call.await.unwrap();
+        }
+
+        assert_eq!(cb.state().await, CircuitState::Open);
+        assert!(cb.open_for().await.is_some());
+        assert_eq!(changes.recv().await.unwrap(), StateChange { from: CircuitState::Closed, to: CircuitState::Open });
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_adopts_shared_open_state() {
+        let store = Arc::new(InProcessCircuitBreakerStore::default());
+        let replica_a = CircuitBreaker::new(1, Duration::from_secs(60)).with_store("payments", store.clone());
+        let replica_b = CircuitBreaker::new(1, Duration::from_secs(60)).with_store("payments", store.clone());
+
+        let _: CircuitBreakerResult<i32, &str> = replica_a.call(|| async { Err("fail") }).await;
+        while !store.is_open("payments").await {
+            tokio::task::yield_now().await;
+        }
+
+        let result: CircuitBreakerResult<i32, &str> = replica_b.call(|| async { Ok(42) }).await;
+        assert!(matches!(result, Err(CircuitBreakerOutcome::CircuitOpen)));
+
+        replica_a.reset().await;
+        while store.is_open("payments").await {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_can_be_forced() {
+        let cb = CircuitBreaker::new(1, Duration::from_millis(10));
+
+        cb.force_open(Duration::from_millis(50)).await;
+        assert!(cb.is_forced().await);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // Past the reset timeout, but still forced
+        let result: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(42) }).await;
+        assert!(matches!(result, Err(CircuitBreakerOutcome::CircuitOpen)));
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(!cb.is_forced().await);
+        let result: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(42) }).await;
+        assert!(result.is_ok());
+
+        cb.force_closed().await;
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Err("fail") }).await;
+        assert_eq!(cb.state().await, CircuitState::Closed);
+        cb.reset().await;
+        assert!(!cb.is_forced().await);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_counts_slow_calls_as_failures() {
+        let cb = CircuitBreaker::new(2, Duration::from_secs(60)).with_slow_call_threshold(Duration::from_millis(10));
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, &str>(42)
+        };
+
+        assert_eq!(cb.call(slow).await.unwrap(), 42);
+        assert_eq!(cb.state().await, CircuitState::Closed);
+        assert_eq!(cb.call(slow).await.unwrap(), 42);
+        assert_eq!(cb.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_stats() {
+        let cb = CircuitBreaker::new(1, Duration::from_secs(60));
+        assert!(cb.stats().await.last_transition_at.is_none());
+
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(1) }).await;
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Err("fail") }).await;
+        let _: CircuitBreakerResult<i32, &str> = cb.call(|| async { Ok(1) }).await;
+
+        let stats = cb.stats().await;
+        assert_eq!(stats.state, CircuitState::Open);
+        assert_eq!((stats.successes, stats.failures, stats.rejections), (1, 1, 1));
+        assert!(stats.last_transition_at.is_some());
+        assert_eq!(serde_json::to_value(&stats).unwrap()["state"], "Open");
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_serves_fallback() {
+        let cb = CircuitBreaker::new(1, Duration::from_secs(60));
+
+        let value = cb.call_with_fallback(|| async { Err::<i32, _>("fail") }, |_| async { 0 }).await;
+        assert_eq!(value, 0);
+
+        let value = cb
+            .call_with_fallback(|| async { Ok::<_, &str>(42) }, |outcome| async move {
+                assert!(matches!(outcome, CircuitBreakerOutcome::CircuitOpen));
+                -1
+            })
+            .await;
+        assert_eq!(value, -1);
+    }
 }
@@ -0,0 +1,280 @@
+//! Composed resilience policies
+//!
+//! A `ResiliencePipeline` wraps one operation in the policies it was given, always layered in
+//! the same order, outermost first:
+//!
+//! 1. timeout: a deadline for the whole call, retries and backoff included
+//! 2. retry: further attempts for operation errors the `RetryPolicy` accepts
+//! 3. circuit breaker: every attempt goes through it; only operation errors count as failures
+//! 4. bulkhead: every attempt takes a slot for as long as the operation runs
+//! 5. chaos: while fault injection is enabled (see `chaos`), faults injected into the
+//!    operation count against the circuit and are retried like operation errors
+//!
+//! ```ignore
+//! let inventory = ResiliencePipeline::new("inventory")
+//!     .with_timeout(Duration::from_secs(3))
+//!     .with_retry(RetryPolicy::exponential(3, Duration::from_millis(100)))
+//!     .with_breaker(breakers.get_or_create("inventory"))
+//!     .with_bulkhead(Bulkhead::new("inventory", 16));
+//!
+//! let stock = inventory.execute(|| client.get_stock(sku)).await?;
+//! ```
+//!
+//! Each layer runs in its own span (`resilience_pipeline`, `retry_attempt`,
+//! `circuit_breaker`, `bulkhead`). The pipeline exports `resilience_pipeline_calls_total` by
+//! `outcome` and `resilience_pipeline_retries_total`, labelled `pipeline`; breakers add their
+//! own metrics when built `with_metrics`.
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::Instrument;
+
+use super::{Bulkhead, BulkheadFull, CircuitBreaker, CircuitBreakerOutcome, RetryPolicy};
+use crate::chaos::{Chaos, ChaosTarget, Fault};
+
+/// Why a pipeline call failed.
+#[derive(Debug, Error)]
+pub enum PipelineError<E> {
+    #[error("Operation timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Circuit breaker is open")]
+    CircuitOpen,
+
+    #[error("{0}")]
+    BulkheadFull(BulkheadFull),
+
+    #[error("Operation failed: {0}")]
+    Operation(E),
+
+    #[error("Chaos: injected {0:?}")]
+    Injected(Fault),
+}
+
+/// Timeout, retry, circuit breaker and bulkhead around an operation; each is optional.
+pub struct ResiliencePipeline<E> {
+    name: String,
+    timeout: Option<Duration>,
+    retry: RetryPolicy<E>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    bulkhead: Option<Bulkhead>,
+    chaos: Option<Chaos>,
+    calls: Counter<u64>,
+    retries: Counter<u64>,
+}
+
+impl<E: std::fmt::Display> ResiliencePipeline<E> {
+    /// A pipeline without policies; `name` labels its spans and metrics and selects its
+    /// chaos rates. Faults are injected by `Chaos::global()` when chaos is enabled.
+    pub fn new(name: &str) -> Self {
+        let meter = global::meter("lanai.resilience");
+        Self {
+            name: name.to_string(),
+            timeout: None,
+            retry: RetryPolicy::none(),
+            breaker: None,
+            bulkhead: None,
+            chaos: Chaos::global().cloned(),
+            calls: meter
+                .u64_counter("resilience_pipeline_calls_total")
+                .with_description("Calls through a resilience pipeline, by outcome")
+                .build(),
+            retries: meter
+                .u64_counter("resilience_pipeline_retries_total")
+                .with_description("Attempts a resilience pipeline made after the first")
+                .build(),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy<E>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    pub fn with_bulkhead(mut self, bulkhead: Bulkhead) -> Self {
+        self.bulkhead = Some(bulkhead);
+        self
+    }
+
+    /// Inject faults with `chaos` instead of the global injector.
+    pub fn with_chaos(mut self, chaos: Chaos) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run `operation` through every configured layer.
+    pub async fn execute<F, Fut, T>(&self, mut operation: F) -> Result<T, PipelineError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let span = tracing::info_span!("resilience_pipeline", pipeline = %self.name);
+        let result = async {
+            match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, self.attempts(&mut operation))
+                    .await
+                    .unwrap_or(Err(PipelineError::Timeout(timeout))),
+                None => self.attempts(&mut operation).await,
+            }
+        }
+        .instrument(span)
+        .await;
+
+        let outcome = match &result {
+            Ok(_) => "success",
+            Err(PipelineError::Timeout(_)) => "timeout",
+            Err(PipelineError::CircuitOpen) => "circuit_open",
+            Err(PipelineError::BulkheadFull(_)) => "bulkhead_full",
+            Err(PipelineError::Operation(_)) => "failure",
+            Err(PipelineError::Injected(_)) => "injected",
+        };
+        self.calls.add(1, &[KeyValue::new("pipeline", self.name.clone()), KeyValue::new("outcome", outcome)]);
+        result
+    }
+
+    /// The retry layer; circuit and bulkhead rejections are not retried.
+    async fn attempts<F, Fut, T>(&self, operation: &mut F) -> Result<T, PipelineError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match self.attempt(operation).instrument(self.retry.attempt_span(attempt)).await {
+                Err(PipelineError::Operation(e)) if self.retry.should_retry(&e, attempt) => {
+                    self.retry.wait(&e, attempt).await;
+                    self.retries.add(1, &[KeyValue::new("pipeline", self.name.clone())]);
+                }
+                Err(PipelineError::Injected(_)) if attempt < self.retry.max_attempts => {
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    self.retries.add(1, &[KeyValue::new("pipeline", self.name.clone())]);
+                }
+                result => return result,
+            }
+            attempt += 1;
+        }
+    }
+
+    /// The circuit breaker layer.
+    async fn attempt<F, Fut, T>(&self, operation: &mut F) -> Result<T, PipelineError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let Some(breaker) = &self.breaker else {
+            return self.guarded(operation).await;
+        };
+        breaker
+            .call_classified(|| self.guarded(operation), |e| {
+                matches!(e, PipelineError::Operation(_) | PipelineError::Injected(_))
+            })
+            .instrument(tracing::info_span!("circuit_breaker"))
+            .await
+            .map_err(|outcome| match outcome {
+                CircuitBreakerOutcome::CircuitOpen => PipelineError::CircuitOpen,
+                CircuitBreakerOutcome::OperationError(e) => e,
+            })
+    }
+
+    /// The bulkhead and chaos layers, around the operation itself.
+    async fn guarded<F, Fut, T>(&self, operation: &mut F) -> Result<T, PipelineError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let _permit = match &self.bulkhead {
+            Some(bulkhead) => Some(
+                bulkhead
+                    .acquire()
+                    .instrument(tracing::info_span!("bulkhead", bulkhead = %bulkhead.name()))
+                    .await
+                    .map_err(PipelineError::BulkheadFull)?,
+            ),
+            None => None,
+        };
+        if let Some(chaos) = &self.chaos {
+            match chaos.inject(ChaosTarget::Outbound, &self.name).await {
+                Some(Fault::Error) => return Err(PipelineError::Injected(Fault::Error)),
+                Some(Fault::Drop) => {
+                    let _ = operation().await;
+                    return Err(PipelineError::Injected(Fault::Drop));
+                }
+                None => {}
+            }
+        }
+        operation().await.map_err(PipelineError::Operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resilience::CircuitState;
+
+    #[tokio::test]
+    async fn test_pipeline_retries_through_breaker() {
+        let breaker = Arc::new(CircuitBreaker::new(2, Duration::from_secs(60)));
+        let pipeline = ResiliencePipeline::<&str>::new("inventory")
+            .with_retry(RetryPolicy::exponential(5, Duration::from_millis(1)))
+            .with_breaker(breaker.clone())
+            .with_bulkhead(Bulkhead::new("inventory", 1));
+        let mut calls = 0;
+
+        let result: Result<(), _> = pipeline
+            .execute(|| {
+                calls += 1;
+                async { Err("down") }
+            })
+            .await;
+
+        assert!(matches!(result, Err(PipelineError::CircuitOpen)));
+        assert_eq!(calls, 2);
+        assert_eq!(breaker.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_injected_faults_trip_the_breaker() {
+        use crate::chaos::{ChaosConfig, OperationRates};
+
+        let chaos = Chaos::forced(
+            ChaosConfig::new().operation("payments", OperationRates { error_rate: Some(1.0), ..Default::default() }),
+        );
+        let breaker = Arc::new(CircuitBreaker::new(2, Duration::from_secs(60)));
+        let pipeline = ResiliencePipeline::<&str>::new("payments").with_breaker(breaker.clone()).with_chaos(chaos);
+
+        for _ in 0..2 {
+            let result: Result<i32, _> = pipeline.execute(|| async { Ok(42) }).await;
+            assert!(matches!(result, Err(PipelineError::Injected(Fault::Error))));
+        }
+        assert_eq!(breaker.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_times_out_across_retries() {
+        let pipeline = ResiliencePipeline::<&str>::new("reports")
+            .with_timeout(Duration::from_millis(20))
+            .with_retry(RetryPolicy::exponential(100, Duration::from_millis(5)));
+
+        let result: Result<(), _> = pipeline.execute(|| async { Err("slow") }).await;
+
+        assert!(matches!(result, Err(PipelineError::Timeout(_))));
+    }
+}
@@ -0,0 +1,153 @@
+//! Named circuit breakers
+//!
+//! One breaker per downstream dependency, created on first use from shared defaults and
+//! instrumented under its name (see `metrics`):
+//!
+//! ```ignore
+//! let breakers = CircuitBreakerRegistry::new()
+//!     .with_defaults(CircuitBreakerConfig { failure_threshold: 3, ..Default::default() });
+//!
+//! let result = breakers.get_or_create("payments").call(|| payments.charge(&order)).await;
+//!
+//! for (name, state) in breakers.states().await {
+//!     info!("{}: {:?}", name, state);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use super::{CircuitBreaker, CircuitBreakerConfig, CircuitBreakerStore, CircuitState};
+use crate::common::{Clock, SystemClock};
+
+/// Circuit breakers by name; clones share the breakers.
+#[derive(Clone)]
+pub struct CircuitBreakerRegistry {
+    breakers: Arc<RwLock<BTreeMap<String, Arc<CircuitBreaker>>>>,
+    defaults: CircuitBreakerConfig,
+    clock: Arc<dyn Clock>,
+    store: Option<Arc<dyn CircuitBreakerStore>>,
+}
+
+impl Default for CircuitBreakerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreakerRegistry {
+    /// Breakers open after 5 failures and retry after 30 seconds.
+    pub fn new() -> Self {
+        Self {
+            breakers: Arc::new(RwLock::new(BTreeMap::new())),
+            defaults: CircuitBreakerConfig::default(),
+            clock: Arc::new(SystemClock),
+            store: None,
+        }
+    }
+
+    /// Settings for breakers created by `get_or_create` from now on.
+    pub fn with_defaults(mut self, defaults: CircuitBreakerConfig) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Clock given to the breakers the registry creates.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Share the state of the breakers the registry creates, under their names (see `store`).
+    pub fn with_store(mut self, store: Arc<dyn CircuitBreakerStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn defaults(&self) -> &CircuitBreakerConfig {
+        &self.defaults
+    }
+
+    /// The breaker named `name`, created with the defaults if there is none yet.
+    pub fn get_or_create(&self, name: &str) -> Arc<CircuitBreaker> {
+        self.get_or_create_with(name, &self.defaults)
+    }
+
+    /// The breaker named `name`, created with `config` if there is none yet. An existing
+    /// breaker keeps its settings.
+    pub fn get_or_create_with(&self, name: &str, config: &CircuitBreakerConfig) -> Arc<CircuitBreaker> {
+        if let Some(breaker) = self.get(name) {
+            return breaker;
+        }
+        self.breakers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(self.build(name, config)))
+            .clone()
+    }
+
+    /// Add a breaker built elsewhere, replacing any breaker with the same name. Unlike created
+    /// breakers it is only instrumented if built `with_metrics`.
+    pub fn register(&self, name: &str, breaker: Arc<CircuitBreaker>) {
+        self.breakers.write().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), breaker);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<CircuitBreaker>> {
+        self.breakers.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+    }
+
+    /// Stop tracking `name`; callers holding the breaker keep using it.
+    pub fn remove(&self, name: &str) -> Option<Arc<CircuitBreaker>> {
+        self.breakers.write().unwrap_or_else(|e| e.into_inner()).remove(name)
+    }
+
+    /// Every breaker, ordered by name.
+    pub fn all(&self) -> Vec<(String, Arc<CircuitBreaker>)> {
+        self.breakers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, breaker)| (name.clone(), breaker.clone()))
+            .collect()
+    }
+
+    /// State of every breaker, ordered by name.
+    pub async fn states(&self) -> Vec<(String, CircuitState)> {
+        let mut states = Vec::new();
+        for (name, breaker) in self.all() {
+            states.push((name, breaker.state().await));
+        }
+        states
+    }
+
+    fn build(&self, name: &str, config: &CircuitBreakerConfig) -> CircuitBreaker {
+        let breaker = CircuitBreaker::from_config(config).with_clock(self.clock.clone()).with_metrics(name);
+        match &self.store {
+            Some(store) => breaker.with_store(name, store.clone()),
+            None => breaker,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resilience::CircuitBreakerResult;
+
+    #[tokio::test]
+    async fn test_breakers_are_shared_by_name() {
+        let registry = CircuitBreakerRegistry::new()
+            .with_defaults(CircuitBreakerConfig { failure_threshold: 1, ..Default::default() });
+
+        let payments = registry.get_or_create("payments");
+        let _: CircuitBreakerResult<(), &str> = payments.call(|| async { Err("boom") }).await;
+        registry.get_or_create("inventory");
+
+        assert!(Arc::ptr_eq(&payments, &registry.clone().get_or_create("payments")));
+        assert_eq!(registry.states().await, vec![
+            ("inventory".to_string(), CircuitState::Closed),
+            ("payments".to_string(), CircuitState::Open),
+        ]);
+    }
+}
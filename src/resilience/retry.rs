@@ -0,0 +1,225 @@
+//! Retries with exponential backoff
+//!
+//! A `RetryPolicy` decides whether and when to retry a failed operation. It is carried by
+//! saga steps, job workers and gRPC clients, and runs operations itself, standalone or with
+//! every attempt going through a `CircuitBreaker`:
+//!
+//! ```ignore
+//! let policy = RetryPolicy::exponential(4, Duration::from_millis(200))
+//!     .retry_if(|e: &HttpClientError| e.is_transient());
+//!
+//! let rates = policy.run(|| client.get_rates()).await?;
+//! let stock = policy.run_with_breaker(&inventory_breaker, || client.get_stock(sku)).await?;
+//! ```
+//!
+//! Each attempt runs in a `retry_attempt` span carrying its attempt number.
+
+use log::warn;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+
+use super::{CircuitBreaker, CircuitBreakerOutcome, CircuitBreakerResult};
+
+/// Exponential backoff retry policy with a retryable-error predicate.
+pub struct RetryPolicy<E> {
+    /// Total attempts including the first one (1 = no retries).
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after every attempt.
+    pub multiplier: f64,
+    retryable: Arc<dyn Fn(&E) -> bool + Send + Sync>,
+}
+
+impl<E> RetryPolicy<E> {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self::exponential(1, Duration::ZERO)
+    }
+
+    /// Retry every error up to `max_attempts` total attempts, doubling the delay each time.
+    pub fn exponential(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            retryable: Arc::new(|_: &E| true),
+        }
+    }
+
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Only retry errors for which `predicate` returns true (e.g. timeouts, connection resets).
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&E) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(predicate);
+        self
+    }
+
+    /// Returns true if `error`, raised on attempt number `attempt` (1-based), should be retried.
+    pub fn should_retry(&self, error: &E, attempt: u32) -> bool {
+        attempt < self.max_attempts && (self.retryable)(error)
+    }
+
+    /// Delay to wait after the failed attempt number `attempt` (1-based), with up to 25% jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let secs = (self.initial_backoff.as_secs_f64() * factor).min(self.max_backoff.as_secs_f64());
+        let base = Duration::from_secs_f64(secs);
+        base + base.mul_f64(0.25 * rand::random::<f64>())
+    }
+}
+
+impl<E> Default for RetryPolicy<E> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> Self {
+        Self {
+            max_attempts: self.max_attempts,
+            initial_backoff: self.initial_backoff,
+            max_backoff: self.max_backoff,
+            multiplier: self.multiplier,
+            retryable: Arc::clone(&self.retryable),
+        }
+    }
+}
+
+impl<E> fmt::Debug for RetryPolicy<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .finish()
+    }
+}
+
+
+impl<E: fmt::Display> RetryPolicy<E> {
+    /// Run `operation` until it succeeds, fails with an error not worth retrying, or runs out
+    /// of attempts; returns the last error.
+    pub async fn run<F, Fut, T>(&self, mut operation: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().instrument(self.attempt_span(attempt)).await {
+                Ok(value) => return Ok(value),
+                Err(e) if self.should_retry(&e, attempt) => self.wait(&e, attempt).await,
+                Err(e) => return Err(e),
+            }
+            attempt += 1;
+        }
+    }
+
+    /// `run` with every attempt made through `breaker`. Stops retrying once the circuit is
+    /// open, and failed attempts count toward opening it.
+    pub async fn run_with_breaker<F, Fut, T>(&self, breaker: &CircuitBreaker, mut operation: F) -> CircuitBreakerResult<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match breaker.call(&mut operation).instrument(self.attempt_span(attempt)).await {
+                Ok(value) => return Ok(value),
+                Err(CircuitBreakerOutcome::OperationError(e)) if self.should_retry(&e, attempt) => {
+                    self.wait(&e, attempt).await
+                }
+                Err(outcome) => return Err(outcome),
+            }
+            attempt += 1;
+        }
+    }
+
+    pub(crate) fn attempt_span(&self, attempt: u32) -> tracing::Span {
+        tracing::info_span!("retry_attempt", retry.attempt = attempt, retry.max_attempts = self.max_attempts)
+    }
+
+    pub(crate) async fn wait(&self, error: &E, attempt: u32) {
+        let delay = self.backoff(attempt);
+        warn!("🔁 Attempt {}/{} failed: {}. Retrying in {:?}", attempt, self.max_attempts, error, delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_retry_respects_attempts_and_predicate() {
+        let policy = RetryPolicy::<&str>::exponential(3, Duration::from_millis(10))
+            .retry_if(|e| *e == "timeout");
+
+        assert!(policy.should_retry(&"timeout", 1));
+        assert!(policy.should_retry(&"timeout", 2));
+        assert!(!policy.should_retry(&"timeout", 3));
+        assert!(!policy.should_retry(&"invalid", 1));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::<()>::exponential(10, Duration::from_millis(100))
+            .with_max_backoff(Duration::from_millis(300));
+
+        assert!(policy.backoff(1) >= Duration::from_millis(100));
+        assert!(policy.backoff(8) <= Duration::from_millis(375));
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_until_success() {
+        let policy = RetryPolicy::<String>::exponential(3, Duration::from_millis(1));
+        let mut calls = 0;
+
+        let result = policy
+            .run(|| {
+                calls += 1;
+                let outcome = if calls < 3 { Err(format!("attempt {} failed", calls)) } else { Ok(calls) };
+                async move { outcome }
+            })
+            .await;
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_breaker_stops_when_open() {
+        let policy = RetryPolicy::<&str>::exponential(5, Duration::from_millis(1));
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let mut calls = 0;
+
+        let result: CircuitBreakerResult<(), &str> = policy
+            .run_with_breaker(&breaker, || {
+                calls += 1;
+                async { Err("down") }
+            })
+            .await;
+
+        assert!(matches!(result, Err(CircuitBreakerOutcome::CircuitOpen)));
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,115 @@
+//! Circuit state shared between replicas
+//!
+//! Each replica's breaker otherwise has to discover an outage on its own, one
+//! `failure_threshold` of failed calls at a time. A breaker given a `CircuitBreakerStore`
+//! (`CircuitBreaker::with_store`, or `CircuitBreakerRegistry::with_store` for all of them)
+//! publishes when it opens and closes, and while closed checks the store at most once per
+//! `SYNC_INTERVAL`, opening too when another replica opened the circuit:
+//!
+//! ```ignore
+//! let breakers = CircuitBreakerRegistry::new()
+//!     .with_store(Arc::new(RedisCircuitBreakerStore::new(shared_connection().await?)));
+//! ```
+//!
+//! Only Open is shared: every replica probes in HalfOpen on its own, and the first one to
+//! close the circuit clears it for the rest. Without a store a breaker's state lives in its
+//! own atomics, which is the default.
+
+use async_trait::async_trait;
+use log::warn;
+use redis::aio::ConnectionManager;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often a closed breaker checks whether another replica opened its circuit.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[async_trait]
+pub trait CircuitBreakerStore: Send + Sync {
+    /// Whether a breaker sharing the store has opened `name` and not closed it since.
+    async fn is_open(&self, name: &str) -> bool;
+
+    /// `name` opened; it counts as open for `reset_timeout` unless closed earlier.
+    async fn set_open(&self, name: &str, reset_timeout: Duration);
+
+    async fn set_closed(&self, name: &str);
+}
+
+/// Shares state between breakers of the same name in this process, e.g. ones built by
+/// separate registries.
+#[derive(Default)]
+pub struct InProcessCircuitBreakerStore {
+    open_until: Mutex<HashMap<String, Instant>>,
+}
+
+#[async_trait]
+impl CircuitBreakerStore for InProcessCircuitBreakerStore {
+    async fn is_open(&self, name: &str) -> bool {
+        let open_until = self.open_until.lock().unwrap_or_else(|e| e.into_inner());
+        open_until.get(name).is_some_and(|until| *until > Instant::now())
+    }
+
+    async fn set_open(&self, name: &str, reset_timeout: Duration) {
+        let mut open_until = self.open_until.lock().unwrap_or_else(|e| e.into_inner());
+        open_until.insert(name.to_string(), Instant::now() + reset_timeout);
+    }
+
+    async fn set_closed(&self, name: &str) {
+        self.open_until.lock().unwrap_or_else(|e| e.into_inner()).remove(name);
+    }
+}
+
+/// Redis store shared by all replicas, under `lanai:circuit:`. Failures are logged and
+/// treated as closed, so an unreachable Redis leaves each replica on its own state.
+pub struct RedisCircuitBreakerStore {
+    conn: ConnectionManager,
+}
+
+impl RedisCircuitBreakerStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl CircuitBreakerStore for RedisCircuitBreakerStore {
+    async fn is_open(&self, name: &str) -> bool {
+        let exists: Result<bool, _> =
+            redis::cmd("EXISTS").arg(format!("lanai:circuit:{}", name)).query_async(&mut self.conn.clone()).await;
+        exists.unwrap_or_else(|e| {
+            warn!("⚠️ Failed to read shared state of circuit '{}': {}", name, e);
+            false
+        })
+    }
+
+    async fn set_open(&self, name: &str, reset_timeout: Duration) {
+        let result: Result<(), _> = redis::cmd("SET")
+            .arg(format!("lanai:circuit:{}", name))
+            .arg("open")
+            .arg("PX")
+            .arg((reset_timeout.as_millis() as u64).max(1))
+            .query_async(&mut self.conn.clone())
+            .await;
+        if let Err(e) = result {
+            warn!("⚠️ Failed to share open circuit '{}': {}", name, e);
+        }
+    }
+
+    async fn set_closed(&self, name: &str) {
+        let result: Result<i64, _> =
+            redis::cmd("DEL").arg(format!("lanai:circuit:{}", name)).query_async(&mut self.conn.clone()).await;
+        if let Err(e) = result {
+            warn!("⚠️ Failed to share closed circuit '{}': {}", name, e);
+        }
+    }
+}
+
+/// A breaker's link to its store.
+pub(crate) struct SharedState {
+    pub(crate) name: String,
+    pub(crate) store: Arc<dyn CircuitBreakerStore>,
+    /// When the store was last checked or written, as a breaker tick (0 = never).
+    pub(crate) last_sync: AtomicU64,
+}
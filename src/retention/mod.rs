@@ -0,0 +1,529 @@
+//! Data retention and purging
+//!
+//! Services declare how long each kind of data is kept with a `RetentionPolicy`, and a
+//! `Retention` runner removes what has expired, a batch at a time, so a backlog of
+//! millions of rows never holds long locks or one huge transaction:
+//!
+//! - `TableRetention` deletes rows of a table whose timestamp column is older than the
+//!   retention period, optionally archiving them first as JSON Lines in object storage
+//! - `ObjectRetention` deletes objects under a storage prefix by last modification time
+//! - anything else (search indexes, NATS streams, ...) implements `RetentionPolicy`
+//!
+//! The runner is scheduled as the `retention` task of a `Scheduler`, so with a
+//! `LeaderElection` only one replica purges. Every policy run that removes data (or fails)
+//! is recorded as a `retention.purge` / `retention.archive` audit event attributed to the
+//! system, and progress is exported as `retention_items_purged_total` and
+//! `retention_batches_total`.
+//!
+//! ```ignore
+//! let day = Duration::from_secs(24 * 3600);
+//! let retention = Retention::new()
+//!     .auditor(auditor.clone())
+//!     .policy(TableRetention::new(pool.clone(), "sessions", "last_seen_at", 30 * day)?)
+//!     .policy(
+//!         TableRetention::new(pool.clone(), "audit_events", "occurred_at", 365 * day)?
+//!             .archive_to(archive_store.clone()),
+//!     )
+//!     .policy(TableRetention::new(pool.clone(), "orders", "deleted_at", 90 * day)?.filter("status = 'cancelled'"))
+//!     .policy(ObjectRetention::new(exports_store, "exports/", 7 * day));
+//!
+//! let _scheduler = retention.schedule(Scheduler::new().with_leader(election), "0 3 * * *")?.start();
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::audit::{Actor, AuditEvent, Auditor, Resource};
+use crate::common::{Clock, SystemClock};
+use crate::scheduler::{Scheduler, SchedulerError, TaskOptions};
+use crate::storage::{ObjectStore, StorageError};
+
+/// Items removed per batch unless configured otherwise.
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Batches per policy and run unless configured otherwise; the rest waits for the next run.
+const DEFAULT_MAX_BATCHES: usize = 1_000;
+
+/// Retention error types
+#[derive(Debug, Error)]
+pub enum RetentionError {
+    #[error("Invalid retention policy: {0}")]
+    InvalidPolicy(String),
+
+    #[error("Retention query failed: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("Failed to archive expired data: {0}")]
+    Archive(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// Expired data is deleted.
+    Delete,
+    /// Expired data is copied to an archive, then deleted.
+    Archive,
+}
+
+impl RetentionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Archive => "archive",
+        }
+    }
+}
+
+/// Result of one `purge_batch` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PurgeBatch {
+    pub purged: u64,
+    /// Whether more expired data may remain.
+    pub more: bool,
+}
+
+/// How long one kind of data is kept, and how to remove it once expired.
+#[async_trait]
+pub trait RetentionPolicy: Send + Sync {
+    /// Unique name, used in metrics and audit events (e.g. the table name).
+    fn name(&self) -> &str;
+
+    fn retention(&self) -> Duration;
+
+    fn action(&self) -> RetentionAction {
+        RetentionAction::Delete
+    }
+
+    /// Remove up to `limit` items that expired before `cutoff`.
+    async fn purge_batch(&self, cutoff: DateTime<Utc>, limit: usize) -> Result<PurgeBatch, RetentionError>;
+}
+
+/// Outcome of one policy in a `Retention::run`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionReport {
+    pub policy: String,
+    pub action: RetentionAction,
+    pub cutoff: DateTime<Utc>,
+    pub purged: u64,
+    pub batches: usize,
+    /// False if the run stopped at the batch limit or on an error.
+    pub complete: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Deletes (or archives) rows of a table by a timestamp column.
+pub struct TableRetention {
+    pool: PgPool,
+    table: String,
+    column: String,
+    retention: Duration,
+    filter: Option<String>,
+    archive: Option<ObjectStore>,
+}
+
+impl TableRetention {
+    /// Rows whose `column` is older than `retention` expire. Rows where it is NULL are kept.
+    pub fn new(pool: PgPool, table: &str, column: &str, retention: Duration) -> Result<Self, RetentionError> {
+        let identifier = |name: &str| {
+            !name.is_empty() && name.split('.').all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        };
+        if !identifier(table) || !identifier(column) {
+            return Err(RetentionError::InvalidPolicy(format!("invalid column '{}.{}'", table, column)));
+        }
+        Ok(Self {
+            pool,
+            table: table.to_string(),
+            column: column.to_string(),
+            retention,
+            filter: None,
+            archive: None,
+        })
+    }
+
+    /// Only expire rows matching the SQL condition `condition` (written by the service, never
+    /// from user input), e.g. `status = 'cancelled'`.
+    pub fn filter(mut self, condition: &str) -> Self {
+        self.filter = Some(condition.to_string());
+        self
+    }
+
+    /// Write each batch to `store` as `retention/{table}/{date}/{batch_id}.jsonl` before
+    /// deleting it; the rows are only deleted once the upload succeeded.
+    pub fn archive_to(mut self, store: ObjectStore) -> Self {
+        self.archive = Some(store);
+        self
+    }
+
+    fn query(&self) -> String {
+        let filter = self.filter.as_ref().map(|f| format!(" AND ({})", f)).unwrap_or_default();
+        let returning = if self.archive.is_some() { "to_jsonb(t)" } else { "NULL::jsonb" };
+        format!(
+            "WITH expired AS (SELECT ctid FROM {table} WHERE {column} < $1{filter} LIMIT $2 FOR UPDATE SKIP LOCKED) \
+             DELETE FROM {table} t USING expired WHERE t.ctid = expired.ctid RETURNING {returning}",
+            table = self.table,
+            column = self.column,
+        )
+    }
+}
+
+#[async_trait]
+impl RetentionPolicy for TableRetention {
+    fn name(&self) -> &str {
+        &self.table
+    }
+
+    fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    fn action(&self) -> RetentionAction {
+        if self.archive.is_some() {
+            RetentionAction::Archive
+        } else {
+            RetentionAction::Delete
+        }
+    }
+
+    async fn purge_batch(&self, cutoff: DateTime<Utc>, limit: usize) -> Result<PurgeBatch, RetentionError> {
+        let mut tx = self.pool.begin().await?;
+        let rows: Vec<Option<serde_json::Value>> =
+            sqlx::query_scalar(&self.query()).bind(cutoff).bind(limit as i64).fetch_all(&mut *tx).await?;
+
+        if let (Some(store), false) = (&self.archive, rows.is_empty()) {
+            let mut lines = String::new();
+            for row in rows.iter().flatten() {
+                lines.push_str(&row.to_string());
+                lines.push('\n');
+            }
+            let key = format!("retention/{}/{}/{}.jsonl", self.table, Utc::now().format("%Y-%m-%d"), Uuid::new_v4());
+            store
+                .put(&key, lines, "application/x-ndjson")
+                .await
+                .map_err(|e| RetentionError::Archive(format!("{}: {}", key, e)))?;
+        }
+
+        // Dropping the transaction on an archive failure rolls the delete back
+        tx.commit().await?;
+        Ok(PurgeBatch { purged: rows.len() as u64, more: rows.len() >= limit })
+    }
+}
+
+/// Deletes objects under a prefix by last modification time.
+pub struct ObjectRetention {
+    store: ObjectStore,
+    prefix: String,
+    retention: Duration,
+    /// Last key examined, so a batch continues where the previous one stopped.
+    cursor: Mutex<Option<String>>,
+}
+
+impl ObjectRetention {
+    pub fn new(store: ObjectStore, prefix: &str, retention: Duration) -> Self {
+        Self { store, prefix: prefix.to_string(), retention, cursor: Mutex::new(None) }
+    }
+}
+
+#[async_trait]
+impl RetentionPolicy for ObjectRetention {
+    fn name(&self) -> &str {
+        &self.prefix
+    }
+
+    fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    async fn purge_batch(&self, cutoff: DateTime<Utc>, limit: usize) -> Result<PurgeBatch, RetentionError> {
+        let start_after = self.cursor.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let page = self.store.list(&self.prefix, start_after.as_deref(), limit).await?;
+
+        let mut purged = 0;
+        for object in &page {
+            if object.last_modified.is_some_and(|modified| modified < cutoff) {
+                self.store.delete(&object.key).await?;
+                purged += 1;
+            }
+        }
+
+        // A short page is the end of the prefix; the next run starts over
+        let more = page.len() >= limit;
+        *self.cursor.lock().unwrap_or_else(|e| e.into_inner()) = page.last().filter(|_| more).map(|o| o.key.clone());
+        Ok(PurgeBatch { purged, more })
+    }
+}
+
+/// Runs retention policies in batches, with metrics and audit events.
+#[derive(Clone)]
+pub struct Retention {
+    policies: Vec<Arc<dyn RetentionPolicy>>,
+    auditor: Option<Auditor>,
+    clock: Arc<dyn Clock>,
+    batch_size: usize,
+    max_batches: usize,
+    pause: Duration,
+    purged: Counter<u64>,
+    batches: Counter<u64>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Retention {
+    pub fn new() -> Self {
+        let meter = global::meter("lanai.retention");
+        Self {
+            policies: Vec::new(),
+            auditor: None,
+            clock: Arc::new(SystemClock),
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_batches: DEFAULT_MAX_BATCHES,
+            pause: Duration::ZERO,
+            purged: meter
+                .u64_counter("retention_items_purged_total")
+                .with_description("Expired rows or objects removed by retention policies")
+                .build(),
+            batches: meter
+                .u64_counter("retention_batches_total")
+                .with_description("Retention batches run, by policy")
+                .build(),
+        }
+    }
+
+    pub fn policy<P: RetentionPolicy + 'static>(mut self, policy: P) -> Self {
+        self.policies.push(Arc::new(policy));
+        self
+    }
+
+    /// Record an audit event for every policy run that removed data or failed.
+    pub fn auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Stop a policy after this many batches per run, leaving the rest for the next run.
+    pub fn max_batches(mut self, max_batches: usize) -> Self {
+        self.max_batches = max_batches.max(1);
+        self
+    }
+
+    /// Sleep between batches to spread the load on the database.
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Register `run` as the `retention` task of `scheduler` on cron `expression`. Attach a
+    /// `LeaderElection` to the scheduler so only one replica purges.
+    pub fn schedule(self, scheduler: Scheduler, expression: &str) -> Result<Scheduler, SchedulerError> {
+        let retention = Arc::new(self);
+        scheduler.task_with("retention", expression, TaskOptions::default(), move || {
+            let retention = retention.clone();
+            async move {
+                let failed: Vec<_> = retention.run().await.into_iter().filter_map(|r| r.error).collect();
+                if failed.is_empty() {
+                    Ok(())
+                } else {
+                    Err(failed.join("; "))
+                }
+            }
+        })
+    }
+
+    /// Purge every policy once; a failing policy does not stop the others.
+    pub async fn run(&self) -> Vec<RetentionReport> {
+        let mut reports = Vec::with_capacity(self.policies.len());
+        for policy in &self.policies {
+            let report = self.run_policy(policy.as_ref()).await;
+            match &report.error {
+                Some(e) => error!("❌ Retention '{}' failed after purging {}: {}", report.policy, report.purged, e),
+                None if report.purged > 0 => info!(
+                    "🧹 Retention '{}' purged {} items older than {} in {} batches",
+                    report.policy, report.purged, report.cutoff, report.batches
+                ),
+                None => {}
+            }
+            self.audit(&report).await;
+            reports.push(report);
+        }
+        reports
+    }
+
+    async fn run_policy(&self, policy: &dyn RetentionPolicy) -> RetentionReport {
+        let retention = chrono::Duration::from_std(policy.retention()).unwrap_or(chrono::Duration::MAX);
+        let cutoff = self.clock.utc_now().checked_sub_signed(retention).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let mut report = RetentionReport {
+            policy: policy.name().to_string(),
+            action: policy.action(),
+            cutoff,
+            purged: 0,
+            batches: 0,
+            complete: false,
+            error: None,
+        };
+        let attributes = [
+            KeyValue::new("policy", report.policy.clone()),
+            KeyValue::new("action", report.action.as_str()),
+        ];
+
+        while report.batches < self.max_batches {
+            if report.batches > 0 && !self.pause.is_zero() {
+                tokio::time::sleep(self.pause).await;
+            }
+            match policy.purge_batch(cutoff, self.batch_size).await {
+                Ok(batch) => {
+                    report.batches += 1;
+                    report.purged += batch.purged;
+                    self.batches.add(1, &attributes[..1]);
+                    self.purged.add(batch.purged, &attributes);
+                    if !batch.more {
+                        report.complete = true;
+                        break;
+                    }
+                }
+                Err(e) => {
+                    report.error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        report
+    }
+
+    async fn audit(&self, report: &RetentionReport) {
+        let Some(auditor) = &self.auditor else {
+            return;
+        };
+        if report.purged == 0 && report.error.is_none() {
+            return;
+        }
+        let action = match report.action {
+            RetentionAction::Delete => "retention.purge",
+            RetentionAction::Archive => "retention.archive",
+        };
+        let mut event = AuditEvent::new(Actor::system(), action, Resource::new("retention_policy", &report.policy))
+            .with_metadata(serde_json::json!({
+                "cutoff": report.cutoff,
+                "purged": report.purged,
+                "batches": report.batches,
+                "complete": report.complete,
+                "error": report.error,
+            }));
+        if report.error.is_some() {
+            event = event.failed();
+        }
+        // Sink failures are logged by the auditor
+        let _ = auditor.record(&event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditOutcome, InMemoryAuditSink};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// `remaining` expired items, failing once `fail_after` have been purged.
+    struct FakePolicy {
+        remaining: AtomicU64,
+        fail_after: Option<u64>,
+        purged: AtomicU64,
+    }
+
+    #[async_trait]
+    impl RetentionPolicy for FakePolicy {
+        fn name(&self) -> &str {
+            if self.fail_after.is_some() {
+                "flaky"
+            } else {
+                "sessions"
+            }
+        }
+
+        fn retention(&self) -> Duration {
+            Duration::from_secs(30 * 24 * 3600)
+        }
+
+        async fn purge_batch(&self, _cutoff: DateTime<Utc>, limit: usize) -> Result<PurgeBatch, RetentionError> {
+            if self.fail_after.is_some_and(|n| self.purged.load(Ordering::SeqCst) >= n) {
+                return Err(RetentionError::Archive("bucket unavailable".to_string()));
+            }
+            let purged = self.remaining.load(Ordering::SeqCst).min(limit as u64);
+            self.remaining.fetch_sub(purged, Ordering::SeqCst);
+            self.purged.fetch_add(purged, Ordering::SeqCst);
+            Ok(PurgeBatch { purged, more: purged == limit as u64 })
+        }
+    }
+
+    fn policy(remaining: u64, fail_after: Option<u64>) -> FakePolicy {
+        FakePolicy { remaining: AtomicU64::new(remaining), fail_after, purged: AtomicU64::new(0) }
+    }
+
+    #[tokio::test]
+    async fn test_policies_run_in_batches_and_are_audited() {
+        let sink = Arc::new(InMemoryAuditSink::new());
+        let retention = Retention::new()
+            .auditor(Auditor::new().sink(sink.clone()))
+            .batch_size(10)
+            .max_batches(3)
+            .policy(policy(25, None))
+            .policy(policy(100, Some(10)));
+
+        let reports = retention.run().await;
+        assert_eq!((reports[0].purged, reports[0].batches, reports[0].complete), (25, 3, true));
+        assert_eq!((reports[1].purged, reports[1].complete), (10, false));
+        assert!(reports[1].error.as_deref().is_some_and(|e| e.contains("bucket unavailable")));
+        let cutoff_age = Utc::now() - reports[0].cutoff;
+        assert_eq!(cutoff_age.num_days(), 30);
+
+        // Nothing left to purge: no audit event
+        assert_eq!(retention.run().await[0].purged, 0);
+        let events = sink.events().await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].action, "retention.purge");
+        assert_eq!(events[0].metadata["purged"], 25);
+        assert_eq!(events[1].outcome, AuditOutcome::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_table_retention_validates_and_builds_query() {
+        let pool = sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/lanai").unwrap();
+        let day = Duration::from_secs(24 * 3600);
+        assert!(TableRetention::new(pool.clone(), "sessions; DROP TABLE users", "created_at", day).is_err());
+
+        let policy =
+            TableRetention::new(pool, "public.orders", "deleted_at", day).unwrap().filter("status = 'cancelled'");
+        assert_eq!(policy.action(), RetentionAction::Delete);
+        let query = policy.query();
+        assert!(query.contains("FROM public.orders WHERE deleted_at < $1 AND (status = 'cancelled') LIMIT $2"));
+        assert!(query.ends_with("RETURNING NULL::jsonb"));
+    }
+}
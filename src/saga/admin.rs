@@ -0,0 +1,184 @@
+//! Admin endpoints for inspecting sagas
+//!
+//! ```ignore
+//! let admin = SagaAdmin::new(store.clone())
+//!     .register(checkout_saga.describe())
+//!     .register_saga(refund_saga.clone());
+//!
+//! ServerBuilder::new("lanai-orders")
+//!     .run(move |cfg| admin.configure(cfg))
+//!     .await
+//! ```
+//!
+//! Routes (every description route accepts `?format=dot` to get Graphviz instead of JSON):
+//! - `GET /admin/sagas` — registered saga definitions
+//! - `GET /admin/sagas/{name}` — one definition
+//! - `GET /admin/sagas/{name}/in-flight` — persisted records still running, waiting or compensating
+//! - `GET /admin/sagas/{name}/{saga_id}` — definition annotated with that execution's progress
+//! - `POST /admin/sagas/{name}/{saga_id}/resume` — resume a saga parked at a wait step
+//! - `POST /admin/sagas/{name}/{saga_id}/abort` — abort it with compensation (body: `{"reason": "..."}`)
+//!
+//! Signal routes are only available for sagas registered with `register_saga`, and run the
+//! saga until it completes or parks again before responding.
+//!
+//! Records include the serialized saga context, so mount these behind `AuthGuard`.
+
+use actix_web::{web, HttpResponse};
+use log::error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{SagaDescription, SagaOrchestrator, SagaSignal, SagaStore, SignalError, SignalTarget};
+
+#[derive(Clone)]
+pub struct SagaAdmin {
+    store: Arc<dyn SagaStore>,
+    sagas: Arc<HashMap<String, SagaDescription>>,
+    targets: Arc<HashMap<String, Arc<dyn SignalTarget>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbortBody {
+    reason: Option<String>,
+}
+
+impl SagaAdmin {
+    pub fn new(store: Arc<dyn SagaStore>) -> Self {
+        Self { store, sagas: Arc::new(HashMap::new()), targets: Arc::new(HashMap::new()) }
+    }
+
+    /// Expose a saga definition (typically `orchestrator.describe()`).
+    pub fn register(mut self, description: SagaDescription) -> Self {
+        Arc::make_mut(&mut self.sagas).insert(description.name.clone(), description);
+        self
+    }
+
+    /// Expose a saga definition and accept resume/abort signals for its executions.
+    pub fn register_saga<C, E>(mut self, saga: Arc<SagaOrchestrator<C, E>>) -> Self
+    where
+        C: Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+        E: Debug + Display + Send + Sync + 'static,
+    {
+        let name = saga.name().to_string();
+        Arc::make_mut(&mut self.targets).insert(name, saga.clone());
+        self.register(saga.describe())
+    }
+
+    /// Mount the admin routes under `/admin/sagas`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone())).service(
+            web::scope("/admin/sagas")
+                .route("", web::get().to(list_sagas))
+                .route("/{name}", web::get().to(get_saga))
+                .route("/{name}/in-flight", web::get().to(list_in_flight))
+                .route("/{name}/{saga_id}", web::get().to(get_run))
+                .route("/{name}/{saga_id}/resume", web::post().to(resume_saga))
+                .route("/{name}/{saga_id}/abort", web::post().to(abort_saga)),
+        );
+    }
+}
+
+async fn list_sagas(admin: web::Data<SagaAdmin>) -> HttpResponse {
+    let mut sagas: Vec<&SagaDescription> = admin.sagas.values().collect();
+    sagas.sort_by(|a, b| a.name.cmp(&b.name));
+    HttpResponse::Ok().json(sagas)
+}
+
+async fn get_saga(admin: web::Data<SagaAdmin>, name: web::Path<String>, query: web::Query<FormatQuery>) -> HttpResponse {
+    match admin.sagas.get(name.as_str()) {
+        Some(description) => render(description, &query),
+        None => not_found(&format!("Saga '{}' is not registered", name)),
+    }
+}
+
+async fn list_in_flight(admin: web::Data<SagaAdmin>, name: web::Path<String>) -> HttpResponse {
+    if !admin.sagas.contains_key(name.as_str()) {
+        return not_found(&format!("Saga '{}' is not registered", name));
+    }
+    match admin.store.list_in_flight(&name).await {
+        Ok(records) => HttpResponse::Ok().json(records),
+        Err(e) => store_error(e),
+    }
+}
+
+async fn get_run(
+    admin: web::Data<SagaAdmin>,
+    path: web::Path<(String, Uuid)>,
+    query: web::Query<FormatQuery>,
+) -> HttpResponse {
+    let (name, saga_id) = path.into_inner();
+    let Some(description) = admin.sagas.get(&name) else {
+        return not_found(&format!("Saga '{}' is not registered", name));
+    };
+
+    match admin.store.load(saga_id).await {
+        Ok(Some(record)) if record.saga_name == name => render(&description.clone().with_trace(&record), &query),
+        Ok(_) => not_found(&format!("Saga {} not found", saga_id)),
+        Err(e) => store_error(e),
+    }
+}
+
+async fn resume_saga(admin: web::Data<SagaAdmin>, path: web::Path<(String, Uuid)>) -> HttpResponse {
+    let (name, saga_id) = path.into_inner();
+    send(&admin, &name, saga_id, SagaSignal::Resume).await
+}
+
+async fn abort_saga(
+    admin: web::Data<SagaAdmin>,
+    path: web::Path<(String, Uuid)>,
+    body: Option<web::Json<AbortBody>>,
+) -> HttpResponse {
+    let (name, saga_id) = path.into_inner();
+    let reason = body
+        .and_then(|body| body.into_inner().reason)
+        .unwrap_or_else(|| "aborted via admin API".to_string());
+    send(&admin, &name, saga_id, SagaSignal::Abort { reason }).await
+}
+
+async fn send(admin: &SagaAdmin, name: &str, saga_id: Uuid, signal: SagaSignal) -> HttpResponse {
+    let Some(target) = admin.targets.get(name) else {
+        return not_found(&format!("Saga '{}' does not accept signals here", name));
+    };
+
+    match admin.store.load(saga_id).await {
+        Ok(Some(record)) if record.saga_name == name => {}
+        Ok(_) => return not_found(&format!("Saga {} not found", saga_id)),
+        Err(e) => return store_error(e),
+    }
+
+    match target.signal(saga_id, signal).await {
+        Ok(outcome) => HttpResponse::Ok().json(serde_json::json!({ "saga_id": saga_id, "result": outcome })),
+        Err(SignalError::NotFound(_)) => not_found(&format!("Saga {} not found", saga_id)),
+        Err(e @ (SignalError::NotWaiting(..) | SignalError::PastPivot(_))) => {
+            HttpResponse::Conflict().json(serde_json::json!({ "error": e.to_string() }))
+        }
+        Err(e) => {
+            error!("❌ Saga admin: signal for {} failed: {}", saga_id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+fn render(description: &SagaDescription, query: &FormatQuery) -> HttpResponse {
+    match query.format.as_deref() {
+        Some("dot") => HttpResponse::Ok().content_type("text/vnd.graphviz").body(description.to_dot()),
+        _ => HttpResponse::Ok().json(description),
+    }
+}
+
+fn not_found(message: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({ "error": message }))
+}
+
+fn store_error(e: super::SagaStoreError) -> HttpResponse {
+    error!("❌ Saga admin: store error: {}", e);
+    HttpResponse::InternalServerError().json(serde_json::json!({ "error": "Saga store unavailable" }))
+}
@@ -0,0 +1,64 @@
+//! Conditional and branching steps
+//!
+//! A branching stage evaluates its arms' predicates against the context when the saga
+//! reaches it and executes the first arm that matches (or nothing, if none match). The
+//! chosen arm is persisted with the saga so compensation only rolls back what actually
+//! ran, even if the context changed afterwards.
+
+use super::orchestrator::StepEntry;
+use super::{SagaStep, StepPolicy};
+
+type Predicate<C> = Box<dyn Fn(&C) -> bool + Send + Sync>;
+
+pub struct Branch<C, E> {
+    pub(crate) arms: Vec<(Predicate<C>, StepEntry<C, E>)>,
+}
+
+impl<C, E> Branch<C, E> {
+    pub fn new() -> Self {
+        Self { arms: Vec::new() }
+    }
+
+    /// Add an arm executed when `predicate` holds and no earlier arm matched.
+    pub fn when<P, S>(self, predicate: P, name: &str, step: S) -> Self
+    where
+        P: Fn(&C) -> bool + Send + Sync + 'static,
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.when_with_policy(predicate, name, step, StepPolicy::default())
+    }
+
+    pub fn when_with_policy<P, S>(mut self, predicate: P, name: &str, step: S, policy: StepPolicy<E>) -> Self
+    where
+        P: Fn(&C) -> bool + Send + Sync + 'static,
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.arms.push((Box::new(predicate), StepEntry::new(name, step, policy)));
+        self
+    }
+
+    /// Add a fallback arm executed when no earlier arm matched.
+    pub fn otherwise<S>(self, name: &str, step: S) -> Self
+    where
+        C: 'static,
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.when(|_: &C| true, name, step)
+    }
+
+    /// First arm whose predicate matches `context`.
+    pub(crate) fn select(&self, context: &C) -> Option<&StepEntry<C, E>> {
+        self.arms.iter().find(|(predicate, _)| predicate(context)).map(|(_, entry)| entry)
+    }
+
+    /// Arm with the given step name.
+    pub(crate) fn arm(&self, name: &str) -> Option<&StepEntry<C, E>> {
+        self.arms.iter().map(|(_, entry)| entry).find(|entry| entry.name == name)
+    }
+}
+
+impl<C, E> Default for Branch<C, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
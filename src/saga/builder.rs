@@ -0,0 +1,172 @@
+//! Fluent saga definition
+//!
+//! ```ignore
+//! let saga = Saga::builder("order_checkout")
+//!     .step("reserve_stock", ReserveStock)
+//!     .step_with_policy("capture_payment", CapturePayment, StepPolicy::default().with_timeout(secs(10)))
+//!     .store(store)
+//!     .build();
+//! ```
+//!
+//! Step names identify steps in logs, metrics, persisted records and errors, so they must be
+//! unique within a saga and stable across deploys.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::metrics::SagaMetrics;
+use super::orchestrator::{Snapshot, Stage, StepEntry};
+use super::{Branch, ParallelGroup, RetryPolicy, SagaError, SagaOrchestrator, SagaStep, SagaStore, StepPolicy};
+
+pub struct SagaBuilder<C, E> {
+    name: String,
+    stages: Vec<Stage<C, E>>,
+    store: Option<(Arc<dyn SagaStore>, Snapshot<C>)>,
+    timeout: Option<Duration>,
+    pivot: Option<usize>,
+    forward_retry: RetryPolicy<SagaError<E>>,
+}
+
+impl<C, E> SagaBuilder<C, E> {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            stages: Vec::new(),
+            store: None,
+            timeout: None,
+            pivot: None,
+            forward_retry: RetryPolicy::exponential(10, Duration::from_secs(1)).with_max_backoff(Duration::from_secs(60)),
+        }
+    }
+
+    /// Append a step with the default policy (no retries, no timeout).
+    pub fn step<S>(self, name: &str, step: S) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.step_with_policy(name, step, StepPolicy::default())
+    }
+
+    /// Append a step whose calls are retried and bounded according to `policy`.
+    pub fn step_with_policy<S>(mut self, name: &str, step: S, policy: StepPolicy<E>) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.stages.push(Stage::Single(StepEntry::new(name, step, policy)));
+        self
+    }
+
+    /// Append the pivot step: the point after which money has moved and rollback is impossible.
+    ///
+    /// If the pivot (or anything before it) fails, the saga is compensated as usual. Once it
+    /// completes, later steps are retried forward with the `forward_retry` policy instead.
+    ///
+    /// # Panics
+    /// If a pivot was already declared.
+    pub fn pivot<S>(self, name: &str, step: S) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.pivot_with_policy(name, step, StepPolicy::default())
+    }
+
+    pub fn pivot_with_policy<S>(mut self, name: &str, step: S, policy: StepPolicy<E>) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        if self.pivot.is_some() {
+            panic!("Saga '{}' declares more than one pivot step", self.name);
+        }
+        self.pivot = Some(self.stages.len());
+        self.step_with_policy(name, step, policy)
+    }
+
+    /// Retry policy for stages after the pivot (default: 10 attempts, 1s doubling up to 60s).
+    /// When it is exhausted the saga is left in flight and returns `SagaError::Stuck`.
+    pub fn forward_retry(mut self, policy: RetryPolicy<SagaError<E>>) -> Self {
+        self.forward_retry = policy;
+        self
+    }
+
+    /// Append a group of independent steps that are executed concurrently.
+    pub fn parallel(mut self, name: &str, group: ParallelGroup<C, E>) -> Self {
+        self.stages.push(Stage::Parallel { name: name.to_string(), group });
+        self
+    }
+
+    /// Append a step that only runs when `predicate` holds for the context at that point
+    /// (e.g. skip payment capture for zero-total orders).
+    pub fn step_if<P, S>(self, name: &str, predicate: P, step: S) -> Self
+    where
+        P: Fn(&C) -> bool + Send + Sync + 'static,
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.branch(name, Branch::new().when(predicate, name, step))
+    }
+
+    /// Append a stage that executes the first matching arm of `branch`, or nothing.
+    pub fn branch(mut self, name: &str, branch: Branch<C, E>) -> Self {
+        self.stages.push(Stage::Branch { name: name.to_string(), branch });
+        self
+    }
+
+    /// Append a point where the saga parks as `Waiting` until it is signalled, e.g. for a
+    /// manual approval. `SagaOrchestrator::signal` resumes it with the next stage or aborts
+    /// it with compensation. Requires a store, since the saga outlives the `run` call.
+    pub fn wait(mut self, name: &str) -> Self {
+        self.stages.push(Stage::Wait { name: name.to_string(), condition: None });
+        self
+    }
+
+    /// Like `wait`, but only parks when `predicate` holds for the context at that point
+    /// (e.g. refunds above a threshold).
+    pub fn wait_if<P>(mut self, name: &str, predicate: P) -> Self
+    where
+        P: Fn(&C) -> bool + Send + Sync + 'static,
+    {
+        self.stages.push(Stage::Wait { name: name.to_string(), condition: Some(Box::new(predicate)) });
+        self
+    }
+
+    /// Persist progress into `store` after every step so the saga can be resumed.
+    pub fn store(mut self, store: Arc<dyn SagaStore>) -> Self
+    where
+        C: Serialize,
+    {
+        self.store = Some((store, |context| serde_json::to_value(context)));
+        self
+    }
+
+    /// Deadline for the whole saga, measured from its creation (including time before a restart).
+    ///
+    /// When the deadline passes, the running step is abandoned and compensation starts.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Build the orchestrator.
+    ///
+    /// # Panics
+    /// If two steps (including parallel group members) share a name.
+    pub fn build(self) -> SagaOrchestrator<C, E> {
+        let mut seen = HashSet::new();
+        for name in self.stages.iter().flat_map(|stage| stage.step_names()) {
+            if !seen.insert(name) {
+                panic!("Saga '{}' declares step '{}' more than once", self.name, name);
+            }
+        }
+
+        SagaOrchestrator {
+            name: self.name,
+            steps: self.stages,
+            store: self.store,
+            timeout: self.timeout,
+            pivot: self.pivot,
+            forward_retry: self.forward_retry,
+            metrics: SagaMetrics::new(),
+        }
+    }
+}
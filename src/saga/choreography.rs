@@ -0,0 +1,370 @@
+//! Choreography-style sagas
+//!
+//! A lighter-weight alternative to `SagaOrchestrator` for teams that prefer event-driven
+//! sagas: each service declares reactions of the form "on event X, do Y, emit Z,
+//! compensate with W" and the events themselves drive the saga.
+//!
+//! ```ignore
+//! let handle = Choreography::new("order_fulfilment")
+//!     .reaction(
+//!         Reaction::on("reserve_stock", "lanai.sales.order.placed.>")
+//!             .emit(|ctx, order: OrderPlaced| async move { inventory.reserve(&order).await })
+//!             .compensate_on("lanai.payments.payment.failed.>", |ctx, order: OrderPlaced| async move {
+//!                 inventory.release(&order).await
+//!             })
+//!             .watchdog(Duration::from_secs(30), &["lanai.payments.payment.captured.>"]),
+//!     )
+//!     .start()
+//!     .await?;
+//! ```
+//!
+//! Every event carries a `Lanai-Correlation-Id` header that is propagated from the
+//! triggering event to the emitted one. A reaction that declares a compensation remembers
+//! the events it handled per correlation ID; if the compensation subject arrives (or the
+//! watchdog expires before any settle subject does) the compensation runs with the
+//! original triggering event. Correlation state is kept in memory.
+
+use futures_util::StreamExt;
+use log::{info, error, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::messaging::events::{decode_event, EventEnvelope, LanaiEvent};
+use crate::messaging::{NatsClient, NatsError};
+
+pub use crate::messaging::CORRELATION_ID_HEADER;
+
+/// Metadata of the event being handled.
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    pub correlation_id: Uuid,
+    pub subject: String,
+}
+
+/// Published when a reaction's handler fails, on `lanai.saga.{choreography}.{reaction}.failed`,
+/// so upstream reactions can `compensate_on` it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionFailedEvent {
+    pub choreography: String,
+    pub reaction: String,
+    pub correlation_id: Uuid,
+    pub error: String,
+}
+
+/// Published when a watchdog expires, on `lanai.saga.{choreography}.{reaction}.timeout`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionTimedOutEvent {
+    pub choreography: String,
+    pub reaction: String,
+    pub correlation_id: Uuid,
+    pub timeout_secs: u64,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type Handler = Arc<dyn Fn(EventContext, Vec<u8>) -> BoxFuture<Result<Option<Emission>, String>> + Send + Sync>;
+type Compensation = Arc<dyn Fn(EventContext, Vec<u8>) -> BoxFuture<Result<(), String>> + Send + Sync>;
+
+struct Emission {
+    subject: String,
+    envelope: EventEnvelope<serde_json::Value>,
+}
+
+/// Declarative "on X, do Y, emit Z, compensate with W" rule.
+pub struct Reaction {
+    name: String,
+    subject: String,
+    handler: Option<Handler>,
+    compensation: Option<(String, Compensation)>,
+    watchdog: Option<(Duration, Vec<String>)>,
+}
+
+impl Reaction {
+    /// React to events published on `subject` (NATS wildcards allowed).
+    pub fn on(name: &str, subject: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            subject: subject.to_string(),
+            handler: None,
+            compensation: None,
+            watchdog: None,
+        }
+    }
+
+    /// Do Y: run `handler` for each event, without emitting anything.
+    pub fn run<In, Err, F, Fut>(mut self, handler: F) -> Self
+    where
+        In: DeserializeOwned + Send + 'static,
+        Err: std::fmt::Display,
+        F: Fn(EventContext, In) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Err>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let erased: Handler = Arc::new(move |ctx: EventContext, payload: Vec<u8>| -> BoxFuture<Result<Option<Emission>, String>> {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let event: In = decode_event(&payload).map_err(|e| e.to_string())?;
+                handler(ctx, event).await.map_err(|e| e.to_string())?;
+                Ok::<_, String>(None)
+            })
+        });
+        self.handler = Some(erased);
+        self
+    }
+
+    /// Do Y and emit Z: run `handler` and publish the event it returns on the event's own subject.
+    pub fn emit<In, Out, Err, F, Fut>(mut self, handler: F) -> Self
+    where
+        In: DeserializeOwned + Send + 'static,
+        Out: LanaiEvent + Serialize + Send + 'static,
+        Err: std::fmt::Display,
+        F: Fn(EventContext, In) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Out, Err>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let erased: Handler = Arc::new(move |ctx: EventContext, payload: Vec<u8>| -> BoxFuture<Result<Option<Emission>, String>> {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let event: In = decode_event(&payload).map_err(|e| e.to_string())?;
+                let correlation_id = ctx.correlation_id;
+                let output = handler(ctx, event).await.map_err(|e| e.to_string())?;
+                let subject = output.subject();
+                let envelope = output.into_envelope().with_correlation_id(correlation_id);
+                let data = serde_json::to_value(&envelope.data).map_err(|e| e.to_string())?;
+                Ok::<_, String>(Some(Emission { subject, envelope: envelope.map(|_| data) }))
+            })
+        });
+        self.handler = Some(erased);
+        self
+    }
+
+    /// Compensate with W: when an event with a tracked correlation ID arrives on `subject`,
+    /// run `compensation` with the original triggering event (`In` is the trigger's type).
+    pub fn compensate_on<In, Err, F, Fut>(mut self, subject: &str, compensation: F) -> Self
+    where
+        In: DeserializeOwned + Send + 'static,
+        Err: std::fmt::Display,
+        F: Fn(EventContext, In) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Err>> + Send + 'static,
+    {
+        let compensation = Arc::new(compensation);
+        let erased: Compensation = Arc::new(move |ctx: EventContext, payload: Vec<u8>| -> BoxFuture<Result<(), String>> {
+            let compensation = Arc::clone(&compensation);
+            Box::pin(async move {
+                let event: In = decode_event(&payload).map_err(|e| e.to_string())?;
+                compensation(ctx, event).await.map_err(|e| e.to_string())
+            })
+        });
+        self.compensation = Some((subject.to_string(), erased));
+        self
+    }
+
+    /// Run the compensation if none of `settle_subjects` (nor the compensation subject)
+    /// arrives for a correlation ID within `timeout` after it was handled.
+    pub fn watchdog(mut self, timeout: Duration, settle_subjects: &[&str]) -> Self {
+        self.watchdog = Some((timeout, settle_subjects.iter().map(|s| s.to_string()).collect()));
+        self
+    }
+}
+
+/// Handled trigger awaiting settlement or compensation.
+struct Pending {
+    subject: String,
+    payload: Vec<u8>,
+    deadline: Option<Instant>,
+}
+
+/// Correlation state of one reaction.
+#[derive(Default)]
+struct Tracker {
+    pending: Mutex<HashMap<Uuid, Pending>>,
+}
+
+/// A set of reactions forming one choreographed saga.
+pub struct Choreography {
+    name: String,
+    reactions: Vec<Reaction>,
+}
+
+/// Running choreography; dropping it leaves the subscriptions running, call `shutdown` to stop.
+pub struct ChoreographyHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ChoreographyHandle {
+    pub fn shutdown(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Choreography {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), reactions: Vec::new() }
+    }
+
+    pub fn reaction(mut self, reaction: Reaction) -> Self {
+        self.reactions.push(reaction);
+        self
+    }
+
+    /// Subscribe every reaction on the global NATS client and start the watchdogs.
+    pub async fn start(self) -> Result<ChoreographyHandle, NatsError> {
+        let choreography = Arc::new(self.name);
+        let mut tasks = Vec::new();
+
+        for reaction in self.reactions {
+            let Some(handler) = reaction.handler else {
+                warn!("⚠️ Choreography '{}': reaction '{}' has no handler, skipping", choreography, reaction.name);
+                continue;
+            };
+            let name = Arc::new(reaction.name);
+            let tracker = Arc::new(Tracker::default());
+            let track = reaction.compensation.is_some();
+            let watchdog_timeout = reaction.watchdog.as_ref().map(|(timeout, _)| *timeout);
+
+            // On X, do Y, emit Z
+            let mut subscriber = NatsClient::subscribe(&reaction.subject).await?;
+            {
+                let (choreography, name, tracker) = (choreography.clone(), name.clone(), tracker.clone());
+                tasks.push(tokio::spawn(async move {
+                    while let Some(message) = subscriber.next().await {
+                        let ctx = event_context(&message);
+                        let payload = message.payload.to_vec();
+                        match handler(ctx.clone(), payload.clone()).await {
+                            Ok(emission) => {
+                                if track {
+                                    tracker.pending.lock().await.insert(ctx.correlation_id, Pending {
+                                        subject: ctx.subject.clone(),
+                                        payload,
+                                        deadline: watchdog_timeout.map(|t| Instant::now() + t),
+                                    });
+                                }
+                                if let Some(emission) = emission {
+                                    publish(&emission.subject, &emission.envelope).await;
+                                }
+                            }
+                            Err(e) => {
+                                error!("❌ Choreography '{}': reaction '{}' failed for {}: {}",
+                                       choreography, name, ctx.correlation_id, e);
+                                let failed = ReactionFailedEvent {
+                                    choreography: choreography.to_string(),
+                                    reaction: name.to_string(),
+                                    correlation_id: ctx.correlation_id,
+                                    error: e,
+                                };
+                                let subject = format!("lanai.saga.{}.{}.failed", choreography, name);
+                                publish(&subject, &EventEnvelope::new(failed).with_correlation_id(ctx.correlation_id)).await;
+                            }
+                        }
+                    }
+                }));
+            }
+
+            let Some((compensation_subject, compensation)) = reaction.compensation else { continue };
+
+            // Compensate with W
+            let mut subscriber = NatsClient::subscribe(&compensation_subject).await?;
+            {
+                let (choreography, name, tracker, compensation) =
+                    (choreography.clone(), name.clone(), tracker.clone(), compensation.clone());
+                tasks.push(tokio::spawn(async move {
+                    while let Some(message) = subscriber.next().await {
+                        let correlation_id = event_context(&message).correlation_id;
+                        let pending = tracker.pending.lock().await.remove(&correlation_id);
+                        if let Some(pending) = pending {
+                            warn!("🔄 Choreography '{}': compensating '{}' for {} after {}",
+                                  choreography, name, correlation_id, message.subject);
+                            run_compensation(&choreography, &name, &compensation, correlation_id, pending).await;
+                        }
+                    }
+                }));
+            }
+
+            let Some((timeout, settle_subjects)) = reaction.watchdog else { continue };
+
+            // Settle subjects end tracking without compensation
+            for settle_subject in settle_subjects {
+                let mut subscriber = NatsClient::subscribe(&settle_subject).await?;
+                let tracker = tracker.clone();
+                tasks.push(tokio::spawn(async move {
+                    while let Some(message) = subscriber.next().await {
+                        let correlation_id = event_context(&message).correlation_id;
+                        tracker.pending.lock().await.remove(&correlation_id);
+                    }
+                }));
+            }
+
+            // Watchdog: compensate correlations that never settled
+            let (choreography, name) = (choreography.clone(), name.clone());
+            let check_every = (timeout / 4).clamp(Duration::from_millis(100), Duration::from_secs(5));
+            tasks.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(check_every);
+                loop {
+                    interval.tick().await;
+                    let expired: Vec<(Uuid, Pending)> = {
+                        let mut pending = tracker.pending.lock().await;
+                        let now = Instant::now();
+                        let ids: Vec<Uuid> = pending
+                            .iter()
+                            .filter(|(_, p)| p.deadline.is_some_and(|d| d <= now))
+                            .map(|(id, _)| *id)
+                            .collect();
+                        ids.into_iter().filter_map(|id| pending.remove(&id).map(|p| (id, p))).collect()
+                    };
+
+                    for (correlation_id, pending) in expired {
+                        warn!("⏰ Choreography '{}': '{}' for {} not settled within {:?}. Compensating...",
+                              choreography, name, correlation_id, timeout);
+                        let timed_out = ReactionTimedOutEvent {
+                            choreography: choreography.to_string(),
+                            reaction: name.to_string(),
+                            correlation_id,
+                            timeout_secs: timeout.as_secs(),
+                        };
+                        let subject = format!("lanai.saga.{}.{}.timeout", choreography, name);
+                        publish(&subject, &EventEnvelope::new(timed_out).with_correlation_id(correlation_id)).await;
+                        run_compensation(&choreography, &name, &compensation, correlation_id, pending).await;
+                    }
+                }
+            }));
+        }
+
+        info!("💃 Choreography '{}' started with {} task(s)", choreography, tasks.len());
+        Ok(ChoreographyHandle { tasks })
+    }
+}
+
+/// Read the correlation ID from the message headers, starting a new correlation if absent.
+fn event_context(message: &async_nats::Message) -> EventContext {
+    let correlation_id = message
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get(CORRELATION_ID_HEADER))
+        .and_then(|value| Uuid::parse_str(value.as_str()).ok())
+        .unwrap_or_else(Uuid::new_v4);
+
+    EventContext { correlation_id, subject: message.subject.to_string() }
+}
+
+async fn publish<T: Serialize>(subject: &str, envelope: &EventEnvelope<T>) {
+    if let Err(e) = NatsClient::publish_envelope_with_headers(subject, envelope, async_nats::HeaderMap::new()).await {
+        error!("❌ Failed to publish {} for correlation {}: {}", subject, envelope.correlation(), e);
+    }
+}
+
+async fn run_compensation(choreography: &str, reaction: &str, compensation: &Compensation, correlation_id: Uuid, pending: Pending) {
+    let ctx = EventContext { correlation_id, subject: pending.subject };
+    if let Err(e) = compensation(ctx, pending.payload).await {
+        error!("❌ Choreography '{}': compensation of '{}' failed for {}: {}. Manual intervention required.",
+               choreography, reaction, correlation_id, e);
+    }
+}
@@ -0,0 +1,312 @@
+//! Saga definition export
+//!
+//! `SagaOrchestrator::describe` exports the structure of a saga (stages, parallel members,
+//! branch arms, pivot, timeouts and retry budgets) as a serializable `SagaDescription`.
+//! Attaching a persisted `SagaRecord` with `with_trace` marks how far that execution got,
+//! and `to_dot` renders either form as a Graphviz digraph for docs and debugging UIs.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::orchestrator::{Stage, StepEntry};
+use super::{SagaError, SagaOrchestrator, SagaRecord, SagaStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageKind {
+    Step,
+    Parallel,
+    Branch,
+    /// Parks the saga until it is signalled.
+    Wait,
+}
+
+/// Progress of a stage or step within a traced execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepState {
+    Completed,
+    /// The next stage to execute (or the one executing right now, or waiting for a signal).
+    Current,
+    /// Executed, and waiting to be rolled back by an in-progress compensation.
+    PendingCompensation,
+    /// A branch arm that was not chosen.
+    Skipped,
+    Pending,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDescription {
+    pub name: String,
+    pub timeout_ms: Option<u64>,
+    pub max_attempts: u32,
+    pub compensate_max_attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<StepState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageDescription {
+    pub name: String,
+    pub kind: StageKind,
+    /// The step itself, the members of a parallel group, or the arms of a branch in order.
+    pub steps: Vec<StepDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<StepState>,
+}
+
+/// Summary of a persisted execution attached by `SagaDescription::with_trace`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SagaTrace {
+    pub saga_id: Uuid,
+    pub status: SagaStatus,
+    pub completed_steps: usize,
+    pub last_step: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SagaDescription {
+    pub name: String,
+    pub timeout_ms: Option<u64>,
+    /// Name of the pivot stage, if any.
+    pub pivot: Option<String>,
+    pub stages: Vec<StageDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<SagaTrace>,
+}
+
+impl<C, E> SagaOrchestrator<C, E> {
+    /// Export the structure of this saga.
+    pub fn describe(&self) -> SagaDescription {
+        SagaDescription {
+            name: self.name.clone(),
+            timeout_ms: self.timeout.map(|t| t.as_millis() as u64),
+            pivot: self.pivot.and_then(|i| self.steps.get(i)).map(|stage| stage.name().to_string()),
+            stages: self.steps.iter().map(describe_stage).collect(),
+            trace: None,
+        }
+    }
+
+    /// Export the structure of this saga together with the persisted state of `saga_id`.
+    pub async fn describe_run(&self, saga_id: Uuid) -> Result<SagaDescription, SagaError<E>> {
+        let store = self.store()?;
+        let record = store.load(saga_id).await?.ok_or(SagaError::NotFound(saga_id))?;
+        Ok(self.describe().with_trace(&record))
+    }
+}
+
+fn describe_stage<C, E>(stage: &Stage<C, E>) -> StageDescription {
+    let (kind, steps) = match stage {
+        Stage::Single(entry) => (StageKind::Step, vec![describe_step(entry)]),
+        Stage::Parallel { group, .. } => (StageKind::Parallel, group.members.iter().map(describe_step).collect()),
+        Stage::Branch { branch, .. } => (StageKind::Branch, branch.arms.iter().map(|(_, arm)| describe_step(arm)).collect()),
+        Stage::Wait { .. } => (StageKind::Wait, Vec::new()),
+    };
+    StageDescription { name: stage.name().to_string(), kind, steps, state: None }
+}
+
+fn describe_step<C, E>(entry: &StepEntry<C, E>) -> StepDescription {
+    StepDescription {
+        name: entry.name.clone(),
+        timeout_ms: entry.policy.timeout.map(|t| t.as_millis() as u64),
+        max_attempts: entry.policy.execute.max_attempts,
+        compensate_max_attempts: entry.policy.compensate.max_attempts,
+        state: None,
+    }
+}
+
+impl SagaDescription {
+    /// Mark every stage and step with its progress in `record`.
+    ///
+    /// Compensated sagas have no executed steps left, so all their stages show as pending;
+    /// the trace status and error explain what happened.
+    pub fn with_trace(mut self, record: &SagaRecord) -> Self {
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            let state = match record.status {
+                _ if i < record.completed_steps && record.status == SagaStatus::Compensating => StepState::PendingCompensation,
+                _ if i < record.completed_steps => StepState::Completed,
+                SagaStatus::Completed => StepState::Completed,
+                SagaStatus::Running | SagaStatus::Waiting if i == record.completed_steps => StepState::Current,
+                _ => StepState::Pending,
+            };
+            stage.state = Some(state);
+
+            for (member, step) in stage.steps.iter_mut().enumerate() {
+                step.state = Some(match stage.kind {
+                    StageKind::Branch if state != StepState::Pending && state != StepState::Current => {
+                        match record.branch_choices.get(&stage.name) {
+                            Some(chosen) if *chosen == step.name => state,
+                            _ => StepState::Skipped,
+                        }
+                    }
+                    StageKind::Parallel if state == StepState::Current && record.completed_members.contains(&member) => {
+                        StepState::Completed
+                    }
+                    _ => state,
+                });
+            }
+        }
+
+        self.trace = Some(SagaTrace {
+            saga_id: record.saga_id,
+            status: record.status,
+            completed_steps: record.completed_steps,
+            last_step: record.last_step.clone(),
+            error: record.error.clone(),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        });
+        self
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Render as a Graphviz digraph: stages left to right, parallel groups as clusters,
+    /// branches as decision diamonds, the pivot with a double border and traced steps colored.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph {} {{", quote(&self.name));
+        let _ = writeln!(dot, "  rankdir=LR;");
+        let _ = writeln!(dot, "  node [shape=box, style=\"rounded,filled\", fillcolor=white];");
+
+        let mut exits = vec![String::from("__start")];
+        let _ = writeln!(dot, "  \"__start\" [shape=circle, label=\"\", width=0.2, fillcolor=black];");
+
+        for stage in &self.stages {
+            let pivot = self.pivot.as_deref() == Some(stage.name.as_str());
+            let entries: Vec<String> = match stage.kind {
+                StageKind::Step => {
+                    let step = &stage.steps[0];
+                    write_node(&mut dot, "  ", &step.name, &step.name, step.state, pivot);
+                    vec![step.name.clone()]
+                }
+                StageKind::Parallel => {
+                    let _ = writeln!(dot, "  subgraph {} {{", quote(&format!("cluster_{}", stage.name)));
+                    let _ = writeln!(dot, "    label={};", quote(&format!("{} (parallel)", stage.name)));
+                    for step in &stage.steps {
+                        write_node(&mut dot, "    ", &step.name, &step.name, step.state, pivot);
+                    }
+                    let _ = writeln!(dot, "  }}");
+                    stage.steps.iter().map(|s| s.name.clone()).collect()
+                }
+                StageKind::Wait => {
+                    let _ = writeln!(dot, "  {} [shape=octagon, label={}, fillcolor={}];",
+                                     quote(&stage.name), quote(&format!("{} (wait)", stage.name)), fill(stage.state));
+                    vec![stage.name.clone()]
+                }
+                StageKind::Branch => {
+                    let decision = format!("{}?", stage.name);
+                    let _ = writeln!(dot, "  {} [shape=diamond, label={}];", quote(&decision), quote(&stage.name));
+                    for step in &stage.steps {
+                        write_node(&mut dot, "  ", &step.name, &step.name, step.state, pivot);
+                        let _ = writeln!(dot, "  {} -> {} [style=dashed];", quote(&decision), quote(&step.name));
+                    }
+                    vec![decision]
+                }
+            };
+
+            for from in &exits {
+                for to in &entries {
+                    let _ = writeln!(dot, "  {} -> {};", quote(from), quote(to));
+                }
+            }
+
+            exits = match stage.kind {
+                // A branch may match no arm, so the next stage is reachable from the decision too.
+                StageKind::Branch => entries.into_iter().chain(stage.steps.iter().map(|s| s.name.clone())).collect(),
+                _ => entries,
+            };
+        }
+
+        let _ = writeln!(dot, "  \"__end\" [shape=doublecircle, label=\"\", width=0.15, fillcolor=black];");
+        for from in &exits {
+            let _ = writeln!(dot, "  {} -> \"__end\";", quote(from));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn write_node(dot: &mut String, indent: &str, id: &str, label: &str, state: Option<StepState>, pivot: bool) {
+    let peripheries = if pivot { ", peripheries=2" } else { "" };
+    let _ = writeln!(dot, "{}{} [label={}, fillcolor={}{}];", indent, quote(id), quote(label), fill(state), peripheries);
+}
+
+fn fill(state: Option<StepState>) -> &'static str {
+    match state {
+        Some(StepState::Completed) => "palegreen",
+        Some(StepState::Current) => "gold",
+        Some(StepState::PendingCompensation) => "orange",
+        Some(StepState::Skipped) => "lightgrey",
+        Some(StepState::Pending) | None => "white",
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saga::{Branch, SagaStep};
+    use async_trait::async_trait;
+
+    struct Noop;
+
+    #[async_trait]
+    impl SagaStep for Noop {
+        type Context = u32;
+        type Error = String;
+
+        async fn execute(&self, _context: &mut u32) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn compensate(&self, _context: &mut u32) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn saga() -> SagaOrchestrator<u32, String> {
+        SagaOrchestrator::builder("checkout")
+            .step("reserve", Noop)
+            .branch("shipping", Branch::new().when(|c: &u32| *c > 0, "ship", Noop).otherwise("pickup", Noop))
+            .pivot("charge", Noop)
+            .build()
+    }
+
+    #[test]
+    fn test_describe_structure() {
+        let description = saga().describe();
+        assert_eq!(description.pivot.as_deref(), Some("charge"));
+        assert_eq!(description.stages.len(), 3);
+        assert_eq!(description.stages[1].kind, StageKind::Branch);
+        assert_eq!(description.stages[1].steps.len(), 2);
+
+        let dot = description.to_dot();
+        assert!(dot.starts_with("digraph \"checkout\""));
+        assert!(dot.contains("\"shipping?\" -> \"pickup\" [style=dashed];"));
+        assert!(dot.contains("\"pickup\" -> \"charge\";"));
+    }
+
+    #[test]
+    fn test_trace_marks_progress() {
+        let mut record = SagaRecord::new(Uuid::new_v4(), "checkout", serde_json::Value::Null);
+        record.completed_steps = 2;
+        record.branch_choices.insert("shipping".to_string(), "pickup".to_string());
+
+        let description = saga().describe().with_trace(&record);
+        let states: Vec<_> = description.stages.iter().map(|s| s.state).collect();
+        assert_eq!(states, vec![Some(StepState::Completed), Some(StepState::Completed), Some(StepState::Current)]);
+        assert_eq!(description.stages[1].steps[0].state, Some(StepState::Skipped));
+        assert_eq!(description.stages[1].steps[1].state, Some(StepState::Completed));
+    }
+}
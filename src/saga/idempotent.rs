@@ -0,0 +1,111 @@
+//! Saga steps that execute at most once per key
+//!
+//! Resumed or retried sagas re-run steps, which is only safe if every step is idempotent.
+//! Wrapping a step in `IdempotentStep` makes a step with external side effects safe to
+//! re-run: its execution is recorded in an `IdempotencyStore` under a key derived from the
+//! context, and later executions with the same key are skipped. Compensating the step
+//! forgets the key, so a fresh attempt runs it again.
+//!
+//! A skipped execution does not re-apply changes the step made to the context, so wrap
+//! steps whose effect lives outside the saga (a charge, a reservation).
+//!
+//! ```ignore
+//! let charge = IdempotentStep::new(ChargeCard, idempotency.clone(), |ctx: &OrderContext| {
+//!     format!("saga:charge:{}", ctx.order_id)
+//! });
+//! let saga = Saga::builder("order_checkout").pivot("charge", charge).build();
+//! ```
+
+use async_trait::async_trait;
+
+use super::SagaStep;
+use crate::idempotency::{Idempotency, IdempotencyError, RunError};
+
+type KeyFn<C> = Box<dyn Fn(&C) -> String + Send + Sync>;
+
+pub struct IdempotentStep<S: SagaStep> {
+    step: S,
+    idempotency: Idempotency,
+    key: KeyFn<S::Context>,
+}
+
+impl<S: SagaStep> IdempotentStep<S> {
+    pub fn new<K>(step: S, idempotency: Idempotency, key: K) -> Self
+    where
+        K: Fn(&S::Context) -> String + Send + Sync + 'static,
+    {
+        Self { step, idempotency, key: Box::new(key) }
+    }
+}
+
+#[async_trait]
+impl<S> SagaStep for IdempotentStep<S>
+where
+    S: SagaStep,
+    S::Context: Send + Sync,
+    S::Error: From<IdempotencyError> + Send,
+{
+    type Context = S::Context;
+    type Error = S::Error;
+
+    async fn execute(&self, context: &mut S::Context) -> Result<(), S::Error> {
+        let key = (self.key)(context);
+        let step = &self.step;
+        self.idempotency.run(&key, || step.execute(context)).await.map_err(|e| match e {
+            RunError::Idempotency(e) => e.into(),
+            RunError::Operation(e) => e,
+        })
+    }
+
+    async fn compensate(&self, context: &mut S::Context) -> Result<(), S::Error> {
+        let key = (self.key)(context);
+        self.step.compensate(context).await?;
+        self.idempotency.store().remove(&key).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idempotency::InMemoryIdempotencyStore;
+    use std::sync::Arc;
+
+    #[derive(Debug, thiserror::Error)]
+    enum StepError {
+        #[error(transparent)]
+        Idempotency(#[from] IdempotencyError),
+    }
+
+    struct Charge;
+
+    #[async_trait]
+    impl SagaStep for Charge {
+        type Context = (u32, u32);
+        type Error = StepError;
+
+        async fn execute(&self, context: &mut (u32, u32)) -> Result<(), StepError> {
+            context.1 += 1;
+            Ok(())
+        }
+
+        async fn compensate(&self, _context: &mut (u32, u32)) -> Result<(), StepError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skips_repeated_execution_until_compensated() {
+        let idempotency = Idempotency::new(Arc::new(InMemoryIdempotencyStore::new()));
+        let step = IdempotentStep::new(Charge, idempotency, |ctx: &(u32, u32)| format!("charge:{}", ctx.0));
+        let mut context = (7, 0);
+
+        step.execute(&mut context).await.unwrap();
+        step.execute(&mut context).await.unwrap();
+        assert_eq!(context.1, 1);
+
+        step.compensate(&mut context).await.unwrap();
+        step.execute(&mut context).await.unwrap();
+        assert_eq!(context.1, 2);
+    }
+}
@@ -0,0 +1,54 @@
+//! Saga metrics
+//!
+//! Step and saga outcomes are exported through the global OpenTelemetry meter, labelled
+//! with the saga and step names declared in the builder.
+
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::{global, KeyValue};
+use std::time::Duration;
+
+pub(crate) struct SagaMetrics {
+    runs: Counter<u64>,
+    steps: Counter<u64>,
+    step_duration: Histogram<f64>,
+}
+
+impl SagaMetrics {
+    pub(crate) fn new() -> Self {
+        let meter = global::meter("lanai.saga");
+        Self {
+            runs: meter
+                .u64_counter("saga_runs_total")
+                .with_description("Sagas that reached a terminal state, by outcome")
+                .build(),
+            steps: meter
+                .u64_counter("saga_steps_total")
+                .with_description("Saga step executions and compensations, by outcome")
+                .build(),
+            step_duration: meter
+                .f64_histogram("saga_step_duration_seconds")
+                .with_description("Duration of saga step executions and compensations")
+                .with_unit("s")
+                .build(),
+        }
+    }
+
+    /// Record one `execute` or `compensate` call (including its retries).
+    pub(crate) fn record_step(&self, saga: &str, step: &str, phase: &'static str, outcome: &'static str, elapsed: Duration) {
+        let attributes = [
+            KeyValue::new("saga", saga.to_string()),
+            KeyValue::new("step", step.to_string()),
+            KeyValue::new("phase", phase),
+            KeyValue::new("outcome", outcome),
+        ];
+        self.steps.add(1, &attributes);
+        self.step_duration.record(elapsed.as_secs_f64(), &attributes);
+    }
+
+    pub(crate) fn record_run(&self, saga: &str, outcome: &'static str) {
+        self.runs.add(1, &[
+            KeyValue::new("saga", saga.to_string()),
+            KeyValue::new("outcome", outcome),
+        ]);
+    }
+}
@@ -1,59 +1,127 @@
 use async_trait::async_trait;
-use log::{info, error, warn};
 use std::fmt::Debug;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod admin;
+pub mod branch;
+pub mod builder;
+pub mod choreography;
+pub mod describe;
+pub mod idempotent;
+mod metrics;
+pub mod orchestrator;
+pub mod parallel;
+pub mod remote;
+pub mod retry;
+pub mod signal;
+pub mod store;
+
+pub use admin::SagaAdmin;
+pub use branch::Branch;
+pub use builder::SagaBuilder;
+pub use choreography::{Choreography, ChoreographyHandle, EventContext, Reaction};
+pub use describe::{SagaDescription, SagaTrace, StageDescription, StageKind, StepDescription, StepState};
+pub use idempotent::IdempotentStep;
+pub use orchestrator::{Saga, SagaOrchestrator};
+pub use parallel::ParallelGroup;
+pub use remote::{RemoteStep, RemoteStepError};
+pub use retry::{RetryPolicy, StepPolicy};
+pub use signal::{SagaSignal, SignalError, SignalOutcome, SignalTarget};
+pub use store::{InMemorySagaStore, RedisSagaStore, SagaRecord, SagaStatus, SagaStore, SagaStoreError};
 
 #[async_trait]
-pub trait SagaStep: Send + Sync + Debug {
+pub trait SagaStep: Send + Sync {
     type Context;
     type Error: Debug + std::fmt::Display;
 
     async fn execute(&self, context: &mut Self::Context) -> Result<(), Self::Error>;
-    async fn compensate(&self, context: &mut Self::Context);
+    async fn compensate(&self, context: &mut Self::Context) -> Result<(), Self::Error>;
 }
 
-pub struct SagaOrchestrator<C, E> {
-    steps: Vec<Box<dyn SagaStep<Context = C, Error = E>>>,
-}
+/// Errors returned by the saga orchestrator.
+#[derive(Debug, Error)]
+pub enum SagaError<E> {
+    /// A step failed; compensation has already been run.
+    #[error("Saga step '{step}' failed: {error}")]
+    Step { step: String, error: E },
 
-impl<C, E> SagaOrchestrator<C, E> 
-where 
-    E: Debug + std::fmt::Display,
-    C: Debug
-{
-    pub fn new() -> Self {
-        Self { steps: Vec::new() }
-    }
+    /// A step exceeded its own deadline; compensation has already been run.
+    #[error("Saga step '{step}' timed out after {timeout:?}")]
+    StepTimeout { step: String, timeout: Duration },
 
-    pub fn add_step(&mut self, step: Box<dyn SagaStep<Context = C, Error = E>>) {
-        self.steps.push(step);
-    }
+    /// The whole saga exceeded its deadline; compensation has already been run.
+    #[error("Saga {0} exceeded its deadline of {1:?}")]
+    DeadlineExceeded(Uuid, Duration),
 
-    pub async fn run(&self, mut context: C) -> Result<C, E> {
-        info!("🎬 Starting Saga with context: {:?}", context);
-        let mut executed_steps = Vec::new();
-
-        for (i, step) in self.steps.iter().enumerate() {
-            info!("⚙️ Executing step {}: {:?}", i + 1, step);
-            match step.execute(&mut context).await {
-                Ok(_) => {
-                    executed_steps.push(step);
-                }
-                Err(e) => {
-                    error!("❌ Step {} failed: {}. Starting compensation...", i + 1, e);
-                    self.compensate(executed_steps, &mut context).await;
-                    return Err(e);
-                }
-            }
-        }
-
-        info!("🎉 Saga completed successfully!");
-        Ok(context)
-    }
+    #[error("Saga store error: {0}")]
+    Store(#[from] SagaStoreError),
+
+    #[error("No saga store configured on this orchestrator")]
+    NoStore,
+
+    #[error("Saga {0} not found")]
+    NotFound(Uuid),
+
+    /// The saga already reached a terminal state.
+    #[error("Saga {0} is not in flight ({1:?})")]
+    NotInFlight(Uuid, SagaStatus),
+
+    /// The persisted record does not match this orchestrator's step list.
+    #[error("Saga {0} does not match the orchestrator definition: {1}")]
+    DefinitionMismatch(Uuid, String),
 
-    async fn compensate(&self, executed_steps: Vec<&Box<dyn SagaStep<Context = C, Error = E>>>, context: &mut C) {
-        for step in executed_steps.into_iter().rev() {
-            warn!("🔄 Compensating step: {:?}", step);
-            step.compensate(context).await;
-        }
+    /// A step after the pivot kept failing; the saga stays in flight and must be resumed later.
+    #[error("Saga step '{step}' after the pivot failed: {reason}")]
+    Stuck { step: String, reason: String },
+
+    /// Compensation was requested for a saga that already passed its pivot step.
+    #[error("Saga {0} already passed its pivot step and cannot be compensated")]
+    PastPivot(Uuid),
+
+    /// Forward execution was abandoned and compensation finished successfully.
+    #[error("Saga {0} was compensated")]
+    Compensated(Uuid),
+
+    /// Not a failure: the saga is parked at a wait step until it receives a signal.
+    #[error("Saga {saga_id} is waiting for a signal at step '{step}'")]
+    Waiting { saga_id: Uuid, step: String },
+
+    /// A signal was sent to a saga that is not parked at a wait step.
+    #[error("Saga {0} is not waiting for a signal ({1:?})")]
+    NotWaiting(Uuid, SagaStatus),
+}
+
+impl<E> SagaError<E> {
+    /// Returns true if the saga was rolled back (as opposed to failing to run at all).
+    pub fn is_compensated(&self) -> bool {
+        matches!(
+            self,
+            Self::Step { .. } | Self::StepTimeout { .. } | Self::DeadlineExceeded(..) | Self::Compensated(_)
+        )
     }
 }
+
+/// What to do with sagas that were still `Running` when the process stopped.
+/// Sagas parked at a wait step are never touched by recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryPolicy {
+    /// Continue executing the remaining steps (steps must be idempotent).
+    Resume,
+    /// Roll back the steps that already executed.
+    Compensate,
+}
+
+/// Outcome of a recovery scan.
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    /// Sagas that ran to completion after resumption.
+    pub completed: Vec<Uuid>,
+    /// Sagas that ended compensated (by policy or after a failing step).
+    pub compensated: Vec<Uuid>,
+    /// Sagas that could not be recovered, with the reason.
+    pub failed: Vec<(Uuid, String)>,
+    /// Sagas left parked at a wait step; they continue when signalled.
+    pub waiting: Vec<Uuid>,
+}
@@ -0,0 +1,948 @@
+//! Saga execution engine
+//!
+//! Drives a saga definition built with `SagaBuilder`: executes stages in order, checkpoints
+//! progress into the configured `SagaStore`, and compensates executed steps in reverse
+//! order when a step fails or times out.
+
+use futures_util::future::join_all;
+use log::{info, error, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::builder::SagaBuilder;
+use super::metrics::SagaMetrics;
+use super::{
+    Branch, ParallelGroup, RecoveryPolicy, RecoveryReport, RetryPolicy, SagaError, SagaRecord, SagaSignal,
+    SagaStatus, SagaStep, SagaStore, SagaStoreError, StepPolicy,
+};
+
+pub(crate) type Condition<C> = Box<dyn Fn(&C) -> bool + Send + Sync>;
+
+/// A registered step together with its name and execution policy.
+pub(crate) struct StepEntry<C, E> {
+    pub(crate) name: String,
+    pub(crate) step: Box<dyn SagaStep<Context = C, Error = E>>,
+    pub(crate) policy: StepPolicy<E>,
+}
+
+impl<C, E> StepEntry<C, E> {
+    pub(crate) fn new<S>(name: &str, step: S, policy: StepPolicy<E>) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        Self { name: name.to_string(), step: Box::new(step), policy }
+    }
+}
+
+/// A unit of sequential progress: a single step, a group of concurrent steps, a
+/// conditional choice between steps, or a point where the saga waits for a signal.
+pub(crate) enum Stage<C, E> {
+    Single(StepEntry<C, E>),
+    Parallel { name: String, group: ParallelGroup<C, E> },
+    Branch { name: String, branch: Branch<C, E> },
+    Wait { name: String, condition: Option<Condition<C>> },
+}
+
+impl<C, E> Stage<C, E> {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::Single(entry) => &entry.name,
+            Self::Parallel { name, .. } | Self::Branch { name, .. } | Self::Wait { name, .. } => name,
+        }
+    }
+
+    /// Returns true if the saga must park here, given the context at this point.
+    pub(crate) fn waits_for(&self, context: &C) -> bool {
+        match self {
+            Self::Wait { condition: Some(condition), .. } => condition(context),
+            Self::Wait { condition: None, .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Names of every step in this stage (the stage itself and any group members).
+    pub(crate) fn step_names(&self) -> Vec<&str> {
+        match self {
+            Self::Single(entry) => vec![entry.name.as_str()],
+            Self::Parallel { name, group } => std::iter::once(name.as_str())
+                .chain(group.members.iter().map(|m| m.name.as_str()))
+                .collect(),
+            // A conditional step is a single-arm branch named after its only step.
+            Self::Branch { name, branch } => std::iter::once(name.as_str())
+                .chain(branch.arms.iter().map(|(_, arm)| arm.name.as_str()).filter(|arm| *arm != name.as_str()))
+                .collect(),
+            Self::Wait { name, .. } => vec![name.as_str()],
+        }
+    }
+}
+
+/// Serializes the context for a checkpoint. Captured by `SagaBuilder::store`, so only sagas
+/// that persist their progress need a `Serialize` context.
+pub(crate) type Snapshot<C> = fn(&C) -> serde_json::Result<serde_json::Value>;
+
+/// Alias used with the builder: `Saga::builder("order_checkout").step(...).build()`.
+pub type Saga<C, E> = SagaOrchestrator<C, E>;
+
+pub struct SagaOrchestrator<C, E> {
+    pub(crate) name: String,
+    pub(crate) steps: Vec<Stage<C, E>>,
+    pub(crate) store: Option<(Arc<dyn SagaStore>, Snapshot<C>)>,
+    pub(crate) timeout: Option<Duration>,
+    /// Index of the pivot stage; stages after it are retried forward instead of compensated.
+    pub(crate) pivot: Option<usize>,
+    pub(crate) forward_retry: RetryPolicy<SagaError<E>>,
+    pub(crate) metrics: SagaMetrics,
+}
+
+impl<C, E> SagaOrchestrator<C, E> {
+    /// Start defining a saga. `name` identifies the definition in persisted records and metrics.
+    pub fn builder(name: &str) -> SagaBuilder<C, E> {
+        SagaBuilder::new(name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn store(&self) -> Result<&dyn SagaStore, SagaError<E>> {
+        self.store.as_ref().map(|(store, _)| store.as_ref()).ok_or(SagaError::NoStore)
+    }
+
+    /// Returns true once a saga with `completed_steps` stages done can no longer be compensated.
+    pub(crate) fn pivot_passed(&self, completed_steps: usize) -> bool {
+        self.pivot.is_some_and(|pivot| completed_steps > pivot)
+    }
+}
+
+impl<C, E> SagaOrchestrator<C, E>
+where
+    E: Debug + std::fmt::Display,
+    C: Debug,
+{
+    pub async fn run(&self, context: C) -> Result<C, SagaError<E>> {
+        self.run_with_id(Uuid::new_v4(), context).await
+    }
+
+    /// Run the saga under a caller-chosen ID (e.g. the order ID) so it can be resumed later.
+    pub async fn run_with_id(&self, saga_id: Uuid, context: C) -> Result<C, SagaError<E>> {
+        info!("🎬 Starting Saga '{}' ({}) with context: {:?}", self.name, saga_id, context);
+        let mut record = SagaRecord::new(saga_id, &self.name, serde_json::Value::Null);
+        record.traceparent = crate::observability::current_traceparent();
+        self.drive(&mut record, context).await
+    }
+
+    /// Span of one execution of a saga, linked to the span that started it when that was in
+    /// another trace (a resumed or recovered saga).
+    fn span(&self, record: &SagaRecord) -> tracing::Span {
+        let span = tracing::info_span!("saga", saga.name = %self.name, saga.id = %record.saga_id);
+        crate::observability::link_span_to_traceparent(&span, record.traceparent.as_deref());
+        span
+    }
+
+    async fn drive(&self, record: &mut SagaRecord, context: C) -> Result<C, SagaError<E>> {
+        let span = self.span(record);
+        self.drive_steps(record, context).instrument(span).await
+    }
+
+    async fn drive_steps(&self, record: &mut SagaRecord, mut context: C) -> Result<C, SagaError<E>> {
+        self.checkpoint(record, &context).await;
+        let saga_deadline = self.deadline(record);
+
+        for (i, stage) in self.steps.iter().enumerate().skip(record.completed_steps) {
+            if stage.waits_for(&context) {
+                record.status = SagaStatus::Waiting;
+                self.checkpoint(record, &context).await;
+                self.metrics.record_run(&self.name, "waiting");
+                info!("⏸️ Saga '{}' ({}) waiting for a signal at step '{}'", self.name, record.saga_id, stage.name());
+                return Err(SagaError::Waiting { saga_id: record.saga_id, step: stage.name().to_string() });
+            }
+
+            let past_pivot = self.pivot_passed(i);
+            let result = if past_pivot {
+                self.execute_forward(record, stage, &mut context).await
+            } else {
+                self.execute_stage(record, stage, &mut context, saga_deadline).await
+            };
+
+            match result {
+                Ok(_) => {
+                    record.completed_steps = i + 1;
+                    record.completed_members.clear();
+                    self.checkpoint(record, &context).await;
+                    if self.pivot == Some(i) {
+                        info!("📌 Saga '{}' passed pivot step '{}'; remaining steps will only be retried forward",
+                              self.name, stage.name());
+                    }
+                }
+                Err(e) if past_pivot => {
+                    error!("❌ Saga '{}': step '{}' after the pivot failed: {}. Leaving saga in flight for forward recovery.",
+                           self.name, stage.name(), e);
+                    record.error = Some(e.to_string());
+                    self.checkpoint(record, &context).await;
+                    self.metrics.record_run(&self.name, "stuck");
+                    return Err(SagaError::Stuck { step: stage.name().to_string(), reason: e.to_string() });
+                }
+                Err(e) => {
+                    error!("❌ Saga '{}': step '{}' failed: {}. Starting compensation...", self.name, stage.name(), e);
+                    record.status = SagaStatus::Compensating;
+                    record.error = Some(e.to_string());
+                    self.checkpoint(record, &context).await;
+                    self.compensate(record, &mut context).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        record.status = SagaStatus::Completed;
+        self.checkpoint(record, &context).await;
+        self.metrics.record_run(&self.name, "completed");
+        info!("🎉 Saga '{}' completed successfully!", self.name);
+        Ok(context)
+    }
+
+    async fn execute_stage(
+        &self,
+        record: &mut SagaRecord,
+        stage: &Stage<C, E>,
+        context: &mut C,
+        saga_deadline: Option<Instant>,
+    ) -> Result<(), SagaError<E>> {
+        match stage {
+            Stage::Single(entry) => {
+                info!("⚙️ Saga '{}': executing step '{}'", self.name, entry.name);
+                self.execute_with_deadline(record.saga_id, entry, context, saga_deadline).await
+            }
+            Stage::Parallel { name, group } => {
+                info!("⚙️ Saga '{}': executing parallel group '{}' ({} steps)", self.name, name, group.len());
+                self.execute_group(record, group, context, saga_deadline).await
+            }
+            Stage::Branch { name, branch } => match branch.select(context) {
+                Some(entry) => {
+                    info!("⚙️ Saga '{}': branch '{}' selected step '{}'", self.name, name, entry.name);
+                    let result = self.execute_with_deadline(record.saga_id, entry, context, saga_deadline).await;
+                    if result.is_ok() {
+                        record.branch_choices.insert(name.clone(), entry.name.clone());
+                    }
+                    result
+                }
+                None => {
+                    info!("⏭️ Saga '{}': skipping '{}' (no condition matched)", self.name, name);
+                    self.metrics.record_step(&self.name, name, "execute", "skipped", Duration::ZERO);
+                    Ok(())
+                }
+            },
+            // Only reached when the wait's condition did not hold.
+            Stage::Wait { name, .. } => {
+                info!("⏭️ Saga '{}': skipping wait '{}' (condition not met)", self.name, name);
+                self.metrics.record_step(&self.name, name, "execute", "skipped", Duration::ZERO);
+                Ok(())
+            }
+        }
+    }
+
+    /// Execute a stage after the pivot: failures are retried with the forward-recovery policy
+    /// and the saga deadline no longer applies, since rolling back is not an option.
+    async fn execute_forward(&self, record: &mut SagaRecord, stage: &Stage<C, E>, context: &mut C) -> Result<(), SagaError<E>> {
+        let mut attempt = 1;
+        loop {
+            match self.execute_stage(record, stage, context, None).await {
+                Ok(()) => return Ok(()),
+                Err(e) if self.forward_retry.should_retry(&e, attempt) => {
+                    let delay = self.forward_retry.backoff(attempt);
+                    warn!("🔁 Saga '{}': step '{}' after the pivot failed (attempt {}/{}): {}. Retrying forward in {:?}...",
+                          self.name, stage.name(), attempt, self.forward_retry.max_attempts, e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Compensate executed steps in reverse order.
+    ///
+    /// Members of a partially completed parallel group are compensated first. A step whose
+    /// compensation still fails after its retries is logged and skipped so the remaining
+    /// steps are rolled back; the saga then ends as `CompensationFailed`.
+    async fn compensate(&self, record: &mut SagaRecord, context: &mut C) {
+        let mut failed = false;
+
+        if let Some(Stage::Parallel { group, .. }) = self.steps.get(record.completed_steps) {
+            for member in record.completed_members.clone().into_iter().rev() {
+                if let Some(entry) = group.members.get(member) {
+                    failed |= !self.compensate_entry(record, entry, context).await;
+                }
+            }
+        }
+        record.completed_members.clear();
+
+        while record.completed_steps > 0 {
+            match &self.steps[record.completed_steps - 1] {
+                Stage::Single(entry) => {
+                    failed |= !self.compensate_entry(record, entry, context).await;
+                }
+                Stage::Parallel { group, .. } => {
+                    for entry in group.members.iter().rev() {
+                        failed |= !self.compensate_entry(record, entry, context).await;
+                    }
+                }
+                Stage::Branch { name, branch } => {
+                    // Only the arm that actually ran is compensated; skipped branches have no choice recorded.
+                    let chosen = record.branch_choices.get(name).and_then(|arm| branch.arm(arm));
+                    if let Some(entry) = chosen {
+                        failed |= !self.compensate_entry(record, entry, context).await;
+                    }
+                }
+                Stage::Wait { .. } => {}
+            }
+            record.completed_steps -= 1;
+            self.checkpoint(record, context).await;
+        }
+
+        record.status = if failed { SagaStatus::CompensationFailed } else { SagaStatus::Compensated };
+        self.checkpoint(record, context).await;
+        self.metrics.record_run(&self.name, if failed { "compensation_failed" } else { "compensated" });
+    }
+
+    /// Compensate a single step, bounded by its timeout. Returns false if compensation failed.
+    async fn compensate_entry(&self, record: &mut SagaRecord, entry: &StepEntry<C, E>, context: &mut C) -> bool {
+        warn!("🔄 Saga '{}': compensating step '{}'", self.name, entry.name);
+        let started = Instant::now();
+        let outcome = match entry.policy.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, self.compensate_step(entry, context)).await {
+                Ok(result) => result.map_err(|e| e.to_string()),
+                Err(_) => Err(format!("timed out after {:?}", timeout)),
+            },
+            None => self.compensate_step(entry, context).await.map_err(|e| e.to_string()),
+        };
+
+        match outcome {
+            Ok(()) => {
+                self.metrics.record_step(&self.name, &entry.name, "compensate", "success", started.elapsed());
+                true
+            }
+            Err(e) => {
+                self.metrics.record_step(&self.name, &entry.name, "compensate", "failure", started.elapsed());
+                error!("❌ Saga '{}': compensation of step '{}' failed: {}. Manual intervention required.",
+                       self.name, entry.name, e);
+                record.error = Some(format!("compensation of step '{}' failed: {}", entry.name, e));
+                false
+            }
+        }
+    }
+
+    /// Execute the members of a parallel group concurrently, skipping members already completed
+    /// before a restart. Successful members are merged into `context` and recorded.
+    async fn execute_group(
+        &self,
+        record: &mut SagaRecord,
+        group: &ParallelGroup<C, E>,
+        context: &mut C,
+        saga_deadline: Option<Instant>,
+    ) -> Result<(), SagaError<E>> {
+        let saga_id = record.saga_id;
+        let pending: Vec<usize> = (0..group.members.len())
+            .filter(|member| !record.completed_members.contains(member))
+            .collect();
+        let mut forks: Vec<C> = pending.iter().map(|_| (group.fork)(context)).collect();
+
+        let results = join_all(pending.iter().zip(forks.iter_mut()).map(|(&member, fork)| {
+            self.execute_with_deadline(saga_id, &group.members[member], fork, saga_deadline)
+        }))
+        .await;
+
+        let mut first_error = None;
+        for ((member, fork), result) in pending.into_iter().zip(forks).zip(results) {
+            match result {
+                Ok(()) => {
+                    (group.merge)(context, fork);
+                    record.completed_members.push(member);
+                }
+                Err(e) => {
+                    error!("❌ Saga '{}': parallel step '{}' failed: {}", self.name, group.members[member].name, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Deadline for the whole saga, if one is configured.
+    fn deadline(&self, record: &SagaRecord) -> Option<Instant> {
+        let timeout = self.timeout?;
+        let elapsed = (chrono::Utc::now() - record.created_at).to_std().unwrap_or_default();
+        Some(Instant::now() + timeout.saturating_sub(elapsed))
+    }
+
+    /// Execute a step (with retries) bounded by the earlier of its own timeout and the saga deadline.
+    async fn execute_with_deadline(
+        &self,
+        saga_id: Uuid,
+        entry: &StepEntry<C, E>,
+        context: &mut C,
+        saga_deadline: Option<Instant>,
+    ) -> Result<(), SagaError<E>> {
+        let started = Instant::now();
+        let step_deadline = entry.policy.timeout.map(|t| started + t);
+        let deadline = match (step_deadline, saga_deadline) {
+            (Some(step), Some(saga)) => Some(step.min(saga)),
+            (step, saga) => step.or(saga),
+        };
+
+        let result = match deadline {
+            None => self.execute_step(entry, context).await.map_err(|error| SagaError::Step {
+                step: entry.name.clone(),
+                error,
+            }),
+            Some(deadline) => match tokio::time::timeout_at(deadline, self.execute_step(entry, context)).await {
+                Ok(result) => result.map_err(|error| SagaError::Step { step: entry.name.clone(), error }),
+                Err(_) if Some(deadline) == step_deadline => Err(SagaError::StepTimeout {
+                    step: entry.name.clone(),
+                    timeout: entry.policy.timeout.unwrap_or_default(),
+                }),
+                Err(_) => Err(SagaError::DeadlineExceeded(saga_id, self.timeout.unwrap_or_default())),
+            },
+        };
+
+        let outcome = match &result {
+            Ok(()) => "success",
+            Err(SagaError::Step { .. }) => "failure",
+            Err(_) => "timeout",
+        };
+        self.metrics.record_step(&self.name, &entry.name, "execute", outcome, started.elapsed());
+        result
+    }
+
+    async fn execute_step(&self, entry: &StepEntry<C, E>, context: &mut C) -> Result<(), E> {
+        let mut attempt = 1;
+        loop {
+            match entry.step.execute(context).await {
+                Ok(()) => return Ok(()),
+                Err(e) if entry.policy.execute.should_retry(&e, attempt) => {
+                    let delay = entry.policy.execute.backoff(attempt);
+                    warn!("🔁 Saga '{}': step '{}' failed (attempt {}/{}): {}. Retrying in {:?}...",
+                          self.name, entry.name, attempt, entry.policy.execute.max_attempts, e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn compensate_step(&self, entry: &StepEntry<C, E>, context: &mut C) -> Result<(), E> {
+        let mut attempt = 1;
+        loop {
+            match entry.step.compensate(context).await {
+                Ok(()) => return Ok(()),
+                Err(e) if entry.policy.compensate.should_retry(&e, attempt) => {
+                    let delay = entry.policy.compensate.backoff(attempt);
+                    warn!("🔁 Saga '{}': compensation of step '{}' failed (attempt {}/{}): {}. Retrying in {:?}...",
+                          self.name, entry.name, attempt, entry.policy.compensate.max_attempts, e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Persist the current progress. Store failures are logged but do not abort the saga.
+    async fn checkpoint(&self, record: &mut SagaRecord, context: &C) {
+        let Some((store, snapshot)) = &self.store else { return };
+
+        record.context = match snapshot(context) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("❌ Failed to serialize context for Saga {}: {}", record.saga_id, e);
+                return;
+            }
+        };
+        record.last_step = record
+            .completed_steps
+            .checked_sub(1)
+            .and_then(|i| self.steps.get(i))
+            .map(|stage| stage.name().to_string());
+        record.updated_at = chrono::Utc::now();
+
+        if let Err(e) = store.save(record).await {
+            error!("❌ Failed to checkpoint Saga {}: {}", record.saga_id, e);
+        }
+    }
+}
+
+/// Resuming needs the context back from its checkpoint, hence `DeserializeOwned`.
+impl<C, E> SagaOrchestrator<C, E>
+where
+    E: Debug + std::fmt::Display,
+    C: Debug + Serialize + DeserializeOwned,
+{
+    /// Resume a persisted saga from its last checkpoint.
+    ///
+    /// `Running` sagas continue with the next step; `Compensating` sagas finish compensation
+    /// and return `SagaError::Compensated`.
+    pub async fn resume(&self, saga_id: Uuid) -> Result<C, SagaError<E>> {
+        let mut record = self.load_in_flight(saga_id).await?;
+        let context = self.restore_context(&record)?;
+
+        match record.status {
+            SagaStatus::Running => {
+                let next = self.steps.get(record.completed_steps).map(|s| s.name()).unwrap_or("<end>");
+                info!("⏯️ Resuming Saga '{}' ({}) at step '{}'", self.name, saga_id, next);
+                self.drive(&mut record, context).await
+            }
+            SagaStatus::Waiting => Err(SagaError::Waiting {
+                saga_id,
+                step: self.steps.get(record.completed_steps).map(|s| s.name()).unwrap_or("<end>").to_string(),
+            }),
+            _ => {
+                info!("⏯️ Resuming compensation of Saga '{}' ({})", self.name, saga_id);
+                let mut context = context;
+                let span = self.span(&record);
+                self.compensate(&mut record, &mut context).instrument(span).await;
+                Err(SagaError::Compensated(saga_id))
+            }
+        }
+    }
+
+    /// Abandon a persisted saga and compensate the steps it already executed.
+    ///
+    /// Fails with `SagaError::PastPivot` if the saga already completed its pivot step.
+    pub async fn compensate_saga(&self, saga_id: Uuid) -> Result<C, SagaError<E>> {
+        let mut record = self.load_in_flight(saga_id).await?;
+        if record.status != SagaStatus::Compensating && self.pivot_passed(record.completed_steps) {
+            return Err(SagaError::PastPivot(saga_id));
+        }
+        let mut context = self.restore_context(&record)?;
+
+        warn!("↩️ Compensating Saga '{}' ({}) on request", self.name, saga_id);
+        record.status = SagaStatus::Compensating;
+        self.checkpoint(&mut record, &context).await;
+        let span = self.span(&record);
+        self.compensate(&mut record, &mut context).instrument(span).await;
+        Ok(context)
+    }
+
+    /// Resume or abort a saga parked at a wait step.
+    ///
+    /// `Resume` continues with the step after the wait (the saga deadline still counts the
+    /// time spent waiting); `Abort` compensates the executed steps and returns
+    /// `SagaError::Compensated`, unless the saga already passed its pivot.
+    pub async fn signal(&self, saga_id: Uuid, signal: SagaSignal) -> Result<C, SagaError<E>> {
+        let mut record = self.load_in_flight(saga_id).await?;
+        if record.status != SagaStatus::Waiting {
+            return Err(SagaError::NotWaiting(saga_id, record.status));
+        }
+        let mut context = self.restore_context(&record)?;
+        let step = self.steps.get(record.completed_steps).map(|s| s.name()).unwrap_or("<end>");
+
+        match signal {
+            SagaSignal::Resume => {
+                info!("▶️ Saga '{}' ({}) resumed at wait step '{}'", self.name, saga_id, step);
+                record.completed_steps += 1;
+                record.status = SagaStatus::Running;
+                self.drive(&mut record, context).await
+            }
+            SagaSignal::Abort { reason } => {
+                if self.pivot_passed(record.completed_steps) {
+                    return Err(SagaError::PastPivot(saga_id));
+                }
+                warn!("⏹️ Saga '{}' ({}) aborted at wait step '{}': {}", self.name, saga_id, step, reason);
+                record.status = SagaStatus::Compensating;
+                record.error = Some(format!("aborted at '{}': {}", step, reason));
+                self.checkpoint(&mut record, &context).await;
+                let span = self.span(&record);
+                self.compensate(&mut record, &mut context).instrument(span).await;
+                Err(SagaError::Compensated(saga_id))
+            }
+        }
+    }
+
+    /// Find every in-flight saga of this definition and drive it to a terminal state.
+    ///
+    /// Sagas past their pivot step are always resumed, regardless of `policy`.
+    /// Intended to be called once on startup, before new sagas are accepted.
+    pub async fn recover(&self, policy: RecoveryPolicy) -> Result<RecoveryReport, SagaError<E>> {
+        let store = self.store()?;
+        let records = store.list_in_flight(&self.name).await?;
+        info!("🔎 Saga recovery for '{}': {} in-flight saga(s) found", self.name, records.len());
+
+        let mut report = RecoveryReport::default();
+        for record in records {
+            let saga_id = record.saga_id;
+            let result = match (record.status, policy) {
+                (SagaStatus::Running, RecoveryPolicy::Compensate) if !self.pivot_passed(record.completed_steps) => {
+                    self.compensate_saga(saga_id)
+                        .await
+                        .and_then(|_| Err(SagaError::Compensated(saga_id)))
+                }
+                _ => self.resume(saga_id).await,
+            };
+
+            match result {
+                Ok(_) => report.completed.push(saga_id),
+                Err(SagaError::Waiting { .. }) => report.waiting.push(saga_id),
+                Err(e) if e.is_compensated() => report.compensated.push(saga_id),
+                Err(e) => {
+                    error!("❌ Failed to recover Saga {}: {}", saga_id, e);
+                    report.failed.push((saga_id, e.to_string()));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn load_in_flight(&self, saga_id: Uuid) -> Result<SagaRecord, SagaError<E>> {
+        let store = self.store()?;
+        let record = store.load(saga_id).await?.ok_or(SagaError::NotFound(saga_id))?;
+
+        if !record.status.is_in_flight() {
+            return Err(SagaError::NotInFlight(saga_id, record.status));
+        }
+        if record.saga_name != self.name {
+            return Err(SagaError::DefinitionMismatch(
+                saga_id,
+                format!("record belongs to saga '{}'", record.saga_name),
+            ));
+        }
+        if record.completed_steps > self.steps.len() {
+            return Err(SagaError::DefinitionMismatch(
+                saga_id,
+                format!("{} steps completed but only {} defined", record.completed_steps, self.steps.len()),
+            ));
+        }
+        if let Some(last_step) = &record.last_step {
+            let expected = record.completed_steps.checked_sub(1).map(|i| self.steps[i].name()).unwrap_or("<none>");
+            if last_step != expected {
+                return Err(SagaError::DefinitionMismatch(
+                    saga_id,
+                    format!("last completed step was '{}' but the definition has '{}' there", last_step, expected),
+                ));
+            }
+        }
+        Ok(record)
+    }
+
+    fn restore_context(&self, record: &SagaRecord) -> Result<C, SagaError<E>> {
+        serde_json::from_value(record.context.clone())
+            .map_err(|e| SagaError::Store(SagaStoreError::Serialization(e.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saga::InMemorySagaStore;
+    use async_trait::async_trait;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    struct OrderContext {
+        reserved: bool,
+        charged: bool,
+    }
+
+    struct Reserve;
+
+    #[async_trait]
+    impl SagaStep for Reserve {
+        type Context = OrderContext;
+        type Error = String;
+
+        async fn execute(&self, context: &mut OrderContext) -> Result<(), String> {
+            context.reserved = true;
+            Ok(())
+        }
+
+        async fn compensate(&self, context: &mut OrderContext) -> Result<(), String> {
+            context.reserved = false;
+            Ok(())
+        }
+    }
+
+    struct Charge;
+
+    #[async_trait]
+    impl SagaStep for Charge {
+        type Context = OrderContext;
+        type Error = String;
+
+        async fn execute(&self, context: &mut OrderContext) -> Result<(), String> {
+            context.charged = true;
+            Ok(())
+        }
+
+        async fn compensate(&self, context: &mut OrderContext) -> Result<(), String> {
+            context.charged = false;
+            Ok(())
+        }
+    }
+
+    struct Stalled;
+
+    #[async_trait]
+    impl SagaStep for Stalled {
+        type Context = OrderContext;
+        type Error = String;
+
+        async fn execute(&self, _context: &mut OrderContext) -> Result<(), String> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+
+        async fn compensate(&self, _context: &mut OrderContext) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl SagaStep for Failing {
+        type Context = OrderContext;
+        type Error = String;
+
+        async fn execute(&self, _context: &mut OrderContext) -> Result<(), String> {
+            Err("erp unavailable".to_string())
+        }
+
+        async fn compensate(&self, _context: &mut OrderContext) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn merge(context: &mut OrderContext, member: OrderContext) {
+        context.reserved |= member.reserved;
+        context.charged |= member.charged;
+    }
+
+    fn orchestrator(store: Arc<InMemorySagaStore>) -> SagaOrchestrator<OrderContext, String> {
+        Saga::builder("order")
+            .step("reserve_stock", Reserve)
+            .step("capture_payment", Charge)
+            .store(store)
+            .build()
+    }
+
+    fn interrupted_record(status: SagaStatus) -> SagaRecord {
+        let context = OrderContext { reserved: true, charged: false };
+        let mut record = SagaRecord::new(Uuid::new_v4(), "order", serde_json::to_value(context).unwrap());
+        record.status = status;
+        record.completed_steps = 1;
+        record.last_step = Some("reserve_stock".to_string());
+        record
+    }
+
+    #[tokio::test]
+    async fn test_resume_continues_from_checkpoint() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let record = interrupted_record(SagaStatus::Running);
+        store.save(&record).await.unwrap();
+
+        let context = orchestrator(store.clone()).resume(record.saga_id).await.unwrap();
+        assert!(context.reserved && context.charged);
+
+        let saved = store.load(record.saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Completed);
+        assert_eq!(saved.last_step.as_deref(), Some("capture_payment"));
+    }
+
+    /// A context holding a live handle: no serde impls, so it can only run without a store.
+    #[derive(Debug, Default)]
+    struct LiveContext {
+        reserved: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    struct ReserveLive;
+
+    #[async_trait]
+    impl SagaStep for ReserveLive {
+        type Context = LiveContext;
+        type Error = String;
+
+        async fn execute(&self, context: &mut LiveContext) -> Result<(), String> {
+            context.reserved.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn compensate(&self, context: &mut LiveContext) -> Result<(), String> {
+            context.reserved.store(false, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_context_without_serde_when_not_persisted() {
+        let saga: SagaOrchestrator<LiveContext, String> = Saga::builder("live").step("reserve_stock", ReserveLive).build();
+
+        let context = saga.run(LiveContext::default()).await.unwrap();
+        assert!(context.reserved.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(matches!(saga.describe_run(Uuid::new_v4()).await, Err(SagaError::NoStore)));
+    }
+
+    #[tokio::test]
+    async fn test_resume_rejects_renamed_steps() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let mut record = interrupted_record(SagaStatus::Running);
+        record.last_step = Some("legacy_reserve".to_string());
+        store.save(&record).await.unwrap();
+
+        let result = orchestrator(store).resume(record.saga_id).await;
+        assert!(matches!(result, Err(SagaError::DefinitionMismatch(..))));
+    }
+
+    #[tokio::test]
+    async fn test_recover_with_compensate_policy() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let record = interrupted_record(SagaStatus::Running);
+        store.save(&record).await.unwrap();
+
+        let report = orchestrator(store.clone()).recover(RecoveryPolicy::Compensate).await.unwrap();
+        assert_eq!(report.compensated, vec![record.saga_id]);
+
+        let saved = store.load(record.saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Compensated);
+        assert_eq!(saved.completed_steps, 0);
+        assert!(store.list_in_flight("order").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_step_timeout_triggers_compensation() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let saga = Saga::builder("order")
+            .step("reserve_stock", Reserve)
+            .step_with_policy("notify_erp", Stalled, StepPolicy::default().with_timeout(Duration::from_millis(20)))
+            .store(store.clone())
+            .build();
+
+        let saga_id = Uuid::new_v4();
+        let result = saga.run_with_id(saga_id, OrderContext::default()).await;
+        assert!(matches!(result, Err(SagaError::StepTimeout { ref step, .. }) if step == "notify_erp"));
+
+        let saved = store.load(saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Compensated);
+        assert!(!saved.context["reserved"].as_bool().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_group_merges_and_compensates() {
+        let saga = Saga::builder("checkout")
+            .parallel("reserve_and_charge", ParallelGroup::new(merge).step("reserve_stock", Reserve).step("capture_payment", Charge))
+            .build();
+        let context = saga.run(OrderContext::default()).await.unwrap();
+        assert!(context.reserved && context.charged);
+
+        let store = Arc::new(InMemorySagaStore::new());
+        let saga = Saga::builder("checkout")
+            .parallel(
+                "reserve_and_notify",
+                ParallelGroup::new(merge)
+                    .step("reserve_stock", Reserve)
+                    .step_with_policy("notify_erp", Stalled, StepPolicy::default().with_timeout(Duration::from_millis(20))),
+            )
+            .store(store.clone())
+            .build();
+
+        let saga_id = Uuid::new_v4();
+        assert!(saga.run_with_id(saga_id, OrderContext::default()).await.is_err());
+
+        let saved = store.load(saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Compensated);
+        assert!(!saved.context["reserved"].as_bool().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once")]
+    fn test_builder_rejects_duplicate_step_names() {
+        let _: SagaOrchestrator<OrderContext, String> = Saga::builder("order")
+            .step("reserve_stock", Reserve)
+            .step("reserve_stock", Reserve)
+            .build();
+    }
+
+    #[tokio::test]
+    async fn test_conditional_step_skipped_and_only_chosen_arm_compensated() {
+        let saga = Saga::builder("order")
+            .step_if("capture_payment", |c: &OrderContext| c.reserved, Charge)
+            .build();
+        let context = saga.run(OrderContext::default()).await.unwrap();
+        assert!(!context.charged);
+
+        let store = Arc::new(InMemorySagaStore::new());
+        let saga = Saga::builder("order")
+            .branch("fulfil", Branch::new().when(|c: &OrderContext| !c.reserved, "reserve_stock", Reserve).otherwise("capture_payment", Charge))
+            .step_with_policy("notify_erp", Stalled, StepPolicy::default().with_timeout(Duration::from_millis(20)))
+            .store(store.clone())
+            .build();
+
+        let saga_id = Uuid::new_v4();
+        assert!(saga.run_with_id(saga_id, OrderContext::default()).await.is_err());
+
+        let saved = store.load(saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.branch_choices.get("fulfil").map(String::as_str), Some("reserve_stock"));
+        assert!(!saved.context["reserved"].as_bool().unwrap());
+        assert!(!saved.context["charged"].as_bool().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_failure_after_pivot_is_not_compensated() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let saga = Saga::builder("order")
+            .step("reserve_stock", Reserve)
+            .pivot("capture_payment", Charge)
+            .step("notify_erp", Failing)
+            .forward_retry(RetryPolicy::exponential(2, Duration::from_millis(1)))
+            .store(store.clone())
+            .build();
+
+        let saga_id = Uuid::new_v4();
+        let result = saga.run_with_id(saga_id, OrderContext::default()).await;
+        assert!(matches!(result, Err(SagaError::Stuck { ref step, .. }) if step == "notify_erp"));
+
+        let saved = store.load(saga_id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Running);
+        assert!(saved.context["reserved"].as_bool().unwrap());
+        assert!(saved.context["charged"].as_bool().unwrap());
+        assert!(matches!(saga.compensate_saga(saga_id).await, Err(SagaError::PastPivot(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wait_step_parks_until_signalled() {
+        let store = Arc::new(InMemorySagaStore::new());
+        let saga = Saga::builder("order")
+            .step("reserve_stock", Reserve)
+            .wait("manager_approval")
+            .step("capture_payment", Charge)
+            .store(store.clone())
+            .build();
+
+        let approved = Uuid::new_v4();
+        let result = saga.run_with_id(approved, OrderContext::default()).await;
+        assert!(matches!(result, Err(SagaError::Waiting { ref step, .. }) if step == "manager_approval"));
+        assert_eq!(store.load(approved).await.unwrap().unwrap().status, SagaStatus::Waiting);
+        assert!(matches!(saga.resume(approved).await, Err(SagaError::Waiting { .. })));
+
+        let context = saga.signal(approved, SagaSignal::Resume).await.unwrap();
+        assert!(context.reserved && context.charged);
+        assert!(matches!(saga.signal(approved, SagaSignal::Resume).await, Err(SagaError::NotInFlight(..))));
+
+        let rejected = Uuid::new_v4();
+        let _ = saga.run_with_id(rejected, OrderContext::default()).await;
+        let abort = SagaSignal::Abort { reason: "over budget".to_string() };
+        assert!(matches!(saga.signal(rejected, abort).await, Err(SagaError::Compensated(_))));
+
+        let saved = store.load(rejected).await.unwrap().unwrap();
+        assert_eq!(saved.status, SagaStatus::Compensated);
+        assert!(!saved.context["reserved"].as_bool().unwrap());
+    }
+}
@@ -0,0 +1,60 @@
+//! Parallel step groups
+//!
+//! Independent steps (e.g. reserving stock and authorizing payment) can be declared as a
+//! group that the orchestrator executes concurrently. Each member runs against its own
+//! copy of the context; once a member finishes, its copy is folded back into the saga
+//! context with the group's merge function.
+//!
+//! If any member fails, the group waits for the others to finish and then compensates
+//! every member that completed, before compensating the preceding steps.
+
+use super::orchestrator::StepEntry;
+use super::{SagaStep, StepPolicy};
+
+type Fork<C> = Box<dyn Fn(&C) -> C + Send + Sync>;
+type Merge<C> = Box<dyn Fn(&mut C, C) + Send + Sync>;
+
+pub struct ParallelGroup<C, E> {
+    pub(crate) members: Vec<StepEntry<C, E>>,
+    pub(crate) fork: Fork<C>,
+    pub(crate) merge: Merge<C>,
+}
+
+impl<C, E> ParallelGroup<C, E> {
+    /// Create an empty group. `merge` folds a member's resulting context into the saga context
+    /// and is called once per successful member, in declaration order.
+    pub fn new<M>(merge: M) -> Self
+    where
+        C: Clone + 'static,
+        M: Fn(&mut C, C) + Send + Sync + 'static,
+    {
+        Self {
+            members: Vec::new(),
+            fork: Box::new(|context: &C| context.clone()),
+            merge: Box::new(merge),
+        }
+    }
+
+    pub fn step<S>(self, name: &str, step: S) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.step_with_policy(name, step, StepPolicy::default())
+    }
+
+    pub fn step_with_policy<S>(mut self, name: &str, step: S, policy: StepPolicy<E>) -> Self
+    where
+        S: SagaStep<Context = C, Error = E> + 'static,
+    {
+        self.members.push(StepEntry::new(name, step, policy));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
@@ -0,0 +1,179 @@
+//! Saga steps backed by NATS request-reply
+//!
+//! A `RemoteStep` sends a typed request to another service and applies its reply to the
+//! saga context; on rollback it sends the matching compensation request.
+//!
+//! ```ignore
+//! let reserve = RemoteStep::new("lanai.inventory.stock.reserve", |ctx: &OrderContext| ReserveStockRequest {
+//!         order_id: ctx.order_id,
+//!         org_id: ctx.org_id,
+//!         items: ctx.items.clone(),
+//!     })
+//!     .on_reply(|_, reply: ReserveStockResponse| match reply.success {
+//!         true => Ok(()),
+//!         false => Err(RemoteStepError::Rejected(reply.error.unwrap_or_default())),
+//!     })
+//!     .compensate_with("lanai.inventory.stock.release", |ctx: &OrderContext| ReleaseStockRequest {
+//!         order_id: ctx.order_id,
+//!         org_id: ctx.org_id,
+//!         items: ctx.items.clone(),
+//!     })
+//!     .timeout(Duration::from_secs(3));
+//!
+//! let saga = Saga::builder("order_checkout").step("reserve_stock", reserve).build();
+//! ```
+//!
+//! Compensation responders must reply with a JSON body (`{}` is enough) so the
+//! orchestrator knows the rollback was applied; no reply counts as a failed compensation
+//! and is retried according to the step's policy.
+
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::time::Duration;
+use thiserror::Error;
+
+use super::SagaStep;
+use crate::messaging::{NatsClient, NatsError};
+
+/// Default time to wait for a reply from the remote service.
+pub const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors produced by a `RemoteStep`. Saga error types embed them via `From`.
+#[derive(Debug, Error)]
+pub enum RemoteStepError {
+    #[error(transparent)]
+    Nats(#[from] NatsError),
+
+    /// The remote service replied, but refused the request.
+    #[error("Remote service rejected the request: {0}")]
+    Rejected(String),
+}
+
+type RequestFn<C, Req> = Box<dyn Fn(&C) -> Req + Send + Sync>;
+type ReplyFn<C, Res> = Box<dyn Fn(&mut C, Res) -> Result<(), RemoteStepError> + Send + Sync>;
+type CompensationFn<C> = Box<dyn Fn(&C) -> Result<serde_json::Value, RemoteStepError> + Send + Sync>;
+
+pub struct RemoteStep<C, Req, Res, E> {
+    subject: String,
+    timeout: Duration,
+    request: RequestFn<C, Req>,
+    on_reply: ReplyFn<C, Res>,
+    compensation: Option<(String, CompensationFn<C>)>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<C, Req, Res, E> RemoteStep<C, Req, Res, E>
+where
+    Res: 'static,
+{
+    /// Request built from the context by `request` and sent to `subject`.
+    /// By default any well-formed reply counts as success.
+    pub fn new<F>(subject: &str, request: F) -> Self
+    where
+        F: Fn(&C) -> Req + Send + Sync + 'static,
+    {
+        Self {
+            subject: subject.to_string(),
+            timeout: DEFAULT_REMOTE_TIMEOUT,
+            request: Box::new(request),
+            on_reply: Box::new(|_, _| Ok(())),
+            compensation: None,
+            _error: PhantomData,
+        }
+    }
+
+    /// Inspect the reply, record its results in the context, or reject it.
+    pub fn on_reply<F>(mut self, on_reply: F) -> Self
+    where
+        F: Fn(&mut C, Res) -> Result<(), RemoteStepError> + Send + Sync + 'static,
+    {
+        self.on_reply = Box::new(on_reply);
+        self
+    }
+
+    /// Request sent to `subject` when the saga rolls this step back.
+    pub fn compensate_with<F, R>(mut self, subject: &str, request: F) -> Self
+    where
+        F: Fn(&C) -> R + Send + Sync + 'static,
+        R: Serialize,
+    {
+        let build: CompensationFn<C> = Box::new(move |context: &C| {
+            serde_json::to_value(request(context))
+                .map_err(|e| RemoteStepError::Nats(NatsError::SerializationError(e.to_string())))
+        });
+        self.compensation = Some((subject.to_string(), build));
+        self
+    }
+
+    /// How long to wait for each reply (default: 5s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl<C, Req, Res, E> SagaStep for RemoteStep<C, Req, Res, E>
+where
+    C: Send + Sync,
+    Req: Serialize + Send + Sync,
+    Res: DeserializeOwned + Send,
+    E: From<RemoteStepError> + Debug + Display + Send,
+{
+    type Context = C;
+    type Error = E;
+
+    async fn execute(&self, context: &mut C) -> Result<(), E> {
+        let request = (self.request)(context);
+        let reply: Res = NatsClient::request_typed(&self.subject, &request, self.timeout)
+            .await
+            .map_err(RemoteStepError::from)?;
+        (self.on_reply)(context, reply)?;
+        Ok(())
+    }
+
+    async fn compensate(&self, context: &mut C) -> Result<(), E> {
+        let Some((subject, build)) = &self.compensation else {
+            return Ok(());
+        };
+
+        let request = build(context)?;
+        match NatsClient::request_typed::<_, serde_json::Value>(subject, &request, self.timeout).await {
+            Ok(_) => {
+                info!("↩️ Compensation request on '{}' acknowledged", subject);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("⚠️ Compensation request on '{}' failed: {}", subject, e);
+                Err(RemoteStepError::from(e).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Step = RemoteStep<u32, u32, u32, RemoteStepError>;
+
+    #[tokio::test]
+    async fn test_execute_requires_nats() {
+        let step: Step = RemoteStep::new("lanai.test.remote", |ctx: &u32| *ctx);
+        let err = step.execute(&mut 1).await.unwrap_err();
+        assert!(matches!(err, RemoteStepError::Nats(NatsError::NotInitialized)));
+    }
+
+    #[tokio::test]
+    async fn test_compensate_without_request_is_noop() {
+        let step: Step = RemoteStep::new("lanai.test.remote", |ctx: &u32| *ctx);
+        assert!(step.compensate(&mut 1).await.is_ok());
+
+        let step: Step = step.compensate_with("lanai.test.remote.undo", |ctx: &u32| *ctx);
+        assert!(step.compensate(&mut 1).await.is_err());
+    }
+}
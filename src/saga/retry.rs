@@ -0,0 +1,58 @@
+//! Retry policies for saga steps
+//!
+//! Transient failures (a NATS timeout, a dropped DB connection) should not trigger a full
+//! compensation cascade. Each step can carry a `StepPolicy` with independent retry
+//! settings for `execute` and `compensate`, plus an optional deadline.
+
+use std::time::Duration;
+
+pub use crate::resilience::RetryPolicy;
+
+/// Per-step execution policy.
+#[derive(Debug)]
+pub struct StepPolicy<E> {
+    /// Retries applied to `SagaStep::execute`.
+    pub execute: RetryPolicy<E>,
+    /// Retries applied to `SagaStep::compensate`.
+    pub compensate: RetryPolicy<E>,
+    /// Deadline for the step including its retries, applied to execution and compensation separately.
+    pub timeout: Option<Duration>,
+}
+
+impl<E> Default for StepPolicy<E> {
+    fn default() -> Self {
+        Self {
+            execute: RetryPolicy::none(),
+            compensate: RetryPolicy::none(),
+            timeout: None,
+        }
+    }
+}
+
+impl<E> Clone for StepPolicy<E> {
+    fn clone(&self) -> Self {
+        Self {
+            execute: self.execute.clone(),
+            compensate: self.compensate.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<E> StepPolicy<E> {
+    pub fn execute_retry(mut self, policy: RetryPolicy<E>) -> Self {
+        self.execute = policy;
+        self
+    }
+
+    pub fn compensate_retry(mut self, policy: RetryPolicy<E>) -> Self {
+        self.compensate = policy;
+        self
+    }
+
+    /// Treat the step as failed if it has not finished within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
@@ -0,0 +1,163 @@
+//! External signals for sagas parked at wait steps
+//!
+//! A saga reaching a `wait` stage is persisted as `Waiting` and stays there until a signal
+//! resumes or aborts it. Signals arrive either through `SagaOrchestrator::signal`, the saga
+//! admin API, or NATS:
+//!
+//! ```ignore
+//! let refunds = Arc::new(Saga::builder("refund")
+//!     .step("validate", Validate)
+//!     .wait_if("manager_approval", |ctx: &RefundContext| ctx.amount > threshold)
+//!     .step("issue_refund", IssueRefund)
+//!     .store(store)
+//!     .build());
+//! let _listener = refunds.clone().listen_for_signals().await?;
+//!
+//! // Elsewhere, once a manager approves:
+//! send_signal("refund", saga_id, SagaSignal::Resume).await?;
+//! ```
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::{SagaError, SagaOrchestrator, SagaStatus};
+use crate::messaging::events::decode_event;
+use crate::messaging::{NatsClient, NatsError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SagaSignal {
+    /// Continue with the stage after the wait.
+    Resume,
+    /// Roll back the executed steps.
+    Abort { reason: String },
+}
+
+/// Message published on `lanai.saga.{saga_name}.signal`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalMessage {
+    pub saga_id: Uuid,
+    #[serde(flatten)]
+    pub signal: SagaSignal,
+}
+
+/// Where a signalled saga ended up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SignalOutcome {
+    Completed,
+    /// Parked again at a later wait step.
+    Waiting { step: String },
+    Compensated,
+    /// A step after the pivot kept failing; the saga stays in flight.
+    Stuck { step: String, reason: String },
+}
+
+/// Reasons a signal was not applied.
+#[derive(Debug, Error)]
+pub enum SignalError {
+    #[error("Saga {0} not found")]
+    NotFound(Uuid),
+
+    #[error("Saga {0} is not waiting for a signal ({1:?})")]
+    NotWaiting(Uuid, SagaStatus),
+
+    #[error("Saga {0} already passed its pivot step and cannot be aborted")]
+    PastPivot(Uuid),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Type-erased view of an orchestrator that accepts signals (used by the admin API).
+#[async_trait]
+pub trait SignalTarget: Send + Sync {
+    async fn signal(&self, saga_id: Uuid, signal: SagaSignal) -> Result<SignalOutcome, SignalError>;
+}
+
+#[async_trait]
+impl<C, E> SignalTarget for SagaOrchestrator<C, E>
+where
+    C: Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+    E: Debug + Display + Send + Sync + 'static,
+{
+    async fn signal(&self, saga_id: Uuid, signal: SagaSignal) -> Result<SignalOutcome, SignalError> {
+        match SagaOrchestrator::signal(self, saga_id, signal).await {
+            Ok(_) => Ok(SignalOutcome::Completed),
+            Err(SagaError::Waiting { step, .. }) => Ok(SignalOutcome::Waiting { step }),
+            Err(SagaError::Stuck { step, reason }) => Ok(SignalOutcome::Stuck { step, reason }),
+            Err(e) if e.is_compensated() => Ok(SignalOutcome::Compensated),
+            Err(SagaError::NotFound(id)) => Err(SignalError::NotFound(id)),
+            Err(SagaError::NotInFlight(id, status) | SagaError::NotWaiting(id, status)) => {
+                Err(SignalError::NotWaiting(id, status))
+            }
+            Err(SagaError::PastPivot(id)) => Err(SignalError::PastPivot(id)),
+            Err(e) => Err(SignalError::Other(e.to_string())),
+        }
+    }
+}
+
+/// Subject signals for the saga definition `saga_name` are published on.
+pub fn signal_subject(saga_name: &str) -> String {
+    format!("lanai.saga.{}.signal", saga_name)
+}
+
+/// Publish a signal for a parked saga; whichever instance runs that saga's listener applies it.
+pub async fn send_signal(saga_name: &str, saga_id: Uuid, signal: SagaSignal) -> Result<(), NatsError> {
+    NatsClient::publish_event(&signal_subject(saga_name), &SignalMessage { saga_id, signal }).await
+}
+
+impl<C, E> SagaOrchestrator<C, E>
+where
+    C: Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+    E: Debug + Display + Send + Sync + 'static,
+{
+    /// Apply signals published with `send_signal`. Instances share a queue group, so each
+    /// signal is handled once. Abort the returned handle to stop listening.
+    pub async fn listen_for_signals(self: Arc<Self>) -> Result<JoinHandle<()>, NatsError> {
+        let subject = signal_subject(&self.name);
+        let mut subscriber = NatsClient::queue_subscribe(&subject, &format!("lanai.saga.{}", self.name)).await?;
+
+        info!("📻 Saga '{}' listening for signals on '{}'", self.name, subject);
+        Ok(tokio::spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                let signal: SignalMessage = match decode_event(&message.payload) {
+                    Ok(signal) => signal,
+                    Err(e) => {
+                        warn!("⚠️ Saga '{}': ignoring malformed signal: {}", self.name, e);
+                        continue;
+                    }
+                };
+
+                match SignalTarget::signal(self.as_ref(), signal.saga_id, signal.signal).await {
+                    Ok(outcome) => info!("📻 Saga '{}' ({}) signalled: {:?}", self.name, signal.saga_id, outcome),
+                    Err(e) => error!("❌ Saga '{}': signal for {} rejected: {}", self.name, signal.saga_id, e),
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_message_format() {
+        let message = SignalMessage { saga_id: Uuid::nil(), signal: SagaSignal::Abort { reason: "denied".into() } };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["action"], "abort");
+        assert_eq!(json["reason"], "denied");
+
+        let parsed: SignalMessage =
+            serde_json::from_str(r#"{"saga_id":"00000000-0000-0000-0000-000000000000","action":"resume"}"#).unwrap();
+        assert_eq!(parsed.signal, SagaSignal::Resume);
+    }
+}
@@ -0,0 +1,242 @@
+//! Saga State Persistence
+//!
+//! Sagas checkpoint their progress into a `SagaStore` after every step so that an
+//! orchestrator restarted by a deploy (or a crash) can pick up where the previous
+//! process left off, either continuing forward or running compensation.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Lifecycle status of a persisted saga.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SagaStatus {
+    /// Steps are being executed forward.
+    Running,
+    /// Parked at a wait step until an external signal resumes or aborts it.
+    Waiting,
+    /// A step failed and already-executed steps are being compensated.
+    Compensating,
+    /// Every step executed successfully.
+    Completed,
+    /// Compensation finished after a failure.
+    Compensated,
+    /// At least one compensation failed even after retries; needs manual intervention.
+    CompensationFailed,
+}
+
+impl SagaStatus {
+    /// Returns true if the saga still has work to do (forward or compensation).
+    pub fn is_in_flight(&self) -> bool {
+        matches!(self, Self::Running | Self::Waiting | Self::Compensating)
+    }
+}
+
+/// Persisted snapshot of a saga execution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SagaRecord {
+    pub saga_id: Uuid,
+    /// Name of the saga definition, used to match records to orchestrators on recovery.
+    pub saga_name: String,
+    pub status: SagaStatus,
+    /// While `Running`: number of steps executed so far (index of the next step).
+    /// While `Waiting`: index of the wait step the saga is parked at.
+    /// While `Compensating`: number of executed steps still awaiting compensation.
+    pub completed_steps: usize,
+    /// Name of the step at index `completed_steps - 1`, used to detect definition changes on resume.
+    #[serde(default)]
+    pub last_step: Option<String>,
+    /// Arm executed by each completed branching stage, keyed by stage name.
+    /// Stages missing here were skipped and are not compensated.
+    #[serde(default)]
+    pub branch_choices: HashMap<String, String>,
+    /// Members of the parallel group at index `completed_steps` that already finished.
+    #[serde(default)]
+    pub completed_members: Vec<usize>,
+    /// Serialized saga context as of the last checkpoint.
+    pub context: serde_json::Value,
+    /// Error that triggered compensation, if any.
+    pub error: Option<String>,
+    /// W3C `traceparent` of the span that started the saga; later executions link to it.
+    #[serde(default)]
+    pub traceparent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SagaRecord {
+    pub fn new(saga_id: Uuid, saga_name: &str, context: serde_json::Value) -> Self {
+        let now = Utc::now();
+        Self {
+            saga_id,
+            saga_name: saga_name.to_string(),
+            status: SagaStatus::Running,
+            completed_steps: 0,
+            last_step: None,
+            branch_choices: HashMap::new(),
+            completed_members: Vec::new(),
+            context,
+            error: None,
+            traceparent: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Error types for saga persistence backends.
+#[derive(Debug, Error)]
+pub enum SagaStoreError {
+    #[error("Failed to serialize saga record: {0}")]
+    Serialization(String),
+
+    #[error("Saga store backend error: {0}")]
+    Backend(String),
+}
+
+/// Storage backend for saga checkpoints.
+#[async_trait]
+pub trait SagaStore: Send + Sync {
+    /// Insert or overwrite the record for `record.saga_id`.
+    async fn save(&self, record: &SagaRecord) -> Result<(), SagaStoreError>;
+
+    /// Load a record by saga ID.
+    async fn load(&self, saga_id: Uuid) -> Result<Option<SagaRecord>, SagaStoreError>;
+
+    /// List every record of the given saga definition that is still in flight.
+    async fn list_in_flight(&self, saga_name: &str) -> Result<Vec<SagaRecord>, SagaStoreError>;
+}
+
+/// In-memory saga store (for tests and single-instance development).
+///
+/// State does not survive a restart, so it cannot be used for real recovery.
+pub struct InMemorySagaStore {
+    records: RwLock<HashMap<Uuid, SagaRecord>>,
+}
+
+impl InMemorySagaStore {
+    pub fn new() -> Self {
+        Self {
+            records: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemorySagaStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SagaStore for InMemorySagaStore {
+    async fn save(&self, record: &SagaRecord) -> Result<(), SagaStoreError> {
+        self.records.write().await.insert(record.saga_id, record.clone());
+        Ok(())
+    }
+
+    async fn load(&self, saga_id: Uuid) -> Result<Option<SagaRecord>, SagaStoreError> {
+        Ok(self.records.read().await.get(&saga_id).cloned())
+    }
+
+    async fn list_in_flight(&self, saga_name: &str) -> Result<Vec<SagaRecord>, SagaStoreError> {
+        Ok(self
+            .records
+            .read()
+            .await
+            .values()
+            .filter(|r| r.saga_name == saga_name && r.status.is_in_flight())
+            .cloned()
+            .collect())
+    }
+}
+
+/// Redis-backed saga store.
+///
+/// Records are stored as JSON under `saga:{id}`; in-flight IDs are tracked per saga
+/// name in the set `saga:in_flight:{name}` so recovery does not need to scan keys.
+pub struct RedisSagaStore {
+    client: redis::Client,
+}
+
+impl RedisSagaStore {
+    pub fn new(url: &str) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(url)?;
+        Ok(Self { client })
+    }
+
+    fn record_key(saga_id: Uuid) -> String {
+        format!("saga:{}", saga_id)
+    }
+
+    fn in_flight_key(saga_name: &str) -> String {
+        format!("saga:in_flight:{}", saga_name)
+    }
+
+    async fn connection(&self) -> Result<redis::aio::Connection, SagaStoreError> {
+        self.client
+            .get_async_connection()
+            .await
+            .map_err(|e| SagaStoreError::Backend(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl SagaStore for RedisSagaStore {
+    async fn save(&self, record: &SagaRecord) -> Result<(), SagaStoreError> {
+        let payload = serde_json::to_string(record)
+            .map_err(|e| SagaStoreError::Serialization(e.to_string()))?;
+        let mut conn = self.connection().await?;
+
+        let in_flight_key = Self::in_flight_key(&record.saga_name);
+        let id = record.saga_id.to_string();
+        let mut pipe = redis::pipe();
+        pipe.atomic().set(Self::record_key(record.saga_id), payload);
+        if record.status.is_in_flight() {
+            pipe.sadd(&in_flight_key, &id);
+        } else {
+            pipe.srem(&in_flight_key, &id);
+        }
+
+        pipe.query_async::<_, ()>(&mut conn)
+            .await
+            .map_err(|e| SagaStoreError::Backend(e.to_string()))
+    }
+
+    async fn load(&self, saga_id: Uuid) -> Result<Option<SagaRecord>, SagaStoreError> {
+        let mut conn = self.connection().await?;
+        let payload: Option<String> = conn
+            .get(Self::record_key(saga_id))
+            .await
+            .map_err(|e| SagaStoreError::Backend(e.to_string()))?;
+
+        payload
+            .map(|p| serde_json::from_str(&p).map_err(|e| SagaStoreError::Serialization(e.to_string())))
+            .transpose()
+    }
+
+    async fn list_in_flight(&self, saga_name: &str) -> Result<Vec<SagaRecord>, SagaStoreError> {
+        let ids: Vec<String> = {
+            let mut conn = self.connection().await?;
+            conn.smembers(Self::in_flight_key(saga_name))
+                .await
+                .map_err(|e| SagaStoreError::Backend(e.to_string()))?
+        };
+
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids {
+            let Ok(saga_id) = Uuid::parse_str(&id) else { continue };
+            if let Some(record) = self.load(saga_id).await? {
+                if record.status.is_in_flight() {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
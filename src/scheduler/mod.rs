@@ -0,0 +1,318 @@
+//! Cron-style Scheduler
+//!
+//! Runs registered async tasks on cron expressions. With a `LeaderElection` attached, only
+//! the leading replica fires; the others keep their schedule in step so a new leader does
+//! not replay runs the previous one already made.
+//!
+//! - Expressions use the 5-field (`min hour dom mon dow`) or 6/7-field (with seconds/year) syntax, in UTC
+//! - Each run executes inside a `scheduler.run` tracing span
+//! - By default a task never overlaps itself; occurrences that pass while it runs are
+//!   handled by its `MissedRunPolicy`
+//!
+//! ```ignore
+//! let election = LeaderElection::new(shared_connection().await?, "billing-cron");
+//! let _election = election.start();
+//!
+//! let _scheduler = Scheduler::new()
+//!     .with_leader(election)
+//!     .task("close-invoices", "0 2 * * *", move || billing.close_overdue_invoices())?
+//!     .task_with("sync-rates", "*/5 * * * *", TaskOptions::default().missed(MissedRunPolicy::Skip), sync_rates)?
+//!     .start();
+//! ```
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use log::{error, info, warn};
+use std::fmt::Display;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::leader::LeaderElection;
+
+/// An occurrence that fires later than this is considered missed.
+const MISSED_AFTER: Duration = Duration::from_secs(5);
+
+/// Upper bound on occurrences replayed at once by `MissedRunPolicy::CatchUp`.
+const MAX_CATCH_UP: usize = 100;
+
+type TaskFn = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// What to do with occurrences that passed while a task was still running (or the process stalled).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedRunPolicy {
+    /// Drop missed occurrences and wait for the next one.
+    Skip,
+    /// Run once as soon as possible, however many occurrences were missed.
+    #[default]
+    RunOnce,
+    /// Run every missed occurrence back to back (at most 100).
+    CatchUp,
+}
+
+/// Per-task scheduling options.
+#[derive(Debug, Clone, Default)]
+pub struct TaskOptions {
+    pub missed: MissedRunPolicy,
+    /// Let a new run start while the previous one is still running.
+    pub allow_overlap: bool,
+}
+
+impl TaskOptions {
+    pub fn missed(mut self, policy: MissedRunPolicy) -> Self {
+        self.missed = policy;
+        self
+    }
+
+    pub fn allow_overlap(mut self) -> Self {
+        self.allow_overlap = true;
+        self
+    }
+}
+
+/// Scheduler error types
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error("Invalid cron expression '{expression}' for task '{task}': {reason}")]
+    InvalidExpression { task: String, expression: String, reason: String },
+
+    #[error("Task '{0}' is already scheduled")]
+    DuplicateTask(String),
+}
+
+struct Task {
+    name: String,
+    schedule: Schedule,
+    options: TaskOptions,
+    run: TaskFn,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Vec<Arc<Task>>,
+    leader: Option<LeaderElection>,
+}
+
+/// Background tasks of a started `Scheduler`.
+pub struct SchedulerHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    /// Stop scheduling. Runs in progress are cancelled.
+    pub fn abort(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only fire tasks while this replica holds `election`. The election must be started separately.
+    pub fn with_leader(mut self, election: LeaderElection) -> Self {
+        self.leader = Some(election);
+        self
+    }
+
+    /// Schedule `task` on `expression` with the default options.
+    pub fn task<F, Fut, E>(self, name: &str, expression: &str, task: F) -> Result<Self, SchedulerError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.task_with(name, expression, TaskOptions::default(), task)
+    }
+
+    pub fn task_with<F, Fut, E>(
+        mut self,
+        name: &str,
+        expression: &str,
+        options: TaskOptions,
+        task: F,
+    ) -> Result<Self, SchedulerError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        if self.tasks.iter().any(|t| t.name == name) {
+            return Err(SchedulerError::DuplicateTask(name.to_string()));
+        }
+
+        let schedule = parse_expression(expression).map_err(|reason| SchedulerError::InvalidExpression {
+            task: name.to_string(),
+            expression: expression.to_string(),
+            reason,
+        })?;
+
+        self.tasks.push(Arc::new(Task {
+            name: name.to_string(),
+            schedule,
+            options,
+            run: Arc::new(move || task().map(|result| result.map_err(|e| e.to_string())).boxed()),
+        }));
+        Ok(self)
+    }
+
+    pub fn start(self) -> SchedulerHandle {
+        info!("⏰ Scheduler started with {} tasks", self.tasks.len());
+        let tasks = self
+            .tasks
+            .into_iter()
+            .map(|task| tokio::spawn(drive(task, self.leader.clone())))
+            .collect();
+        SchedulerHandle { tasks }
+    }
+}
+
+/// Accept the classic 5-field syntax by adding a zero seconds field.
+fn parse_expression(expression: &str) -> Result<Schedule, String> {
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&normalized).map_err(|e| e.to_string())
+}
+
+/// Occurrences after `cursor` that are due at `now`, oldest first.
+fn due_occurrences(schedule: &Schedule, cursor: DateTime<Utc>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let mut due: Vec<_> = schedule.after(&cursor).take_while(|t| *t <= now).collect();
+    if due.len() > MAX_CATCH_UP {
+        due.drain(..due.len() - MAX_CATCH_UP);
+    }
+    due
+}
+
+/// Pick which of the due occurrences to run under `policy`.
+fn select_runs(policy: MissedRunPolicy, due: Vec<DateTime<Utc>>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let is_missed = |t: &DateTime<Utc>| (now - *t).to_std().unwrap_or_default() > MISSED_AFTER;
+    match policy {
+        MissedRunPolicy::Skip => due.into_iter().filter(|t| !is_missed(t)).collect(),
+        MissedRunPolicy::RunOnce => due.last().copied().into_iter().collect(),
+        MissedRunPolicy::CatchUp => due,
+    }
+}
+
+async fn drive(task: Arc<Task>, leader: Option<LeaderElection>) {
+    let mut cursor = Utc::now();
+    loop {
+        let Some(next) = task.schedule.after(&cursor).next() else {
+            info!("⏰ Task '{}' has no further occurrences", task.name);
+            return;
+        };
+        tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+
+        let now = Utc::now();
+        let due = due_occurrences(&task.schedule, cursor, now);
+        cursor = due.last().copied().unwrap_or(next);
+
+        if leader.as_ref().is_some_and(|l| !l.am_i_leader()) {
+            continue;
+        }
+
+        let total = due.len();
+        let runs = select_runs(task.options.missed, due, now);
+        if runs.len() < total {
+            warn!(
+                "⏭️ Task '{}' missed {} runs ({:?} policy)",
+                task.name,
+                total - runs.len(),
+                task.options.missed
+            );
+        }
+
+        let catching_up = runs.len() > 1;
+        for scheduled_for in runs {
+            if task.options.allow_overlap {
+                tokio::spawn(run_once(task.clone(), scheduled_for, catching_up));
+            } else {
+                run_once(task.clone(), scheduled_for, catching_up).await;
+            }
+        }
+    }
+}
+
+async fn run_once(task: Arc<Task>, scheduled_for: DateTime<Utc>, catch_up: bool) {
+    let span = tracing::info_span!(
+        "scheduler.run",
+        task = %task.name,
+        scheduled_for = %scheduled_for.to_rfc3339(),
+        catch_up,
+    );
+    let started = Instant::now();
+    let result = AssertUnwindSafe((task.run)())
+        .catch_unwind()
+        .instrument(span)
+        .await
+        .unwrap_or_else(|_| Err("task panicked".to_string()));
+
+    match result {
+        Ok(()) => info!("✅ Task '{}' completed in {:?}", task.name, started.elapsed()),
+        Err(e) => error!("❌ Task '{}' failed after {:?}: {}", task.name, started.elapsed(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, h, m, s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_expression_accepts_five_fields() {
+        let schedule = parse_expression("*/15 * * * *").unwrap();
+        assert_eq!(schedule.after(&at(10, 1, 0)).next(), Some(at(10, 15, 0)));
+
+        assert!(parse_expression("0 0 * * * *").is_ok());
+        assert!(parse_expression("not a cron").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_and_invalid_tasks_are_rejected() {
+        let noop = || async { Ok::<(), String>(()) };
+        let scheduler = Scheduler::new().task("a", "* * * * *", noop).unwrap();
+        assert!(matches!(
+            scheduler.task("a", "* * * * *", noop),
+            Err(SchedulerError::DuplicateTask(_))
+        ));
+        assert!(matches!(
+            Scheduler::new().task("b", "61 * * * *", noop),
+            Err(SchedulerError::InvalidExpression { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missed_run_policies() {
+        let schedule = parse_expression("* * * * *").unwrap();
+        // Previous run started at 10:00 and finished at 10:03:02.
+        let now = at(10, 3, 2);
+        let due = due_occurrences(&schedule, at(10, 0, 0), now);
+        assert_eq!(due, vec![at(10, 1, 0), at(10, 2, 0), at(10, 3, 0)]);
+
+        assert_eq!(select_runs(MissedRunPolicy::Skip, due.clone(), now), vec![at(10, 3, 0)]);
+        assert_eq!(select_runs(MissedRunPolicy::RunOnce, due.clone(), now), vec![at(10, 3, 0)]);
+        assert_eq!(select_runs(MissedRunPolicy::CatchUp, due.clone(), now).len(), 3);
+
+        // All occurrences stale: Skip drops them entirely, RunOnce still runs one.
+        let late = at(10, 3, 30);
+        assert!(select_runs(MissedRunPolicy::Skip, due.clone(), late).is_empty());
+        assert_eq!(select_runs(MissedRunPolicy::RunOnce, due, late), vec![at(10, 3, 0)]);
+    }
+}
@@ -0,0 +1,170 @@
+//! Elasticsearch (and OpenSearch) indexer
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+use super::{check, document_id, Indexer, SearchError, SearchQuery, SearchResults};
+
+pub const ELASTICSEARCH_URL_ENV: &str = "ELASTICSEARCH_URL";
+
+/// Writes through the bulk API; text queries use `multi_match` across all fields, filters
+/// are `term` queries (map filtered fields as `keyword`).
+pub struct ElasticsearchIndexer {
+    url: String,
+    credentials: Option<(String, String)>,
+    refresh: bool,
+    client: reqwest::Client,
+}
+
+impl ElasticsearchIndexer {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            credentials: None,
+            refresh: false,
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, SearchError> {
+        let url = std::env::var(ELASTICSEARCH_URL_ENV)
+            .map_err(|_| SearchError::Config(format!("{} is not set", ELASTICSEARCH_URL_ENV)))?;
+        Ok(Self::new(&url))
+    }
+
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Make writes searchable before returning (`refresh=wait_for`); slower, useful in tests.
+    pub fn refresh_on_write(mut self) -> Self {
+        self.refresh = true;
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}{}", self.url, path));
+        match &self.credentials {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+
+    async fn bulk(&self, body: String) -> Result<(), SearchError> {
+        let path = if self.refresh { "/_bulk?refresh=wait_for" } else { "/_bulk" };
+        let response = self
+            .request(reqwest::Method::POST, path)
+            .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?;
+        let result: serde_json::Value = check(response).await?.json().await?;
+
+        // The bulk API answers 200 even when individual items fail.
+        if result["errors"].as_bool() == Some(true) {
+            let first = result["items"]
+                .as_array()
+                .and_then(|items| items.iter().find_map(|item| item.as_object()?.values().next()?.get("error").cloned()))
+                .unwrap_or_default();
+            return Err(SearchError::Engine { status: 200, body: first.to_string() });
+        }
+        Ok(())
+    }
+}
+
+/// Elasticsearch query body for a `SearchQuery`.
+fn query_body(query: &SearchQuery) -> serde_json::Value {
+    let must = if query.q.trim().is_empty() {
+        json!({ "match_all": {} })
+    } else {
+        json!({ "multi_match": { "query": query.q, "fields": ["*"], "fuzziness": "AUTO", "lenient": true } })
+    };
+    let filter: Vec<serde_json::Value> =
+        query.filters.iter().map(|(field, value)| json!({ "term": { field.as_str(): value } })).collect();
+
+    json!({
+        "query": { "bool": { "must": must, "filter": filter } },
+        "from": query.offset,
+        "size": query.limit,
+    })
+}
+
+#[async_trait]
+impl Indexer for ElasticsearchIndexer {
+    async fn ensure_index(&self, index: &str) -> Result<(), SearchError> {
+        let response = self.request(reqwest::Method::HEAD, &format!("/{}", index)).send().await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let response = self.request(reqwest::Method::PUT, &format!("/{}", index)).send().await?;
+        match check(response).await {
+            Ok(_) => Ok(()),
+            // Created concurrently by another replica.
+            Err(SearchError::Engine { status: 400, body }) if body.contains("resource_already_exists_exception") => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let mut body = String::new();
+        for document in &documents {
+            let action = json!({ "index": { "_index": index, "_id": document_id(document)? } });
+            body.push_str(&action.to_string());
+            body.push('\n');
+            body.push_str(&document.to_string());
+            body.push('\n');
+        }
+        self.bulk(body).await
+    }
+
+    async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let body: String =
+            ids.iter().map(|id| format!("{}\n", json!({ "delete": { "_index": index, "_id": id } }))).collect();
+        self.bulk(body).await
+    }
+
+    async fn search(&self, index: &str, query: &SearchQuery) -> Result<SearchResults, SearchError> {
+        let response = self
+            .request(reqwest::Method::POST, &format!("/{}/_search", index))
+            .json(&query_body(query))
+            .send()
+            .await?;
+        let result: serde_json::Value = check(response).await?.json().await?;
+
+        let hits = result["hits"]["hits"]
+            .as_array()
+            .map(|hits| hits.iter().map(|hit| hit["_source"].clone()).collect())
+            .unwrap_or_default();
+        Ok(SearchResults { hits, total: result["hits"]["total"]["value"].as_u64().unwrap_or(0) })
+    }
+
+    async fn drop_index(&self, index: &str) -> Result<(), SearchError> {
+        let response = self.request(reqwest::Method::DELETE, &format!("/{}", index)).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check(response).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_body() {
+        let body = query_body(&SearchQuery::new("").filter("category", "bebidas").limit(5).offset(10));
+        assert_eq!(body["query"]["bool"]["must"], json!({ "match_all": {} }));
+        assert_eq!(body["query"]["bool"]["filter"][0], json!({ "term": { "category": "bebidas" } }));
+        assert_eq!(body["from"], 10);
+        assert_eq!(body["size"], 5);
+    }
+}
@@ -0,0 +1,67 @@
+//! Bulk indexing through the jobs subsystem
+//!
+//! Reindexing a catalog of 100k products should not happen inside a request. Split it into
+//! batches with `enqueue_bulk` and let any replica's `WorkerPool` (with `register`) push
+//! them to the engine, with the pool's retries and dead-lettering.
+//!
+//! ```ignore
+//! let pool = search::jobs::register(WorkerPool::new(queue.clone()), indexer.clone());
+//! search::jobs::enqueue_bulk(&queue, &products.for_org(org_id), documents, 500).await?;
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{Indexer, TenantIndex};
+use crate::jobs::{Job, JobError, JobQueue, WorkerPool};
+
+/// Upsert a batch of documents into `index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexDocuments {
+    pub index: String,
+    pub documents: Vec<serde_json::Value>,
+}
+
+impl Job for IndexDocuments {
+    const NAME: &'static str = "search.index_documents";
+}
+
+/// Delete a batch of documents from `index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteDocuments {
+    pub index: String,
+    pub ids: Vec<String>,
+}
+
+impl Job for DeleteDocuments {
+    const NAME: &'static str = "search.delete_documents";
+}
+
+/// Handle the search jobs in `pool` with `indexer`.
+pub fn register(pool: WorkerPool, indexer: Arc<dyn Indexer>) -> WorkerPool {
+    let deleter = indexer.clone();
+    pool.register(move |job: IndexDocuments| {
+        let indexer = indexer.clone();
+        async move { indexer.upsert(&job.index, job.documents).await }
+    })
+    .register(move |job: DeleteDocuments| {
+        let indexer = deleter.clone();
+        async move { indexer.delete(&job.index, job.ids).await }
+    })
+}
+
+/// Enqueue `documents` for `index` in jobs of up to `batch_size`. Returns the job IDs.
+pub async fn enqueue_bulk(
+    queue: &JobQueue,
+    index: &TenantIndex,
+    documents: Vec<serde_json::Value>,
+    batch_size: usize,
+) -> Result<Vec<Uuid>, JobError> {
+    let mut ids = Vec::new();
+    for batch in documents.chunks(batch_size.max(1)) {
+        let job = IndexDocuments { index: index.name().to_string(), documents: batch.to_vec() };
+        ids.push(queue.enqueue(&job).await?);
+    }
+    Ok(ids)
+}
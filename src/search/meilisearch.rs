@@ -0,0 +1,144 @@
+//! Meilisearch indexer
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+use super::{check, Indexer, SearchError, SearchQuery, SearchResults};
+
+pub const MEILISEARCH_URL_ENV: &str = "MEILISEARCH_URL";
+pub const MEILISEARCH_API_KEY_ENV: &str = "MEILISEARCH_API_KEY";
+
+/// Indexes with `id` as primary key. Writes are asynchronous tasks in Meilisearch: they are
+/// accepted here and become searchable shortly after.
+pub struct MeilisearchIndexer {
+    url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl MeilisearchIndexer {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            api_key: None,
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, SearchError> {
+        let url = std::env::var(MEILISEARCH_URL_ENV)
+            .map_err(|_| SearchError::Config(format!("{} is not set", MEILISEARCH_URL_ENV)))?;
+        let indexer = Self::new(&url);
+        Ok(match std::env::var(MEILISEARCH_API_KEY_ENV) {
+            Ok(key) => indexer.api_key(&key),
+            Err(_) => indexer,
+        })
+    }
+
+    pub fn api_key(mut self, key: &str) -> Self {
+        self.api_key = Some(key.to_string());
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}{}", self.url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+}
+
+/// Meilisearch filter expression for exact matches.
+fn filter_expression(filters: &[(String, serde_json::Value)]) -> Option<String> {
+    let clauses: Vec<String> = filters
+        .iter()
+        .map(|(field, value)| match value {
+            serde_json::Value::String(s) => format!("{} = \"{}\"", field, s.replace('"', "\\\"")),
+            other => format!("{} = {}", field, other),
+        })
+        .collect();
+    (!clauses.is_empty()).then(|| clauses.join(" AND "))
+}
+
+#[async_trait]
+impl Indexer for MeilisearchIndexer {
+    async fn ensure_index(&self, index: &str) -> Result<(), SearchError> {
+        let response = self.request(reqwest::Method::GET, &format!("/indexes/{}", index)).send().await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let response = self
+            .request(reqwest::Method::POST, "/indexes")
+            .json(&json!({ "uid": index, "primaryKey": "id" }))
+            .send()
+            .await?;
+        check(response).await.map(|_| ())
+    }
+
+    async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let response = self
+            .request(reqwest::Method::POST, &format!("/indexes/{}/documents?primaryKey=id", index))
+            .json(&documents)
+            .send()
+            .await?;
+        check(response).await.map(|_| ())
+    }
+
+    async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let response = self
+            .request(reqwest::Method::POST, &format!("/indexes/{}/documents/delete-batch", index))
+            .json(&ids)
+            .send()
+            .await?;
+        check(response).await.map(|_| ())
+    }
+
+    async fn search(&self, index: &str, query: &SearchQuery) -> Result<SearchResults, SearchError> {
+        let mut body = json!({ "q": query.q, "limit": query.limit, "offset": query.offset });
+        if let Some(filter) = filter_expression(&query.filters) {
+            body["filter"] = json!(filter);
+        }
+        let response = self
+            .request(reqwest::Method::POST, &format!("/indexes/{}/search", index))
+            .json(&body)
+            .send()
+            .await?;
+        let result: serde_json::Value = check(response).await?.json().await?;
+
+        Ok(SearchResults {
+            hits: result["hits"].as_array().cloned().unwrap_or_default(),
+            total: result["estimatedTotalHits"].as_u64().or_else(|| result["totalHits"].as_u64()).unwrap_or(0),
+        })
+    }
+
+    async fn drop_index(&self, index: &str) -> Result<(), SearchError> {
+        let response = self.request(reqwest::Method::DELETE, &format!("/indexes/{}", index)).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check(response).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_expression() {
+        let filters = vec![("category".to_string(), json!("bebidas \"frías\"")), ("active".to_string(), json!(true))];
+        assert_eq!(
+            filter_expression(&filters).unwrap(),
+            "category = \"bebidas \\\"frías\\\"\" AND active = true"
+        );
+        assert_eq!(filter_expression(&[]), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Search Indexing
+//!
+//! An `Indexer` talks to a search engine (`MeilisearchIndexer`, `ElasticsearchIndexer`).
+//! Every organization gets its own index (`products_<org>`) through `TenantIndexes`, so a
+//! query can never return another tenant's documents. Large imports go through the jobs
+//! subsystem (`jobs::enqueue_bulk`), and `SearchSync` keeps an index up to date from domain
+//! events:
+//!
+//! ```ignore
+//! let indexer: Arc<dyn Indexer> = Arc::new(MeilisearchIndexer::from_env()?);
+//! let products = TenantIndexes::new(indexer.clone(), "products");
+//!
+//! let _sync = SearchSync::new(products.clone())
+//!     .upsert_on("lanai.inventory.product.created.*", |e: ProductCreatedEvent| {
+//!         (e.org_id.into_uuid(), json!({ "id": e.product_id, "name": e.name, "description": e.description }))
+//!     })
+//!     .start()
+//!     .await?;
+//!
+//! let results = products.for_org(tenant.org_id).search(&SearchQuery::new("cafe").limit(20)).await?;
+//! ```
+//!
+//! Documents are JSON objects with a string or UUID `id`.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod elasticsearch;
+pub mod jobs;
+pub mod meilisearch;
+pub mod sync;
+
+pub use elasticsearch::ElasticsearchIndexer;
+pub use meilisearch::MeilisearchIndexer;
+pub use sync::SearchSync;
+
+/// Search error types
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("Search engine request failed: {0}")]
+    Request(String),
+
+    #[error("Search engine returned {status}: {body}")]
+    Engine { status: u16, body: String },
+
+    #[error("Invalid search configuration: {0}")]
+    Config(String),
+
+    #[error("Document has no id")]
+    MissingId,
+
+    #[error("Malformed event: {0}")]
+    Event(String),
+}
+
+impl From<reqwest::Error> for SearchError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(e.to_string())
+    }
+}
+
+/// Turn a non-2xx response into `SearchError::Engine`.
+pub(crate) async fn check(response: reqwest::Response) -> Result<reqwest::Response, SearchError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let body = response.text().await.unwrap_or_default();
+    Err(SearchError::Engine { status, body })
+}
+
+/// `id` of a document as a string.
+pub(crate) fn document_id(document: &serde_json::Value) -> Result<String, SearchError> {
+    match document.get("id") {
+        Some(serde_json::Value::String(id)) => Ok(id.clone()),
+        Some(serde_json::Value::Number(id)) => Ok(id.to_string()),
+        _ => Err(SearchError::MissingId),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Exact-match filters on document fields.
+    #[serde(default)]
+    pub filters: Vec<(String, serde_json::Value)>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl SearchQuery {
+    /// First 20 matches for `q` (empty matches everything).
+    pub fn new(q: &str) -> Self {
+        Self { q: q.to_string(), filters: Vec::new(), limit: 20, offset: 0 }
+    }
+
+    pub fn filter(mut self, field: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.filters.push((field.to_string(), value.into()));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<serde_json::Value>,
+    /// Total matches, estimated by some engines.
+    pub total: u64,
+}
+
+/// A search engine.
+#[async_trait]
+pub trait Indexer: Send + Sync {
+    /// Create `index` if it does not exist.
+    async fn ensure_index(&self, index: &str) -> Result<(), SearchError>;
+
+    /// Add or replace documents by `id`.
+    async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError>;
+
+    async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError>;
+
+    async fn search(&self, index: &str, query: &SearchQuery) -> Result<SearchResults, SearchError>;
+
+    /// Remove `index` and its documents (e.g. when an organization is deleted).
+    async fn drop_index(&self, index: &str) -> Result<(), SearchError>;
+}
+
+/// One logical index split per organization.
+#[derive(Clone)]
+pub struct TenantIndexes {
+    indexer: Arc<dyn Indexer>,
+    base: Arc<str>,
+}
+
+impl TenantIndexes {
+    pub fn new(indexer: Arc<dyn Indexer>, base: &str) -> Self {
+        Self { indexer, base: Arc::from(base) }
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn indexer(&self) -> Arc<dyn Indexer> {
+        self.indexer.clone()
+    }
+
+    /// Index name for `org_id`, e.g. `products_4f0c...` (valid for both engines).
+    pub fn index_name(&self, org_id: Uuid) -> String {
+        format!("{}_{}", self.base, org_id.simple())
+    }
+
+    pub fn for_org(&self, org_id: Uuid) -> TenantIndex {
+        TenantIndex { indexer: self.indexer.clone(), name: self.index_name(org_id) }
+    }
+}
+
+/// The index of one organization.
+#[derive(Clone)]
+pub struct TenantIndex {
+    indexer: Arc<dyn Indexer>,
+    name: String,
+}
+
+impl TenantIndex {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn ensure(&self) -> Result<(), SearchError> {
+        self.indexer.ensure_index(&self.name).await
+    }
+
+    pub async fn upsert(&self, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+        self.indexer.upsert(&self.name, documents).await
+    }
+
+    pub async fn delete(&self, ids: Vec<String>) -> Result<(), SearchError> {
+        self.indexer.delete(&self.name, ids).await
+    }
+
+    pub async fn search(&self, query: &SearchQuery) -> Result<SearchResults, SearchError> {
+        self.indexer.search(&self.name, query).await
+    }
+
+    pub async fn drop(&self) -> Result<(), SearchError> {
+        self.indexer.drop_index(&self.name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_index_names_and_ids() {
+        struct Noop;
+        #[async_trait]
+        impl Indexer for Noop {
+            async fn ensure_index(&self, _: &str) -> Result<(), SearchError> {
+                Ok(())
+            }
+            async fn upsert(&self, _: &str, _: Vec<serde_json::Value>) -> Result<(), SearchError> {
+                Ok(())
+            }
+            async fn delete(&self, _: &str, _: Vec<String>) -> Result<(), SearchError> {
+                Ok(())
+            }
+            async fn search(&self, _: &str, _: &SearchQuery) -> Result<SearchResults, SearchError> {
+                Ok(SearchResults::default())
+            }
+            async fn drop_index(&self, _: &str) -> Result<(), SearchError> {
+                Ok(())
+            }
+        }
+
+        let org_id = Uuid::new_v4();
+        let products = TenantIndexes::new(Arc::new(Noop), "products");
+        assert_eq!(products.for_org(org_id).name(), format!("products_{}", org_id.simple()));
+        assert_ne!(products.index_name(org_id), products.index_name(Uuid::new_v4()));
+
+        assert_eq!(document_id(&serde_json::json!({ "id": 7 })).unwrap(), "7");
+        assert!(matches!(document_id(&serde_json::json!({ "name": "x" })), Err(SearchError::MissingId)));
+    }
+}
@@ -0,0 +1,152 @@
+//! Keeping an index in sync with domain events
+
+use futures_util::StreamExt;
+use log::{error, info};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::{document_id, SearchError, TenantIndexes};
+use crate::messaging::events::decode_event;
+use crate::messaging::{NatsClient, NatsError};
+
+type Apply = Arc<dyn Fn(&[u8]) -> Result<Change, SearchError> + Send + Sync>;
+
+/// What an event does to the index.
+enum Change {
+    Upsert(Uuid, serde_json::Value),
+    Delete(Uuid, String),
+}
+
+/// Upserts and deletes documents of a `TenantIndexes` as events arrive (enveloped or bare).
+/// Replicas share the events through a queue group; a failed update is logged and the
+/// document catches up on the next event or reindex.
+pub struct SearchSync {
+    indexes: TenantIndexes,
+    routes: Vec<(String, Apply)>,
+}
+
+impl SearchSync {
+    pub fn new(indexes: TenantIndexes) -> Self {
+        Self { indexes, routes: Vec::new() }
+    }
+
+    /// On `subject`, upsert the `(org_id, document)` that `to_document` builds from the event.
+    pub fn upsert_on<E, F>(mut self, subject: &str, to_document: F) -> Self
+    where
+        E: DeserializeOwned,
+        F: Fn(E) -> (Uuid, serde_json::Value) + Send + Sync + 'static,
+    {
+        let apply: Apply = Arc::new(move |payload| {
+            let event = decode_event::<E>(payload).map_err(|e| SearchError::Event(e.to_string()))?;
+            let (org_id, document) = to_document(event);
+            Ok(Change::Upsert(org_id, document))
+        });
+        self.routes.push((subject.to_string(), apply));
+        self
+    }
+
+    /// On `subject`, delete the `(org_id, document id)` that `to_id` returns.
+    pub fn delete_on<E, F>(mut self, subject: &str, to_id: F) -> Self
+    where
+        E: DeserializeOwned,
+        F: Fn(E) -> (Uuid, String) + Send + Sync + 'static,
+    {
+        let apply: Apply = Arc::new(move |payload| {
+            let event = decode_event::<E>(payload).map_err(|e| SearchError::Event(e.to_string()))?;
+            let (org_id, id) = to_id(event);
+            Ok(Change::Delete(org_id, id))
+        });
+        self.routes.push((subject.to_string(), apply));
+        self
+    }
+
+    /// Subscribe to every registered subject until the handles are aborted.
+    pub async fn start(self) -> Result<Vec<JoinHandle<()>>, NatsError> {
+        let group = format!("lanai-search-{}", self.indexes.base());
+        let mut tasks = Vec::new();
+
+        for (subject, apply) in self.routes {
+            let mut subscriber = NatsClient::queue_subscribe(&subject, &group).await?;
+            info!("🔎 Syncing search index '{}' from '{}'", self.indexes.base(), subject);
+
+            let indexes = self.indexes.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    if let Err(e) = apply_change(&indexes, apply(&message.payload)).await {
+                        error!("❌ Search sync of '{}' failed for '{}': {}", indexes.base(), message.subject, e);
+                    }
+                }
+            }));
+        }
+        Ok(tasks)
+    }
+}
+
+async fn apply_change(indexes: &TenantIndexes, change: Result<Change, SearchError>) -> Result<(), SearchError> {
+    match change? {
+        Change::Upsert(org_id, document) => {
+            document_id(&document)?;
+            indexes.for_org(org_id).upsert(vec![document]).await
+        }
+        Change::Delete(org_id, id) => indexes.for_org(org_id).delete(vec![id]).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::events::{LanaiEvent, ProductCreatedEvent};
+    use crate::search::{Indexer, SearchQuery, SearchResults};
+    use async_trait::async_trait;
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recording {
+        upserts: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    #[async_trait]
+    impl Indexer for Recording {
+        async fn ensure_index(&self, _: &str) -> Result<(), SearchError> {
+            Ok(())
+        }
+        async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+            self.upserts.lock().await.extend(documents.into_iter().map(|d| (index.to_string(), d)));
+            Ok(())
+        }
+        async fn delete(&self, _: &str, _: Vec<String>) -> Result<(), SearchError> {
+            Ok(())
+        }
+        async fn search(&self, _: &str, _: &SearchQuery) -> Result<SearchResults, SearchError> {
+            Ok(SearchResults::default())
+        }
+        async fn drop_index(&self, _: &str) -> Result<(), SearchError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_becomes_tenant_document() {
+        let indexer = Arc::new(Recording::default());
+        let indexes = TenantIndexes::new(indexer.clone(), "products");
+        let sync = SearchSync::new(indexes.clone()).upsert_on("lanai.inventory.product.created.*", |e: ProductCreatedEvent| {
+            (e.org_id.into_uuid(), serde_json::json!({ "id": e.product_id, "name": e.name }))
+        });
+
+        let event = ProductCreatedEvent {
+            product_id: crate::common::ProductId::generate(),
+            org_id: crate::common::OrgId::generate(),
+            name: "Café de altura".to_string(),
+            description: None,
+        };
+        let org_id = event.org_id.into_uuid();
+        let payload = serde_json::to_vec(&event.into_envelope()).unwrap();
+        apply_change(&indexes, (sync.routes[0].1)(&payload)).await.unwrap();
+
+        let upserts = indexer.upserts.lock().await;
+        assert_eq!(upserts[0].0, indexes.index_name(org_id));
+        assert_eq!(upserts[0].1["name"], "Café de altura");
+    }
+}
@@ -0,0 +1,61 @@
+//! Environment variable secrets
+//!
+//! Keys map to variable names by upper-casing them and replacing `/`, `-` and `.` with
+//! `_`: `jwt/signing-key` reads `JWT_SIGNING_KEY` (or `{PREFIX}_JWT_SIGNING_KEY`).
+
+use async_trait::async_trait;
+
+use super::{Secret, SecretError, SecretProvider};
+
+#[derive(Debug, Clone, Default)]
+pub struct EnvProvider {
+    prefix: Option<String>,
+}
+
+impl EnvProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self { prefix: Some(prefix.trim_end_matches('_').to_uppercase()) }
+    }
+
+    pub fn var_name(&self, key: &str) -> String {
+        let name: String = key
+            .chars()
+            .map(|c| if matches!(c, '/' | '-' | '.') { '_' } else { c.to_ascii_uppercase() })
+            .collect();
+        match &self.prefix {
+            Some(prefix) => format!("{}_{}", prefix, name),
+            None => name,
+        }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for EnvProvider {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn get(&self, key: &str) -> Result<Secret, SecretError> {
+        let name = self.var_name(key);
+        std::env::var(&name).map(Secret::new).map_err(|_| SecretError::NotFound(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_env_lookup() {
+        let provider = EnvProvider::with_prefix("lanai_test_secrets");
+        assert_eq!(provider.var_name("jwt/signing-key"), "LANAI_TEST_SECRETS_JWT_SIGNING_KEY");
+
+        std::env::set_var("LANAI_TEST_SECRETS_JWT_SIGNING_KEY", "s3cret");
+        assert_eq!(provider.get("jwt/signing-key").await.unwrap().value.expose(), "s3cret");
+        assert!(matches!(provider.get("missing").await, Err(SecretError::NotFound(_))));
+    }
+}
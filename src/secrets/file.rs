@@ -0,0 +1,80 @@
+//! Mounted-file secrets
+//!
+//! Reads `{dir}/{key}`, as laid out by Kubernetes secret volumes or the Vault agent
+//! injector. The file's modification time is the secret version, so `SecretsManager`
+//! notices when the mount is updated.
+
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use super::{Secret, SecretError, SecretProvider};
+
+#[derive(Debug, Clone)]
+pub struct FileProvider {
+    dir: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, SecretError> {
+        let relative = Path::new(key);
+        // Keys must stay inside the secrets directory.
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(SecretError::NotFound(key.to_string()));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait]
+impl SecretProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, key: &str) -> Result<Secret, SecretError> {
+        let path = self.path(key)?;
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(SecretError::NotFound(path.display().to_string()))
+            }
+            Err(e) => return Err(SecretError::Provider(format!("{}: {}", path.display(), e))),
+        };
+
+        let version = tokio::fs::metadata(&path)
+            .await
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos().to_string());
+
+        let mut secret = Secret::new(contents.trim_end_matches(['\n', '\r']));
+        secret.version = version;
+        Ok(secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_trimmed_file_and_rejects_traversal() {
+        let dir = std::env::temp_dir().join(format!("lanai-secrets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("db-password"), "p4ss\n").unwrap();
+
+        let provider = FileProvider::new(&dir);
+        let secret = provider.get("db-password").await.unwrap();
+        assert_eq!(secret.value.expose(), "p4ss");
+        assert!(secret.version.is_some());
+
+        assert!(matches!(provider.get("../etc/passwd").await, Err(SecretError::NotFound(_))));
+        assert!(matches!(provider.get("missing").await, Err(SecretError::NotFound(_))));
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
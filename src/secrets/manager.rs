@@ -0,0 +1,218 @@
+//! Cached secrets with lease renewal and rotation callbacks
+
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use super::{Secret, SecretError, SecretProvider, SecretValue};
+
+type Callback = Box<dyn Fn(&SecretValue) + Send + Sync>;
+
+struct Inner {
+    provider: Arc<dyn SecretProvider>,
+    cache: RwLock<HashMap<String, Secret>>,
+    callbacks: Mutex<HashMap<String, Vec<Callback>>>,
+    refresh_interval: Duration,
+}
+
+#[derive(Clone)]
+pub struct SecretsManager {
+    inner: Arc<Inner>,
+}
+
+impl SecretsManager {
+    /// Check secrets for renewal or rotation every 60 seconds once started.
+    pub fn new<P: SecretProvider + 'static>(provider: P) -> Self {
+        Self::from_provider(Arc::new(provider))
+    }
+
+    pub fn from_provider(provider: Arc<dyn SecretProvider>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                provider,
+                cache: RwLock::new(HashMap::new()),
+                callbacks: Mutex::new(HashMap::new()),
+                refresh_interval: Duration::from_secs(60),
+            }),
+        }
+    }
+
+    /// Must be called before the manager is cloned or started.
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.refresh_interval = interval;
+        }
+        self
+    }
+
+    /// Cached value of `key`, fetched from the provider on first use or once its lease expired.
+    pub async fn get(&self, key: &str) -> Result<SecretValue, SecretError> {
+        if let Some(secret) = self.inner.cache.read().await.get(key) {
+            if !secret.lease.as_ref().is_some_and(|lease| lease.is_expired()) {
+                return Ok(secret.value.clone());
+            }
+        }
+
+        let secret = self.inner.provider.get(key).await?;
+        let value = secret.value.clone();
+        self.inner.cache.write().await.insert(key.to_string(), secret);
+        Ok(value)
+    }
+
+    /// Call `callback` with the new value whenever `key` is rotated.
+    pub fn on_rotate<F>(&self, key: &str, callback: F)
+    where
+        F: Fn(&SecretValue) + Send + Sync + 'static,
+    {
+        self.inner
+            .callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.to_string())
+            .or_default()
+            .push(Box::new(callback));
+    }
+
+    /// Periodically renew leases and pick up rotated secrets. Abort the handle to stop.
+    pub fn start(&self) -> JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(manager.inner.refresh_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                manager.refresh().await;
+            }
+        })
+    }
+
+    /// One renewal/rotation pass over every cached secret.
+    pub async fn refresh(&self) {
+        let cached: Vec<(String, Secret)> = self
+            .inner
+            .cache
+            .read()
+            .await
+            .iter()
+            .map(|(key, secret)| (key.clone(), secret.clone()))
+            .collect();
+
+        for (key, current) in cached {
+            match &current.lease {
+                Some(lease) if !lease.needs_renewal() => continue,
+                Some(lease) if lease.renewable => match self.inner.provider.renew(lease).await {
+                    Ok(renewed) => {
+                        debug!("🔐 Renewed lease for secret '{}' ({:?})", key, renewed.duration);
+                        if let Some(secret) = self.inner.cache.write().await.get_mut(&key) {
+                            secret.lease = Some(renewed);
+                        }
+                        continue;
+                    }
+                    Err(e) => warn!("⚠️ Failed to renew lease for secret '{}', fetching a new one: {}", key, e),
+                },
+                _ => {}
+            }
+
+            let fresh = match self.inner.provider.get(&key).await {
+                Ok(fresh) => fresh,
+                Err(e) => {
+                    warn!("⚠️ Failed to refresh secret '{}' from {}: {}", key, self.inner.provider.name(), e);
+                    continue;
+                }
+            };
+
+            let rotated = fresh.value != current.value;
+            let value = fresh.value.clone();
+            self.inner.cache.write().await.insert(key.clone(), fresh);
+
+            if rotated {
+                info!("🔑 Secret '{}' was rotated", key);
+                if let Some(callbacks) = self.inner.callbacks.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+                    for callback in callbacks {
+                        callback(&value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::Lease;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct Rotating {
+        version: AtomicU32,
+        renewals: AtomicU32,
+        leased: bool,
+    }
+
+    #[async_trait]
+    impl SecretProvider for Rotating {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        async fn get(&self, _key: &str) -> Result<Secret, SecretError> {
+            let mut secret = Secret::new(format!("v{}", self.version.load(Ordering::SeqCst)));
+            if self.leased {
+                secret.lease = Some(Lease {
+                    id: "lease".to_string(),
+                    duration: Duration::from_secs(30),
+                    renewable: true,
+                    obtained_at: Instant::now() - Duration::from_secs(25),
+                });
+            }
+            Ok(secret)
+        }
+
+        async fn renew(&self, lease: &Lease) -> Result<Lease, SecretError> {
+            self.renewals.fetch_add(1, Ordering::SeqCst);
+            Ok(Lease { obtained_at: Instant::now(), ..lease.clone() })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_notifies_callbacks() {
+        let provider = Arc::new(Rotating::default());
+        let manager = SecretsManager::from_provider(provider.clone());
+        assert_eq!(manager.get("jwt").await.unwrap().expose(), "v0");
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        manager.on_rotate("jwt", move |value| sink.lock().unwrap().push(value.expose().to_string()));
+
+        manager.refresh().await;
+        assert!(seen.lock().unwrap().is_empty());
+
+        provider.version.store(1, Ordering::SeqCst);
+        manager.refresh().await;
+        assert_eq!(*seen.lock().unwrap(), vec!["v1".to_string()]);
+        assert_eq!(manager.get("jwt").await.unwrap().expose(), "v1");
+    }
+
+    #[tokio::test]
+    async fn test_leases_are_renewed_not_refetched() {
+        let provider = Arc::new(Rotating { leased: true, ..Default::default() });
+        let manager = SecretsManager::from_provider(provider.clone());
+        manager.get("db").await.unwrap();
+
+        provider.version.store(1, Ordering::SeqCst);
+        manager.refresh().await;
+        assert_eq!(provider.renewals.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.get("db").await.unwrap().expose(), "v0");
+
+        // Freshly renewed: nothing to do on the next pass.
+        manager.refresh().await;
+        assert_eq!(provider.renewals.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,151 @@
+//! Secrets Providers
+//!
+//! A `SecretProvider` resolves named secrets (JWT signing keys, DB passwords, API tokens)
+//! from a backing store:
+//! - `EnvProvider`: environment variables, for local development
+//! - `FileProvider`: files mounted by Kubernetes or a sidecar, picked up when they change
+//! - `VaultProvider`: HashiCorp Vault KV v2 and dynamic secrets, with token or Kubernetes auth
+//!
+//! `SecretsManager` caches resolved secrets, renews Vault leases before they expire and
+//! re-reads rotated secrets, notifying `on_rotate` callbacks so pools and signers can
+//! pick up new credentials without a restart.
+//!
+//! ```ignore
+//! let secrets = SecretsManager::new(VaultProvider::from_env()?);
+//! let db_password = secrets.get("database/creds/billing#password").await?;
+//! secrets.on_rotate("database/creds/billing#password", |password| rebuild_pool(password));
+//! let _refresh = secrets.start();
+//! ```
+
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub mod env;
+pub mod file;
+pub mod manager;
+pub mod vault;
+
+pub use env::EnvProvider;
+pub use file::FileProvider;
+pub use manager::SecretsManager;
+pub use vault::{VaultAuth, VaultProvider};
+
+/// Secret material. `Debug` and `Display` never print the value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretValue(Arc<str>);
+
+impl SecretValue {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Arc::from(value.into()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretValue(***)")
+    }
+}
+
+impl fmt::Display for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// Lease attached to a dynamic secret; the secret is revoked once it expires.
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub id: String,
+    pub duration: Duration,
+    pub renewable: bool,
+    pub obtained_at: Instant,
+}
+
+impl Lease {
+    /// True once two thirds of the lease have elapsed.
+    pub fn needs_renewal(&self) -> bool {
+        self.obtained_at.elapsed() >= self.duration.mul_f64(2.0 / 3.0)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.obtained_at.elapsed() >= self.duration
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Secret {
+    pub value: SecretValue,
+    /// Provider-specific version (KV version, file mtime); `None` if unknown.
+    pub version: Option<String>,
+    pub lease: Option<Lease>,
+}
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self { value: SecretValue::new(value), version: None, lease: None }
+    }
+}
+
+/// Secrets error types
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("Secret not found: {0}")]
+    NotFound(String),
+
+    #[error("Secret provider authentication failed: {0}")]
+    Auth(String),
+
+    #[error("Secret provider error: {0}")]
+    Provider(String),
+
+    #[error("Lease {0} is not renewable")]
+    NotRenewable(String),
+
+    #[error("Secret provider is not configured: {0}")]
+    NotConfigured(String),
+}
+
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    async fn get(&self, key: &str) -> Result<Secret, SecretError>;
+
+    /// Extend `lease`, returning the renewed lease.
+    async fn renew(&self, lease: &Lease) -> Result<Lease, SecretError> {
+        Err(SecretError::NotRenewable(lease.id.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_value_is_redacted() {
+        let value = SecretValue::new("hunter2");
+        assert_eq!(format!("{:?}", value), "SecretValue(***)");
+        assert_eq!(value.to_string(), "***");
+        assert_eq!(value.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_lease_renewal_window() {
+        let lease = Lease {
+            id: "db/creds/1".to_string(),
+            duration: Duration::from_secs(60),
+            renewable: true,
+            obtained_at: Instant::now() - Duration::from_secs(45),
+        };
+        assert!(lease.needs_renewal());
+        assert!(!lease.is_expired());
+    }
+}
@@ -0,0 +1,278 @@
+//! HashiCorp Vault secrets
+//!
+//! Keys are Vault API paths with an optional `#field` selector:
+//! - `secret/data/billing/jwt#signing_key`: KV v2 entry (the version is tracked)
+//! - `database/creds/billing#password`: dynamic secret with a renewable lease
+//!
+//! Without a selector the entry's only field, or its `value` field, is used.
+//! With Kubernetes auth the provider logs in with the pod's service account token and
+//! logs in again when its Vault token is rejected.
+
+use async_trait::async_trait;
+use log::{info, warn};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use super::{Lease, Secret, SecretError, SecretProvider};
+
+pub const VAULT_ADDR_ENV: &str = "VAULT_ADDR";
+pub const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+pub const VAULT_NAMESPACE_ENV: &str = "VAULT_NAMESPACE";
+pub const VAULT_K8S_ROLE_ENV: &str = "VAULT_K8S_ROLE";
+pub const VAULT_K8S_MOUNT_ENV: &str = "VAULT_K8S_MOUNT";
+
+const SERVICE_ACCOUNT_TOKEN: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+#[derive(Debug, Clone)]
+pub enum VaultAuth {
+    Token(String),
+    Kubernetes { role: String, mount: String, jwt_path: PathBuf },
+}
+
+impl VaultAuth {
+    /// Kubernetes auth on the default `kubernetes` mount with the pod's service account token.
+    pub fn kubernetes(role: &str) -> Self {
+        Self::Kubernetes {
+            role: role.to_string(),
+            mount: "kubernetes".to_string(),
+            jwt_path: PathBuf::from(SERVICE_ACCOUNT_TOKEN),
+        }
+    }
+}
+
+pub struct VaultProvider {
+    client: reqwest::Client,
+    addr: String,
+    namespace: Option<String>,
+    auth: VaultAuth,
+    token: RwLock<Option<String>>,
+}
+
+impl VaultProvider {
+    pub fn new(addr: &str, auth: VaultAuth) -> Self {
+        let token = match &auth {
+            VaultAuth::Token(token) => Some(token.clone()),
+            VaultAuth::Kubernetes { .. } => None,
+        };
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            addr: addr.trim_end_matches('/').to_string(),
+            namespace: None,
+            auth,
+            token: RwLock::new(token),
+        }
+    }
+
+    /// `VAULT_ADDR` plus `VAULT_TOKEN` or `VAULT_K8S_ROLE` (and optionally `VAULT_K8S_MOUNT`, `VAULT_NAMESPACE`).
+    pub fn from_env() -> Result<Self, SecretError> {
+        let addr = std::env::var(VAULT_ADDR_ENV).map_err(|_| SecretError::NotConfigured(VAULT_ADDR_ENV.to_string()))?;
+        let auth = if let Ok(token) = std::env::var(VAULT_TOKEN_ENV) {
+            VaultAuth::Token(token)
+        } else if let Ok(role) = std::env::var(VAULT_K8S_ROLE_ENV) {
+            let mut auth = VaultAuth::kubernetes(&role);
+            if let (VaultAuth::Kubernetes { mount, .. }, Ok(custom)) = (&mut auth, std::env::var(VAULT_K8S_MOUNT_ENV)) {
+                *mount = custom;
+            }
+            auth
+        } else {
+            return Err(SecretError::NotConfigured(format!("{} or {}", VAULT_TOKEN_ENV, VAULT_K8S_ROLE_ENV)));
+        };
+
+        let mut provider = Self::new(&addr, auth);
+        provider.namespace = std::env::var(VAULT_NAMESPACE_ENV).ok();
+        Ok(provider)
+    }
+
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    async fn token(&self) -> Result<String, SecretError> {
+        if let Some(token) = self.token.read().await.clone() {
+            return Ok(token);
+        }
+
+        let mut slot = self.token.write().await;
+        if let Some(token) = slot.clone() {
+            return Ok(token);
+        }
+        let token = self.login().await?;
+        *slot = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn login(&self) -> Result<String, SecretError> {
+        let VaultAuth::Kubernetes { role, mount, jwt_path } = &self.auth else {
+            return Err(SecretError::Auth("Vault token was rejected".to_string()));
+        };
+
+        let jwt = tokio::fs::read_to_string(jwt_path)
+            .await
+            .map_err(|e| SecretError::Auth(format!("cannot read {}: {}", jwt_path.display(), e)))?;
+
+        let mut request = self
+            .client
+            .post(format!("{}/v1/auth/{}/login", self.addr, mount))
+            .json(&json!({ "role": role, "jwt": jwt.trim() }));
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+
+        let response = request.send().await.map_err(|e| SecretError::Provider(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(SecretError::Auth(format!("Kubernetes login as '{}' failed: {}", role, response.status())));
+        }
+        let body: Value = response.json().await.map_err(|e| SecretError::Provider(e.to_string()))?;
+        let token = body["auth"]["client_token"]
+            .as_str()
+            .ok_or_else(|| SecretError::Auth("login response has no client token".to_string()))?;
+
+        info!("🔐 Logged in to Vault with Kubernetes role '{}'", role);
+        Ok(token.to_string())
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value, SecretError> {
+        let url = format!("{}/v1/{}", self.addr, path.trim_start_matches('/'));
+        let mut relogged = false;
+
+        loop {
+            let mut request = self.client.request(method.clone(), &url).header("X-Vault-Token", self.token().await?);
+            if let Some(namespace) = &self.namespace {
+                request = request.header("X-Vault-Namespace", namespace);
+            }
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
+
+            let response = request.send().await.map_err(|e| SecretError::Provider(e.to_string()))?;
+            match response.status() {
+                StatusCode::NOT_FOUND => return Err(SecretError::NotFound(path.to_string())),
+                StatusCode::FORBIDDEN if !relogged && matches!(self.auth, VaultAuth::Kubernetes { .. }) => {
+                    warn!("⚠️ Vault token rejected, logging in again");
+                    *self.token.write().await = None;
+                    relogged = true;
+                }
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(SecretError::Auth(format!("access to '{}' denied", path)))
+                }
+                status if !status.is_success() => {
+                    let detail = response.text().await.unwrap_or_default();
+                    return Err(SecretError::Provider(format!("{} {}: {}", status, path, detail)));
+                }
+                _ => return response.json().await.map_err(|e| SecretError::Provider(e.to_string())),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for VaultProvider {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn get(&self, key: &str) -> Result<Secret, SecretError> {
+        let (path, field) = match key.split_once('#') {
+            Some((path, field)) => (path, Some(field)),
+            None => (key, None),
+        };
+        let body = self.send(Method::GET, path, None).await?;
+        parse_secret(key, &body, field)
+    }
+
+    async fn renew(&self, lease: &Lease) -> Result<Lease, SecretError> {
+        if !lease.renewable {
+            return Err(SecretError::NotRenewable(lease.id.clone()));
+        }
+        let body = self
+            .send(
+                Method::PUT,
+                "sys/leases/renew",
+                Some(json!({ "lease_id": lease.id, "increment": lease.duration.as_secs() })),
+            )
+            .await?;
+        parse_lease(&body).ok_or_else(|| SecretError::Provider(format!("renewal of {} returned no lease", lease.id)))
+    }
+}
+
+fn parse_lease(body: &Value) -> Option<Lease> {
+    let id = body["lease_id"].as_str().filter(|id| !id.is_empty())?;
+    Some(Lease {
+        id: id.to_string(),
+        duration: Duration::from_secs(body["lease_duration"].as_u64().unwrap_or(0)),
+        renewable: body["renewable"].as_bool().unwrap_or(false),
+        obtained_at: Instant::now(),
+    })
+}
+
+/// Extract the selected field from a KV v2 or dynamic secret response.
+fn parse_secret(key: &str, body: &Value, field: Option<&str>) -> Result<Secret, SecretError> {
+    let data = &body["data"];
+    let (fields, version) = match (data["data"].as_object(), data.get("metadata")) {
+        (Some(fields), Some(metadata)) => (fields, metadata["version"].as_u64().map(|v| v.to_string())),
+        _ => match data.as_object() {
+            Some(fields) => (fields, None),
+            None => return Err(SecretError::NotFound(key.to_string())),
+        },
+    };
+
+    let value = match field {
+        Some(field) => fields.get(field),
+        None if fields.len() == 1 => fields.values().next(),
+        None => fields.get("value"),
+    }
+    .ok_or_else(|| SecretError::NotFound(key.to_string()))?;
+
+    let value = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    let mut secret = Secret::new(value);
+    secret.version = version;
+    secret.lease = parse_lease(body);
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kv_v2_response() {
+        let body = json!({
+            "lease_id": "",
+            "data": {
+                "data": { "signing_key": "abc", "issuer": "lanai" },
+                "metadata": { "version": 3 }
+            }
+        });
+        let secret = parse_secret("k", &body, Some("signing_key")).unwrap();
+        assert_eq!(secret.value.expose(), "abc");
+        assert_eq!(secret.version.as_deref(), Some("3"));
+        assert!(secret.lease.is_none());
+        assert!(parse_secret("k", &body, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_dynamic_response() {
+        let body = json!({
+            "lease_id": "database/creds/billing/abc123",
+            "lease_duration": 3600,
+            "renewable": true,
+            "data": { "username": "v-billing-xyz", "password": "pw" }
+        });
+        let secret = parse_secret("k", &body, Some("password")).unwrap();
+        assert_eq!(secret.value.expose(), "pw");
+        let lease = secret.lease.unwrap();
+        assert_eq!(lease.duration, Duration::from_secs(3600));
+        assert!(lease.renewable);
+    }
+}
@@ -0,0 +1,316 @@
+//! Batched sub-requests
+//!
+//! Clients on slow links (mobile POS) can send several API calls in one round-trip:
+//!
+//! ```json
+//! POST /batch
+//! { "requests": [
+//!     { "id": "stock", "method": "GET", "path": "/api/v1/stock?sku=CAF-250" },
+//!     { "id": "sale", "method": "POST", "path": "/api/v1/sales", "body": { "sku": "CAF-250", "qty": 2 } }
+//! ] }
+//! ```
+//!
+//! Every item is sent back to this service over loopback with the caller's credentials, so
+//! it goes through the same middleware as a direct call: auth, tenant, and rate limiting
+//! (counted against the caller, not against loopback). The answer lists one result per item
+//! in order; it is `200` when every item succeeded and `207` when some failed.
+//!
+//! ```ignore
+//! ServerBuilder::new("lanai-sales-service").batch(BatchEndpoint::new()).run(configure).await?;
+//! ```
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::stream::{self, StreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::error::LanaiError;
+
+/// Marks a sub-request as issued by this process's batch endpoint.
+pub const BATCH_HEADER: &str = "X-Lanai-Batch";
+/// Address of the client that sent the batch, for rate limiting.
+pub const BATCH_CLIENT_HEADER: &str = "X-Lanai-Batch-Client";
+
+/// Caller headers copied to every item (an item's own headers take precedence).
+const FORWARDED_HEADERS: &[&str] = &["authorization", "cookie", "x-api-key", "x-organization-id", "accept-language"];
+
+/// Random per process: only sub-requests sent by this process can claim a batch client.
+fn batch_secret() -> &'static str {
+    static SECRET: OnceLock<String> = OnceLock::new();
+    SECRET.get_or_init(|| uuid::Uuid::new_v4().simple().to_string())
+}
+
+/// The original client of a sub-request sent by this process's batch endpoint.
+pub(crate) fn batch_client(headers: &actix_web::http::header::HeaderMap) -> Option<String> {
+    let secret = headers.get(BATCH_HEADER)?.to_str().ok()?;
+    if secret != batch_secret() {
+        return None;
+    }
+    headers.get(BATCH_CLIENT_HEADER)?.to_str().ok().map(str::to_string)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    /// Echoed in the result; defaults to the item's position.
+    #[serde(default)]
+    pub id: Option<String>,
+    pub method: String,
+    /// Path and query, e.g. `/api/v1/products?page=2`.
+    pub path: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchPayload {
+    pub requests: Vec<BatchItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItemResult {
+    pub id: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// The response body as JSON, or as a string if it is not JSON.
+    pub body: serde_json::Value,
+}
+
+impl BatchItemResult {
+    fn error(id: String, status: StatusCode, message: &str) -> Self {
+        Self { id, status: status.as_u16(), headers: HashMap::new(), body: serde_json::json!({ "error": message }) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResults {
+    pub responses: Vec<BatchItemResult>,
+}
+
+struct Inner {
+    path: String,
+    max_items: usize,
+    concurrency: usize,
+    local_url: String,
+    item_timeout: Duration,
+    client: reqwest::Client,
+}
+
+/// `POST /batch`, executing items against this service.
+#[derive(Clone)]
+pub struct BatchEndpoint {
+    inner: Arc<Inner>,
+}
+
+impl Default for BatchEndpoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchEndpoint {
+    /// `POST /batch`, up to 20 items, 4 at a time, 30s per item. `ServerBuilder::batch` sets
+    /// the loopback URL; otherwise call `local_url`.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                path: "/batch".to_string(),
+                max_items: 20,
+                concurrency: 4,
+                local_url: "http://127.0.0.1:8080".to_string(),
+                item_timeout: Duration::from_secs(30),
+                client: reqwest::Client::builder()
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()
+                    .expect("HTTP client for batch items"),
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("BatchEndpoint is configured before it is shared"));
+        self
+    }
+
+    pub fn path(self, path: &str) -> Self {
+        self.update(|inner| inner.path = path.to_string())
+    }
+
+    pub fn max_items(self, max_items: usize) -> Self {
+        self.update(|inner| inner.max_items = max_items.max(1))
+    }
+
+    /// Items executed at the same time; 1 runs them strictly in order.
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        self.update(|inner| inner.concurrency = concurrency.max(1))
+    }
+
+    /// Where this service listens, as reached from itself (e.g. `http://127.0.0.1:8080`).
+    pub fn local_url(self, url: &str) -> Self {
+        self.update(|inner| inner.local_url = url.trim_end_matches('/').to_string())
+    }
+
+    pub fn item_timeout(self, timeout: Duration) -> Self {
+        self.update(|inner| inner.item_timeout = timeout)
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let batch = self.clone();
+        cfg.route(
+            &self.inner.path,
+            web::post().to(move |req: HttpRequest, payload: web::Json<BatchPayload>| {
+                let batch = batch.clone();
+                async move { batch.handle(req, payload.into_inner()).await }
+            }),
+        );
+    }
+
+    async fn handle(&self, req: HttpRequest, payload: BatchPayload) -> Result<HttpResponse, LanaiError> {
+        if payload.requests.len() > self.inner.max_items {
+            return Err(LanaiError::BadRequest(format!("A batch holds at most {} requests", self.inner.max_items)));
+        }
+
+        let client_ip = req.connection_info().peer_addr().unwrap_or("unknown").to_string();
+        let client_ip = batch_client(req.headers()).unwrap_or(client_ip);
+        let mut shared = reqwest::header::HeaderMap::new();
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = req.headers().get(*name) {
+                if let Ok(value) = reqwest::header::HeaderValue::from_bytes(value.as_bytes()) {
+                    shared.insert(*name, value);
+                }
+            }
+        }
+        shared.insert(header_name(BATCH_HEADER), reqwest::header::HeaderValue::from_static(batch_secret()));
+        if let Ok(value) = reqwest::header::HeaderValue::from_str(&client_ip) {
+            shared.insert(header_name(BATCH_CLIENT_HEADER), value);
+        }
+        let cx = tracing::Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut opentelemetry_http::HeaderInjector(&mut shared))
+        });
+
+        let responses: Vec<BatchItemResult> = stream::iter(payload.requests.into_iter().enumerate())
+            .map(|(position, item)| self.execute(position, item, &shared))
+            .buffered(self.inner.concurrency)
+            .collect()
+            .await;
+
+        let status = if responses.iter().all(|r| r.status < 400) { StatusCode::OK } else { StatusCode::MULTI_STATUS };
+        Ok(HttpResponse::build(status).json(BatchResults { responses }))
+    }
+
+    async fn execute(&self, position: usize, item: BatchItem, shared: &reqwest::header::HeaderMap) -> BatchItemResult {
+        let id = item.id.clone().unwrap_or_else(|| position.to_string());
+        let Ok(method) = reqwest::Method::from_bytes(item.method.to_ascii_uppercase().as_bytes()) else {
+            return BatchItemResult::error(id, StatusCode::BAD_REQUEST, "invalid method");
+        };
+        if !item.path.starts_with('/') || item.path.starts_with("//") {
+            return BatchItemResult::error(id, StatusCode::BAD_REQUEST, "path must start with '/'");
+        }
+        if item.path.split('?').next() == Some(self.inner.path.as_str()) {
+            return BatchItemResult::error(id, StatusCode::BAD_REQUEST, "batches cannot be nested");
+        }
+
+        let mut headers = shared.clone();
+        for (name, value) in &item.headers {
+            if name.eq_ignore_ascii_case(BATCH_HEADER) || name.eq_ignore_ascii_case(BATCH_CLIENT_HEADER) {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()),
+                reqwest::header::HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+
+        let mut request = self
+            .inner
+            .client
+            .request(method, format!("{}{}", self.inner.local_url, item.path))
+            .timeout(self.inner.item_timeout)
+            .headers(headers);
+        if let Some(body) = &item.body {
+            request = request.json(body);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("⚠️ Batch item '{}' ({} {}) failed: {}", id, item.method, item.path, e);
+                let status = if e.is_timeout() { StatusCode::GATEWAY_TIMEOUT } else { StatusCode::SERVICE_UNAVAILABLE };
+                return BatchItemResult::error(id, status, "request could not be completed");
+            }
+        };
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| matches!(name.as_str(), "content-type" | "location" | "etag" | "retry-after"))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let bytes = response.bytes().await.unwrap_or_default();
+        let body = match serde_json::from_slice(&bytes) {
+            Ok(body) => body,
+            Err(_) if bytes.is_empty() => serde_json::Value::Null,
+            Err(_) => serde_json::Value::String(String::from_utf8_lossy(&bytes).into_owned()),
+        };
+        BatchItemResult { id, status, headers, body }
+    }
+}
+
+fn header_name(name: &str) -> reqwest::header::HeaderName {
+    reqwest::header::HeaderName::from_bytes(name.as_bytes()).expect("valid header name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+
+    #[actix_web::test]
+    async fn test_invalid_items_fail_individually() {
+        let batch = BatchEndpoint::new().max_items(3);
+        let app = actix_test::init_service(App::new().configure(|cfg| batch.configure(cfg))).await;
+
+        let payload = serde_json::json!({ "requests": [
+            { "id": "nested", "method": "POST", "path": "/batch" },
+            { "method": "GET", "path": "api/v1/products" },
+        ] });
+        let req = actix_test::TestRequest::post().uri("/batch").set_json(&payload).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+
+        let results: BatchResults = actix_test::read_body_json(res).await;
+        assert_eq!(results.responses[0].id, "nested");
+        assert_eq!(results.responses[1].id, "1");
+        assert!(results.responses.iter().all(|r| r.status == 400));
+
+        let too_many = serde_json::json!({ "requests": vec![serde_json::json!({ "method": "GET", "path": "/" }); 4] });
+        let req = actix_test::TestRequest::post().uri("/batch").set_json(&too_many).to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_batch_client_requires_process_secret() {
+        let req = actix_test::TestRequest::default()
+            .insert_header((BATCH_HEADER, batch_secret()))
+            .insert_header((BATCH_CLIENT_HEADER, "203.0.113.7"))
+            .to_http_request();
+        assert_eq!(batch_client(req.headers()).as_deref(), Some("203.0.113.7"));
+
+        let forged = actix_test::TestRequest::default()
+            .insert_header((BATCH_HEADER, "guess"))
+            .insert_header((BATCH_CLIENT_HEADER, "203.0.113.7"))
+            .to_http_request();
+        assert_eq!(batch_client(forged.headers()), None);
+    }
+}
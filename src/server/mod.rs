@@ -1,28 +1,54 @@
 use actix_web::{web, App, HttpServer, middleware};
 use std::sync::Arc;
-use log::info;
+use log::{info, warn};
 
 use crate::middleware::security_headers::SecurityHeadersMiddleware;
 use crate::middleware::request_size::RequestSizeLimitMiddleware;
 use crate::middleware::rate_limit::RateLimitMiddleware;
+use crate::middleware::maintenance::MaintenanceMiddleware;
+use crate::middleware::bot_score::BotScoreMiddleware;
 use crate::rate_limit::create_limiter;
+use crate::db::migrate::{migrations_enabled, run_migrations};
+use crate::env::EnvRegistry;
+use crate::health::{DependencyGate, HealthRegistry};
+use crate::messaging::NatsClient;
+
+pub mod batch;
+pub mod spa;
+
+pub use batch::BatchEndpoint;
+pub use spa::{Spa, SpaAssets};
 
 /// Builder for standardized Actix Web servers in the Lanai ecosystem.
 ///
 /// This builder enforces:
 /// - Standard Middleware (Tracing, Logging, Compression, CORS, CSRF, Security Headers)
-/// - Rate Limiting (Redis-backed if available)
+/// - Rate Limiting (Redis-backed if available), optionally informed by bot scoring
 /// - Request Size Limiting
-/// - Consistent Shutdown/Timeout settings
+/// - Consistent Shutdown/Timeout settings, draining NATS once the server stops
+/// - Optional environment validation, refusing to start on missing or malformed variables
+/// - Optional dependency gate, waiting for Postgres/Redis/NATS/JWKS before binding
+/// - Optional startup migrations (gated by `DB_RUN_MIGRATIONS`)
+/// - Optional `/health/live` and `/health/ready` probes backed by a `HealthRegistry`
+/// - Optional `POST /batch` endpoint (see `batch`)
+/// - Optional single-page app and static assets (see `spa`)
 pub struct ServerBuilder {
     name: String,
     host: String,
     port: u16,
     workers: usize,
     max_request_size: usize,
+    route_size_limits: Vec<(String, usize)>,
     rate_limit_requests: u32,
     rate_limit_window_seconds: u64,
     enable_cors: bool,
+    env: Option<EnvRegistry>,
+    dependencies: Option<DependencyGate>,
+    migrations: Option<(sqlx::PgPool, sqlx::migrate::Migrator)>,
+    health: Option<HealthRegistry>,
+    batch: Option<BatchEndpoint>,
+    bot_scoring: Option<BotScoreMiddleware>,
+    spa: Option<Spa>,
 }
 
 impl ServerBuilder {
@@ -33,9 +59,17 @@ impl ServerBuilder {
             port: 8080,
             workers: 4,
             max_request_size: 2 * 1024 * 1024, // 2MB default
+            route_size_limits: Vec::new(),
             rate_limit_requests: 1000,
             rate_limit_window_seconds: 60,
             enable_cors: true,
+            env: None,
+            dependencies: None,
+            migrations: None,
+            health: None,
+            batch: None,
+            bot_scoring: None,
+            spa: None,
         }
     }
 
@@ -58,6 +92,12 @@ impl ServerBuilder {
         self.max_request_size = size;
         self
     }
+
+    /// Raise (or lower) the body size limit for paths under `prefix`, e.g. bulk imports.
+    pub fn max_request_size_for(mut self, prefix: &str, size: usize) -> Self {
+        self.route_size_limits.push((prefix.to_string(), size));
+        self
+    }
     
     pub fn rate_limit(mut self, requests: u32, window: u64) -> Self {
         self.rate_limit_requests = requests;
@@ -70,6 +110,51 @@ impl ServerBuilder {
         self
     }
 
+    /// Validate the variables of `registry` (e.g. `EnvRegistry::builtin()`) and log a redacted
+    /// summary before anything else. Startup fails if one is missing or malformed.
+    pub fn env(mut self, registry: EnvRegistry) -> Self {
+        self.env = Some(registry);
+        self
+    }
+
+    /// Wait for the dependencies of `gate` (e.g. `DependencyGate::from_env()`) before
+    /// migrating and binding. Startup fails if they stay unreachable.
+    pub fn wait_for(mut self, gate: DependencyGate) -> Self {
+        self.dependencies = Some(gate);
+        self
+    }
+
+    /// Apply `migrator` (usually `db::migrate!()`) to `pool` before binding, when
+    /// `DB_RUN_MIGRATIONS=true`. A failed migration aborts startup.
+    pub fn migrations(mut self, pool: sqlx::PgPool, migrator: sqlx::migrate::Migrator) -> Self {
+        self.migrations = Some((pool, migrator));
+        self
+    }
+
+    /// Serve `/health`, `/health/live` and `/health/ready` from `registry`.
+    pub fn health(mut self, registry: HealthRegistry) -> Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Serve `batch` (by default `POST /batch`), sending its items to this server over loopback.
+    pub fn batch(mut self, batch: BatchEndpoint) -> Self {
+        self.batch = Some(batch);
+        self
+    }
+
+    /// Score requests with `scoring` before rate limiting, penalizing likely bots.
+    pub fn bot_scoring(mut self, scoring: BotScoreMiddleware) -> Self {
+        self.bot_scoring = Some(scoring);
+        self
+    }
+
+    /// Serve `spa` (a built front end) after the routes added by `configure`, which take precedence.
+    pub fn serve_spa(mut self, spa: Spa) -> Self {
+        self.spa = Some(spa);
+        self
+    }
+
     /// Start the server and return the `Server` instance (Future) without awaiting it.
     /// Useful for running the server concurrently with other tasks (e.g., gRPC server).
     pub async fn start<F>(self, configure: F) -> std::io::Result<actix_web::dev::Server>
@@ -80,14 +165,36 @@ impl ServerBuilder {
         crate::observability::init_tracing(&self.name);
         
         info!("🚀 Starting {} on {}:{}", self.name, self.host, self.port);
+
+        if let Some(registry) = &self.env {
+            registry.validate().map_err(std::io::Error::other)?;
+        }
+
+        if let Some(gate) = &self.dependencies {
+            gate.wait().await.map_err(std::io::Error::other)?;
+        }
+
+        if let Some((pool, migrator)) = &self.migrations {
+            if migrations_enabled() {
+                run_migrations(pool, migrator).await.map_err(std::io::Error::other)?;
+            } else {
+                info!("⏭️ Skipping database migrations ({} is not set)", crate::db::migrate::DB_RUN_MIGRATIONS_ENV);
+            }
+        }
         
         let limiter = create_limiter().await;
         
         // Capture configuration to move into closure
         let max_size = self.max_request_size;
+        let route_limits = self.route_size_limits.clone();
         let rl_reqs = self.rate_limit_requests;
         let rl_window = self.rate_limit_window_seconds;
         let enable_cors = self.enable_cors;
+        let health = self.health.clone();
+        let loopback = if self.host == "0.0.0.0" { "127.0.0.1" } else { self.host.as_str() };
+        let bot_scoring = self.bot_scoring;
+        let spa = self.spa;
+        let batch = self.batch.map(|batch| batch.local_url(&format!("http://{}:{}", loopback, self.port)));
 
         Ok(HttpServer::new(move || {
             let app = App::new();
@@ -95,6 +202,7 @@ impl ServerBuilder {
             // 1. Core Middleware
             let app = app
                 .wrap(middleware::Compress::default())
+                .wrap(crate::db::QueryContextMiddleware)
                 .wrap(crate::middleware::tenant_context::TenantMiddleware);
 
             // 2. CORS (Optional but recommended)
@@ -120,15 +228,41 @@ impl ServerBuilder {
                     max_requests: rl_reqs,
                     window_seconds: rl_window,
                 })
-                .wrap(RequestSizeLimitMiddleware {
-                    max_size,
-                });
+                .wrap(middleware::Condition::new(bot_scoring.is_some(), bot_scoring.clone().unwrap_or_default()))
+                .wrap(route_limits.iter().fold(RequestSizeLimitMiddleware::new(max_size), |limits, (prefix, size)| {
+                    limits.route_limit(prefix, *size)
+                }))
+                .wrap(MaintenanceMiddleware::new());
 
             let app = app.wrap(tracing_actix_web::TracingLogger::default());
             let app = app.wrap(middleware::Logger::default());
 
-            // 6. User Configuration (Routes, AppData)
-            app.configure(configure.clone())
+            // 6. Health probes
+            let health = health.clone();
+            let app = app.configure(move |cfg| {
+                if let Some(registry) = &health {
+                    registry.configure(cfg);
+                }
+            });
+
+            // 7. Batch endpoint
+            let batch = batch.clone();
+            let app = app.configure(move |cfg| {
+                if let Some(batch) = &batch {
+                    batch.configure(cfg);
+                }
+            });
+
+            // 8. User Configuration (Routes, AppData)
+            let app = app.configure(configure.clone());
+
+            // 9. Single-page app, matched after every other route
+            let spa = spa.clone();
+            app.configure(move |cfg| {
+                if let Some(spa) = &spa {
+                    spa.configure(cfg);
+                }
+            })
         })
         .bind((self.host.as_str(), self.port))?
         .workers(self.workers)
@@ -138,11 +272,18 @@ impl ServerBuilder {
         .run())
     }
 
-    /// Run the server and await it until shutdown.
+    /// Run the server and await it until shutdown, then drain NATS (see
+    /// `messaging::shutdown`) so events in flight are not lost.
     pub async fn run<F>(self, configure: F) -> std::io::Result<()>
     where
         F: Fn(&mut web::ServiceConfig) + Send + Clone + 'static,
     {
-        self.start(configure).await?.await
+        let result = self.start(configure).await?.await;
+        if NatsClient::is_initialized() {
+            if let Err(e) = NatsClient::shutdown().await {
+                warn!("⚠️ NATS did not drain cleanly: {}", e);
+            }
+        }
+        result
     }
 }
@@ -0,0 +1,300 @@
+//! Static asset and single-page app serving
+//!
+//! Serves a built front end (a small admin UI bundled with a service) from a directory or
+//! from files embedded in the binary with `include_bytes!`:
+//!
+//! ```ignore
+//! let ui = Spa::new(SpaAssets::dir("ui/dist")).mount("/admin");
+//! // or, with nothing to deploy next to the binary
+//! let ui = Spa::new(SpaAssets::embedded(&[
+//!     ("index.html", include_bytes!("../ui/dist/index.html")),
+//!     ("assets/index-4f3a9c1e.js", include_bytes!("../ui/dist/assets/index-4f3a9c1e.js")),
+//!     ("assets/index-4f3a9c1e.js.br", include_bytes!("../ui/dist/assets/index-4f3a9c1e.js.br")),
+//! ]));
+//!
+//! ServerBuilder::new("inventory").serve_spa(ui).run(configure).await?;
+//! ```
+//!
+//! - Paths without a file extension that match no asset (client-side routes such as
+//!   `/admin/users/42`) get `index.html`; missing files with an extension are a 404
+//! - `index.html` is `no-cache`, so deploys show up immediately; assets under `assets/` or
+//!   `static/` or with a hex hash in their name (`main.3f9a1c2b.js`) are cached for a year
+//!   as immutable; anything else for an hour. Every response has a content ETag
+//! - A pre-compressed `{file}.br` or `{file}.gz` is served when the client accepts it
+//! - HTML gets its own Content-Security-Policy (see `Spa::csp`), which the server-wide
+//!   `SecurityHeadersMiddleware` leaves in place
+//!
+//! The SPA is registered after the service's own routes, so API routes take precedence.
+
+use actix_web::{
+    http::{
+        header::{self, HeaderValue},
+        Method, StatusCode,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Content-Security-Policy of HTML responses unless configured otherwise: same-origin
+/// scripts, inline styles (common in component libraries), data: images.
+pub const DEFAULT_SPA_CSP: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; font-src 'self' data:; connect-src 'self'; frame-ancestors 'none'; base-uri 'self'";
+
+const INDEX: &str = "index.html";
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const SHORT_LIVED: &str = "public, max-age=3600";
+
+/// Where the built files come from.
+#[derive(Clone)]
+pub enum SpaAssets {
+    /// Read from disk on each request, so a rebuilt UI is picked up without a restart.
+    Dir(PathBuf),
+    /// Files compiled into the binary, by path relative to the build output.
+    Embedded(Arc<HashMap<String, &'static [u8]>>),
+}
+
+impl SpaAssets {
+    pub fn dir(path: impl Into<PathBuf>) -> Self {
+        Self::Dir(path.into())
+    }
+
+    pub fn embedded(files: &[(&str, &'static [u8])]) -> Self {
+        let files = files.iter().map(|(path, bytes)| (path.trim_start_matches('/').to_string(), *bytes));
+        Self::Embedded(Arc::new(files.collect()))
+    }
+
+    async fn read(&self, path: &str) -> Option<Bytes> {
+        match self {
+            Self::Dir(root) => {
+                let file = root.join(path);
+                // Directories and unreadable files are simply not assets
+                tokio::fs::read(&file).await.ok().map(Bytes::from)
+            }
+            Self::Embedded(files) => files.get(path).map(|bytes| Bytes::from_static(bytes)),
+        }
+    }
+}
+
+/// A single-page app served by `ServerBuilder::serve_spa`.
+#[derive(Clone)]
+pub struct Spa {
+    assets: SpaAssets,
+    mount: String,
+    csp: String,
+}
+
+impl Spa {
+    /// Serve `assets` at the root with `DEFAULT_SPA_CSP`.
+    pub fn new(assets: SpaAssets) -> Self {
+        Self { assets, mount: String::new(), csp: DEFAULT_SPA_CSP.to_string() }
+    }
+
+    /// Serve under `prefix` (e.g. `/admin`) instead of the root.
+    pub fn mount(mut self, prefix: &str) -> Self {
+        self.mount = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Content-Security-Policy of HTML responses, e.g. to allow an analytics origin in
+    /// `connect-src`.
+    pub fn csp(mut self, policy: &str) -> Self {
+        self.csp = policy.to_string();
+        self
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let spa = self.clone();
+        cfg.service(web::scope(&self.mount).default_service(web::to(move |req: HttpRequest| {
+            let spa = spa.clone();
+            async move { spa.serve(&req).await }
+        })));
+    }
+
+    async fn serve(&self, req: &HttpRequest) -> HttpResponse {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return HttpResponse::MethodNotAllowed().insert_header((header::ALLOW, "GET, HEAD")).finish();
+        }
+        let path = req.path().strip_prefix(self.mount.as_str()).unwrap_or_default().trim_start_matches('/');
+        if path.split('/').any(|segment| segment == ".." || segment == "." || segment.starts_with('.')) {
+            return HttpResponse::NotFound().finish();
+        }
+
+        let mut requested = path.to_string();
+        if requested.is_empty() || requested.ends_with('/') {
+            requested.push_str(INDEX);
+        }
+        let file = match self.assets.read(&requested).await {
+            Some(_) => requested,
+            // Client-side routes have no extension; a missing script or image is a real 404
+            None if !last_segment(&requested).contains('.') => INDEX.to_string(),
+            None => return HttpResponse::NotFound().finish(),
+        };
+        self.respond(req, &file).await
+    }
+
+    async fn respond(&self, req: &HttpRequest, file: &str) -> HttpResponse {
+        let accepted = req.headers().get(header::ACCEPT_ENCODING).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        let accepts =
+            |encoding: &str| accepted.split(',').any(|e| e.split(';').next().unwrap_or_default().trim() == encoding);
+
+        let mut body = None;
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            if accepts(encoding) {
+                body = self.assets.read(&format!("{}.{}", file, extension)).await.map(|b| (b, Some(encoding)));
+                if body.is_some() {
+                    break;
+                }
+            }
+        }
+        if body.is_none() {
+            body = self.assets.read(file).await.map(|bytes| (bytes, None));
+        }
+        let Some((bytes, encoding)) = body else {
+            return HttpResponse::NotFound().finish();
+        };
+
+        let digest = Sha256::digest(&bytes);
+        let suffix = encoding.map(|e| format!("-{}", e)).unwrap_or_default();
+        let etag = format!("\"{}{}\"", hex::encode(&digest[..8]), suffix);
+        let cache_control = if file == INDEX || file.ends_with(".html") {
+            "no-cache"
+        } else if is_fingerprinted(file) {
+            IMMUTABLE
+        } else {
+            SHORT_LIVED
+        };
+
+        let not_modified = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+        let mut response = if not_modified {
+            HttpResponse::build(StatusCode::NOT_MODIFIED)
+        } else {
+            HttpResponse::Ok()
+        };
+        response
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::VARY, "Accept-Encoding"));
+        if content_type(file).starts_with("text/html") {
+            if let Ok(csp) = HeaderValue::from_str(&self.csp) {
+                response.insert_header((header::CONTENT_SECURITY_POLICY, csp));
+            }
+        }
+        if not_modified {
+            return response.finish();
+        }
+        if let Some(encoding) = encoding {
+            response.insert_header((header::CONTENT_ENCODING, encoding));
+        }
+        response.content_type(content_type(file)).body(bytes)
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Whether `path` is a build output whose name changes with its content.
+fn is_fingerprinted(path: &str) -> bool {
+    if path.starts_with("assets/") || path.starts_with("static/") {
+        return true;
+    }
+    last_segment(path)
+        .split(['.', '-', '_'])
+        .any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn content_type(path: &str) -> &'static str {
+    let extension = last_segment(path).rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "txt" => "text/plain; charset=utf-8",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::security_headers::SecurityHeadersMiddleware;
+    use actix_web::{test, App};
+
+    fn app_assets() -> SpaAssets {
+        SpaAssets::embedded(&[
+            ("index.html", b"<!doctype html><div id=app></div>"),
+            ("assets/index-4f3a9c1e.js", b"console.log('app')"),
+            ("assets/index-4f3a9c1e.js.br", b"brotli bytes"),
+            ("robots.txt", b"User-agent: *"),
+        ])
+    }
+
+    #[actix_web::test]
+    async fn test_spa_fallback_caching_and_precompressed_assets() {
+        let spa = Spa::new(app_assets()).mount("/admin");
+        let app = test::init_service(
+            App::new()
+                .wrap(SecurityHeadersMiddleware {
+                    content_security_policy: Some("default-src 'none'".to_string()),
+                    hsts_preload: false,
+                    hsts_max_age_seconds: 0,
+                    hsts_include_subdomains: false,
+                    referrer_policy: "no-referrer".to_string(),
+                    permissions_policy: None,
+                })
+                .route("/api/ping", web::get().to(|| async { "pong" }))
+                .configure(|cfg| spa.configure(cfg)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/admin/users/42").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        assert_eq!(res.headers().get(header::CONTENT_SECURITY_POLICY).unwrap(), DEFAULT_SPA_CSP);
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(test::read_body(res).await, "<!doctype html><div id=app></div>");
+
+        let req = test::TestRequest::get().uri("/admin/").insert_header((header::IF_NONE_MATCH, etag)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/assets/index-4f3a9c1e.js")
+            .insert_header((header::ACCEPT_ENCODING, "gzip, br;q=1.0"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), IMMUTABLE);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "text/javascript; charset=utf-8");
+        assert_eq!(res.headers().get(header::CONTENT_SECURITY_POLICY).unwrap(), "default-src 'none'");
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/admin/robots.txt").to_request()).await;
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), SHORT_LIVED);
+        for missing in ["/admin/assets/missing.js", "/admin/../secrets.env"] {
+            let res = test::call_service(&app, test::TestRequest::get().uri(missing).to_request()).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", missing);
+        }
+        let res = test::call_service(&app, test::TestRequest::get().uri("/api/ping").to_request()).await;
+        assert_eq!(test::read_body(res).await, "pong");
+    }
+}
@@ -0,0 +1,217 @@
+//! Loading, CSRF checking and saving of sessions around each request
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    cookie::{time, Cookie, SameSite},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use log::{debug, error, warn};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Session, SessionState, SessionStore, Status, CSRF_HEADER};
+use crate::error::LanaiError;
+use crate::middleware::tenant_context::TenantContext;
+
+struct Config {
+    cookie_name: String,
+    ttl: Duration,
+    secure: bool,
+    csrf: bool,
+}
+
+/// Provides the `Session` of each request. Reads the `TenantContext`, so register it
+/// before `TenantMiddleware` (actix runs the last registered middleware first).
+pub struct SessionMiddleware {
+    store: Arc<dyn SessionStore>,
+    config: Rc<Config>,
+}
+
+impl SessionMiddleware {
+    /// Cookie `lanai_session`, 8 hour sessions, `Secure`, CSRF checks on.
+    pub fn new(store: Arc<dyn SessionStore>) -> Self {
+        let config = Config { cookie_name: "lanai_session".to_string(), ttl: Duration::from_secs(8 * 3600), secure: true, csrf: true };
+        Self { store, config: Rc::new(config) }
+    }
+
+    fn config_mut(&mut self) -> &mut Config {
+        Rc::get_mut(&mut self.config).expect("SessionMiddleware is configured before it is shared")
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.config_mut().cookie_name = name.to_string();
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.config_mut().ttl = ttl;
+        self
+    }
+
+    /// Only for local development over plain HTTP.
+    pub fn insecure(mut self) -> Self {
+        self.config_mut().secure = false;
+        self
+    }
+
+    /// Skip the automatic `X-CSRF-Token` check, e.g. when forms are checked with
+    /// `Session::verify_csrf` instead.
+    pub fn without_csrf(mut self) -> Self {
+        self.config_mut().csrf = false;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SessionMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionMiddlewareService {
+            service: Rc::new(service),
+            store: Arc::clone(&self.store),
+            config: Rc::clone(&self.config),
+        }))
+    }
+}
+
+pub struct SessionMiddlewareService<S> {
+    service: Rc<S>,
+    store: Arc<dyn SessionStore>,
+    config: Rc<Config>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let store = Arc::clone(&self.store);
+        let config = Rc::clone(&self.config);
+
+        Box::pin(async move {
+            let cookie = req.cookie(&config.cookie_name).map(|cookie| cookie.value().to_string());
+            let tenant = req.extensions().get::<TenantContext>().map(|tenant| tenant.org_id);
+
+            let loaded = match &cookie {
+                Some(cookie) => store.load(cookie).await.unwrap_or_else(|e| {
+                    debug!("🍪 Ignoring session cookie: {}", e);
+                    None
+                }),
+                None => None,
+            };
+            let loaded = loaded.filter(|state| match (state.org_id, tenant) {
+                (Some(session_org), Some(org_id)) if session_org != org_id => {
+                    warn!("⚠️ Session of org {} presented for org {}, ignoring it", session_org, org_id);
+                    false
+                }
+                _ => true,
+            });
+
+            let existing = loaded.is_some();
+            let session = Session::new(loaded.unwrap_or_else(|| SessionState::new(tenant)), existing);
+
+            if config.csrf && existing && !req.method().is_safe() {
+                let token = req.headers().get(CSRF_HEADER).and_then(|value| value.to_str().ok());
+                if !token.is_some_and(|token| session.verify_csrf(token)) {
+                    warn!("⚠️ Rejected {} {}: invalid or missing CSRF token", req.method(), req.path());
+                    let error = LanaiError::Forbidden("Invalid or missing CSRF token".to_string());
+                    return Ok(req.into_response(error.error_response()));
+                }
+            }
+
+            req.extensions_mut().insert(session.clone());
+            let mut res = service.call(req).await?.map_into_boxed_body();
+
+            match session.status() {
+                Status::Unchanged => {}
+                Status::Purged => {
+                    if let Some(cookie) = &cookie {
+                        if let Err(e) = store.destroy(cookie).await {
+                            error!("❌ Failed to destroy session: {}", e);
+                        }
+                    }
+                    let removal = Cookie::build(config.cookie_name.clone(), "").path("/").finish();
+                    res.response_mut().add_removal_cookie(&removal)?;
+                }
+                status => {
+                    if let (Status::Renewed, Some(cookie)) = (status, &cookie) {
+                        if let Err(e) = store.destroy(cookie).await {
+                            error!("❌ Failed to destroy renewed session: {}", e);
+                        }
+                    }
+                    match store.save(&session.state(), config.ttl).await {
+                        Ok(value) => {
+                            let cookie = Cookie::build(config.cookie_name.clone(), value)
+                                .path("/")
+                                .http_only(true)
+                                .secure(config.secure)
+                                .same_site(SameSite::Lax)
+                                .max_age(time::Duration::seconds(config.ttl.as_secs() as i64))
+                                .finish();
+                            res.response_mut().add_cookie(&cookie)?;
+                        }
+                        Err(e) => error!("❌ Failed to save session: {}", e),
+                    }
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::CookieSessionStore;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_session_round_trip_and_csrf() {
+        let store = Arc::new(CookieSessionStore::new(&[3u8; 32]).unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(store).insecure())
+                .route("/login", web::post().to(|session: Session| async move {
+                    session.renew();
+                    session.insert("user_id", &"user-1").unwrap();
+                    HttpResponse::Ok().body(session.csrf_token())
+                }))
+                .route("/me", web::post().to(|session: Session| async move {
+                    HttpResponse::Ok().body(session.get::<String>("user_id").unwrap_or_default())
+                })),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::post().uri("/login").to_request()).await;
+        let cookie = res.response().cookies().next().unwrap().into_owned();
+        let csrf = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+        let forged = test::TestRequest::post().uri("/me").cookie(cookie.clone()).to_request();
+        assert_eq!(test::call_service(&app, forged).await.status(), 403);
+
+        let req = test::TestRequest::post().uri("/me").cookie(cookie).insert_header((CSRF_HEADER, csrf)).to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "user-1");
+    }
+}
@@ -0,0 +1,232 @@
+//! Cookie sessions for server-rendered admin pages
+//!
+//! APIs authenticate with JWTs; the few server-rendered admin surfaces need a browser
+//! session instead. `SessionMiddleware` loads the session named by a cookie, exposes it as
+//! the `Session` extractor and writes it back when it changed. Two stores:
+//! - `CookieSessionStore`: the whole session, AES-256-GCM encrypted, inside the cookie
+//!   (no server state; keep sessions small)
+//! - `RedisSessionStore`: the cookie holds an id, the session lives under
+//!   `lanai:session:{org_id}:{id}`
+//!
+//! Sessions are bound to the organization they were created for; a session presented to
+//! another tenant's host is ignored. Every session carries a CSRF token: unsafe requests
+//! (`POST`, `PUT`, `PATCH`, `DELETE`) on an existing session must echo it in `X-CSRF-Token`
+//! or are rejected with 403.
+//!
+//! ```ignore
+//! let sessions = SessionMiddleware::new(Arc::new(CookieSessionStore::from_env()?));
+//! App::new().wrap(sessions).wrap(TenantMiddleware)
+//!
+//! async fn login(session: Session, form: web::Form<Login>) -> LanaiResult<HttpResponse> {
+//!     let user = authenticate(&form).await?;
+//!     session.renew();
+//!     session.insert("user_id", &user.id);
+//!     ...
+//! }
+//!
+//! // in templates: <meta name="csrf-token" content="{{ session.csrf_token() }}">
+//! ```
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::{ready, Ready};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::error::LanaiError;
+
+pub mod middleware;
+pub mod store;
+
+pub use middleware::SessionMiddleware;
+pub use store::{CookieSessionStore, RedisSessionStore};
+
+/// Base64 AES-256 key for `CookieSessionStore`
+pub const SESSION_KEY_ENV: &str = "LANAI_SESSION_KEY";
+/// Header carrying the session's CSRF token (shared with cookie-based JWT auth)
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Session error types
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("Session key is not configured: {0}")]
+    NotConfigured(String),
+
+    #[error("Invalid session key: {0}")]
+    InvalidKey(String),
+
+    /// Tampered, expired-key or foreign cookie; deliberately not more specific.
+    #[error("Invalid session cookie")]
+    InvalidCookie,
+
+    #[error("Session is too large for a cookie ({0} bytes)")]
+    TooLarge(usize),
+
+    #[error("Failed to serialize session: {0}")]
+    Serialization(String),
+
+    #[error("Session store error: {0}")]
+    Store(String),
+}
+
+/// What a store persists for a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub id: String,
+    pub org_id: Option<Uuid>,
+    pub csrf_token: String,
+    #[serde(default)]
+    pub data: BTreeMap<String, serde_json::Value>,
+    /// Unix seconds.
+    pub expires_at: i64,
+}
+
+impl SessionState {
+    pub fn new(org_id: Option<Uuid>) -> Self {
+        Self { id: random_token(), org_id, csrf_token: random_token(), data: BTreeMap::new(), expires_at: 0 }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= chrono::Utc::now().timestamp()
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE64_URL.encode(bytes)
+}
+
+/// Where sessions are kept. The cookie value is whatever `save` returns.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// `Ok(None)` for unknown or expired sessions.
+    async fn load(&self, cookie: &str) -> Result<Option<SessionState>, SessionError>;
+
+    async fn save(&self, state: &SessionState, ttl: Duration) -> Result<String, SessionError>;
+
+    async fn destroy(&self, cookie: &str) -> Result<(), SessionError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Unchanged,
+    Changed,
+    /// New id (and CSRF token); the previous cookie must be destroyed.
+    Renewed,
+    Purged,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: SessionState,
+    status: Status,
+    /// Loaded from a cookie, as opposed to started by this request.
+    existing: bool,
+}
+
+/// The request's session. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Session {
+    fn new(state: SessionState, existing: bool) -> Self {
+        Self { inner: Arc::new(Mutex::new(Inner { state, status: Status::Unchanged, existing })) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn changed(inner: &mut Inner) {
+        if inner.status == Status::Unchanged {
+            inner.status = Status::Changed;
+        }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.lock().state.data.get(key).and_then(|value| T::deserialize(value).ok())
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: &T) -> Result<(), SessionError> {
+        let value = serde_json::to_value(value).map_err(|e| SessionError::Serialization(e.to_string()))?;
+        let mut inner = self.lock();
+        inner.state.data.insert(key.to_string(), value);
+        Self::changed(&mut inner);
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.lock();
+        if inner.state.data.remove(key).is_some() {
+            Self::changed(&mut inner);
+        }
+    }
+
+    /// New id and CSRF token, keeping the data. Call it on login and privilege changes so
+    /// a session id planted before authentication is worthless.
+    pub fn renew(&self) {
+        let mut inner = self.lock();
+        inner.state.id = random_token();
+        inner.state.csrf_token = random_token();
+        inner.status = Status::Renewed;
+    }
+
+    /// Log out: drop the data and expire the cookie.
+    pub fn purge(&self) {
+        let mut inner = self.lock();
+        inner.state.data.clear();
+        inner.status = Status::Purged;
+    }
+
+    /// Handing out the token of a new session saves it, so the token stays valid.
+    pub fn csrf_token(&self) -> String {
+        let mut inner = self.lock();
+        if !inner.existing {
+            Self::changed(&mut inner);
+        }
+        inner.state.csrf_token.clone()
+    }
+
+    /// Constant-time comparison with the session's CSRF token, e.g. for a form field.
+    pub fn verify_csrf(&self, token: &str) -> bool {
+        let expected = self.lock().state.csrf_token.clone();
+        expected.len() == token.len() && expected.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+    pub fn org_id(&self) -> Option<Uuid> {
+        self.lock().state.org_id
+    }
+
+    fn status(&self) -> Status {
+        self.lock().status
+    }
+
+    fn state(&self) -> SessionState {
+        self.lock().state.clone()
+    }
+}
+
+/// Needs `SessionMiddleware`; 500 if it is not installed.
+impl FromRequest for Session {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Session>()
+                .cloned()
+                .ok_or_else(|| LanaiError::internal("Session extractor used without SessionMiddleware")),
+        )
+    }
+}
@@ -0,0 +1,156 @@
+//! Encrypted-cookie and Redis session stores
+
+use aes_gcm::aead::KeyInit;
+use aes_gcm::Aes256Gcm;
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use base64::Engine;
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{SessionError, SessionState, SessionStore, SESSION_KEY_ENV};
+use crate::crypto;
+
+const COOKIE_AAD: &[u8] = b"lanai-session:v1";
+/// Browsers drop cookies over 4096 bytes including name and attributes.
+const MAX_COOKIE_LEN: usize = 3800;
+
+/// The session itself, encrypted, is the cookie value.
+pub struct CookieSessionStore {
+    cipher: Aes256Gcm,
+}
+
+impl CookieSessionStore {
+    /// `key` must be 32 bytes.
+    pub fn new(key: &[u8]) -> Result<Self, SessionError> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|_| SessionError::InvalidKey(format!("session key must be 32 bytes, got {}", key.len())))?;
+        Ok(Self { cipher })
+    }
+
+    /// Base64 key from `LANAI_SESSION_KEY`.
+    pub fn from_env() -> Result<Self, SessionError> {
+        let encoded = std::env::var(SESSION_KEY_ENV).map_err(|_| SessionError::NotConfigured(SESSION_KEY_ENV.to_string()))?;
+        let key = BASE64.decode(encoded.trim()).map_err(|e| SessionError::InvalidKey(e.to_string()))?;
+        Self::new(&key)
+    }
+}
+
+#[async_trait]
+impl SessionStore for CookieSessionStore {
+    async fn load(&self, cookie: &str) -> Result<Option<SessionState>, SessionError> {
+        let sealed = BASE64_URL.decode(cookie).map_err(|_| SessionError::InvalidCookie)?;
+        let plain = crypto::open(&self.cipher, &sealed, COOKIE_AAD).map_err(|_| SessionError::InvalidCookie)?;
+        let state: SessionState = serde_json::from_slice(&plain).map_err(|_| SessionError::InvalidCookie)?;
+        Ok((!state.is_expired()).then_some(state))
+    }
+
+    async fn save(&self, state: &SessionState, ttl: Duration) -> Result<String, SessionError> {
+        let state = SessionState { expires_at: chrono::Utc::now().timestamp() + ttl.as_secs() as i64, ..state.clone() };
+        let plain = serde_json::to_vec(&state).map_err(|e| SessionError::Serialization(e.to_string()))?;
+        let sealed = crypto::seal(&self.cipher, &plain, COOKIE_AAD).map_err(|e| SessionError::Serialization(e.to_string()))?;
+        let cookie = BASE64_URL.encode(sealed);
+        if cookie.len() > MAX_COOKIE_LEN {
+            return Err(SessionError::TooLarge(cookie.len()));
+        }
+        Ok(cookie)
+    }
+
+    /// Nothing to do: expiring the cookie is all there is.
+    async fn destroy(&self, _cookie: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+}
+
+/// Sessions as JSON under `lanai:session:{org_id}:{id}` (`global` without a tenant); the
+/// cookie holds `{org_id}.{id}`.
+pub struct RedisSessionStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    fn scope(org_id: Option<Uuid>) -> String {
+        org_id.map_or_else(|| "global".to_string(), |org_id| org_id.to_string())
+    }
+
+    /// Redis key for a cookie value, rejecting anything that is not `{scope}.{id}`.
+    fn redis_key(cookie: &str) -> Result<(String, &str), SessionError> {
+        let (scope, id) = cookie.split_once('.').ok_or(SessionError::InvalidCookie)?;
+        let valid_scope = scope == "global" || Uuid::parse_str(scope).is_ok();
+        let valid_id = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid_scope || !valid_id {
+            return Err(SessionError::InvalidCookie);
+        }
+        Ok((format!("lanai:session:{}:{}", scope, id), id))
+    }
+}
+
+fn store_error(e: redis::RedisError) -> SessionError {
+    SessionError::Store(e.to_string())
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn load(&self, cookie: &str) -> Result<Option<SessionState>, SessionError> {
+        let (key, id) = Self::redis_key(cookie)?;
+        let stored: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut self.conn.clone()).await.map_err(store_error)?;
+        let Some(stored) = stored else {
+            return Ok(None);
+        };
+        let state: SessionState = serde_json::from_str(&stored).map_err(|e| SessionError::Serialization(e.to_string()))?;
+        Ok((state.id == id).then_some(state))
+    }
+
+    async fn save(&self, state: &SessionState, ttl: Duration) -> Result<String, SessionError> {
+        let cookie = format!("{}.{}", Self::scope(state.org_id), state.id);
+        let (key, _) = Self::redis_key(&cookie)?;
+        let state = SessionState { expires_at: chrono::Utc::now().timestamp() + ttl.as_secs() as i64, ..state.clone() };
+        let value = serde_json::to_string(&state).map_err(|e| SessionError::Serialization(e.to_string()))?;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        Ok(cookie)
+    }
+
+    async fn destroy(&self, cookie: &str) -> Result<(), SessionError> {
+        let (key, _) = Self::redis_key(cookie)?;
+        redis::cmd("DEL").arg(&key).query_async::<_, ()>(&mut self.conn.clone()).await.map_err(store_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cookie_store_round_trip_and_tampering() {
+        let store = CookieSessionStore::new(&[7u8; 32]).unwrap();
+        let mut state = SessionState::new(Some(Uuid::new_v4()));
+        state.data.insert("user_id".to_string(), serde_json::json!("user-1"));
+
+        let cookie = store.save(&state, Duration::from_secs(60)).await.unwrap();
+        let loaded = store.load(&cookie).await.unwrap().unwrap();
+        assert_eq!(loaded.data, state.data);
+        assert_eq!(loaded.csrf_token, state.csrf_token);
+
+        let mut tampered = cookie.into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert!(store.load(std::str::from_utf8(&tampered).unwrap()).await.is_err());
+
+        let other = CookieSessionStore::new(&[8u8; 32]).unwrap();
+        let cookie = store.save(&state, Duration::from_secs(60)).await.unwrap();
+        assert!(other.load(&cookie).await.is_err());
+        assert!(RedisSessionStore::redis_key("global.abc:def").is_err());
+    }
+}
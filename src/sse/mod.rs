@@ -0,0 +1,304 @@
+//! Server-Sent Events
+//!
+//! A `Broadcaster` keeps one channel per tenant. Handlers publish typed events to an
+//! organization and every client streaming that organization's events receives them.
+//! Streams send a comment heartbeat to keep proxies from closing idle connections, and each
+//! channel keeps a short history so a reconnecting `EventSource` (which sends the
+//! `Last-Event-ID` header) receives the events it missed.
+//!
+//! With several replicas, `with_nats` plus `start_fanout` relays published events through
+//! NATS so clients connected to any replica see them.
+//!
+//! ```ignore
+//! let events = Broadcaster::new().with_nats("lanai.sse.inventory");
+//! let _fanout = events.start_fanout().await?;
+//!
+//! events.publish(tenant.org_id, "stock.updated", &StockItem { .. }).await?;
+//!
+//! ServerBuilder::new("lanai-inventory").run(move |cfg| events.configure(cfg)).await
+//! ```
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::messaging::NatsClient;
+use crate::middleware::tenant_context::TenantContext;
+
+/// SSE error types
+#[derive(Debug, Error)]
+pub enum SseError {
+    #[error("Failed to serialize event: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("NATS fan-out is not configured")]
+    NotConfigured,
+
+    #[error("NATS fan-out error: {0}")]
+    Bus(String),
+}
+
+/// One event as sent on the wire.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SseEvent {
+    pub id: String,
+    pub event: String,
+    pub data: String,
+}
+
+impl SseEvent {
+    /// `text/event-stream` framing; multi-line data becomes several `data:` fields.
+    pub fn encode(&self) -> String {
+        let mut frame = format!("id: {}\nevent: {}\n", self.id, self.event);
+        for line in self.data.lines() {
+            frame.push_str("data: ");
+            frame.push_str(line);
+            frame.push('\n');
+        }
+        frame.push('\n');
+        frame
+    }
+}
+
+/// Message relayed between replicas.
+#[derive(Debug, Serialize, Deserialize)]
+struct FanoutMessage {
+    origin: Uuid,
+    org_id: Uuid,
+    event: SseEvent,
+}
+
+struct TenantChannel {
+    sender: broadcast::Sender<Arc<SseEvent>>,
+    history: VecDeque<Arc<SseEvent>>,
+}
+
+struct Inner {
+    channels: Mutex<HashMap<Uuid, TenantChannel>>,
+    heartbeat: Duration,
+    history: usize,
+    capacity: usize,
+    nats_subject: Option<String>,
+    instance_id: Uuid,
+}
+
+#[derive(Clone)]
+pub struct Broadcaster {
+    inner: Arc<Inner>,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Broadcaster {
+    /// 15s heartbeats, 100 events of history and a 256 event buffer per tenant.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                channels: Mutex::new(HashMap::new()),
+                heartbeat: Duration::from_secs(15),
+                history: 100,
+                capacity: 256,
+                nats_subject: None,
+                instance_id: Uuid::new_v4(),
+            }),
+        }
+    }
+
+    /// Must be called before the broadcaster is cloned.
+    pub fn with_heartbeat(mut self, interval: Duration) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.heartbeat = interval;
+        }
+        self
+    }
+
+    /// Events kept per tenant for `Last-Event-ID` replay. Must be called before cloning.
+    pub fn with_history(mut self, events: usize) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.history = events;
+        }
+        self
+    }
+
+    /// Relay events to other replicas on `subject` (requires `NatsClient::init`).
+    /// Must be called before cloning.
+    pub fn with_nats(mut self, subject: &str) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.nats_subject = Some(subject.to_string());
+        }
+        self
+    }
+
+    /// Send `data` as an `event` to every client of `org_id` (on all replicas if fan-out is on).
+    pub async fn publish<T: Serialize>(&self, org_id: Uuid, event: &str, data: &T) -> Result<(), SseError> {
+        let event = SseEvent {
+            id: Uuid::new_v4().to_string(),
+            event: event.to_string(),
+            data: serde_json::to_string(data)?,
+        };
+        self.deliver(org_id, event.clone());
+
+        if let Some(subject) = &self.inner.nats_subject {
+            if !NatsClient::is_initialized() {
+                return Err(SseError::NotConfigured);
+            }
+            let message = FanoutMessage { origin: self.inner.instance_id, org_id, event };
+            NatsClient::publish_with_headers(subject, async_nats::HeaderMap::new(), serde_json::to_vec(&message)?.into())
+                .await
+                .map_err(|e| SseError::Bus(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Deliver events published by other replicas. Abort the handle to stop.
+    pub async fn start_fanout(&self) -> Result<JoinHandle<()>, SseError> {
+        let subject = self.inner.nats_subject.clone().ok_or(SseError::NotConfigured)?;
+        if !NatsClient::is_initialized() {
+            return Err(SseError::NotConfigured);
+        }
+        let mut subscriber = NatsClient::subscribe(&subject).await.map_err(|e| SseError::Bus(e.to_string()))?;
+
+        info!("📡 Relaying server-sent events over '{}'", subject);
+        let broadcaster = self.clone();
+        Ok(tokio::spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                match serde_json::from_slice::<FanoutMessage>(&message.payload) {
+                    Ok(message) if message.origin == broadcaster.inner.instance_id => {}
+                    Ok(message) => broadcaster.deliver(message.org_id, message.event),
+                    Err(e) => warn!("⚠️ Ignoring malformed SSE fan-out message: {}", e),
+                }
+            }
+            warn!("⚠️ SSE fan-out subscription on '{}' ended", subject);
+        }))
+    }
+
+    /// Number of clients currently streaming `org_id`'s events on this replica.
+    pub fn connections(&self, org_id: Uuid) -> usize {
+        self.lock().get(&org_id).map_or(0, |channel| channel.sender.receiver_count())
+    }
+
+    /// Streaming `text/event-stream` response for `org_id`, replaying events after
+    /// `last_event_id` if they are still in the history.
+    pub fn stream(&self, org_id: Uuid, last_event_id: Option<&str>) -> HttpResponse {
+        let (missed, receiver) = self.subscribe(org_id, last_event_id);
+        let heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + self.inner.heartbeat, self.inner.heartbeat);
+
+        // Ask clients to reconnect after 3s, then replay, then follow the live channel.
+        let state = (Some("retry: 3000\n\n".to_string()), missed, receiver, heartbeat);
+        let body = futures_util::stream::unfold(state, |(first, mut missed, mut receiver, mut heartbeat)| async move {
+            if let Some(frame) = first {
+                return Some((Ok(web::Bytes::from(frame)), (None, missed, receiver, heartbeat)));
+            }
+            if let Some(event) = missed.pop_front() {
+                return Some((Ok(web::Bytes::from(event.encode())), (None, missed, receiver, heartbeat)));
+            }
+
+            let frame = tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(event) => event.encode(),
+                    // Close the stream; the client reconnects with Last-Event-ID and is replayed.
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("🐢 SSE client lagged by {} events, closing stream", skipped);
+                        return None;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+                _ = heartbeat.tick() => ": keep-alive\n\n".to_string(),
+            };
+            Some((Ok::<_, actix_web::Error>(web::Bytes::from(frame)), (None, missed, receiver, heartbeat)))
+        });
+
+        HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(("Cache-Control", "no-cache"))
+            .insert_header(("X-Accel-Buffering", "no"))
+            .streaming(body)
+    }
+
+    /// Mount `GET /events`, streaming the caller's organization (mount behind `AuthGuard`).
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone()))
+            .route("/events", web::get().to(stream_events));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, TenantChannel>> {
+        self.inner.channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn deliver(&self, org_id: Uuid, event: SseEvent) {
+        let event = Arc::new(event);
+        let mut channels = self.lock();
+        let channel = self.channel(&mut channels, org_id);
+        channel.history.push_back(event.clone());
+        while channel.history.len() > self.inner.history {
+            channel.history.pop_front();
+        }
+        // No receivers is fine: the event is still kept for replay.
+        let _ = channel.sender.send(event);
+    }
+
+    /// Missed events and a live receiver, taken under one lock so none are lost or repeated.
+    fn subscribe(&self, org_id: Uuid, last_event_id: Option<&str>) -> (VecDeque<Arc<SseEvent>>, broadcast::Receiver<Arc<SseEvent>>) {
+        let mut channels = self.lock();
+        let channel = self.channel(&mut channels, org_id);
+        let missed = last_event_id
+            .and_then(|id| channel.history.iter().position(|event| event.id == id))
+            .map(|position| channel.history.iter().skip(position + 1).cloned().collect())
+            .unwrap_or_default();
+        (missed, channel.sender.subscribe())
+    }
+
+    fn channel<'a>(&self, channels: &'a mut HashMap<Uuid, TenantChannel>, org_id: Uuid) -> &'a mut TenantChannel {
+        channels.entry(org_id).or_insert_with(|| TenantChannel {
+            sender: broadcast::channel(self.inner.capacity).0,
+            history: VecDeque::new(),
+        })
+    }
+}
+
+async fn stream_events(broadcaster: web::Data<Broadcaster>, tenant: TenantContext, req: HttpRequest) -> HttpResponse {
+    let last_event_id = req.headers().get("Last-Event-ID").and_then(|v| v.to_str().ok());
+    broadcaster.stream(tenant.org_id, last_event_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_encoding() {
+        let event = SseEvent { id: "1".to_string(), event: "stock.updated".to_string(), data: "a\nb".to_string() };
+        assert_eq!(event.encode(), "id: 1\nevent: stock.updated\ndata: a\ndata: b\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_replays_events_after_last_event_id() {
+        let broadcaster = Broadcaster::new();
+        let org_id = Uuid::new_v4();
+        for n in 0..3 {
+            broadcaster.publish(org_id, "tick", &n).await.unwrap();
+        }
+
+        let first_id = broadcaster.lock()[&org_id].history[0].id.clone();
+        let (missed, mut receiver) = broadcaster.subscribe(org_id, Some(&first_id));
+        let data: Vec<_> = missed.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, vec!["1", "2"]);
+
+        broadcaster.publish(org_id, "tick", &3).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, "3");
+        assert_eq!(broadcaster.connections(org_id), 1);
+        assert_eq!(broadcaster.connections(Uuid::new_v4()), 0);
+    }
+}
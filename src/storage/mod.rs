@@ -0,0 +1,468 @@
+//! Object Storage (S3 / MinIO)
+//!
+//! Objects live in one bucket per service, namespaced like cache keys:
+//! `global/{key}` or `org/{org_id}/{key}` for tenant-scoped stores.
+//!
+//! - `put` / `get` / `delete` for small objects held in memory
+//! - `put_stream` for uploads of unknown size (multipart above 8 MiB), capped by `max_upload_size`
+//! - `list` to page through the objects under a prefix
+//! - `presigned_url` / `presigned_upload_url` to let browsers transfer directly
+//!
+//! Every operation runs inside a `storage.*` tracing span.
+//!
+//! ```ignore
+//! let images = ObjectStore::from_env()?.with_max_upload_size(10 * 1024 * 1024);
+//! let store = images.for_tenant(&tenant);
+//! store.put_stream(&format!("products/{}.png", id), payload, "image/png").await?;
+//! let url = store.presigned_url(&format!("products/{}.png", id), Duration::from_secs(900)).await?;
+//! ```
+
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use log::{info, warn};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::Instrument;
+
+use crate::middleware::tenant_context::TenantContext;
+
+pub const S3_ENDPOINT_ENV: &str = "S3_ENDPOINT";
+pub const S3_REGION_ENV: &str = "S3_REGION";
+pub const S3_BUCKET_ENV: &str = "S3_BUCKET";
+pub const S3_ACCESS_KEY_ID_ENV: &str = "S3_ACCESS_KEY_ID";
+pub const S3_SECRET_ACCESS_KEY_ENV: &str = "S3_SECRET_ACCESS_KEY";
+
+/// Uploads larger than this are sent as multipart uploads of this part size.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Storage error types
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Object storage is not configured: {0} is not set")]
+    NotConfigured(&'static str),
+
+    #[error("Invalid object key '{0}'")]
+    InvalidKey(String),
+
+    #[error("Object not found: {0}")]
+    NotFound(String),
+
+    #[error("Upload exceeds the {0} byte limit")]
+    TooLarge(u64),
+
+    #[error("Upload stream failed: {0}")]
+    Upload(String),
+
+    #[error("Object storage error: {0}")]
+    Backend(String),
+}
+
+fn backend<E: std::error::Error>(error: E) -> StorageError {
+    StorageError::Backend(DisplayErrorContext(error).to_string())
+}
+
+/// An object returned by `list`; `key` is relative to the store's namespace.
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct ObjectStore {
+    client: Client,
+    bucket: Arc<str>,
+    prefix: Arc<str>,
+    max_upload_size: u64,
+}
+
+impl ObjectStore {
+    /// Uploads are limited to 100 MiB unless configured otherwise.
+    pub fn new(client: Client, bucket: &str) -> Self {
+        Self {
+            client,
+            bucket: Arc::from(bucket),
+            prefix: Arc::from("global"),
+            max_upload_size: 100 * 1024 * 1024,
+        }
+    }
+
+    /// Configure from `S3_BUCKET`, `S3_ACCESS_KEY_ID`, `S3_SECRET_ACCESS_KEY`, `S3_REGION`
+    /// (default `us-east-1`) and `S3_ENDPOINT` (set for MinIO; enables path-style addressing).
+    pub fn from_env() -> Result<Self, StorageError> {
+        let var = |name: &'static str| std::env::var(name).map_err(|_| StorageError::NotConfigured(name));
+        let bucket = var(S3_BUCKET_ENV)?;
+        let credentials = Credentials::new(
+            var(S3_ACCESS_KEY_ID_ENV)?,
+            var(S3_SECRET_ACCESS_KEY_ENV)?,
+            None,
+            None,
+            "lanai-env",
+        );
+
+        let mut config = aws_sdk_s3::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(std::env::var(S3_REGION_ENV).unwrap_or_else(|_| "us-east-1".to_string())))
+            .credentials_provider(credentials);
+        if let Ok(endpoint) = std::env::var(S3_ENDPOINT_ENV) {
+            config = config.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        info!("🪣 Object storage configured for bucket '{}'", bucket);
+        Ok(Self::new(Client::from_conf(config.build()), &bucket))
+    }
+
+    pub fn with_max_upload_size(mut self, bytes: u64) -> Self {
+        self.max_upload_size = bytes;
+        self
+    }
+
+    /// Store scoped to the tenant's organization.
+    pub fn for_tenant(&self, tenant: &TenantContext) -> Self {
+        Self { prefix: Arc::from(format!("org/{}", tenant.org_id)), ..self.clone() }
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Full object key for `key`, rejecting keys that could escape the namespace.
+    pub fn key(&self, key: &str) -> Result<String, StorageError> {
+        let invalid = key.is_empty()
+            || key.starts_with('/')
+            || key.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..");
+        if invalid {
+            return Err(StorageError::InvalidKey(key.to_string()));
+        }
+        Ok(format!("{}/{}", self.prefix, key))
+    }
+
+    pub async fn put(&self, key: &str, body: impl Into<Bytes>, content_type: &str) -> Result<(), StorageError> {
+        let full_key = self.key(key)?;
+        let body = body.into();
+        if body.len() as u64 > self.max_upload_size {
+            return Err(StorageError::TooLarge(self.max_upload_size));
+        }
+
+        let span = tracing::info_span!("storage.put", bucket = %self.bucket, key = %full_key, size = body.len());
+        self.client
+            .put_object()
+            .bucket(self.bucket.as_ref())
+            .key(&full_key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .instrument(span)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    /// Upload a stream (e.g. an actix `web::Payload`), failing once it exceeds `max_upload_size`.
+    /// Returns the number of bytes stored.
+    pub async fn put_stream<S, E>(&self, key: &str, stream: S, content_type: &str) -> Result<u64, StorageError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: Display,
+    {
+        let full_key = self.key(key)?;
+        let span = tracing::info_span!("storage.put_stream", bucket = %self.bucket, key = %full_key);
+        self.upload(full_key, stream, content_type).instrument(span).await
+    }
+
+    async fn upload<S, E>(&self, full_key: String, stream: S, content_type: &str) -> Result<u64, StorageError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: Display,
+    {
+        futures_util::pin_mut!(stream);
+        let mut buffer = BytesMut::new();
+        let mut total: u64 = 0;
+        let mut multipart: Option<Multipart> = None;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return self.fail(multipart, StorageError::Upload(e.to_string())).await,
+            };
+            total += chunk.len() as u64;
+            if total > self.max_upload_size {
+                return self.fail(multipart, StorageError::TooLarge(self.max_upload_size)).await;
+            }
+            buffer.extend_from_slice(&chunk);
+
+            while buffer.len() >= PART_SIZE {
+                let part = buffer.split_to(PART_SIZE).freeze();
+                if multipart.is_none() {
+                    multipart = Some(self.start_multipart(&full_key, content_type).await?);
+                }
+                let upload = multipart.as_mut().expect("multipart upload started");
+                if let Err(e) = self.upload_part(upload, part).await {
+                    return self.fail(multipart, e).await;
+                }
+            }
+        }
+
+        match multipart {
+            None => {
+                self.client
+                    .put_object()
+                    .bucket(self.bucket.as_ref())
+                    .key(&full_key)
+                    .content_type(content_type)
+                    .body(ByteStream::from(buffer.freeze()))
+                    .send()
+                    .await
+                    .map_err(backend)?;
+            }
+            Some(mut upload) => {
+                if !buffer.is_empty() {
+                    if let Err(e) = self.upload_part(&mut upload, buffer.freeze()).await {
+                        return self.fail(Some(upload), e).await;
+                    }
+                }
+                let completed = CompletedMultipartUpload::builder().set_parts(Some(upload.parts.clone())).build();
+                let result = self
+                    .client
+                    .complete_multipart_upload()
+                    .bucket(self.bucket.as_ref())
+                    .key(&upload.key)
+                    .upload_id(&upload.id)
+                    .multipart_upload(completed)
+                    .send()
+                    .await;
+                if let Err(e) = result {
+                    return self.fail(Some(upload), backend(e)).await;
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    async fn start_multipart(&self, full_key: &str, content_type: &str) -> Result<Multipart, StorageError> {
+        let created = self
+            .client
+            .create_multipart_upload()
+            .bucket(self.bucket.as_ref())
+            .key(full_key)
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(backend)?;
+        let id = created
+            .upload_id()
+            .ok_or_else(|| StorageError::Backend("multipart upload has no ID".to_string()))?;
+        Ok(Multipart { key: full_key.to_string(), id: id.to_string(), parts: Vec::new() })
+    }
+
+    async fn upload_part(&self, upload: &mut Multipart, body: Bytes) -> Result<(), StorageError> {
+        let part_number = upload.parts.len() as i32 + 1;
+        let uploaded = self
+            .client
+            .upload_part()
+            .bucket(self.bucket.as_ref())
+            .key(&upload.key)
+            .upload_id(&upload.id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(backend)?;
+        upload.parts.push(
+            CompletedPart::builder()
+                .set_e_tag(uploaded.e_tag().map(str::to_string))
+                .part_number(part_number)
+                .build(),
+        );
+        Ok(())
+    }
+
+    /// Abort an in-progress multipart upload (so its parts are not billed) and return `error`.
+    async fn fail<T>(&self, multipart: Option<Multipart>, error: StorageError) -> Result<T, StorageError> {
+        if let Some(upload) = multipart {
+            let aborted = self
+                .client
+                .abort_multipart_upload()
+                .bucket(self.bucket.as_ref())
+                .key(&upload.key)
+                .upload_id(&upload.id)
+                .send()
+                .await;
+            if let Err(e) = aborted {
+                warn!("⚠️ Failed to abort multipart upload of '{}': {}", upload.key, DisplayErrorContext(e));
+            }
+        }
+        Err(error)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Bytes, StorageError> {
+        let full_key = self.key(key)?;
+        let span = tracing::info_span!("storage.get", bucket = %self.bucket, key = %full_key);
+        async {
+            let object = self
+                .client
+                .get_object()
+                .bucket(self.bucket.as_ref())
+                .key(&full_key)
+                .send()
+                .await
+                .map_err(|e| {
+                    let error = e.into_service_error();
+                    if error.is_no_such_key() {
+                        StorageError::NotFound(full_key.clone())
+                    } else {
+                        backend(error)
+                    }
+                })?;
+            let body = object.body.collect().await.map_err(backend)?;
+            Ok(body.into_bytes())
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Delete `key`. Deleting a missing object succeeds.
+    pub async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let full_key = self.key(key)?;
+        let span = tracing::info_span!("storage.delete", bucket = %self.bucket, key = %full_key);
+        self.client
+            .delete_object()
+            .bucket(self.bucket.as_ref())
+            .key(&full_key)
+            .send()
+            .instrument(span)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    /// Up to `limit` (at most 1000) objects whose key starts with `prefix`, in key order and
+    /// after `start_after` if given. Pass the last key of a page to get the next one.
+    pub async fn list(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<ObjectInfo>, StorageError> {
+        let namespace = format!("{}/", self.prefix);
+        let span = tracing::info_span!("storage.list", bucket = %self.bucket, prefix = %prefix);
+        let output = self
+            .client
+            .list_objects_v2()
+            .bucket(self.bucket.as_ref())
+            .prefix(format!("{}{}", namespace, prefix))
+            .set_start_after(start_after.map(|key| format!("{}{}", namespace, key)))
+            .max_keys(limit.clamp(1, 1000) as i32)
+            .send()
+            .instrument(span)
+            .await
+            .map_err(backend)?;
+        Ok(output
+            .contents()
+            .iter()
+            .filter_map(|object| {
+                Some(ObjectInfo {
+                    key: object.key()?.strip_prefix(&namespace)?.to_string(),
+                    size: object.size().unwrap_or_default().max(0) as u64,
+                    last_modified: object
+                        .last_modified()
+                        .and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+                })
+            })
+            .collect())
+    }
+
+    /// Time-limited URL to download `key` without credentials.
+    pub async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let full_key = self.key(key)?;
+        let config = PresigningConfig::expires_in(expires_in).map_err(backend)?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(self.bucket.as_ref())
+            .key(&full_key)
+            .presigned(config)
+            .await
+            .map_err(backend)?;
+        Ok(request.uri().to_string())
+    }
+
+    /// Time-limited URL to `PUT` `key` directly. The upload size limit is not enforced here.
+    pub async fn presigned_upload_url(
+        &self,
+        key: &str,
+        content_type: &str,
+        expires_in: Duration,
+    ) -> Result<String, StorageError> {
+        let full_key = self.key(key)?;
+        let config = PresigningConfig::expires_in(expires_in).map_err(backend)?;
+        let request = self
+            .client
+            .put_object()
+            .bucket(self.bucket.as_ref())
+            .key(&full_key)
+            .content_type(content_type)
+            .presigned(config)
+            .await
+            .map_err(backend)?;
+        Ok(request.uri().to_string())
+    }
+}
+
+struct Multipart {
+    key: String,
+    id: String,
+    parts: Vec<CompletedPart>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn store() -> ObjectStore {
+        let config = aws_sdk_s3::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .endpoint_url("http://127.0.0.1:9")
+            .force_path_style(true)
+            .build();
+        ObjectStore::new(Client::from_conf(config), "lanai-test")
+    }
+
+    #[test]
+    fn test_keys_are_namespaced_and_validated() {
+        let store = store();
+        assert_eq!(store.key("reports/q1.csv").unwrap(), "global/reports/q1.csv");
+
+        let org_id = Uuid::new_v4();
+        let tenant = store.for_tenant(&TenantContext { org_id });
+        assert_eq!(tenant.key("logo.png").unwrap(), format!("org/{}/logo.png", org_id));
+
+        for bad in ["", "/etc/passwd", "../other-org/logo.png", "a//b", "a/./b"] {
+            assert!(matches!(store.key(bad), Err(StorageError::InvalidKey(_))), "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_upload_enforces_size_limit() {
+        let store = store().with_max_upload_size(10);
+        let chunks = futures_util::stream::iter(vec![
+            Ok::<_, std::io::Error>(Bytes::from_static(b"123456")),
+            Ok(Bytes::from_static(b"7890ab")),
+        ]);
+        let result = store.put_stream("big.bin", chunks, "application/octet-stream").await;
+        assert!(matches!(result, Err(StorageError::TooLarge(10))));
+    }
+}
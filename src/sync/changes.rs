@@ -0,0 +1,195 @@
+//! The change log terminals pull from
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{SyncError, VectorClock};
+
+/// Table used by `ChangeLog`; include it in a service migration or call `ensure_schema`.
+/// Add a row-level security policy on `org_id` like any tenant table.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS lanai_sync_changes (
+    seq BIGSERIAL PRIMARY KEY,
+    org_id UUID NOT NULL,
+    entity TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    op TEXT NOT NULL,
+    data JSONB,
+    clock JSONB NOT NULL DEFAULT '{}',
+    origin TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS lanai_sync_changes_org_seq ON lanai_sync_changes (org_id, seq)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Upsert,
+    Delete,
+}
+
+impl ChangeOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Upsert => "upsert",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A change to record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewChange {
+    pub entity: String,
+    pub entity_id: String,
+    pub op: ChangeOp,
+    /// The full record after an upsert; `None` for deletes.
+    pub data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub clock: VectorClock,
+    /// Node that made the change (terminal id or `server`), so terminals can skip their own.
+    pub origin: String,
+}
+
+/// A recorded change. `seq` is the cursor position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub seq: i64,
+    pub entity: String,
+    pub entity_id: String,
+    pub op: ChangeOp,
+    pub data: Option<serde_json::Value>,
+    pub clock: VectorClock,
+    pub origin: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ChangeRow {
+    seq: i64,
+    entity: String,
+    entity_id: String,
+    op: String,
+    data: Option<serde_json::Value>,
+    clock: Json<VectorClock>,
+    origin: String,
+    changed_at: DateTime<Utc>,
+}
+
+impl From<ChangeRow> for Change {
+    fn from(row: ChangeRow) -> Self {
+        Self {
+            seq: row.seq,
+            entity: row.entity,
+            entity_id: row.entity_id,
+            op: if row.op == "delete" { ChangeOp::Delete } else { ChangeOp::Upsert },
+            data: row.data,
+            clock: row.clock.0,
+            origin: row.origin,
+            changed_at: row.changed_at,
+        }
+    }
+}
+
+/// One page of changes after a cursor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangePage {
+    pub changes: Vec<Change>,
+    /// Pass as `since` for the next pull; unchanged when there was nothing new.
+    pub cursor: i64,
+    pub has_more: bool,
+}
+
+/// Writes and reads `lanai_sync_changes`. Write on the transaction of the change itself
+/// (e.g. `TenantTx::conn`), so a terminal never pulls a change that was rolled back.
+pub struct ChangeLog;
+
+impl ChangeLog {
+    pub async fn ensure_schema(pool: &PgPool) -> Result<(), SyncError> {
+        sqlx::raw_sql(SCHEMA).execute(pool).await?;
+        Ok(())
+    }
+
+    /// Returns the change's `seq`.
+    pub async fn record(conn: &mut PgConnection, org_id: Uuid, change: &NewChange) -> Result<i64, SyncError> {
+        let seq = sqlx::query_scalar(
+            "INSERT INTO lanai_sync_changes (org_id, entity, entity_id, op, data, clock, origin)
+             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING seq",
+        )
+        .bind(org_id)
+        .bind(&change.entity)
+        .bind(&change.entity_id)
+        .bind(change.op.as_str())
+        .bind(&change.data)
+        .bind(Json(&change.clock))
+        .bind(&change.origin)
+        .fetch_one(conn)
+        .await?;
+        Ok(seq)
+    }
+
+    /// Changes of `org_id` after `since`, oldest first, optionally only for `entities`.
+    pub async fn since(
+        conn: &mut PgConnection,
+        org_id: Uuid,
+        since: i64,
+        entities: &[String],
+        limit: u32,
+    ) -> Result<ChangePage, SyncError> {
+        let limit = limit.max(1);
+        let mut query = Self::since_query(org_id, since, entities, limit);
+        let rows: Vec<ChangeRow> = query.build_query_as().fetch_all(conn).await?;
+        let mut changes: Vec<Change> = rows.into_iter().map(Change::from).collect();
+
+        let has_more = changes.len() > limit as usize;
+        changes.truncate(limit as usize);
+        let cursor = changes.last().map_or(since, |change| change.seq);
+        Ok(ChangePage { changes, cursor, has_more })
+    }
+
+    /// Fetches one row more than `limit` to tell whether there are more.
+    fn since_query(org_id: Uuid, since: i64, entities: &[String], limit: u32) -> QueryBuilder<'static, Postgres> {
+        let mut query = QueryBuilder::new(
+            "SELECT seq, entity, entity_id, op, data, clock, origin, changed_at FROM lanai_sync_changes WHERE org_id = ",
+        );
+        query.push_bind(org_id).push(" AND seq > ").push_bind(since);
+        if !entities.is_empty() {
+            query.push(" AND entity = ANY(").push_bind(entities.to_vec()).push(")");
+        }
+        query.push(" ORDER BY seq LIMIT ").push_bind(i64::from(limit) + 1);
+        query
+    }
+
+    /// Delete changes older than `retention`. Terminals whose cursor falls before the
+    /// oldest remaining change must resync from scratch. Returns how many were deleted.
+    pub async fn prune(pool: &PgPool, retention: Duration) -> Result<u64, SyncError> {
+        let result = sqlx::query("DELETE FROM lanai_sync_changes WHERE changed_at < now() - make_interval(secs => $1)")
+            .bind(retention.as_secs_f64())
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_since_query() {
+        let query = ChangeLog::since_query(Uuid::nil(), 42, &["products".to_string()], 100);
+        assert_eq!(
+            query.sql(),
+            "SELECT seq, entity, entity_id, op, data, clock, origin, changed_at FROM lanai_sync_changes \
+             WHERE org_id = $1 AND seq > $2 AND entity = ANY($3) ORDER BY seq LIMIT $4"
+        );
+        let change: NewChange = serde_json::from_value(serde_json::json!({
+            "entity": "products", "entity_id": "p-1", "op": "delete", "data": null, "origin": "pos-01"
+        }))
+        .unwrap();
+        assert_eq!((change.op, change.clock), (ChangeOp::Delete, VectorClock::new()));
+    }
+}
@@ -0,0 +1,190 @@
+//! Vector clocks and merging of concurrent edits
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Causal relation between two clocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockOrdering {
+    Equal,
+    /// The left clock happened before the right one.
+    Before,
+    After,
+    /// Neither saw the other: both sides edited independently.
+    Concurrent,
+}
+
+/// Edit counter per node (terminal or server), serialized as `{"pos-01": 3, "server": 7}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(BTreeMap<String, u64>);
+
+impl VectorClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, node: &str) -> u64 {
+        self.0.get(node).copied().unwrap_or(0)
+    }
+
+    /// Record an edit made on `node`.
+    pub fn increment(&mut self, node: &str) {
+        *self.0.entry(node.to_string()).or_insert(0) += 1;
+    }
+
+    /// Pointwise maximum: the clock of a value that has seen both histories.
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (node, counter) in &other.0 {
+            let entry = self.0.entry(node.clone()).or_insert(0);
+            *entry = (*entry).max(*counter);
+        }
+    }
+
+    pub fn compare(&self, other: &VectorClock) -> ClockOrdering {
+        let nodes = self.0.keys().chain(other.0.keys());
+        let (mut less, mut greater) = (false, false);
+        for node in nodes {
+            match self.get(node).cmp(&other.get(node)) {
+                Ordering::Less => less = true,
+                Ordering::Greater => greater = true,
+                Ordering::Equal => {}
+            }
+        }
+        match (less, greater) {
+            (false, false) => ClockOrdering::Equal,
+            (true, false) => ClockOrdering::Before,
+            (false, true) => ClockOrdering::After,
+            (true, true) => ClockOrdering::Concurrent,
+        }
+    }
+}
+
+/// A record with the metadata needed to merge it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub value: T,
+    pub clock: VectorClock,
+    /// Wall-clock time of the last edit, for last-write-wins. Terminal clocks drift, so it
+    /// only decides between concurrent edits.
+    pub updated_at: DateTime<Utc>,
+    /// Node of the last edit; breaks `updated_at` ties deterministically.
+    pub node: String,
+}
+
+impl<T> Versioned<T> {
+    /// A first version created on `node`.
+    pub fn new(value: T, node: &str) -> Self {
+        let mut clock = VectorClock::new();
+        clock.increment(node);
+        Self { value, clock, updated_at: Utc::now(), node: node.to_string() }
+    }
+
+    /// Replace the value with an edit made on `node`.
+    pub fn update(&mut self, value: T, node: &str) {
+        self.value = value;
+        self.clock.increment(node);
+        self.updated_at = Utc::now();
+        self.node = node.to_string();
+    }
+
+    /// Last-write-wins order: later `updated_at`, then greater node id.
+    fn wins_over(&self, other: &Versioned<T>) -> bool {
+        (self.updated_at, &self.node) > (other.updated_at, &other.node)
+    }
+}
+
+/// Result of `merge`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Merged<T> {
+    /// The local version already includes the remote one.
+    KeptLocal(Versioned<T>),
+    /// The remote version supersedes the local one.
+    TookRemote(Versioned<T>),
+    /// Concurrent edits, resolved; the clock covers both histories.
+    Resolved(Versioned<T>),
+}
+
+impl<T> Merged<T> {
+    pub fn into_inner(self) -> Versioned<T> {
+        match self {
+            Self::KeptLocal(v) | Self::TookRemote(v) | Self::Resolved(v) => v,
+        }
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Self::Resolved(_))
+    }
+}
+
+/// Picks the value of two concurrent versions.
+pub type Resolver<T> = dyn Fn(&Versioned<T>, &Versioned<T>) -> T;
+
+/// Merge a remote version into the local one. Causally ordered versions need no
+/// resolution; concurrent ones go through `resolve` (pass `None` for last-write-wins).
+pub fn merge<T: Clone>(
+    local: Versioned<T>,
+    remote: Versioned<T>,
+    resolve: Option<&Resolver<T>>,
+) -> Merged<T> {
+    match local.clock.compare(&remote.clock) {
+        ClockOrdering::Equal | ClockOrdering::After => Merged::KeptLocal(local),
+        ClockOrdering::Before => Merged::TookRemote(remote),
+        ClockOrdering::Concurrent => {
+            let mut clock = local.clock.clone();
+            clock.merge(&remote.clock);
+            let winner = if remote.wins_over(&local) { &remote } else { &local };
+            let value = match resolve {
+                Some(resolve) => resolve(&local, &remote),
+                None => winner.value.clone(),
+            };
+            Merged::Resolved(Versioned { value, clock, updated_at: winner.updated_at, node: winner.node.clone() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_ordering() {
+        let mut a = VectorClock::new();
+        a.increment("pos-01");
+        let mut b = a.clone();
+        b.increment("server");
+        assert_eq!(a.compare(&b), ClockOrdering::Before);
+        assert_eq!(b.compare(&a), ClockOrdering::After);
+
+        a.increment("pos-01");
+        assert_eq!(a.compare(&b), ClockOrdering::Concurrent);
+        a.merge(&b);
+        assert_eq!((a.get("pos-01"), a.get("server")), (2, 1));
+        assert_eq!(serde_json::to_value(&a).unwrap(), serde_json::json!({"pos-01": 2, "server": 1}));
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = Versioned::new(10, "server");
+        let mut local = base.clone();
+        local.update(8, "server");
+        let mut remote = base.clone();
+        remote.update(7, "pos-01");
+        remote.updated_at = local.updated_at + chrono::Duration::seconds(5);
+
+        assert_eq!(merge(base.clone(), local.clone(), None), Merged::TookRemote(local.clone()));
+        assert_eq!(merge(local.clone(), base, None), Merged::KeptLocal(local.clone()));
+
+        let lww = merge(local.clone(), remote.clone(), None);
+        assert!(lww.is_conflict());
+        let lww = lww.into_inner();
+        assert_eq!((lww.value, lww.node.as_str()), (7, "pos-01"));
+        assert_eq!(lww.clock.compare(&local.clock), ClockOrdering::After);
+
+        // Stock decrements from both sides: apply both deltas to the base.
+        let summed = merge(local, remote, Some(&|l: &Versioned<i32>, r: &Versioned<i32>| l.value + r.value - 10));
+        assert_eq!(summed.into_inner().value, 5);
+    }
+}
@@ -0,0 +1,283 @@
+//! Delta-sync routes
+//!
+//! | Route | |
+//! |---|---|
+//! | `GET {path}?since=&entities=&limit=` | changes after the cursor (`ChangePage`) |
+//! | `POST {path}` | `{"device_id": "pos-01", "changes": [...]}`, applied in one transaction |
+//!
+//! Both run on a `TenantTx`, so `TenantDb` must be registered as app data.
+
+use actix_web::{web, HttpResponse};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{ChangeLog, ChangeOp, NewChange, SyncError, VectorClock, Versioned};
+use crate::db::TenantTx;
+use crate::error::LanaiError;
+
+/// A change made on a terminal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushedChange {
+    pub entity: String,
+    pub entity_id: String,
+    pub op: ChangeOp,
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+    pub clock: VectorClock,
+    /// Terminal wall-clock time of the edit.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PushedChange {
+    /// The pushed record as a `Versioned` for `merge` (`None` for deletes).
+    pub fn versioned(&self, device_id: &str) -> Option<Versioned<serde_json::Value>> {
+        let value = self.data.clone()?;
+        Some(Versioned { value, clock: self.clock.clone(), updated_at: self.updated_at, node: device_id.to_string() })
+    }
+}
+
+/// What a handler stored for a pushed change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Applied {
+    /// The record as stored (after merging); `None` if it was deleted.
+    pub data: Option<serde_json::Value>,
+    pub clock: VectorClock,
+    /// Concurrent edits were resolved; the terminal must take `data`.
+    pub merged: bool,
+}
+
+/// Applies pushed changes of one entity type.
+#[async_trait]
+pub trait SyncHandler: Send + Sync {
+    /// Merge `change` into the stored record on the tenant transaction. Return
+    /// `SyncError::Rejected` before writing anything to skip just this change; any other
+    /// error rolls back the whole push.
+    async fn apply(
+        &self,
+        conn: &mut PgConnection,
+        org_id: Uuid,
+        device_id: &str,
+        change: &PushedChange,
+    ) -> Result<Applied, SyncError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushStatus {
+    Applied,
+    Merged,
+    Rejected,
+}
+
+/// Outcome of one pushed change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushResult {
+    pub entity: String,
+    pub entity_id: String,
+    pub status: PushStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
+    /// The stored record, for merged changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PushRequest {
+    device_id: String,
+    changes: Vec<PushedChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct PushResponse {
+    results: Vec<PushResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullQuery {
+    #[serde(default)]
+    since: Option<String>,
+    /// Comma-separated entity names.
+    #[serde(default)]
+    entities: Option<String>,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+struct Inner {
+    path: String,
+    handlers: HashMap<String, Arc<dyn SyncHandler>>,
+    page_size: u32,
+    max_push: usize,
+}
+
+/// Builder for the pull and push routes of one service.
+#[derive(Clone)]
+pub struct DeltaSync {
+    inner: Arc<Inner>,
+}
+
+impl DeltaSync {
+    /// Pages of up to 500 changes, pushes of up to 1000.
+    pub fn new(path: &str) -> Self {
+        Self {
+            inner: Arc::new(Inner { path: path.to_string(), handlers: HashMap::new(), page_size: 500, max_push: 1000 }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("DeltaSync is configured before it is shared"));
+        self
+    }
+
+    /// Accept pushed changes of `entity`. Entities without a handler can still be pulled.
+    pub fn handler(self, entity: &str, handler: Arc<dyn SyncHandler>) -> Self {
+        self.update(|inner| {
+            inner.handlers.insert(entity.to_string(), handler);
+        })
+    }
+
+    /// Upper bound for `limit` on pulls.
+    pub fn page_size(self, page_size: u32) -> Self {
+        self.update(|inner| inner.page_size = page_size.max(1))
+    }
+
+    pub fn max_push(self, max_push: usize) -> Self {
+        self.update(|inner| inner.max_push = max_push.max(1))
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.service(
+            web::resource(self.inner.path.as_str())
+                .app_data(web::Data::new(self.clone()))
+                .route(web::get().to(pull))
+                .route(web::post().to(push)),
+        );
+    }
+}
+
+fn parse_cursor(since: Option<&str>) -> Result<i64, SyncError> {
+    match since {
+        None | Some("") => Ok(0),
+        Some(since) => {
+            since.parse().ok().filter(|cursor| *cursor >= 0).ok_or_else(|| SyncError::InvalidCursor(since.to_string()))
+        }
+    }
+}
+
+async fn pull(
+    sync: web::Data<DeltaSync>,
+    mut tx: TenantTx,
+    query: web::Query<PullQuery>,
+) -> Result<HttpResponse, LanaiError> {
+    let since = parse_cursor(query.since.as_deref())?;
+    let entities: Vec<String> = query
+        .entities
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entity| !entity.is_empty())
+        .map(str::to_string)
+        .collect();
+    let limit = query.limit.unwrap_or(sync.inner.page_size).clamp(1, sync.inner.page_size);
+
+    let org_id = tx.org_id();
+    let page = ChangeLog::since(tx.conn(), org_id, since, &entities, limit).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+async fn push(
+    sync: web::Data<DeltaSync>,
+    mut tx: TenantTx,
+    body: web::Json<PushRequest>,
+) -> Result<HttpResponse, LanaiError> {
+    let PushRequest { device_id, changes } = body.into_inner();
+    if device_id.is_empty() {
+        return Err(LanaiError::BadRequest("device_id is required".to_string()));
+    }
+    if changes.len() > sync.inner.max_push {
+        return Err(LanaiError::BadRequest(format!("At most {} changes per push", sync.inner.max_push)));
+    }
+    if let Some(change) = changes.iter().find(|change| !sync.inner.handlers.contains_key(&change.entity)) {
+        return Err(SyncError::UnknownEntity(change.entity.clone()).into());
+    }
+
+    let org_id = tx.org_id();
+    let mut results = Vec::with_capacity(changes.len());
+    let mut merged = 0;
+    for change in &changes {
+        let handler = &sync.inner.handlers[&change.entity];
+        let result = match handler.apply(tx.conn(), org_id, &device_id, change).await {
+            Ok(applied) => {
+                let record = NewChange {
+                    entity: change.entity.clone(),
+                    entity_id: change.entity_id.clone(),
+                    op: if applied.data.is_some() { ChangeOp::Upsert } else { ChangeOp::Delete },
+                    data: applied.data.clone(),
+                    clock: applied.clock,
+                    origin: device_id.clone(),
+                };
+                let seq = ChangeLog::record(tx.conn(), org_id, &record).await?;
+                merged += applied.merged as usize;
+                PushResult {
+                    entity: record.entity,
+                    entity_id: record.entity_id,
+                    status: if applied.merged { PushStatus::Merged } else { PushStatus::Applied },
+                    seq: Some(seq),
+                    data: if applied.merged { applied.data } else { None },
+                    error: None,
+                }
+            }
+            Err(SyncError::Rejected(reason)) => {
+                warn!("⚠️ Rejected {} {} from {}: {}", change.entity, change.entity_id, device_id, reason);
+                PushResult {
+                    entity: change.entity.clone(),
+                    entity_id: change.entity_id.clone(),
+                    status: PushStatus::Rejected,
+                    seq: None,
+                    data: None,
+                    error: Some(reason),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        results.push(result);
+    }
+    tx.commit().await?;
+
+    info!("🔄 Synced {} changes from {} for org {} ({} merged)", changes.len(), device_id, org_id, merged);
+    Ok(HttpResponse::Ok().json(PushResponse { results }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_and_push_payload() {
+        assert_eq!(parse_cursor(None).unwrap(), 0);
+        assert_eq!(parse_cursor(Some("42")).unwrap(), 42);
+        assert!(parse_cursor(Some("-1")).is_err());
+
+        let request: PushRequest = serde_json::from_value(serde_json::json!({
+            "device_id": "pos-01",
+            "changes": [{
+                "entity": "products", "entity_id": "p-1", "op": "upsert", "data": {"stock": 4},
+                "clock": {"pos-01": 3}, "updated_at": "2024-05-10T12:00:00Z"
+            }]
+        }))
+        .unwrap();
+        let versioned = request.changes[0].versioned(&request.device_id).unwrap();
+        assert_eq!((versioned.clock.get("pos-01"), versioned.node.as_str()), (3, "pos-01"));
+    }
+}
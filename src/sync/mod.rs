@@ -0,0 +1,65 @@
+//! Offline sync for POS terminals
+//!
+//! Terminals keep selling while offline and reconcile when they reconnect:
+//!
+//! - `clock`: vector clocks and `Versioned<T>` records, with `merge` deciding between a
+//!   local and a remote version (causal order first, last-write-wins for concurrent edits
+//!   unless a custom resolver is given)
+//! - `changes`: the `lanai_sync_changes` change log, written in the same transaction as the
+//!   change itself and read by cursor
+//! - `endpoint`: `DeltaSync`, mounting `GET {path}?since=` (pull) and `POST {path}` (push)
+//!
+//! ```ignore
+//! let sync = DeltaSync::new("/sync").handler("products", Arc::new(ProductSync)).page_size(500);
+//! ServerBuilder::new().run(move |cfg| sync.configure(cfg)).await
+//! ```
+
+use thiserror::Error;
+
+use crate::db::DbError;
+use crate::error::LanaiError;
+
+pub mod changes;
+pub mod clock;
+pub mod endpoint;
+
+pub use changes::{Change, ChangeLog, ChangeOp, ChangePage, NewChange};
+pub use clock::{merge, ClockOrdering, Merged, Resolver, VectorClock, Versioned};
+pub use endpoint::{Applied, DeltaSync, PushResult, PushStatus, PushedChange, SyncHandler};
+
+/// Sync error types
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("Invalid sync cursor '{0}'")]
+    InvalidCursor(String),
+
+    #[error("No sync handler for entity '{0}'")]
+    UnknownEntity(String),
+
+    /// The change cannot be applied (e.g. it violates a business rule).
+    #[error("Change rejected: {0}")]
+    Rejected(String),
+
+    #[error("Change log error: {0}")]
+    Database(#[from] DbError),
+
+    #[error("Failed to serialize change: {0}")]
+    Serialization(String),
+}
+
+impl From<sqlx::Error> for SyncError {
+    fn from(e: sqlx::Error) -> Self {
+        SyncError::Database(DbError::from(e))
+    }
+}
+
+impl From<SyncError> for LanaiError {
+    fn from(e: SyncError) -> Self {
+        match e {
+            SyncError::InvalidCursor(_) | SyncError::UnknownEntity(_) => LanaiError::BadRequest(e.to_string()),
+            SyncError::Rejected(_) => LanaiError::Conflict(e.to_string()),
+            SyncError::Database(e) => e.into(),
+            SyncError::Serialization(_) => LanaiError::Internal(e.to_string()),
+        }
+    }
+}
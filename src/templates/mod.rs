@@ -0,0 +1,451 @@
+//! Message templates (MiniJinja)
+//!
+//! A `TemplateEngine` renders the subject and bodies of transactional messages from
+//! templates that tenants can override. Templates are looked up by key, most specific
+//! first, in each registered `TemplateStore` and finally in the built-in defaults:
+//! 1. the tenant's template for the locale (`email/order_shipped.pt`)
+//! 2. the tenant's template (`email/order_shipped`)
+//! 3. the same two keys without a tenant (platform-wide)
+//!
+//! Resolved templates are cached for a few minutes, so a store is not queried per message.
+//! The notifications subsystem renders `{channel}/{name}` templates with it, and webhook
+//! payloads can be reshaped per tenant with `webhook/{event_type}` templates (see
+//! `render_json`).
+//!
+//! ```ignore
+//! let templates = TemplateEngine::new()
+//!     .store(PostgresTemplateStore::new(pool.clone()))
+//!     .default_template("email/order_shipped", MessageTemplate::new("Order {{ number }} shipped")
+//!         .with_subject("{{ t('order-shipped') }}")
+//!         .with_html("<p>{{ total | money('BRL') }}</p>"));
+//!
+//! let rendered = templates.render(Some(org_id), "email/order_shipped", Some(Locale::Pt), &vars).await?;
+//! ```
+//!
+//! Besides the MiniJinja builtins, templates get:
+//! - `t(id, **args)` — message `id` from the installed i18n `Catalog`, in the render locale
+//! - `value | money(currency)` — amount with two decimals, separators after the locale
+//! - `value | date(format)` — RFC 3339 timestamp formatted with `chrono` (default `%Y-%m-%d`)
+//!
+//! Undefined variables are errors, and HTML bodies are escaped automatically.
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use log::warn;
+use minijinja::value::Kwargs;
+use minijinja::{AutoEscape, Environment, State, UndefinedBehavior};
+use moka::future::Cache;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::i18n::{Catalog, Locale};
+use crate::storage::{ObjectStore, StorageError};
+
+/// Template error types
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Template '{0}' not found")]
+    NotFound(String),
+
+    #[error("Failed to render template '{name}': {reason}")]
+    Render { name: String, reason: String },
+
+    #[error("Template '{name}' did not render valid JSON: {reason}")]
+    InvalidJson { name: String, reason: String },
+
+    #[error("Template store error: {0}")]
+    Store(String),
+}
+
+/// Subject and bodies of a message, as template sources.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub html: Option<String>,
+}
+
+impl MessageTemplate {
+    pub fn new(body: &str) -> Self {
+        Self { subject: None, body: body.to_string(), html: None }
+    }
+
+    pub fn with_subject(mut self, subject: &str) -> Self {
+        self.subject = Some(subject.to_string());
+        self
+    }
+
+    pub fn with_html(mut self, html: &str) -> Self {
+        self.html = Some(html.to_string());
+        self
+    }
+}
+
+/// Rendered subject and bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub subject: Option<String>,
+    pub body: String,
+    pub html: Option<String>,
+}
+
+/// Where templates live. `org_id: None` is the platform-wide template.
+#[async_trait]
+pub trait TemplateStore: Send + Sync {
+    async fn get(&self, org_id: Option<Uuid>, key: &str) -> Result<Option<MessageTemplate>, TemplateError>;
+}
+
+/// In-memory `TemplateStore`, for tests and templates compiled into the binary.
+#[derive(Default)]
+pub struct InMemoryTemplateStore {
+    templates: RwLock<HashMap<(Option<Uuid>, String), MessageTemplate>>,
+}
+
+impl InMemoryTemplateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, org_id: Option<Uuid>, key: &str, template: MessageTemplate) {
+        self.templates.write().unwrap_or_else(|e| e.into_inner()).insert((org_id, key.to_string()), template);
+    }
+}
+
+#[async_trait]
+impl TemplateStore for InMemoryTemplateStore {
+    async fn get(&self, org_id: Option<Uuid>, key: &str) -> Result<Option<MessageTemplate>, TemplateError> {
+        let templates = self.templates.read().unwrap_or_else(|e| e.into_inner());
+        Ok(templates.get(&(org_id, key.to_string())).cloned())
+    }
+}
+
+/// Templates in a `message_templates` table:
+///
+/// ```sql
+/// CREATE TABLE message_templates (
+///     org_id UUID,
+///     key TEXT NOT NULL,
+///     subject TEXT,
+///     body TEXT NOT NULL,
+///     html TEXT,
+///     updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+///     UNIQUE NULLS NOT DISTINCT (org_id, key)
+/// );
+/// ```
+pub struct PostgresTemplateStore {
+    pool: PgPool,
+}
+
+impl PostgresTemplateStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TemplateStore for PostgresTemplateStore {
+    async fn get(&self, org_id: Option<Uuid>, key: &str) -> Result<Option<MessageTemplate>, TemplateError> {
+        let row: Option<(Option<String>, String, Option<String>)> = sqlx::query_as(
+            "SELECT subject, body, html FROM message_templates WHERE key = $1 AND org_id IS NOT DISTINCT FROM $2",
+        )
+        .bind(key)
+        .bind(org_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TemplateError::Store(e.to_string()))?;
+        Ok(row.map(|(subject, body, html)| MessageTemplate { subject, body, html }))
+    }
+}
+
+/// Templates as JSON objects at `templates/{org_id}/{key}.json`, or
+/// `templates/global/{key}.json` for platform-wide ones.
+pub struct ObjectStoreTemplates {
+    store: ObjectStore,
+}
+
+impl ObjectStoreTemplates {
+    pub fn new(store: ObjectStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl TemplateStore for ObjectStoreTemplates {
+    async fn get(&self, org_id: Option<Uuid>, key: &str) -> Result<Option<MessageTemplate>, TemplateError> {
+        let scope = org_id.map_or_else(|| "global".to_string(), |id| id.to_string());
+        match self.store.get(&format!("templates/{}/{}.json", scope, key)).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| TemplateError::Store(e.to_string())),
+            Err(StorageError::NotFound(_)) => Ok(None),
+            Err(e) => Err(TemplateError::Store(e.to_string())),
+        }
+    }
+}
+
+type CacheKey = (Option<Uuid>, String, Option<Locale>);
+
+struct Inner {
+    env: Environment<'static>,
+    stores: Vec<Arc<dyn TemplateStore>>,
+    defaults: HashMap<String, MessageTemplate>,
+    cache: Cache<CacheKey, Option<Arc<MessageTemplate>>>,
+}
+
+/// Resolves and renders message templates; clones share the cache.
+#[derive(Clone)]
+pub struct TemplateEngine {
+    inner: Arc<Inner>,
+}
+
+impl Default for TemplateEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateEngine {
+    /// Caches resolved templates for 5 minutes.
+    pub fn new() -> Self {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_auto_escape_callback(|name| match name.rsplit('.').next() {
+            Some("html") => AutoEscape::Html,
+            Some("json") => AutoEscape::Json,
+            _ => AutoEscape::None,
+        });
+        env.add_function("t", translate);
+        env.add_filter("money", money);
+        env.add_filter("date", date);
+
+        Self {
+            inner: Arc::new(Inner {
+                env,
+                stores: Vec::new(),
+                defaults: HashMap::new(),
+                cache: Cache::builder().max_capacity(10_000).time_to_live(Duration::from_secs(300)).build(),
+            }),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("TemplateEngine is configured before it is shared"));
+        self
+    }
+
+    /// Consult `store`, after the ones registered before it.
+    pub fn store(self, store: impl TemplateStore + 'static) -> Self {
+        self.update(|inner| inner.stores.push(Arc::new(store)))
+    }
+
+    /// Built-in template for `key`, used when no store has one.
+    pub fn default_template(self, key: &str, template: MessageTemplate) -> Self {
+        self.update(|inner| {
+            inner.defaults.insert(key.to_string(), template);
+        })
+    }
+
+    pub fn cache_ttl(self, ttl: Duration) -> Self {
+        self.update(|inner| inner.cache = Cache::builder().max_capacity(10_000).time_to_live(ttl).build())
+    }
+
+    /// Forget resolved templates, e.g. after a tenant edited one.
+    pub fn invalidate(&self) {
+        self.inner.cache.invalidate_all();
+    }
+
+    /// The most specific template for `key`, if any.
+    pub async fn resolve(
+        &self,
+        org_id: Option<Uuid>,
+        key: &str,
+        locale: Option<Locale>,
+    ) -> Result<Option<Arc<MessageTemplate>>, TemplateError> {
+        let cache_key = (org_id, key.to_string(), locale);
+        if let Some(cached) = self.inner.cache.get(&cache_key).await {
+            return Ok(cached);
+        }
+
+        let keys: Vec<String> =
+            locale.map(|locale| format!("{}.{}", key, locale)).into_iter().chain([key.to_string()]).collect();
+        let scopes = org_id.map(Some).into_iter().chain([None]);
+        let mut found = None;
+        'lookup: for scope in scopes {
+            for store in &self.inner.stores {
+                for key in &keys {
+                    if let Some(template) = store.get(scope, key).await? {
+                        found = Some(template);
+                        break 'lookup;
+                    }
+                }
+            }
+        }
+        let found = found
+            .or_else(|| keys.iter().find_map(|key| self.inner.defaults.get(key).cloned()))
+            .map(Arc::new);
+
+        self.inner.cache.insert(cache_key, found.clone()).await;
+        Ok(found)
+    }
+
+    /// Render `key` with `vars`; a missing template is an error.
+    pub async fn render(
+        &self,
+        org_id: Option<Uuid>,
+        key: &str,
+        locale: Option<Locale>,
+        vars: &Value,
+    ) -> Result<Rendered, TemplateError> {
+        let template =
+            self.resolve(org_id, key, locale).await?.ok_or_else(|| TemplateError::NotFound(key.to_string()))?;
+        let context = context(vars, locale);
+        let render = |suffix: &str, source: &str| {
+            let name = format!("{}.{}", key, suffix);
+            self.inner
+                .env
+                .render_named_str(&name, source, &context)
+                .map_err(|e| TemplateError::Render { name, reason: format!("{:#}", e) })
+        };
+
+        Ok(Rendered {
+            subject: template.subject.as_deref().map(|s| render("subject", s)).transpose()?,
+            body: render("txt", &template.body)?,
+            html: template.html.as_deref().map(|h| render("html", h)).transpose()?,
+        })
+    }
+
+    /// Render the body of `key` as JSON (values are JSON-escaped), or `None` when there is
+    /// no template for it.
+    pub async fn render_json(
+        &self,
+        org_id: Option<Uuid>,
+        key: &str,
+        vars: &Value,
+    ) -> Result<Option<Value>, TemplateError> {
+        let Some(template) = self.resolve(org_id, key, None).await? else {
+            return Ok(None);
+        };
+        let name = format!("{}.json", key);
+        let rendered = self
+            .inner
+            .env
+            .render_named_str(&name, &template.body, context(vars, None))
+            .map_err(|e| TemplateError::Render { name: name.clone(), reason: format!("{:#}", e) })?;
+        serde_json::from_str(&rendered)
+            .map(Some)
+            .map_err(|e| TemplateError::InvalidJson { name, reason: e.to_string() })
+    }
+}
+
+/// `vars` plus the render locale, for `t` and `money`.
+fn context(vars: &Value, locale: Option<Locale>) -> minijinja::Value {
+    let vars = minijinja::Value::from_serialize(vars);
+    match locale {
+        Some(locale) => minijinja::context! { locale => locale.as_str(), ..vars },
+        None => vars,
+    }
+}
+
+fn render_locale(state: &State) -> Locale {
+    state
+        .lookup("locale")
+        .and_then(|locale| locale.as_str().and_then(Locale::parse))
+        .unwrap_or_else(|| Catalog::global().default_locale())
+}
+
+fn translate(state: &State, id: &str, kwargs: Kwargs) -> Result<String, minijinja::Error> {
+    let mut args = Vec::new();
+    for name in kwargs.args() {
+        let value: minijinja::Value = kwargs.get(name)?;
+        args.push((name, value.to_string()));
+    }
+    kwargs.assert_all_used()?;
+    Ok(Catalog::global().message(render_locale(state), id, &args))
+}
+
+fn money(state: &State, amount: f64, currency: &str) -> String {
+    let (thousands, decimal) = match render_locale(state) {
+        Locale::En => (',', '.'),
+        Locale::Es | Locale::Pt => ('.', ','),
+    };
+    let fixed = format!("{:.2}", amount.abs());
+    let (units, cents) = fixed.split_once('.').unwrap_or((&fixed, "00"));
+    let mut grouped = String::new();
+    for (i, digit) in units.chars().enumerate() {
+        if i > 0 && (units.len() - i) % 3 == 0 {
+            grouped.push(thousands);
+        }
+        grouped.push(digit);
+    }
+    let sign = if amount < 0.0 { "-" } else { "" };
+    format!("{}{}{}{} {}", sign, grouped, decimal, cents, currency)
+}
+
+fn date(value: &str, format: Option<&str>) -> Result<String, minijinja::Error> {
+    let parsed = DateTime::parse_from_rfc3339(value).map_err(|e| {
+        warn!("⚠️ Template date '{}' is not RFC 3339: {}", value, e);
+        minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, format!("invalid date '{}'", value))
+    })?;
+    Ok(parsed.format(format.unwrap_or("%Y-%m-%d")).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_tenant_and_locale_overrides() {
+        let org_id = Uuid::new_v4();
+        let store = InMemoryTemplateStore::new();
+        store.insert(Some(org_id), "email/welcome.pt", MessageTemplate::new("Olá {{ name }}"));
+        store.insert(None, "email/welcome", MessageTemplate::new("Hello {{ name }}"));
+        let engine = TemplateEngine::new()
+            .store(store)
+            .default_template(
+                "email/receipt",
+                MessageTemplate::new("{{ total | money('BRL') }} on {{ at | date }}").with_html("<b>{{ name }}</b>"),
+            );
+        let vars = json!({ "name": "Ana <3", "total": 1234.5, "at": "2026-03-01T10:00:00Z" });
+
+        let pt = engine.render(Some(org_id), "email/welcome", Some(Locale::Pt), &vars).await.unwrap();
+        assert_eq!(pt.body, "Olá Ana <3");
+        let en = engine.render(Some(org_id), "email/welcome", Some(Locale::En), &vars).await.unwrap();
+        assert_eq!(en.body, "Hello Ana <3");
+        let other = engine.render(Some(Uuid::new_v4()), "email/welcome", Some(Locale::Pt), &vars).await.unwrap();
+        assert_eq!(other.body, "Hello Ana <3");
+
+        let receipt = engine.render(None, "email/receipt", Some(Locale::Pt), &vars).await.unwrap();
+        assert_eq!(receipt.body, "1.234,50 BRL on 2026-03-01");
+        assert_eq!(receipt.html.as_deref(), Some("<b>Ana &lt;3</b>"));
+
+        assert!(matches!(
+            engine.render(None, "email/missing", None, &vars).await,
+            Err(TemplateError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_strict_variables_and_json_payloads() {
+        let payload = MessageTemplate::new(r#"{"id": {{ order_id }}, "note": {{ note }}}"#);
+        let engine = TemplateEngine::new()
+            .default_template("email/welcome", MessageTemplate::new("Hi {{ customer.name }}"))
+            .default_template("webhook/order.paid", payload);
+
+        let result = engine.render(None, "email/welcome", None, &json!({})).await;
+        assert!(matches!(result, Err(TemplateError::Render { .. })));
+
+        let payload = engine
+            .render_json(None, "webhook/order.paid", &json!({ "order_id": 7, "note": "say \"hi\"" }))
+            .await
+            .unwrap();
+        assert_eq!(payload, Some(json!({ "id": 7, "note": "say \"hi\"" })));
+        assert_eq!(engine.render_json(None, "webhook/order.created", &json!({})).await.unwrap(), None);
+    }
+}
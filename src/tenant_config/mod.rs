@@ -0,0 +1,372 @@
+//! Per-tenant configuration client
+//!
+//! Organization settings (currency, tax regime, enabled features) live in one
+//! configuration service. `TenantConfigClient` fetches them from that service, over NATS
+//! request-reply or HTTP, and caches each organization's settings. When settings change,
+//! the config service publishes `lanai.tenant_config.updated.{org_id}` and every replica
+//! evicts its cached copy:
+//!
+//! ```ignore
+//! let tenant_config = TenantConfigClient::new(Arc::new(NatsConfigSource::new()));
+//! let _listener = tenant_config.start_invalidation().await?;
+//! App::new().app_data(web::Data::new(tenant_config.clone()))
+//!
+//! async fn create_invoice(config: TenantConfig, ...) -> Result<HttpResponse, LanaiError> {
+//!     let currency = &config.currency;
+//!     if config.has_feature("e_invoicing") { ... }
+//! }
+//!
+//! // In the config service, after saving:
+//! TenantConfigClient::publish_update(org_id).await?;
+//! ```
+
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::error::LanaiError;
+use crate::messaging::NatsClient;
+use crate::middleware::tenant_context::TenantContext;
+
+/// Request-reply subject served by the configuration service
+pub const FETCH_SUBJECT: &str = "lanai.tenant_config.get";
+/// Prefix of the `{prefix}.{org_id}` subjects announcing changed settings
+pub const UPDATED_SUBJECT: &str = "lanai.tenant_config.updated";
+
+/// Tenant configuration error types
+#[derive(Debug, Clone, Error)]
+pub enum TenantConfigError {
+    #[error("No configuration for organization {0}")]
+    NotFound(Uuid),
+
+    #[error("Tenant configuration source failed: {0}")]
+    Source(String),
+
+    #[error("Invalid tenant configuration: {0}")]
+    Decode(String),
+}
+
+impl From<TenantConfigError> for LanaiError {
+    fn from(e: TenantConfigError) -> Self {
+        match e {
+            TenantConfigError::NotFound(_) => Self::NotFound("Organization configuration not found".to_string()),
+            TenantConfigError::Source(_) => {
+                error!("❌ {}", e);
+                Self::Unavailable("Tenant configuration unavailable".to_string())
+            }
+            TenantConfigError::Decode(_) => Self::internal(e),
+        }
+    }
+}
+
+/// An organization's settings. Settings without a typed field are kept in `extra`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantSettings {
+    pub org_id: Uuid,
+    /// ISO 4217 code, e.g. `MXN`.
+    pub currency: String,
+    /// Tax regime identifier, e.g. `601` (SAT) or `standard`.
+    pub tax_regime: String,
+    #[serde(default)]
+    pub features: BTreeSet<String>,
+    #[serde(default, flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TenantSettings {
+    pub fn new(org_id: Uuid, currency: &str, tax_regime: &str) -> Self {
+        Self {
+            org_id,
+            currency: currency.to_string(),
+            tax_regime: tax_regime.to_string(),
+            features: BTreeSet::new(),
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn feature(mut self, feature: &str) -> Self {
+        self.features.insert(feature.to_string());
+        self
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// An untyped setting, or `None` if it is missing or does not decode as `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.extra.get(key).and_then(|value| T::deserialize(value).ok())
+    }
+}
+
+/// Where tenant settings come from. `Ok(None)` means the organization has none.
+#[async_trait]
+pub trait TenantConfigSource: Send + Sync {
+    async fn fetch(&self, org_id: Uuid) -> Result<Option<TenantSettings>, TenantConfigError>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FetchRequest {
+    org_id: Uuid,
+}
+
+/// Asks the configuration service on `lanai.tenant_config.get` (requires `NatsClient::init`).
+#[derive(Debug, Clone)]
+pub struct NatsConfigSource {
+    subject: String,
+    timeout: Duration,
+}
+
+impl Default for NatsConfigSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NatsConfigSource {
+    pub fn new() -> Self {
+        Self { subject: FETCH_SUBJECT.to_string(), timeout: Duration::from_secs(2) }
+    }
+
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.subject = subject.to_string();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl TenantConfigSource for NatsConfigSource {
+    async fn fetch(&self, org_id: Uuid) -> Result<Option<TenantSettings>, TenantConfigError> {
+        NatsClient::request_typed(&self.subject, &FetchRequest { org_id }, self.timeout)
+            .await
+            .map_err(|e| TenantConfigError::Source(e.to_string()))
+    }
+}
+
+/// Reads `GET {base_url}/organizations/{org_id}/settings`; 404 means no settings.
+#[derive(Debug, Clone)]
+pub struct HttpConfigSource {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpConfigSource {
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .unwrap_or_default();
+        Self::with_client(base_url, client)
+    }
+
+    pub fn with_client(base_url: &str, client: reqwest::Client) -> Self {
+        Self { base_url: base_url.trim_end_matches('/').to_string(), client }
+    }
+}
+
+#[async_trait]
+impl TenantConfigSource for HttpConfigSource {
+    async fn fetch(&self, org_id: Uuid) -> Result<Option<TenantSettings>, TenantConfigError> {
+        let url = format!("{}/organizations/{}/settings", self.base_url, org_id);
+        let response = self.client.get(&url).send().await.map_err(|e| TenantConfigError::Source(e.to_string()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(TenantConfigError::Source(format!("{} answered {}", url, response.status())));
+        }
+        response.json().await.map(Some).map_err(|e| TenantConfigError::Decode(e.to_string()))
+    }
+}
+
+/// Fixed settings, for tests and local development.
+#[derive(Debug, Clone, Default)]
+pub struct StaticTenantConfig {
+    settings: HashMap<Uuid, TenantSettings>,
+}
+
+impl StaticTenantConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn assign(mut self, settings: TenantSettings) -> Self {
+        self.settings.insert(settings.org_id, settings);
+        self
+    }
+}
+
+#[async_trait]
+impl TenantConfigSource for StaticTenantConfig {
+    async fn fetch(&self, org_id: Uuid) -> Result<Option<TenantSettings>, TenantConfigError> {
+        Ok(self.settings.get(&org_id).cloned())
+    }
+}
+
+/// Cached access to tenant settings. Concurrent misses for one organization share a
+/// single fetch.
+#[derive(Clone)]
+pub struct TenantConfigClient {
+    source: Arc<dyn TenantConfigSource>,
+    cache: moka::future::Cache<Uuid, Arc<TenantSettings>>,
+}
+
+impl TenantConfigClient {
+    /// Caches up to 10,000 organizations for 5 minutes each.
+    pub fn new(source: Arc<dyn TenantConfigSource>) -> Self {
+        Self::with_cache(source, 10_000, Duration::from_secs(300))
+    }
+
+    /// The TTL bounds staleness if an update notification is missed.
+    pub fn with_cache(source: Arc<dyn TenantConfigSource>, max_entries: u64, ttl: Duration) -> Self {
+        let cache = moka::future::Cache::builder().max_capacity(max_entries).time_to_live(ttl).build();
+        Self { source, cache }
+    }
+
+    pub async fn get(&self, org_id: Uuid) -> Result<Arc<TenantSettings>, TenantConfigError> {
+        let source = Arc::clone(&self.source);
+        self.cache
+            .try_get_with(org_id, async move {
+                let settings = source.fetch(org_id).await?.ok_or(TenantConfigError::NotFound(org_id))?;
+                Ok(Arc::new(settings))
+            })
+            .await
+            .map_err(|e: Arc<TenantConfigError>| (*e).clone())
+    }
+
+    pub async fn for_tenant(&self, tenant: &TenantContext) -> Result<Arc<TenantSettings>, TenantConfigError> {
+        self.get(tenant.org_id).await
+    }
+
+    /// Drop the cached settings of `org_id`; the next `get` fetches them again.
+    pub async fn invalidate(&self, org_id: Uuid) {
+        self.cache.invalidate(&org_id).await;
+    }
+
+    /// Announce that the settings of `org_id` changed, evicting them on every replica.
+    pub async fn publish_update(org_id: Uuid) -> Result<(), TenantConfigError> {
+        NatsClient::publish_event(&format!("{}.{}", UPDATED_SUBJECT, org_id), &FetchRequest { org_id })
+            .await
+            .map_err(|e| TenantConfigError::Source(e.to_string()))
+    }
+
+    /// Listen for update announcements. Abort the handle to stop.
+    pub async fn start_invalidation(&self) -> Result<JoinHandle<()>, TenantConfigError> {
+        let subject = format!("{}.*", UPDATED_SUBJECT);
+        let mut updates = NatsClient::subscribe(&subject).await.map_err(|e| TenantConfigError::Source(e.to_string()))?;
+        let cache = self.cache.clone();
+        info!("🏢 Listening for tenant configuration updates on '{}'", subject);
+
+        Ok(tokio::spawn(async move {
+            while let Some(message) = updates.next().await {
+                match message.subject.rsplit('.').next().map(Uuid::parse_str) {
+                    Some(Ok(org_id)) => {
+                        debug!("🏢 Evicting configuration of org {}", org_id);
+                        cache.invalidate(&org_id).await;
+                    }
+                    _ => warn!("⚠️ Ignoring tenant configuration update on '{}'", message.subject),
+                }
+            }
+            warn!("⚠️ Tenant configuration update stream on '{}' ended", subject);
+        }))
+    }
+}
+
+/// The settings of the request's tenant, from the `TenantConfigClient` app data.
+#[derive(Debug, Clone)]
+pub struct TenantConfig(pub Arc<TenantSettings>);
+
+impl Deref for TenantConfig {
+    type Target = TenantSettings;
+
+    fn deref(&self) -> &TenantSettings {
+        &self.0
+    }
+}
+
+impl FromRequest for TenantConfig {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let tenant = req.extensions().get::<TenantContext>().copied();
+        let client = req.app_data::<web::Data<TenantConfigClient>>().cloned();
+
+        Box::pin(async move {
+            let tenant = tenant.ok_or_else(|| actix_web::error::ErrorForbidden("Tenant context required"))?;
+            let client = client.ok_or_else(|| {
+                error!("❌ TenantConfig extractor used without TenantConfigClient app data");
+                actix_web::error::ErrorInternalServerError("Tenant configuration unavailable")
+            })?;
+
+            let settings = client.for_tenant(&tenant).await.map_err(LanaiError::from)?;
+            Ok(TenantConfig(settings))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting {
+        inner: StaticTenantConfig,
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TenantConfigSource for Counting {
+        async fn fetch(&self, org_id: Uuid) -> Result<Option<TenantSettings>, TenantConfigError> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.inner.fetch(org_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_caches_until_invalidated() {
+        let org = Uuid::new_v4();
+        let mut settings = TenantSettings::new(org, "MXN", "601").feature("e_invoicing");
+        settings.extra.insert("fiscal_year_start".to_string(), serde_json::json!(1));
+        let source = Arc::new(Counting { inner: StaticTenantConfig::new().assign(settings), fetches: AtomicUsize::new(0) });
+        let client = TenantConfigClient::new(source.clone());
+
+        let fetched = client.get(org).await.unwrap();
+        assert_eq!(fetched.currency, "MXN");
+        assert!(fetched.has_feature("e_invoicing"));
+        assert_eq!(fetched.get::<u32>("fiscal_year_start"), Some(1));
+        client.get(org).await.unwrap();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 1);
+
+        client.invalidate(org).await;
+        client.get(org).await.unwrap();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 2);
+
+        let missing = Uuid::new_v4();
+        assert!(matches!(client.get(missing).await, Err(TenantConfigError::NotFound(id)) if id == missing));
+    }
+
+    #[test]
+    fn test_unknown_settings_are_kept() {
+        let org = Uuid::new_v4();
+        let json = serde_json::json!({ "org_id": org, "currency": "USD", "tax_regime": "standard", "rounding": "half_even" });
+        let settings: TenantSettings = serde_json::from_value(json).unwrap();
+        assert!(settings.features.is_empty());
+        assert_eq!(settings.get::<String>("rounding").as_deref(), Some("half_even"));
+    }
+}
@@ -0,0 +1,146 @@
+//! In-memory message bus
+
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+
+use crate::messaging::{EmbeddedBroker, MessageBus, NatsError, Subscription};
+
+/// A message captured by `InMemoryMessageBus`.
+#[derive(Debug, Clone)]
+pub struct PublishedMessage {
+    pub subject: String,
+    pub headers: HeaderMap,
+    pub payload: Bytes,
+}
+
+impl PublishedMessage {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.payload)
+    }
+}
+
+/// Captures everything published, in order, and delivers it to the bus's subscribers.
+/// Clones share the captured messages and subscribers.
+///
+/// ```ignore
+/// let bus = InMemoryMessageBus::new();
+/// let service = OrderService::new(Arc::new(bus.clone()));
+/// service.place(order).await?;
+/// let reserve: ReserveStockRequest = bus.assert_published("lanai.inventory.stock.reserve");
+/// ```
+#[derive(Clone, Default)]
+pub struct InMemoryMessageBus {
+    messages: Arc<Mutex<Vec<PublishedMessage>>>,
+    failure: Arc<Mutex<Option<String>>>,
+    broker: EmbeddedBroker,
+}
+
+impl InMemoryMessageBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every publish fail with `NatsError::PublishError(reason)` until `recover`.
+    pub fn fail_with(&self, reason: &str) {
+        *self.failure.lock().unwrap_or_else(|p| p.into_inner()) = Some(reason.to_string());
+    }
+
+    pub fn recover(&self) {
+        *self.failure.lock().unwrap_or_else(|p| p.into_inner()) = None;
+    }
+
+    pub fn published(&self) -> Vec<PublishedMessage> {
+        self.messages.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// Messages published on exactly `subject`.
+    pub fn published_to(&self, subject: &str) -> Vec<PublishedMessage> {
+        self.published().into_iter().filter(|m| m.subject == subject).collect()
+    }
+
+    pub fn clear(&self) {
+        self.messages.lock().unwrap_or_else(|p| p.into_inner()).clear();
+    }
+
+    /// The last message on `subject`, deserialized. Panics if there is none.
+    pub fn assert_published<T: DeserializeOwned>(&self, subject: &str) -> T {
+        let message = self.published_to(subject).pop().unwrap_or_else(|| {
+            let subjects: Vec<_> = self.published().into_iter().map(|m| m.subject).collect();
+            panic!("nothing was published on '{}' (published: {:?})", subject, subjects)
+        });
+        message
+            .json()
+            .unwrap_or_else(|e| panic!("message on '{}' is not the expected type: {}", subject, e))
+    }
+
+    /// Panics if `count` messages were not published on `subject`.
+    pub fn assert_published_count(&self, subject: &str, count: usize) {
+        let actual = self.published_to(subject).len();
+        assert_eq!(actual, count, "expected {} messages on '{}', got {}", count, subject, actual);
+    }
+
+    /// Panics if anything was published.
+    pub fn assert_nothing_published(&self) {
+        let subjects: Vec<_> = self.published().into_iter().map(|m| m.subject).collect();
+        assert!(subjects.is_empty(), "expected no messages, got {:?}", subjects);
+    }
+}
+
+#[async_trait]
+impl MessageBus for InMemoryMessageBus {
+    async fn publish_raw(&self, subject: &str, headers: HeaderMap, payload: Bytes) -> Result<(), NatsError> {
+        if let Some(reason) = self.failure.lock().unwrap_or_else(|p| p.into_inner()).clone() {
+            return Err(NatsError::PublishError(reason));
+        }
+        self.messages
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .push(PublishedMessage { subject: subject.to_string(), headers: headers.clone(), payload: payload.clone() });
+        self.broker.publish(subject, None, headers, payload);
+        Ok(())
+    }
+
+    async fn subscribe(&self, subject: &str) -> Result<Subscription, NatsError> {
+        Ok(self.broker.subscribe(subject))
+    }
+
+    async fn queue_subscribe(&self, subject: &str, group: &str) -> Result<Subscription, NatsError> {
+        Ok(self.broker.queue_subscribe(subject, group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::MessageBusExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_captures_and_fails_on_demand() {
+        let bus = InMemoryMessageBus::new();
+        bus.publish_event("orders.created", &json!({"id": 1})).await.unwrap();
+
+        let event: serde_json::Value = bus.assert_published("orders.created");
+        assert_eq!(event["id"], 1);
+        bus.assert_published_count("orders.created", 1);
+
+        bus.fail_with("broker down");
+        assert!(bus.publish_event("orders.created", &json!({})).await.is_err());
+        bus.clear();
+        bus.assert_nothing_published();
+    }
+
+    #[tokio::test]
+    async fn test_delivers_to_subscribers() {
+        let bus = InMemoryMessageBus::new();
+        let mut orders = bus.subscribe("orders.>").await.unwrap();
+        bus.publish_event("orders.created", &json!({"id": 1})).await.unwrap();
+
+        let message = orders.next().await.unwrap();
+        assert_eq!(message.subject.as_str(), "orders.created");
+    }
+}
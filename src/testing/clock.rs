@@ -0,0 +1,109 @@
+//! Manually advanced clock
+
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::common::Clock;
+
+/// A `Clock` that only moves when told to. Clones share the same time.
+///
+/// ```ignore
+/// let clock = FakeClock::new();
+/// let breaker = CircuitBreaker::new(1, Duration::from_secs(30)).with_clock(Arc::new(clock.clone()));
+/// // ... trip the breaker ...
+/// clock.advance(Duration::from_secs(31)); // now half-open, no sleeping
+/// ```
+#[derive(Clone)]
+pub struct FakeClock {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    instant: Instant,
+    utc: DateTime<Utc>,
+}
+
+impl FakeClock {
+    /// Starts at the current time.
+    pub fn new() -> Self {
+        Self::at(Utc::now())
+    }
+
+    /// Starts at `utc`.
+    pub fn at(utc: DateTime<Utc>) -> Self {
+        Self { state: Arc::new(Mutex::new(State { instant: Instant::now(), utc })) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.instant += by;
+        state.utc += chrono::Duration::from_std(by).unwrap_or(chrono::Duration::MAX);
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).instant
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).utc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::{InMemoryRateLimiter, RateLimiterBackend};
+    use crate::resilience::{CircuitBreaker, CircuitBreakerResult, CircuitState};
+
+    #[tokio::test]
+    async fn test_circuit_breaker_half_opens_after_advance() {
+        let clock = FakeClock::new();
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30)).with_clock(Arc::new(clock.clone()));
+
+        let _: CircuitBreakerResult<(), &str> = breaker.call(|| async { Err("down") }).await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+
+        clock.advance(Duration::from_secs(31));
+        let result: CircuitBreakerResult<(), &str> = breaker.call(|| async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert_eq!(breaker.state().await, CircuitState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_window_slides() {
+        let clock = FakeClock::new();
+        let limiter = InMemoryRateLimiter::new().with_clock(Arc::new(clock.clone()));
+
+        assert!(limiter.is_allowed("ip", 1, 60).await);
+        assert!(!limiter.is_allowed("ip", 1, 60).await);
+        clock.advance(Duration::from_secs(61));
+        assert!(limiter.is_allowed("ip", 1, 60).await);
+    }
+
+    #[actix_web::test]
+    async fn test_auth_guard_expiry_follows_the_clock() {
+        use actix_web::{test, web, App, HttpResponse};
+
+        // Generating the shared keys can take seconds; start the clock after
+        let keys = crate::testing::TestKeys::shared();
+        let clock = FakeClock::new();
+        let guard = keys.auth_guard().leeway(Duration::from_secs(30)).with_clock(Arc::new(clock.clone()));
+        let app = test::init_service(App::new().wrap(guard).route("/", web::get().to(HttpResponse::Ok))).await;
+        let token = keys.token().expires_in(Duration::from_secs(60)).sign();
+        let call = || test::TestRequest::get().uri("/").insert_header(("Authorization", format!("Bearer {}", token)));
+
+        clock.advance(Duration::from_secs(80));
+        assert_eq!(test::call_service(&app, call().to_request()).await.status(), 200);
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(test::call_service(&app, call().to_request()).await.status(), 401);
+    }
+}
@@ -0,0 +1,298 @@
+//! Schema-checked contract tests
+//!
+//! A `ContractSet` collects the JSON Schemas (schemars) of the events a service publishes
+//! and the DTOs of its API, and compares them against snapshots committed next to the
+//! tests. A test fails when a change would break consumers:
+//!
+//! ```ignore
+//! #[test]
+//! fn contracts_are_compatible() {
+//!     ContractSet::new()
+//!         .event::<ProductCreatedEvent>()
+//!         .response::<ProductV2>()
+//!         .request::<CreateProductV2>()
+//!         .assert_compatible("tests/contracts");
+//! }
+//! ```
+//!
+//! Events and response DTOs are read by consumers, so removing a field, making it
+//! optional, widening its type or adding enum values breaks them. Request DTOs are written
+//! by clients, so new required fields, narrowed types and removed enum values break them.
+//! Anything else (new optional fields, new contracts) is compatible.
+//!
+//! Missing snapshots are written on the first run. After an intended breaking change (a
+//! new event version, a new DTO version), rerun with `LANAI_UPDATE_CONTRACTS=1` to accept
+//! the new schemas, and commit them.
+
+use schemars::JsonSchema;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::dto::DescribeDto;
+use crate::messaging::events::DescribeEvent;
+
+/// Set to accept the current schemas as the new snapshots.
+pub const UPDATE_CONTRACTS_ENV: &str = "LANAI_UPDATE_CONTRACTS";
+
+/// Who reads a payload: consumers of what we produce, or us reading what clients send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Events and responses; consumers must still understand new payloads.
+    Produced,
+    /// Requests; payloads clients already send must still be accepted.
+    Consumed,
+}
+
+#[derive(Debug, Clone)]
+struct Contract {
+    direction: Direction,
+    schema: Value,
+}
+
+/// Named JSON Schemas to check against snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct ContractSet {
+    contracts: BTreeMap<String, Contract>,
+}
+
+impl ContractSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the schema of `T` under `name`, a relative path without extension.
+    pub fn schema<T: JsonSchema>(mut self, name: &str, direction: Direction) -> Self {
+        let schema = schemars::schema_for!(T).to_value();
+        self.contracts.insert(name.to_string(), Contract { direction, schema });
+        self
+    }
+
+    /// A published event, as `events/{event_type}.v{version}`.
+    pub fn event<T: DescribeEvent + JsonSchema>(self) -> Self {
+        let described = T::event_schema();
+        self.schema::<T>(&format!("events/{}.v{}", described.event_type, described.version), Direction::Produced)
+    }
+
+    /// A response DTO, as `dtos/{model}V{version}`.
+    pub fn response<T: DescribeDto + JsonSchema>(self) -> Self {
+        self.schema::<T>(&format!("dtos/{}", T::dto_schema().component_name()), Direction::Produced)
+    }
+
+    /// A request DTO, as `dtos/{model}V{version}`.
+    pub fn request<T: DescribeDto + JsonSchema>(self) -> Self {
+        self.schema::<T>(&format!("dtos/{}", T::dto_schema().component_name()), Direction::Consumed)
+    }
+
+    /// Current schemas by name.
+    pub fn schemas(&self) -> BTreeMap<&str, &Value> {
+        self.contracts.iter().map(|(name, contract)| (name.as_str(), &contract.schema)).collect()
+    }
+
+    /// Breaking changes against the snapshots in `dir`, writing missing snapshots (and,
+    /// with `LANAI_UPDATE_CONTRACTS`, replacing existing ones).
+    pub fn check(&self, dir: impl AsRef<Path>) -> std::io::Result<Vec<String>> {
+        let update = std::env::var(UPDATE_CONTRACTS_ENV).is_ok_and(|v| !v.is_empty() && v != "0");
+        let mut breaking = Vec::new();
+        for (name, contract) in &self.contracts {
+            let path = dir.as_ref().join(format!("{}.json", name));
+            if !update && path.exists() {
+                let snapshot: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                breaking.extend(
+                    breaking_changes(&snapshot, &contract.schema, contract.direction)
+                        .into_iter()
+                        .map(|change| format!("{}: {}", name, change)),
+                );
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let pretty = serde_json::to_string_pretty(&contract.schema).map_err(std::io::Error::other)?;
+            std::fs::write(&path, pretty + "\n")?;
+        }
+        Ok(breaking)
+    }
+
+    /// Panic listing every breaking change against the snapshots in `dir`.
+    pub fn assert_compatible(&self, dir: impl AsRef<Path>) {
+        let dir = dir.as_ref();
+        let breaking = self.check(dir).unwrap_or_else(|e| panic!("reading contracts in {}: {}", dir.display(), e));
+        assert!(
+            breaking.is_empty(),
+            "breaking contract changes (rerun with {}=1 to accept them):\n  {}",
+            UPDATE_CONTRACTS_ENV,
+            breaking.join("\n  ")
+        );
+    }
+}
+
+/// Changes from `old` to `new` that break the readers of a payload going in `direction`.
+pub fn breaking_changes(old: &Value, new: &Value, direction: Direction) -> Vec<String> {
+    let mut changes = Vec::new();
+    compare(old, new, "", direction, &mut changes);
+
+    // Named subschemas (`$defs`) are compared by name; renamed ones surface as `$ref` changes
+    let defs = |schema: &Value| schema.get("$defs").and_then(Value::as_object).cloned().unwrap_or_default();
+    let new_defs = defs(new);
+    for (name, old_def) in defs(old) {
+        if let Some(new_def) = new_defs.get(&name) {
+            compare(&old_def, new_def, &format!("$defs.{}", name), direction, &mut changes);
+        }
+    }
+    changes
+}
+
+fn compare(old: &Value, new: &Value, path: &str, direction: Direction, changes: &mut Vec<String>) {
+    let at = if path.is_empty() { "root".to_string() } else { path.to_string() };
+    let (old_types, new_types) = (kinds(old), kinds(new));
+    if !old_types.is_empty() && !new_types.is_empty() {
+        let (from, to) = match direction {
+            Direction::Produced => (&old_types, &new_types),
+            Direction::Consumed => (&new_types, &old_types),
+        };
+        if !to.is_subset(from) {
+            changes.push(format!("{} changed type from {:?} to {:?}", at, old_types, new_types));
+        }
+    }
+
+    let (old_enum, new_enum) = (enum_values(old), enum_values(new));
+    if let (Some(old_enum), Some(new_enum)) = (&old_enum, &new_enum) {
+        let (from, to, verb) = match direction {
+            Direction::Produced => (old_enum, new_enum, "gained"),
+            Direction::Consumed => (new_enum, old_enum, "lost"),
+        };
+        let diff: Vec<_> = to.difference(from).collect();
+        if !diff.is_empty() {
+            changes.push(format!("{} {} enum values {:?}", at, verb, diff));
+        }
+    }
+
+    let (old_required, new_required) = (required(old), required(new));
+    let properties = |schema: &Value| schema.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+    let (old_properties, new_properties) = (properties(old), properties(new));
+    match direction {
+        Direction::Produced => {
+            for (name, old_property) in &old_properties {
+                match new_properties.get(name) {
+                    None => changes.push(format!("{} was removed", join(path, name))),
+                    Some(_) if old_required.contains(name) && !new_required.contains(name) => {
+                        changes.push(format!("{} is no longer required", join(path, name)))
+                    }
+                    Some(new_property) => compare(old_property, new_property, &join(path, name), direction, changes),
+                }
+            }
+        }
+        Direction::Consumed => {
+            for name in new_required.difference(&old_required) {
+                changes.push(format!("{} is now required", join(path, name)));
+            }
+            for (name, old_property) in &old_properties {
+                if let Some(new_property) = new_properties.get(name) {
+                    compare(old_property, new_property, &join(path, name), direction, changes);
+                }
+            }
+        }
+    }
+
+    if let (Some(old_items), Some(new_items)) = (old.get("items"), new.get("items")) {
+        compare(old_items, new_items, &format!("{}[]", path), direction, changes);
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// JSON types and `$ref`s a schema allows, looking through `anyOf`/`oneOf`.
+fn kinds(schema: &Value) -> BTreeSet<String> {
+    let mut allowed = BTreeSet::new();
+    match schema.get("type") {
+        Some(Value::String(kind)) => {
+            allowed.insert(kind.clone());
+        }
+        Some(Value::Array(types)) => allowed.extend(types.iter().filter_map(Value::as_str).map(str::to_string)),
+        _ => {}
+    }
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        allowed.insert(reference.to_string());
+    }
+    for key in ["anyOf", "oneOf"] {
+        for variant in schema.get(key).and_then(Value::as_array).into_iter().flatten() {
+            allowed.extend(kinds(variant));
+        }
+    }
+    // An integer is also a number
+    if allowed.contains("number") {
+        allowed.insert("integer".to_string());
+    }
+    allowed
+}
+
+fn enum_values(schema: &Value) -> Option<BTreeSet<String>> {
+    schema.get("enum").and_then(Value::as_array).map(|values| values.iter().map(Value::to_string).collect())
+}
+
+fn required(schema: &Value) -> BTreeSet<String> {
+    let names = schema.get("required").and_then(Value::as_array).into_iter().flatten();
+    names.filter_map(Value::as_str).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::events::ProductCreatedEvent;
+    use serde_json::json;
+
+    #[test]
+    fn test_breaking_changes_by_direction() {
+        let old = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "status": { "type": "string", "enum": ["active", "archived"] },
+                "note": { "type": ["string", "null"] }
+            },
+            "required": ["name", "status"]
+        });
+        let new = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": ["string", "null"] },
+                "status": { "type": "string", "enum": ["active", "archived", "draft"] },
+                "sku": { "type": "string" }
+            },
+            "required": ["name", "status", "sku"]
+        });
+
+        let produced = breaking_changes(&old, &new, Direction::Produced);
+        assert_eq!(produced.len(), 3, "{:?}", produced);
+        assert!(produced.iter().any(|c| c == "note was removed"));
+        assert!(produced.iter().any(|c| c.starts_with("name changed type")));
+        assert!(produced.iter().any(|c| c.starts_with("status gained enum values")));
+
+        assert_eq!(breaking_changes(&old, &new, Direction::Consumed), vec!["sku is now required".to_string()]);
+        assert!(breaking_changes(&new, &new, Direction::Produced).is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_are_written_then_checked() {
+        let dir = std::env::temp_dir().join(format!("lanai-contracts-{}", uuid::Uuid::new_v4()));
+        let contracts = ContractSet::new().event::<ProductCreatedEvent>();
+        assert!(contracts.check(&dir).unwrap().is_empty());
+        assert!(dir.join("events/ProductCreatedEvent.v1.json").exists());
+        contracts.assert_compatible(&dir);
+
+        let snapshot = dir.join("events/ProductCreatedEvent.v1.json");
+        let mut schema: Value = serde_json::from_str(&std::fs::read_to_string(&snapshot).unwrap()).unwrap();
+        schema["properties"]["legacy_code"] = json!({ "type": "string" });
+        std::fs::write(&snapshot, schema.to_string()).unwrap();
+        assert_eq!(contracts.check(&dir).unwrap(), vec!["events/ProductCreatedEvent.v1: legacy_code was removed"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+//! Containerized integration test harness
+//!
+//! `TestHarness::start` runs NATS (with JetStream), Redis and PostgreSQL in Docker, points
+//! `NATS_URL`, `REDIS_URL` and `DATABASE_URL` at them and initializes the crate's shared
+//! clients (`NatsClient`, `cache::shared_connection`). The containers are started once per
+//! test binary and reused by every test in it; they are removed when the process exits.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn reserves_stock() {
+//!     let harness = TestHarness::start().await.unwrap();
+//!     let pool = harness.pool().await.unwrap();
+//!     let mut probe = harness.subscribe("lanai.inventory.stock.reserved.>").await.unwrap();
+//!
+//!     place_order(&pool).await;
+//!
+//!     let event: StockReserved = probe.next_event(Duration::from_secs(5)).await.unwrap();
+//!     assert_eq!(event.items.len(), 2);
+//! }
+//! ```
+//!
+//! The shared clients run on a dedicated runtime owned by the harness, so they keep working
+//! across `#[tokio::test]` functions, each of which has its own runtime.
+
+use futures_util::StreamExt;
+use log::info;
+use serde::de::DeserializeOwned;
+use sqlx::PgPool;
+use std::sync::OnceLock;
+use std::time::Duration;
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, ImageExt};
+use testcontainers_modules::{nats::Nats, postgres::Postgres, redis::Redis};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+use tokio::sync::OnceCell;
+
+use crate::db::pool::DATABASE_URL_ENV;
+use crate::db::PgPoolBuilder;
+use crate::messaging::{NatsClient, NATS_URL_ENV};
+use crate::rate_limit::REDIS_URL_ENV;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+static HARNESS: OnceCell<TestHarness> = OnceCell::const_new();
+
+/// Test harness error types
+#[derive(Debug, Error)]
+pub enum TestHarnessError {
+    #[error("Failed to start container: {0}")]
+    Container(String),
+
+    #[error("Failed to connect to test infrastructure: {0}")]
+    Client(String),
+
+    #[error("No event on '{0}' within {1:?}")]
+    Timeout(String, Duration),
+
+    #[error("Failed to deserialize event: {0}")]
+    Deserialize(String),
+
+    #[error("Unexpected message on '{0}'")]
+    Unexpected(String),
+}
+
+pub struct TestHarness {
+    pub nats_url: String,
+    pub redis_url: String,
+    pub database_url: String,
+    _nats: ContainerAsync<Nats>,
+    _redis: ContainerAsync<Redis>,
+    _postgres: ContainerAsync<Postgres>,
+}
+
+fn container_error(e: impl std::fmt::Display) -> TestHarnessError {
+    TestHarnessError::Container(e.to_string())
+}
+
+fn client_error(e: impl std::fmt::Display) -> TestHarnessError {
+    TestHarnessError::Client(e.to_string())
+}
+
+impl TestHarness {
+    /// Start the containers and clients, or return the ones already started by this process.
+    pub async fn start() -> Result<&'static TestHarness, TestHarnessError> {
+        HARNESS
+            .get_or_try_init(|| async {
+                let runtime = RUNTIME.get_or_init(|| {
+                    tokio::runtime::Builder::new_multi_thread()
+                        .worker_threads(2)
+                        .thread_name("lanai-test-harness")
+                        .enable_all()
+                        .build()
+                        .expect("failed to build the test harness runtime")
+                });
+                runtime.spawn(Self::launch()).await.map_err(client_error)?
+            })
+            .await
+    }
+
+    async fn launch() -> Result<TestHarness, TestHarnessError> {
+        info!("🧪 Starting test containers (NATS, Redis, PostgreSQL)...");
+        let (nats, redis, postgres) = tokio::try_join!(
+            async { Nats::default().with_cmd(["-js"]).start().await.map_err(container_error) },
+            async { Redis::default().with_tag("7-alpine").start().await.map_err(container_error) },
+            async { Postgres::default().with_tag("16-alpine").start().await.map_err(container_error) },
+        )?;
+
+        let nats_url = format!(
+            "nats://{}:{}",
+            nats.get_host().await.map_err(container_error)?,
+            nats.get_host_port_ipv4(4222).await.map_err(container_error)?
+        );
+        let redis_url = format!(
+            "redis://{}:{}",
+            redis.get_host().await.map_err(container_error)?,
+            redis.get_host_port_ipv4(6379).await.map_err(container_error)?
+        );
+        let database_url = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            postgres.get_host().await.map_err(container_error)?,
+            postgres.get_host_port_ipv4(5432).await.map_err(container_error)?
+        );
+
+        std::env::set_var(NATS_URL_ENV, &nats_url);
+        std::env::set_var(REDIS_URL_ENV, &redis_url);
+        std::env::set_var(DATABASE_URL_ENV, &database_url);
+
+        NatsClient::init(&nats_url).await.map_err(client_error)?;
+        crate::cache::shared_connection().await.map_err(client_error)?;
+
+        info!("✅ Test containers ready (NATS {}, Redis {}, PostgreSQL {})", nats_url, redis_url, database_url);
+        Ok(TestHarness { nats_url, redis_url, database_url, _nats: nats, _redis: redis, _postgres: postgres })
+    }
+
+    /// A new pool for the calling test. Pools are not shared between tests because their
+    /// connections belong to the runtime that opened them.
+    pub async fn pool(&self) -> Result<PgPool, TestHarnessError> {
+        PgPoolBuilder::new(&self.database_url, "lanai-tests")
+            .max_connections(5)
+            .build()
+            .await
+            .map_err(client_error)
+    }
+
+    /// Subscribe to `subject` (wildcards allowed). Subscribe before triggering the action
+    /// that publishes, or the event may be missed.
+    pub async fn subscribe(&self, subject: &str) -> Result<EventProbe, TestHarnessError> {
+        let client = NatsClient::global().ok_or_else(|| client_error("NATS client is not initialized"))?;
+        let subscriber = client.subscribe(subject.to_string()).await.map_err(client_error)?;
+        Ok(EventProbe { subject: subject.to_string(), subscriber })
+    }
+
+    /// Wait for the next event on `subject` published after this call.
+    pub async fn wait_for_event<T: DeserializeOwned>(&self, subject: &str, timeout: Duration) -> Result<T, TestHarnessError> {
+        self.subscribe(subject).await?.next_event(timeout).await
+    }
+}
+
+/// Messages received on a subscription opened by `TestHarness::subscribe`.
+pub struct EventProbe {
+    subject: String,
+    subscriber: async_nats::Subscriber,
+}
+
+impl EventProbe {
+    /// The next message, as raw NATS message.
+    pub async fn next_message(&mut self, timeout: Duration) -> Result<async_nats::Message, TestHarnessError> {
+        tokio::time::timeout(timeout, self.subscriber.next())
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| TestHarnessError::Timeout(self.subject.clone(), timeout))
+    }
+
+    /// The next message, deserialized from JSON.
+    pub async fn next_event<T: DeserializeOwned>(&mut self, timeout: Duration) -> Result<T, TestHarnessError> {
+        let message = self.next_message(timeout).await?;
+        serde_json::from_slice(&message.payload).map_err(|e| TestHarnessError::Deserialize(e.to_string()))
+    }
+
+    /// Assert that nothing arrives within `window`.
+    pub async fn expect_none(&mut self, window: Duration) -> Result<(), TestHarnessError> {
+        match tokio::time::timeout(window, self.subscriber.next()).await {
+            Ok(Some(message)) => Err(TestHarnessError::Unexpected(message.subject.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
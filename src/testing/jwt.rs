@@ -0,0 +1,182 @@
+//! RS256 keys and tokens for tests and local development
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use std::sync::OnceLock;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::test_claims;
+use crate::middleware::auth_guard::{AuthGuard, Claims};
+
+/// A freshly generated RS256 keypair that signs tokens a real `AuthGuard` accepts.
+///
+/// ```ignore
+/// let keys = TestKeys::shared();
+/// let app = test::init_service(App::new().wrap(keys.auth_guard()).configure(routes::configure)).await;
+/// let token = keys.token().role("viewer").org(org_id).sign();
+/// let req = test::TestRequest::get().uri("/products").insert_header(("Authorization", format!("Bearer {token}")));
+/// ```
+pub struct TestKeys {
+    private_key_pem: String,
+    public_key_pem: String,
+    encoding_key: EncodingKey,
+}
+
+impl TestKeys {
+    /// Generate a new 2048-bit keypair (slow in debug builds; prefer `shared`).
+    pub fn generate() -> Self {
+        let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 2048).expect("RSA key generation failed");
+        let private_key_pem = private_key.to_pkcs8_pem(LineEnding::LF).expect("PEM encoding failed").to_string();
+        let public_key_pem = RsaPublicKey::from(&private_key)
+            .to_public_key_pem(LineEnding::LF)
+            .expect("PEM encoding failed");
+        let encoding_key = EncodingKey::from_rsa_pem(private_key_pem.as_bytes()).expect("generated key is valid");
+        Self { private_key_pem, public_key_pem, encoding_key }
+    }
+
+    /// One keypair for the whole test binary.
+    pub fn shared() -> &'static TestKeys {
+        static KEYS: OnceLock<TestKeys> = OnceLock::new();
+        KEYS.get_or_init(Self::generate)
+    }
+
+    pub fn private_key_pem(&self) -> &str {
+        &self.private_key_pem
+    }
+
+    /// What services read from their JWT public key setting.
+    pub fn public_key_pem(&self) -> &str {
+        &self.public_key_pem
+    }
+
+    /// An `AuthGuard` that verifies tokens signed by these keys.
+    pub fn auth_guard(&self) -> AuthGuard {
+        AuthGuard::new(self.public_key_pem.clone())
+    }
+
+    /// Sign arbitrary claims.
+    pub fn sign(&self, claims: &Claims) -> String {
+        encode(&Header::new(Algorithm::RS256), claims, &self.encoding_key).expect("signing test claims failed")
+    }
+
+    /// Start from `test_claims(None)`: an admin without organization, valid for an hour.
+    pub fn token(&self) -> TokenBuilder<'_> {
+        TokenBuilder { keys: self, claims: test_claims(None) }
+    }
+}
+
+/// Claims being prepared for signing by `TestKeys::token`.
+pub struct TokenBuilder<'a> {
+    keys: &'a TestKeys,
+    claims: Claims,
+}
+
+impl TokenBuilder<'_> {
+    pub fn role(mut self, role: &str) -> Self {
+        self.claims.role = role.to_string();
+        self
+    }
+
+    pub fn org(mut self, org_id: Uuid) -> Self {
+        self.claims.org_id = Some(org_id.to_string());
+        self
+    }
+
+    pub fn subject(mut self, sub: &str) -> Self {
+        self.claims.sub = sub.to_string();
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.claims.email = email.to_string();
+        self
+    }
+
+    pub fn vertical(mut self, vertical: &str) -> Self {
+        self.claims.vertical = Some(vertical.to_string());
+        self
+    }
+
+    pub fn issuer(mut self, iss: &str) -> Self {
+        self.claims.iss = iss.to_string();
+        self
+    }
+
+    pub fn expires_in(mut self, ttl: Duration) -> Self {
+        self.claims.exp = self.claims.iat + ttl.as_secs() as i64;
+        self
+    }
+
+    /// Expired well beyond the validation leeway.
+    pub fn expired(mut self) -> Self {
+        self.claims.exp = self.claims.iat - 3600;
+        self
+    }
+
+    pub fn claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    pub fn sign(self) -> String {
+        self.keys.sign(&self.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpMessage, HttpRequest, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_auth_guard_accepts_signed_tokens() {
+        let keys = TestKeys::shared();
+        let app = test::init_service(App::new().wrap(keys.auth_guard()).route(
+            "/whoami",
+            web::get().to(|req: HttpRequest| async move {
+                let role = req.extensions().get::<Claims>().map(|c| c.role.clone()).unwrap_or_default();
+                HttpResponse::Ok().body(role)
+            }),
+        ))
+        .await;
+
+        let token = keys.token().role("viewer").org(Uuid::new_v4()).sign();
+        let req = test::TestRequest::get()
+            .uri("/whoami")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "viewer");
+
+        let expired = keys.token().expired().sign();
+        let req = test::TestRequest::get()
+            .uri("/whoami")
+            .insert_header(("Authorization", format!("Bearer {}", expired)))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn test_auth_guard_hints_refresh_and_explains_rejections() {
+        let keys = TestKeys::shared();
+        let guard = keys.auth_guard().leeway(Duration::ZERO).refresh_window(Duration::from_secs(120));
+        let app = test::init_service(App::new().wrap(guard).route("/", web::get().to(HttpResponse::Ok))).await;
+        let call = |token: String| {
+            test::TestRequest::get().uri("/").insert_header(("Authorization", format!("Bearer {}", token))).to_request()
+        };
+
+        let res = test::call_service(&app, call(keys.token().sign())).await;
+        assert!(res.headers().get("X-Token-Expires-In").is_none());
+
+        let res = test::call_service(&app, call(keys.token().expires_in(Duration::from_secs(60)).sign())).await;
+        let expires_in: i64 = res.headers().get("X-Token-Expires-In").unwrap().to_str().unwrap().parse().unwrap();
+        assert!((0..=60).contains(&expires_in));
+
+        let body: serde_json::Value = test::call_and_read_body_json(&app, call(keys.token().expired().sign())).await;
+        assert_eq!((body["reason"].as_str(), body["code"].as_str()), (Some("expired"), Some("AUTH_TOKEN_EXPIRED")));
+
+        let body: serde_json::Value = test::call_and_read_body_json(&app, call("not-a-jwt".to_string())).await;
+        assert_eq!(body["reason"], "invalid");
+    }
+}
@@ -0,0 +1,31 @@
+//! Test support for services built on this crate
+//!
+//! - `test-utils` feature: in-memory doubles for unit tests (`FakeClock`,
+//!   `InMemoryMessageBus`, `MockRateLimiter`), `TestServer`, an HTTP server with
+//!   authentication stubbed out, and `TestKeys` for signing real RS256 tokens
+//! - `testing` feature (implies `test-utils`): `TestHarness`, containerized
+//!   NATS/Redis/PostgreSQL (requires Docker)
+//! - `contracts` feature (implies `test-utils`): `ContractSet`, JSON Schema snapshots of
+//!   events and DTOs that fail tests on breaking changes
+
+pub mod bus;
+pub mod clock;
+pub mod jwt;
+pub mod rate_limit;
+pub mod server;
+
+#[cfg(feature = "testing")]
+pub mod harness;
+#[cfg(feature = "contracts")]
+pub mod contracts;
+
+pub use bus::{InMemoryMessageBus, PublishedMessage};
+pub use clock::FakeClock;
+pub use jwt::{TestKeys, TokenBuilder};
+pub use rate_limit::MockRateLimiter;
+pub use server::{test_claims, RunningTestServer, TestServer};
+
+#[cfg(feature = "testing")]
+pub use harness::{EventProbe, TestHarness, TestHarnessError};
+#[cfg(feature = "contracts")]
+pub use contracts::{ContractSet, Direction};
@@ -0,0 +1,58 @@
+//! Scriptable rate limiter
+
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crate::rate_limit::RateLimiterBackend;
+
+/// A `RateLimiterBackend` that allows or denies on command and records every check.
+/// Clones share state.
+#[derive(Clone, Default)]
+pub struct MockRateLimiter {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    deny_all: bool,
+    denied_keys: HashSet<String>,
+    calls: Vec<String>,
+}
+
+impl MockRateLimiter {
+    /// Allows everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Denies everything.
+    pub fn denying() -> Self {
+        let limiter = Self::new();
+        limiter.set_deny_all(true);
+        limiter
+    }
+
+    pub fn set_deny_all(&self, deny: bool) {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).deny_all = deny;
+    }
+
+    /// Deny checks for `key` (as built by the middleware, e.g. the client IP).
+    pub fn deny_key(&self, key: &str) {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).denied_keys.insert(key.to_string());
+    }
+
+    /// Keys checked so far, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).calls.clone()
+    }
+}
+
+#[async_trait]
+impl RateLimiterBackend for MockRateLimiter {
+    async fn is_allowed(&self, key: &str, _limit: u32, _window_secs: u64) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        state.calls.push(key.to_string());
+        !state.deny_all && !state.denied_keys.contains(key)
+    }
+}
@@ -0,0 +1,219 @@
+//! HTTP server for handler tests, with authentication stubbed out
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, App, HttpMessage, HttpServer};
+use futures_util::future::{ok, LocalBoxFuture, Ready};
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::MockRateLimiter;
+use crate::middleware::auth_guard::Claims;
+use crate::middleware::rate_limit::RateLimitMiddleware;
+use crate::middleware::tenant_context::TenantMiddleware;
+use crate::rate_limit::RateLimiterBackend;
+
+/// Builds a real HTTP server on a random local port, serving the service's routes behind
+/// the tenant and rate-limit middleware of `ServerBuilder`. Instead of validating a JWT,
+/// every request is authenticated as the configured test user.
+///
+/// ```ignore
+/// let server = TestServer::new().org(org_id).start(routes::configure).await?;
+/// let response = server.get("/products").send().await?;
+/// assert_eq!(response.status(), 200);
+/// ```
+pub struct TestServer {
+    claims: Option<Claims>,
+    limiter: Arc<dyn RateLimiterBackend>,
+}
+
+impl TestServer {
+    /// Authenticated as `test_claims(None)`, rate limiting always allowing.
+    pub fn new() -> Self {
+        Self { claims: Some(test_claims(None)), limiter: Arc::new(MockRateLimiter::new()) }
+    }
+
+    /// Authenticate requests as `claims`.
+    pub fn claims(mut self, claims: Claims) -> Self {
+        self.claims = Some(claims);
+        self
+    }
+
+    /// Scope the test user's token to `org_id`.
+    pub fn org(mut self, org_id: Uuid) -> Self {
+        let claims = self.claims.get_or_insert_with(|| test_claims(None));
+        claims.org_id = Some(org_id.to_string());
+        self
+    }
+
+    /// Send requests unauthenticated (public routes, `X-Organization-ID` header tenancy).
+    pub fn anonymous(mut self) -> Self {
+        self.claims = None;
+        self
+    }
+
+    pub fn rate_limiter(mut self, limiter: Arc<dyn RateLimiterBackend>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub async fn start<F>(self, configure: F) -> std::io::Result<RunningTestServer>
+    where
+        F: Fn(&mut web::ServiceConfig) + Send + Clone + 'static,
+    {
+        let claims = self.claims;
+        let limiter = self.limiter;
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap(TenantMiddleware)
+                .wrap(StubAuth { claims: claims.clone() })
+                .wrap(RateLimitMiddleware { limiter: Arc::clone(&limiter), max_requests: 1000, window_seconds: 60 })
+                .configure(configure.clone())
+        })
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))?;
+
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(RunningTestServer { addr, handle, client: reqwest::Client::new() })
+    }
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Claims of the default test user, optionally scoped to an organization.
+pub fn test_claims(org_id: Option<Uuid>) -> Claims {
+    let now = chrono::Utc::now().timestamp();
+    Claims {
+        sub: "00000000-0000-0000-0000-000000000001".to_string(),
+        email: "test@lanai.test".to_string(),
+        username: "test".to_string(),
+        role: "admin".to_string(),
+        org_id: org_id.map(|id| id.to_string()),
+        vertical: None,
+        exp: now + 3600,
+        iat: now,
+        iss: "lanai-auth".to_string(),
+        jti: Uuid::new_v4().to_string(),
+    }
+}
+
+pub struct RunningTestServer {
+    addr: SocketAddr,
+    handle: actix_web::dev::ServerHandle,
+    client: reqwest::Client,
+}
+
+impl RunningTestServer {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.get(self.url(path))
+    }
+
+    pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.post(self.url(path))
+    }
+
+    pub fn put(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.put(self.url(path))
+    }
+
+    pub fn delete(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.delete(self.url(path))
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+/// Inserts fixed claims where `AuthGuard` would insert the decoded token's.
+struct StubAuth {
+    claims: Option<Claims>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for StubAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = StubAuthService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(StubAuthService { service: Rc::new(service), claims: self.claims.clone() })
+    }
+}
+
+struct StubAuthService<S> {
+    service: Rc<S>,
+    claims: Option<Claims>,
+}
+
+impl<S, B> Service<ServiceRequest> for StubAuthService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(claims) = &self.claims {
+            req.extensions_mut().insert(claims.clone());
+        }
+        let service = self.service.clone();
+        Box::pin(async move { service.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::tenant_context::TenantContext;
+    use actix_web::HttpResponse;
+
+    #[tokio::test]
+    async fn test_requests_are_authenticated_as_test_user() {
+        let org_id = Uuid::new_v4();
+        let server = TestServer::new()
+            .org(org_id)
+            .start(|cfg| {
+                cfg.route(
+                    "/whoami",
+                    web::get().to(|tenant: TenantContext| async move { HttpResponse::Ok().body(tenant.org_id.to_string()) }),
+                );
+            })
+            .await
+            .unwrap();
+
+        let body = server.get("/whoami").send().await.unwrap().text().await.unwrap();
+        assert_eq!(body, org_id.to_string());
+        server.stop().await;
+    }
+}
@@ -0,0 +1,334 @@
+//! Units of measure for fractional stock
+//!
+//! `StockItem.quantity` is a bare `Decimal`; `Quantity` adds the unit so kilos, grams,
+//! litres and packs are converted the same way everywhere. Units of one dimension (mass,
+//! volume, count) convert into each other through a base unit (g, ml, unit); mixing
+//! dimensions fails with `UomError::Incompatible`.
+//!
+//! Every unit has a precision: results are rounded (half to even, like `Money`) to grams
+//! for kg, to millilitres for L, to whole units for `unit`, and so on. Arithmetic between
+//! two quantities happens in the unit of the left-hand side:
+//!
+//! ```ignore
+//! let stock = Quantity::new(dec!(2.5), Unit::Kilogram);
+//! let portion = Quantity::new(dec!(180), Unit::Gram);
+//! let left = stock.checked_sub(&portion)?;          // 2.320 kg
+//! let packs = Quantity::new(dec!(36), Unit::Each).convert_to(Unit::Pack(12))?; // 3 pack:12
+//! ```
+//!
+//! Serialized as `{"value": "2.320", "unit": "kg"}`. In PostgreSQL store the value as
+//! `NUMERIC` and the unit as `TEXT` (`Unit` implements the sqlx traits).
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Unit of measure error types
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UomError {
+    #[error("Cannot convert between {0} and {1}")]
+    Incompatible(Unit, Unit),
+
+    #[error("Unknown unit '{0}'")]
+    UnknownUnit(String),
+
+    #[error("Invalid quantity '{0}'")]
+    InvalidQuantity(String),
+
+    #[error("Arithmetic overflow")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    Mass,
+    Volume,
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Milligram,
+    Gram,
+    Kilogram,
+    /// Metric ton.
+    Tonne,
+    Pound,
+    Ounce,
+    Millilitre,
+    Litre,
+    /// Single items.
+    Each,
+    Dozen,
+    /// A pack of `n` items (box, crate, six-pack).
+    Pack(u32),
+}
+
+impl Unit {
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Self::Milligram | Self::Gram | Self::Kilogram | Self::Tonne | Self::Pound | Self::Ounce => Dimension::Mass,
+            Self::Millilitre | Self::Litre => Dimension::Volume,
+            Self::Each | Self::Dozen | Self::Pack(_) => Dimension::Count,
+        }
+    }
+
+    /// Size in the dimension's base unit (g, ml, unit).
+    pub fn factor(&self) -> Decimal {
+        match self {
+            Self::Milligram => Decimal::new(1, 3),
+            Self::Gram | Self::Millilitre | Self::Each => Decimal::ONE,
+            Self::Kilogram | Self::Litre => Decimal::from(1_000),
+            Self::Tonne => Decimal::from(1_000_000),
+            Self::Pound => Decimal::new(45_359_237, 5),
+            Self::Ounce => Decimal::new(28_349_523_125, 9),
+            Self::Dozen => Decimal::from(12),
+            Self::Pack(size) => Decimal::from(*size),
+        }
+    }
+
+    /// Decimal places kept for quantities in this unit.
+    pub fn precision(&self) -> u32 {
+        match self {
+            Self::Milligram | Self::Millilitre | Self::Each => 0,
+            Self::Gram | Self::Ounce => 2,
+            Self::Kilogram | Self::Litre | Self::Pound | Self::Dozen | Self::Pack(_) => 3,
+            Self::Tonne => 6,
+        }
+    }
+
+    pub fn code(&self) -> String {
+        match self {
+            Self::Milligram => "mg".to_string(),
+            Self::Gram => "g".to_string(),
+            Self::Kilogram => "kg".to_string(),
+            Self::Tonne => "t".to_string(),
+            Self::Pound => "lb".to_string(),
+            Self::Ounce => "oz".to_string(),
+            Self::Millilitre => "ml".to_string(),
+            Self::Litre => "l".to_string(),
+            Self::Each => "unit".to_string(),
+            Self::Dozen => "dozen".to_string(),
+            Self::Pack(size) => format!("pack:{}", size),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.code())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = UomError;
+
+    /// Codes plus common English and Spanish spellings, case-insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_ascii_lowercase();
+        if let Some(size) = code.strip_prefix("pack:") {
+            return match size.parse() {
+                Ok(size) if size > 0 => Ok(Self::Pack(size)),
+                _ => Err(UomError::UnknownUnit(s.to_string())),
+            };
+        }
+        Ok(match code.as_str() {
+            "mg" => Self::Milligram,
+            "g" | "gr" | "gram" | "grams" | "gramo" | "gramos" => Self::Gram,
+            "kg" | "kilo" | "kilos" | "kilogram" | "kilograms" | "kilogramo" | "kilogramos" => Self::Kilogram,
+            "t" | "tonne" | "tonelada" | "toneladas" => Self::Tonne,
+            "lb" | "lbs" | "pound" | "pounds" | "libra" | "libras" => Self::Pound,
+            "oz" | "ounce" | "ounces" | "onza" | "onzas" => Self::Ounce,
+            "ml" | "millilitre" | "milliliter" | "mililitro" | "mililitros" => Self::Millilitre,
+            "l" | "lt" | "litre" | "liter" | "litro" | "litros" => Self::Litre,
+            "unit" | "units" | "u" | "un" | "und" | "unidad" | "unidades" | "each" | "ea" => Self::Each,
+            "dozen" | "doz" | "docena" | "docenas" => Self::Dozen,
+            _ => return Err(UomError::UnknownUnit(s.to_string())),
+        })
+    }
+}
+
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for Unit {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <&str as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <&str as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Postgres> for Unit {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(self.code(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for Unit {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+/// An amount in a unit of measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Quantity {
+    #[serde(deserialize_with = "crate::common::decimal_serde::deserialize")]
+    value: Decimal,
+    unit: Unit,
+}
+
+impl Quantity {
+    /// Not rounded; call `round` to apply the unit's precision.
+    pub fn new(value: Decimal, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn zero(unit: Unit) -> Self {
+        Self::new(Decimal::ZERO, unit)
+    }
+
+    pub fn value(&self) -> Decimal {
+        self.value
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value.is_sign_negative() && !self.value.is_zero()
+    }
+
+    /// Round to the unit's precision, half to even.
+    pub fn round(&self) -> Quantity {
+        let value = self.value.round_dp_with_strategy(self.unit.precision(), RoundingStrategy::MidpointNearestEven);
+        Self::new(value, self.unit)
+    }
+
+    /// The same amount in `unit`, rounded to its precision.
+    pub fn convert_to(&self, unit: Unit) -> Result<Quantity, UomError> {
+        if self.unit == unit {
+            return Ok(self.round());
+        }
+        Ok(Self::new(self.exact_in(unit)?, unit).round())
+    }
+
+    /// Value in `unit` without rounding.
+    fn exact_in(&self, unit: Unit) -> Result<Decimal, UomError> {
+        if self.unit.dimension() != unit.dimension() {
+            return Err(UomError::Incompatible(self.unit, unit));
+        }
+        self.value
+            .checked_mul(self.unit.factor())
+            .and_then(|base| base.checked_div(unit.factor()))
+            .ok_or(UomError::Overflow)
+    }
+
+    /// `other`'s value in this quantity's unit, to combine the two.
+    fn operand(&self, other: &Quantity) -> Result<Decimal, UomError> {
+        if self.unit.dimension() != other.unit.dimension() {
+            return Err(UomError::Incompatible(self.unit, other.unit));
+        }
+        other.exact_in(self.unit)
+    }
+
+    /// Sum in this quantity's unit.
+    pub fn checked_add(&self, other: &Quantity) -> Result<Quantity, UomError> {
+        let value = self.value.checked_add(self.operand(other)?).ok_or(UomError::Overflow)?;
+        Ok(Self::new(value, self.unit).round())
+    }
+
+    /// Difference in this quantity's unit.
+    pub fn checked_sub(&self, other: &Quantity) -> Result<Quantity, UomError> {
+        let value = self.value.checked_sub(self.operand(other)?).ok_or(UomError::Overflow)?;
+        Ok(Self::new(value, self.unit).round())
+    }
+
+    /// Scale by a factor (portions, recipe yield), rounded to the unit's precision.
+    pub fn checked_mul(&self, factor: Decimal) -> Result<Quantity, UomError> {
+        let value = self.value.checked_mul(factor).ok_or(UomError::Overflow)?;
+        Ok(Self::new(value, self.unit).round())
+    }
+}
+
+impl PartialOrd for Quantity {
+    /// Quantities of different dimensions are not comparable.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let other = other.exact_in(self.unit).ok()?;
+        Some(self.value.cmp(&other))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit)
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = UomError;
+
+    /// `"1.5 kg"`, `"1.5kg"`, `"250 ml"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value = Decimal::from_str(value).map_err(|_| UomError::InvalidQuantity(s.to_string()))?;
+        Ok(Self::new(value, unit.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qty(s: &str) -> Quantity {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_conversion_and_arithmetic() {
+        assert_eq!(qty("2.5 kg").checked_sub(&qty("180 g")).unwrap(), qty("2.32 kg"));
+        assert_eq!(qty("1.2345 kg").convert_to(Unit::Gram).unwrap(), qty("1234.5 g"));
+        assert_eq!(qty("1 g").convert_to(Unit::Kilogram).unwrap(), qty("0.001 kg"));
+        assert_eq!(qty("0.0005 kg").round(), qty("0 kg"));
+        assert_eq!(qty("750 ml").checked_add(&qty("1.5 l")).unwrap(), qty("2250 ml"));
+        assert_eq!(qty("36 unit").convert_to(Unit::Pack(12)).unwrap(), qty("3 pack:12"));
+        assert_eq!(qty("1 lb").convert_to(Unit::Gram).unwrap(), qty("453.59 g"));
+        assert_eq!(qty("1 kg").checked_add(&qty("1 l")), Err(UomError::Incompatible(Unit::Kilogram, Unit::Litre)));
+        assert!(qty("900 g") < qty("1 kg"));
+        assert_eq!(qty("1 kg").partial_cmp(&qty("1 l")), None);
+    }
+
+    #[test]
+    fn test_serde() {
+        let quantity: Quantity = serde_json::from_str(r#"{"value": 1.5, "unit": "Kilos"}"#).unwrap();
+        assert_eq!(quantity, qty("1.5 kg"));
+        assert_eq!(serde_json::to_value(quantity).unwrap(), serde_json::json!({"value": "1.5", "unit": "kg"}));
+        assert_eq!(serde_json::to_value(Unit::Pack(6)).unwrap(), "pack:6");
+        assert!(serde_json::from_str::<Unit>("\"pack:0\"").is_err());
+    }
+}
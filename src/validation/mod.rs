@@ -0,0 +1,235 @@
+//! Validated Extractors
+//!
+//! `ValidatedJson<T>` and `ValidatedQuery<T>` deserialize like `web::Json` / `web::Query`
+//! and then run `T`'s `validator` rules. Failures are `LanaiError::Validation` responses
+//! listing every invalid field with the violated constraint, instead of an opaque
+//! deserialize error:
+//!
+//! ```ignore
+//! #[derive(Deserialize, Validate)]
+//! struct CreateProduct {
+//!     #[validate(length(min = 1, max = 120))]
+//!     name: String,
+//!     #[validate(range(min = 0))]
+//!     stock: i32,
+//! }
+//!
+//! async fn create(ValidatedJson(body): ValidatedJson<CreateProduct>) -> LanaiResult<HttpResponse> { ... }
+//! ```
+//!
+//! Type errors in the body (a string where a number is expected) are reported for the
+//! offending field as well, with the code `invalid_type`. The body size limit is the one
+//! configured for `web::Bytes` (`web::PayloadConfig`).
+
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde::de::DeserializeOwned;
+use std::ops::{Deref, DerefMut};
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{FieldViolation, LanaiError};
+
+/// JSON body extractor that validates `T`.
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+/// Query string extractor that validates `T`.
+#[derive(Debug)]
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ValidatedQuery<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ValidatedJson<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Deref for ValidatedQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ValidatedQuery<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = LanaiError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let is_json = req.content_type().ends_with("json");
+        let body = web::Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            if !is_json {
+                return Err(LanaiError::BadRequest("Content-Type must be application/json".to_string()));
+            }
+            let body = body.await.map_err(|e| LanaiError::BadRequest(format!("Invalid request body: {}", e)))?;
+
+            let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+            let value: T = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                let path = e.path().to_string();
+                let inner = e.into_inner();
+                if inner.is_data() && path != "." {
+                    LanaiError::Validation(vec![FieldViolation::new(path, "invalid_type").message(inner.to_string())])
+                } else {
+                    LanaiError::BadRequest(format!("Malformed JSON body: {}", inner))
+                }
+            })?;
+
+            value.validate()?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+impl<T: DeserializeOwned + Validate> FromRequest for ValidatedQuery<T> {
+    type Error = LanaiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = web::Query::<T>::from_query(req.query_string())
+            .map_err(|e| LanaiError::BadRequest(format!("Invalid query string: {}", e)))
+            .and_then(|web::Query(value)| {
+                value.validate()?;
+                Ok(ValidatedQuery(value))
+            });
+        ready(result)
+    }
+}
+
+impl From<ValidationErrors> for LanaiError {
+    fn from(errors: ValidationErrors) -> Self {
+        LanaiError::Validation(violations(&errors))
+    }
+}
+
+/// Flatten `validator` errors into one violation per field and constraint, sorted by field.
+/// Nested structs and lists produce paths like `address.city` and `items[2].quantity`.
+pub fn violations(errors: &ValidationErrors) -> Vec<FieldViolation> {
+    let mut violations = Vec::new();
+    collect("", errors, &mut violations);
+    violations.sort_by(|a, b| a.field.cmp(&b.field));
+    violations
+}
+
+fn collect(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldViolation>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() { field.to_string() } else { format!("{}.{}", prefix, field) };
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                for error in field_errors {
+                    let mut violation = FieldViolation::new(path.clone(), error.code.to_string());
+                    if let Some(message) = &error.message {
+                        violation = violation.message(message.to_string());
+                    }
+                    // `value` is the rejected input, which may be a password or other secret.
+                    for (name, value) in error.params.iter().filter(|(name, _)| *name != "value") {
+                        violation = violation.param(name.to_string(), value.clone());
+                    }
+                    out.push(violation);
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => collect(&path, nested, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(&format!("{}[{}]", path, index), nested, out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Line {
+        #[validate(range(min = 1))]
+        quantity: i32,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Order {
+        #[validate(email)]
+        email: String,
+        #[validate(nested)]
+        lines: Vec<Line>,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Page {
+        #[validate(range(max = 100))]
+        limit: u32,
+    }
+
+    async fn extract_json(body: &str) -> Result<ValidatedJson<Order>, LanaiError> {
+        let (req, mut payload) = TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .set_payload(body.to_string())
+            .to_http_parts();
+        ValidatedJson::<Order>::from_request(&req, &mut payload).await
+    }
+
+    #[actix_web::test]
+    async fn test_json_reports_each_invalid_field() {
+        let err = extract_json(r#"{"email": "nope", "lines": [{"quantity": 2}, {"quantity": 0}]}"#).await.unwrap_err();
+        let LanaiError::Validation(violations) = err else { panic!("expected validation error") };
+
+        let fields: Vec<_> = violations.iter().map(|v| (v.field.as_str(), v.code.as_str())).collect();
+        assert_eq!(fields, vec![("email", "email"), ("lines[1].quantity", "range")]);
+        assert!(violations[1].params.contains_key("min"));
+        assert!(!violations[1].params.contains_key("value"));
+
+        let ok = extract_json(r#"{"email": "a@b.co", "lines": [{"quantity": 1}]}"#).await.unwrap();
+        assert_eq!(ok.lines.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_json_type_errors_name_the_field() {
+        let err = extract_json(r#"{"email": "a@b.co", "lines": [{"quantity": "two"}]}"#).await.unwrap_err();
+        let LanaiError::Validation(violations) = err else { panic!("expected validation error") };
+        assert_eq!(violations[0].field, "lines[0].quantity");
+        assert_eq!(violations[0].code, "invalid_type");
+
+        assert!(matches!(extract_json("{not json").await, Err(LanaiError::BadRequest(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_query_is_validated() {
+        let req = TestRequest::get().uri("/?limit=500").to_http_request();
+        let err = ValidatedQuery::<Page>::extract(&req).await.unwrap_err();
+        assert!(matches!(err, LanaiError::Validation(ref v) if v[0].field == "limit"));
+
+        let req = TestRequest::get().uri("/?limit=50").to_http_request();
+        assert_eq!(ValidatedQuery::<Page>::extract(&req).await.unwrap().limit, 50);
+    }
+}
@@ -0,0 +1,206 @@
+//! Tenant-facing webhook management endpoints
+//!
+//! Routes (scoped to the caller's organization via `TenantContext`; mount behind `AuthGuard`):
+//! - `GET /webhooks/endpoints` — registered endpoints (secrets omitted)
+//! - `POST /webhooks/endpoints` — register one (body: `{"url": "...", "events": ["order.*"]}`);
+//!   the response is the only time the signing secret is returned
+//! - `DELETE /webhooks/endpoints/{id}` — remove one
+//! - `GET /webhooks/endpoints/{id}/attempts?limit=50` — recent delivery attempts
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{WebhookEndpoint, WebhookError, WebhookStore};
+use crate::http_client::EgressPolicy;
+use crate::middleware::tenant_context::TenantContext;
+
+#[derive(Clone)]
+pub struct WebhookAdmin {
+    store: Arc<dyn WebhookStore>,
+    egress: EgressPolicy,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateEndpoint {
+    url: String,
+    events: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttemptsQuery {
+    limit: Option<usize>,
+}
+
+/// Endpoint as shown by the API, without its secret.
+#[derive(Debug, Serialize)]
+struct EndpointView {
+    id: Uuid,
+    url: String,
+    events: Vec<String>,
+    active: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<&WebhookEndpoint> for EndpointView {
+    fn from(endpoint: &WebhookEndpoint) -> Self {
+        Self {
+            id: endpoint.id,
+            url: endpoint.url.clone(),
+            events: endpoint.events.clone(),
+            active: endpoint.active,
+            created_at: endpoint.created_at,
+        }
+    }
+}
+
+impl WebhookAdmin {
+    /// Receiver URLs are checked against `EgressPolicy::from_env()`.
+    pub fn new(store: Arc<dyn WebhookStore>) -> Self {
+        Self { store, egress: EgressPolicy::from_env() }
+    }
+
+    /// Use the same policy as the `WebhookDispatcher`.
+    pub fn egress_policy(mut self, policy: EgressPolicy) -> Self {
+        self.egress = policy;
+        self
+    }
+
+    /// Mount the routes under `/webhooks/endpoints`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone())).service(
+            web::scope("/webhooks/endpoints")
+                .route("", web::get().to(list_endpoints))
+                .route("", web::post().to(create_endpoint))
+                .route("/{id}", web::delete().to(delete_endpoint))
+                .route("/{id}/attempts", web::get().to(list_attempts)),
+        );
+    }
+
+    /// Load `id` if it belongs to the tenant.
+    async fn owned(&self, tenant: &TenantContext, id: Uuid) -> Result<Option<WebhookEndpoint>, WebhookError> {
+        Ok(self.store.endpoint(id).await?.filter(|e| e.org_id == tenant.org_id))
+    }
+}
+
+/// Receivers must be absolute http(s) URLs the egress policy allows.
+pub(crate) fn validate_url(url: &str, egress: &EgressPolicy) -> Result<(), WebhookError> {
+    match url.split_once("://") {
+        Some(("https" | "http", rest)) if !rest.is_empty() && !rest.starts_with('/') => {}
+        _ => return Err(WebhookError::InvalidEndpoint(format!("'{}' is not an http(s) URL", url))),
+    }
+    egress.check_url(url).map(|_| ()).map_err(|e| WebhookError::InvalidEndpoint(e.to_string()))
+}
+
+fn internal_error(e: WebhookError) -> HttpResponse {
+    error!("❌ Webhook admin request failed: {}", e);
+    HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() }))
+}
+
+async fn list_endpoints(admin: web::Data<WebhookAdmin>, tenant: TenantContext) -> HttpResponse {
+    match admin.store.endpoints_for(tenant.org_id).await {
+        Ok(endpoints) => HttpResponse::Ok().json(endpoints.iter().map(EndpointView::from).collect::<Vec<_>>()),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn create_endpoint(
+    admin: web::Data<WebhookAdmin>,
+    tenant: TenantContext,
+    body: web::Json<CreateEndpoint>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    if let Err(e) = validate_url(&body.url, &admin.egress) {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }));
+    }
+    if body.events.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "at least one event type is required" }));
+    }
+
+    let endpoint = WebhookEndpoint::new(tenant.org_id, &body.url, body.events);
+    match admin.store.save_endpoint(&endpoint).await {
+        Ok(()) => HttpResponse::Created().json(serde_json::json!({
+            "endpoint": EndpointView::from(&endpoint),
+            "secret": endpoint.secret,
+        })),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn delete_endpoint(admin: web::Data<WebhookAdmin>, tenant: TenantContext, path: web::Path<Uuid>) -> HttpResponse {
+    let id = path.into_inner();
+    match admin.owned(&tenant, id).await {
+        Ok(Some(_)) => match admin.store.delete_endpoint(id).await {
+            Ok(_) => HttpResponse::NoContent().finish(),
+            Err(e) => internal_error(e),
+        },
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn list_attempts(
+    admin: web::Data<WebhookAdmin>,
+    tenant: TenantContext,
+    path: web::Path<Uuid>,
+    query: web::Query<AttemptsQuery>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    match admin.owned(&tenant, id).await {
+        Ok(Some(_)) => match admin.store.attempts(id, query.limit.unwrap_or(50).min(500)).await {
+            Ok(attempts) => HttpResponse::Ok().json(attempts),
+            Err(e) => internal_error(e),
+        },
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::InMemoryWebhookStore;
+    use actix_web::test::{self as actix_test, TestRequest};
+    use actix_web::{App, HttpMessage};
+
+    #[test]
+    fn test_validate_url() {
+        let egress = EgressPolicy::new();
+        assert!(validate_url("https://hooks.example.com/lanai", &egress).is_ok());
+        assert!(validate_url("ftp://example.com", &egress).is_err());
+        assert!(validate_url("https://", &egress).is_err());
+        assert!(validate_url("http://169.254.169.254/latest/meta-data/", &egress).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_endpoints_are_tenant_scoped() {
+        let store: Arc<dyn WebhookStore> = Arc::new(InMemoryWebhookStore::new());
+        let other = WebhookEndpoint::new(Uuid::new_v4(), "https://other.test/hook", vec!["*".to_string()]);
+        store.save_endpoint(&other).await.unwrap();
+
+        let admin = WebhookAdmin::new(store.clone());
+        let app = actix_test::init_service(App::new().configure(|cfg| admin.configure(cfg))).await;
+        let org_id = Uuid::new_v4();
+
+        let request = TestRequest::post()
+            .uri("/webhooks/endpoints")
+            .set_json(serde_json::json!({ "url": "https://mine.test/hook", "events": ["order.*"] }))
+            .to_request();
+        request.extensions_mut().insert(TenantContext { org_id });
+        let created: serde_json::Value = actix_test::call_and_read_body_json(&app, request).await;
+        assert!(created["secret"].as_str().unwrap().starts_with("whsec_"));
+
+        let request = TestRequest::get().uri("/webhooks/endpoints").to_request();
+        request.extensions_mut().insert(TenantContext { org_id });
+        let listed: serde_json::Value = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert!(listed[0].get("secret").is_none());
+
+        let request = TestRequest::delete().uri(&format!("/webhooks/endpoints/{}", other.id)).to_request();
+        request.extensions_mut().insert(TenantContext { org_id });
+        assert_eq!(actix_test::call_service(&app, request).await.status(), 404);
+    }
+}
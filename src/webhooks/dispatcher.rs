@@ -0,0 +1,263 @@
+//! Fan-out and signed delivery of webhook events
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::signing::{self, EVENT_ID_HEADER, EVENT_TYPE_HEADER, SIGNATURE_HEADER};
+use super::{DeliveryAttempt, WebhookError, WebhookEvent, WebhookStore};
+use crate::http_client::EgressPolicy;
+use crate::jobs::{Job, JobQueue, WorkerPool};
+use crate::resilience::{CircuitBreaker, CircuitBreakerOutcome};
+use crate::templates::TemplateEngine;
+
+/// Job payload: deliver `event` to one endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    endpoint_id: Uuid,
+    event: WebhookEvent,
+}
+
+impl Job for Delivery {
+    const NAME: &'static str = "lanai.webhook.delivery";
+}
+
+#[derive(Debug)]
+struct DeliveryFailure {
+    status: Option<u16>,
+    message: String,
+}
+
+impl fmt::Display for DeliveryFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn delivery_client(egress: &EgressPolicy) -> Result<reqwest::Client, WebhookError> {
+    egress.client(Duration::from_secs(10)).map_err(|e| WebhookError::Client(e.to_string()))
+}
+
+pub struct WebhookDispatcher {
+    store: Arc<dyn WebhookStore>,
+    queue: JobQueue,
+    client: reqwest::Client,
+    egress: EgressPolicy,
+    breakers: Mutex<HashMap<Uuid, Arc<CircuitBreaker>>>,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    templates: Option<TemplateEngine>,
+}
+
+impl WebhookDispatcher {
+    /// 10s delivery timeout; an endpoint's breaker opens after 5 consecutive failures for 60s.
+    /// Deliveries follow `EgressPolicy::from_env()`.
+    pub fn new(store: Arc<dyn WebhookStore>, queue: JobQueue) -> Result<Self, WebhookError> {
+        let egress = EgressPolicy::from_env();
+        Ok(Self {
+            store,
+            queue,
+            client: delivery_client(&egress)?,
+            egress,
+            breakers: Mutex::new(HashMap::new()),
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(60),
+            templates: None,
+        })
+    }
+
+    /// Restrict where deliveries may go (e.g. an allowlist of receiver hosts).
+    pub fn with_egress_policy(mut self, policy: EgressPolicy) -> Result<Self, WebhookError> {
+        self.client = delivery_client(&policy)?;
+        self.egress = policy;
+        Ok(self)
+    }
+
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.failure_threshold = failure_threshold;
+        self.reset_timeout = reset_timeout;
+        self
+    }
+
+    /// Render event data through `webhook/{event_type}` templates of `engine` where they exist
+    /// (rendered as JSON with the data as variables); other events are delivered unchanged.
+    pub fn with_templates(mut self, engine: TemplateEngine) -> Self {
+        self.templates = Some(engine);
+        self
+    }
+
+    /// Queue `data` as an `event_type` event for every active endpoint of `org_id` subscribed
+    /// to it. Returns the event ID.
+    pub async fn dispatch(&self, org_id: Uuid, event_type: &str, data: serde_json::Value) -> Result<Uuid, WebhookError> {
+        let data = match &self.templates {
+            Some(engine) => {
+                let key = format!("webhook/{}", event_type);
+                engine.render_json(Some(org_id), &key, &data).await?.unwrap_or(data)
+            }
+            None => data,
+        };
+        let event = WebhookEvent { id: Uuid::new_v4(), event_type: event_type.to_string(), created_at: Utc::now(), data };
+
+        let endpoints = self.store.endpoints_for(org_id).await?;
+        let mut queued = 0;
+        for endpoint in endpoints.iter().filter(|e| e.active && e.subscribes_to(event_type)) {
+            self.queue.enqueue(&Delivery { endpoint_id: endpoint.id, event: event.clone() }).await?;
+            queued += 1;
+        }
+
+        info!("🪝 Event '{}' ({}) queued for {} webhook endpoints", event_type, event.id, queued);
+        Ok(event.id)
+    }
+
+    /// Handle delivery jobs on `pool`; failed deliveries follow the pool's retry policy.
+    pub fn register(self: Arc<Self>, pool: WorkerPool) -> WorkerPool {
+        pool.register(move |delivery: Delivery| {
+            let dispatcher = self.clone();
+            async move { dispatcher.deliver(delivery).await }
+        })
+    }
+
+    fn breaker(&self, endpoint_id: Uuid) -> Arc<CircuitBreaker> {
+        self.breakers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(endpoint_id)
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(self.failure_threshold, self.reset_timeout)))
+            .clone()
+    }
+
+    async fn deliver(&self, delivery: Delivery) -> Result<(), String> {
+        let endpoint = match self.store.endpoint(delivery.endpoint_id).await.map_err(|e| e.to_string())? {
+            Some(endpoint) if endpoint.active => endpoint,
+            _ => {
+                info!("⏭️ Dropping delivery of {} to removed or disabled endpoint {}", delivery.event.id, delivery.endpoint_id);
+                return Ok(());
+            }
+        };
+
+        // Endpoints registered before a policy change, or through another path, are checked again.
+        if let Err(e) = self.egress.check_url(&endpoint.url) {
+            warn!("🚫 Not delivering {} to endpoint {}: {}", delivery.event.id, endpoint.id, e);
+            return Ok(());
+        }
+
+        let body = serde_json::to_vec(&delivery.event).map_err(|e| e.to_string())?;
+        let signature = signing::sign(&endpoint.secret, Utc::now().timestamp(), &body);
+        let started = Instant::now();
+
+        let outcome = self
+            .breaker(endpoint.id)
+            .call(|| async {
+                let response = self
+                    .client
+                    .post(&endpoint.url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, signature.as_str())
+                    .header(EVENT_ID_HEADER, delivery.event.id.to_string())
+                    .header(EVENT_TYPE_HEADER, delivery.event.event_type.as_str())
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .map_err(|e| DeliveryFailure { status: None, message: e.to_string() })?;
+
+                let status = response.status();
+                if status.is_success() {
+                    Ok(status.as_u16())
+                } else {
+                    Err(DeliveryFailure { status: Some(status.as_u16()), message: format!("HTTP {}", status) })
+                }
+            })
+            .await;
+
+        let (status_code, error) = match &outcome {
+            Ok(status) => (Some(*status), None),
+            Err(CircuitBreakerOutcome::CircuitOpen) => (None, Some("circuit breaker open".to_string())),
+            Err(CircuitBreakerOutcome::OperationError(failure)) => (failure.status, Some(failure.message.clone())),
+        };
+
+        let attempt = DeliveryAttempt {
+            id: Uuid::new_v4(),
+            endpoint_id: endpoint.id,
+            event_id: delivery.event.id,
+            event_type: delivery.event.event_type.clone(),
+            attempted_at: Utc::now(),
+            status_code,
+            success: error.is_none(),
+            error: error.clone(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        if let Err(e) = self.store.record_attempt(&attempt).await {
+            warn!("⚠️ Failed to record webhook delivery attempt: {}", e);
+        }
+
+        // 410 Gone: the receiver asks us to stop sending.
+        if status_code == Some(410) {
+            warn!("🪝 Endpoint {} returned 410 Gone, disabling it", endpoint.id);
+            let disabled = super::WebhookEndpoint { active: false, ..endpoint };
+            self.store.save_endpoint(&disabled).await.map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+
+        match error {
+            None => Ok(()),
+            Some(error) => Err(format!("delivery to {} failed: {}", endpoint.url, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::{InMemoryWebhookStore, WebhookEndpoint};
+
+    #[test]
+    fn test_delivery_job_round_trip() {
+        let delivery = Delivery {
+            endpoint_id: Uuid::new_v4(),
+            event: WebhookEvent {
+                id: Uuid::new_v4(),
+                event_type: "order.paid".to_string(),
+                created_at: Utc::now(),
+                data: serde_json::json!({ "order_id": 7 }),
+            },
+        };
+        let json = serde_json::to_value(&delivery).unwrap();
+        assert_eq!(json["event"]["type"], "order.paid");
+        let decoded: Delivery = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.event.id, delivery.event.id);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_listed_newest_first() {
+        let store = InMemoryWebhookStore::new();
+        let endpoint = WebhookEndpoint::new(Uuid::new_v4(), "https://a.test/hook", vec!["*".to_string()]);
+        store.save_endpoint(&endpoint).await.unwrap();
+
+        for status in [500u16, 502, 200] {
+            let attempt = DeliveryAttempt {
+                id: Uuid::new_v4(),
+                endpoint_id: endpoint.id,
+                event_id: Uuid::new_v4(),
+                event_type: "order.paid".to_string(),
+                attempted_at: Utc::now(),
+                status_code: Some(status),
+                success: status == 200,
+                error: None,
+                duration_ms: 1,
+            };
+            store.record_attempt(&attempt).await.unwrap();
+        }
+
+        let attempts = store.attempts(endpoint.id, 2).await.unwrap();
+        let statuses: Vec<_> = attempts.iter().map(|a| a.status_code.unwrap()).collect();
+        assert_eq!(statuses, vec![200, 502]);
+
+        assert!(store.delete_endpoint(endpoint.id).await.unwrap());
+        assert!(store.attempts(endpoint.id, 10).await.unwrap().is_empty());
+    }
+}
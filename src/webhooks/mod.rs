@@ -0,0 +1,148 @@
+//! Outbound Webhooks
+//!
+//! Tenants register endpoints for event types; `WebhookDispatcher::dispatch` fans an event
+//! out to every matching endpoint as one job per delivery, so retries with exponential
+//! backoff and the dead-letter stream come from the jobs subsystem. Each delivery is
+//! signed with the endpoint's secret (see `signing`), guarded by a per-endpoint circuit
+//! breaker, and recorded as a `DeliveryAttempt` for the admin API. Receiver URLs are
+//! tenant-supplied, so registration and delivery both follow an `http_client::EgressPolicy`.
+//! With `with_templates`, a tenant's `webhook/{event_type}` template reshapes the event data.
+//!
+//! For receiving, `middleware::webhook_signature::WebhookSignatureMiddleware` verifies the
+//! same signature format and rejects replays via a `ReplayCache`.
+//!
+//! ```ignore
+//! let store = Arc::new(RedisWebhookStore::new(shared_connection().await?));
+//! let queue = JobQueue::new(shared_connection().await?, "webhooks");
+//! let dispatcher = Arc::new(WebhookDispatcher::new(store.clone(), queue.clone())?);
+//! dispatcher.clone().register(WorkerPool::new(queue).concurrency(16)).start().await?;
+//!
+//! dispatcher.dispatch(tenant.org_id, "order.paid", json!({ "order_id": id })).await?;
+//!
+//! ServerBuilder::new("lanai-orders").run(move |cfg| WebhookAdmin::new(store.clone()).configure(cfg)).await
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod admin;
+pub mod dispatcher;
+pub mod replay;
+pub mod signing;
+pub mod store;
+
+pub use admin::WebhookAdmin;
+pub use dispatcher::WebhookDispatcher;
+pub use replay::{InMemoryReplayCache, RedisReplayCache, ReplayCache};
+pub use store::{InMemoryWebhookStore, RedisWebhookStore, WebhookStore};
+
+use crate::jobs::JobError;
+use crate::templates::TemplateError;
+
+/// A tenant's registered receiver.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub url: String,
+    /// Event types to deliver: exact (`order.paid`), prefix (`order.*`) or `*`.
+    pub events: Vec<String>,
+    pub secret: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookEndpoint {
+    /// New active endpoint with a freshly generated secret.
+    pub fn new(org_id: Uuid, url: &str, events: Vec<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            org_id,
+            url: url.to_string(),
+            events,
+            secret: signing::generate_secret(),
+            active: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn subscribes_to(&self, event_type: &str) -> bool {
+        self.events.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => event_type.starts_with(prefix),
+            None => pattern == event_type,
+        })
+    }
+}
+
+/// Body delivered to endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub created_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+/// Outcome of one HTTP delivery attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryAttempt {
+    pub id: Uuid,
+    pub endpoint_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub attempted_at: DateTime<Utc>,
+    /// HTTP status, if a response was received.
+    pub status_code: Option<u16>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Webhook subsystem error types
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("Webhook endpoint not found: {0}")]
+    NotFound(Uuid),
+
+    #[error("Invalid webhook endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("Webhook store error: {0}")]
+    Store(String),
+
+    #[error("Failed to serialize webhook data: {0}")]
+    Serialization(String),
+
+    #[error("Failed to render webhook payload: {0}")]
+    Template(#[from] TemplateError),
+
+    #[error("Failed to enqueue delivery: {0}")]
+    Queue(#[from] JobError),
+
+    #[error("Failed to build the delivery HTTP client: {0}")]
+    Client(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_matching() {
+        let endpoint = WebhookEndpoint::new(
+            Uuid::new_v4(),
+            "https://example.com/hooks",
+            vec!["order.*".to_string(), "invoice.paid".to_string()],
+        );
+        assert!(endpoint.subscribes_to("order.created"));
+        assert!(endpoint.subscribes_to("invoice.paid"));
+        assert!(!endpoint.subscribes_to("invoice.voided"));
+        assert!(endpoint.secret.starts_with("whsec_"));
+
+        let all = WebhookEndpoint { events: vec!["*".to_string()], ..endpoint };
+        assert!(all.subscribes_to("anything.at_all"));
+    }
+}
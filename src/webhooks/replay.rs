@@ -0,0 +1,102 @@
+//! Replay protection for inbound webhooks
+//!
+//! A verified signature is remembered for as long as its timestamp would still be accepted,
+//! so a captured delivery cannot be re-sent within the tolerance window. Senders that retry
+//! re-sign with a fresh timestamp, so legitimate retries are not affected.
+
+use async_trait::async_trait;
+use log::error;
+use redis::aio::ConnectionManager;
+use std::time::{Duration, Instant};
+
+#[async_trait]
+pub trait ReplayCache: Send + Sync {
+    /// Remember `key` for `ttl`. Returns false if it was already seen.
+    async fn first_seen(&self, key: &str, ttl: Duration) -> bool;
+}
+
+struct PerEntryTtl;
+
+impl moka::Expiry<String, Duration> for PerEntryTtl {
+    fn expire_after_create(&self, _key: &String, ttl: &Duration, _created_at: Instant) -> Option<Duration> {
+        Some(*ttl)
+    }
+}
+
+/// In-process replay cache; only protects a single instance.
+pub struct InMemoryReplayCache {
+    seen: moka::future::Cache<String, Duration>,
+}
+
+impl InMemoryReplayCache {
+    /// Remembers up to 100,000 signatures.
+    pub fn new() -> Self {
+        Self::with_capacity(100_000)
+    }
+
+    pub fn with_capacity(max_entries: u64) -> Self {
+        Self { seen: moka::future::Cache::builder().max_capacity(max_entries).expire_after(PerEntryTtl).build() }
+    }
+}
+
+impl Default for InMemoryReplayCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ReplayCache for InMemoryReplayCache {
+    async fn first_seen(&self, key: &str, ttl: Duration) -> bool {
+        self.seen.entry(key.to_string()).or_insert(ttl).await.is_fresh()
+    }
+}
+
+/// Redis-backed replay cache shared by all replicas (`SET NX EX` under `lanai:webhooks:seen:`).
+///
+/// Fails open if Redis is unavailable: the timestamp tolerance still bounds replays.
+pub struct RedisReplayCache {
+    conn: ConnectionManager,
+}
+
+impl RedisReplayCache {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl ReplayCache for RedisReplayCache {
+    async fn first_seen(&self, key: &str, ttl: Duration) -> bool {
+        let result: Result<Option<String>, _> = redis::cmd("SET")
+            .arg(format!("lanai:webhooks:seen:{}", key))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query_async(&mut self.conn.clone())
+            .await;
+
+        match result {
+            Ok(reply) => reply.is_some(),
+            Err(e) => {
+                error!("❌ Replay cache unavailable, accepting webhook: {}", e);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_detects_replays() {
+        let cache = InMemoryReplayCache::new();
+        let ttl = Duration::from_secs(60);
+        assert!(cache.first_seen("t=1,v1=aa", ttl).await);
+        assert!(!cache.first_seen("t=1,v1=aa", ttl).await);
+        assert!(cache.first_seen("t=2,v1=bb", ttl).await);
+    }
+}
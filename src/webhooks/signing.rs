@@ -0,0 +1,146 @@
+//! Webhook payload signatures
+//!
+//! Signature header format (Stripe-style): `t=<unix seconds>,v1=<hex HMAC-SHA256>`, where
+//! the HMAC covers `"{t}.{raw body}"` with the endpoint's secret. Including the timestamp
+//! lets receivers reject old deliveries, which bounds replay attacks.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+use thiserror::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "Lanai-Signature";
+pub const EVENT_ID_HEADER: &str = "Lanai-Event-Id";
+pub const EVENT_TYPE_HEADER: &str = "Lanai-Event-Type";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("Malformed signature header")]
+    Malformed,
+
+    #[error("Signature timestamp is outside the {0:?} tolerance")]
+    Expired(Duration),
+
+    #[error("Signature does not match")]
+    Mismatch,
+}
+
+/// Generate a new endpoint secret (`whsec_` + 32 random bytes, hex encoded).
+pub fn generate_secret() -> String {
+    format!("whsec_{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`.
+pub fn compute(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    hex::encode(mac(secret, timestamp, body).finalize().into_bytes())
+}
+
+/// Signature header value for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    format!("t={},v1={}", timestamp, compute(secret, timestamp, body))
+}
+
+/// What a valid signature header signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    pub timestamp: i64,
+    /// The `v1` signature that matched, as lowercase hex.
+    pub signature: String,
+}
+
+impl Verified {
+    /// Identifies the delivery however its header was formatted, for replay caches.
+    pub fn replay_key(&self) -> String {
+        format!("t={},v1={}", self.timestamp, self.signature)
+    }
+}
+
+/// Check `header` against `body`, accepting timestamps within `tolerance` of `now`.
+/// Several `v1` entries are allowed (secret rotation).
+pub fn verify(
+    secret: &str,
+    header: &str,
+    body: &[u8],
+    tolerance: Duration,
+    now: i64,
+) -> Result<Verified, SignatureError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or(SignatureError::Malformed)?;
+    if signatures.is_empty() {
+        return Err(SignatureError::Malformed);
+    }
+    if now.abs_diff(timestamp) > tolerance.as_secs() {
+        return Err(SignatureError::Expired(tolerance));
+    }
+
+    signatures
+        .into_iter()
+        .filter_map(|signature| hex::decode(signature).ok())
+        .find(|expected| mac(secret, timestamp, body).verify_slice(expected).is_ok())
+        .map(|matched| Verified { timestamp, signature: hex::encode(matched) })
+        .ok_or(SignatureError::Mismatch)
+}
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "whsec_test";
+    const TOLERANCE: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn test_sign_and_verify() {
+        let header = sign(SECRET, 1_700_000_000, b"{\"id\":1}");
+        let verified = verify(SECRET, &header, b"{\"id\":1}", TOLERANCE, 1_700_000_100).unwrap();
+        assert_eq!(verified.timestamp, 1_700_000_000);
+        assert_eq!(verified.replay_key(), header);
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_stale_signatures() {
+        let header = sign(SECRET, 1_700_000_000, b"{\"amount\":10}");
+        assert_eq!(
+            verify(SECRET, &header, b"{\"amount\":1000}", TOLERANCE, 1_700_000_000),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify("whsec_other", &header, b"{\"amount\":10}", TOLERANCE, 1_700_000_000),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify(SECRET, &header, b"{\"amount\":10}", TOLERANCE, 1_700_001_000),
+            Err(SignatureError::Expired(TOLERANCE))
+        );
+        assert_eq!(verify(SECRET, "v1=abc", b"", TOLERANCE, 0), Err(SignatureError::Malformed));
+    }
+
+    #[test]
+    fn test_accepts_any_of_several_signatures() {
+        let header = format!("{},v1={}", sign("whsec_old", 10, b"x"), compute(SECRET, 10, b"x"));
+        let verified = verify(SECRET, &header, b"x", TOLERANCE, 10).unwrap();
+        assert_eq!(verified.replay_key(), sign(SECRET, 10, b"x"));
+
+        // Reformatting the header does not change what it signed.
+        let reformatted = format!("t=10, v0=x, v1={}", compute(SECRET, 10, b"x").to_uppercase());
+        assert_eq!(verify(SECRET, &reformatted, b"x", TOLERANCE, 10), Ok(verified));
+    }
+}
@@ -0,0 +1,216 @@
+//! Storage for webhook endpoints and delivery attempts
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{DeliveryAttempt, WebhookEndpoint, WebhookError};
+
+/// Attempts kept per endpoint; older ones are discarded.
+pub const MAX_ATTEMPTS_KEPT: usize = 1000;
+
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    /// Insert or overwrite an endpoint.
+    async fn save_endpoint(&self, endpoint: &WebhookEndpoint) -> Result<(), WebhookError>;
+
+    async fn endpoint(&self, id: Uuid) -> Result<Option<WebhookEndpoint>, WebhookError>;
+
+    async fn endpoints_for(&self, org_id: Uuid) -> Result<Vec<WebhookEndpoint>, WebhookError>;
+
+    /// Returns false if the endpoint did not exist.
+    async fn delete_endpoint(&self, id: Uuid) -> Result<bool, WebhookError>;
+
+    async fn record_attempt(&self, attempt: &DeliveryAttempt) -> Result<(), WebhookError>;
+
+    /// Most recent attempts for an endpoint, newest first.
+    async fn attempts(&self, endpoint_id: Uuid, limit: usize) -> Result<Vec<DeliveryAttempt>, WebhookError>;
+}
+
+/// In-memory webhook store (for tests and single-instance development).
+#[derive(Default)]
+pub struct InMemoryWebhookStore {
+    endpoints: RwLock<HashMap<Uuid, WebhookEndpoint>>,
+    attempts: RwLock<HashMap<Uuid, Vec<DeliveryAttempt>>>,
+}
+
+impl InMemoryWebhookStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebhookStore for InMemoryWebhookStore {
+    async fn save_endpoint(&self, endpoint: &WebhookEndpoint) -> Result<(), WebhookError> {
+        self.endpoints.write().await.insert(endpoint.id, endpoint.clone());
+        Ok(())
+    }
+
+    async fn endpoint(&self, id: Uuid) -> Result<Option<WebhookEndpoint>, WebhookError> {
+        Ok(self.endpoints.read().await.get(&id).cloned())
+    }
+
+    async fn endpoints_for(&self, org_id: Uuid) -> Result<Vec<WebhookEndpoint>, WebhookError> {
+        Ok(self.endpoints.read().await.values().filter(|e| e.org_id == org_id).cloned().collect())
+    }
+
+    async fn delete_endpoint(&self, id: Uuid) -> Result<bool, WebhookError> {
+        self.attempts.write().await.remove(&id);
+        Ok(self.endpoints.write().await.remove(&id).is_some())
+    }
+
+    async fn record_attempt(&self, attempt: &DeliveryAttempt) -> Result<(), WebhookError> {
+        let mut attempts = self.attempts.write().await;
+        let list = attempts.entry(attempt.endpoint_id).or_default();
+        list.insert(0, attempt.clone());
+        list.truncate(MAX_ATTEMPTS_KEPT);
+        Ok(())
+    }
+
+    async fn attempts(&self, endpoint_id: Uuid, limit: usize) -> Result<Vec<DeliveryAttempt>, WebhookError> {
+        Ok(self
+            .attempts
+            .read()
+            .await
+            .get(&endpoint_id)
+            .map(|list| list.iter().take(limit).cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+/// Redis-backed webhook store.
+///
+/// Endpoints are JSON under `lanai:webhooks:endpoint:{id}`, indexed per organization in the
+/// set `lanai:webhooks:org:{org_id}`; attempts are a capped list `lanai:webhooks:attempts:{id}`.
+pub struct RedisWebhookStore {
+    conn: ConnectionManager,
+}
+
+impl RedisWebhookStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    fn endpoint_key(id: Uuid) -> String {
+        format!("lanai:webhooks:endpoint:{}", id)
+    }
+
+    fn org_key(org_id: Uuid) -> String {
+        format!("lanai:webhooks:org:{}", org_id)
+    }
+
+    fn attempts_key(endpoint_id: Uuid) -> String {
+        format!("lanai:webhooks:attempts:{}", endpoint_id)
+    }
+}
+
+fn store_error(e: redis::RedisError) -> WebhookError {
+    WebhookError::Store(e.to_string())
+}
+
+fn decode<T: serde::de::DeserializeOwned>(payload: &str) -> Result<T, WebhookError> {
+    serde_json::from_str(payload).map_err(|e| WebhookError::Serialization(e.to_string()))
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<String, WebhookError> {
+    serde_json::to_string(value).map_err(|e| WebhookError::Serialization(e.to_string()))
+}
+
+#[async_trait]
+impl WebhookStore for RedisWebhookStore {
+    async fn save_endpoint(&self, endpoint: &WebhookEndpoint) -> Result<(), WebhookError> {
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(Self::endpoint_key(endpoint.id))
+            .arg(encode(endpoint)?)
+            .ignore()
+            .cmd("SADD")
+            .arg(Self::org_key(endpoint.org_id))
+            .arg(endpoint.id.to_string())
+            .ignore()
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)
+    }
+
+    async fn endpoint(&self, id: Uuid) -> Result<Option<WebhookEndpoint>, WebhookError> {
+        let payload: Option<String> = redis::cmd("GET")
+            .arg(Self::endpoint_key(id))
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        payload.as_deref().map(decode).transpose()
+    }
+
+    async fn endpoints_for(&self, org_id: Uuid) -> Result<Vec<WebhookEndpoint>, WebhookError> {
+        let ids: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(Self::org_key(org_id))
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+
+        let mut endpoints = Vec::with_capacity(ids.len());
+        for id in ids {
+            let Ok(id) = Uuid::parse_str(&id) else { continue };
+            if let Some(endpoint) = self.endpoint(id).await? {
+                endpoints.push(endpoint);
+            }
+        }
+        Ok(endpoints)
+    }
+
+    async fn delete_endpoint(&self, id: Uuid) -> Result<bool, WebhookError> {
+        let Some(endpoint) = self.endpoint(id).await? else {
+            return Ok(false);
+        };
+        redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(Self::endpoint_key(id))
+            .arg(Self::attempts_key(id))
+            .ignore()
+            .cmd("SREM")
+            .arg(Self::org_key(endpoint.org_id))
+            .arg(id.to_string())
+            .ignore()
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        Ok(true)
+    }
+
+    async fn record_attempt(&self, attempt: &DeliveryAttempt) -> Result<(), WebhookError> {
+        let key = Self::attempts_key(attempt.endpoint_id);
+        redis::pipe()
+            .cmd("LPUSH")
+            .arg(&key)
+            .arg(encode(attempt)?)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&key)
+            .arg(0)
+            .arg(MAX_ATTEMPTS_KEPT - 1)
+            .ignore()
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await
+            .map_err(store_error)
+    }
+
+    async fn attempts(&self, endpoint_id: Uuid, limit: usize) -> Result<Vec<DeliveryAttempt>, WebhookError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let payloads: Vec<String> = redis::cmd("LRANGE")
+            .arg(Self::attempts_key(endpoint_id))
+            .arg(0)
+            .arg(limit - 1)
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(store_error)?;
+        payloads.iter().map(|p| decode(p)).collect()
+    }
+}
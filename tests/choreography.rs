@@ -0,0 +1,137 @@
+//! `Choreography` reactions driven through the embedded broker.
+
+use futures_util::StreamExt;
+use lanai_infrastructure::messaging::events::LanaiEvent;
+use lanai_infrastructure::messaging::{EventEnvelope, NatsClient, CORRELATION_ID_HEADER};
+use lanai_infrastructure::saga::choreography::ReactionFailedEvent;
+use lanai_infrastructure::saga::{Choreography, Reaction};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OrderPlaced {
+    order_id: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StockReserved {
+    flow: String,
+    order_id: u32,
+}
+
+impl LanaiEvent for StockReserved {
+    fn subject(&self) -> String {
+        format!("test.choreography.{}.reserved", self.flow)
+    }
+}
+
+async fn place_order(subject: &str, order_id: u32, correlation_id: Uuid) {
+    let mut headers = async_nats::HeaderMap::new();
+    headers.insert(CORRELATION_ID_HEADER, correlation_id.to_string().as_str());
+    NatsClient::publish_event_with_headers(subject, &OrderPlaced { order_id }, headers).await.unwrap();
+}
+
+/// Reacts to `test.choreography.{flow}.placed` and emits `test.choreography.{flow}.reserved`.
+fn reserve_stock(flow: &'static str) -> Reaction {
+    Reaction::on("reserve_stock", &format!("test.choreography.{}.placed", flow)).emit(move |_ctx, order: OrderPlaced| async move {
+        Ok::<_, String>(StockReserved { flow: flow.to_string(), order_id: order.order_id })
+    })
+}
+
+#[tokio::test]
+async fn test_reaction_emits_with_propagated_correlation() {
+    NatsClient::init_embedded();
+    let mut reserved = NatsClient::subscribe("test.choreography.dispatch.reserved").await.unwrap();
+    let handle = Choreography::new("dispatch")
+        .reaction(reserve_stock("dispatch"))
+        .start()
+        .await
+        .unwrap();
+
+    let correlation_id = Uuid::new_v4();
+    place_order("test.choreography.dispatch.placed", 7, correlation_id).await;
+
+    let message = timeout(Duration::from_secs(5), reserved.next()).await.unwrap().unwrap();
+    let header = message.headers.as_ref().and_then(|headers| headers.get(CORRELATION_ID_HEADER)).unwrap();
+    assert_eq!(header.as_str(), correlation_id.to_string());
+    let envelope: EventEnvelope<StockReserved> = serde_json::from_slice(&message.payload).unwrap();
+    assert_eq!(envelope.correlation_id, Some(correlation_id));
+    assert_eq!(envelope.event_type, "StockReserved");
+    assert_eq!(envelope.data.order_id, 7);
+
+    handle.shutdown();
+}
+
+#[tokio::test]
+async fn test_failed_reaction_compensates_upstream_with_trigger() {
+    NatsClient::init_embedded();
+    let mut failures = NatsClient::subscribe("lanai.saga.failing.capture_payment.failed").await.unwrap();
+    let (compensated, mut compensations) = mpsc::unbounded_channel();
+    let handle = Choreography::new("failing")
+        .reaction(reserve_stock("failing").compensate_on(
+            "lanai.saga.failing.capture_payment.failed",
+            move |ctx, order: OrderPlaced| {
+                let compensated = compensated.clone();
+                async move { compensated.send((ctx.correlation_id, order.order_id)).map_err(|e| e.to_string()) }
+            },
+        ))
+        .reaction(
+            Reaction::on("capture_payment", "test.choreography.failing.reserved")
+                .run(|_ctx, _reserved: StockReserved| async { Err::<(), _>("card declined") }),
+        )
+        .start()
+        .await
+        .unwrap();
+
+    let correlation_id = Uuid::new_v4();
+    place_order("test.choreography.failing.placed", 9, correlation_id).await;
+
+    let message = timeout(Duration::from_secs(5), failures.next()).await.unwrap().unwrap();
+    let failed: EventEnvelope<ReactionFailedEvent> = serde_json::from_slice(&message.payload).unwrap();
+    assert_eq!(failed.data.reaction, "capture_payment");
+    assert_eq!(failed.data.correlation_id, correlation_id);
+    assert_eq!(failed.data.error, "card declined");
+
+    let compensation = timeout(Duration::from_secs(5), compensations.recv()).await.unwrap();
+    assert_eq!(compensation, Some((correlation_id, 9)));
+
+    handle.shutdown();
+}
+
+#[tokio::test]
+async fn test_watchdog_compensates_unsettled_and_skips_settled() {
+    NatsClient::init_embedded();
+    let (compensated, mut compensations) = mpsc::unbounded_channel();
+    let handle = Choreography::new("watched")
+        .reaction(
+            Reaction::on("reserve_stock", "test.choreography.watched.placed")
+                .run(|_ctx, _order: OrderPlaced| async { Ok::<_, String>(()) })
+                .compensate_on("test.choreography.watched.cancelled", move |ctx, order: OrderPlaced| {
+                    let compensated = compensated.clone();
+                    async move { compensated.send((ctx.correlation_id, order.order_id)).map_err(|e| e.to_string()) }
+                })
+                .watchdog(Duration::from_millis(500), &["test.choreography.watched.shipped"]),
+        )
+        .start()
+        .await
+        .unwrap();
+
+    let (settled, abandoned) = (Uuid::new_v4(), Uuid::new_v4());
+    place_order("test.choreography.watched.placed", 1, settled).await;
+    place_order("test.choreography.watched.placed", 2, abandoned).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut headers = async_nats::HeaderMap::new();
+    headers.insert(CORRELATION_ID_HEADER, settled.to_string().as_str());
+    NatsClient::publish_event_with_headers("test.choreography.watched.shipped", &OrderPlaced { order_id: 1 }, headers)
+        .await
+        .unwrap();
+
+    let compensation = timeout(Duration::from_secs(5), compensations.recv()).await.unwrap();
+    assert_eq!(compensation, Some((abandoned, 2)));
+    assert!(timeout(Duration::from_millis(500), compensations.recv()).await.is_err());
+
+    handle.shutdown();
+}
@@ -0,0 +1,74 @@
+//! `#[derive(LanaiEvent)]` as seen by a downstream crate, including the errors it reports.
+
+use lanai_infrastructure::common::OrgId;
+use lanai_infrastructure::messaging::events::{DescribeEvent, EventField, LanaiEvent};
+use uuid::Uuid;
+
+#[derive(LanaiEvent)]
+#[event(domain = "billing", entity = "invoice", action = "issued", version = 2)]
+struct InvoiceIssued {
+    invoice_id: Uuid,
+    org_id: OrgId,
+}
+
+#[derive(LanaiEvent)]
+#[event(domain = "platform", entity = "deploy", action = "finished")]
+struct DeployFinished {
+    service: String,
+}
+
+#[derive(LanaiEvent)]
+#[event(subject = "lanai.inventory.stock.adjusted.{org_id}.{warehouse_id}")]
+struct StockAdjusted {
+    org_id: Uuid,
+    warehouse_id: u32,
+}
+
+#[test]
+fn test_subject_built_from_domain_entity_action() {
+    let (invoice_id, org_id) = (Uuid::new_v4(), OrgId::generate());
+    let event = InvoiceIssued { invoice_id, org_id };
+
+    assert_eq!(event.subject(), format!("lanai.billing.invoice.issued.{}", org_id));
+    assert_eq!(event.event_type(), "InvoiceIssued");
+    assert_eq!(event.version(), 2);
+    assert_eq!(event.org_id(), Some(org_id.into_uuid()));
+
+    let schema = InvoiceIssued::event_schema();
+    assert_eq!(schema.event_type, "InvoiceIssued");
+    assert_eq!(schema.subject, "lanai.billing.invoice.issued.{org_id}");
+    assert_eq!(schema.subject_pattern, "lanai.billing.invoice.issued.*");
+    assert_eq!(schema.fields, [EventField { name: "invoice_id", ty: "Uuid" }, EventField { name: "org_id", ty: "OrgId" }]);
+
+    let envelope = event.into_envelope();
+    assert_eq!((envelope.event_type.as_str(), envelope.version), ("InvoiceIssued", 2));
+    assert_eq!(envelope.data.invoice_id, invoice_id);
+}
+
+#[test]
+fn test_subject_without_org_id_and_default_version() {
+    let event = DeployFinished { service: "billing".to_string() };
+
+    assert_eq!(event.subject(), "lanai.platform.deploy.finished");
+    assert_eq!(event.event_type(), "DeployFinished");
+    assert_eq!(event.version(), 1);
+    assert_eq!(event.org_id(), None);
+    assert_eq!(DeployFinished::event_schema().subject_pattern, "lanai.platform.deploy.finished");
+    assert_eq!(event.into_envelope().data.service, "billing");
+}
+
+#[test]
+fn test_subject_template_fills_fields() {
+    let org_id = Uuid::new_v4();
+    let event = StockAdjusted { org_id, warehouse_id: 7 };
+
+    assert_eq!(event.subject(), format!("lanai.inventory.stock.adjusted.{}.7", org_id));
+    assert_eq!(event.event_type(), "StockAdjusted");
+    assert_eq!(event.org_id(), Some(org_id));
+    assert_eq!(StockAdjusted::event_schema().subject_pattern, "lanai.inventory.stock.adjusted.*.*");
+}
+
+#[test]
+fn test_invalid_attributes_fail_to_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/derive/*.rs");
+}
@@ -0,0 +1,104 @@
+//! `JobQueue` and `WorkerPool` against Redis, through the containerized `TestHarness`.
+#![cfg(feature = "testing")]
+
+use lanai_infrastructure::jobs::{Job, JobQueue, Priority, WorkerPool};
+use lanai_infrastructure::saga::RetryPolicy;
+use lanai_infrastructure::testing::TestHarness;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+struct Label {
+    text: String,
+}
+
+impl Job for Label {
+    const NAME: &'static str = "label";
+}
+
+async fn queue() -> JobQueue {
+    TestHarness::start().await.unwrap();
+    let conn = lanai_infrastructure::cache::shared_connection().await.unwrap();
+    JobQueue::new(conn, &format!("test-{}", Uuid::new_v4()))
+}
+
+/// Poll `condition` every 50ms until it holds, failing the test after `timeout`.
+async fn eventually<F, Fut>(timeout: Duration, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = Instant::now() + timeout;
+    while !condition().await {
+        assert!(Instant::now() < deadline, "condition not met within {:?}", timeout);
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_workers_claim_higher_lanes_first() {
+    let queue = queue().await;
+    for (text, priority) in [("low", Priority::Low), ("normal", Priority::Normal), ("high", Priority::High)] {
+        queue.enqueue_with_priority(&Label { text: text.to_string() }, priority).await.unwrap();
+    }
+
+    let claimed = Arc::new(Mutex::new(Vec::new()));
+    let recorder = claimed.clone();
+    let workers = WorkerPool::new(queue.clone())
+        .concurrency(1)
+        .poll_interval(Duration::from_millis(50))
+        .register(move |job: Label| {
+            recorder.lock().unwrap_or_else(|e| e.into_inner()).push(job.text);
+            async { Ok::<_, String>(()) }
+        })
+        .start()
+        .await
+        .unwrap();
+
+    eventually(Duration::from_secs(10), || async { claimed.lock().unwrap_or_else(|e| e.into_inner()).len() == 3 }).await;
+    assert_eq!(*claimed.lock().unwrap_or_else(|e| e.into_inner()), ["high", "normal", "low"]);
+    assert_eq!(queue.len().await.unwrap(), 0);
+
+    workers.abort();
+}
+
+#[tokio::test]
+async fn test_failing_job_is_retried_with_backoff_then_dead_lettered() {
+    let queue = queue().await;
+    let id = queue.enqueue(&Label { text: "flaky".to_string() }).await.unwrap();
+
+    let attempts = Arc::new(Mutex::new(0));
+    let counter = attempts.clone();
+    let workers = WorkerPool::new(queue.clone())
+        .poll_interval(Duration::from_millis(50))
+        .retry(RetryPolicy::exponential(2, Duration::from_secs(2)))
+        .register(move |_: Label| {
+            *counter.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+            async { Err::<(), _>("printer offline") }
+        })
+        .start()
+        .await
+        .unwrap();
+
+    // The first failure parks the job in the delayed set for the backoff
+    eventually(Duration::from_secs(10), || async { !queue.scheduled(1).await.unwrap().is_empty() }).await;
+    let scheduled = queue.scheduled(1).await.unwrap().remove(0);
+    assert_eq!(scheduled.job.id, id);
+    assert_eq!(scheduled.job.attempt, 2);
+    assert_eq!(scheduled.job.last_error.as_deref(), Some("printer offline"));
+    assert!(scheduled.run_at >= chrono::Utc::now() + chrono::Duration::milliseconds(1000));
+
+    // The second (last) attempt fails for good
+    eventually(Duration::from_secs(15), || async { queue.stats().await.unwrap().dead == 1 }).await;
+    let dead = queue.dead_letters(1).await.unwrap().remove(0);
+    assert_eq!(dead.job.id, id);
+    assert_eq!(dead.job.attempt, 2);
+    assert_eq!(dead.job.last_error.as_deref(), Some("printer offline"));
+    assert_eq!(*attempts.lock().unwrap_or_else(|e| e.into_inner()), 2);
+    assert_eq!(queue.stats().await.unwrap().scheduled, 0);
+
+    workers.abort();
+}
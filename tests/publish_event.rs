@@ -0,0 +1,45 @@
+//! `NatsClient::publish_event` headers, observed through the embedded broker.
+
+use futures_util::StreamExt;
+use lanai_infrastructure::messaging::dedup::{message_id, with_message_id};
+use lanai_infrastructure::messaging::{EventEnvelope, NatsClient};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_publish_event_sets_message_id() {
+    NatsClient::init_embedded();
+    let mut received = NatsClient::subscribe("test.publish_event.ids").await.unwrap();
+
+    NatsClient::publish_event("test.publish_event.ids", &json!({"order_id": 1})).await.unwrap();
+    NatsClient::publish_event("test.publish_event.ids", &json!({"order_id": 1})).await.unwrap();
+    let headers = async_nats::HeaderMap::new();
+    NatsClient::publish_event_with_headers("test.publish_event.ids", &json!({}), with_message_id(headers, "evt-1"))
+        .await
+        .unwrap();
+
+    let first = message_id(&received.next().await.unwrap()).unwrap();
+    let second = message_id(&received.next().await.unwrap()).unwrap();
+    assert_ne!(first, second);
+    assert_eq!(message_id(&received.next().await.unwrap()).as_deref(), Some("evt-1"));
+}
+
+#[tokio::test]
+async fn test_publish_event_wraps_in_envelope() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct StockLow {
+        sku: String,
+    }
+
+    NatsClient::init_embedded();
+    let mut raw = NatsClient::subscribe("test.publish_event.envelope").await.unwrap();
+    let mut typed = NatsClient::subscribe_typed::<StockLow>("test.publish_event.envelope").await.unwrap();
+
+    NatsClient::publish_event("test.publish_event.envelope", &StockLow { sku: "SKU-1".to_string() }).await.unwrap();
+
+    let message = raw.next().await.unwrap();
+    let envelope: EventEnvelope<StockLow> = serde_json::from_slice(&message.payload).unwrap();
+    assert_eq!(envelope.event_type, "StockLow");
+    assert_eq!(envelope.data.sku, "SKU-1");
+    assert_eq!(message_id(&message), Some(envelope.event_id.to_string()));
+    assert_eq!(typed.next().await.unwrap().unwrap().event.sku, "SKU-1");
+}
@@ -0,0 +1,65 @@
+//! `NatsClient::request_typed` against the embedded broker. Kept out of the unit tests
+//! because `init_embedded` switches the process-wide client.
+
+use futures_util::StreamExt;
+use lanai_infrastructure::messaging::{NatsClient, NatsError};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize)]
+struct Reserve {
+    sku: String,
+    quantity: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Reserved {
+    sku: String,
+    remaining: u32,
+}
+
+/// Answer the next request on `subject` with `reply`.
+async fn respond_once(subject: &str, reply: impl Fn(&[u8]) -> Vec<u8> + Send + 'static) {
+    let mut requests = NatsClient::subscribe(subject).await.unwrap();
+    tokio::spawn(async move {
+        let message = requests.next().await.unwrap();
+        let payload = reply(&message.payload);
+        NatsClient::publish_with_headers(message.reply.as_deref().unwrap(), async_nats::HeaderMap::new(), payload.into())
+            .await
+            .unwrap();
+    });
+}
+
+#[tokio::test]
+async fn test_request_typed_round_trip() {
+    NatsClient::init_embedded();
+    respond_once("test.request_typed.reserve", |payload| {
+        let reserve: Reserve = serde_json::from_slice(payload).unwrap();
+        serde_json::to_vec(&Reserved { sku: reserve.sku, remaining: 10 - reserve.quantity }).unwrap()
+    })
+    .await;
+
+    let request = Reserve { sku: "SKU-1".to_string(), quantity: 3 };
+    let reserved: Reserved = NatsClient::request_typed("test.request_typed.reserve", &request, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(reserved.sku, "SKU-1");
+    assert_eq!(reserved.remaining, 7);
+}
+
+#[tokio::test]
+async fn test_request_typed_errors() {
+    NatsClient::init_embedded();
+    let unanswered = NatsClient::request_typed::<_, u32>("test.request_typed.nobody", &1, Duration::from_secs(1)).await;
+    assert!(matches!(unanswered, Err(NatsError::RequestError(_))));
+
+    let mut requests = NatsClient::subscribe("test.request_typed.silent").await.unwrap();
+    let silent = tokio::spawn(async move { requests.next().await });
+    let timed_out = NatsClient::request_typed::<_, u32>("test.request_typed.silent", &1, Duration::from_millis(50)).await;
+    assert!(matches!(timed_out, Err(NatsError::Timeout(_, _))));
+    silent.abort();
+
+    respond_once("test.request_typed.garbled", |_| b"not json".to_vec()).await;
+    let garbled = NatsClient::request_typed::<_, u32>("test.request_typed.garbled", &1, Duration::from_secs(1)).await;
+    assert!(matches!(garbled, Err(NatsError::DeserializationError(_))));
+}
@@ -0,0 +1,9 @@
+use lanai_infrastructure::messaging::events::LanaiEvent;
+
+#[derive(LanaiEvent)]
+#[event(subject = "inventory.Stock.adjusted")]
+struct StockAdjusted {
+    warehouse_id: u32,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use lanai_infrastructure::messaging::events::LanaiEvent;
+
+#[derive(LanaiEvent)]
+struct StockAdjusted {
+    warehouse_id: u32,
+}
+
+fn main() {}